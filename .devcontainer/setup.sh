@@ -13,7 +13,7 @@ cargo run -- setup --chain starknet --from-remote --base-path /tmp/deoxys \
 # cargo run -- \
 #     --deoxys \
 #     --rpc-port 9944 \ 
-#     --network main \
+#     --network mainnet \
 #     --pruning archive \
 #     --rpc-cors=all \
 #     --l1-endpoint "key_url" # replace with your own l1 provider url key
@@ -7,9 +7,23 @@ use rocksdb::{
     WriteBatchWithTransaction, WriteOptions,
 };
 
-use crate::{BonsaiDbError, Column, DatabaseExt, DB};
-
-pub type RocksDBTransaction = WriteBatchWithTransaction<true>;
+use crate::shards::BonsaiInstances;
+use crate::{trie_writes, BonsaiDbError, Column, DatabaseExt, DB};
+
+/// Write batches of the RocksDB instances of a bonsai storage, indexed like [`BonsaiInstances`].
+///
+/// Each batch is written atomically, but not the batches of different instances together.
+#[derive(Default)]
+pub struct RocksDBTransaction(Vec<WriteBatchWithTransaction<true>>);
+
+impl RocksDBTransaction {
+    fn get_mut(&mut self, index: usize) -> &mut WriteBatchWithTransaction<true> {
+        if self.0.len() <= index {
+            self.0.resize_with(index + 1, Default::default);
+        }
+        &mut self.0[index]
+    }
+}
 
 #[derive(Clone, Debug)]
 pub(crate) struct DatabaseKeyMapping {
@@ -29,16 +43,28 @@ impl DatabaseKeyMapping {
 }
 
 pub struct BonsaiDb<'db> {
-    /// Database interface for key-value operations.
-    db: &'db DB,
+    /// Database instances, the contract storage tries may be sharded across several of them.
+    instances: BonsaiInstances<'db>,
     /// Mapping from `DatabaseKey` => rocksdb column name
     column_mapping: DatabaseKeyMapping,
-    snapshots: BTreeMap<BasicId, SnapshotWithThreadMode<'db, DB>>,
+    snapshots: BTreeMap<BasicId, Vec<SnapshotWithThreadMode<'db, DB>>>,
 }
 
 impl<'db> BonsaiDb<'db> {
-    pub(crate) fn new(db: &'db DB, column_mapping: DatabaseKeyMapping) -> Self {
-        Self { db, column_mapping, snapshots: BTreeMap::new() }
+    pub(crate) fn new(instances: BonsaiInstances<'db>, column_mapping: DatabaseKeyMapping) -> Self {
+        Self { instances, column_mapping, snapshots: BTreeMap::new() }
+    }
+
+    /// Reads `key` from the instance it is written to, then from its fallback, if any.
+    fn read(&self, key: &DatabaseKey) -> Result<Option<Vec<u8>>, BonsaiDbError> {
+        let column = self.column_mapping.map(key);
+        for index in std::iter::once(self.instances.route(key)).chain(self.instances.fallback(key)) {
+            let db = self.instances.get(index);
+            if let Some(value) = db.get_cf(&db.get_column(column), key.as_slice())? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
     }
 }
 
@@ -52,29 +78,34 @@ impl BonsaiDatabase for BonsaiDb<'_> {
 
     fn get(&self, key: &DatabaseKey) -> Result<Option<Vec<u8>>, Self::DatabaseError> {
         log::trace!("Getting from RocksDB: {:?}", key);
-        let handle = self.db.get_column(self.column_mapping.map(key));
-        Ok(self.db.get_cf(&handle, key.as_slice())?)
+        self.read(key)
     }
 
     fn get_by_prefix(&self, prefix: &DatabaseKey) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Self::DatabaseError> {
         log::trace!("Getting from RocksDB: {:?}", prefix);
-        let handle = self.db.get_column(self.column_mapping.map(prefix));
-        let iter = self.db.iterator_cf(&handle, IteratorMode::From(prefix.as_slice(), Direction::Forward));
-        Ok(iter
-            .map_while(|kv| {
+        let mut entries = Vec::new();
+        for index in self.instances.route_prefix(prefix) {
+            let db = self.instances.get(index);
+            let handle = db.get_column(self.column_mapping.map(prefix));
+            let iter = db.iterator_cf(&handle, IteratorMode::From(prefix.as_slice(), Direction::Forward));
+            entries.extend(iter.map_while(|kv| {
                 if let Ok((key, value)) = kv {
                     if key.starts_with(prefix.as_slice()) { Some((key.to_vec(), value.to_vec())) } else { None }
                 } else {
                     None
                 }
-            })
-            .collect())
+            }));
+        }
+        // the instances hold disjoint sets of keys, but for the trie logs whose move to the cold
+        // storage was interrupted, which are in both
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        entries.dedup_by(|(a, _), (b, _)| a == b);
+        Ok(entries)
     }
 
     fn contains(&self, key: &DatabaseKey) -> Result<bool, Self::DatabaseError> {
         log::trace!("Checking if RocksDB contains: {:?}", key);
-        let handle = self.db.get_column(self.column_mapping.map(key));
-        Ok(self.db.get_cf(&handle, key.as_slice()).map(|value| value.is_some())?)
+        Ok(self.read(key)?.is_some())
     }
 
     fn insert(
@@ -84,97 +115,132 @@ impl BonsaiDatabase for BonsaiDb<'_> {
         batch: Option<&mut Self::Batch>,
     ) -> Result<Option<Vec<u8>>, Self::DatabaseError> {
         log::trace!("Inserting into RocksDB: {:?} {:?}", key, value);
-        let handle = self.db.get_column(self.column_mapping.map(key));
-        let old_value = self.db.get_cf(&handle, key.as_slice())?;
+        let index = self.instances.route(key);
+        let db = self.instances.get(index);
+        let column = self.column_mapping.map(key);
+        let handle = db.get_column(column);
+        let old_value = self.read(key)?;
         if let Some(batch) = batch {
-            batch.put_cf(&handle, key.as_slice(), value);
+            batch.get_mut(index).put_cf(&handle, key.as_slice(), value);
         } else {
-            self.db.put_cf(&handle, key.as_slice(), value)?;
+            db.put_cf(&handle, key.as_slice(), value)?;
         }
+        trie_writes::record_insert(column, key.as_slice(), value, old_value.as_deref());
         Ok(old_value)
     }
 
     fn remove(
         &mut self,
         key: &DatabaseKey,
-        batch: Option<&mut Self::Batch>,
+        mut batch: Option<&mut Self::Batch>,
     ) -> Result<Option<Vec<u8>>, Self::DatabaseError> {
         log::trace!("Removing from RocksDB: {:?}", key);
-        let handle = self.db.get_column(self.column_mapping.map(key));
-        let old_value = self.db.get_cf(&handle, key.as_slice())?;
-        if let Some(batch) = batch {
-            batch.delete_cf(&handle, key.as_slice());
-        } else {
-            self.db.delete_cf(&handle, key.as_slice())?;
+        let column = self.column_mapping.map(key);
+        let old_value = self.read(key)?;
+        for index in std::iter::once(self.instances.route(key)).chain(self.instances.fallback(key)) {
+            let db = self.instances.get(index);
+            let handle = db.get_column(column);
+            if let Some(batch) = batch.as_deref_mut() {
+                batch.get_mut(index).delete_cf(&handle, key.as_slice());
+            } else {
+                db.delete_cf(&handle, key.as_slice())?;
+            }
         }
+        trie_writes::record_remove(column, key.as_slice(), old_value.as_deref());
         Ok(old_value)
     }
 
     fn remove_by_prefix(&mut self, prefix: &DatabaseKey) -> Result<(), Self::DatabaseError> {
         log::trace!("Getting from RocksDB: {:?}", prefix);
-        let handle = self.db.get_column(self.column_mapping.map(prefix));
-        let iter = self.db.iterator_cf(&handle, IteratorMode::From(prefix.as_slice(), Direction::Forward));
         let mut batch = self.create_batch();
-        for kv in iter {
-            if let Ok((key, _)) = kv {
-                if key.starts_with(prefix.as_slice()) {
-                    batch.delete_cf(&handle, &key);
+        for index in self.instances.route_prefix(prefix) {
+            let db = self.instances.get(index);
+            let handle = db.get_column(self.column_mapping.map(prefix));
+            let iter = db.iterator_cf(&handle, IteratorMode::From(prefix.as_slice(), Direction::Forward));
+            for kv in iter {
+                if let Ok((key, _)) = kv {
+                    if key.starts_with(prefix.as_slice()) {
+                        batch.get_mut(index).delete_cf(&handle, &key);
+                    } else {
+                        break;
+                    }
                 } else {
                     break;
                 }
-            } else {
-                break;
             }
         }
-        drop(handle);
         self.write_batch(batch)?;
         Ok(())
     }
 
     fn write_batch(&mut self, batch: Self::Batch) -> Result<(), Self::DatabaseError> {
-        Ok(self.db.write(batch)?)
+        for (index, batch) in self.instances.in_write_order(batch.0) {
+            if !batch.is_empty() {
+                self.instances.get(index).write(batch)?;
+            }
+        }
+        Ok(())
     }
 }
 
 pub struct BonsaiTransaction<'db> {
-    txn: Transaction<'db, DB>,
-    db: &'db DB,
+    /// One transaction per database instance, indexed like [`BonsaiInstances`].
+    txns: Vec<Transaction<'db, DB>>,
+    instances: BonsaiInstances<'db>,
     column_mapping: DatabaseKeyMapping,
 }
 
+impl BonsaiTransaction<'_> {
+    /// Reads `key` from the instance it is written to, then from its fallback, if any.
+    fn read(&self, key: &DatabaseKey) -> Result<Option<Vec<u8>>, BonsaiDbError> {
+        let column = self.column_mapping.map(key);
+        for index in std::iter::once(self.instances.route(key)).chain(self.instances.fallback(key)) {
+            let handle = self.instances.get(index).get_column(column);
+            if let Some(value) = self.txns[index].get_cf(&handle, key.as_slice())? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+}
+
 impl<'db> BonsaiDatabase for BonsaiTransaction<'db> {
-    type Batch = WriteBatchWithTransaction<true>;
+    type Batch = RocksDBTransaction;
     type DatabaseError = BonsaiDbError;
 
     fn create_batch(&self) -> Self::Batch {
-        self.txn.get_writebatch()
+        RocksDBTransaction(self.txns.iter().map(|txn| txn.get_writebatch()).collect())
     }
 
     fn get(&self, key: &DatabaseKey) -> Result<Option<Vec<u8>>, Self::DatabaseError> {
         log::trace!("Getting from RocksDB: {:?}", key);
-        let handle = self.db.get_column(self.column_mapping.map(key));
-        Ok(self.txn.get_cf(&handle, key.as_slice())?)
+        self.read(key)
     }
 
     fn get_by_prefix(&self, prefix: &DatabaseKey) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Self::DatabaseError> {
         log::trace!("Getting from RocksDB: {:?}", prefix);
-        let handle = self.db.get_column(self.column_mapping.map(prefix));
-        let iter = self.txn.iterator_cf(&handle, IteratorMode::From(prefix.as_slice(), Direction::Forward));
-        Ok(iter
-            .map_while(|kv| {
+        let mut entries = Vec::new();
+        for index in self.instances.route_prefix(prefix) {
+            let handle = self.instances.get(index).get_column(self.column_mapping.map(prefix));
+            let iter = self.txns[index].iterator_cf(&handle, IteratorMode::From(prefix.as_slice(), Direction::Forward));
+            entries.extend(iter.map_while(|kv| {
                 if let Ok((key, value)) = kv {
                     if key.starts_with(prefix.as_slice()) { Some((key.to_vec(), value.to_vec())) } else { None }
                 } else {
                     None
                 }
-            })
-            .collect())
+            }));
+        }
+        // the instances hold disjoint sets of keys, but for the trie logs whose move to the cold
+        // storage was interrupted, which are in both
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        entries.dedup_by(|(a, _), (b, _)| a == b);
+        Ok(entries)
     }
 
     fn contains(&self, key: &DatabaseKey) -> Result<bool, Self::DatabaseError> {
         log::trace!("Checking if RocksDB contains: {:?}", key);
-        let handle = self.db.get_column(self.column_mapping.map(key));
-        Ok(self.txn.get_cf(&handle, key.as_slice()).map(|value| value.is_some())?)
+        Ok(self.read(key)?.is_some())
     }
 
     fn insert(
@@ -184,12 +250,13 @@ impl<'db> BonsaiDatabase for BonsaiTransaction<'db> {
         batch: Option<&mut Self::Batch>,
     ) -> Result<Option<Vec<u8>>, Self::DatabaseError> {
         log::trace!("Inserting into RocksDB: {:?} {:?}", key, value);
-        let handle = self.db.get_column(self.column_mapping.map(key));
-        let old_value = self.txn.get_cf(&handle, key.as_slice())?;
+        let index = self.instances.route(key);
+        let handle = self.instances.get(index).get_column(self.column_mapping.map(key));
+        let old_value = self.read(key)?;
         if let Some(batch) = batch {
-            batch.put_cf(&handle, key.as_slice(), value);
+            batch.get_mut(index).put_cf(&handle, key.as_slice(), value);
         } else {
-            self.txn.put_cf(&handle, key.as_slice(), value)?;
+            self.txns[index].put_cf(&handle, key.as_slice(), value)?;
         }
         Ok(old_value)
     }
@@ -197,42 +264,48 @@ impl<'db> BonsaiDatabase for BonsaiTransaction<'db> {
     fn remove(
         &mut self,
         key: &DatabaseKey,
-        batch: Option<&mut Self::Batch>,
+        mut batch: Option<&mut Self::Batch>,
     ) -> Result<Option<Vec<u8>>, Self::DatabaseError> {
         log::trace!("Removing from RocksDB: {:?}", key);
-        let handle = self.db.get_column(self.column_mapping.map(key));
-        let old_value = self.txn.get_cf(&handle, key.as_slice())?;
-        if let Some(batch) = batch {
-            batch.delete_cf(&handle, key.as_slice());
-        } else {
-            self.txn.delete_cf(&handle, key.as_slice())?;
+        let old_value = self.read(key)?;
+        for index in std::iter::once(self.instances.route(key)).chain(self.instances.fallback(key)) {
+            let handle = self.instances.get(index).get_column(self.column_mapping.map(key));
+            if let Some(batch) = batch.as_deref_mut() {
+                batch.get_mut(index).delete_cf(&handle, key.as_slice());
+            } else {
+                self.txns[index].delete_cf(&handle, key.as_slice())?;
+            }
         }
         Ok(old_value)
     }
 
     fn remove_by_prefix(&mut self, prefix: &DatabaseKey) -> Result<(), Self::DatabaseError> {
         log::trace!("Getting from RocksDB: {:?}", prefix);
-        let handle = self.db.get_column(self.column_mapping.map(prefix));
-        let iter = self.txn.iterator_cf(&handle, IteratorMode::From(prefix.as_slice(), Direction::Forward));
         let mut batch = self.create_batch();
-        for kv in iter {
-            if let Ok((key, _)) = kv {
-                if key.starts_with(prefix.as_slice()) {
-                    batch.delete_cf(&handle, &key);
+        for index in self.instances.route_prefix(prefix) {
+            let handle = self.instances.get(index).get_column(self.column_mapping.map(prefix));
+            let iter = self.txns[index].iterator_cf(&handle, IteratorMode::From(prefix.as_slice(), Direction::Forward));
+            for kv in iter {
+                if let Ok((key, _)) = kv {
+                    if key.starts_with(prefix.as_slice()) {
+                        batch.get_mut(index).delete_cf(&handle, &key);
+                    } else {
+                        break;
+                    }
                 } else {
                     break;
                 }
-            } else {
-                break;
             }
         }
-        drop(handle);
         self.write_batch(batch)?;
         Ok(())
     }
 
     fn write_batch(&mut self, batch: Self::Batch) -> Result<(), Self::DatabaseError> {
-        Ok(self.txn.rebuild_from_writebatch(&batch)?)
+        for (txn, batch) in self.txns.iter().zip(batch.0) {
+            txn.rebuild_from_writebatch(&batch)?;
+        }
+        Ok(())
     }
 }
 
@@ -245,29 +318,39 @@ where
 
     fn snapshot(&mut self, id: BasicId) {
         log::trace!("Generating RocksDB snapshot");
-        let snapshot = self.db.snapshot();
-        self.snapshots.insert(id, snapshot);
+        let snapshots = (0..self.instances.count()).map(|index| self.instances.get(index).snapshot()).collect();
+        self.snapshots.insert(id, snapshots);
     }
 
     fn transaction(&self, id: BasicId) -> Option<Self::Transaction> {
         log::trace!("Generating RocksDB transaction");
-        if let Some(snapshot) = self.snapshots.get(&id) {
-            let write_opts = WriteOptions::default();
-            let mut txn_opts = OptimisticTransactionOptions::default();
-            txn_opts.set_snapshot(true);
-            let txn = self.db.transaction_opt(&write_opts, &txn_opts);
-
-            let mut read_options = ReadOptions::default();
-            read_options.set_snapshot(snapshot);
-
-            Some(BonsaiTransaction { txn, db: self.db, column_mapping: self.column_mapping.clone() })
+        if let Some(snapshots) = self.snapshots.get(&id) {
+            let txns = snapshots
+                .iter()
+                .enumerate()
+                .map(|(index, snapshot)| {
+                    let write_opts = WriteOptions::default();
+                    let mut txn_opts = OptimisticTransactionOptions::default();
+                    txn_opts.set_snapshot(true);
+                    let txn = self.instances.get(index).transaction_opt(&write_opts, &txn_opts);
+
+                    let mut read_options = ReadOptions::default();
+                    read_options.set_snapshot(snapshot);
+
+                    txn
+                })
+                .collect();
+
+            Some(BonsaiTransaction { txns, instances: self.instances, column_mapping: self.column_mapping.clone() })
         } else {
             None
         }
     }
 
     fn merge(&mut self, transaction: Self::Transaction) -> Result<(), Self::DatabaseError> {
-        transaction.txn.commit()?;
+        for (_, txn) in transaction.instances.in_write_order(transaction.txns) {
+            txn.commit()?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use mp_contract::class::{ContractClassData, ContractClassWrapper};
+// Substrate
+use parity_scale_codec::{Decode, Encode};
+use rocksdb::WriteBatchWithTransaction;
+// Starknet
+use starknet_api::core::ClassHash;
+
+use crate::{Column, DatabaseExt, DbError, DB};
+
+/// Allow interaction with the class artifacts db
+///
+/// Compiling Sierra classes is the most expensive part of fetching them. Class artifacts exported
+/// by another node can be imported in this db, so that the sync uses them instead of downloading
+/// and compiling the classes again.
+pub struct ClassArtifactDb {
+    pub(crate) db: Arc<DB>,
+}
+
+impl ClassArtifactDb {
+    pub(crate) fn new(db: Arc<DB>) -> Self {
+        Self { db }
+    }
+
+    /// Return the imported artifact of the class with the given hash
+    pub fn get(&self, class_hash: &ClassHash) -> Result<Option<ContractClassWrapper>, DbError> {
+        let column = self.db.get_column(Column::ClassArtifacts);
+
+        match self.db.get_cf(&column, class_hash.encode())? {
+            Some(raw) => Ok(Some(ContractClassWrapper::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store the artifacts of the given classes, replacing existing ones
+    pub fn store(&self, classes: &[ContractClassData]) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::ClassArtifacts);
+
+        let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+        for class in classes {
+            transaction.put_cf(&column, class.hash.encode(), class.contract_class.encode());
+        }
+
+        self.db.write(transaction)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+// Substrate
+use parity_scale_codec::{Decode, Encode};
+use rocksdb::{Direction, IteratorMode, WriteBatchWithTransaction};
+// Starknet
+use starknet_api::core::{ClassHash, CompiledClassHash};
+
+use crate::{Column, DatabaseExt, DbError, DB};
+
+/// A class declared in a block.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct ClassDeclaration {
+    /// The Sierra class hash, or the class hash of Cairo 0 classes.
+    pub class_hash: ClassHash,
+    /// Absent for Cairo 0 classes.
+    pub compiled_class_hash: Option<CompiledClassHash>,
+    /// Size of the class as stored by the node, in bytes, if known.
+    pub size: Option<u64>,
+}
+
+/// Lengths of a Sierra class, which are part of the class info of its declaration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct ClassLengths {
+    /// Number of felts of the Sierra program.
+    pub sierra_program_length: u64,
+    /// Number of characters of the ABI.
+    pub abi_length: u64,
+}
+
+/// A page of class declarations, in ascending block number order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassDeclarationsPage {
+    pub declarations: Vec<(u64, ClassDeclaration)>,
+    /// The first block which was not covered, if the page is full.
+    pub continuation_block: Option<u64>,
+}
+
+/// Allow interaction with the class declarations db
+///
+/// The class declarations db maps each class hash to the number of the block in which it was
+/// declared, so that classes are not served for blocks prior to their declaration. Declarations
+/// are also indexed by block, so that the classes declared in a range of blocks can be listed.
+///
+/// Only the compiled classes are kept, so the lengths of the Sierra classes are stored when they
+/// are fetched.
+pub struct ClassDb {
+    pub(crate) db: Arc<DB>,
+}
+
+impl ClassDb {
+    pub(crate) fn new(db: Arc<DB>) -> Self {
+        Self { db }
+    }
+
+    /// Return the number of the block in which the class with the given hash was declared
+    pub fn declaration_block_number(&self, class_hash: &ClassHash) -> Result<Option<u64>, DbError> {
+        let column = self.db.get_column(Column::ClassDeclarations);
+
+        match self.db.get_cf(&column, class_hash.encode())? {
+            Some(raw) => Ok(Some(u64::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Register the classes declared in the given block
+    ///
+    /// Classes which were already registered keep their original declaration block number.
+    pub fn store_declarations(&self, block_number: u64, declarations: &[ClassDeclaration]) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::ClassDeclarations);
+        let column_by_block = self.db.get_column(Column::ClassDeclarationsByBlock);
+
+        let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+        for declaration in declarations {
+            if self.declaration_block_number(&declaration.class_hash)?.is_none() {
+                transaction.put_cf(&column, declaration.class_hash.encode(), block_number.encode());
+                transaction.put_cf(
+                    &column_by_block,
+                    by_block_key(block_number, &declaration.class_hash),
+                    declaration.encode(),
+                );
+            }
+        }
+
+        self.db.write(transaction)?;
+        Ok(())
+    }
+
+    /// Return the lengths of the Sierra class with the given hash, if they were stored
+    pub fn lengths(&self, class_hash: &ClassHash) -> Result<Option<ClassLengths>, DbError> {
+        let column = self.db.get_column(Column::ClassLengths);
+
+        match self.db.get_cf(&column, class_hash.encode())? {
+            Some(raw) => Ok(Some(ClassLengths::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store the lengths of the Sierra class with the given hash
+    pub fn store_lengths(&self, class_hash: &ClassHash, lengths: ClassLengths) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::ClassLengths);
+        self.db.put_cf(&column, class_hash.encode(), lengths.encode())?;
+        Ok(())
+    }
+
+    /// Return the classes declared from block `from` to block `to` included
+    ///
+    /// Pages end on block boundaries: a page holds at least `limit` declarations unless the end of
+    /// the range was reached, and more if the last block has more declarations.
+    pub fn declarations(&self, from: u64, to: u64, limit: usize) -> Result<ClassDeclarationsPage, DbError> {
+        let column = self.db.get_column(Column::ClassDeclarationsByBlock);
+        let start = from.to_be_bytes();
+
+        let mut declarations: Vec<(u64, ClassDeclaration)> = Vec::new();
+        for kv in self.db.iterator_cf(&column, IteratorMode::From(&start, Direction::Forward)) {
+            let (key, value) = kv?;
+            let block_number = u64::from_be_bytes(key[..8].try_into().expect("Keys start with the block number"));
+            if block_number > to {
+                break;
+            }
+            if declarations.len() >= limit && declarations.last().is_some_and(|(last, _)| *last != block_number) {
+                return Ok(ClassDeclarationsPage { declarations, continuation_block: Some(block_number) });
+            }
+
+            declarations.push((block_number, ClassDeclaration::decode(&mut &value[..])?));
+        }
+
+        Ok(ClassDeclarationsPage { declarations, continuation_block: None })
+    }
+}
+
+/// Block numbers are big-endian encoded so that declarations are iterated over by block.
+pub(crate) fn by_block_key(block_number: u64, class_hash: &ClassHash) -> Vec<u8> {
+    [block_number.to_be_bytes().as_slice(), &class_hash.encode()].concat()
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+// Substrate
+use parity_scale_codec::{Decode, Encode};
+use rocksdb::IteratorMode;
+// Starknet
+use starknet_api::core::{ClassHash, CompiledClassHash};
+
+use crate::{Column, DatabaseExt, DbError, DB};
+
+/// The class the node uses for a quarantined class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum QuarantineResolution {
+    /// The compiled class provided by the feeder gateway, which matches the declared hash.
+    GatewayCasm,
+    /// The class compiled by the node, as the feeder gateway did not provide a matching one.
+    LocalCasm,
+    /// None, the class served by the feeder gateway was rejected and the sync stopped, as it did
+    /// not serve one matching the class hash.
+    Rejected,
+}
+
+/// Why a class was quarantined.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub enum QuarantineReason {
+    /// The node compiles the Sierra class to another compiled class hash than the one declared on
+    /// chain.
+    CompiledClassHash {
+        declared: CompiledClassHash,
+        computed: CompiledClassHash,
+        /// The version of the compiler used by the node.
+        compiler_version: String,
+    },
+    /// The class served by the feeder gateway hashes to another class hash.
+    ClassHash { computed: ClassHash },
+}
+
+/// A class which does not match the hashes declared on chain.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct QuarantinedClass {
+    pub class_hash: ClassHash,
+    /// The block in which the class was declared.
+    pub block_number: u64,
+    pub reason: QuarantineReason,
+    pub resolution: QuarantineResolution,
+}
+
+/// Allow interaction with the class quarantine db
+///
+/// Classes compiled by the node to a different compiled class hash than the one declared on chain,
+/// typically because of a compiler version drift, and classes served by the feeder gateway which
+/// do not hash to their class hash, are recorded here so that operators can review them.
+pub struct ClassQuarantineDb {
+    pub(crate) db: Arc<DB>,
+}
+
+impl ClassQuarantineDb {
+    pub(crate) fn new(db: Arc<DB>) -> Self {
+        Self { db }
+    }
+
+    /// Return the quarantine report of the class with the given hash
+    pub fn get(&self, class_hash: &ClassHash) -> Result<Option<QuarantinedClass>, DbError> {
+        let column = self.db.get_column(Column::ClassQuarantine);
+
+        match self.db.get_cf(&column, class_hash.encode())? {
+            Some(raw) => Ok(Some(QuarantinedClass::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Record a quarantined class, replacing its previous report
+    pub fn store(&self, class: &QuarantinedClass) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::ClassQuarantine);
+
+        self.db.put_cf(&column, class.class_hash.encode(), class.encode())?;
+        Ok(())
+    }
+
+    /// Return all the quarantined classes, in ascending declaration block order
+    pub fn all(&self) -> Result<Vec<QuarantinedClass>, DbError> {
+        let column = self.db.get_column(Column::ClassQuarantine);
+
+        let mut classes = Vec::new();
+        for kv in self.db.iterator_cf(&column, IteratorMode::Start) {
+            let (_, value) = kv?;
+            classes.push(QuarantinedClass::decode(&mut &value[..])?);
+        }
+        classes.sort_by_key(|class| class.block_number);
+
+        Ok(classes)
+    }
+}
@@ -0,0 +1,224 @@
+//! Cold storage of the data of old blocks, which is rarely read once written.
+//!
+//! The transaction receipts and the trie logs grow with every block, but those of old blocks are
+//! only read to serve old receipts and to revert the tries far back. When a cold storage directory
+//! is set, they are written to the primary database as usual, and a background task of the node
+//! moves those of the blocks older than a given depth to a separate RocksDB instance in that
+//! directory, see [`ColdStorageDb::move_blocks`], which may be on a larger and slower drive than
+//! the rest of the database. Reads go to the primary database first, then to the cold storage.
+//!
+//! The last block moved is stored in the meta column of the primary database. The database can't
+//! be opened without the cold storage once blocks were moved, as their data would be missing.
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use parity_scale_codec::{Decode, Encode};
+use rocksdb::{Direction, IteratorMode, WriteBatchWithTransaction};
+
+use crate::transaction_db::TransactionDb;
+use crate::{consistency, open_rocksdb_columns, Column, DatabaseExt, DbError, DbTuning, DB};
+
+/// The columns held by the cold storage.
+pub(crate) const COLD_COLUMNS: [Column; 4] = [
+    Column::TransactionReceipts,
+    Column::BonsaiContractsLog,
+    Column::BonsaiContractsStorageLog,
+    Column::BonsaiClassesLog,
+];
+
+/// The columns of the trie logs, whose keys start with the [`BasicId`](bonsai_trie::id::BasicId)
+/// the tries were committed with, the one following the block number.
+const LOG_COLUMNS: [Column; 3] =
+    [Column::BonsaiContractsLog, Column::BonsaiContractsStorageLog, Column::BonsaiClassesLog];
+
+/// Number of entries written at once when moving blocks.
+const MOVE_BATCH_SIZE: usize = 10_000;
+
+/// Whether `column` may be partly held by the cold storage, if there is one.
+pub(crate) fn is_cold(column: Column) -> bool {
+    COLD_COLUMNS.contains(&column)
+}
+
+/// Opens the cold storage in `dir`, if set.
+///
+/// Fails if blocks were already moved to a cold storage, but `dir` is not set.
+pub(crate) fn open_cold_storage(
+    primary: &DB,
+    dir: Option<&Path>,
+    dirty: bool,
+    tuning: &DbTuning,
+) -> Result<Option<DB>> {
+    let meta = primary.get_column(Column::Meta);
+    let moved = primary.get_cf(&meta, crate::static_keys::COLD_STORAGE)?.is_some();
+
+    let Some(dir) = dir else {
+        if moved {
+            bail!(
+                "The transaction receipts and trie logs of the old blocks were moved to a cold storage, please set \
+                 --cold-storage-path to its directory."
+            );
+        }
+        return Ok(None);
+    };
+
+    let cold = open_rocksdb_columns(dir, true, dirty, &COLD_COLUMNS, tuning)
+        .with_context(|| format!("Failed to open the cold storage at {}", dir.display()))?;
+    if dirty {
+        consistency::check_columns(&cold, &COLD_COLUMNS)
+            .context("The cold storage is corrupted following an unclean shutdown")?;
+    }
+
+    Ok(Some(cold))
+}
+
+/// Allow moving the data of old blocks to the cold storage
+pub struct ColdStorageDb {
+    db: Arc<DB>,
+    cold: Option<Arc<DB>>,
+    transactions: Arc<TransactionDb>,
+}
+
+impl ColdStorageDb {
+    pub(crate) fn new(db: Arc<DB>, cold: Option<Arc<DB>>, transactions: Arc<TransactionDb>) -> Self {
+        Self { db, cold, transactions }
+    }
+
+    /// Whether the database has a cold storage
+    pub fn is_enabled(&self) -> bool {
+        self.cold.is_some()
+    }
+
+    /// Return the last block whose data was moved to the cold storage, `None` if none was
+    pub fn moved_block(&self) -> Result<Option<u64>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf(&column, crate::static_keys::COLD_STORAGE)? {
+            Some(raw) => Ok(Some(u64::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Move the receipts and trie logs of the blocks up to `block_number` to the cold storage,
+    /// from the one following the last block moved, returning the number of blocks moved
+    ///
+    /// The blocks are durably written to the cold storage before being deleted from the primary
+    /// database, atomically with the update of the last block moved, so that an interrupted move is
+    /// resumed by the next one. Does nothing if there is no cold storage.
+    pub fn move_blocks(&self, block_number: u64) -> Result<u64, DbError> {
+        let Some(cold) = &self.cold else {
+            return Ok(0);
+        };
+        let meta = self.db.get_column(Column::Meta);
+
+        let from = self.moved_block()?.map_or(0, |moved| moved + 1);
+        let mut to_cold: WriteBatchWithTransaction<true> = Default::default();
+        let mut from_primary: WriteBatchWithTransaction<true> = Default::default();
+        for block_n in from..=block_number {
+            self.collect_block(cold, block_n, &mut to_cold, &mut from_primary)?;
+
+            if to_cold.len() >= MOVE_BATCH_SIZE || block_n == block_number {
+                cold.write(std::mem::take(&mut to_cold))?;
+                cold.flush_wal(true)?;
+                from_primary.put_cf(&meta, crate::static_keys::COLD_STORAGE, block_n.encode());
+                self.db.write(std::mem::take(&mut from_primary))?;
+            }
+        }
+
+        Ok((block_number + 1).saturating_sub(from))
+    }
+
+    /// Adds the writes moving the receipts and trie logs of `block_number` to the cold storage.
+    fn collect_block(
+        &self,
+        cold: &DB,
+        block_number: u64,
+        to_cold: &mut WriteBatchWithTransaction<true>,
+        from_primary: &mut WriteBatchWithTransaction<true>,
+    ) -> Result<(), DbError> {
+        let receipts = self.db.get_column(Column::TransactionReceipts);
+        let cold_receipts = cold.get_column(Column::TransactionReceipts);
+        for hash in self.transactions.block_hashes(block_number)? {
+            let key = hash.encode();
+            if let Some(receipt) = self.db.get_cf(&receipts, &key)? {
+                to_cold.put_cf(&cold_receipts, &key, receipt);
+                from_primary.delete_cf(&receipts, &key);
+            }
+        }
+
+        // the state resulting from block `n` is committed with id `n + 1`
+        let prefix = (block_number + 1).to_be_bytes();
+        for column in LOG_COLUMNS {
+            let logs = self.db.get_column(column);
+            let cold_logs = cold.get_column(column);
+            for kv in self.db.iterator_cf(&logs, IteratorMode::From(&prefix, Direction::Forward)) {
+                let (key, value) = kv?;
+                if !key.starts_with(&prefix) {
+                    break;
+                }
+                to_cold.put_cf(&cold_logs, &key, value);
+                from_primary.delete_cf(&logs, &key);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_api::hash::StarkFelt;
+    use starknet_api::transaction::TransactionHash;
+
+    use super::*;
+    use crate::test_utils::{test_db, test_dir};
+
+    #[test]
+    fn old_blocks_are_moved_to_the_cold_storage() {
+        let primary = test_db("cold-storage");
+        let dir = test_dir("cold-storage-cold");
+        let cold =
+            Arc::new(open_cold_storage(&primary, Some(dir.path()), false, &DbTuning::default()).unwrap().unwrap());
+        let transactions = Arc::new(TransactionDb::new(primary.clone()));
+        let storage = ColdStorageDb::new(primary.clone(), Some(Arc::clone(&cold)), Arc::clone(&transactions));
+
+        let receipts = primary.get_column(Column::TransactionReceipts);
+        let logs = primary.get_column(Column::BonsaiContractsLog);
+        let log_key = |block_number: u64| [&(block_number + 1).to_be_bytes()[..], b"key"].concat();
+        for block_number in 0..3u64 {
+            let hash = TransactionHash(StarkFelt::from(block_number as u128 + 1));
+            transactions.store_block(block_number, &[hash]).unwrap();
+            primary.put_cf(&receipts, hash.encode(), b"receipt").unwrap();
+            primary.put_cf(&logs, log_key(block_number), b"log").unwrap();
+        }
+
+        assert_eq!(storage.move_blocks(1).unwrap(), 2);
+        assert_eq!(storage.moved_block().unwrap(), Some(1));
+        let moved = TransactionHash(StarkFelt::from(2u128)).encode();
+        let kept = TransactionHash(StarkFelt::from(3u128)).encode();
+        assert_eq!(primary.get_cf(&receipts, &moved).unwrap(), None);
+        assert_eq!(
+            cold.get_cf(&cold.get_column(Column::TransactionReceipts), &moved).unwrap(),
+            Some(b"receipt".to_vec())
+        );
+        assert!(primary.get_cf(&receipts, &kept).unwrap().is_some());
+        assert_eq!(primary.get_cf(&logs, log_key(1)).unwrap(), None);
+        assert!(cold.get_cf(&cold.get_column(Column::BonsaiContractsLog), log_key(1)).unwrap().is_some());
+        assert!(primary.get_cf(&logs, log_key(2)).unwrap().is_some());
+
+        // the moved blocks are not moved again, and the database needs its cold storage from now on
+        assert_eq!(storage.move_blocks(1).unwrap(), 0);
+        assert!(open_cold_storage(&primary, None, false, &DbTuning::default()).is_err());
+    }
+
+    #[test]
+    fn nothing_is_moved_without_a_cold_storage() {
+        let primary = test_db("no-cold-storage");
+        let transactions = Arc::new(TransactionDb::new(primary.clone()));
+        let storage = ColdStorageDb::new(primary.clone(), None, transactions);
+
+        assert_eq!(storage.move_blocks(10).unwrap(), 0);
+        assert_eq!(storage.moved_block().unwrap(), None);
+        assert!(open_cold_storage(&primary, None, false, &DbTuning::default()).unwrap().is_none());
+    }
+}
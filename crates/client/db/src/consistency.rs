@@ -0,0 +1,36 @@
+use anyhow::{Context, Result};
+use mp_types::block::DHashT;
+use parity_scale_codec::Decode;
+use rocksdb::IteratorMode;
+
+use crate::{Column, DatabaseExt, DB};
+
+/// Runs fast consistency checks over the database, to be used after an unclean shutdown.
+///
+/// This does not walk the whole database: it checks that each column can be read at both of its
+/// ends, and that the syncing tips can still be decoded. Deeper corruption is left to RocksDB's
+/// paranoid checks, which are enabled when opening a database after an unclean shutdown.
+pub(crate) fn fast_check(db: &DB) -> Result<()> {
+    check_columns(db, Column::ALL)?;
+
+    let meta = db.get_column(Column::Meta);
+    if let Some(raw) = db.get_cf(&meta, crate::static_keys::CURRENT_SYNCING_TIPS)? {
+        Vec::<DHashT>::decode(&mut &raw[..]).context("Failed to decode the current syncing tips")?;
+    }
+
+    Ok(())
+}
+
+/// Checks that each of the given columns can be read at both of its ends.
+pub(crate) fn check_columns(db: &DB, columns: &[Column]) -> Result<()> {
+    for column in columns {
+        let handle = db.get_column(*column);
+        for mode in [IteratorMode::Start, IteratorMode::End] {
+            db.iterator_cf(&handle, mode)
+                .next()
+                .transpose()
+                .with_context(|| format!("Failed to read column `{column}`"))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,588 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+// Substrate
+use parity_scale_codec::{Decode, Encode};
+use rocksdb::{BoundColumnFamily, Direction, IteratorMode, SnapshotWithThreadMode, WriteBatchWithTransaction};
+// Starknet
+use starknet_api::core::{ClassHash, ContractAddress, Nonce, PatriciaKey};
+use starknet_api::hash::StarkFelt;
+use starknet_api::state::StorageKey;
+
+use crate::{Column, DatabaseExt, DbError, DB};
+
+/// A value of the state which differs between two blocks, `None` when it was never set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change<T> {
+    pub before: Option<T>,
+    pub after: Option<T>,
+}
+
+/// The net changes made to a contract between two blocks, see [`ContractStorageDb::contract_diff`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractDiff {
+    /// The changed storage slots, ordered by key
+    pub storage: Vec<(StorageKey, Change<StarkFelt>)>,
+    pub nonce: Option<Change<Nonce>>,
+    pub class_hash: Option<Change<ClassHash>>,
+}
+
+/// Why the diff of a contract could not be computed, see [`ContractStorageDb::contract_diff`]
+#[derive(Debug, thiserror::Error)]
+pub enum ContractDiffError {
+    #[error("more than {0} storage slots were updated")]
+    TooManySlots(usize),
+    #[error("the storage updates of block {0} are not indexed")]
+    Unindexed(u64),
+    #[error(transparent)]
+    Db(#[from] DbError),
+}
+
+impl From<parity_scale_codec::Error> for ContractDiffError {
+    fn from(err: parity_scale_codec::Error) -> Self {
+        Self::Db(err.into())
+    }
+}
+
+impl From<rocksdb::Error> for ContractDiffError {
+    fn from(err: rocksdb::Error) -> Self {
+        Self::Db(err.into())
+    }
+}
+
+/// Allow interaction with the flat contract storage db
+///
+/// This mirrors the contract storage tries as plain `(contract, key, block) -> value` entries, so
+/// that a storage value at any block can be read with a single seek instead of a trie traversal.
+/// The tries remain the source of truth for the state commitments.
+///
+/// The nonces and class hashes of the contracts are likewise stored as `(contract, block) ->
+/// value` entries, so that the state of a past block can be read without going through the
+/// runtime.
+///
+/// Databases created before this column existed only hold the blocks synced since. The missing
+/// history is filled in by a backfill task: until it is done, [`ContractStorageDb::is_available`]
+/// returns `false` for older blocks and reads should fall back to the tries.
+///
+/// The contracts whose storage, class or nonce were modified by each block are also recorded, from
+/// the first block imported by a version of the node recording them on.
+pub struct ContractStorageDb {
+    pub(crate) db: Arc<DB>,
+}
+
+impl ContractStorageDb {
+    pub(crate) fn new(db: Arc<DB>) -> Self {
+        Self { db }
+    }
+
+    /// Return the value of a storage slot right after the given block, if it was ever set
+    pub fn get_at(
+        &self,
+        contract_address: &ContractAddress,
+        key: &StorageKey,
+        block_number: u64,
+    ) -> Result<Option<StarkFelt>, DbError> {
+        self.latest_at(Column::ContractStorage, &slot_prefix(contract_address, key), block_number)
+    }
+
+    /// Return the nonce of a contract right after the given block, if it was ever set
+    pub fn nonce_at(&self, contract_address: &ContractAddress, block_number: u64) -> Result<Option<Nonce>, DbError> {
+        Ok(self.latest_at(Column::ContractNonces, contract_address.0.0.bytes(), block_number)?.map(Nonce))
+    }
+
+    /// Return the class hash of a contract right after the given block, if it was deployed
+    pub fn class_hash_at(
+        &self,
+        contract_address: &ContractAddress,
+        block_number: u64,
+    ) -> Result<Option<ClassHash>, DbError> {
+        Ok(self.latest_at(Column::ContractClassHashes, contract_address.0.0.bytes(), block_number)?.map(ClassHash))
+    }
+
+    /// Return the latest value stored under `prefix` at or before the given block
+    fn latest_at(&self, column: Column, prefix: &[u8], block_number: u64) -> Result<Option<StarkFelt>, DbError> {
+        let column = self.db.get_column(column);
+        let start = [prefix, &block_number.to_be_bytes()].concat();
+
+        match self.db.iterator_cf(&column, IteratorMode::From(&start, Direction::Reverse)).next().transpose()? {
+            Some((key, value)) if key.starts_with(prefix) => Ok(Some(StarkFelt::decode(&mut &value[..])?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Return the values of many storage slots right after the given block, in order
+    ///
+    /// The slots are read from a single snapshot of the database, so that a reorg in the meantime
+    /// can't mix values from two chains.
+    pub fn get_many_at<'a>(
+        &self,
+        slots: impl IntoIterator<Item = (&'a ContractAddress, &'a StorageKey)>,
+        block_number: u64,
+    ) -> Result<Vec<Option<StarkFelt>>, DbError> {
+        let column = self.db.get_column(Column::ContractStorage);
+        let snapshot = self.db.snapshot();
+
+        slots
+            .into_iter()
+            .map(|(contract_address, key)| {
+                latest_in(&snapshot, &column, &slot_prefix(contract_address, key), block_number)
+            })
+            .collect()
+    }
+
+    /// Return the net changes made to a contract between the states right after two blocks
+    ///
+    /// A storage slot, nonce or class hash is only part of the diff if its value differs between
+    /// the two states, a value which was never set being zero, so `from_block` may also come after
+    /// `to_block`. The candidate slots are the ones updated by the blocks in between, read from the
+    /// index of the storage updates of each block, and both states are read from a single snapshot
+    /// of the database.
+    ///
+    /// Fails with [`ContractDiffError::TooManySlots`] as soon as more than `max_slots` slots were
+    /// updated in between, changed or not, which bounds the work done.
+    pub fn contract_diff(
+        &self,
+        contract_address: &ContractAddress,
+        from_block: u64,
+        to_block: u64,
+        max_slots: usize,
+    ) -> Result<ContractDiff, ContractDiffError> {
+        let (first_block, last_block) = (from_block.min(to_block) + 1, from_block.max(to_block));
+        if first_block <= last_block && first_block < self.storage_updates_from()? {
+            return Err(ContractDiffError::Unindexed(first_block));
+        }
+
+        let snapshot = self.db.snapshot();
+        let contract_prefix = contract_address.0.0.bytes();
+        let change = |column: Column, prefix: &[u8]| {
+            let column = self.db.get_column(column);
+            let before = latest_in(&snapshot, &column, prefix, from_block)?;
+            let after = latest_in(&snapshot, &column, prefix, to_block)?;
+            Ok::<_, DbError>(
+                (before.unwrap_or_default() != after.unwrap_or_default()).then_some(Change { before, after }),
+            )
+        };
+
+        // the slots updated by the blocks in between, whose values may differ
+        let updates_column = self.db.get_column(Column::ContractStorageUpdates);
+        let start = contract_key(contract_address, first_block);
+        let mut updated_keys = BTreeSet::new();
+        for entry in snapshot.iterator_cf(&updates_column, IteratorMode::From(&start, Direction::Forward)) {
+            let (key, value) = entry?;
+            if !key.starts_with(contract_prefix) {
+                break;
+            }
+            let block_number = u64::from_be_bytes(key[32..].try_into().expect("Block numbers are 8 bytes long"));
+            if block_number > last_block {
+                break;
+            }
+            updated_keys.extend(Vec::<StarkFelt>::decode(&mut &value[..])?);
+            if updated_keys.len() > max_slots {
+                return Err(ContractDiffError::TooManySlots(max_slots));
+            }
+        }
+
+        let storage_column = self.db.get_column(Column::ContractStorage);
+        let mut storage = Vec::new();
+        for key in updated_keys {
+            let key = StorageKey(PatriciaKey(key));
+            let slot = slot_prefix(contract_address, &key);
+            let before = latest_in(&snapshot, &storage_column, &slot, from_block)?;
+            let after = latest_in(&snapshot, &storage_column, &slot, to_block)?;
+            if before.unwrap_or_default() != after.unwrap_or_default() {
+                storage.push((key, Change { before, after }));
+            }
+        }
+
+        Ok(ContractDiff {
+            storage,
+            nonce: change(Column::ContractNonces, contract_prefix)?
+                .map(|change| Change { before: change.before.map(Nonce), after: change.after.map(Nonce) }),
+            class_hash: change(Column::ContractClassHashes, contract_prefix)?
+                .map(|change| Change { before: change.before.map(ClassHash), after: change.after.map(ClassHash) }),
+        })
+    }
+
+    /// Return the storage updates made to a contract by a block, ordered by key
+    ///
+    /// Returns `None` if the updated keys of the block were not indexed, see
+    /// [`Self::start_live_updates`].
+    pub fn storage_updates(
+        &self,
+        contract_address: &ContractAddress,
+        block_number: u64,
+    ) -> Result<Option<Vec<(StorageKey, StarkFelt)>>, DbError> {
+        if block_number < self.storage_updates_from()? {
+            return Ok(None);
+        }
+
+        let snapshot = self.db.snapshot();
+        let updates_column = self.db.get_column(Column::ContractStorageUpdates);
+        let storage_column = self.db.get_column(Column::ContractStorage);
+        let keys = match snapshot.get_cf(&updates_column, contract_key(contract_address, block_number))? {
+            Some(raw) => Vec::<StarkFelt>::decode(&mut &raw[..])?,
+            None => return Ok(Some(Vec::new())),
+        };
+
+        let mut updates = Vec::with_capacity(keys.len());
+        for key in keys {
+            let key = StorageKey(PatriciaKey(key));
+            let slot = [slot_prefix(contract_address, &key).as_slice(), &block_number.to_be_bytes()].concat();
+            if let Some(raw) = snapshot.get_cf(&storage_column, slot)? {
+                updates.push((key, StarkFelt::decode(&mut &raw[..])?));
+            }
+        }
+        Ok(Some(updates))
+    }
+
+    /// Store the storage updates of a block
+    ///
+    /// The updated keys of each contract are also indexed by block, for [`Self::contract_diff`].
+    /// Storing the updates of a block again replaces the keys indexed for the contracts it updates.
+    pub fn store_block<'a>(
+        &self,
+        block_number: u64,
+        updates: impl IntoIterator<Item = (&'a ContractAddress, &'a StorageKey, &'a StarkFelt)>,
+    ) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::ContractStorage);
+        let updates_column = self.db.get_column(Column::ContractStorageUpdates);
+
+        let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+        let mut updated_keys: BTreeMap<&ContractAddress, Vec<StarkFelt>> = BTreeMap::new();
+        for (contract_address, key, value) in updates {
+            let slot = slot_prefix(contract_address, key);
+            transaction.put_cf(&column, [slot.as_slice(), &block_number.to_be_bytes()].concat(), value.encode());
+            updated_keys.entry(contract_address).or_default().push(key.0.0);
+        }
+        for (contract_address, mut keys) in updated_keys {
+            keys.sort();
+            keys.dedup();
+            transaction.put_cf(&updates_column, contract_key(contract_address, block_number), keys.encode());
+        }
+
+        self.db.write(transaction)?;
+        Ok(())
+    }
+
+    /// Return the first block whose updated storage keys were indexed
+    fn storage_updates_from(&self) -> Result<u64, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf(&column, crate::static_keys::STORAGE_UPDATES_FROM)? {
+            Some(raw) => Ok(u64::decode(&mut &raw[..])?),
+            None => Ok(0),
+        }
+    }
+
+    /// Store the nonce updates and the classes of the contracts deployed or replaced by a block
+    pub fn store_contracts<'a>(
+        &self,
+        block_number: u64,
+        nonces: impl IntoIterator<Item = (&'a ContractAddress, &'a Nonce)>,
+        class_hashes: impl IntoIterator<Item = (&'a ContractAddress, &'a ClassHash)>,
+    ) -> Result<(), DbError> {
+        let nonces_column = self.db.get_column(Column::ContractNonces);
+        let class_hashes_column = self.db.get_column(Column::ContractClassHashes);
+
+        let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+        for (contract_address, nonce) in nonces {
+            transaction.put_cf(&nonces_column, contract_key(contract_address, block_number), nonce.0.encode());
+        }
+        for (contract_address, class_hash) in class_hashes {
+            transaction.put_cf(
+                &class_hashes_column,
+                contract_key(contract_address, block_number),
+                class_hash.0.encode(),
+            );
+        }
+
+        self.db.write(transaction)?;
+        Ok(())
+    }
+
+    /// Store the contracts modified by a block
+    ///
+    /// Storing the modified contracts of a block again replaces them, as when the block is
+    /// reorganized away.
+    pub fn store_modified_contracts(&self, block_number: u64, contracts: &[ContractAddress]) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::ModifiedContracts);
+        let meta = self.db.get_column(Column::Meta);
+
+        let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+        if self.db.get_cf(&meta, crate::static_keys::MODIFIED_CONTRACTS_FROM)?.is_none() {
+            transaction.put_cf(&meta, crate::static_keys::MODIFIED_CONTRACTS_FROM, block_number.encode());
+        }
+        transaction.put_cf(&column, block_number.to_be_bytes(), contracts.encode());
+
+        self.db.write(transaction)?;
+        Ok(())
+    }
+
+    /// Return the contracts modified by a block
+    ///
+    /// Returns `None` if the block was imported before the modified contracts were recorded, or
+    /// if its flat storage is yet to be backfilled.
+    pub fn modified_contracts(&self, block_number: u64) -> Result<Option<Vec<ContractAddress>>, DbError> {
+        let column = self.db.get_column(Column::ModifiedContracts);
+        let meta = self.db.get_column(Column::Meta);
+
+        let recorded_from = match self.db.get_cf(&meta, crate::static_keys::MODIFIED_CONTRACTS_FROM)? {
+            Some(raw) => u64::decode(&mut &raw[..])?,
+            None => return Ok(None),
+        };
+        if block_number < recorded_from || self.backfill_range()?.contains(&block_number) {
+            return Ok(None);
+        }
+
+        match self.db.get_cf(&column, block_number.to_be_bytes())? {
+            Some(raw) => Ok(Some(Vec::<ContractAddress>::decode(&mut &raw[..])?)),
+            None => Ok(Some(Vec::new())),
+        }
+    }
+
+    /// Record that storage updates are stored as blocks are synced, starting from `block_number`
+    ///
+    /// Only the first call has an effect: on later runs, the blocks in between were stored as
+    /// they were synced. Returns the block from which storage updates were first stored.
+    ///
+    /// The backfill indexes the updated keys of the blocks it stores, but a flat storage started
+    /// before they were indexed only has them from the block given on the first run since.
+    pub fn start_live_updates(&self, block_number: u64) -> Result<u64, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        let live_from = match self.db.get_cf(&column, crate::static_keys::FLAT_STORAGE_LIVE_FROM)? {
+            Some(raw) => u64::decode(&mut &raw[..])?,
+            None => {
+                self.db.put_cf(&column, crate::static_keys::FLAT_STORAGE_LIVE_FROM, block_number.encode())?;
+                self.db.put_cf(&column, crate::static_keys::STORAGE_UPDATES_FROM, 0u64.encode())?;
+                return Ok(block_number);
+            }
+        };
+        if self.db.get_cf(&column, crate::static_keys::STORAGE_UPDATES_FROM)?.is_none() {
+            self.db.put_cf(&column, crate::static_keys::STORAGE_UPDATES_FROM, block_number.encode())?;
+        }
+        Ok(live_from)
+    }
+
+    /// Return the blocks which still need to be backfilled
+    pub fn backfill_range(&self) -> Result<std::ops::Range<u64>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        let live_from = match self.db.get_cf(&column, crate::static_keys::FLAT_STORAGE_LIVE_FROM)? {
+            Some(raw) => u64::decode(&mut &raw[..])?,
+            None => return Ok(0..0),
+        };
+        let backfilled = match self.db.get_cf(&column, crate::static_keys::FLAT_STORAGE_BACKFILLED)? {
+            Some(raw) => u64::decode(&mut &raw[..])?,
+            None => 0,
+        };
+
+        Ok(backfilled..live_from)
+    }
+
+    /// Record that all the blocks before `block_number` were backfilled
+    pub fn set_backfilled(&self, block_number: u64) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        self.db.put_cf(&column, crate::static_keys::FLAT_STORAGE_BACKFILLED, block_number.encode())?;
+        Ok(())
+    }
+
+    /// Whether reads at the given block can be served from this db
+    pub fn is_available(&self, block_number: u64) -> Result<bool, DbError> {
+        let missing = self.backfill_range()?;
+        Ok(missing.is_empty() || block_number < missing.start)
+    }
+}
+
+/// Return the latest value stored under `prefix` at or before the given block, in a snapshot
+fn latest_in(
+    snapshot: &SnapshotWithThreadMode<DB>,
+    column: &Arc<BoundColumnFamily>,
+    prefix: &[u8],
+    block_number: u64,
+) -> Result<Option<StarkFelt>, DbError> {
+    let start = [prefix, &block_number.to_be_bytes()].concat();
+
+    match snapshot.iterator_cf(column, IteratorMode::From(&start, Direction::Reverse)).next().transpose()? {
+        Some((key, value)) if key.starts_with(prefix) => Ok(Some(StarkFelt::decode(&mut &value[..])?)),
+        _ => Ok(None),
+    }
+}
+
+fn slot_prefix(contract_address: &ContractAddress, key: &StorageKey) -> Vec<u8> {
+    [contract_address.0.0.bytes(), key.0.0.bytes()].concat()
+}
+
+fn contract_key(contract_address: &ContractAddress, block_number: u64) -> Vec<u8> {
+    let mut key = contract_address.0.0.bytes().to_vec();
+    key.extend_from_slice(&block_number.to_be_bytes());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_db;
+
+    #[test]
+    fn values_are_read_as_of_a_block() {
+        let db = test_db("contract-storage");
+        let storage = ContractStorageDb::new(db.clone());
+
+        let address = ContractAddress(PatriciaKey(StarkFelt::from(1u128)));
+        let key = StorageKey(PatriciaKey(StarkFelt::from(2u128)));
+        let other_key = StorageKey(PatriciaKey(StarkFelt::from(3u128)));
+
+        storage.store_block(5, [(&address, &key, &StarkFelt::from(10u128))]).unwrap();
+        storage.store_block(8, [(&address, &key, &StarkFelt::from(20u128))]).unwrap();
+        storage.store_block(9, [(&address, &other_key, &StarkFelt::from(30u128))]).unwrap();
+
+        assert_eq!(storage.get_at(&address, &key, 4).unwrap(), None);
+        assert_eq!(storage.get_at(&address, &key, 5).unwrap(), Some(StarkFelt::from(10u128)));
+        assert_eq!(storage.get_at(&address, &key, 7).unwrap(), Some(StarkFelt::from(10u128)));
+        assert_eq!(storage.get_at(&address, &key, u64::MAX).unwrap(), Some(StarkFelt::from(20u128)));
+        assert_eq!(storage.get_at(&address, &other_key, 8).unwrap(), None);
+        assert_eq!(
+            storage.get_many_at([(&address, &key), (&address, &other_key)], 9).unwrap(),
+            [Some(StarkFelt::from(20u128)), Some(StarkFelt::from(30u128))]
+        );
+
+        // an existing database starts storing updates at block 100
+        assert!(storage.is_available(1000).unwrap());
+        assert_eq!(storage.start_live_updates(100).unwrap(), 100);
+        assert_eq!(storage.start_live_updates(200).unwrap(), 100);
+        assert_eq!(storage.backfill_range().unwrap(), 0..100);
+        assert!(!storage.is_available(100).unwrap());
+
+        storage.set_backfilled(100).unwrap();
+        assert!(storage.is_available(100).unwrap());
+    }
+
+    #[test]
+    fn nonces_and_class_hashes_are_read_as_of_a_block() {
+        let db = test_db("contract-nonces");
+        let storage = ContractStorageDb::new(db.clone());
+
+        let address = ContractAddress(PatriciaKey(StarkFelt::from(1u128)));
+        let other_address = ContractAddress(PatriciaKey(StarkFelt::from(2u128)));
+        let class_hash = ClassHash(StarkFelt::from(10u128));
+        let new_class_hash = ClassHash(StarkFelt::from(11u128));
+
+        storage.store_contracts(3, [], [(&address, &class_hash)]).unwrap();
+        storage.store_contracts(4, [(&address, &Nonce(StarkFelt::from(1u128)))], []).unwrap();
+        storage
+            .store_contracts(6, [(&address, &Nonce(StarkFelt::from(2u128)))], [(&address, &new_class_hash)])
+            .unwrap();
+
+        assert_eq!(storage.class_hash_at(&address, 2).unwrap(), None);
+        assert_eq!(storage.class_hash_at(&address, 5).unwrap(), Some(class_hash));
+        assert_eq!(storage.class_hash_at(&address, 6).unwrap(), Some(new_class_hash));
+        assert_eq!(storage.nonce_at(&address, 3).unwrap(), None);
+        assert_eq!(storage.nonce_at(&address, 5).unwrap(), Some(Nonce(StarkFelt::from(1u128))));
+        assert_eq!(storage.nonce_at(&address, u64::MAX).unwrap(), Some(Nonce(StarkFelt::from(2u128))));
+        assert_eq!(storage.nonce_at(&other_address, u64::MAX).unwrap(), None);
+    }
+
+    #[test]
+    fn contract_diffs_only_hold_net_changes() {
+        let db = test_db("contract-diff");
+        let storage = ContractStorageDb::new(db.clone());
+
+        let address = ContractAddress(PatriciaKey(StarkFelt::from(1u128)));
+        let other_address = ContractAddress(PatriciaKey(StarkFelt::from(2u128)));
+        let reverted = StorageKey(PatriciaKey(StarkFelt::from(3u128)));
+        let updated = StorageKey(PatriciaKey(StarkFelt::from(4u128)));
+        let created = StorageKey(PatriciaKey(StarkFelt::from(5u128)));
+        let zeroed = StorageKey(PatriciaKey(StarkFelt::from(6u128)));
+        let class_hash = ClassHash(StarkFelt::from(10u128));
+
+        let (one, two) = (StarkFelt::from(1u128), StarkFelt::from(2u128));
+        storage.store_contracts(1, [], [(&address, &class_hash)]).unwrap();
+        storage.store_block(1, [(&address, &reverted, &one), (&address, &updated, &one)]).unwrap();
+        storage
+            .store_block(
+                2,
+                [(&address, &reverted, &two), (&address, &created, &one), (&address, &zeroed, &StarkFelt::ZERO)],
+            )
+            .unwrap();
+        storage.store_block(2, [(&other_address, &updated, &one)]).unwrap();
+        storage.store_block(3, [(&address, &reverted, &one), (&address, &updated, &two)]).unwrap();
+        storage.store_contracts(3, [(&address, &Nonce(one))], []).unwrap();
+
+        let diff = storage.contract_diff(&address, 1, 3, 10).unwrap();
+        assert_eq!(
+            diff.storage,
+            [
+                (updated, Change { before: Some(one), after: Some(two) }),
+                (created, Change { before: None, after: Some(one) }),
+            ]
+        );
+        assert_eq!(diff.nonce, Some(Change { before: None, after: Some(Nonce(one)) }));
+        assert_eq!(diff.class_hash, None);
+
+        // the diff of a deployment, read backwards
+        let diff = storage.contract_diff(&address, 3, 0, 10).unwrap();
+        assert_eq!(diff.storage.len(), 3);
+        assert_eq!(diff.class_hash, Some(Change { before: Some(class_hash), after: None }));
+
+        // the updated slots are bounded, whether they changed or not
+        assert!(matches!(storage.contract_diff(&address, 1, 3, 2), Err(ContractDiffError::TooManySlots(2))));
+        assert!(storage.contract_diff(&address, 3, 3, 0).unwrap().storage.is_empty());
+        assert!(storage.contract_diff(&address, 0, 3, 10).unwrap().storage.iter().all(|(key, _)| *key != zeroed));
+
+        // the updates of a single block are all kept
+        assert_eq!(
+            storage.storage_updates(&address, 2).unwrap(),
+            Some(vec![(reverted, two), (created, one), (zeroed, StarkFelt::ZERO)])
+        );
+        assert_eq!(storage.storage_updates(&other_address, 3).unwrap(), Some(vec![]));
+    }
+
+    #[test]
+    fn contract_diffs_need_the_storage_updates_to_be_indexed() {
+        let db = test_db("contract-diff-index");
+        let storage = ContractStorageDb::new(db.clone());
+        let address = ContractAddress(PatriciaKey(StarkFelt::from(1u128)));
+
+        // a flat storage started before the updated keys were indexed
+        let meta = storage.db.get_column(Column::Meta);
+        storage.db.put_cf(&meta, crate::static_keys::FLAT_STORAGE_LIVE_FROM, 5u64.encode()).unwrap();
+        assert_eq!(storage.start_live_updates(10).unwrap(), 5);
+        assert_eq!(storage.start_live_updates(20).unwrap(), 5);
+
+        assert!(matches!(storage.contract_diff(&address, 8, 12, 10), Err(ContractDiffError::Unindexed(9))));
+        assert!(storage.contract_diff(&address, 12, 9, 10).unwrap().storage.is_empty());
+        assert_eq!(storage.storage_updates(&address, 9).unwrap(), None);
+    }
+
+    #[test]
+    fn modified_contracts_are_recorded_from_the_first_block_stored() {
+        let db = test_db("modified-contracts");
+        let storage = ContractStorageDb::new(db.clone());
+
+        let first = ContractAddress(PatriciaKey(StarkFelt::from(1u128)));
+        let second = ContractAddress(PatriciaKey(StarkFelt::from(2u128)));
+
+        assert_eq!(storage.modified_contracts(10).unwrap(), None);
+
+        storage.store_modified_contracts(10, &[first, second]).unwrap();
+        storage.store_modified_contracts(12, &[second]).unwrap();
+
+        assert_eq!(storage.modified_contracts(9).unwrap(), None);
+        assert_eq!(storage.modified_contracts(10).unwrap(), Some(vec![first, second]));
+        assert_eq!(storage.modified_contracts(11).unwrap(), Some(vec![]));
+        assert_eq!(storage.modified_contracts(12).unwrap(), Some(vec![second]));
+
+        // a reorganized block replaces the contracts of the block it replaces
+        storage.store_modified_contracts(12, &[first]).unwrap();
+        assert_eq!(storage.modified_contracts(12).unwrap(), Some(vec![first]));
+
+        // the blocks of a flat storage being backfilled
+        let meta = storage.db.get_column(Column::Meta);
+        storage.db.put_cf(&meta, crate::static_keys::FLAT_STORAGE_LIVE_FROM, 11u64.encode()).unwrap();
+        storage.set_backfilled(10).unwrap();
+        assert_eq!(storage.modified_contracts(10).unwrap(), None);
+        assert_eq!(storage.modified_contracts(12).unwrap(), Some(vec![first]));
+    }
+}
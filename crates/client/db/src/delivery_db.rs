@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// Substrate
+use parity_scale_codec::{Decode, Encode};
+use rocksdb::{Direction, IteratorMode};
+
+use crate::{Column, DatabaseExt, DbError, DB};
+
+/// An item waiting to be delivered to a downstream consumer.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct QueuedDelivery {
+    /// Position of the item in its queue, increasing in insertion order.
+    pub id: u64,
+    /// Number of failed delivery attempts so far.
+    pub attempts: u32,
+    /// Unix time in milliseconds before which the delivery should not be attempted again.
+    pub not_before: u64,
+    pub payload: Vec<u8>,
+}
+
+/// Allow interaction with the outbound delivery queues
+///
+/// Subsystems sending data out of the node (webhooks, exporters...) buffer their items here until
+/// they are acknowledged by the consumer, so that nothing is lost if the consumer is down or the
+/// node restarts. Each subsystem uses its own queue, identified by a topic which must not contain
+/// any `/`.
+pub struct DeliveryDb {
+    pub(crate) db: Arc<DB>,
+    /// Next id of each queue, loaded from the database on first use.
+    next_ids: Mutex<HashMap<String, u64>>,
+}
+
+impl DeliveryDb {
+    pub(crate) fn new(db: Arc<DB>) -> Self {
+        Self { db, next_ids: Default::default() }
+    }
+
+    /// Append an item to the queue of `topic`, returning its id
+    pub fn enqueue(&self, topic: &str, payload: Vec<u8>) -> Result<u64, DbError> {
+        let column = self.db.get_column(Column::DeliveryQueue);
+
+        let mut next_ids = self.next_ids.lock().expect("Failed to acquire lock on delivery queue ids");
+        let id = match next_ids.get(topic) {
+            Some(id) => *id,
+            None => self.last_id(topic)?.map_or(0, |id| id + 1),
+        };
+
+        let delivery = QueuedDelivery { id, attempts: 0, not_before: 0, payload };
+        self.db.put_cf(&column, queue_key(topic, id), delivery.encode())?;
+        next_ids.insert(topic.to_string(), id + 1);
+
+        Ok(id)
+    }
+
+    /// Return up to `limit` items of the queue of `topic` which are due at time `now` (in unix
+    /// milliseconds), oldest first
+    pub fn due(&self, topic: &str, now: u64, limit: usize) -> Result<Vec<QueuedDelivery>, DbError> {
+        let column = self.db.get_column(Column::DeliveryQueue);
+        let prefix = queue_prefix(topic);
+
+        let mut due = Vec::new();
+        for kv in self.db.iterator_cf(&column, IteratorMode::From(&prefix, Direction::Forward)) {
+            let (key, value) = kv?;
+            if !key.starts_with(&prefix) || due.len() == limit {
+                break;
+            }
+
+            let delivery = QueuedDelivery::decode(&mut &value[..])?;
+            if delivery.not_before <= now {
+                due.push(delivery);
+            }
+        }
+
+        Ok(due)
+    }
+
+    /// Remove a delivered item from the queue of `topic`
+    pub fn ack(&self, topic: &str, id: u64) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::DeliveryQueue);
+
+        self.db.delete_cf(&column, queue_key(topic, id))?;
+        Ok(())
+    }
+
+    /// Store an item of the queue of `topic` back after a failed delivery attempt
+    pub fn reschedule(&self, topic: &str, delivery: &QueuedDelivery) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::DeliveryQueue);
+
+        self.db.put_cf(&column, queue_key(topic, delivery.id), delivery.encode())?;
+        Ok(())
+    }
+
+    /// Return the number of items waiting in the queue of `topic`
+    pub fn len(&self, topic: &str) -> Result<usize, DbError> {
+        let column = self.db.get_column(Column::DeliveryQueue);
+        let prefix = queue_prefix(topic);
+
+        let mut len = 0;
+        for kv in self.db.iterator_cf(&column, IteratorMode::From(&prefix, Direction::Forward)) {
+            let (key, _) = kv?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            len += 1;
+        }
+
+        Ok(len)
+    }
+
+    fn last_id(&self, topic: &str) -> Result<Option<u64>, DbError> {
+        let column = self.db.get_column(Column::DeliveryQueue);
+        let prefix = queue_prefix(topic);
+        let end = queue_key(topic, u64::MAX);
+
+        match self.db.iterator_cf(&column, IteratorMode::From(&end, Direction::Reverse)).next().transpose()? {
+            Some((key, _)) if key.starts_with(&prefix) => {
+                Ok(Some(u64::from_be_bytes(key[prefix.len()..].try_into().expect("Queue keys end with the item id"))))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+fn queue_prefix(topic: &str) -> Vec<u8> {
+    [topic.as_bytes(), b"/"].concat()
+}
+
+/// Ids are big-endian encoded so that items are iterated over in insertion order.
+fn queue_key(topic: &str, id: u64) -> Vec<u8> {
+    [queue_prefix(topic).as_slice(), &id.to_be_bytes()].concat()
+}
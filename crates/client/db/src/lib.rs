@@ -19,25 +19,66 @@ use anyhow::{bail, Context, Result};
 use bonsai_db::{BonsaiDb, DatabaseKeyMapping};
 use bonsai_trie::id::BasicId;
 use bonsai_trie::{BonsaiStorage, BonsaiStorageConfig};
+use class_artifact_db::ClassArtifactDb;
+use class_db::ClassDb;
+use class_quarantine_db::ClassQuarantineDb;
+use cold::ColdStorageDb;
+use contract_storage_db::ContractStorageDb;
 use da_db::DaDb;
+use delivery_db::DeliveryDb;
 use l1_handler_tx_fee::L1HandlerTxFeeDb;
+use lock::DataDirLock;
+use maintenance_db::MaintenanceDb;
 use mapping_db::MappingDb;
 use meta_db::MetaDb;
+use receipt_db::ReceiptDb;
 use sc_client_db::DatabaseSource;
-
+use shards::BonsaiInstances;
+use transaction_db::TransactionDb;
+
+mod class_artifact_db;
+mod class_db;
+mod class_quarantine_db;
+mod cold;
+mod consistency;
+mod contract_storage_db;
 mod error;
 mod mapping_db;
+mod migration;
 use rocksdb::{BoundColumnFamily, ColumnFamilyDescriptor, MultiThreaded, OptimisticTransactionDB, Options};
 mod da_db;
+mod delivery_db;
 use starknet_api::hash::StarkHash;
 use starknet_types_core::hash::{Pedersen, Poseidon};
 pub mod bonsai_db;
 mod l1_handler_tx_fee;
+mod lock;
+mod maintenance_db;
 mod meta_db;
+mod receipt_db;
+mod shards;
 pub mod storage;
-
+#[cfg(test)]
+mod test_utils;
+mod transaction_db;
+mod trie_delta;
+mod trie_writes;
+mod tuning;
+
+pub use class_db::{ClassDeclaration, ClassDeclarationsPage, ClassLengths};
+pub use class_quarantine_db::{QuarantineReason, QuarantineResolution, QuarantinedClass};
+pub use contract_storage_db::{Change, ContractDiff, ContractDiffError};
+pub use delivery_db::QueuedDelivery;
 pub use error::{BonsaiDbError, DbError};
+pub use maintenance_db::ColumnStats;
 pub use mapping_db::MappingCommitment;
+pub use meta_db::VerifiedBlock;
+pub use migration::SCHEMA_VERSION;
+pub use shards::MAX_STORAGE_TRIE_SHARDS;
+pub use transaction_db::{EventSlice, TransactionLocation};
+pub use trie_delta::TrieDelta;
+pub use trie_writes::{ColumnWrites, TrieWrites};
+pub use tuning::{DbCompression, DbProfile, DbTuning};
 
 const DB_HASH_LEN: usize = 32;
 /// Hash type that this backend uses for the database.
@@ -49,6 +90,16 @@ struct DatabaseSettings {
     pub max_saved_trie_logs: Option<usize>,
     pub max_saved_snapshots: Option<usize>,
     pub snapshot_interval: u64,
+    /// Whether the database was not closed properly the last time it was used.
+    pub dirty: bool,
+    /// The number of RocksDB instances the contract storage tries are sharded across, if set by the
+    /// operator.
+    pub storage_trie_shards: Option<usize>,
+    /// The directory of the cold storage, holding the receipts and trie logs of old blocks, if set
+    /// by the operator.
+    pub cold_storage: Option<PathBuf>,
+    /// The tuning of the RocksDB instances.
+    pub tuning: DbTuning,
 }
 
 impl From<&DatabaseSettings> for BonsaiStorageConfig {
@@ -65,36 +116,58 @@ pub type DB = OptimisticTransactionDB<MultiThreaded>;
 
 pub(crate) fn open_database(config: &DatabaseSettings) -> Result<DB> {
     Ok(match &config.source {
-        DatabaseSource::RocksDb { path, .. } => open_rocksdb(path, true)?,
-        DatabaseSource::Auto { paritydb_path: _, rocksdb_path, .. } => open_rocksdb(rocksdb_path, false)?,
+        DatabaseSource::RocksDb { path, .. } => {
+            open_rocksdb_columns(path, true, config.dirty, Column::ALL, &config.tuning)?
+        }
+        DatabaseSource::Auto { paritydb_path: _, rocksdb_path, .. } => {
+            open_rocksdb_columns(rocksdb_path, false, config.dirty, Column::ALL, &config.tuning)?
+        }
         _ => bail!("only the rocksdb database source is supported at the moment"),
     })
 }
 
-pub(crate) fn open_rocksdb(path: &Path, create: bool) -> Result<OptimisticTransactionDB<MultiThreaded>> {
+#[cfg(test)]
+pub(crate) fn open_rocksdb(path: &Path, create: bool, dirty: bool) -> Result<OptimisticTransactionDB<MultiThreaded>> {
+    open_rocksdb_columns(path, create, dirty, Column::ALL, &DbTuning::default())
+}
+
+/// Opens a RocksDB instance holding only the given columns.
+pub(crate) fn open_rocksdb_columns(
+    path: &Path,
+    create: bool,
+    dirty: bool,
+    columns: &[Column],
+    tuning: &DbTuning,
+) -> Result<OptimisticTransactionDB<MultiThreaded>> {
     let mut opts = Options::default();
+    // after an unclean shutdown, verify checksums of everything RocksDB reads while recovering
+    opts.set_paranoid_checks(dirty);
     opts.set_report_bg_io_stats(true);
     opts.set_use_fsync(false);
     opts.create_if_missing(create);
     opts.create_missing_column_families(true);
     opts.set_bytes_per_sync(1024 * 1024);
     opts.set_keep_log_file_num(1);
-    let cores = std::thread::available_parallelism().map(|e| e.get() as i32).unwrap_or(1);
-    opts.increase_parallelism(i32::max(cores / 2, 1));
+    tuning.apply(&mut opts);
 
+    let cache = tuning.block_cache();
     let db = OptimisticTransactionDB::<MultiThreaded>::open_cf_descriptors(
         &opts,
         path,
-        Column::ALL.iter().map(|col| ColumnFamilyDescriptor::new(col.rocksdb_name(), col.rocksdb_options())),
+        columns.iter().map(|col| ColumnFamilyDescriptor::new(col.rocksdb_name(), tuning.column_options(*col, &cache))),
     )?;
 
     Ok(db)
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Column {
     Meta,
     BlockMapping,
+
+    /// This column is used to map Starknet block hashes to their block number.
+    BlockNumbers,
+
     TransactionMapping,
     SyncedMapping,
     Da,
@@ -113,6 +186,63 @@ pub enum Column {
     // TODO: remove this
     L1HandlerPaidFee,
 
+    /// This column is used to map class hashes to the number of the block they were declared in.
+    ClassDeclarations,
+
+    /// This column is used to list the classes declared in each block, along with their compiled
+    /// class hash and size.
+    ClassDeclarationsByBlock,
+
+    /// This column is used to map Sierra class hashes to the length of their Sierra program and
+    /// ABI, needed to execute their declaration again.
+    ClassLengths,
+
+    /// This column is used to map class hashes to the compiled classes imported from another
+    /// node.
+    ClassArtifacts,
+
+    /// This column is used to map class hashes to the report of their quarantine, for the classes
+    /// whose compiled class hash does not match the declared one.
+    ClassQuarantine,
+
+    /// This column is used to map transaction hashes to their receipt, as provided by the feeder
+    /// gateway.
+    TransactionReceipts,
+
+    /// This column is used to map transaction hashes to their block number and index in the
+    /// block.
+    TransactionLocations,
+
+    /// This column is used to map the block number and index of transactions to their hash.
+    BlockTransactionHashes,
+
+    /// This column is used to map transaction hashes to the position of their events among the
+    /// events of their block.
+    TransactionEvents,
+
+    /// This column is used to map contract storage slots to their value after each block in which
+    /// they were updated.
+    ContractStorage,
+
+    /// This column is used to map contracts to the storage keys updated by each block which
+    /// updated their storage.
+    ContractStorageUpdates,
+
+    /// This column is used to map block numbers to the contracts whose state they modified.
+    ModifiedContracts,
+
+    /// This column is used to map contracts to their nonce after each block in which it was
+    /// updated.
+    ContractNonces,
+
+    /// This column is used to map contracts to their class hash after each block in which they
+    /// were deployed or had their class replaced.
+    ContractClassHashes,
+
+    /// This column holds the items of the outbound delivery queues which were not acknowledged by
+    /// their consumer yet.
+    DeliveryQueue,
+
     // Each bonsai storage has 3 columns
     BonsaiContractsTrie,
     BonsaiContractsFlat,
@@ -145,12 +275,28 @@ impl Column {
         &[
             Meta,
             BlockMapping,
+            BlockNumbers,
             TransactionMapping,
             SyncedMapping,
             Da,
             StarknetTransactionHashesCache,
             StarknetBlockHashesCache,
             L1HandlerPaidFee,
+            ClassDeclarations,
+            ClassDeclarationsByBlock,
+            ClassLengths,
+            ClassArtifacts,
+            ClassQuarantine,
+            TransactionReceipts,
+            TransactionLocations,
+            BlockTransactionHashes,
+            TransactionEvents,
+            ContractStorage,
+            ContractStorageUpdates,
+            ModifiedContracts,
+            ContractNonces,
+            ContractClassHashes,
+            DeliveryQueue,
             BonsaiContractsTrie,
             BonsaiContractsFlat,
             BonsaiContractsLog,
@@ -164,16 +310,58 @@ impl Column {
     };
     pub const NUM_COLUMNS: usize = Self::ALL.len();
 
+    /// Returns the column with the given name, as displayed.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|column| column.rocksdb_name() == name)
+    }
+
+    /// Whether the column holds the trie logs of a bonsai storage, used to revert the tries.
+    pub fn is_trie_log(&self) -> bool {
+        matches!(self, Column::BonsaiContractsLog | Column::BonsaiContractsStorageLog | Column::BonsaiClassesLog)
+    }
+
+    /// Whether the column belongs to a bonsai storage.
+    pub fn is_bonsai(&self) -> bool {
+        matches!(
+            self,
+            Column::BonsaiContractsTrie
+                | Column::BonsaiContractsFlat
+                | Column::BonsaiContractsLog
+                | Column::BonsaiContractsStorageTrie
+                | Column::BonsaiContractsStorageFlat
+                | Column::BonsaiContractsStorageLog
+                | Column::BonsaiClassesTrie
+                | Column::BonsaiClassesFlat
+                | Column::BonsaiClassesLog
+        )
+    }
+
     pub(crate) fn rocksdb_name(&self) -> &'static str {
         match self {
             Column::Meta => "meta",
             Column::BlockMapping => "block_mapping",
+            Column::BlockNumbers => "block_numbers",
             Column::TransactionMapping => "transaction_mapping",
             Column::SyncedMapping => "synced_mapping",
             Column::Da => "da",
             Column::StarknetTransactionHashesCache => "starknet_transaction_hashes_cache",
             Column::StarknetBlockHashesCache => "starnet_block_hashes_cache",
             Column::L1HandlerPaidFee => "l1_handler_paid_fee",
+            Column::ClassDeclarations => "class_declarations",
+            Column::ClassDeclarationsByBlock => "class_declarations_by_block",
+            Column::ClassLengths => "class_lengths",
+            Column::ClassArtifacts => "class_artifacts",
+            Column::ClassQuarantine => "class_quarantine",
+            Column::TransactionReceipts => "transaction_receipts",
+            Column::TransactionLocations => "transaction_locations",
+            Column::BlockTransactionHashes => "block_transaction_hashes",
+            Column::TransactionEvents => "transaction_events",
+            Column::ContractStorage => "contract_storage",
+            Column::ContractStorageUpdates => "contract_storage_updates",
+            Column::ModifiedContracts => "modified_contracts",
+            Column::ContractNonces => "contract_nonces",
+            Column::ContractClassHashes => "contract_class_hashes",
+            Column::DeliveryQueue => "delivery_queue",
             Column::BonsaiContractsTrie => "bonsai_contracts_trie",
             Column::BonsaiContractsFlat => "bonsai_contracts_flat",
             Column::BonsaiContractsLog => "bonsai_contracts_log",
@@ -209,12 +397,28 @@ impl DatabaseExt for DB {
 pub mod static_keys {
     pub const CURRENT_SYNCING_TIPS: &[u8] = b"CURRENT_SYNCING_TIPS";
     pub const LAST_PROVED_BLOCK: &[u8] = b"LAST_PROVED_BLOCK";
+    pub const LAST_VERIFIED_BLOCK: &[u8] = b"LAST_VERIFIED_BLOCK";
+    pub const TRUSTED_CHECKPOINT: &[u8] = b"TRUSTED_CHECKPOINT";
     pub const LAST_SYNCED_L1_EVENT_BLOCK: &[u8] = b"LAST_SYNCED_L1_EVENT_BLOCK";
+    pub const FLAT_STORAGE_LIVE_FROM: &[u8] = b"FLAT_STORAGE_LIVE_FROM";
+    pub const FLAT_STORAGE_BACKFILLED: &[u8] = b"FLAT_STORAGE_BACKFILLED";
+    pub const STORAGE_UPDATES_FROM: &[u8] = b"STORAGE_UPDATES_FROM";
+    pub const MODIFIED_CONTRACTS_FROM: &[u8] = b"MODIFIED_CONTRACTS_FROM";
+    pub const TRANSACTION_INDEX_LIVE_FROM: &[u8] = b"TRANSACTION_INDEX_LIVE_FROM";
+    pub const TRANSACTION_INDEX_BACKFILLED: &[u8] = b"TRANSACTION_INDEX_BACKFILLED";
+    pub const SCHEMA_VERSION: &[u8] = b"SCHEMA_VERSION";
+    pub const STORAGE_TRIE_SHARDS: &[u8] = b"STORAGE_TRIE_SHARDS";
+    pub const COLD_STORAGE: &[u8] = b"COLD_STORAGE";
+}
+
+/// Returns the directory holding the Starknet databases.
+fn starknet_dir(db_config_dir: &Path) -> PathBuf {
+    db_config_dir.join("starknet")
 }
 
 /// Returns the Starknet database directory.
 pub fn starknet_database_dir(db_config_dir: &Path, db_path: &str) -> PathBuf {
-    db_config_dir.join("starknet").join(db_path)
+    starknet_dir(db_config_dir).join(db_path)
 }
 
 /// Deoxys client database backend singleton.
@@ -229,17 +433,39 @@ pub fn starknet_database_dir(db_config_dir: &Path, db_path: &str) -> PathBuf {
 /// * `messaging`: Stores Ethereum L1 messaging data.
 /// * `sierra_classes`: @antyro what is this for?
 /// * `l1_handler_paid_fee`: @antyro what is this for?
+/// * `class`: stores the block number at which each class was declared.
+/// * `class_artifact`: stores the compiled classes imported from another node.
+/// * `class_quarantine`: stores the classes whose compiled class hash does not match the declared
+///   one.
+/// * `receipt`: stores the transaction receipts, the ones of old blocks possibly in the cold
+///   storage.
+/// * `transaction`: indexes the transactions by hash and by position.
+/// * `contract_storage`: flat copy of the contract storage, by block.
+/// * `maintenance`: reports the size of the columns and compacts them.
 /// * `bonsai_contract`: Bezu-bonsai trie used to compute the contract root.
-/// * `bonsai_storage`: Bezu-bonsai trie used to compute the storage root for each contract.
+/// * `bonsai_storage`: Bezu-bonsai trie used to compute the storage root for each contract, which
+///   may be sharded across several RocksDB instances.
 /// * `bonsai_class`: Bezu-bonsai trie used to compute the class root.
+/// * `lock`: prevents multiple nodes from using the same data directory.
 pub struct DeoxysBackend {
     meta: Arc<MetaDb>,
     mapping: Arc<MappingDb>,
     da: Arc<DaDb>,
     l1_handler_paid_fee: Arc<L1HandlerTxFeeDb>,
+    class: Arc<ClassDb>,
+    class_artifact: Arc<ClassArtifactDb>,
+    class_quarantine: Arc<ClassQuarantineDb>,
+    receipt: Arc<ReceiptDb>,
+    transaction: Arc<TransactionDb>,
+    cold_storage: Arc<ColdStorageDb>,
+    contract_storage: Arc<ContractStorageDb>,
+    delivery: Arc<DeliveryDb>,
+    maintenance: Arc<MaintenanceDb>,
     bonsai_contract: RwLock<BonsaiStorage<BasicId, BonsaiDb<'static>, Pedersen>>,
     bonsai_storage: RwLock<BonsaiStorage<BasicId, BonsaiDb<'static>, Pedersen>>,
     bonsai_class: RwLock<BonsaiStorage<BasicId, BonsaiDb<'static>, Poseidon>>,
+    bonsai_config: BonsaiStorageConfig,
+    lock: DataDirLock,
 }
 
 // Singleton backing instance for `DeoxysBackend`
@@ -247,25 +473,111 @@ static BACKEND_SINGLETON: OnceLock<Arc<DeoxysBackend>> = OnceLock::new();
 
 static DB_SINGLETON: OnceLock<Arc<DB>> = OnceLock::new();
 
+// The shards of the contract storage tries, empty if they are not sharded
+static SHARDS_SINGLETON: OnceLock<Vec<DB>> = OnceLock::new();
+
+// The cold storage holding the receipts and trie logs of old blocks, if there is one
+static COLD_SINGLETON: OnceLock<Option<Arc<DB>>> = OnceLock::new();
+
+// The columns of the contract, contract storage and class tries
+const BONSAI_CONTRACT_COLUMNS: DatabaseKeyMapping = DatabaseKeyMapping {
+    flat: Column::BonsaiContractsFlat,
+    trie: Column::BonsaiContractsTrie,
+    trie_log: Column::BonsaiContractsLog,
+};
+const BONSAI_STORAGE_COLUMNS: DatabaseKeyMapping = DatabaseKeyMapping {
+    flat: Column::BonsaiContractsStorageFlat,
+    trie: Column::BonsaiContractsStorageTrie,
+    trie_log: Column::BonsaiContractsStorageLog,
+};
+const BONSAI_CLASS_COLUMNS: DatabaseKeyMapping = DatabaseKeyMapping {
+    flat: Column::BonsaiClassesFlat,
+    trie: Column::BonsaiClassesTrie,
+    trie_log: Column::BonsaiClassesLog,
+};
+
+/// Opens the database of the bonsai storage held in `columns`, over the opened RocksDB instances.
+/// Only the contract storage tries are sharded.
+fn open_bonsai_db(columns: DatabaseKeyMapping) -> BonsaiDb<'static> {
+    let db = DB_SINGLETON.get().expect("Database not opened");
+    let shards = match columns.trie {
+        Column::BonsaiContractsStorageTrie => SHARDS_SINGLETON.get().map_or(&[][..], Vec::as_slice),
+        _ => &[],
+    };
+    let cold = COLD_SINGLETON.get().and_then(Option::as_deref);
+    BonsaiDb::new(BonsaiInstances::new(db, shards, cold), columns)
+}
+
 impl DeoxysBackend {
     /// Initializes a local database, returning a singleton backend instance.
     ///
     /// This backend should only be used to pass to substrate functions. Use the static functions
     /// defined below to access static fields instead.
+    ///
+    /// Fails if the data directory is already in use by another process. If the database was not
+    /// closed properly the last time it was used, fast consistency checks are run before it is
+    /// returned.
+    ///
+    /// `storage_trie_shards` is the number of RocksDB instances the contract storage tries are
+    /// sharded across. It can only be chosen when the database is created, and defaults to the
+    /// number it was created with.
+    ///
+    /// `cold_storage` is the directory the receipts and trie logs of old blocks are moved to, in a
+    /// separate RocksDB instance, see [`ColdStorageDb::move_blocks`]. Once blocks were moved, it
+    /// must be set every time the database is opened.
+    ///
+    /// `tuning` is the tuning of all the RocksDB instances of the database.
     pub fn open(
         database: &DatabaseSource,
         db_config_dir: &Path,
         cache_more_things: bool,
+        storage_trie_shards: Option<usize>,
+        cold_storage: Option<PathBuf>,
+        tuning: DbTuning,
     ) -> Result<&'static Arc<DeoxysBackend>> {
         BACKEND_SINGLETON
-            .set(Arc::new(Self::init(database, db_config_dir, cache_more_things).unwrap()))
+            .set(Arc::new(Self::init(
+                database,
+                db_config_dir,
+                cache_more_things,
+                storage_trie_shards,
+                cold_storage,
+                tuning,
+            )?))
             .ok()
             .context("Backend already initialized")?;
 
         Ok(BACKEND_SINGLETON.get().unwrap())
     }
 
-    fn init(database: &DatabaseSource, db_config_dir: &Path, cache_more_things: bool) -> Result<Self> {
+    /// Opens the backend in a temporary directory, once per process, for the tests of the crates
+    /// depending on this one. The directory is left behind, the backend being never closed.
+    #[cfg(feature = "testing")]
+    pub fn open_for_testing() -> &'static Arc<DeoxysBackend> {
+        static OPENED: OnceLock<()> = OnceLock::new();
+
+        OPENED.get_or_init(|| {
+            let dir = std::env::temp_dir().join(format!("deoxys-backend-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            let database = DatabaseSource::RocksDb { path: dir.clone(), cache_size: 0 };
+            Self::open(&database, &dir, false, None, None, DbTuning::default()).expect("failed to open the backend");
+        });
+        BACKEND_SINGLETON.get().expect("backend opened")
+    }
+
+    fn init(
+        database: &DatabaseSource,
+        db_config_dir: &Path,
+        cache_more_things: bool,
+        storage_trie_shards: Option<usize>,
+        cold_storage: Option<PathBuf>,
+        tuning: DbTuning,
+    ) -> Result<Self> {
+        let lock = DataDirLock::acquire(&starknet_dir(db_config_dir))?;
+        if lock.was_dirty() {
+            log::warn!("⚠️ The database was not closed properly, running consistency checks");
+        }
+
         Self::new(
             &DatabaseSettings {
                 source: match database {
@@ -285,69 +597,99 @@ impl DeoxysBackend {
                 max_saved_trie_logs: None,
                 max_saved_snapshots: None,
                 snapshot_interval: 100,
+                dirty: lock.was_dirty(),
+                storage_trie_shards,
+                cold_storage,
+                tuning,
             },
+            &starknet_database_dir(db_config_dir, "storage-shards"),
             cache_more_things,
+            lock,
         )
     }
 
-    fn new(config: &DatabaseSettings, cache_more_things: bool) -> Result<Self> {
+    fn new(config: &DatabaseSettings, shards_dir: &Path, cache_more_things: bool, lock: DataDirLock) -> Result<Self> {
         DB_SINGLETON.set(Arc::new(open_database(config)?)).unwrap();
         let db = DB_SINGLETON.get().unwrap();
+
+        if config.dirty {
+            consistency::fast_check(db)
+                .context("The database is corrupted following an unclean shutdown, please remove it and sync again")?;
+            log::info!("✅ Database consistency checks passed");
+        }
+
+        migration::migrate(db)?;
+
+        let shard_count = shards::storage_trie_shards(db, config.storage_trie_shards)?;
+        let shards = shards::open_storage_trie_shards(shards_dir, shard_count, config.dirty, &config.tuning)?;
+        SHARDS_SINGLETON.set(shards).map_err(|_| anyhow::anyhow!("Storage trie shards already opened"))?;
+        if shard_count > 0 {
+            log::info!("🗃️ Contract storage tries sharded across {shard_count} databases");
+        }
+
+        let cold = cold::open_cold_storage(db, config.cold_storage.as_deref(), config.dirty, &config.tuning)?;
+        COLD_SINGLETON.set(cold.map(Arc::new)).map_err(|_| anyhow::anyhow!("Cold storage already opened"))?;
+        let cold_storage = COLD_SINGLETON.get().unwrap().clone();
+
         let bonsai_config = BonsaiStorageConfig::from(config);
 
-        let mut bonsai_contract = BonsaiStorage::new(
-            BonsaiDb::new(
-                db,
-                DatabaseKeyMapping {
-                    flat: Column::BonsaiContractsFlat,
-                    trie: Column::BonsaiContractsTrie,
-                    trie_log: Column::BonsaiContractsLog,
-                },
-            ),
-            bonsai_config.clone(),
-        )
-        .unwrap();
+        let mut bonsai_contract =
+            BonsaiStorage::new(open_bonsai_db(BONSAI_CONTRACT_COLUMNS), bonsai_config.clone()).unwrap();
         bonsai_contract.commit(BasicId::new(0)).unwrap();
 
-        let mut bonsai_contract_storage = BonsaiStorage::new(
-            BonsaiDb::new(
-                db,
-                DatabaseKeyMapping {
-                    flat: Column::BonsaiContractsStorageFlat,
-                    trie: Column::BonsaiContractsStorageTrie,
-                    trie_log: Column::BonsaiContractsStorageLog,
-                },
-            ),
-            bonsai_config.clone(),
-        )
-        .unwrap();
+        let mut bonsai_contract_storage =
+            BonsaiStorage::new(open_bonsai_db(BONSAI_STORAGE_COLUMNS), bonsai_config.clone()).unwrap();
         bonsai_contract_storage.commit(BasicId::new(0)).unwrap();
 
-        let mut bonsai_classes = BonsaiStorage::new(
-            BonsaiDb::new(
-                db,
-                DatabaseKeyMapping {
-                    flat: Column::BonsaiClassesFlat,
-                    trie: Column::BonsaiClassesTrie,
-                    trie_log: Column::BonsaiClassesLog,
-                },
-            ),
-            bonsai_config.clone(),
-        )
-        .unwrap();
+        let mut bonsai_classes =
+            BonsaiStorage::new(open_bonsai_db(BONSAI_CLASS_COLUMNS), bonsai_config.clone()).unwrap();
         bonsai_classes.commit(BasicId::new(0)).unwrap();
 
+        let transaction = Arc::new(TransactionDb::new(Arc::clone(db)));
         Ok(Self {
             mapping: Arc::new(MappingDb::new(Arc::clone(db), cache_more_things)),
             meta: Arc::new(MetaDb::new(Arc::clone(db))),
             da: Arc::new(DaDb::new(Arc::clone(db))),
             l1_handler_paid_fee: Arc::new(L1HandlerTxFeeDb::new(Arc::clone(db))),
+            class: Arc::new(ClassDb::new(Arc::clone(db))),
+            class_artifact: Arc::new(ClassArtifactDb::new(Arc::clone(db))),
+            class_quarantine: Arc::new(ClassQuarantineDb::new(Arc::clone(db))),
+            receipt: Arc::new(ReceiptDb::new(Arc::clone(db), cold_storage.clone())),
+            cold_storage: Arc::new(ColdStorageDb::new(Arc::clone(db), cold_storage.clone(), Arc::clone(&transaction))),
+            transaction,
+            contract_storage: Arc::new(ContractStorageDb::new(Arc::clone(db))),
+            delivery: Arc::new(DeliveryDb::new(Arc::clone(db))),
+            maintenance: Arc::new(MaintenanceDb::new(Arc::clone(db), cold_storage)),
             bonsai_contract: RwLock::new(bonsai_contract),
             bonsai_storage: RwLock::new(bonsai_contract_storage),
             bonsai_class: RwLock::new(bonsai_classes),
+            bonsai_config,
+            lock,
         })
     }
 
+    /// Flushes the database to disk and releases the data directory.
+    ///
+    /// This should be called once the node has stopped, so that the next startup does not consider
+    /// the shutdown as unclean. Does nothing if the backend was never opened.
+    pub fn close() -> Result<()> {
+        let (Some(backend), Some(db)) = (BACKEND_SINGLETON.get(), DB_SINGLETON.get()) else {
+            return Ok(());
+        };
+
+        db.flush_wal(true).context("Failed to flush the database write-ahead log")?;
+        db.flush().context("Failed to flush the database")?;
+        for shard in SHARDS_SINGLETON.get().into_iter().flatten() {
+            shard.flush_wal(true).context("Failed to flush a storage trie shard write-ahead log")?;
+            shard.flush().context("Failed to flush a storage trie shard")?;
+        }
+        if let Some(cold) = COLD_SINGLETON.get().and_then(Option::as_ref) {
+            cold.flush_wal(true).context("Failed to flush the cold storage write-ahead log")?;
+            cold.flush().context("Failed to flush the cold storage")?;
+        }
+        backend.lock.release()
+    }
+
     /// Return the mapping database manager
     pub fn mapping() -> &'static Arc<MappingDb> {
         BACKEND_SINGLETON.get().map(|backend| &backend.mapping).expect("Backend not initialized")
@@ -380,6 +722,51 @@ impl DeoxysBackend {
         BACKEND_SINGLETON.get().map(|backend| &backend.l1_handler_paid_fee).expect("Backend not initialized")
     }
 
+    /// Return the class declarations database manager
+    pub fn class() -> &'static Arc<ClassDb> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.class).expect("Backend not initialized")
+    }
+
+    /// Return the imported class artifacts database manager
+    pub fn class_artifact() -> &'static Arc<ClassArtifactDb> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.class_artifact).expect("Backend not initialized")
+    }
+
+    /// Return the quarantined classes database manager
+    pub fn class_quarantine() -> &'static Arc<ClassQuarantineDb> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.class_quarantine).expect("Backend not initialized")
+    }
+
+    /// Return the transaction receipts database manager
+    pub fn receipt() -> &'static Arc<ReceiptDb> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.receipt).expect("Backend not initialized")
+    }
+
+    /// Return the transaction index database manager
+    pub fn transaction() -> &'static Arc<TransactionDb> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.transaction).expect("Backend not initialized")
+    }
+
+    /// Return the cold storage database manager
+    pub fn cold_storage() -> &'static Arc<ColdStorageDb> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.cold_storage).expect("Backend not initialized")
+    }
+
+    /// Return the flat contract storage database manager
+    pub fn contract_storage() -> &'static Arc<ContractStorageDb> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.contract_storage).expect("Backend not initialized")
+    }
+
+    /// Return the outbound delivery queues database manager
+    pub fn delivery() -> &'static Arc<DeliveryDb> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.delivery).expect("Backend not initialized")
+    }
+
+    /// Return the database maintenance manager
+    pub fn maintenance() -> &'static Arc<MaintenanceDb> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.maintenance).expect("Backend not initialized")
+    }
+
     /// In the future, we will compute the block global state root asynchronously in the client,
     /// using the Starknet-Bonzai-trie.
     /// That what replaces it for now :)
@@ -0,0 +1,138 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+/// Name of the file holding the PID of the process using the data directory.
+const LOCK_FILE: &str = "deoxys.lock";
+/// Name of the file marking that the database is in use and was not closed properly yet.
+const DIRTY_FILE: &str = "deoxys.dirty";
+
+/// Exclusive lock over a data directory.
+///
+/// The lock is a file containing the PID of the process owning the directory. A lock left behind
+/// by a process which is no longer running is considered stale and is taken over.
+///
+/// Alongside the lock, a dirty-shutdown marker is created when the database is opened and only
+/// removed by [`DataDirLock::release`]. Finding the marker on startup means that the previous
+/// process did not exit cleanly.
+pub struct DataDirLock {
+    dir: PathBuf,
+    /// Whether the previous process using the directory exited without releasing it.
+    dirty: bool,
+}
+
+impl DataDirLock {
+    /// Acquires the lock over `dir`, creating the directory if needed.
+    ///
+    /// Fails if the lock is held by another running process.
+    pub fn acquire(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create data directory {}", dir.display()))?;
+
+        let lock_path = dir.join(LOCK_FILE);
+        let pid = std::process::id();
+
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                let owner = fs::read_to_string(&lock_path).ok().and_then(|raw| raw.trim().parse::<u32>().ok());
+                match owner {
+                    Some(owner) if owner != pid && is_process_running(owner) => bail!(
+                        "Data directory {} is already in use by process {owner}, refusing to start a second node on it",
+                        dir.display()
+                    ),
+                    _ => {
+                        log::warn!("⚠️ Removing stale lock file {} (owner: {owner:?})", lock_path.display());
+                        fs::remove_file(&lock_path)
+                            .with_context(|| format!("Failed to remove stale lock file {}", lock_path.display()))?;
+                        OpenOptions::new()
+                            .write(true)
+                            .create_new(true)
+                            .open(&lock_path)
+                            .with_context(|| format!("Failed to create lock file {}", lock_path.display()))?
+                    }
+                }
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to create lock file {}", lock_path.display())),
+        };
+        write!(file, "{pid}").and_then(|_| file.sync_all()).context("Failed to write lock file")?;
+
+        let dirty_path = dir.join(DIRTY_FILE);
+        let dirty = dirty_path.exists();
+        fs::write(&dirty_path, pid.to_string())
+            .with_context(|| format!("Failed to create dirty-shutdown marker {}", dirty_path.display()))?;
+
+        Ok(Self { dir: dir.to_path_buf(), dirty })
+    }
+
+    /// Whether the previous process using the directory did not shut down cleanly.
+    pub fn was_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Marks the shutdown as clean and releases the lock.
+    ///
+    /// This must only be called once all writes to the database have been flushed.
+    pub fn release(&self) -> Result<()> {
+        fs::remove_file(self.dir.join(DIRTY_FILE)).context("Failed to remove dirty-shutdown marker")?;
+        fs::remove_file(self.dir.join(LOCK_FILE)).context("Failed to remove lock file")?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_process_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn is_process_running(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(true)
+}
+
+#[cfg(not(unix))]
+fn is_process_running(_pid: u32) -> bool {
+    // we have no portable way to check this, so better safe than sorry
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_dir;
+
+    #[test]
+    #[cfg(unix)]
+    fn lock_held_by_running_process_is_refused() {
+        let dir = test_dir("lock-held");
+        fs::create_dir_all(dir.path()).unwrap();
+        // the parent of the test process is still running
+        fs::write(dir.path().join(LOCK_FILE), std::os::unix::process::parent_id().to_string()).unwrap();
+
+        assert!(DataDirLock::acquire(dir.path()).is_err());
+    }
+
+    #[test]
+    fn dirty_shutdown_is_detected() {
+        let dir = test_dir("lock-dirty");
+
+        let lock = DataDirLock::acquire(dir.path()).unwrap();
+        assert!(!lock.was_dirty());
+        // simulate a crash: the lock is never released and becomes stale
+        fs::write(dir.path().join(LOCK_FILE), u32::MAX.to_string()).unwrap();
+
+        let lock = DataDirLock::acquire(dir.path()).unwrap();
+        assert!(lock.was_dirty());
+        lock.release().unwrap();
+
+        let lock = DataDirLock::acquire(dir.path()).unwrap();
+        assert!(!lock.was_dirty());
+        lock.release().unwrap();
+    }
+}
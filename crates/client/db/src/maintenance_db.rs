@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use crate::cold::is_cold;
+use crate::{Column, DatabaseExt, DbError, DB};
+
+/// Size of a column, as estimated by RocksDB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnStats {
+    pub column: Column,
+    /// Size of the live data, excluding the overwritten and deleted values not compacted yet.
+    pub live_data_size: u64,
+    /// Size of the SST files on disk.
+    pub sst_files_size: u64,
+    pub estimated_keys: u64,
+}
+
+/// Allow inspecting and compacting the columns of the database
+pub struct MaintenanceDb {
+    pub(crate) db: Arc<DB>,
+    /// The cold storage, if there is one.
+    pub(crate) cold: Option<Arc<DB>>,
+}
+
+impl MaintenanceDb {
+    pub(crate) fn new(db: Arc<DB>, cold: Option<Arc<DB>>) -> Self {
+        Self { db, cold }
+    }
+
+    /// The database instances holding `column`, which may be split between the primary database
+    /// and the cold storage.
+    fn instances(&self, column: Column) -> impl Iterator<Item = &DB> {
+        let cold = self.cold.as_deref().filter(|_| is_cold(column));
+        std::iter::once(self.db.as_ref()).chain(cold)
+    }
+
+    /// Return the estimated size of every column
+    pub fn column_stats(&self) -> Result<Vec<ColumnStats>, DbError> {
+        Column::ALL
+            .iter()
+            .map(|&column| {
+                let property = |name: &str| -> Result<u64, DbError> {
+                    let mut total = 0;
+                    for db in self.instances(column) {
+                        total += db.property_int_value_cf(&db.get_column(column), name)?.unwrap_or_default();
+                    }
+                    Ok(total)
+                };
+
+                Ok(ColumnStats {
+                    column,
+                    live_data_size: property("rocksdb.estimate-live-data-size")?,
+                    sst_files_size: property("rocksdb.total-sst-files-size")?,
+                    estimated_keys: property("rocksdb.estimate-num-keys")?,
+                })
+            })
+            .collect()
+    }
+
+    /// Compact the given columns, blocking until done
+    ///
+    /// Compaction rewrites the whole column and can take hours on the largest ones.
+    pub fn compact(&self, columns: &[Column]) {
+        for &column in columns {
+            log::info!("🗜️ Compacting column {column}");
+            for db in self.instances(column) {
+                db.compact_range_cf(&db.get_column(column), None::<&[u8]>, None::<&[u8]>);
+            }
+            log::info!("🗜️ Compacted column {column}");
+        }
+    }
+}
@@ -54,6 +54,19 @@ impl MappingDb {
         }
     }
 
+    /// Return the number of the Starknet block with given hash
+    ///
+    /// The number is indexed when the block is imported. The block at that number in the local
+    /// chain may not be this one after a reorg, so the result has to be checked against it.
+    pub fn block_number(&self, starknet_block_hash: StarkHash) -> Result<Option<u64>, DbError> {
+        let block_numbers_col = self.db.get_column(Column::BlockNumbers);
+
+        match self.db.get_cf(&block_numbers_col, starknet_block_hash.encode())? {
+            Some(raw) => Ok(Some(u64::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
     /// Register that a Substrate block has been seen, without it containing a Starknet one
     pub fn write_none(&self, block_hash: DHashT) -> Result<(), DbError> {
         let synced_mapping_col = self.db.get_column(Column::SyncedMapping);
@@ -66,6 +79,7 @@ impl MappingDb {
     pub fn write_hashes(&self, commitment: MappingCommitment<DBlockT>) -> Result<(), DbError> {
         let synced_mapping_col = self.db.get_column(Column::SyncedMapping);
         let block_mapping_col = self.db.get_column(Column::BlockMapping);
+        let block_numbers_col = self.db.get_column(Column::BlockNumbers);
         let transaction_mapping_col = self.db.get_column(Column::TransactionMapping);
         let starknet_tx_hashes_col = self.db.get_column(Column::StarknetTransactionHashesCache);
         let starknet_block_hashes_col = self.db.get_column(Column::StarknetBlockHashesCache);
@@ -87,6 +101,11 @@ impl MappingDb {
         };
 
         transaction.put_cf(&block_mapping_col, &commitment.starknet_block_hash.encode(), &substrate_hashes.encode());
+        transaction.put_cf(
+            &block_numbers_col,
+            &commitment.starknet_block_hash.encode(),
+            &commitment.block_number.encode(),
+        );
 
         transaction.put_cf(&synced_mapping_col, &commitment.block_hash.encode(), &true.encode());
 
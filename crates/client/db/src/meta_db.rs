@@ -10,6 +10,9 @@ use crate::{Column, DatabaseExt, DbError, DB};
 ///
 /// The meta db store the tips of the synced chain.
 /// In case of forks, there can be multiple tips.
+///
+/// It also stores the last block whose content was verified against its header, blocks being
+/// served as soon as they are imported.
 pub struct MetaDb {
     pub(crate) db: Arc<DB>,
 }
@@ -36,4 +39,170 @@ impl MetaDb {
         self.db.put_cf(&column, crate::static_keys::CURRENT_SYNCING_TIPS, tips.encode())?;
         Ok(())
     }
+
+    /// Retrieve the last verified block, `None` if no block was verified yet
+    ///
+    /// Watermarks stored before the hash of the verified block was recorded along with its number
+    /// are ignored, so that the blocks are verified again.
+    pub fn verified_block(&self) -> Result<Option<VerifiedBlock>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf(&column, crate::static_keys::LAST_VERIFIED_BLOCK)? {
+            Some(raw) if raw.len() == std::mem::size_of::<u64>() => Ok(None),
+            Some(raw) => Ok(Some(VerifiedBlock::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store the last verified block
+    pub fn set_verified_block(&self, block: VerifiedBlock) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        self.db.put_cf(&column, crate::static_keys::LAST_VERIFIED_BLOCK, block.encode())?;
+        Ok(())
+    }
+
+    /// Forget the last verified block, so that all the blocks are verified again
+    pub fn clear_verified_block(&self) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        self.db.delete_cf(&column, crate::static_keys::LAST_VERIFIED_BLOCK)?;
+        Ok(())
+    }
+
+    /// Retrieve the highest trusted checkpoint the sync went through, `None` if it never trusted
+    /// a checkpoint
+    ///
+    /// The state diffs of the blocks below it were committed to the tries in batches, so that the
+    /// tries hold no state root for most of them.
+    pub fn trusted_checkpoint(&self) -> Result<Option<u64>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf(&column, crate::static_keys::TRUSTED_CHECKPOINT)? {
+            Some(raw) => Ok(Some(u64::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store the trusted checkpoint the sync goes through, if it is higher than the stored one
+    pub fn set_trusted_checkpoint(&self, block_number: u64) -> Result<(), DbError> {
+        if self.trusted_checkpoint()?.is_some_and(|stored| stored >= block_number) {
+            return Ok(());
+        }
+        let column = self.db.get_column(Column::Meta);
+
+        self.db.put_cf(&column, crate::static_keys::TRUSTED_CHECKPOINT, block_number.encode())?;
+        Ok(())
+    }
+}
+
+/// The last block whose content was verified against its header.
+///
+/// The blocks up to it are only verified as long as it is part of the local chain: after a reorg,
+/// the watermark is rewound to the last block shared by the verified and the new chains, see
+/// [`VerifiedBlock::common_ancestor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct VerifiedBlock {
+    pub number: u64,
+    /// The hash of the Substrate block wrapping the verified block.
+    pub hash: DHashT,
+}
+
+impl VerifiedBlock {
+    /// Whether the verified block is still the one at its height in the local chain, given the
+    /// hash of the block at each height in it.
+    pub fn is_canonical(&self, canonical_hash: impl Fn(u64) -> Option<DHashT>) -> bool {
+        canonical_hash(self.number) == Some(self.hash)
+    }
+
+    /// The last verified block which is part of the local chain, following the parents of the
+    /// verified block until one is found at its height in the local chain.
+    ///
+    /// Returns `None` if no verified block is part of the local chain anymore, or if the parent of
+    /// a replaced block can't be read: the verification then starts over.
+    pub fn common_ancestor(
+        self,
+        canonical_hash: impl Fn(u64) -> Option<DHashT>,
+        parent_hash: impl Fn(DHashT) -> Option<DHashT>,
+    ) -> Option<Self> {
+        let mut block = self;
+        while !block.is_canonical(&canonical_hash) {
+            block = Self { number: block.number.checked_sub(1)?, hash: parent_hash(block.hash)? };
+        }
+        Some(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::test_utils::test_db;
+
+    fn hash(n: u8) -> DHashT {
+        DHashT::repeat_byte(n)
+    }
+
+    #[test]
+    fn verified_blocks_are_stored_with_their_hash() {
+        let db = test_db("meta-db");
+        let meta = MetaDb::new(db.clone());
+
+        assert_eq!(meta.verified_block().unwrap(), None);
+        let verified = VerifiedBlock { number: 7, hash: hash(7) };
+        meta.set_verified_block(verified).unwrap();
+        assert_eq!(meta.verified_block().unwrap(), Some(verified));
+
+        // watermarks stored as a bare block number are verified again
+        let column = meta.db.get_column(Column::Meta);
+        meta.db.put_cf(&column, crate::static_keys::LAST_VERIFIED_BLOCK, 7u64.encode()).unwrap();
+        assert_eq!(meta.verified_block().unwrap(), None);
+
+        meta.set_verified_block(verified).unwrap();
+        meta.clear_verified_block().unwrap();
+        assert_eq!(meta.verified_block().unwrap(), None);
+    }
+
+    #[test]
+    fn only_the_highest_trusted_checkpoint_is_kept() {
+        let db = test_db("meta-db-checkpoint");
+        let meta = MetaDb::new(db.clone());
+
+        assert_eq!(meta.trusted_checkpoint().unwrap(), None);
+        meta.set_trusted_checkpoint(10).unwrap();
+        meta.set_trusted_checkpoint(5).unwrap();
+        assert_eq!(meta.trusted_checkpoint().unwrap(), Some(10));
+        meta.set_trusted_checkpoint(12).unwrap();
+        assert_eq!(meta.trusted_checkpoint().unwrap(), Some(12));
+    }
+
+    #[test]
+    fn reorgs_rewind_the_watermark_to_the_common_ancestor() {
+        // blocks 1 to 5 were verified, blocks 4 and 5 are replaced by a longer chain
+        let verified_chain: HashMap<DHashT, DHashT> = (1..=5).map(|n| (hash(n), hash(n - 1))).collect();
+        let canonical = |number: u64| match number {
+            0..=3 => Some(hash(number as u8)),
+            4..=6 => Some(hash(number as u8 + 100)),
+            _ => None,
+        };
+        let parent = |hash: DHashT| verified_chain.get(&hash).copied();
+
+        let verified = VerifiedBlock { number: 5, hash: hash(5) };
+        assert!(!verified.is_canonical(canonical));
+        assert_eq!(verified.common_ancestor(canonical, parent), Some(VerifiedBlock { number: 3, hash: hash(3) }));
+
+        // a reorg to the same height
+        let verified = VerifiedBlock { number: 4, hash: hash(4) };
+        assert_eq!(verified.common_ancestor(canonical, parent), Some(VerifiedBlock { number: 3, hash: hash(3) }));
+
+        // the chain is only extended
+        let verified = VerifiedBlock { number: 3, hash: hash(3) };
+        assert!(verified.is_canonical(canonical));
+        assert_eq!(verified.common_ancestor(canonical, parent), Some(verified));
+
+        // the parents of the replaced blocks are unknown, or no verified block is left
+        assert_eq!(VerifiedBlock { number: 5, hash: hash(5) }.common_ancestor(canonical, |_| None), None);
+        assert_eq!(VerifiedBlock { number: 1, hash: hash(50) }.common_ancestor(canonical, |_| Some(hash(51))), None);
+    }
 }
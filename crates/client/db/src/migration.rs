@@ -0,0 +1,477 @@
+//! In-place upgrades of existing databases.
+//!
+//! The version of the database layout is stored in the meta column. Whenever a change would make
+//! existing databases unreadable or incomplete (a new index, values encoded differently...), the
+//! change bumps [`SCHEMA_VERSION`] and adds a migration to [`MIGRATIONS`], which upgrades a
+//! database from the previous version. Migrations are run in order when the database is opened,
+//! and the version is saved after each one, so that an interrupted upgrade resumes where it
+//! stopped.
+//!
+//! Migrations must be idempotent: the node may stop after a migration is run but before the new
+//! version is saved.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use anyhow::{bail, Context, Result};
+use mp_types::block::DHashT;
+use parity_scale_codec::{Decode, DecodeAll, Encode};
+use rocksdb::{IteratorMode, WriteBatchWithTransaction};
+use starknet_api::core::{ClassHash, CompiledClassHash, ContractAddress, PatriciaKey};
+use starknet_api::hash::StarkFelt;
+
+use crate::class_db::{by_block_key, ClassDeclaration};
+use crate::class_quarantine_db::{QuarantineReason, QuarantineResolution, QuarantinedClass};
+use crate::transaction_db::TransactionLocation;
+use crate::{Column, DatabaseExt, DB};
+
+/// Version of the database layout written by this version of the node.
+pub const SCHEMA_VERSION: u32 = 7;
+
+/// Number of entries written at once by migrations going through whole columns.
+const MIGRATION_BATCH_SIZE: usize = 10_000;
+
+struct Migration {
+    /// The version the database is at once the migration has run.
+    version: u32,
+    description: &'static str,
+    run: fn(&DB) -> Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "index the class declarations by block",
+        run: index_class_declarations_by_block,
+    },
+    Migration { version: 2, description: "index the block numbers by block hash", run: index_block_numbers },
+    Migration {
+        version: 3,
+        description: "backfill the flat storage again to record the nonces and class hashes",
+        run: restart_flat_storage_backfill,
+    },
+    Migration { version: 4, description: "record why the quarantined classes are", run: add_quarantine_reasons },
+    Migration {
+        version: 5,
+        description: "index the transactions of the blocks synced before the transaction index existed",
+        run: restart_transaction_index_backfill,
+    },
+    Migration {
+        version: 6,
+        description: "backfill the transaction index again to record the position of the events",
+        run: restart_transaction_index_backfill,
+    },
+    Migration {
+        version: 7,
+        description: "record the contracts modified by the blocks stored in the flat storage",
+        run: record_modified_contracts,
+    },
+];
+
+/// Upgrades the database to [`SCHEMA_VERSION`].
+///
+/// New databases are created at the latest version. Databases created before versioning was
+/// introduced are considered to be at version 0. Fails if the database was written by a newer
+/// version of the node, as its layout is unknown.
+pub(crate) fn migrate(db: &DB) -> Result<()> {
+    let version = match stored_version(db)? {
+        Some(version) => version,
+        None if is_empty(db)? => {
+            return save_version(db, SCHEMA_VERSION);
+        }
+        None => 0,
+    };
+
+    if version > SCHEMA_VERSION {
+        bail!(
+            "The database schema version is {version}, but this node only supports up to version {SCHEMA_VERSION}. \
+             Please upgrade the node."
+        );
+    }
+
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > version) {
+        log::info!("🔁 Migrating the database to version {}: {}", migration.version, migration.description);
+        (migration.run)(db)
+            .with_context(|| format!("Failed to migrate the database to version {}", migration.version))?;
+        save_version(db, migration.version)?;
+    }
+
+    Ok(())
+}
+
+fn stored_version(db: &DB) -> Result<Option<u32>> {
+    let column = db.get_column(Column::Meta);
+
+    match db.get_cf(&column, crate::static_keys::SCHEMA_VERSION)? {
+        Some(raw) => Ok(Some(u32::decode(&mut &raw[..]).context("Failed to decode the database schema version")?)),
+        None => Ok(None),
+    }
+}
+
+fn save_version(db: &DB, version: u32) -> Result<()> {
+    let column = db.get_column(Column::Meta);
+
+    db.put_cf(&column, crate::static_keys::SCHEMA_VERSION, version.encode())?;
+    Ok(())
+}
+
+/// Whether no block was ever stored in the database.
+fn is_empty(db: &DB) -> Result<bool> {
+    let column = db.get_column(Column::BlockMapping);
+
+    Ok(db.iterator_cf(&column, IteratorMode::Start).next().transpose()?.is_none())
+}
+
+/// Version 1: class declarations are also indexed by block.
+///
+/// The compiled class hashes and sizes of the classes declared before the index existed are not
+/// known, and are left unset.
+fn index_class_declarations_by_block(db: &DB) -> Result<()> {
+    let column = db.get_column(Column::ClassDeclarations);
+    let column_by_block = db.get_column(Column::ClassDeclarationsByBlock);
+
+    let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+    for kv in db.iterator_cf(&column, IteratorMode::Start) {
+        let (key, value) = kv?;
+        let class_hash = ClassHash::decode(&mut &key[..])?;
+        let block_number = u64::decode(&mut &value[..])?;
+
+        let declaration = ClassDeclaration { class_hash, compiled_class_hash: None, size: None };
+        transaction.put_cf(&column_by_block, by_block_key(block_number, &class_hash), declaration.encode());
+
+        if transaction.len() >= MIGRATION_BATCH_SIZE {
+            db.write(std::mem::take(&mut transaction))?;
+        }
+    }
+
+    db.write(transaction)?;
+    Ok(())
+}
+
+/// Version 2: block numbers are indexed by Starknet block hash.
+///
+/// The numbers of the blocks are recovered from the block hashes cache, for nodes which ran with
+/// `--cache`, and from the location of their transactions. Blocks left out of the index are still
+/// found through the block mapping.
+fn index_block_numbers(db: &DB) -> Result<()> {
+    let column = db.get_column(Column::BlockNumbers);
+
+    let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+    for kv in db.iterator_cf(&db.get_column(Column::StarknetBlockHashesCache), IteratorMode::Start) {
+        // the cache maps the encoded block numbers to the encoded block hashes
+        let (block_number, block_hash) = kv?;
+        transaction.put_cf(&column, block_hash, block_number);
+
+        if transaction.len() >= MIGRATION_BATCH_SIZE {
+            db.write(std::mem::take(&mut transaction))?;
+        }
+    }
+
+    let column_locations = db.get_column(Column::TransactionLocations);
+    let mut block_numbers = HashMap::new();
+    for kv in db.iterator_cf(&db.get_column(Column::TransactionMapping), IteratorMode::Start) {
+        let (transaction_hash, substrate_hash) = kv?;
+        let substrate_hash = DHashT::decode(&mut &substrate_hash[..])?;
+        if block_numbers.contains_key(&substrate_hash) {
+            continue;
+        }
+        if let Some(location) = db.get_cf(&column_locations, transaction_hash)? {
+            block_numbers.insert(substrate_hash, TransactionLocation::decode(&mut &location[..])?.block_number);
+        }
+    }
+
+    for kv in db.iterator_cf(&db.get_column(Column::BlockMapping), IteratorMode::Start) {
+        let (block_hash, substrate_hashes) = kv?;
+        let block_number = Vec::<DHashT>::decode(&mut &substrate_hashes[..])?
+            .iter()
+            .find_map(|substrate_hash| block_numbers.get(substrate_hash).copied());
+        if let Some(block_number) = block_number {
+            transaction.put_cf(&column, block_hash, block_number.encode());
+        }
+
+        if transaction.len() >= MIGRATION_BATCH_SIZE {
+            db.write(std::mem::take(&mut transaction))?;
+        }
+    }
+
+    db.write(transaction)?;
+    Ok(())
+}
+
+/// Version 3: the flat storage also records the nonces and class hashes of the contracts.
+///
+/// Forgetting where the flat storage started has the sync mark the next block it imports as the
+/// start, and the backfill fetch all the blocks before it again, storing their storage updates
+/// once more along with their nonces and class hashes.
+fn restart_flat_storage_backfill(db: &DB) -> Result<()> {
+    let column = db.get_column(Column::Meta);
+
+    let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+    transaction.delete_cf(&column, crate::static_keys::FLAT_STORAGE_LIVE_FROM);
+    transaction.delete_cf(&column, crate::static_keys::FLAT_STORAGE_BACKFILLED);
+
+    db.write(transaction)?;
+    Ok(())
+}
+
+/// A quarantine report as stored up to version 3, when only compiled class hash mismatches were
+/// quarantined.
+#[derive(Encode, Decode)]
+struct QuarantinedClassV3 {
+    class_hash: ClassHash,
+    block_number: u64,
+    declared_compiled_class_hash: CompiledClassHash,
+    computed_compiled_class_hash: CompiledClassHash,
+    compiler_version: String,
+    resolution: QuarantineResolution,
+}
+
+/// Version 4: the quarantine reports record why the classes were quarantined.
+///
+/// Reports which do not decode as version 3 ones were already migrated.
+fn add_quarantine_reasons(db: &DB) -> Result<()> {
+    let column = db.get_column(Column::ClassQuarantine);
+
+    let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+    for kv in db.iterator_cf(&column, IteratorMode::Start) {
+        let (key, value) = kv?;
+        let Ok(report) = QuarantinedClassV3::decode_all(&mut &value[..]) else {
+            continue;
+        };
+
+        let report = QuarantinedClass {
+            class_hash: report.class_hash,
+            block_number: report.block_number,
+            reason: QuarantineReason::CompiledClassHash {
+                declared: report.declared_compiled_class_hash,
+                computed: report.computed_compiled_class_hash,
+                compiler_version: report.compiler_version,
+            },
+            resolution: report.resolution,
+        };
+        transaction.put_cf(&column, key, report.encode());
+    }
+
+    db.write(transaction)?;
+    Ok(())
+}
+
+/// Versions 5 and 6: transactions are indexed by hash and by position, along with the position of
+/// their events.
+///
+/// Forgetting where the transaction index started has the sync mark the next block it imports as
+/// the start, and the backfill index the transactions of all the blocks before it, and their
+/// events, from the local chain.
+fn restart_transaction_index_backfill(db: &DB) -> Result<()> {
+    let column = db.get_column(Column::Meta);
+
+    let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+    transaction.delete_cf(&column, crate::static_keys::TRANSACTION_INDEX_LIVE_FROM);
+    transaction.delete_cf(&column, crate::static_keys::TRANSACTION_INDEX_BACKFILLED);
+
+    db.write(transaction)?;
+    Ok(())
+}
+
+/// Version 7: the contracts modified by each block are recorded.
+///
+/// They are derived from the storage, nonce and class hash updates of the flat storage, whose keys
+/// start with the contract address and end with the block number. The blocks which are yet to be
+/// backfilled in the flat storage get their modified contracts recorded by the backfill.
+fn record_modified_contracts(db: &DB) -> Result<()> {
+    // the length of the keys of the updates, the block number is in the last 8 bytes
+    let columns = [(Column::ContractStorage, 72), (Column::ContractNonces, 40), (Column::ContractClassHashes, 40)];
+
+    let mut modified: BTreeMap<u64, BTreeSet<ContractAddress>> = BTreeMap::new();
+    let mut pending = 0;
+    for (column, key_len) in columns {
+        for kv in db.iterator_cf(&db.get_column(column), IteratorMode::Start) {
+            let (key, _) = kv?;
+            if key.len() != key_len {
+                continue;
+            }
+            let address = StarkFelt::new(key[..32].try_into()?)?;
+            let block_number = u64::from_be_bytes(key[key_len - 8..].try_into()?);
+            modified.entry(block_number).or_default().insert(ContractAddress(PatriciaKey(address)));
+
+            pending += 1;
+            if pending >= MIGRATION_BATCH_SIZE {
+                merge_modified_contracts(db, std::mem::take(&mut modified))?;
+                pending = 0;
+            }
+        }
+    }
+    merge_modified_contracts(db, modified)?;
+
+    let meta = db.get_column(Column::Meta);
+    db.put_cf(&meta, crate::static_keys::MODIFIED_CONTRACTS_FROM, 0u64.encode())?;
+    Ok(())
+}
+
+/// Adds contracts to the ones recorded as modified by their block.
+fn merge_modified_contracts(db: &DB, modified: BTreeMap<u64, BTreeSet<ContractAddress>>) -> Result<()> {
+    let column = db.get_column(Column::ModifiedContracts);
+
+    let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+    for (block_number, mut contracts) in modified {
+        if let Some(raw) = db.get_cf(&column, block_number.to_be_bytes())? {
+            contracts.extend(Vec::<ContractAddress>::decode(&mut &raw[..])?);
+        }
+        transaction.put_cf(&column, block_number.to_be_bytes(), contracts.into_iter().collect::<Vec<_>>().encode());
+    }
+
+    db.write(transaction)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_db;
+
+    #[test]
+    fn new_databases_start_at_the_latest_version() {
+        let db = test_db("migration-new");
+
+        migrate(&db).unwrap();
+        assert_eq!(stored_version(&db).unwrap(), Some(SCHEMA_VERSION));
+
+        save_version(&db, SCHEMA_VERSION + 1).unwrap();
+        assert!(migrate(&db).is_err());
+    }
+
+    #[test]
+    fn unversioned_databases_are_migrated() {
+        let db = test_db("migration-unversioned");
+        let class_hash = ClassHash(StarkFelt::from(1u128));
+        db.put_cf(&db.get_column(Column::BlockMapping), b"block", Vec::<DHashT>::new().encode()).unwrap();
+        db.put_cf(&db.get_column(Column::ClassDeclarations), class_hash.encode(), 7u64.encode()).unwrap();
+
+        migrate(&db).unwrap();
+        assert_eq!(stored_version(&db).unwrap(), Some(SCHEMA_VERSION));
+        let indexed =
+            db.get_cf(&db.get_column(Column::ClassDeclarationsByBlock), by_block_key(7, &class_hash)).unwrap();
+        assert_eq!(
+            ClassDeclaration::decode(&mut &indexed.unwrap()[..]).unwrap(),
+            ClassDeclaration { class_hash, compiled_class_hash: None, size: None }
+        );
+    }
+
+    #[test]
+    fn block_numbers_are_indexed() {
+        let db = test_db("migration-block-numbers");
+        let (cached_hash, mapped_hash) = (StarkFelt::from(1u128), StarkFelt::from(2u128));
+        let substrate_hash = DHashT::repeat_byte(3);
+        let transaction_hash = StarkFelt::from(4u128);
+        db.put_cf(&db.get_column(Column::StarknetBlockHashesCache), 9u64.encode(), cached_hash.encode()).unwrap();
+        db.put_cf(&db.get_column(Column::BlockMapping), mapped_hash.encode(), vec![substrate_hash].encode()).unwrap();
+        db.put_cf(&db.get_column(Column::TransactionMapping), transaction_hash.encode(), substrate_hash.encode())
+            .unwrap();
+        db.put_cf(
+            &db.get_column(Column::TransactionLocations),
+            transaction_hash.encode(),
+            TransactionLocation { block_number: 5, index: 0 }.encode(),
+        )
+        .unwrap();
+
+        migrate(&db).unwrap();
+        let block_number = |hash: StarkFelt| {
+            db.get_cf(&db.get_column(Column::BlockNumbers), hash.encode())
+                .unwrap()
+                .map(|raw| u64::decode(&mut &raw[..]).unwrap())
+        };
+        assert_eq!(block_number(cached_hash), Some(9));
+        assert_eq!(block_number(mapped_hash), Some(5));
+    }
+
+    #[test]
+    fn flat_storage_backfill_is_restarted() {
+        let db = test_db("migration-flat-storage");
+        let meta = db.get_column(Column::Meta);
+        db.put_cf(&db.get_column(Column::BlockMapping), b"block", Vec::<DHashT>::new().encode()).unwrap();
+        save_version(&db, 2).unwrap();
+        db.put_cf(&meta, crate::static_keys::FLAT_STORAGE_LIVE_FROM, 100u64.encode()).unwrap();
+        db.put_cf(&meta, crate::static_keys::FLAT_STORAGE_BACKFILLED, 100u64.encode()).unwrap();
+
+        migrate(&db).unwrap();
+        assert_eq!(db.get_cf(&meta, crate::static_keys::FLAT_STORAGE_LIVE_FROM).unwrap(), None);
+        assert_eq!(db.get_cf(&meta, crate::static_keys::FLAT_STORAGE_BACKFILLED).unwrap(), None);
+    }
+
+    #[test]
+    fn quarantine_reasons_are_added() {
+        let db = test_db("migration-quarantine");
+        let column = db.get_column(Column::ClassQuarantine);
+        db.put_cf(&db.get_column(Column::BlockMapping), b"block", Vec::<DHashT>::new().encode()).unwrap();
+        save_version(&db, 3).unwrap();
+        let class_hash = ClassHash(StarkFelt::from(1u128));
+        let report = QuarantinedClassV3 {
+            class_hash,
+            block_number: 7,
+            declared_compiled_class_hash: CompiledClassHash(StarkFelt::from(2u128)),
+            computed_compiled_class_hash: CompiledClassHash(StarkFelt::from(3u128)),
+            compiler_version: "2.6.0".to_string(),
+            resolution: QuarantineResolution::GatewayCasm,
+        };
+        db.put_cf(&column, class_hash.encode(), report.encode()).unwrap();
+
+        let expected = QuarantinedClass {
+            class_hash,
+            block_number: 7,
+            reason: QuarantineReason::CompiledClassHash {
+                declared: CompiledClassHash(StarkFelt::from(2u128)),
+                computed: CompiledClassHash(StarkFelt::from(3u128)),
+                compiler_version: "2.6.0".to_string(),
+            },
+            resolution: QuarantineResolution::GatewayCasm,
+        };
+        migrate(&db).unwrap();
+        let migrated = db.get_cf(&column, class_hash.encode()).unwrap().unwrap();
+        assert_eq!(QuarantinedClass::decode(&mut &migrated[..]).unwrap(), expected);
+
+        // migrated reports are left as they are
+        add_quarantine_reasons(&db).unwrap();
+        let migrated = db.get_cf(&column, class_hash.encode()).unwrap().unwrap();
+        assert_eq!(QuarantinedClass::decode(&mut &migrated[..]).unwrap(), expected);
+    }
+
+    #[test]
+    fn transaction_index_backfill_is_restarted() {
+        let db = test_db("migration-transaction-index");
+        let meta = db.get_column(Column::Meta);
+        db.put_cf(&db.get_column(Column::BlockMapping), b"block", Vec::<DHashT>::new().encode()).unwrap();
+        save_version(&db, 5).unwrap();
+        db.put_cf(&meta, crate::static_keys::TRANSACTION_INDEX_LIVE_FROM, 100u64.encode()).unwrap();
+        db.put_cf(&meta, crate::static_keys::TRANSACTION_INDEX_BACKFILLED, 100u64.encode()).unwrap();
+
+        migrate(&db).unwrap();
+        assert_eq!(db.get_cf(&meta, crate::static_keys::TRANSACTION_INDEX_LIVE_FROM).unwrap(), None);
+        assert_eq!(db.get_cf(&meta, crate::static_keys::TRANSACTION_INDEX_BACKFILLED).unwrap(), None);
+    }
+
+    #[test]
+    fn modified_contracts_are_recorded() {
+        let db = test_db("migration-modified-contracts");
+        db.put_cf(&db.get_column(Column::BlockMapping), b"block", Vec::<DHashT>::new().encode()).unwrap();
+        save_version(&db, 6).unwrap();
+        let [first, second, third] =
+            [1u128, 2, 3].map(|address| ContractAddress(PatriciaKey(StarkFelt::from(address))));
+        let update_key = |address: &ContractAddress, slot: Option<u128>, block_number: u64| {
+            let slot = slot.map(|slot| StarkFelt::from(slot).bytes().to_vec()).unwrap_or_default();
+            [address.0.0.bytes(), slot.as_slice(), &block_number.to_be_bytes()].concat()
+        };
+        let value = StarkFelt::from(9u128).encode();
+        db.put_cf(&db.get_column(Column::ContractStorage), update_key(&second, Some(5), 3), &value).unwrap();
+        db.put_cf(&db.get_column(Column::ContractStorage), update_key(&second, Some(6), 3), &value).unwrap();
+        db.put_cf(&db.get_column(Column::ContractNonces), update_key(&first, None, 3), &value).unwrap();
+        db.put_cf(&db.get_column(Column::ContractClassHashes), update_key(&third, None, 4), &value).unwrap();
+
+        migrate(&db).unwrap();
+        let storage = crate::contract_storage_db::ContractStorageDb::new(db.clone());
+        assert_eq!(storage.modified_contracts(2).unwrap(), Some(vec![]));
+        assert_eq!(storage.modified_contracts(3).unwrap(), Some(vec![first, second]));
+        assert_eq!(storage.modified_contracts(4).unwrap(), Some(vec![third]));
+
+        // recording them again leaves them as they are
+        record_modified_contracts(&db).unwrap();
+        assert_eq!(storage.modified_contracts(3).unwrap(), Some(vec![first, second]));
+    }
+}
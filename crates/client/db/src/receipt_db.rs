@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use mp_block::receipt::TransactionReceiptWrapper;
+// Substrate
+use parity_scale_codec::{Decode, Encode};
+use rocksdb::WriteBatchWithTransaction;
+// Starknet
+use starknet_api::transaction::TransactionHash;
+
+use crate::{Column, DatabaseExt, DbError, DB};
+
+/// Allow interaction with the transaction receipts db
+///
+/// Receipts are stored as provided by the feeder gateway, so that they do not need to be
+/// reconstructed by re-executing the transactions. The receipts of old blocks may be moved to the
+/// cold storage, see [`crate::cold`], from which they are read back transparently.
+pub struct ReceiptDb {
+    pub(crate) db: Arc<DB>,
+    /// The cold storage, holding the receipts of old blocks, if there is one.
+    cold: Option<Arc<DB>>,
+}
+
+impl ReceiptDb {
+    pub(crate) fn new(db: Arc<DB>, cold: Option<Arc<DB>>) -> Self {
+        Self { db, cold }
+    }
+
+    /// Return the receipt of the transaction with the given hash
+    pub fn get(&self, transaction_hash: &TransactionHash) -> Result<Option<TransactionReceiptWrapper>, DbError> {
+        for db in std::iter::once(&self.db).chain(&self.cold) {
+            let column = db.get_column(Column::TransactionReceipts);
+            if let Some(raw) = db.get_cf(&column, transaction_hash.encode())? {
+                return Ok(Some(TransactionReceiptWrapper::decode(&mut &raw[..])?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Store the receipts of all the transactions in a block
+    pub fn store_receipts(&self, receipts: &[TransactionReceiptWrapper]) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::TransactionReceipts);
+
+        let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+        for receipt in receipts {
+            let transaction_hash = TransactionHash(receipt.transaction_hash.into());
+            transaction.put_cf(&column, transaction_hash.encode(), receipt.encode());
+        }
+
+        self.db.write(transaction)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mp_block::receipt::ExecutionResourcesWrapper;
+    use starknet_api::hash::StarkFelt;
+
+    use super::*;
+    use crate::test_utils::test_db;
+
+    fn receipt(transaction_hash: u128, revert_error: Option<&str>) -> TransactionReceiptWrapper {
+        TransactionReceiptWrapper {
+            transaction_hash: StarkFelt::from(transaction_hash).into(),
+            actual_fee: StarkFelt::from(10u128).into(),
+            revert_error: revert_error.map(str::to_string),
+            execution_resources: ExecutionResourcesWrapper::default(),
+            messages_sent: vec![],
+            events: vec![],
+        }
+    }
+
+    #[test]
+    fn revert_reasons_are_stored() {
+        let db = test_db("receipts");
+        let receipts = ReceiptDb::new(db.clone(), None);
+
+        let succeeded = receipt(1, None);
+        let reverted = receipt(2, Some("Error in the called contract"));
+        receipts.store_receipts(&[succeeded.clone(), reverted.clone()]).unwrap();
+
+        let hash = |receipt: &TransactionReceiptWrapper| TransactionHash(receipt.transaction_hash.into());
+        let stored = receipts.get(&hash(&succeeded)).unwrap().unwrap();
+        assert!(!stored.is_reverted());
+        let stored = receipts.get(&hash(&reverted)).unwrap().unwrap();
+        assert!(stored.is_reverted());
+        assert_eq!(stored.revert_error.as_deref(), Some("Error in the called contract"));
+    }
+}
@@ -0,0 +1,181 @@
+//! Sharding of the contract storage tries across several RocksDB instances.
+//!
+//! On large archive nodes, compacting the single RocksDB instance holding the whole state falls
+//! behind the writes of the sync. The contract storage tries, by far the largest part of the
+//! state, can instead be spread over several instances ("shards"), each compacted independently.
+//! Every shard holds the tries of a contiguous range of contract addresses, selected by the top
+//! bits of the address. The trie logs, used to revert the tries, are written to the primary
+//! instance, from which those of old blocks may be moved to the cold storage, see [`crate::cold`].
+//!
+//! The number of shards is chosen when the database is created, and can't be changed afterwards
+//! as the tries would be looked up in the wrong instance.
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use bonsai_trie::DatabaseKey;
+use parity_scale_codec::{Decode, Encode};
+use rocksdb::IteratorMode;
+
+use crate::{open_rocksdb_columns, Column, DatabaseExt, DbTuning, DB};
+
+/// Maximum number of shards of the contract storage tries.
+pub const MAX_STORAGE_TRIE_SHARDS: usize = 64;
+
+/// Length of the identifiers of the contract storage tries, which are contract addresses.
+const IDENTIFIER_LEN: usize = 32;
+
+/// Number of address prefixes shards are assigned from. Contract addresses are felts, whose 5 top
+/// bits are always unset, so shards are assigned on the 11 next ones.
+const PREFIX_RANGE: usize = 1 << 11;
+
+/// The columns of the contract storage tries held by the shards.
+const SHARD_COLUMNS: [Column; 2] = [Column::BonsaiContractsStorageTrie, Column::BonsaiContractsStorageFlat];
+
+/// Returns the number of shards of the contract storage tries.
+///
+/// `requested` is the number of shards asked for by the operator. It is saved when the database is
+/// created, and must then match the saved number. Existing databases whose storage tries are not
+/// empty can't be sharded.
+pub(crate) fn storage_trie_shards(db: &DB, requested: Option<usize>) -> Result<usize> {
+    let column = db.get_column(Column::Meta);
+
+    let stored = match db.get_cf(&column, crate::static_keys::STORAGE_TRIE_SHARDS)? {
+        Some(raw) => Some(u32::decode(&mut &raw[..]).context("Failed to decode the number of storage trie shards")?),
+        None => None,
+    };
+
+    match (stored.map(|stored| stored as usize), requested) {
+        (Some(stored), Some(requested)) if stored != requested => bail!(
+            "The database was created with {stored} contract storage trie shards, it can't be opened with \
+             {requested}. Please remove it and sync again to change the number of shards."
+        ),
+        (Some(stored), _) => Ok(stored),
+        (None, requested) => {
+            let requested = requested.unwrap_or(0);
+            if requested > MAX_STORAGE_TRIE_SHARDS {
+                bail!("At most {MAX_STORAGE_TRIE_SHARDS} contract storage trie shards are supported");
+            }
+            let trie = db.get_column(Column::BonsaiContractsStorageTrie);
+            if requested > 0 && db.iterator_cf(&trie, IteratorMode::Start).next().transpose()?.is_some() {
+                bail!(
+                    "The contract storage tries of an existing database can't be sharded. Please remove it and sync \
+                     again to use shards."
+                );
+            }
+
+            db.put_cf(&column, crate::static_keys::STORAGE_TRIE_SHARDS, (requested as u32).encode())?;
+            Ok(requested)
+        }
+    }
+}
+
+/// Opens the `count` shards of the contract storage tries, in subdirectories of `dir`.
+pub(crate) fn open_storage_trie_shards(dir: &Path, count: usize, dirty: bool, tuning: &DbTuning) -> Result<Vec<DB>> {
+    (0..count)
+        .map(|index| {
+            let path = dir.join(format!("shard-{index}"));
+            open_rocksdb_columns(&path, true, dirty, &SHARD_COLUMNS, tuning)
+                .with_context(|| format!("Failed to open the contract storage trie shard at {}", path.display()))
+        })
+        .collect()
+}
+
+/// The RocksDB instances holding a bonsai storage: the primary database, followed by the shards of
+/// the contract storage tries, if any, and by the cold storage holding the trie logs of old blocks,
+/// if any.
+#[derive(Clone, Copy)]
+pub(crate) struct BonsaiInstances<'db> {
+    primary: &'db DB,
+    shards: &'db [DB],
+    cold: Option<&'db DB>,
+}
+
+impl<'db> BonsaiInstances<'db> {
+    pub(crate) fn new(primary: &'db DB, shards: &'db [DB], cold: Option<&'db DB>) -> Self {
+        Self { primary, shards, cold }
+    }
+
+    /// Number of instances, including the primary database.
+    pub(crate) fn count(&self) -> usize {
+        1 + self.shards.len() + usize::from(self.cold.is_some())
+    }
+
+    /// The instance at `index`, the primary database being at index 0.
+    pub(crate) fn get(&self, index: usize) -> &'db DB {
+        match index {
+            0 => self.primary,
+            index if index <= self.shards.len() => &self.shards[index - 1],
+            _ => self.cold.expect("instance index out of bounds"),
+        }
+    }
+
+    /// Index of the cold storage, if there is one.
+    fn cold_index(&self) -> Option<usize> {
+        self.cold.map(|_| 1 + self.shards.len())
+    }
+
+    /// Pairs the per instance `items` with their index, in the order they are written: the shards
+    /// and the cold storage first, and the primary database, holding the trie logs, last.
+    pub(crate) fn in_write_order<T>(&self, items: Vec<T>) -> Vec<(usize, T)> {
+        let mut items: Vec<_> = items.into_iter().enumerate().collect();
+        items.sort_by_key(|(index, _)| (*index == 0, std::cmp::Reverse(*index)));
+        items
+    }
+
+    /// Index of the instance `key` is written to.
+    pub(crate) fn route(&self, key: &DatabaseKey) -> usize {
+        match key {
+            DatabaseKey::TrieLog(_) => 0,
+            _ if self.shards.is_empty() || key.as_slice().len() < IDENTIFIER_LEN => 0,
+            _ => 1 + shard_of(key.as_slice(), self.shards.len()),
+        }
+    }
+
+    /// Index of the instance `key` is looked up in when it is not found in the one it is written
+    /// to: the cold storage for the trie logs of old blocks.
+    pub(crate) fn fallback(&self, key: &DatabaseKey) -> Option<usize> {
+        match key {
+            DatabaseKey::TrieLog(_) => self.cold_index(),
+            _ => None,
+        }
+    }
+
+    /// Indices of the instances which may hold keys starting with `prefix`.
+    ///
+    /// Prefixes shorter than a trie identifier may match the keys of any trie, so they are looked
+    /// up in all the instances.
+    pub(crate) fn route_prefix(&self, prefix: &DatabaseKey) -> Vec<usize> {
+        match prefix {
+            DatabaseKey::TrieLog(_) => std::iter::once(0).chain(self.cold_index()).collect(),
+            _ if self.shards.is_empty() => vec![0],
+            _ if prefix.as_slice().len() < IDENTIFIER_LEN => (0..1 + self.shards.len()).collect(),
+            _ => vec![self.route(prefix)],
+        }
+    }
+}
+
+/// The shard, among `count`, holding the tries whose identifier starts with `bytes`.
+fn shard_of(bytes: &[u8], count: usize) -> usize {
+    let prefix = u16::from_be_bytes([bytes[0], bytes[1]]) as usize % PREFIX_RANGE;
+    prefix * count / PREFIX_RANGE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shards_hold_contiguous_address_ranges() {
+        let address = |prefix: u16| [prefix.to_be_bytes().as_slice(), &[0xff; 30]].concat();
+
+        assert_eq!(shard_of(&address(0x0000), 4), 0);
+        assert_eq!(shard_of(&address(0x01ff), 4), 0);
+        assert_eq!(shard_of(&address(0x0200), 4), 1);
+        assert_eq!(shard_of(&address(0x05ff), 4), 2);
+        assert_eq!(shard_of(&address(0x07ff), 4), 3);
+
+        let shards: Vec<usize> = (0..PREFIX_RANGE as u16).map(|prefix| shard_of(&address(prefix), 3)).collect();
+        assert!(shards.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(shards.last(), Some(&2));
+    }
+}
@@ -1,10 +1,11 @@
 use std::fmt::Display;
-use std::sync::RwLockReadGuard;
+use std::sync::{RwLock, RwLockReadGuard};
 
 use bitvec::prelude::Msb0;
 use bitvec::vec::BitVec;
 use bitvec::view::AsBits;
 use bonsai_trie::id::BasicId;
+pub use bonsai_trie::ProofNode;
 use bonsai_trie::{BonsaiDatabase, BonsaiStorage};
 use sp_core::hexdisplay::AsBytesRef;
 use starknet_api::core::{ClassHash, ContractAddress};
@@ -21,21 +22,37 @@ use crate::DeoxysBackend;
 
 /// Type-safe bonsai storage handler with exclusif acces to the Deoxys backend. Use this to access
 /// storage instead of manually querying the bonsai tries.
+///
+/// Each trie is behind a lock, taken for writing by the sync for the whole commit of a block. The
+/// views hold it for reading as long as they live, which holds up the commits, while the snapshots
+/// returned by [`StorageHandler::contract_at`], [`StorageHandler::contract_storage_at`] and
+/// [`StorageHandler::class_at`] only take it while being opened, and should be preferred by the
+/// readers which are not part of the sync. All the accessors block the calling thread until the
+/// lock is free: async code should call them from the blocking thread pool.
 pub struct StorageHandler;
 
 pub struct ContractTrieMut(BonsaiStorage<BasicId, BonsaiTransaction<'static>, Pedersen>);
 
 pub struct ContractTrieView<'a>(RwLockReadGuard<'a, BonsaiStorage<BasicId, BonsaiDb<'static>, Pedersen>>);
 
+/// Read-only contract trie as of a past block.
+pub struct ContractTrieSnapshot(BonsaiStorage<BasicId, BonsaiTransaction<'static>, Pedersen>);
+
 pub struct ContractStorageTrieMut(BonsaiStorage<BasicId, BonsaiTransaction<'static>, Pedersen>);
 
 pub struct ContractStorageTrieView<'a>(RwLockReadGuard<'a, BonsaiStorage<BasicId, BonsaiDb<'static>, Pedersen>>);
 
+/// Read-only contract storage as of a past block.
+pub struct ContractStorageTrieSnapshot(BonsaiStorage<BasicId, BonsaiTransaction<'static>, Pedersen>);
+
 pub struct ClassTrieMut(BonsaiStorage<BasicId, BonsaiTransaction<'static>, Poseidon>);
 
 pub struct ClassTrieView<'a>(RwLockReadGuard<'a, BonsaiStorage<BasicId, BonsaiDb<'static>, Poseidon>>);
 
-#[derive(Debug)]
+/// Read-only class trie as of a past block.
+pub struct ClassTrieSnapshot(BonsaiStorage<BasicId, BonsaiTransaction<'static>, Poseidon>);
+
+#[derive(Debug, Clone, Copy)]
 pub enum StorageType {
     Contract,
     ContractStorage,
@@ -72,6 +89,14 @@ pub enum DeoxysStorageError {
     TrieMergeError(StorageType),
     #[error("failed to retrieve latest id for {0}")]
     TrieIdError(StorageType),
+    #[error("failed to generate proof for {0}")]
+    TrieProofError(StorageType),
+    #[error("failed to revert {0}")]
+    TrieRevertError(StorageType),
+    #[error("failed to read the trie delta of {0}")]
+    TrieDeltaReadError(StorageType),
+    #[error("failed to apply the trie delta to {0}")]
+    TrieDeltaApplyError(StorageType),
 }
 
 pub mod bonsai_identifier {
@@ -79,6 +104,7 @@ pub mod bonsai_identifier {
     pub const CLASS: &[u8] = "0xclass".as_bytes();
     pub const TRANSACTION: &[u8] = "0xtransaction".as_bytes();
     pub const EVENT: &[u8] = "0xevent".as_bytes();
+    pub const RECEIPT: &[u8] = "0xreceipt".as_bytes();
 }
 
 impl StorageHandler {
@@ -108,6 +134,11 @@ impl StorageHandler {
         ))
     }
 
+    /// Contract trie as it was right after block `block_number` was applied.
+    pub fn contract_at(block_number: u64) -> Result<ContractTrieSnapshot, DeoxysStorageError> {
+        snapshot(DeoxysBackend::bonsai_contract(), block_number, StorageType::Contract).map(ContractTrieSnapshot)
+    }
+
     #[rustfmt::skip]
     pub fn contract_storage_mut(block_id: BlockId) -> Result<ContractStorageTrieMut, DeoxysStorageError> {
 		let bonsai_storage = DeoxysBackend::bonsai_storage().read().unwrap();
@@ -134,6 +165,15 @@ impl StorageHandler {
         ))
     }
 
+    /// Contract storage as it was right after block `block_number` was applied.
+    ///
+    /// Opening a snapshot replays the trie logs back from the closest stored snapshot, so it
+    /// should be reused for all the reads made at the same block.
+    pub fn contract_storage_at(block_number: u64) -> Result<ContractStorageTrieSnapshot, DeoxysStorageError> {
+        snapshot(DeoxysBackend::bonsai_storage(), block_number, StorageType::ContractStorage)
+            .map(ContractStorageTrieSnapshot)
+    }
+
     #[rustfmt::skip]
     pub fn class_mut(block_id: BlockId) -> Result<ClassTrieMut, DeoxysStorageError> {
         let bonsai_classes = DeoxysBackend::bonsai_class().read().unwrap();
@@ -159,6 +199,33 @@ impl StorageHandler {
                 .map_err(|_| DeoxysStorageError::StoraveViewError(StorageType::Class))?,
         ))
     }
+
+    /// Class trie as it was right after block `block_number` was applied.
+    pub fn class_at(block_number: u64) -> Result<ClassTrieSnapshot, DeoxysStorageError> {
+        snapshot(DeoxysBackend::bonsai_class(), block_number, StorageType::Class).map(ClassTrieSnapshot)
+    }
+
+    /// Reverts the contract, contract storage and class tries to their state right after block
+    /// `block_number` was applied, undoing the later blocks with the trie logs.
+    ///
+    /// Used to drop the blocks replaced by a reorg, or rolled back by the operator. Only the last
+    /// `max_saved_trie_logs` blocks can be reverted. Does nothing if the tries are not past
+    /// `block_number`.
+    pub fn rewind_to(block_number: u64) -> Result<(), DeoxysStorageError> {
+        let mut bonsai_contract = DeoxysBackend::bonsai_contract()
+            .write()
+            .map_err(|_| DeoxysStorageError::TrieRevertError(StorageType::Contract))?;
+        let mut bonsai_storage = DeoxysBackend::bonsai_storage()
+            .write()
+            .map_err(|_| DeoxysStorageError::TrieRevertError(StorageType::ContractStorage))?;
+        let mut bonsai_class = DeoxysBackend::bonsai_class()
+            .write()
+            .map_err(|_| DeoxysStorageError::TrieRevertError(StorageType::Class))?;
+
+        rewind(&mut bonsai_contract, block_number, StorageType::Contract)?;
+        rewind(&mut bonsai_storage, block_number, StorageType::ContractStorage)?;
+        rewind(&mut bonsai_class, block_number, StorageType::Class)
+    }
 }
 
 impl ContractTrieMut {
@@ -240,6 +307,36 @@ impl ContractTrieView<'_> {
             .root_hash(bonsai_identifier::CONTRACT)
             .map_err(|_| DeoxysStorageError::TrieRootError(StorageType::Contract))
     }
+
+    /// Merkle proof of the leaf of `key` in the latest state of the contract trie, from the root
+    /// down. The proof is a non-membership proof if `key` has no leaf.
+    pub fn get_proof(&self, key: &ContractAddress) -> Result<Vec<ProofNode>, DeoxysStorageError> {
+        self.0
+            .get_proof(bonsai_identifier::CONTRACT, &conv_contract_key(key))
+            .map_err(|_| DeoxysStorageError::TrieProofError(StorageType::Contract))
+    }
+}
+
+impl ContractTrieSnapshot {
+    pub fn get(&self, key: &ContractAddress) -> Result<Option<Felt>, DeoxysStorageError> {
+        self.0
+            .get(bonsai_identifier::CONTRACT, &conv_contract_key(key))
+            .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::Contract))
+    }
+
+    pub fn root(&self) -> Result<Felt, DeoxysStorageError> {
+        self.0
+            .root_hash(bonsai_identifier::CONTRACT)
+            .map_err(|_| DeoxysStorageError::TrieRootError(StorageType::Contract))
+    }
+
+    /// Merkle proof of the leaf of `key` in the contract trie, from the root down. The proof is a
+    /// non-membership proof if `key` has no leaf.
+    pub fn get_proof(&self, key: &ContractAddress) -> Result<Vec<ProofNode>, DeoxysStorageError> {
+        self.0
+            .get_proof(bonsai_identifier::CONTRACT, &conv_contract_key(key))
+            .map_err(|_| DeoxysStorageError::TrieProofError(StorageType::Contract))
+    }
 }
 
 impl ContractStorageTrieMut {
@@ -362,6 +459,44 @@ impl ContractStorageTrieView<'_> {
             .root_hash(conv_contract_identifier(identifier))
             .map_err(|_| DeoxysStorageError::TrieRootError(StorageType::ContractStorage))
     }
+
+    /// Merkle proof of the leaf of `key` in the latest state of the storage trie of the contract
+    /// `identifier`, from the root down.
+    pub fn get_proof(
+        &self,
+        identifier: &ContractAddress,
+        key: &StorageKey,
+    ) -> Result<Vec<ProofNode>, DeoxysStorageError> {
+        self.0
+            .get_proof(conv_contract_identifier(identifier), &conv_contract_storage_key(key))
+            .map_err(|_| DeoxysStorageError::TrieProofError(StorageType::ContractStorage))
+    }
+}
+
+impl ContractStorageTrieSnapshot {
+    pub fn get(&self, identifier: &ContractAddress, key: &StorageKey) -> Result<Option<Felt>, DeoxysStorageError> {
+        self.0
+            .get(conv_contract_identifier(identifier), &conv_contract_storage_key(key))
+            .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractStorage))
+    }
+
+    pub fn root(&self, identifier: &ContractAddress) -> Result<Felt, DeoxysStorageError> {
+        self.0
+            .root_hash(conv_contract_identifier(identifier))
+            .map_err(|_| DeoxysStorageError::TrieRootError(StorageType::ContractStorage))
+    }
+
+    /// Merkle proof of the leaf of `key` in the storage trie of the contract `identifier`, from the
+    /// root down.
+    pub fn get_proof(
+        &self,
+        identifier: &ContractAddress,
+        key: &StorageKey,
+    ) -> Result<Vec<ProofNode>, DeoxysStorageError> {
+        self.0
+            .get_proof(conv_contract_identifier(identifier), &conv_contract_storage_key(key))
+            .map_err(|_| DeoxysStorageError::TrieProofError(StorageType::ContractStorage))
+    }
 }
 
 impl ClassTrieMut {
@@ -452,6 +587,18 @@ impl ClassTrieView<'_> {
     }
 }
 
+impl ClassTrieSnapshot {
+    pub fn get(&self, key: &ClassHash) -> Result<Option<Felt>, DeoxysStorageError> {
+        self.0
+            .get(bonsai_identifier::CLASS, &conv_class_key(key))
+            .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::Class))
+    }
+
+    pub fn root(&self) -> Result<Felt, DeoxysStorageError> {
+        self.0.root_hash(bonsai_identifier::CLASS).map_err(|_| DeoxysStorageError::TrieRootError(StorageType::Class))
+    }
+}
+
 fn conv_contract_identifier(identifier: &ContractAddress) -> &[u8] {
     identifier.0.0.0.as_bytes_ref()
 }
@@ -472,6 +619,46 @@ fn conv_class_key(key: &ClassHash) -> BitVec<u8, Msb0> {
     key.0.0.as_bits()[5..].to_owned()
 }
 
+/// Opens the state of `bonsai` right after block `block_number` was applied, only holding its lock
+/// while the trie logs are replayed from the closest stored snapshot.
+fn snapshot<H>(
+    bonsai: &RwLock<BonsaiStorage<BasicId, BonsaiDb<'static>, H>>,
+    block_number: u64,
+    storage_type: StorageType,
+) -> Result<BonsaiStorage<BasicId, BonsaiTransaction<'static>, H>, DeoxysStorageError>
+where
+    H: StarkHash + Send + Sync,
+{
+    let bonsai = bonsai.read().map_err(|_| DeoxysStorageError::StoraveViewError(storage_type))?;
+    // the state resulting from block `n` is committed with id `n + 1`
+    let bonsai_id = BasicId::new(block_number + 1);
+
+    match bonsai.get_transactional_state(bonsai_id, bonsai.get_config()) {
+        Ok(Some(transactional_storage)) => Ok(transactional_storage),
+        _ => Err(DeoxysStorageError::StoraveViewError(storage_type)),
+    }
+}
+
+/// Reverts `bonsai` to its state right after block `block_number` was applied.
+fn rewind<DB, H>(
+    bonsai: &mut BonsaiStorage<BasicId, DB, H>,
+    block_number: u64,
+    storage_type: StorageType,
+) -> Result<(), DeoxysStorageError>
+where
+    DB: BonsaiDatabase,
+    H: StarkHash + Send + Sync,
+{
+    // the state resulting from block `n` is committed with id `n + 1`
+    let target = BasicId::new(block_number + 1);
+    let latest = bonsai.get_latest_id().ok_or(DeoxysStorageError::TrieIdError(storage_type))?;
+    if target >= latest {
+        return Ok(());
+    }
+
+    bonsai.revert_to(target, latest).map_err(|_| DeoxysStorageError::TrieRevertError(storage_type))
+}
+
 fn conv_block_id<DB, H>(
     bonsai: &BonsaiStorage<BasicId, DB, H>,
     block_id: BlockId,
@@ -489,3 +676,48 @@ where
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bonsai_trie::BonsaiStorageConfig;
+    use starknet_api::core::PatriciaKey;
+
+    use super::*;
+    use crate::bonsai_db::DatabaseKeyMapping;
+    use crate::shards::BonsaiInstances;
+    use crate::test_utils::test_db;
+    use crate::Column;
+
+    #[test]
+    fn rewound_tries_match_the_historical_roots() {
+        let db = test_db("trie-rewind");
+        let mapping = DatabaseKeyMapping {
+            flat: Column::BonsaiContractsFlat,
+            trie: Column::BonsaiContractsTrie,
+            trie_log: Column::BonsaiContractsLog,
+        };
+        let mut bonsai: BonsaiStorage<BasicId, _, Pedersen> = BonsaiStorage::new(
+            BonsaiDb::new(BonsaiInstances::new(&db, &[], None), mapping),
+            BonsaiStorageConfig::default(),
+        )
+        .unwrap();
+        bonsai.commit(BasicId::new(0)).unwrap();
+
+        let key = |n: u128| conv_contract_key(&ContractAddress(PatriciaKey(StarkFelt::from(n))));
+        let mut roots = Vec::new();
+        for block_number in 0..4u128 {
+            bonsai.insert(bonsai_identifier::CONTRACT, &key(block_number), &Felt::from(block_number + 10)).unwrap();
+            bonsai.insert(bonsai_identifier::CONTRACT, &key(0), &Felt::from(block_number + 100)).unwrap();
+            bonsai.commit(BasicId::new(block_number as u64 + 1)).unwrap();
+            roots.push(bonsai.root_hash(bonsai_identifier::CONTRACT).unwrap());
+        }
+
+        rewind(&mut bonsai, 3, StorageType::Contract).unwrap();
+        assert_eq!(bonsai.root_hash(bonsai_identifier::CONTRACT).unwrap(), roots[3]);
+
+        rewind(&mut bonsai, 1, StorageType::Contract).unwrap();
+        assert_eq!(bonsai.root_hash(bonsai_identifier::CONTRACT).unwrap(), roots[1]);
+        assert_eq!(bonsai.get(bonsai_identifier::CONTRACT, &key(0)).unwrap(), Some(Felt::from(101u128)));
+        assert_eq!(bonsai.get(bonsai_identifier::CONTRACT, &key(3)).unwrap(), None);
+    }
+}
@@ -0,0 +1,52 @@
+//! Temporary directories and databases shared by the tests of this crate.
+//!
+//! Both are removed from the disk when dropped, so a failing test doesn't leave them behind.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::DB;
+
+/// A directory under the system temporary directory, removed on drop.
+pub(crate) struct TestDir(PathBuf);
+
+impl TestDir {
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A database with all the columns, opened in its own [`TestDir`].
+pub(crate) struct TestDb {
+    // dropped before the directory is removed
+    db: Arc<DB>,
+    _dir: TestDir,
+}
+
+impl Deref for TestDb {
+    type Target = Arc<DB>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.db
+    }
+}
+
+/// Returns an empty directory whose name is unique to the test and to the process.
+pub(crate) fn test_dir(name: &str) -> TestDir {
+    let dir = std::env::temp_dir().join(format!("deoxys-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    TestDir(dir)
+}
+
+/// Opens a new database in a [`test_dir`].
+pub(crate) fn test_db(name: &str) -> TestDb {
+    let dir = test_dir(name);
+    TestDb { db: Arc::new(crate::open_rocksdb(dir.path(), true, false).unwrap()), _dir: dir }
+}
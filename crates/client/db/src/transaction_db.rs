@@ -0,0 +1,347 @@
+use std::sync::Arc;
+
+use mp_block::BlockEvents;
+// Substrate
+use parity_scale_codec::{Decode, Encode};
+use rocksdb::{Direction, IteratorMode, WriteBatchWithTransaction};
+// Starknet
+use starknet_api::transaction::TransactionHash;
+
+use crate::{Column, DatabaseExt, DbError, DB};
+
+/// Position of a transaction in the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct TransactionLocation {
+    pub block_number: u64,
+    /// Index of the transaction in its block.
+    pub index: u64,
+}
+
+/// Position of the events of a transaction among the events of its block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct EventSlice {
+    pub block_number: u64,
+    /// Index of the events of the transaction in the events of its block, which are grouped by
+    /// transaction. Transactions which emitted no events have no group, this is then the index of
+    /// the next group.
+    pub position: u64,
+    /// Number of events emitted by the transaction.
+    pub count: u64,
+}
+
+/// Allow interaction with the transaction index db
+///
+/// Transactions are indexed both by hash and by position, so that a transaction can be found from
+/// its hash, and its hash from its position, without going through the block bodies.
+pub struct TransactionDb {
+    pub(crate) db: Arc<DB>,
+}
+
+impl TransactionDb {
+    pub(crate) fn new(db: Arc<DB>) -> Self {
+        Self { db }
+    }
+
+    /// Return the position of the transaction with the given hash
+    pub fn location(&self, transaction_hash: &TransactionHash) -> Result<Option<TransactionLocation>, DbError> {
+        let column = self.db.get_column(Column::TransactionLocations);
+
+        match self.db.get_cf(&column, transaction_hash.encode())? {
+            Some(raw) => Ok(Some(TransactionLocation::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Return the position of the transaction with the given hash, if that position still holds it
+    ///
+    /// Locations left behind by blocks which were replaced are then ignored, rather than pointing
+    /// at another transaction.
+    pub fn checked_location(&self, transaction_hash: &TransactionHash) -> Result<Option<TransactionLocation>, DbError> {
+        let Some(location) = self.location(transaction_hash)? else {
+            return Ok(None);
+        };
+        match self.hash_at(location.block_number, location.index)? {
+            Some(indexed) if indexed == *transaction_hash => Ok(Some(location)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Return the hash of the transaction at the given position
+    pub fn hash_at(&self, block_number: u64, index: u64) -> Result<Option<TransactionHash>, DbError> {
+        let column = self.db.get_column(Column::BlockTransactionHashes);
+
+        match self.db.get_cf(&column, position_key(block_number, index))? {
+            Some(raw) => Ok(Some(TransactionHash::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Return the hashes of the transactions of the given block, in block order
+    pub fn block_hashes(&self, block_number: u64) -> Result<Vec<TransactionHash>, DbError> {
+        let column = self.db.get_column(Column::BlockTransactionHashes);
+        let prefix = block_number.to_be_bytes();
+
+        let mut hashes = Vec::new();
+        for kv in self.db.iterator_cf(&column, IteratorMode::From(&prefix, Direction::Forward)) {
+            let (key, value) = kv?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            hashes.push(TransactionHash::decode(&mut &value[..])?);
+        }
+        Ok(hashes)
+    }
+
+    /// Return the position of the events of the transaction with the given hash
+    pub fn event_slice(&self, transaction_hash: &TransactionHash) -> Result<Option<EventSlice>, DbError> {
+        let column = self.db.get_column(Column::TransactionEvents);
+
+        match self.db.get_cf(&column, transaction_hash.encode())? {
+            Some(raw) => Ok(Some(EventSlice::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Index the events of a block, given the hashes of its transactions in block order
+    pub fn store_events(
+        &self,
+        block_number: u64,
+        transaction_hashes: &[TransactionHash],
+        events: &BlockEvents,
+    ) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::TransactionEvents);
+
+        // the events are grouped by transaction, in block order
+        let mut groups = events.iter().enumerate().peekable();
+        let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+        for (index, transaction_hash) in transaction_hashes.iter().enumerate() {
+            let slice = match groups.next_if(|(_, ordered_events)| ordered_events.index() == index as u128) {
+                Some((position, ordered_events)) => {
+                    EventSlice { block_number, position: position as u64, count: ordered_events.events().len() as u64 }
+                }
+                None => {
+                    let position = groups.peek().map_or(events.len(), |(position, _)| *position);
+                    EventSlice { block_number, position: position as u64, count: 0 }
+                }
+            };
+            transaction.put_cf(&column, transaction_hash.encode(), slice.encode());
+        }
+
+        self.db.write(transaction)?;
+        Ok(())
+    }
+
+    /// Index the transactions of a block, given in block order
+    pub fn store_block(&self, block_number: u64, transaction_hashes: &[TransactionHash]) -> Result<(), DbError> {
+        let column_locations = self.db.get_column(Column::TransactionLocations);
+        let column_hashes = self.db.get_column(Column::BlockTransactionHashes);
+
+        let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+        for (index, transaction_hash) in transaction_hashes.iter().enumerate() {
+            let location = TransactionLocation { block_number, index: index as u64 };
+            transaction.put_cf(&column_locations, transaction_hash.encode(), location.encode());
+            transaction.put_cf(&column_hashes, position_key(block_number, location.index), transaction_hash.encode());
+        }
+
+        self.db.write(transaction)?;
+        Ok(())
+    }
+
+    /// Remove the transactions of the blocks after `block_number` from the index, along with the
+    /// position of their events, once they were replaced by a reorg
+    pub fn remove_blocks_after(&self, block_number: u64) -> Result<(), DbError> {
+        let column_locations = self.db.get_column(Column::TransactionLocations);
+        let column_hashes = self.db.get_column(Column::BlockTransactionHashes);
+        let column_events = self.db.get_column(Column::TransactionEvents);
+        let Some(first_removed) = block_number.checked_add(1) else {
+            return Ok(());
+        };
+
+        let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+        let start = position_key(first_removed, 0);
+        for kv in self.db.iterator_cf(&column_hashes, IteratorMode::From(&start, Direction::Forward)) {
+            let (key, value) = kv?;
+            let transaction_hash = TransactionHash::decode(&mut &value[..])?;
+            // a transaction included again by the new chain is indexed at its new position
+            if self.location(&transaction_hash)?.map_or(false, |location| location.block_number > block_number) {
+                transaction.delete_cf(&column_locations, transaction_hash.encode());
+            }
+            if self.event_slice(&transaction_hash)?.map_or(false, |slice| slice.block_number > block_number) {
+                transaction.delete_cf(&column_events, transaction_hash.encode());
+            }
+            transaction.delete_cf(&column_hashes, key);
+        }
+
+        self.db.write(transaction)?;
+        Ok(())
+    }
+
+    /// Record that transactions are indexed as blocks are synced, starting from `block_number`
+    ///
+    /// Only the first call has an effect: on later runs, the blocks in between were indexed as
+    /// they were synced. Returns the block from which transactions were first indexed.
+    pub fn start_live_indexing(&self, block_number: u64) -> Result<u64, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf(&column, crate::static_keys::TRANSACTION_INDEX_LIVE_FROM)? {
+            Some(raw) => Ok(u64::decode(&mut &raw[..])?),
+            None => {
+                self.db.put_cf(&column, crate::static_keys::TRANSACTION_INDEX_LIVE_FROM, block_number.encode())?;
+                Ok(block_number)
+            }
+        }
+    }
+
+    /// Return the blocks which still need to be backfilled
+    pub fn backfill_range(&self) -> Result<std::ops::Range<u64>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        let live_from = match self.db.get_cf(&column, crate::static_keys::TRANSACTION_INDEX_LIVE_FROM)? {
+            Some(raw) => u64::decode(&mut &raw[..])?,
+            None => return Ok(0..0),
+        };
+        let backfilled = match self.db.get_cf(&column, crate::static_keys::TRANSACTION_INDEX_BACKFILLED)? {
+            Some(raw) => u64::decode(&mut &raw[..])?,
+            None => 0,
+        };
+
+        Ok(backfilled..live_from)
+    }
+
+    /// Record that all the blocks before `block_number` were backfilled
+    pub fn set_backfilled(&self, block_number: u64) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        self.db.put_cf(&column, crate::static_keys::TRANSACTION_INDEX_BACKFILLED, block_number.encode())?;
+        Ok(())
+    }
+}
+
+fn position_key(block_number: u64, index: u64) -> [u8; 16] {
+    let mut key = [0; 16];
+    key[..8].copy_from_slice(&block_number.to_be_bytes());
+    key[8..].copy_from_slice(&index.to_be_bytes());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use mp_block::OrderedEvents;
+    use starknet_api::hash::StarkFelt;
+    use starknet_api::transaction::Event;
+
+    use super::*;
+    use crate::test_utils::test_db;
+
+    #[test]
+    fn transactions_are_indexed_by_hash_and_position() {
+        let db = test_db("transaction-index");
+        let transactions = TransactionDb::new(db.clone());
+
+        let hashes: Vec<_> = (1..=3u128).map(|n| TransactionHash(StarkFelt::from(n))).collect();
+        transactions.store_block(12, &hashes).unwrap();
+
+        assert_eq!(
+            transactions.location(&hashes[2]).unwrap(),
+            Some(TransactionLocation { block_number: 12, index: 2 })
+        );
+        assert_eq!(transactions.location(&TransactionHash(StarkFelt::from(4u128))).unwrap(), None);
+        assert_eq!(transactions.hash_at(12, 1).unwrap(), Some(hashes[1]));
+        assert_eq!(transactions.hash_at(12, 3).unwrap(), None);
+        assert_eq!(transactions.hash_at(13, 0).unwrap(), None);
+        assert_eq!(transactions.block_hashes(12).unwrap(), hashes);
+        assert_eq!(transactions.block_hashes(11).unwrap(), vec![]);
+        assert_eq!(transactions.block_hashes(13).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn replaced_blocks_are_removed_from_the_index() {
+        let db = test_db("transaction-index-reorg");
+        let transactions = TransactionDb::new(db.clone());
+
+        let hashes: Vec<_> = (1..=4u128).map(|n| TransactionHash(StarkFelt::from(n))).collect();
+        transactions.store_block(12, &hashes[..1]).unwrap();
+        transactions.store_block(13, &hashes[1..3]).unwrap();
+        transactions.store_block(14, &hashes[3..]).unwrap();
+        // the new block 13 includes again a transaction of the replaced block 14
+        transactions.store_block(13, &hashes[3..]).unwrap();
+        assert_eq!(transactions.checked_location(&hashes[1]).unwrap(), None);
+        assert_eq!(
+            transactions.checked_location(&hashes[3]).unwrap(),
+            Some(TransactionLocation { block_number: 13, index: 0 })
+        );
+
+        transactions.remove_blocks_after(13).unwrap();
+        assert_eq!(transactions.block_hashes(14).unwrap(), vec![]);
+        assert_eq!(
+            transactions.location(&hashes[3]).unwrap(),
+            Some(TransactionLocation { block_number: 13, index: 0 })
+        );
+
+        transactions.remove_blocks_after(12).unwrap();
+        assert_eq!(transactions.block_hashes(13).unwrap(), vec![]);
+        assert_eq!(transactions.location(&hashes[1]).unwrap(), None);
+        assert_eq!(transactions.location(&hashes[3]).unwrap(), None);
+        assert_eq!(
+            transactions.location(&hashes[0]).unwrap(),
+            Some(TransactionLocation { block_number: 12, index: 0 })
+        );
+    }
+
+    #[test]
+    fn backfill_covers_the_blocks_before_the_live_index() {
+        let db = test_db("transaction-index-backfill");
+        let transactions = TransactionDb::new(db.clone());
+
+        assert_eq!(transactions.backfill_range().unwrap(), 0..0);
+        assert_eq!(transactions.start_live_indexing(10).unwrap(), 10);
+        assert_eq!(transactions.start_live_indexing(20).unwrap(), 10);
+        assert_eq!(transactions.backfill_range().unwrap(), 0..10);
+        transactions.set_backfilled(4).unwrap();
+        assert_eq!(transactions.backfill_range().unwrap(), 4..10);
+    }
+
+    #[test]
+    fn events_are_indexed_by_transaction() {
+        let db = test_db("transaction-events");
+        let transactions = TransactionDb::new(db.clone());
+
+        // the second transaction emits no events, so it has no group in the block events
+        let hashes: Vec<_> = (1..=3u128).map(|n| TransactionHash(StarkFelt::from(n))).collect();
+        let events = vec![
+            OrderedEvents::new(0, vec![Event::default()]),
+            OrderedEvents::new(2, vec![Event::default(), Event::default()]),
+        ];
+        transactions.store_events(12, &hashes, &events).unwrap();
+
+        assert_eq!(
+            transactions.event_slice(&hashes[2]).unwrap(),
+            Some(EventSlice { block_number: 12, position: 1, count: 2 })
+        );
+        assert_eq!(
+            transactions.event_slice(&hashes[1]).unwrap(),
+            Some(EventSlice { block_number: 12, position: 1, count: 0 })
+        );
+        assert_eq!(transactions.event_slice(&TransactionHash(StarkFelt::from(4u128))).unwrap(), None);
+    }
+
+    #[test]
+    fn events_of_replaced_blocks_are_removed() {
+        let db = test_db("transaction-events-reorg");
+        let transactions = TransactionDb::new(db.clone());
+
+        let hashes: Vec<_> = (1..=2u128).map(|n| TransactionHash(StarkFelt::from(n))).collect();
+        let events = vec![OrderedEvents::new(0, vec![Event::default()])];
+        transactions.store_block(12, &hashes[..1]).unwrap();
+        transactions.store_events(12, &hashes[..1], &events).unwrap();
+        transactions.store_block(13, &hashes[1..]).unwrap();
+        transactions.store_events(13, &hashes[1..], &events).unwrap();
+
+        transactions.remove_blocks_after(12).unwrap();
+        assert_eq!(
+            transactions.event_slice(&hashes[0]).unwrap(),
+            Some(EventSlice { block_number: 12, position: 0, count: 1 })
+        );
+        assert_eq!(transactions.event_slice(&hashes[1]).unwrap(), None);
+    }
+}
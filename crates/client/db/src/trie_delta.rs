@@ -0,0 +1,247 @@
+//! Trie deltas: the changes made to the bonsai tries by a block, which read replicas apply instead
+//! of recomputing the tries from the state diff of the block.
+//!
+//! The tries log each of their commits to be able to revert them, the state right after block `n`
+//! being committed with id `n + 1`. For each trie node and flat value changed by a commit, the
+//! trie log holds its old and new values, under the keys
+//!
+//! ```text
+//! id: u64 (big endian) | 0 | key | key type: u8 (0 trie node, 1 flat value) | change: u8 (0 new, 1 old)
+//! ```
+//!
+//! The delta of a block is the trie log of its commit, for each of the contract, contract storage
+//! and class tries. Applying it writes the new values of the changed keys, removes the keys which
+//! have no new value, and copies the trie log itself so that the block can still be reverted.
+use std::collections::BTreeMap;
+
+use bonsai_trie::id::BasicId;
+use bonsai_trie::{BonsaiDatabase, BonsaiStorage, DatabaseKey};
+use parity_scale_codec::{Decode, Encode};
+use starknet_types_core::hash::StarkHash;
+
+use crate::bonsai_db::{BonsaiDb, DatabaseKeyMapping};
+use crate::storage::{DeoxysStorageError, StorageType};
+use crate::{
+    open_bonsai_db, BonsaiDbError, BACKEND_SINGLETON, BONSAI_CLASS_COLUMNS, BONSAI_CONTRACT_COLUMNS,
+    BONSAI_STORAGE_COLUMNS,
+};
+
+/// Length of the trie ids prefixing the keys of the trie logs.
+const ID_LEN: usize = 8;
+const KEY_SEPARATOR: u8 = 0;
+const TRIE_KEY: u8 = 0;
+const FLAT_KEY: u8 = 1;
+const NEW_VALUE: u8 = 0;
+const OLD_VALUE: u8 = 1;
+
+/// The entries of a trie log.
+type TrieLog = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// The changes made to the contract, contract storage and class tries by a block.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+pub struct TrieDelta {
+    contract: TrieLog,
+    contract_storage: TrieLog,
+    class: TrieLog,
+}
+
+impl TrieDelta {
+    /// Reads the delta of block `block_number` from the trie logs.
+    ///
+    /// Returns `None` if the tries hold no log for the block: its state diff was not committed to
+    /// them, or its logs were pruned.
+    pub fn of_block(block_number: u64) -> Result<Option<Self>, DeoxysStorageError> {
+        let read = |columns, storage_type| {
+            read_log(&open_bonsai_db(columns), block_number)
+                .map_err(|_| DeoxysStorageError::TrieDeltaReadError(storage_type))
+        };
+        let delta = Self {
+            contract: read(BONSAI_CONTRACT_COLUMNS, StorageType::Contract)?,
+            contract_storage: read(BONSAI_STORAGE_COLUMNS, StorageType::ContractStorage)?,
+            class: read(BONSAI_CLASS_COLUMNS, StorageType::Class)?,
+        };
+        Ok(if delta.is_empty() { None } else { Some(delta) })
+    }
+
+    /// Whether the delta changes none of the tries.
+    pub fn is_empty(&self) -> bool {
+        self.contract.is_empty() && self.contract_storage.is_empty() && self.class.is_empty()
+    }
+
+    /// Applies the delta of block `block_number` to the tries, which must hold the state right
+    /// before that block.
+    ///
+    /// The tries are locked for writing until the delta is applied, then reopened from the
+    /// database, as they cache their roots.
+    pub fn apply(&self, block_number: u64) -> Result<(), DeoxysStorageError> {
+        let backend = BACKEND_SINGLETON.get().expect("Backend not initialized");
+        let (mut contract, mut contract_storage, mut class) = (
+            backend
+                .bonsai_contract
+                .write()
+                .map_err(|_| DeoxysStorageError::TrieDeltaApplyError(StorageType::Contract))?,
+            backend
+                .bonsai_storage
+                .write()
+                .map_err(|_| DeoxysStorageError::TrieDeltaApplyError(StorageType::ContractStorage))?,
+            backend.bonsai_class.write().map_err(|_| DeoxysStorageError::TrieDeltaApplyError(StorageType::Class))?,
+        );
+
+        apply_to(&mut contract, BONSAI_CONTRACT_COLUMNS, &self.contract, block_number, StorageType::Contract)?;
+        apply_to(
+            &mut contract_storage,
+            BONSAI_STORAGE_COLUMNS,
+            &self.contract_storage,
+            block_number,
+            StorageType::ContractStorage,
+        )?;
+        apply_to(&mut class, BONSAI_CLASS_COLUMNS, &self.class, block_number, StorageType::Class)
+    }
+}
+
+/// Applies `log` to the columns of `bonsai`, then reopens it.
+fn apply_to<H>(
+    bonsai: &mut BonsaiStorage<BasicId, BonsaiDb<'static>, H>,
+    columns: DatabaseKeyMapping,
+    log: &TrieLog,
+    block_number: u64,
+    storage_type: StorageType,
+) -> Result<(), DeoxysStorageError>
+where
+    H: StarkHash + Send + Sync,
+{
+    let config = BACKEND_SINGLETON.get().expect("Backend not initialized").bonsai_config.clone();
+
+    apply_log(&mut open_bonsai_db(columns.clone()), log, block_number).map_err(|e| {
+        log::error!("Failed to apply the delta of block {block_number} to the {storage_type}: {e}");
+        DeoxysStorageError::TrieDeltaApplyError(storage_type)
+    })?;
+    *bonsai = BonsaiStorage::new(open_bonsai_db(columns), config)
+        .map_err(|_| DeoxysStorageError::TrieDeltaApplyError(storage_type))?;
+    Ok(())
+}
+
+/// The entries of the trie log of the commit of block `block_number`.
+fn read_log(db: &BonsaiDb<'_>, block_number: u64) -> Result<TrieLog, BonsaiDbError> {
+    // the state resulting from block `n` is committed with id `n + 1`
+    let id = (block_number + 1).to_be_bytes();
+    db.get_by_prefix(&DatabaseKey::TrieLog(&id))
+}
+
+/// Writes the changes logged in `log` for block `block_number`, along with the log itself.
+fn apply_log(db: &mut BonsaiDb<'_>, log: &TrieLog, block_number: u64) -> Result<(), String> {
+    let id = (block_number + 1).to_be_bytes();
+    let mut batch = db.create_batch();
+
+    // the new value of each changed key, none for the keys removed by the block
+    let mut changes: BTreeMap<(u8, &[u8]), Option<&[u8]>> = BTreeMap::new();
+    for (key, value) in log {
+        if !key.starts_with(&id) {
+            return Err(format!("Trie log entry {key:x?} is not part of the block"));
+        }
+        let (changed, is_new) = parse_log_key(key).ok_or_else(|| format!("Invalid trie log entry {key:x?}"))?;
+        let new_value = changes.entry(changed).or_default();
+        if is_new {
+            *new_value = Some(value);
+        }
+        db.insert(&DatabaseKey::TrieLog(key), value, Some(&mut batch)).map_err(|e| e.to_string())?;
+    }
+
+    for ((key_type, key), value) in changes {
+        let key = if key_type == TRIE_KEY { DatabaseKey::Trie(key) } else { DatabaseKey::Flat(key) };
+        match value {
+            Some(value) => db.insert(&key, value, Some(&mut batch)),
+            None => db.remove(&key, Some(&mut batch)),
+        }
+        .map_err(|e| e.to_string())?;
+    }
+    db.write_batch(batch).map_err(|e| e.to_string())
+}
+
+/// Splits the key of a trie log entry into the type and key of the entry whose change it logs, and
+/// whether it holds its new value.
+fn parse_log_key(key: &[u8]) -> Option<((u8, &[u8]), bool)> {
+    if key.len() < ID_LEN + 3 || key[ID_LEN] != KEY_SEPARATOR {
+        return None;
+    }
+    let (key, suffix) = key.split_at(key.len() - 2);
+    let key_type = match suffix[0] {
+        TRIE_KEY | FLAT_KEY => suffix[0],
+        _ => return None,
+    };
+    let is_new = match suffix[1] {
+        NEW_VALUE => true,
+        OLD_VALUE => false,
+        _ => return None,
+    };
+    Some(((key_type, &key[ID_LEN + 1..]), is_new))
+}
+
+#[cfg(test)]
+mod tests {
+    use bitvec::prelude::*;
+    use bonsai_trie::BonsaiStorageConfig;
+    use starknet_types_core::felt::Felt;
+    use starknet_types_core::hash::Pedersen;
+
+    use super::*;
+    use crate::shards::BonsaiInstances;
+    use crate::storage::bonsai_identifier;
+    use crate::test_utils::test_db;
+    use crate::DB;
+
+    fn storage(db: &DB) -> BonsaiStorage<BasicId, BonsaiDb<'_>, Pedersen> {
+        BonsaiStorage::new(
+            BonsaiDb::new(BonsaiInstances::new(db, &[], None), BONSAI_CONTRACT_COLUMNS),
+            BonsaiStorageConfig::default(),
+        )
+        .unwrap()
+    }
+
+    fn key(n: u64) -> BitVec<u8, Msb0> {
+        let mut bytes = [0u8; 32];
+        bytes[24..].copy_from_slice(&n.to_be_bytes());
+        bytes.view_bits::<Msb0>()[5..].to_owned()
+    }
+
+    #[test]
+    fn applied_deltas_match_the_committed_tries() {
+        let primary = test_db("trie-delta-primary");
+        let replica = test_db("trie-delta-replica");
+
+        // block 0 is committed to both tries, block 1 only to the primary one
+        let mut bonsai = storage(&primary);
+        let mut replica_bonsai = storage(&replica);
+        for bonsai in [&mut bonsai, &mut replica_bonsai] {
+            bonsai.commit(BasicId::new(0)).unwrap();
+            for n in 0..3 {
+                bonsai.insert(bonsai_identifier::CONTRACT, &key(n), &Felt::from(n + 10)).unwrap();
+            }
+            bonsai.commit(BasicId::new(1)).unwrap();
+        }
+        drop(replica_bonsai);
+        bonsai.insert(bonsai_identifier::CONTRACT, &key(0), &Felt::from(100u64)).unwrap();
+        bonsai.insert(bonsai_identifier::CONTRACT, &key(5), &Felt::from(105u64)).unwrap();
+        bonsai.remove(bonsai_identifier::CONTRACT, &key(2)).unwrap();
+        bonsai.commit(BasicId::new(2)).unwrap();
+
+        let log =
+            read_log(&BonsaiDb::new(BonsaiInstances::new(&primary, &[], None), BONSAI_CONTRACT_COLUMNS), 1).unwrap();
+        assert!(!log.is_empty());
+        let mut replica_db = BonsaiDb::new(BonsaiInstances::new(&replica, &[], None), BONSAI_CONTRACT_COLUMNS);
+        assert!(apply_log(&mut replica_db, &log, 0).is_err());
+        apply_log(&mut replica_db, &log, 1).unwrap();
+
+        let replica_bonsai = storage(&replica);
+        assert_eq!(
+            replica_bonsai.root_hash(bonsai_identifier::CONTRACT).unwrap(),
+            bonsai.root_hash(bonsai_identifier::CONTRACT).unwrap()
+        );
+        assert_eq!(replica_bonsai.get(bonsai_identifier::CONTRACT, &key(0)).unwrap(), Some(Felt::from(100u64)));
+        assert_eq!(replica_bonsai.get(bonsai_identifier::CONTRACT, &key(2)).unwrap(), None);
+        assert_eq!(
+            read_log(&BonsaiDb::new(BonsaiInstances::new(&replica, &[], None), BONSAI_CONTRACT_COLUMNS), 1).unwrap(),
+            log
+        );
+    }
+}
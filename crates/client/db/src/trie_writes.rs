@@ -0,0 +1,89 @@
+//! Accounting of the writes made by the bonsai tries to the database.
+//!
+//! The tries write their nodes, flat values and trie logs through [`BonsaiDb`], which records each
+//! write here, per column. The sync takes the writes of a block once its tries are committed, with
+//! [`TrieWrites::take`], to export them as metrics.
+//!
+//! [`BonsaiDb`]: crate::bonsai_db::BonsaiDb
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::Column;
+
+static WRITES: Mutex<BTreeMap<&'static str, ColumnWrites>> = Mutex::new(BTreeMap::new());
+
+/// Writes to a column of the database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ColumnWrites {
+    /// Entries written which were not in the column.
+    pub created: u64,
+    /// Entries overwritten.
+    pub modified: u64,
+    /// Entries deleted.
+    pub removed: u64,
+    /// Size of the keys and values written.
+    pub bytes: u64,
+}
+
+/// Writes made by the tries since the last [`TrieWrites::take`], by column name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrieWrites(pub BTreeMap<&'static str, ColumnWrites>);
+
+impl TrieWrites {
+    /// Returns the writes made by the tries since the last call, and resets them.
+    pub fn take() -> Self {
+        Self(std::mem::take(&mut *WRITES.lock().unwrap_or_else(|e| e.into_inner())))
+    }
+
+    /// Writes to the columns holding trie nodes, leaving out the flat values and trie logs.
+    pub fn nodes(&self) -> ColumnWrites {
+        self.0.iter().filter(|(column, _)| column.ends_with("_trie")).fold(
+            ColumnWrites::default(),
+            |total, (_, writes)| ColumnWrites {
+                created: total.created + writes.created,
+                modified: total.modified + writes.modified,
+                removed: total.removed + writes.removed,
+                bytes: total.bytes + writes.bytes,
+            },
+        )
+    }
+}
+
+/// Records the insertion of `key` and `value` in `column`, which held `old_value`.
+pub(crate) fn record_insert(column: Column, key: &[u8], value: &[u8], old_value: Option<&[u8]>) {
+    let mut writes = WRITES.lock().unwrap_or_else(|e| e.into_inner());
+    let writes = writes.entry(column.rocksdb_name()).or_default();
+    match old_value {
+        Some(_) => writes.modified += 1,
+        None => writes.created += 1,
+    }
+    writes.bytes += (key.len() + value.len()) as u64;
+}
+
+/// Records the deletion of `key` from `column`, which held `old_value`.
+pub(crate) fn record_remove(column: Column, key: &[u8], old_value: Option<&[u8]>) {
+    if old_value.is_none() {
+        return;
+    }
+    let mut writes = WRITES.lock().unwrap_or_else(|e| e.into_inner());
+    let writes = writes.entry(column.rocksdb_name()).or_default();
+    writes.removed += 1;
+    writes.bytes += key.len() as u64;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nodes_only_count_the_trie_columns() {
+        let writes = |created, bytes| ColumnWrites { created, modified: 1, removed: 0, bytes };
+        let trie_writes = TrieWrites(BTreeMap::from([
+            ("bonsai_contracts_trie", writes(2, 100)),
+            ("bonsai_contracts_flat", writes(5, 1000)),
+            ("bonsai_contracts_storage_trie", writes(3, 200)),
+            ("bonsai_contracts_storage_log", writes(7, 5000)),
+        ]));
+        assert_eq!(trie_writes.nodes(), ColumnWrites { created: 5, modified: 2, removed: 0, bytes: 300 });
+    }
+}
@@ -0,0 +1,155 @@
+//! Tuning of the RocksDB instances of the database.
+//!
+//! The defaults of RocksDB fit neither an archive node syncing as fast as the disk allows, nor a
+//! node running next to other services on a small machine. A [`DbProfile`] sets the memory budget
+//! and the parallelism of the database for one of these uses, and each of its settings can be
+//! overridden by the operator.
+//!
+//! The tuning applies to every RocksDB instance of the database: the primary database, the shards
+//! of the contract storage tries and the cold storage, which each get their own block cache.
+use rocksdb::{BlockBasedOptions, Cache, DBCompressionType, Options};
+
+use crate::cold::COLD_COLUMNS;
+use crate::Column;
+
+const MIB: usize = 1024 * 1024;
+
+/// Presets of the RocksDB tuning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DbProfile {
+    /// Balanced settings for a node with its database on an SSD.
+    #[default]
+    SsdDefault,
+    /// Large caches and write buffers, and as many background jobs as there are cores, for nodes
+    /// syncing or serving as many blocks as they can.
+    Throughput,
+    /// Small caches and write buffers, and few open files and background jobs, for nodes sharing
+    /// a small machine.
+    LowMemory,
+}
+
+/// Compression of the data files of a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbCompression {
+    None,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+impl From<DbCompression> for DBCompressionType {
+    fn from(compression: DbCompression) -> Self {
+        match compression {
+            DbCompression::None => DBCompressionType::None,
+            DbCompression::Snappy => DBCompressionType::Snappy,
+            DbCompression::Lz4 => DBCompressionType::Lz4,
+            DbCompression::Zstd => DBCompressionType::Zstd,
+        }
+    }
+}
+
+/// Tuning of the RocksDB instances of the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbTuning {
+    /// Size of the block cache of each instance, shared by its columns, in bytes.
+    pub block_cache_size: usize,
+    /// Size of the write buffer of each column, in bytes.
+    pub write_buffer_size: usize,
+    /// Maximum number of files each instance keeps open, unlimited if negative.
+    pub max_open_files: i32,
+    /// Maximum number of concurrent flushes and compactions of each instance.
+    pub background_jobs: i32,
+    /// Compression of the columns which are not listed in `column_compression`.
+    pub compression: DbCompression,
+    /// Compression of specific columns.
+    pub column_compression: Vec<(Column, DbCompression)>,
+}
+
+impl DbTuning {
+    /// The tuning of the given profile.
+    pub fn profile(profile: DbProfile) -> Self {
+        let cores = std::thread::available_parallelism().map(|e| e.get() as i32).unwrap_or(1);
+
+        match profile {
+            DbProfile::SsdDefault => Self {
+                block_cache_size: 256 * MIB,
+                write_buffer_size: 64 * MIB,
+                max_open_files: -1,
+                background_jobs: i32::max(cores / 2, 1),
+                compression: DbCompression::Lz4,
+                column_compression: Vec::new(),
+            },
+            DbProfile::Throughput => Self {
+                block_cache_size: 2048 * MIB,
+                write_buffer_size: 256 * MIB,
+                max_open_files: -1,
+                background_jobs: cores,
+                compression: DbCompression::Lz4,
+                // the trie logs and receipts are written once and rarely read
+                column_compression: COLD_COLUMNS.into_iter().map(|column| (column, DbCompression::Zstd)).collect(),
+            },
+            DbProfile::LowMemory => Self {
+                block_cache_size: 32 * MIB,
+                write_buffer_size: 8 * MIB,
+                max_open_files: 256,
+                background_jobs: 2,
+                compression: DbCompression::Zstd,
+                column_compression: Vec::new(),
+            },
+        }
+    }
+
+    /// The compression of `column`.
+    pub fn compression_of(&self, column: Column) -> DbCompression {
+        self.column_compression
+            .iter()
+            .rev()
+            .find(|(tuned, _)| *tuned == column)
+            .map_or(self.compression, |(_, compression)| *compression)
+    }
+
+    /// Applies the instance wide settings to `opts`.
+    pub(crate) fn apply(&self, opts: &mut Options) {
+        opts.increase_parallelism(self.background_jobs.max(1));
+        opts.set_max_background_jobs(self.background_jobs.max(1));
+        opts.set_max_open_files(self.max_open_files);
+    }
+
+    /// The options of `column`, whose blocks are cached in `cache`.
+    pub(crate) fn column_options(&self, column: Column, cache: &Cache) -> Options {
+        let mut block_opts = BlockBasedOptions::default();
+        block_opts.set_block_cache(cache);
+
+        let mut opts = column.rocksdb_options();
+        opts.set_block_based_table_factory(&block_opts);
+        opts.set_write_buffer_size(self.write_buffer_size);
+        opts.set_compression_type(self.compression_of(column).into());
+        opts
+    }
+
+    /// A block cache of the configured size, for one instance.
+    pub(crate) fn block_cache(&self) -> Cache {
+        Cache::new_lru_cache(self.block_cache_size)
+    }
+}
+
+impl Default for DbTuning {
+    fn default() -> Self {
+        Self::profile(DbProfile::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn column_compression_overrides_the_profile() {
+        let mut tuning = DbTuning::profile(DbProfile::Throughput);
+        assert_eq!(tuning.compression_of(Column::BonsaiClassesLog), DbCompression::Zstd);
+        assert_eq!(tuning.compression_of(Column::BonsaiClassesTrie), DbCompression::Lz4);
+
+        tuning.column_compression.push((Column::BonsaiClassesLog, DbCompression::None));
+        assert_eq!(tuning.compression_of(Column::BonsaiClassesLog), DbCompression::None);
+    }
+}
@@ -0,0 +1,200 @@
+//! Persistent queues for the data sent out of the node.
+//!
+//! Subsystems delivering items to downstream consumers (webhooks, analytics exporters...) push
+//! them to a [`DeliveryQueue`] rather than sending them directly. Items are persisted in the
+//! database before [`DeliveryQueue::push`] returns, and a [`DeliveryWorker`] then hands them to
+//! a [`DeliverySink`] in the background. Items are only removed once the sink reports they were
+//! delivered: failed deliveries are retried with exponential backoff, including across restarts.
+//!
+//! Delivery is at-least-once, and items may be delivered out of order when some of them are
+//! retried. Consumers should deduplicate on their side if needed.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use mc_db::{DbError, DeoxysBackend, QueuedDelivery};
+use tokio::sync::Notify;
+
+/// Maximum number of items handed to the sink in one go.
+const BATCH_SIZE: usize = 64;
+/// How often the queue is checked for items which are due for a retry.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeliveryError {
+    /// The consumer could not be reached or failed to process the item, which is retried later.
+    #[error("transient delivery failure: {0}")]
+    Transient(String),
+    /// The consumer refused the item, which will never succeed and is dropped.
+    #[error("item rejected by the consumer: {0}")]
+    Rejected(String),
+}
+
+/// A downstream consumer of queued items.
+#[async_trait]
+pub trait DeliverySink: Send + Sync + 'static {
+    /// Deliver an item, returning once the consumer acknowledged it.
+    async fn deliver(&self, payload: &[u8]) -> Result<(), DeliveryError>;
+}
+
+/// How failed deliveries are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Delay before the first retry, doubled after each failed attempt.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Number of attempts after which an item is dropped. Items are retried forever if unset.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { initial_backoff: Duration::from_secs(1), max_backoff: Duration::from_secs(10 * 60), max_attempts: None }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the next attempt of an item which failed `attempts` times.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+
+    fn gives_up(&self, attempts: u32) -> bool {
+        self.max_attempts.is_some_and(|max| attempts >= max)
+    }
+}
+
+/// Handle used to push items to a queue.
+#[derive(Clone)]
+pub struct DeliveryQueue {
+    topic: Arc<str>,
+    notify: Arc<Notify>,
+}
+
+impl DeliveryQueue {
+    /// Persist an item and wake the worker up, returning the id of the item in the queue.
+    pub fn push(&self, payload: Vec<u8>) -> Result<u64, DbError> {
+        let id = DeoxysBackend::delivery().enqueue(&self.topic, payload)?;
+        self.notify.notify_one();
+        Ok(id)
+    }
+
+    /// Number of items which were not delivered yet.
+    pub fn pending(&self) -> Result<usize, DbError> {
+        DeoxysBackend::delivery().len(&self.topic)
+    }
+}
+
+/// Background task delivering the items of a queue to its sink.
+pub struct DeliveryWorker<S> {
+    topic: Arc<str>,
+    sink: S,
+    policy: RetryPolicy,
+    notify: Arc<Notify>,
+}
+
+/// Create the queue `topic`, along with the worker delivering its items to `sink`.
+///
+/// Items left over by a previous run are delivered as soon as the worker starts. The topic
+/// identifies the queue in the database: it must be unique to the subsystem, stable across
+/// releases, and must not contain any `/`.
+pub fn new<S: DeliverySink>(topic: &str, sink: S, policy: RetryPolicy) -> (DeliveryWorker<S>, DeliveryQueue) {
+    assert!(!topic.contains('/'), "Delivery queue topics must not contain '/'");
+
+    let topic: Arc<str> = topic.into();
+    let notify = Arc::new(Notify::new());
+    let worker = DeliveryWorker { topic: Arc::clone(&topic), sink, policy, notify: Arc::clone(&notify) };
+
+    (worker, DeliveryQueue { topic, notify })
+}
+
+impl<S: DeliverySink> DeliveryWorker<S> {
+    pub async fn run(self) {
+        loop {
+            match self.deliver_due().await {
+                // more items might be due right away
+                Ok(BATCH_SIZE) => continue,
+                Ok(_) => {}
+                Err(e) => log::error!("📤 Failed to access the `{}` delivery queue: {e}", self.topic),
+            }
+
+            tokio::select! {
+                _ = self.notify.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    }
+
+    /// Attempt to deliver the items which are due, returning how many were attempted.
+    async fn deliver_due(&self) -> Result<usize, DbError> {
+        let queue = DeoxysBackend::delivery();
+        let due = queue.due(&self.topic, now_millis(), BATCH_SIZE)?;
+        let attempted = due.len();
+
+        for mut delivery in due {
+            match self.sink.deliver(&delivery.payload).await {
+                Ok(()) => queue.ack(&self.topic, delivery.id)?,
+                Err(DeliveryError::Rejected(e)) => {
+                    log::error!("📤 Dropping item {} of the `{}` delivery queue: {e}", delivery.id, self.topic);
+                    queue.ack(&self.topic, delivery.id)?;
+                }
+                Err(DeliveryError::Transient(e)) => self.retry_later(&mut delivery, e)?,
+            }
+        }
+
+        Ok(attempted)
+    }
+
+    fn retry_later(&self, delivery: &mut QueuedDelivery, error: String) -> Result<(), DbError> {
+        let queue = DeoxysBackend::delivery();
+        delivery.attempts = delivery.attempts.saturating_add(1);
+
+        if self.policy.gives_up(delivery.attempts) {
+            log::error!(
+                "📤 Dropping item {} of the `{}` delivery queue after {} attempts: {error}",
+                delivery.id,
+                self.topic,
+                delivery.attempts
+            );
+            return queue.ack(&self.topic, delivery.id);
+        }
+
+        let backoff = self.policy.backoff(delivery.attempts);
+        log::debug!(
+            "📤 Failed to deliver item {} of the `{}` queue, retrying in {backoff:?}: {error}",
+            delivery.id,
+            self.topic
+        );
+        delivery.not_before = now_millis().saturating_add(backoff.as_millis() as u64);
+        queue.reschedule(&self.topic, delivery)
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_millis() as u64).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(10),
+            max_attempts: Some(6),
+        };
+
+        let backoffs: Vec<_> = (1..=6).map(|attempts| policy.backoff(attempts).as_secs()).collect();
+        assert_eq!(backoffs, vec![1, 2, 4, 8, 10, 10]);
+        // does not overflow after many attempts
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(10));
+
+        assert!(!policy.gives_up(5));
+        assert!(policy.gives_up(6));
+        assert!(!RetryPolicy::default().gives_up(u32::MAX));
+    }
+}
@@ -0,0 +1,310 @@
+//! Genesis of custom chains, for devnets and appchains.
+//!
+//! A [`CustomGenesis`] declares the classes listed in its `genesis.json`, deploys the fee tokens at
+//! the ETH and STRK addresses of the networks, and deploys the configured accounts, each funded in
+//! both fee tokens. The [`GenesisBuilder`] turns it into the storage of the chain at genesis.
+//!
+//! The accounts and fee tokens are written in the storage layout of the OpenZeppelin contracts: the
+//! account stores its public key in `Account_public_key`, and the fee tokens store their balances
+//! and supply as `u256` in `ERC20_balances` and `ERC20_total_supply`.
+//!
+//! With an [account seed](GenesisBuilder::with_account_seed), the private keys of the accounts are
+//! derived from the seed instead of being read from the `genesis.json`, so that reproducible runs
+//! deploy the same accounts.
+use std::path::{Path, PathBuf};
+
+use blockifier::execution::contract_class::ContractClass as StarknetContractClass;
+use mp_genesis_config::{
+    ClassHash, ContractClass, CustomGenesis, GenesisData, HexFelt, PredeployedAccount, StorageKey, StorageValue,
+};
+use starknet_core::utils::{cairo_short_string_to_felt, get_contract_address, get_storage_var_address};
+use starknet_crypto::{get_public_key, poseidon_hash_many, FieldElement};
+
+use crate::LoadGenesisDataError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum GenesisBuildError {
+    #[error("Unable to read class {class_hash:#x} at {path}: {reason}")]
+    InvalidClass { class_hash: FieldElement, path: String, reason: String },
+    #[error("Class {0:#x} is not declared by the genesis")]
+    UndeclaredClass(FieldElement),
+}
+
+/// The state of a custom chain at genesis.
+#[derive(Clone)]
+pub struct BuiltGenesis {
+    /// Contracts deployed and storage written at genesis.
+    pub data: GenesisData,
+    /// Classes declared at genesis.
+    pub classes: Vec<(ClassHash, StarknetContractClass)>,
+    /// The funded accounts, along with their keys.
+    pub accounts: Vec<PredeployedAccount>,
+}
+
+/// Builds the state at genesis of a [`CustomGenesis`].
+pub struct GenesisBuilder {
+    /// Directory the paths of the classes are relative to.
+    base_path: PathBuf,
+    genesis: CustomGenesis,
+    /// Seed the private keys of the accounts are derived from, if set.
+    account_seed: Option<u64>,
+}
+
+impl GenesisBuilder {
+    pub fn new(base_path: PathBuf, genesis: CustomGenesis) -> Self {
+        Self { base_path, genesis, account_seed: None }
+    }
+
+    /// Derives the private keys of the accounts from `seed` and their position in the genesis,
+    /// ignoring the ones of the `genesis.json`.
+    pub fn with_account_seed(mut self, seed: u64) -> Self {
+        self.account_seed = Some(seed);
+        self
+    }
+
+    /// Reads the genesis at `path`, the paths of its classes being relative to its directory.
+    pub fn from_file(path: &Path) -> Result<Self, LoadGenesisDataError> {
+        let content = std::fs::read_to_string(path).map_err(|_| LoadGenesisDataError::InvalidPath)?;
+        let genesis = serde_json::from_str(&content).map_err(|_| LoadGenesisDataError::InvalidJson)?;
+        let base_path = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(Self::new(base_path, genesis))
+    }
+
+    pub fn build(&self) -> Result<BuiltGenesis, GenesisBuildError> {
+        let (data, accounts) = self.state()?;
+        let classes = self
+            .genesis
+            .classes
+            .iter()
+            .map(|class| Ok((class.class_hash, self.load_class(class.class_hash, &class.class)?)))
+            .collect::<Result<_, GenesisBuildError>>()?;
+
+        Ok(BuiltGenesis { data, classes, accounts })
+    }
+
+    /// The contracts and storage at genesis, and the funded accounts.
+    fn state(&self) -> Result<(GenesisData, Vec<PredeployedAccount>), GenesisBuildError> {
+        for class_hash in [self.genesis.account_class_hash, self.genesis.erc20_class_hash] {
+            if !self.genesis.classes.iter().any(|class| class.class_hash.0 == class_hash.0) {
+                return Err(GenesisBuildError::UndeclaredClass(class_hash.0));
+            }
+        }
+
+        let mut contracts = Vec::new();
+        let mut storage = Vec::new();
+        let mut accounts = Vec::new();
+        let mut balances = Vec::new();
+        let mut total_supply = 0u128;
+        for (index, account) in self.genesis.accounts.iter().enumerate() {
+            let private_key = match self.account_seed {
+                Some(seed) => seeded_private_key(seed, index),
+                None => account.private_key.0,
+            };
+            let public_key = get_public_key(&private_key);
+            let address =
+                get_contract_address(public_key, self.genesis.account_class_hash.0, &[public_key], FieldElement::ZERO);
+
+            contracts.push((HexFelt(address), self.genesis.account_class_hash));
+            storage.push((HexFelt(address), vec![(storage_var("Account_public_key", &[]), HexFelt(public_key))]));
+            balances.extend(u256_storage("ERC20_balances", &[address], account.balance));
+            total_supply = total_supply.saturating_add(account.balance);
+            accounts.push(PredeployedAccount {
+                contract_address: HexFelt(address),
+                class_hash: self.genesis.account_class_hash,
+                name: account.name.clone(),
+                private_key: Some(private_key.to_bytes_be().to_vec()),
+                public_key: HexFelt(public_key),
+            });
+        }
+
+        let fee_tokens = [
+            (CustomGenesis::eth_fee_token_address(), "Ether", "ETH"),
+            (CustomGenesis::strk_fee_token_address(), "Starknet Token", "STRK"),
+        ];
+        for (address, name, symbol) in fee_tokens {
+            let mut token_storage = vec![
+                (storage_var("ERC20_name", &[]), short_string(name)),
+                (storage_var("ERC20_symbol", &[]), short_string(symbol)),
+                (storage_var("ERC20_decimals", &[]), HexFelt(FieldElement::from(18u8))),
+            ];
+            token_storage.extend(u256_storage("ERC20_total_supply", &[], total_supply));
+            token_storage.extend(balances.iter().copied());
+
+            contracts.push((address, self.genesis.erc20_class_hash));
+            storage.push((address, token_storage));
+        }
+
+        let sierra_class_hash_to_casm_class_hash = self
+            .genesis
+            .classes
+            .iter()
+            .filter_map(|class| {
+                class.compiled_class_hash.map(|compiled_class_hash| (class.class_hash, compiled_class_hash))
+            })
+            .collect();
+
+        let data = GenesisData {
+            contracts,
+            sierra_class_hash_to_casm_class_hash,
+            storage,
+            strk_fee_token_address: CustomGenesis::strk_fee_token_address(),
+            eth_fee_token_address: CustomGenesis::eth_fee_token_address(),
+        };
+        Ok((data, accounts))
+    }
+
+    /// Reads a class, from its JSON artifact if it is given as a path.
+    ///
+    /// Legacy classes are read from their compiled JSON, Sierra classes from their CASM.
+    fn load_class(
+        &self,
+        class_hash: ClassHash,
+        class: &ContractClass,
+    ) -> Result<StarknetContractClass, GenesisBuildError> {
+        let (path, version) = match class {
+            ContractClass::Class(class) => return Ok(class.clone()),
+            ContractClass::Path { path, version } => (self.base_path.join(path), *version),
+        };
+        let invalid = |reason: String| GenesisBuildError::InvalidClass {
+            class_hash: class_hash.0,
+            path: path.display().to_string(),
+            reason,
+        };
+
+        let content = std::fs::read_to_string(&path).map_err(|e| invalid(e.to_string()))?;
+        match version {
+            0 => Ok(StarknetContractClass::V0(serde_json::from_str(&content).map_err(|e| invalid(e.to_string()))?)),
+            1 => {
+                let casm_class: cairo_lang_starknet_classes::casm_contract_class::CasmContractClass =
+                    serde_json::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+                Ok(StarknetContractClass::V1(casm_class.try_into().map_err(|e| invalid(format!("{e:?}")))?))
+            }
+            version => Err(invalid(format!("unsupported class version {version}"))),
+        }
+    }
+}
+
+/// The private key of the account at `index` in the genesis, derived from `seed`.
+fn seeded_private_key(seed: u64, index: usize) -> FieldElement {
+    let domain = cairo_short_string_to_felt("deoxys_genesis_account").expect("short strings fit in a felt");
+    poseidon_hash_many(&[domain, FieldElement::from(seed), FieldElement::from(index)])
+}
+
+fn storage_var(name: &str, keys: &[FieldElement]) -> StorageKey {
+    HexFelt(get_storage_var_address(name, keys).expect("storage variable names are ASCII"))
+}
+
+fn short_string(value: &str) -> StorageValue {
+    HexFelt(cairo_short_string_to_felt(value).expect("short strings fit in a felt"))
+}
+
+/// The storage of a `u256` variable, its low 128 bits at the address of the variable and its high
+/// bits at the next one. The high bits of a `u128` are always zero.
+fn u256_storage(name: &str, keys: &[FieldElement], value: u128) -> [(StorageKey, StorageValue); 2] {
+    let low = storage_var(name, keys);
+    let high = HexFelt(low.0 + FieldElement::ONE);
+    [(low, HexFelt(FieldElement::from(value))), (high, HexFelt(FieldElement::ZERO))]
+}
+
+#[cfg(test)]
+mod tests {
+    use mp_genesis_config::{ContractAddress, GenesisAccount, GenesisClass};
+
+    use super::*;
+
+    fn felt(value: &str) -> HexFelt {
+        HexFelt(FieldElement::from_hex_be(value).unwrap())
+    }
+
+    fn storage_of(data: &GenesisData, address: ContractAddress) -> &[(StorageKey, StorageValue)] {
+        &data.storage.iter().find(|(contract, _)| contract.0 == address.0).expect("contract has storage").1
+    }
+
+    fn value_at(storage: &[(StorageKey, StorageValue)], key: StorageKey) -> FieldElement {
+        storage.iter().find(|(k, _)| k.0 == key.0).expect("key is set").1.0
+    }
+
+    #[test]
+    fn accounts_are_deployed_and_funded() {
+        let account_class_hash = felt("0x1");
+        let erc20_class_hash = felt("0x2");
+        // classes are only read when building the whole genesis
+        let class = |class_hash| GenesisClass {
+            class_hash,
+            compiled_class_hash: None,
+            class: ContractClass::Path { path: "unused.json".to_string(), version: 0 },
+        };
+        let genesis = CustomGenesis {
+            classes: vec![class(account_class_hash), class(erc20_class_hash)],
+            account_class_hash,
+            erc20_class_hash,
+            accounts: vec![
+                GenesisAccount { name: "alice".to_string(), private_key: felt("0x1234"), balance: 1000 },
+                GenesisAccount { name: "bob".to_string(), private_key: felt("0x5678"), balance: 500 },
+            ],
+        };
+
+        let (data, accounts) = GenesisBuilder::new(PathBuf::new(), genesis).state().unwrap();
+        assert_eq!(accounts.len(), 2);
+        // two accounts and two fee tokens
+        assert_eq!(data.contracts.len(), 4);
+
+        let alice = &accounts[0];
+        let public_key = get_public_key(&FieldElement::from_hex_be("0x1234").unwrap());
+        assert_eq!(alice.public_key.0, public_key);
+        assert_eq!(
+            alice.contract_address.0,
+            get_contract_address(public_key, account_class_hash.0, &[public_key], FieldElement::ZERO)
+        );
+        let account_storage = storage_of(&data, alice.contract_address);
+        assert_eq!(value_at(account_storage, storage_var("Account_public_key", &[])), public_key);
+
+        let eth = storage_of(&data, CustomGenesis::eth_fee_token_address());
+        let [(balance, _), _] = u256_storage("ERC20_balances", &[alice.contract_address.0], 0);
+        assert_eq!(value_at(eth, balance), FieldElement::from(1000u128));
+        let [(supply, _), _] = u256_storage("ERC20_total_supply", &[], 0);
+        assert_eq!(value_at(eth, supply), FieldElement::from(1500u128));
+    }
+
+    #[test]
+    fn seeded_accounts_have_keys_derived_from_the_seed() {
+        let genesis = |private_key| CustomGenesis {
+            classes: vec![GenesisClass {
+                class_hash: felt("0x1"),
+                compiled_class_hash: None,
+                class: ContractClass::Path { path: "unused.json".to_string(), version: 0 },
+            }],
+            account_class_hash: felt("0x1"),
+            erc20_class_hash: felt("0x1"),
+            accounts: vec![
+                GenesisAccount { name: "alice".to_string(), private_key, balance: 1000 },
+                GenesisAccount { name: "bob".to_string(), private_key, balance: 500 },
+            ],
+        };
+        let accounts = |genesis, seed| {
+            GenesisBuilder::new(PathBuf::new(), genesis).with_account_seed(seed).state().unwrap().1
+        };
+
+        // the keys of the genesis.json are ignored
+        let seeded = accounts(genesis(felt("0x1234")), 7);
+        assert_eq!(seeded[0].private_key, accounts(genesis(felt("0x5678")), 7)[0].private_key);
+        assert_eq!(seeded[0].private_key, Some(seeded_private_key(7, 0).to_bytes_be().to_vec()));
+        assert_ne!(seeded[0].contract_address.0, seeded[1].contract_address.0);
+        assert_ne!(seeded[0].private_key, accounts(genesis(felt("0x1234")), 8)[0].private_key);
+    }
+
+    #[test]
+    fn account_class_must_be_declared() {
+        let genesis = CustomGenesis {
+            classes: vec![],
+            account_class_hash: felt("0x1"),
+            erc20_class_hash: felt("0x2"),
+            accounts: vec![],
+        };
+
+        assert!(matches!(
+            GenesisBuilder::new(PathBuf::new(), genesis).state(),
+            Err(GenesisBuildError::UndeclaredClass(class_hash)) if class_hash == FieldElement::ONE
+        ));
+    }
+}
@@ -1,3 +1,4 @@
+pub mod builder;
 mod constants;
 
 use std::path::PathBuf;
@@ -1,8 +1,10 @@
 use std::num::NonZeroU128;
+use std::time::Instant;
 
 use blockifier::blockifier::block::GasPrices;
 use mc_db::DeoxysBackend;
 use mc_rpc::utils::get_block_by_block_hash;
+use mc_sync::lifecycle::{self, Phase};
 use mp_digest_log::{find_starknet_block, FindLogError};
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
@@ -31,6 +33,7 @@ where
     // wrapped Starknet block it contains is the same that we can find in the storage at this height.
     // Then we will store the two block hashes (wrapper and wrapped) alongside in our db.
 
+    let start = Instant::now();
     let substrate_block_hash = header.hash();
     match mp_digest_log::find_starknet_block(header.digest()) {
         Ok(digest_starknet_block) => {
@@ -95,7 +98,10 @@ where
                                 .set(f64::from_u128(l1_gas_price.strk_l1_gas_price.into()).unwrap_or(f64::MIN))
                         }
 
-                        DeoxysBackend::mapping().write_hashes(mapping_commitment).map_err(|e| anyhow::anyhow!(e))
+                        let block_number = mapping_commitment.block_number;
+                        DeoxysBackend::mapping().write_hashes(mapping_commitment).map_err(|e| anyhow::anyhow!(e))?;
+                        lifecycle::record(Phase::Mapping, block_number, start.elapsed());
+                        Ok(())
                     }
                 }
                 // If there is not Starknet block in this Substrate block, we write it in the db
@@ -0,0 +1,89 @@
+//! OpenTelemetry traces for Deoxys.
+//!
+//! When enabled, spans for RPC requests, block imports and feeder gateway fetches are exported to
+//! an OTLP collector (Jaeger, Tempo...), so that operators can follow where latency is spent across
+//! services.
+//!
+//! Spans are created through the global tracer provider: until [`init`] is called, all helpers of
+//! this crate are no-ops.
+
+use std::future::Future;
+
+use opentelemetry::global::{self, BoxedSpan};
+use opentelemetry::trace::{FutureExt, TraceContextExt, TraceError, Tracer};
+use opentelemetry::Context;
+pub use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::{self as sdktrace, Sampler};
+use opentelemetry_sdk::Resource;
+
+/// Name of the instrumentation library, as reported in the exported spans.
+const TRACER_NAME: &str = "deoxys";
+
+/// Configuration of the OTLP traces exporter.
+#[derive(Debug, Clone)]
+pub struct OtelConfig {
+    /// gRPC endpoint of the OTLP collector, e.g. `http://localhost:4317`.
+    pub endpoint: String,
+    /// Name of the service the spans are reported under.
+    pub service_name: String,
+    /// Ratio of the traces which are sampled, between `0.0` and `1.0`.
+    pub sampling_ratio: f64,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum OtelError {
+    #[error("Invalid sampling ratio `{0}`, expected a value between 0 and 1")]
+    InvalidSamplingRatio(f64),
+    #[error("Failed to install the OTLP traces exporter: {0}")]
+    Install(#[from] TraceError),
+}
+
+/// Installs the OTLP exporter as the global tracer provider.
+///
+/// Must be called from within a tokio runtime, which is used to export spans in batches.
+pub fn init(config: &OtelConfig) -> Result<(), OtelError> {
+    if !(0.0..=1.0).contains(&config.sampling_ratio) {
+        return Err(OtelError::InvalidSamplingRatio(config.sampling_ratio));
+    }
+
+    // child spans follow the sampling decision of their parent, so that traces are never partial
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sampling_ratio)));
+
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(&config.endpoint))
+        .with_trace_config(
+            sdktrace::config()
+                .with_sampler(sampler)
+                .with_resource(Resource::new(vec![KeyValue::new("service.name", config.service_name.clone())])),
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+
+    log::info!("🔭 Exporting traces to {} (sampling ratio: {})", config.endpoint, config.sampling_ratio);
+    Ok(())
+}
+
+/// Flushes the remaining spans and shuts down the exporter.
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+/// Starts a new span, child of the currently active one.
+///
+/// The span ends when dropped.
+pub fn start_span(name: &'static str, attributes: Vec<KeyValue>) -> BoxedSpan {
+    let tracer = global::tracer(TRACER_NAME);
+    tracer.span_builder(name).with_attributes(attributes).start(&tracer)
+}
+
+/// Runs `f` inside of a new span.
+pub fn in_span<T>(name: &'static str, attributes: Vec<KeyValue>, f: impl FnOnce() -> T) -> T {
+    let _guard = Context::current_with_span(start_span(name, attributes)).attach();
+    f()
+}
+
+/// Runs `future` inside of a new span, which ends once the future completes.
+pub async fn in_span_async<F: Future>(name: &'static str, attributes: Vec<KeyValue>, future: F) -> F::Output {
+    future.with_context(Context::current_with_span(start_span(name, attributes))).await
+}
@@ -0,0 +1,222 @@
+use std::io;
+
+use async_trait::async_trait;
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use libp2p::request_response;
+use prost::Message;
+
+use crate::protocol::{Request, Response, StarknetProtocol};
+
+/// Maximum size of an encoded request.
+const MAX_REQUEST_SIZE: usize = 1024;
+/// Maximum size of a single message of a response stream. Classes are the largest messages and are
+/// bounded by the gateway at a few MiB.
+pub(crate) const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+/// Maximum total size of the messages of a response stream.
+pub(crate) const MAX_RESPONSE_SIZE: usize = 128 * 1024 * 1024;
+/// Maximum number of messages in a response stream.
+const MAX_RESPONSE_MESSAGES: usize = 1 << 20;
+
+/// Encodes requests and responses as varint length-delimited protobuf messages.
+///
+/// A request is a single message, a response is a sequence of messages read until the peer closes
+/// the stream.
+#[derive(Debug, Clone, Default)]
+pub struct StarknetCodec;
+
+/// Reads a varint length-delimited message, returning `None` if the stream ended cleanly.
+async fn read_delimited<T>(io: &mut T, max_size: usize) -> io::Result<Option<Vec<u8>>>
+where
+    T: AsyncRead + Unpin + Send,
+{
+    let mut len: u64 = 0;
+    for i in 0..10 {
+        let mut byte = [0u8; 1];
+        if io.read(&mut byte).await? == 0 {
+            return match i {
+                0 => Ok(None),
+                _ => Err(io::ErrorKind::UnexpectedEof.into()),
+            };
+        }
+        len |= u64::from(byte[0] & 0x7f) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            let len = usize::try_from(len).unwrap_or(usize::MAX);
+            if len > max_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("message of {len} bytes exceeds the limit of {max_size} bytes"),
+                ));
+            }
+            let mut buf = vec![0u8; len];
+            io.read_exact(&mut buf).await?;
+            return Ok(Some(buf));
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "invalid message length"))
+}
+
+fn decode<M: Message + Default>(buf: &[u8]) -> io::Result<M> {
+    M::decode(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+async fn read_message<M, T>(io: &mut T, max_size: usize) -> io::Result<Option<M>>
+where
+    M: Message + Default,
+    T: AsyncRead + Unpin + Send,
+{
+    read_delimited(io, max_size).await?.map(|buf| decode(&buf)).transpose()
+}
+
+async fn read_messages<M, T>(io: &mut T) -> io::Result<Vec<M>>
+where
+    M: Message + Default,
+    T: AsyncRead + Unpin + Send,
+{
+    let mut messages = Vec::new();
+    let mut size = 0;
+    while let Some(buf) = read_delimited(io, MAX_MESSAGE_SIZE.min(MAX_RESPONSE_SIZE - size)).await? {
+        if messages.len() == MAX_RESPONSE_MESSAGES {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "too many messages in response"));
+        }
+        size += buf.len();
+        messages.push(decode(&buf)?);
+    }
+    Ok(messages)
+}
+
+async fn write_messages<'a, M, T>(io: &mut T, messages: impl IntoIterator<Item = &'a M>) -> io::Result<()>
+where
+    M: Message + 'a,
+    T: AsyncWrite + Unpin + Send,
+{
+    for message in messages {
+        io.write_all(&message.encode_length_delimited_to_vec()).await?;
+    }
+    io.close().await
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "missing request")
+}
+
+#[async_trait]
+impl request_response::Codec for StarknetCodec {
+    type Protocol = StarknetProtocol;
+    type Request = Request;
+    type Response = Response;
+
+    async fn read_request<T>(&mut self, protocol: &StarknetProtocol, io: &mut T) -> io::Result<Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let request = match protocol {
+            StarknetProtocol::Headers => {
+                Request::Headers(read_message(io, MAX_REQUEST_SIZE).await?.ok_or_else(unexpected_eof)?)
+            }
+            StarknetProtocol::Events => {
+                Request::Events(read_message(io, MAX_REQUEST_SIZE).await?.ok_or_else(unexpected_eof)?)
+            }
+            StarknetProtocol::StateDiffs => {
+                Request::StateDiffs(read_message(io, MAX_REQUEST_SIZE).await?.ok_or_else(unexpected_eof)?)
+            }
+            StarknetProtocol::Classes => {
+                Request::Classes(read_message(io, MAX_REQUEST_SIZE).await?.ok_or_else(unexpected_eof)?)
+            }
+        };
+        Ok(request)
+    }
+
+    async fn read_response<T>(&mut self, protocol: &StarknetProtocol, io: &mut T) -> io::Result<Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let response = match protocol {
+            StarknetProtocol::Headers => Response::Headers(read_messages(io).await?),
+            StarknetProtocol::Events => Response::Events(read_messages(io).await?),
+            StarknetProtocol::StateDiffs => Response::StateDiffs(read_messages(io).await?),
+            StarknetProtocol::Classes => Response::Classes(read_messages(io).await?),
+        };
+        Ok(response)
+    }
+
+    async fn write_request<T>(&mut self, _protocol: &StarknetProtocol, io: &mut T, request: Request) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        match &request {
+            Request::Headers(request) => write_messages(io, [request]).await,
+            Request::Events(request) => write_messages(io, [request]).await,
+            Request::StateDiffs(request) => write_messages(io, [request]).await,
+            Request::Classes(request) => write_messages(io, [request]).await,
+        }
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _protocol: &StarknetProtocol,
+        io: &mut T,
+        response: Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        match &response {
+            Response::Headers(messages) => write_messages(io, messages).await,
+            Response::Events(messages) => write_messages(io, messages).await,
+            Response::StateDiffs(messages) => write_messages(io, messages).await,
+            Response::Classes(messages) => write_messages(io, messages).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+    use request_response::Codec;
+
+    use super::*;
+    use crate::proto;
+    use crate::proto::block_headers_response::HeaderMessage;
+
+    #[test]
+    fn headers_response_roundtrip() {
+        let header = proto::SignedBlockHeader { number: 42, transaction_count: 3, ..Default::default() };
+        let response = Response::Headers(vec![
+            proto::BlockHeadersResponse { header_message: Some(HeaderMessage::Header(header)) },
+            proto::BlockHeadersResponse { header_message: Some(HeaderMessage::Fin(proto::Fin {})) },
+        ]);
+
+        let mut io = Cursor::new(Vec::new());
+        block_on(StarknetCodec.write_response(&StarknetProtocol::Headers, &mut io, response.clone())).unwrap();
+
+        io.set_position(0);
+        let decoded = block_on(StarknetCodec.read_response(&StarknetProtocol::Headers, &mut io)).unwrap();
+        assert_eq!(decoded, response);
+    }
+
+    #[test]
+    fn oversized_response_is_rejected() {
+        let header = proto::SignedBlockHeader { protocol_version: "0".repeat(1 << 20), ..Default::default() };
+        let message = proto::BlockHeadersResponse { header_message: Some(HeaderMessage::Header(header)) };
+        let count = MAX_RESPONSE_SIZE / message.encoded_len() + 1;
+
+        let mut io = Cursor::new(Vec::new());
+        block_on(write_messages(&mut io, iter::repeat(&message).take(count))).unwrap();
+
+        io.set_position(0);
+        assert!(block_on(StarknetCodec.read_response(&StarknetProtocol::Headers, &mut io)).is_err());
+    }
+
+    #[test]
+    fn oversized_request_is_rejected() {
+        let mut encoded = Vec::new();
+        prost::encoding::encode_varint(MAX_REQUEST_SIZE as u64 + 1, &mut encoded);
+        encoded.resize(encoded.len() + MAX_REQUEST_SIZE + 1, 0);
+
+        let mut io = Cursor::new(encoded);
+        assert!(block_on(StarknetCodec.read_request(&StarknetProtocol::Headers, &mut io)).is_err());
+    }
+}
@@ -0,0 +1,32 @@
+//! Experimental support of the Starknet p2p protocol.
+//!
+//! Deoxys serves the blocks it has synced to other nodes over libp2p, using the request/response
+//! streams of the Starknet p2p specs:
+//!
+//! * `/starknet/headers`: signed block headers,
+//! * `/starknet/events`: the events emitted in blocks,
+//! * `/starknet/state_diffs`: the state diffs of blocks,
+//! * `/starknet/classes`: the classes declared in blocks.
+//!
+//! Each request covers a range of blocks (an [`proto::Iteration`]) and is answered with a stream of
+//! messages ending with a [`proto::Fin`].
+//!
+//! Responses are bounded in size: a peer serves as many whole blocks as fit, and is asked for the
+//! following ones again.
+//!
+//! The [`P2pHandle`] can be used to query peers with the same protocols. With
+//! [`P2pConfig::sync`], the sync pulls the state diffs and classes of blocks from the bootnodes,
+//! the streams above not carrying transactions yet. This subsystem is experimental and disabled by
+//! default.
+
+mod codec;
+pub mod proto;
+mod protocol;
+mod service;
+mod source;
+
+pub use codec::StarknetCodec;
+pub use libp2p::{Multiaddr, PeerId};
+pub use protocol::{Request, Response, StarknetProtocol};
+pub use service::{new, P2pConfig, P2pError, P2pHandle, P2pService};
+pub use source::{BlockSource, ClientBlockSource};
@@ -0,0 +1,219 @@
+//! Protobuf messages of the Starknet p2p protocol.
+//!
+//! These follow the message definitions of the Starknet p2p specs, restricted to the fields Deoxys
+//! is able to serve for now. Field tags match the specs so that unknown fields sent by other
+//! implementations are simply ignored.
+
+/// A field element, big-endian encoded.
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct Felt252 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub elements: Vec<u8>,
+}
+
+/// A hash, big-endian encoded.
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct Hash {
+    #[prost(bytes = "vec", tag = "1")]
+    pub elements: Vec<u8>,
+}
+
+/// A contract address, big-endian encoded.
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct Address {
+    #[prost(bytes = "vec", tag = "1")]
+    pub elements: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Direction {
+    Forward = 0,
+    Backward = 1,
+}
+
+/// A range of blocks to stream.
+///
+/// Only iterations starting from a block number are supported, not from a block hash.
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct Iteration {
+    #[prost(uint64, tag = "1")]
+    pub start_block: u64,
+    #[prost(enumeration = "Direction", tag = "3")]
+    pub direction: i32,
+    #[prost(uint64, tag = "4")]
+    pub limit: u64,
+    #[prost(uint64, tag = "5")]
+    pub step: u64,
+}
+
+/// Marks the end of a stream of responses.
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct Fin {}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct BlockHeadersRequest {
+    #[prost(message, optional, tag = "1")]
+    pub iteration: Option<Iteration>,
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct SignedBlockHeader {
+    #[prost(message, optional, tag = "1")]
+    pub block_hash: Option<Hash>,
+    #[prost(message, optional, tag = "2")]
+    pub parent_hash: Option<Hash>,
+    #[prost(uint64, tag = "3")]
+    pub number: u64,
+    #[prost(uint64, tag = "4")]
+    pub time: u64,
+    #[prost(message, optional, tag = "5")]
+    pub sequencer_address: Option<Address>,
+    #[prost(message, optional, tag = "6")]
+    pub state_root: Option<Hash>,
+    #[prost(uint64, tag = "7")]
+    pub transaction_count: u64,
+    #[prost(message, optional, tag = "8")]
+    pub transaction_commitment: Option<Hash>,
+    #[prost(uint64, tag = "9")]
+    pub event_count: u64,
+    #[prost(message, optional, tag = "10")]
+    pub event_commitment: Option<Hash>,
+    #[prost(string, tag = "11")]
+    pub protocol_version: String,
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct BlockHeadersResponse {
+    #[prost(oneof = "block_headers_response::HeaderMessage", tags = "1, 2")]
+    pub header_message: Option<block_headers_response::HeaderMessage>,
+}
+
+pub mod block_headers_response {
+    #[derive(Clone, PartialEq, Eq, prost::Oneof)]
+    pub enum HeaderMessage {
+        #[prost(message, tag = "1")]
+        Header(super::SignedBlockHeader),
+        #[prost(message, tag = "2")]
+        Fin(super::Fin),
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct EventsRequest {
+    #[prost(message, optional, tag = "1")]
+    pub iteration: Option<Iteration>,
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct Event {
+    #[prost(message, optional, tag = "1")]
+    pub transaction_hash: Option<Hash>,
+    #[prost(message, optional, tag = "2")]
+    pub from_address: Option<Felt252>,
+    #[prost(message, repeated, tag = "3")]
+    pub keys: Vec<Felt252>,
+    #[prost(message, repeated, tag = "4")]
+    pub data: Vec<Felt252>,
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct EventsResponse {
+    #[prost(oneof = "events_response::EventMessage", tags = "1, 2")]
+    pub event_message: Option<events_response::EventMessage>,
+}
+
+pub mod events_response {
+    #[derive(Clone, PartialEq, Eq, prost::Oneof)]
+    pub enum EventMessage {
+        #[prost(message, tag = "1")]
+        Event(super::Event),
+        #[prost(message, tag = "2")]
+        Fin(super::Fin),
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct StateDiffsRequest {
+    #[prost(message, optional, tag = "1")]
+    pub iteration: Option<Iteration>,
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct ContractStoredValue {
+    #[prost(message, optional, tag = "1")]
+    pub key: Option<Felt252>,
+    #[prost(message, optional, tag = "2")]
+    pub value: Option<Felt252>,
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct ContractDiff {
+    #[prost(message, optional, tag = "1")]
+    pub address: Option<Address>,
+    #[prost(message, optional, tag = "2")]
+    pub nonce: Option<Felt252>,
+    #[prost(message, optional, tag = "3")]
+    pub class_hash: Option<Hash>,
+    #[prost(message, repeated, tag = "4")]
+    pub values: Vec<ContractStoredValue>,
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct DeclaredClass {
+    #[prost(message, optional, tag = "1")]
+    pub class_hash: Option<Hash>,
+    /// Absent for Cairo 0 classes.
+    #[prost(message, optional, tag = "2")]
+    pub compiled_class_hash: Option<Hash>,
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct StateDiffsResponse {
+    #[prost(oneof = "state_diffs_response::StateDiffMessage", tags = "1, 2, 3")]
+    pub state_diff_message: Option<state_diffs_response::StateDiffMessage>,
+}
+
+pub mod state_diffs_response {
+    #[derive(Clone, PartialEq, Eq, prost::Oneof)]
+    pub enum StateDiffMessage {
+        #[prost(message, tag = "1")]
+        ContractDiff(super::ContractDiff),
+        #[prost(message, tag = "2")]
+        DeclaredClass(super::DeclaredClass),
+        #[prost(message, tag = "3")]
+        Fin(super::Fin),
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct ClassesRequest {
+    #[prost(message, optional, tag = "1")]
+    pub iteration: Option<Iteration>,
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct Class {
+    #[prost(message, optional, tag = "1")]
+    pub class_hash: Option<Hash>,
+    /// The class as compiled by the node, a SCALE encoded `ContractClassWrapper` as in the archives
+    /// of `export-classes`: nodes do not keep the Sierra program of the classes they compile.
+    #[prost(bytes = "vec", tag = "2")]
+    pub definition: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct ClassesResponse {
+    #[prost(oneof = "classes_response::ClassMessage", tags = "1, 2")]
+    pub class_message: Option<classes_response::ClassMessage>,
+}
+
+pub mod classes_response {
+    #[derive(Clone, PartialEq, Eq, prost::Oneof)]
+    pub enum ClassMessage {
+        #[prost(message, tag = "1")]
+        Class(super::Class),
+        #[prost(message, tag = "2")]
+        Fin(super::Fin),
+    }
+}
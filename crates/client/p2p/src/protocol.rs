@@ -0,0 +1,66 @@
+use libp2p::core::upgrade::ProtocolName;
+
+use crate::proto;
+
+/// The request/response protocols of the Starknet p2p specs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StarknetProtocol {
+    Headers,
+    Events,
+    StateDiffs,
+    Classes,
+}
+
+impl StarknetProtocol {
+    pub const ALL: &'static [StarknetProtocol] =
+        &[StarknetProtocol::Headers, StarknetProtocol::Events, StarknetProtocol::StateDiffs, StarknetProtocol::Classes];
+}
+
+impl ProtocolName for StarknetProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        match self {
+            StarknetProtocol::Headers => b"/starknet/headers/0.1.0-rc.0",
+            StarknetProtocol::Events => b"/starknet/events/0.1.0-rc.0",
+            StarknetProtocol::StateDiffs => b"/starknet/state_diffs/0.1.0-rc.0",
+            StarknetProtocol::Classes => b"/starknet/classes/0.1.0-rc.0",
+        }
+    }
+}
+
+/// A request sent to a peer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Headers(proto::BlockHeadersRequest),
+    Events(proto::EventsRequest),
+    StateDiffs(proto::StateDiffsRequest),
+    Classes(proto::ClassesRequest),
+}
+
+impl Request {
+    pub fn protocol(&self) -> StarknetProtocol {
+        match self {
+            Request::Headers(_) => StarknetProtocol::Headers,
+            Request::Events(_) => StarknetProtocol::Events,
+            Request::StateDiffs(_) => StarknetProtocol::StateDiffs,
+            Request::Classes(_) => StarknetProtocol::Classes,
+        }
+    }
+
+    pub fn iteration(&self) -> Option<&proto::Iteration> {
+        match self {
+            Request::Headers(request) => request.iteration.as_ref(),
+            Request::Events(request) => request.iteration.as_ref(),
+            Request::StateDiffs(request) => request.iteration.as_ref(),
+            Request::Classes(request) => request.iteration.as_ref(),
+        }
+    }
+}
+
+/// The stream of messages answering a [`Request`], the last one being a `Fin`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    Headers(Vec<proto::BlockHeadersResponse>),
+    Events(Vec<proto::EventsResponse>),
+    StateDiffs(Vec<proto::StateDiffsResponse>),
+    Classes(Vec<proto::ClassesResponse>),
+}
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::iter;
+use std::sync::Arc;
+
+use futures::StreamExt;
+use libp2p::core::upgrade;
+use libp2p::multiaddr::Protocol;
+use libp2p::request_response::{self, OutboundFailure, ProtocolSupport, RequestId, ResponseChannel};
+use libp2p::swarm::{DialError, SwarmBuilder, SwarmEvent};
+use libp2p::{identity, noise, tcp, yamux, Multiaddr, PeerId, Swarm, Transport, TransportError};
+use mp_hashers::poseidon::PoseidonHasher;
+use mp_hashers::HasherT;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::codec::{StarknetCodec, MAX_MESSAGE_SIZE, MAX_RESPONSE_SIZE};
+use crate::proto::{self, Direction};
+use crate::protocol::{Request, Response, StarknetProtocol};
+use crate::source::BlockSource;
+
+/// Maximum number of blocks served for a single request.
+const MAX_BLOCKS_PER_REQUEST: u64 = 128;
+
+/// Configuration of the p2p subsystem.
+#[derive(Debug, Clone)]
+pub struct P2pConfig {
+    /// Address to listen on for incoming connections.
+    pub listen_address: Multiaddr,
+    /// Peers to connect to on startup.
+    pub bootnodes: Vec<Multiaddr>,
+    /// Whether the sync pulls the state diffs and classes of blocks from the bootnodes whose
+    /// address holds their peer id.
+    pub sync: bool,
+    /// Seed the node identity is derived from, so that it keeps the same peer id across runs. A
+    /// new identity is generated if not set.
+    pub identity_seed: Option<u64>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum P2pError {
+    #[error("Failed to set up the noise handshake: {0}")]
+    Noise(#[from] noise::Error),
+    #[error("Failed to listen for incoming connections: {0}")]
+    Listen(#[from] TransportError<std::io::Error>),
+    #[error("Failed to dial peer: {0}")]
+    Dial(#[from] DialError),
+    #[error("Request to peer failed: {0}")]
+    Outbound(#[from] OutboundFailure),
+    #[error("The p2p service is not running")]
+    ServiceStopped,
+}
+
+enum Command {
+    Request { peer: PeerId, request: Request, sender: oneshot::Sender<Result<Response, P2pError>> },
+}
+
+/// Handle used to send requests to peers through the [`P2pService`].
+#[derive(Clone)]
+pub struct P2pHandle {
+    local_peer_id: PeerId,
+    bootnode_peers: Vec<PeerId>,
+    commands: mpsc::Sender<Command>,
+}
+
+impl std::fmt::Debug for P2pHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("P2pHandle")
+            .field("local_peer_id", &self.local_peer_id)
+            .field("bootnode_peers", &self.bootnode_peers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl P2pHandle {
+    pub fn local_peer_id(&self) -> PeerId {
+        self.local_peer_id
+    }
+
+    /// The peers of the bootnodes whose address holds their peer id, which are redialed when
+    /// disconnected.
+    pub fn bootnode_peers(&self) -> &[PeerId] {
+        &self.bootnode_peers
+    }
+
+    pub async fn request(&self, peer: PeerId, request: Request) -> Result<Response, P2pError> {
+        let (sender, receiver) = oneshot::channel();
+        self.commands.send(Command::Request { peer, request, sender }).await.map_err(|_| P2pError::ServiceStopped)?;
+        receiver.await.map_err(|_| P2pError::ServiceStopped)?
+    }
+
+    /// Fetches the headers of the blocks in `iteration` from `peer`.
+    pub async fn headers(
+        &self,
+        peer: PeerId,
+        iteration: proto::Iteration,
+    ) -> Result<Vec<proto::SignedBlockHeader>, P2pError> {
+        let request = Request::Headers(proto::BlockHeadersRequest { iteration: Some(iteration) });
+        let Response::Headers(messages) = self.request(peer, request).await? else {
+            unreachable!("responses are decoded according to the request protocol")
+        };
+
+        Ok(messages
+            .into_iter()
+            .filter_map(|message| match message.header_message {
+                Some(proto::block_headers_response::HeaderMessage::Header(header)) => Some(header),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Fetches the state diffs of the blocks in `iteration` from `peer`.
+    pub async fn state_diffs(
+        &self,
+        peer: PeerId,
+        iteration: proto::Iteration,
+    ) -> Result<Vec<proto::state_diffs_response::StateDiffMessage>, P2pError> {
+        use proto::state_diffs_response::StateDiffMessage;
+
+        let request = Request::StateDiffs(proto::StateDiffsRequest { iteration: Some(iteration) });
+        let Response::StateDiffs(messages) = self.request(peer, request).await? else {
+            unreachable!("responses are decoded according to the request protocol")
+        };
+
+        Ok(messages
+            .into_iter()
+            .filter_map(|message| match message.state_diff_message {
+                Some(StateDiffMessage::Fin(_)) | None => None,
+                message => message,
+            })
+            .collect())
+    }
+
+    /// Fetches the classes declared in the blocks in `iteration` from `peer`.
+    pub async fn classes(&self, peer: PeerId, iteration: proto::Iteration) -> Result<Vec<proto::Class>, P2pError> {
+        let request = Request::Classes(proto::ClassesRequest { iteration: Some(iteration) });
+        let Response::Classes(messages) = self.request(peer, request).await? else {
+            unreachable!("responses are decoded according to the request protocol")
+        };
+
+        Ok(messages
+            .into_iter()
+            .filter_map(|message| match message.class_message {
+                Some(proto::classes_response::ClassMessage::Class(class)) => Some(class),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+/// Serves the Starknet p2p protocols and forwards the requests of the [`P2pHandle`] to peers.
+pub struct P2pService<S> {
+    swarm: Swarm<request_response::Behaviour<StarknetCodec>>,
+    source: Arc<S>,
+    bootnodes: Vec<Multiaddr>,
+    commands: mpsc::Receiver<Command>,
+    pending_requests: HashMap<RequestId, oneshot::Sender<Result<Response, P2pError>>>,
+}
+
+/// The ed25519 secret key of the identity derived from `seed`: the hash of the seed, so that the
+/// whole key depends on it.
+fn identity_secret(seed: u64) -> [u8; 32] {
+    let preimage = [b"deoxys_p2p_identity".as_slice(), &seed.to_be_bytes()].concat();
+    PoseidonHasher::hash_bytes(&preimage).0.to_bytes_be()
+}
+
+/// Creates the p2p service, and the handle used to send requests through it.
+///
+/// A new identity is generated each time the node starts, unless an identity seed is set.
+pub fn new<S: BlockSource>(config: P2pConfig, source: Arc<S>) -> Result<(P2pService<S>, P2pHandle), P2pError> {
+    let keypair = match config.identity_seed {
+        Some(seed) => identity::Keypair::ed25519_from_bytes(identity_secret(seed))
+            .expect("32 bytes are a valid ed25519 secret key"),
+        None => identity::Keypair::generate_ed25519(),
+    };
+    let local_peer_id = PeerId::from(keypair.public());
+    let bootnode_peers: Vec<PeerId> = config.bootnodes.iter().filter_map(peer_id).collect();
+
+    let transport = tcp::tokio::Transport::new(tcp::Config::default().nodelay(true))
+        .upgrade(upgrade::Version::V1)
+        .authenticate(noise::Config::new(&keypair)?)
+        .multiplex(yamux::Config::default())
+        .boxed();
+
+    let behaviour = request_response::Behaviour::new(
+        StarknetCodec,
+        StarknetProtocol::ALL.iter().map(|protocol| (*protocol, ProtocolSupport::Full)),
+        request_response::Config::default(),
+    );
+
+    let mut swarm = SwarmBuilder::with_tokio_executor(transport, behaviour, local_peer_id).build();
+    swarm.listen_on(config.listen_address)?;
+    // so that the requests sent to the bootnodes redial them
+    for bootnode in &config.bootnodes {
+        if let Some(peer) = peer_id(bootnode) {
+            swarm.behaviour_mut().add_address(&peer, bootnode.clone());
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel(64);
+    let service =
+        P2pService { swarm, source, bootnodes: config.bootnodes, commands: receiver, pending_requests: HashMap::new() };
+
+    Ok((service, P2pHandle { local_peer_id, bootnode_peers, commands: sender }))
+}
+
+/// The peer id held by `address`, if any.
+fn peer_id(address: &Multiaddr) -> Option<PeerId> {
+    address.iter().find_map(|protocol| match protocol {
+        Protocol::P2p(hash) => PeerId::from_multihash(hash).ok(),
+        _ => None,
+    })
+}
+
+impl<S: BlockSource> P2pService<S> {
+    pub async fn run(mut self) {
+        log::info!("🌐 Starting p2p service with peer id {}", self.swarm.local_peer_id());
+
+        for bootnode in std::mem::take(&mut self.bootnodes) {
+            if let Err(e) = self.swarm.dial(bootnode.clone()) {
+                log::warn!("⚠️ Failed to dial bootnode {bootnode}: {e}");
+            }
+        }
+
+        // responses are built on blocking threads, as they read from the database
+        let (response_sender, mut response_receiver) =
+            mpsc::unbounded_channel::<(ResponseChannel<Response>, Response)>();
+
+        loop {
+            tokio::select! {
+                event = self.swarm.select_next_some() => self.on_swarm_event(event, &response_sender),
+                Some(command) = self.commands.recv() => self.on_command(command),
+                Some((channel, response)) = response_receiver.recv() => {
+                    if self.swarm.behaviour_mut().send_response(channel, response).is_err() {
+                        log::debug!("Peer closed the connection before the response was sent");
+                    }
+                }
+            }
+        }
+    }
+
+    fn on_command(&mut self, command: Command) {
+        match command {
+            Command::Request { peer, request, sender } => {
+                let request_id = self.swarm.behaviour_mut().send_request(&peer, request);
+                self.pending_requests.insert(request_id, sender);
+            }
+        }
+    }
+
+    fn on_swarm_event<E: std::fmt::Debug>(
+        &mut self,
+        event: SwarmEvent<request_response::Event<Request, Response>, E>,
+        response_sender: &mpsc::UnboundedSender<(ResponseChannel<Response>, Response)>,
+    ) {
+        match event {
+            SwarmEvent::NewListenAddr { address, .. } => log::info!("🌐 Listening for p2p connections on {address}"),
+            SwarmEvent::ConnectionEstablished { peer_id, .. } => log::debug!("Connected to peer {peer_id}"),
+            SwarmEvent::ConnectionClosed { peer_id, .. } => log::debug!("Disconnected from peer {peer_id}"),
+            SwarmEvent::Behaviour(request_response::Event::Message { peer, message }) => match message {
+                request_response::Message::Request { request, channel, .. } => {
+                    log::debug!("Serving {:?} request from {peer}", request.protocol());
+                    let source = Arc::clone(&self.source);
+                    let response_sender = response_sender.clone();
+                    tokio::task::spawn_blocking(move || {
+                        let response = serve(source.as_ref(), &request);
+                        let _ = response_sender.send((channel, response));
+                    });
+                }
+                request_response::Message::Response { request_id, response } => {
+                    if let Some(sender) = self.pending_requests.remove(&request_id) {
+                        let _ = sender.send(Ok(response));
+                    }
+                }
+            },
+            SwarmEvent::Behaviour(request_response::Event::OutboundFailure { request_id, error, .. }) => {
+                if let Some(sender) = self.pending_requests.remove(&request_id) {
+                    let _ = sender.send(Err(error.into()));
+                }
+            }
+            SwarmEvent::Behaviour(request_response::Event::InboundFailure { peer, error, .. }) => {
+                log::debug!("Failed to serve request from {peer}: {error}");
+            }
+            event => log::trace!("p2p event: {event:?}"),
+        }
+    }
+}
+
+/// Block numbers covered by `iteration`, bounded by [`MAX_BLOCKS_PER_REQUEST`].
+fn block_numbers(iteration: &proto::Iteration) -> impl Iterator<Item = u64> {
+    let step = iteration.step.max(1);
+    let backward = iteration.direction == Direction::Backward as i32;
+    let limit = iteration.limit.clamp(1, MAX_BLOCKS_PER_REQUEST);
+
+    iter::successors(
+        Some(iteration.start_block),
+        move |n| if backward { n.checked_sub(step) } else { n.checked_add(step) },
+    )
+    .take(limit as usize)
+}
+
+/// Builds the response to `request`, stopping at the first block the source does not have.
+///
+/// Blocks are served whole, as long as the response stays within the [`MAX_RESPONSE_SIZE`] its
+/// peer accepts: the peer requests the following blocks again.
+fn serve<S: BlockSource>(source: &S, request: &Request) -> Response {
+    use proto::block_headers_response::HeaderMessage;
+    use proto::classes_response::ClassMessage;
+    use proto::events_response::EventMessage;
+    use proto::state_diffs_response::StateDiffMessage;
+
+    let block_numbers: Vec<u64> =
+        request.iteration().map(|iteration| block_numbers(iteration).collect()).unwrap_or_default();
+
+    match request {
+        Request::Headers(_) => Response::Headers(
+            collect(block_numbers, |n| source.header(n).map(|header| vec![HeaderMessage::Header(header)]))
+                .into_iter()
+                .chain(iter::once(HeaderMessage::Fin(proto::Fin {})))
+                .map(|message| proto::BlockHeadersResponse { header_message: Some(message) })
+                .collect(),
+        ),
+        Request::Events(_) => Response::Events(
+            collect(block_numbers, |n| {
+                source.events(n).map(|events| events.into_iter().map(EventMessage::Event).collect())
+            })
+            .into_iter()
+            .chain(iter::once(EventMessage::Fin(proto::Fin {})))
+            .map(|message| proto::EventsResponse { event_message: Some(message) })
+            .collect(),
+        ),
+        Request::StateDiffs(_) => Response::StateDiffs(
+            collect(block_numbers, |n| source.state_diff(n))
+                .into_iter()
+                .chain(iter::once(StateDiffMessage::Fin(proto::Fin {})))
+                .map(|message| proto::StateDiffsResponse { state_diff_message: Some(message) })
+                .collect(),
+        ),
+        Request::Classes(_) => Response::Classes(
+            collect(block_numbers, |n| {
+                source.classes(n).map(|classes| classes.into_iter().map(ClassMessage::Class).collect())
+            })
+            .into_iter()
+            .chain(iter::once(ClassMessage::Fin(proto::Fin {})))
+            .map(|message| proto::ClassesResponse { class_message: Some(message) })
+            .collect(),
+        ),
+    }
+}
+
+/// Collects the messages of the blocks `block_numbers`, stopping at the first block `messages`
+/// returns `None` for, or whose messages would not fit in the response.
+fn collect<M: prost::Oneof>(block_numbers: Vec<u64>, mut messages: impl FnMut(u64) -> Option<Vec<M>>) -> Vec<M> {
+    // room for the `Fin`
+    let mut remaining = MAX_RESPONSE_SIZE - 16;
+    let mut collected = Vec::new();
+    for n in block_numbers {
+        let Some(block_messages) = messages(n) else { break };
+        // the size of the response messages wrapping them
+        let sizes: Vec<usize> = block_messages.iter().map(|message| message.encoded_len()).collect();
+        let size: usize = sizes.iter().sum();
+        if size > remaining || sizes.iter().any(|size| *size > MAX_MESSAGE_SIZE) {
+            log::debug!("Block {n} does not fit in the response, ending it");
+            break;
+        }
+        remaining -= size;
+        collected.extend(block_messages);
+    }
+    collected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iteration_is_bounded() {
+        let iteration =
+            proto::Iteration { start_block: 10, direction: Direction::Backward as i32, limit: 1000, step: 4 };
+        assert_eq!(block_numbers(&iteration).collect::<Vec<_>>(), vec![10, 6, 2]);
+
+        let iteration = proto::Iteration { start_block: 0, direction: Direction::Forward as i32, limit: 1000, step: 0 };
+        assert_eq!(block_numbers(&iteration).count(), MAX_BLOCKS_PER_REQUEST as usize);
+    }
+
+    #[test]
+    fn responses_are_bounded() {
+        use proto::classes_response::ClassMessage;
+
+        // 4 classes of 10 MiB per block
+        let class = ClassMessage::Class(proto::Class { class_hash: None, definition: vec![0; 10 << 20] });
+        let blocks = |n: u64| (n < 100).then(|| vec![class.clone(); 4]);
+        let collected = collect((0..10).collect(), blocks);
+        assert_eq!(collected.len(), 4 * (MAX_RESPONSE_SIZE / (40 << 20)));
+
+        // blocks are served up to the first one missing
+        assert_eq!(collect(vec![98, 99, 100, 101], blocks).len(), 8);
+
+        // a single message must not exceed the message size limit
+        let class = ClassMessage::Class(proto::Class { class_hash: None, definition: vec![0; MAX_MESSAGE_SIZE] });
+        assert!(collect(vec![0], |_| Some(vec![class.clone()])).is_empty());
+    }
+
+    #[test]
+    fn identities_are_hashed_from_the_seed() {
+        let secret = identity_secret(1);
+        assert_eq!(secret, identity_secret(1));
+        assert_ne!(secret, identity_secret(2));
+        assert!(secret[8..].iter().any(|byte| *byte != 0));
+    }
+}
@@ -0,0 +1,187 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use mc_db::DeoxysBackend;
+use mc_storage::OverrideHandle;
+use mp_block::DeoxysBlock;
+use mp_contract::class::ContractClassWrapper;
+use mp_digest_log::find_starknet_block;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_storage::StarknetStorageSchemaVersion;
+use mp_types::block::DBlockT;
+use parity_scale_codec::Encode;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::traits::{Header as HeaderT, UniqueSaturatedInto};
+use starknet_api::hash::StarkFelt;
+
+use crate::proto;
+
+/// Provides the data served to other peers.
+///
+/// Returning `None` ends the response stream, which tells the peer that we do not have the block.
+pub trait BlockSource: Send + Sync + 'static {
+    fn header(&self, block_number: u64) -> Option<proto::SignedBlockHeader>;
+
+    fn events(&self, block_number: u64) -> Option<Vec<proto::Event>>;
+
+    fn state_diff(&self, _block_number: u64) -> Option<Vec<proto::state_diffs_response::StateDiffMessage>> {
+        None
+    }
+
+    fn classes(&self, _block_number: u64) -> Option<Vec<proto::Class>> {
+        None
+    }
+}
+
+/// A [`BlockSource`] reading blocks from the Substrate client.
+///
+/// State diffs are read from the flat storage, so they are only served for the blocks whose
+/// modified contracts were recorded. Classes are served as compiled by the node.
+pub struct ClientBlockSource<C, H> {
+    client: Arc<C>,
+    overrides: Arc<OverrideHandle<DBlockT>>,
+    chain_id: Felt252Wrapper,
+    _hasher: PhantomData<H>,
+}
+
+impl<C, H> ClientBlockSource<C, H> {
+    pub fn new(client: Arc<C>, overrides: Arc<OverrideHandle<DBlockT>>, chain_id: Felt252Wrapper) -> Self {
+        Self { client, overrides, chain_id, _hasher: PhantomData }
+    }
+}
+
+impl<C, H> ClientBlockSource<C, H>
+where
+    C: HeaderBackend<DBlockT>,
+{
+    fn block(&self, block_number: u64) -> Option<DeoxysBlock> {
+        let substrate_hash = self.client.hash(UniqueSaturatedInto::unique_saturated_into(block_number)).ok()??;
+        let header = self.client.header(substrate_hash).ok()??;
+        find_starknet_block(header.digest()).ok()
+    }
+}
+
+impl<C, H> BlockSource for ClientBlockSource<C, H>
+where
+    C: HeaderBackend<DBlockT> + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    fn header(&self, block_number: u64) -> Option<proto::SignedBlockHeader> {
+        let block = self.block(block_number)?;
+        let header = block.header();
+
+        Some(proto::SignedBlockHeader {
+            block_hash: Some(hash(header.hash::<H>().into())),
+            parent_hash: Some(hash(header.parent_block_hash)),
+            number: header.block_number,
+            time: header.block_timestamp,
+            sequencer_address: Some(proto::Address { elements: header.sequencer_address.0.0.bytes().to_vec() }),
+            state_root: Some(hash(header.global_state_root)),
+            transaction_count: header.transaction_count as u64,
+            transaction_commitment: Some(hash(header.transaction_commitment)),
+            event_count: header.event_count as u64,
+            event_commitment: Some(hash(header.event_commitment)),
+            protocol_version: header.protocol_version.from_utf8().unwrap_or_default(),
+        })
+    }
+
+    fn events(&self, block_number: u64) -> Option<Vec<proto::Event>> {
+        let block = self.block(block_number)?;
+        let transaction_hashes: Vec<_> = block.transactions_hashes::<H>(self.chain_id, Some(block_number)).collect();
+
+        let events = block
+            .events()
+            .iter()
+            .flat_map(|ordered_events| {
+                let transaction_hash =
+                    transaction_hashes.get(ordered_events.index as usize).map(|tx_hash| hash(tx_hash.0));
+                ordered_events.events.iter().map(move |event| proto::Event {
+                    transaction_hash: transaction_hash.clone(),
+                    from_address: Some(felt(event.from_address.0.0)),
+                    keys: event.content.keys.iter().map(|key| felt(key.0)).collect(),
+                    data: event.content.data.0.iter().copied().map(felt).collect(),
+                })
+            })
+            .collect();
+
+        Some(events)
+    }
+
+    fn state_diff(&self, block_number: u64) -> Option<Vec<proto::state_diffs_response::StateDiffMessage>> {
+        use proto::state_diffs_response::StateDiffMessage;
+
+        // the state before the genesis block is not stored
+        let parent_number = block_number.checked_sub(1)?;
+        let storage = DeoxysBackend::contract_storage();
+        let contracts = storage.modified_contracts(block_number).ok()??;
+
+        let mut messages = Vec::with_capacity(contracts.len());
+        for address in contracts {
+            let updates = storage.storage_updates(&address, block_number).ok()??;
+            let nonce = storage.nonce_at(&address, block_number).ok()?;
+            let class_hash = storage.class_hash_at(&address, block_number).ok()?;
+            let contract_diff = proto::ContractDiff {
+                address: Some(proto::Address { elements: address.0.0.bytes().to_vec() }),
+                nonce: (nonce != storage.nonce_at(&address, parent_number).ok()?)
+                    .then_some(nonce)
+                    .flatten()
+                    .map(|nonce| felt(nonce.0)),
+                class_hash: (class_hash != storage.class_hash_at(&address, parent_number).ok()?)
+                    .then_some(class_hash)
+                    .flatten()
+                    .map(|class_hash| hash(class_hash.0)),
+                values: updates
+                    .into_iter()
+                    .map(|(key, value)| proto::ContractStoredValue {
+                        key: Some(felt(key.0.0)),
+                        value: Some(felt(value)),
+                    })
+                    .collect(),
+            };
+            if contract_diff.nonce.is_some() || contract_diff.class_hash.is_some() || !contract_diff.values.is_empty() {
+                messages.push(StateDiffMessage::ContractDiff(contract_diff));
+            }
+        }
+
+        let declarations = DeoxysBackend::class().declarations(block_number, block_number, usize::MAX).ok()?;
+        messages.extend(declarations.declarations.into_iter().map(|(_, declaration)| {
+            StateDiffMessage::DeclaredClass(proto::DeclaredClass {
+                class_hash: Some(hash(declaration.class_hash.0)),
+                compiled_class_hash: declaration
+                    .compiled_class_hash
+                    .map(|compiled_class_hash| hash(compiled_class_hash.0)),
+            })
+        }));
+
+        Some(messages)
+    }
+
+    fn classes(&self, block_number: u64) -> Option<Vec<proto::Class>> {
+        let substrate_hash = self.client.hash(UniqueSaturatedInto::unique_saturated_into(block_number)).ok()??;
+        let storage = self.overrides.for_schema_version(&StarknetStorageSchemaVersion::Undefined);
+        let declarations = DeoxysBackend::class().declarations(block_number, block_number, usize::MAX).ok()?;
+
+        declarations
+            .declarations
+            .into_iter()
+            .map(|(_, declaration)| {
+                let class_hash = declaration.class_hash;
+                let contract = storage.contract_class_by_class_hash(substrate_hash, class_hash)?;
+                let abi = storage.contract_abi_by_class_hash(substrate_hash, class_hash)?;
+                Some(proto::Class {
+                    class_hash: Some(hash(class_hash.0)),
+                    definition: ContractClassWrapper { contract, abi }.encode(),
+                })
+            })
+            .collect()
+    }
+}
+
+fn hash(value: StarkFelt) -> proto::Hash {
+    proto::Hash { elements: value.bytes().to_vec() }
+}
+
+fn felt(value: StarkFelt) -> proto::Felt252 {
+    proto::Felt252 { elements: value.bytes().to_vec() }
+}
@@ -0,0 +1,101 @@
+//! Restriction of the write methods to accounts of allowed classes.
+//!
+//! Private appchains may only want to accept transactions from accounts they trust. When an
+//! [`AccountClassWhitelist`] is set, the `add*Transaction` and `simulateTransactions` methods
+//! reject transactions sent from, or deploying, an account whose class is not in the whitelist.
+use std::collections::{HashMap, HashSet};
+
+use mp_felt::Felt252Wrapper;
+use mp_types::block::DBlockT;
+use sc_client_api::backend::{Backend, StorageProvider};
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::{
+    BroadcastedDeclareTransaction, BroadcastedDeployAccountTransaction, BroadcastedInvokeTransaction,
+    BroadcastedTransaction, FieldElement,
+};
+use starknet_core::utils::get_contract_address;
+
+use crate::errors::StarknetRpcApiError;
+use crate::Starknet;
+
+/// Class hashes of the accounts allowed to send transactions.
+#[derive(Debug, Clone, Default)]
+pub struct AccountClassWhitelist(HashSet<FieldElement>);
+
+impl AccountClassWhitelist {
+    pub fn new(class_hashes: impl IntoIterator<Item = FieldElement>) -> Self {
+        Self(class_hashes.into_iter().collect())
+    }
+
+    pub fn allows(&self, class_hash: &FieldElement) -> bool {
+        self.0.contains(class_hash)
+    }
+}
+
+impl<A: sc_transaction_pool::ChainApi, BE, G, C, P, H> Starknet<A, BE, G, C, P, H>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+{
+    /// Checks that the transactions are sent from accounts of whitelisted classes, if a whitelist
+    /// is set.
+    ///
+    /// Senders are looked up in the latest block, or among the accounts deployed by the previous
+    /// transactions, so that a deployment can be simulated along with the first transactions of
+    /// the new account.
+    ///
+    /// ### Errors
+    ///
+    /// * `ACCOUNT_CLASS_NOT_ALLOWED` - If a transaction is sent from, or deploys, an account whose
+    ///   class is not whitelisted, or from an address which holds no contract.
+    pub(crate) fn check_account_classes(
+        &self,
+        transactions: &[BroadcastedTransaction],
+    ) -> Result<(), StarknetRpcApiError> {
+        let Some(whitelist) = &self.account_class_whitelist else {
+            return Ok(());
+        };
+
+        let latest_block_hash = self.client.info().best_hash;
+        let mut deployed = HashMap::new();
+
+        for transaction in transactions {
+            let sender_address = match transaction {
+                BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V1(tx)) => tx.sender_address,
+                BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V3(tx)) => tx.sender_address,
+                BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V1(tx)) => tx.sender_address,
+                BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V2(tx)) => tx.sender_address,
+                BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V3(tx)) => tx.sender_address,
+                BroadcastedTransaction::DeployAccount(tx) => {
+                    let (class_hash, salt, calldata) = match tx {
+                        BroadcastedDeployAccountTransaction::V1(tx) => {
+                            (tx.class_hash, tx.contract_address_salt, &tx.constructor_calldata)
+                        }
+                        BroadcastedDeployAccountTransaction::V3(tx) => {
+                            (tx.class_hash, tx.contract_address_salt, &tx.constructor_calldata)
+                        }
+                    };
+                    let address = get_contract_address(salt, class_hash, calldata, FieldElement::ZERO);
+                    deployed.insert(address, class_hash);
+                    address
+                }
+            };
+
+            let class_hash = match deployed.get(&sender_address) {
+                Some(class_hash) => Some(*class_hash),
+                None => self
+                    .overrides
+                    .for_block_hash(self.client.as_ref(), latest_block_hash)
+                    .contract_class_hash_by_address(latest_block_hash, Felt252Wrapper(sender_address).into())
+                    .map(|class_hash| Felt252Wrapper::from(class_hash).into()),
+            };
+
+            if !class_hash.is_some_and(|class_hash| whitelist.allows(&class_hash)) {
+                log::debug!("Rejected transaction from account {sender_address:#x} of class {class_hash:?}");
+                return Err(StarknetRpcApiError::AccountClassNotAllowed);
+            }
+        }
+
+        Ok(())
+    }
+}
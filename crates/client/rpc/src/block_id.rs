@@ -0,0 +1,259 @@
+//! Resolution of the block ids received by the RPC methods.
+//!
+//! All the methods taking a [`BlockId`] go through [`Starknet::resolve_block_id`], so that hashes,
+//! numbers and tags are resolved, and unknown blocks reported, the same way everywhere. Resolving
+//! a block requires reading its header to recover the Starknet block number and hash: these are
+//! kept in a [`BlockIdCache`], by Substrate block hash, since they never change for a given block.
+//!
+//! Block hashes are resolved through the index of the block numbers by hash, the block mapping
+//! being only searched for the blocks missing from the index.
+//!
+//! The methods which need the Starknet block itself read it with [`Starknet::starknet_block`],
+//! which reports the blocks which can't be read the same way as the resolution.
+//!
+//! Nodes started with `--rpc-only-verified` only resolve the blocks of the local chain up to the
+//! last one verified against its header, so that no method serves data which may not match the
+//! block hashes. Blocks replaced by a reorg are not served until the verification has rewound its
+//! watermark to the new chain.
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use lru::LruCache;
+use mc_db::{DeoxysBackend, VerifiedBlock};
+use mp_block::DeoxysBlock;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::{DBlockT, DHashT};
+use sp_arithmetic::traits::UniqueSaturatedInto;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::{BlockId, BlockTag, FieldElement};
+
+use crate::errors::StarknetRpcApiError;
+use crate::utils::get_block_by_block_hash;
+use crate::{madara_backend_client, Starknet};
+
+/// A block id resolved against the local chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedBlock {
+    /// The Starknet block number.
+    pub number: u64,
+    /// The hash of the Substrate block wrapping the Starknet block.
+    pub substrate_hash: DHashT,
+    /// The Starknet block hash.
+    pub starknet_hash: Felt252Wrapper,
+    /// Whether the pending block was requested. The other fields then describe the latest block,
+    /// on top of which the pending block is built.
+    pub pending: bool,
+}
+
+impl ResolvedBlock {
+    /// Fails if the pending block was requested, for methods which only serve imported blocks.
+    pub fn not_pending(self) -> Result<Self, StarknetRpcApiError> {
+        if self.pending { Err(StarknetRpcApiError::PendingBlockUnsupported) } else { Ok(self) }
+    }
+}
+
+/// Starknet block numbers and hashes of recently resolved blocks, by Substrate block hash.
+pub struct BlockIdCache(Mutex<LruCache<DHashT, (u64, Felt252Wrapper)>>);
+
+impl BlockIdCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self(Mutex::new(LruCache::new(capacity)))
+    }
+
+    fn get(&self, substrate_hash: &DHashT) -> Option<(u64, Felt252Wrapper)> {
+        self.0.lock().expect("Failed to acquire lock on block id cache").get(substrate_hash).copied()
+    }
+
+    fn insert(&self, substrate_hash: DHashT, block: (u64, Felt252Wrapper)) {
+        self.0.lock().expect("Failed to acquire lock on block id cache").put(substrate_hash, block);
+    }
+}
+
+impl<A: sc_transaction_pool::ChainApi, BE, G, C, P, H> Starknet<A, BE, G, C, P, H>
+where
+    C: HeaderBackend<DBlockT> + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    /// Resolves a block id against the local chain.
+    ///
+    /// The `pending` tag resolves to the latest block, flagged as [`ResolvedBlock::pending`]:
+    /// methods serving the pending block check the flag, the others serve the latest block or call
+    /// [`ResolvedBlock::not_pending`].
+    ///
+    /// When the RPC only serves verified blocks, the tags resolve to the last verified block, which
+    /// is never flagged as pending, and the blocks after it, or off the local chain, are not found.
+    ///
+    /// ### Errors
+    ///
+    /// * `BLOCK_NOT_FOUND` - If the block is not in the local chain, or is not verified yet when
+    ///   only verified blocks are served.
+    pub fn resolve_block_id(&self, block_id: BlockId) -> Result<ResolvedBlock, StarknetRpcApiError> {
+        let verified = if self.rpc_config.only_verified { Some(self.verified_block_number()?) } else { None };
+        let block_id = match (block_id, verified) {
+            (BlockId::Tag(_), Some(verified)) => BlockId::Number(verified),
+            (block_id, _) => block_id,
+        };
+
+        let substrate_hash = match block_id {
+            BlockId::Hash(hash) => self.substrate_hash_of(hash)?,
+            BlockId::Number(number) => {
+                self.client.hash(UniqueSaturatedInto::unique_saturated_into(number)).map_err(|e| {
+                    log::error!("Failed to load the Substrate block hash of block {number}: {e}");
+                    StarknetRpcApiError::InternalServerError
+                })?
+            }
+            BlockId::Tag(_) => Some(self.client.info().best_hash),
+        }
+        .ok_or_else(|| {
+            log::debug!("Block not found: {block_id:?}");
+            StarknetRpcApiError::BlockNotFound
+        })?;
+
+        let (number, starknet_hash) = self.starknet_block_id(substrate_hash)?;
+        if verified.is_some_and(|verified| !is_served(number, substrate_hash, verified, |n| self.canonical_hash(n))) {
+            log::debug!("Block not verified yet: {block_id:?}");
+            return Err(StarknetRpcApiError::BlockNotFound);
+        }
+
+        Ok(ResolvedBlock {
+            number,
+            substrate_hash,
+            starknet_hash,
+            pending: matches!(block_id, BlockId::Tag(BlockTag::Pending)),
+        })
+    }
+
+    /// Reads the Starknet block of a resolved block id, the latest block for the `pending` tag.
+    ///
+    /// ### Errors
+    ///
+    /// * `BLOCK_NOT_FOUND` - If the block can't be read, e.g. it was pruned since its resolution.
+    pub fn starknet_block(&self, block: ResolvedBlock) -> Result<DeoxysBlock, StarknetRpcApiError> {
+        get_block_by_block_hash(self.client.as_ref(), block.substrate_hash).map_err(|e| {
+            log::error!("Failed to retrieve Starknet block {}: {e}", block.number);
+            StarknetRpcApiError::BlockNotFound
+        })
+    }
+
+    /// The hash of the Substrate block wrapping the Starknet block with the given hash in the
+    /// local chain.
+    ///
+    /// The block at the indexed number may be another one after a reorg: the block mapping is
+    /// searched if it does not have the requested hash.
+    fn substrate_hash_of(&self, hash: FieldElement) -> Result<Option<DHashT>, StarknetRpcApiError> {
+        let starknet_hash = Felt252Wrapper::from(hash);
+        let block_number = DeoxysBackend::mapping().block_number(starknet_hash.into()).map_err(|e| {
+            log::error!("Failed to load the number of Starknet block {hash:#x}: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?;
+
+        if let Some(block_number) = block_number {
+            let substrate_hash =
+                self.client.hash(UniqueSaturatedInto::unique_saturated_into(block_number)).map_err(|e| {
+                    log::error!("Failed to load the Substrate block hash of block {block_number}: {e}");
+                    StarknetRpcApiError::InternalServerError
+                })?;
+            if let Some(substrate_hash) = substrate_hash {
+                if self.starknet_block_id(substrate_hash)?.1 == starknet_hash {
+                    return Ok(Some(substrate_hash));
+                }
+            }
+        }
+
+        madara_backend_client::load_hash(self.client.as_ref(), starknet_hash.into()).map_err(|e| {
+            log::error!("Failed to load the Substrate block hash of Starknet block {hash:#x}: {e}");
+            StarknetRpcApiError::InternalServerError
+        })
+    }
+
+    /// The last block served when only verified blocks are served, see [`served_up_to`].
+    fn verified_block_number(&self) -> Result<u64, StarknetRpcApiError> {
+        let verified = DeoxysBackend::meta().verified_block().map_err(|e| {
+            log::error!("Failed to load the last verified block: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?;
+        served_up_to(verified, |number| self.canonical_hash(number)).ok_or_else(|| {
+            log::debug!("No verified block in the local chain");
+            StarknetRpcApiError::BlockNotFound
+        })
+    }
+
+    /// The hash of the Substrate block at height `number` in the local chain, `None` if there is
+    /// none or it can't be read.
+    fn canonical_hash(&self, number: u64) -> Option<DHashT> {
+        self.client.hash(UniqueSaturatedInto::unique_saturated_into(number)).unwrap_or_else(|e| {
+            log::error!("Failed to load the Substrate block hash of block {number}: {e}");
+            None
+        })
+    }
+
+    /// The Starknet block number and hash of the given Substrate block.
+    fn starknet_block_id(&self, substrate_hash: DHashT) -> Result<(u64, Felt252Wrapper), StarknetRpcApiError> {
+        if let Some(block) = self.block_id_cache.get(&substrate_hash) {
+            return Ok(block);
+        }
+
+        let block = get_block_by_block_hash(self.client.as_ref(), substrate_hash).map_err(|e| {
+            log::error!("Failed to retrieve the Starknet block of Substrate block {substrate_hash}: {e}");
+            StarknetRpcApiError::BlockNotFound
+        })?;
+        let header = block.header();
+        let resolved = (header.block_number, header.hash::<H>());
+        self.block_id_cache.insert(substrate_hash, resolved);
+        Ok(resolved)
+    }
+}
+
+/// The number of the last block served when only verified blocks are served, given the hash of the
+/// block at each height in the local chain.
+///
+/// `None` if no block can be served: none was verified yet, or the verified block was replaced by a
+/// reorg and the verification did not rewind its watermark yet.
+fn served_up_to(verified: Option<VerifiedBlock>, canonical_hash: impl Fn(u64) -> Option<DHashT>) -> Option<u64> {
+    verified.filter(|verified| verified.is_canonical(canonical_hash)).map(|verified| verified.number)
+}
+
+/// Whether block `number`, wrapped in the Substrate block `substrate_hash`, is served when only the
+/// blocks up to `served_up_to` are: it must also be part of the local chain.
+fn is_served(
+    number: u64,
+    substrate_hash: DHashT,
+    served_up_to: u64,
+    canonical_hash: impl Fn(u64) -> Option<DHashT>,
+) -> bool {
+    number <= served_up_to && canonical_hash(number) == Some(substrate_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(n: u8) -> DHashT {
+        DHashT::repeat_byte(n)
+    }
+
+    /// Blocks 0 to 5 in the local chain, blocks 4 and 5 having replaced the verified ones.
+    fn canonical_hash(number: u64) -> Option<DHashT> {
+        match number {
+            0..=3 => Some(hash(number as u8)),
+            4 | 5 => Some(hash(number as u8 + 100)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn only_verified_blocks_of_the_local_chain_are_served() {
+        assert_eq!(served_up_to(None, canonical_hash), None);
+        assert_eq!(served_up_to(Some(VerifiedBlock { number: 3, hash: hash(3) }), canonical_hash), Some(3));
+        // the verified block was replaced, until the watermark is rewound
+        assert_eq!(served_up_to(Some(VerifiedBlock { number: 4, hash: hash(4) }), canonical_hash), None);
+        assert_eq!(served_up_to(Some(VerifiedBlock { number: 6, hash: hash(6) }), canonical_hash), None);
+
+        assert!(is_served(0, hash(0), 3, canonical_hash));
+        assert!(is_served(3, hash(3), 3, canonical_hash));
+        assert!(!is_served(4, hash(104), 3, canonical_hash));
+        // a block off the local chain, resolved by its hash
+        assert!(!is_served(2, hash(42), 3, canonical_hash));
+    }
+}
@@ -0,0 +1,70 @@
+//! Configuration of the Starknet RPC, set by the operator.
+use std::time::Duration;
+
+use starknet_core::types::{BlockId, BlockTag};
+
+use crate::constants::{
+    MAX_EVENTS_CHUNK_SIZE, MAX_RPC_CONNECTIONS, MAX_RPC_REQUEST_BODY_SIZE, MAX_RPC_RESPONSE_BODY_SIZE,
+};
+use crate::rate_limit::{MethodClass, MethodLimits};
+
+/// Limits of the Starknet RPC methods and servers.
+#[derive(Debug, Clone)]
+pub struct RpcConfig {
+    /// Maximum number of events returned in a single `starknet_getEvents` page.
+    pub max_events_chunk_size: usize,
+    /// Origins allowed to query the RPC from a browser, any origin if `None`.
+    pub cors: Option<Vec<String>>,
+    /// Maximum size of a request body, in bytes.
+    pub max_request_body_size: usize,
+    /// Maximum size of a response body, in bytes.
+    pub max_response_body_size: usize,
+    /// Maximum number of connections served at once.
+    pub max_connections: usize,
+    /// Limits of `pathfinder_getProof`.
+    pub proof_limits: MethodLimits,
+    /// Limits of the trace and simulation methods.
+    pub trace_limits: MethodLimits,
+    /// Maximum time a transaction waits for those of its account with lower nonces to be
+    /// forwarded, if transactions are forwarded in nonce order.
+    pub nonce_queue_time: Option<Duration>,
+    /// Maximum number of attempts to forward the transactions which could not be forwarded to the
+    /// gateway, which are pooled meanwhile. Such transactions fail if unset.
+    pub forward_retries: Option<u32>,
+    /// Block read by the call, fee estimation, nonce and storage methods when the block id is
+    /// omitted.
+    pub default_block: BlockTag,
+    /// Only serve the blocks up to the last one verified against its header, blocks being
+    /// otherwise served as soon as they are imported.
+    pub only_verified: bool,
+}
+
+impl RpcConfig {
+    /// Whether a browser page served from `origin` may query the RPC.
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.cors.as_ref().map_or(true, |cors| cors.iter().any(|allowed| allowed == origin))
+    }
+
+    /// The block read by a method whose block id is omitted.
+    pub fn block_id_or_default(&self, block_id: Option<BlockId>) -> BlockId {
+        block_id.unwrap_or(BlockId::Tag(self.default_block))
+    }
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            max_events_chunk_size: MAX_EVENTS_CHUNK_SIZE,
+            cors: Some(Vec::new()),
+            max_request_body_size: MAX_RPC_REQUEST_BODY_SIZE,
+            max_response_body_size: MAX_RPC_RESPONSE_BODY_SIZE,
+            max_connections: MAX_RPC_CONNECTIONS,
+            proof_limits: MethodLimits::default_for(MethodClass::Proof),
+            trace_limits: MethodLimits::default_for(MethodClass::Trace),
+            nonce_queue_time: None,
+            forward_retries: None,
+            default_block: BlockTag::Latest,
+            only_verified: false,
+        }
+    }
+}
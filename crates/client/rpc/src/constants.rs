@@ -1,4 +1,47 @@
+use std::time::Duration;
+
 /// Maximum number of filter keys that can be passed to the `get_events` RPC.
 pub const MAX_EVENTS_KEYS: usize = 100;
-/// Maximum number of events that can be fetched in a single chunk for the `get_events` RPC.
+/// Default maximum number of events that can be fetched in a single chunk for the `get_events`
+/// RPC, see [`crate::RpcConfig`].
 pub const MAX_EVENTS_CHUNK_SIZE: usize = 1000;
+/// Default maximum size of an RPC request body, large enough for the declaration of big classes.
+pub const MAX_RPC_REQUEST_BODY_SIZE: usize = 15 * 1024 * 1024;
+/// Default maximum size of an RPC response body.
+pub const MAX_RPC_RESPONSE_BODY_SIZE: usize = 15 * 1024 * 1024;
+/// Default maximum number of connections an RPC server serves at once.
+pub const MAX_RPC_CONNECTIONS: usize = 100;
+/// Number of state values kept in memory for the executions of the node.
+pub const STATE_CACHE_SIZE: usize = 100_000;
+/// Number of resolved block ids kept in memory.
+pub const BLOCK_ID_CACHE_SIZE: usize = 1024;
+/// Maximum number of storage keys that can be proven in a single `pathfinder_getProof` call.
+pub const MAX_PROOF_KEYS: usize = 100;
+/// Default maximum number of `pathfinder_getProof` requests served at once.
+pub const MAX_CONCURRENT_PROOFS: usize = 4;
+/// Default maximum number of `pathfinder_getProof` requests started per second.
+pub const MAX_PROOFS_PER_SECOND: u64 = 20;
+/// Default maximum number of trace and simulation requests served at once.
+pub const MAX_CONCURRENT_TRACES: usize = 4;
+/// Default maximum number of trace and simulation requests started per second.
+pub const MAX_TRACES_PER_SECOND: u64 = 10;
+/// Default maximum time a proof, trace or simulation request waits to be served.
+pub const MAX_EXPENSIVE_QUEUE_TIME: Duration = Duration::from_secs(5);
+/// Time the next nonce of an account is tracked after it last forwarded a transaction, long enough
+/// for its transactions to be included in a block.
+pub const NONCE_TRACKING_TIME: Duration = Duration::from_secs(600);
+/// Maximum number of pooled transactions listed by the `deoxys_poolStatus` RPC.
+pub const MAX_POOL_STATUS_TRANSACTIONS: usize = 1000;
+/// Maximum number of headers returned in a single page by the `deoxys_getHeaders` RPC.
+pub const MAX_HEADERS_CHUNK_SIZE: u64 = 100;
+/// Maximum number of headers the `deoxys_getHeaders` RPC serves per second, across all callers.
+pub const MAX_HEADERS_PER_SECOND: u64 = 1000;
+/// Maximum number of classes returned in a single page by the `deoxys_getClassDeclarations` RPC.
+pub const MAX_CLASS_DECLARATIONS_CHUNK_SIZE: usize = 1000;
+/// Maximum number of events returned in a single page by the `deoxys_getTransactionEvents` RPC.
+pub const MAX_TRANSACTION_EVENTS_CHUNK_SIZE: usize = 1000;
+/// Maximum number of storage slots read in a single `deoxys_getStorageAtBatch` call.
+pub const MAX_STORAGE_BATCH_KEYS: usize = 10_000;
+/// Maximum number of storage slots updated between the two blocks of the `deoxys_getContractDiff`
+/// RPC, changed or not.
+pub const MAX_CONTRACT_DIFF_SLOTS: usize = 10_000;
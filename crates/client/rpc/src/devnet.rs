@@ -0,0 +1,633 @@
+//! Local block production, for devnet operation.
+//!
+//! In devnet mode, the transactions sent to the write methods are not forwarded to the gateway:
+//! they are validated locally and queued in a [`DevnetPool`]. The [`DevnetBlockBuilder`] then
+//! executes them on top of the latest block and builds the next block, with its state diff,
+//! receipts and declared classes, which is imported like the synced blocks.
+//!
+//! The devnet starts from the genesis of the selected network, or from a custom genesis declaring
+//! its own classes and funding its own accounts. As block 0 is built from the chain spec, the state
+//! of a custom genesis is written by the first block the devnet produces.
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use blockifier::execution::call_info::CallInfo;
+use blockifier::execution::contract_class::ContractClass;
+use blockifier::state::cached_state::{CachedState, GlobalContractCache};
+use blockifier::state::state_api::State;
+use blockifier::transaction::account_transaction::AccountTransaction;
+use blockifier::transaction::objects::TransactionExecutionInfo;
+use blockifier::transaction::transactions::ExecutableTransaction;
+use mc_genesis_data_provider::builder::BuiltGenesis;
+use mc_storage::OverrideHandle;
+use mc_sync::commitments::hashers::CommitmentHashers;
+use mc_sync::commitments::lib::{
+    build_commitment_state_diff, calculate_commitments, calculate_v0_13_2_commitments, update_state_root,
+};
+use mc_sync::commitments::versions::has_v0_13_2_commitments;
+use mc_sync::import::ArchivedBlock;
+use mc_sync::utility::get_config;
+use mp_block::receipt::{EventWrapper, ExecutionResourcesWrapper, MessageToL1Wrapper, TransactionReceiptWrapper};
+use mp_block::state_update::{
+    DeclaredContractWrapper, DeployedContractWrapper, StateDiffWrapper, StateUpdateWrapper, StorageDiffWrapper,
+};
+use mp_block::{DeoxysBlock, Header, OrderedEvents};
+use mp_contract::class::{ClassUpdateWrapper, ContractClassData, ContractClassWrapper};
+use mp_contract::{AbiEntryWrapper, ContractAbi};
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::StarknetRuntimeApi;
+use sc_client_api::backend::{Backend, StorageProvider};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::U256;
+use starknet_api::core::{ChainId, ClassHash, CompiledClassHash, ContractAddress, PatriciaKey};
+use starknet_api::hash::StarkFelt;
+use starknet_api::state::StorageKey;
+use starknet_api::transaction::{Event, Transaction};
+use starknet_core::types::{BroadcastedDeclareTransaction, BroadcastedTransaction, FieldElement};
+use thiserror::Error;
+use tokio::sync::Notify;
+
+use crate::state_reader::{DeoxysStateReader, StateCache};
+use crate::utils::{account_tx_to_api_tx, blockifier_call_info_to_starknet_resources, get_block_by_block_hash};
+
+/// How the devnet produces its blocks.
+#[derive(Debug, Clone)]
+pub struct DevnetConfig {
+    /// Interval between two blocks, which are then produced even when empty. A block is produced
+    /// as soon as a transaction is received if `None`.
+    pub block_time: Option<Duration>,
+    /// Whether the transactions of the produced blocks are charged their fee.
+    pub charge_fee: bool,
+    /// `genesis.json` of a custom genesis, the genesis of the network being kept if `None`.
+    pub genesis: Option<PathBuf>,
+    /// Seed of reproducible runs: the blocks are then timestamped at a fixed interval from their
+    /// parent instead of with the clock, and the accounts of the custom genesis get private keys
+    /// derived from it.
+    pub seed: Option<u64>,
+}
+
+/// Interval between the timestamps of two blocks of a seeded devnet without a block time.
+const SEEDED_BLOCK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A transaction waiting to be included in a block.
+pub struct PendingTransaction {
+    pub transaction: AccountTransaction,
+    /// The transaction as it was received, which the next transactions are validated after.
+    pub broadcasted: BroadcastedTransaction,
+    pub transaction_hash: FieldElement,
+    /// The ABI of the declared class, for declare transactions.
+    pub abi: Option<ContractAbi>,
+}
+
+/// Transactions received by the write methods, waiting for the next block.
+#[derive(Default)]
+pub struct DevnetPool {
+    transactions: Mutex<Vec<PendingTransaction>>,
+    notify: Notify,
+}
+
+impl DevnetPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn submit(&self, transaction: PendingTransaction) {
+        self.transactions.lock().expect("Failed to acquire lock on devnet pool").push(transaction);
+        self.notify.notify_one();
+    }
+
+    /// Waits until at least one transaction is pending.
+    pub async fn wait_for_transactions(&self) {
+        while self.transactions.lock().expect("Failed to acquire lock on devnet pool").is_empty() {
+            self.notify.notified().await;
+        }
+    }
+
+    /// The pending transactions as they were received, in order.
+    pub(crate) fn pending_transactions(&self) -> Vec<BroadcastedTransaction> {
+        let transactions = self.transactions.lock().expect("Failed to acquire lock on devnet pool");
+        transactions.iter().map(|transaction| transaction.broadcasted.clone()).collect()
+    }
+
+    /// Removes the pending transactions, in the order they were received.
+    pub fn take(&self) -> Vec<PendingTransaction> {
+        std::mem::take(&mut *self.transactions.lock().expect("Failed to acquire lock on devnet pool"))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DevnetError {
+    #[error("failed to read the latest block: {0}")]
+    LatestBlock(String),
+    #[error("failed to read the chain config: {0}")]
+    Config(String),
+    #[error("failed to read the fee token addresses: {0}")]
+    FeeTokens(String),
+    #[error("failed to write the genesis state: {0}")]
+    Genesis(String),
+}
+
+/// The ABI of the class declared by `transaction`, as stored with the class.
+pub(crate) fn declared_class_abi(transaction: &BroadcastedDeclareTransaction) -> ContractAbi {
+    match transaction {
+        BroadcastedDeclareTransaction::V1(tx) => ContractAbi::Cairo(
+            tx.contract_class.abi.clone().map(|entries| entries.into_iter().map(AbiEntryWrapper::from).collect()),
+        ),
+        BroadcastedDeclareTransaction::V2(tx) => ContractAbi::Sierra(tx.contract_class.abi.clone()),
+        BroadcastedDeclareTransaction::V3(tx) => ContractAbi::Sierra(tx.contract_class.abi.clone()),
+    }
+}
+
+/// Builds the blocks of the devnet from the pending transactions.
+pub struct DevnetBlockBuilder<C, BE> {
+    client: Arc<C>,
+    overrides: Arc<OverrideHandle<DBlockT>>,
+    charge_fee: bool,
+    /// Interval between the timestamps of two blocks, which are read from the clock if `None`.
+    block_interval: Option<Duration>,
+    /// State values read by the executions, shared by all blocks
+    state_cache: StateCache,
+    _marker: std::marker::PhantomData<BE>,
+}
+
+impl<C, BE> DevnetBlockBuilder<C, BE>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + StorageProvider<DBlockT, BE> + ProvideRuntimeApi<DBlockT> + 'static,
+    C::Api: StarknetRuntimeApi<DBlockT>,
+{
+    pub fn new(client: Arc<C>, overrides: Arc<OverrideHandle<DBlockT>>, config: &DevnetConfig) -> Self {
+        Self {
+            client,
+            overrides,
+            charge_fee: config.charge_fee,
+            block_interval: config.seed.map(|_| config.block_time.unwrap_or(SEEDED_BLOCK_INTERVAL)),
+            state_cache: StateCache::new(
+                std::num::NonZeroUsize::new(crate::constants::STATE_CACHE_SIZE)
+                    .expect("State cache size should not be zero"),
+            ),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Executes `transactions` on top of the latest block, and builds the next block.
+    ///
+    /// Transactions failing their validation are left out of the block, reverted ones are
+    /// included. The contract and class tries are updated with the state diff of the block, so the
+    /// block must be imported, or the tries reverted to its parent, before the next one is built.
+    pub fn build_block<H: HasherT>(&self, transactions: Vec<PendingTransaction>) -> Result<ArchivedBlock, DevnetError> {
+        self.build::<H>(transactions, None)
+    }
+
+    /// Builds the next block, declaring the classes and writing the storage of `genesis`.
+    pub fn build_genesis_block<H: HasherT>(&self, genesis: &BuiltGenesis) -> Result<ArchivedBlock, DevnetError> {
+        self.build::<H>(Vec::new(), Some(genesis))
+    }
+
+    fn build<H: HasherT>(
+        &self,
+        transactions: Vec<PendingTransaction>,
+        genesis: Option<&BuiltGenesis>,
+    ) -> Result<ArchivedBlock, DevnetError> {
+        let parent_substrate_hash = self.client.info().best_hash;
+        let parent = get_block_by_block_hash(self.client.as_ref(), parent_substrate_hash)
+            .map_err(|e| DevnetError::LatestBlock(e.to_string()))?;
+        let parent_header = parent.header();
+        let block_number = parent_header.block_number + 1;
+
+        let config = get_config().map_err(|e| DevnetError::Config(e.to_string()))?;
+        let chain_id = Felt252Wrapper::from(config.chain_id);
+        let fee_token_addresses = self
+            .client
+            .runtime_api()
+            .fee_token_addresses(parent_substrate_hash)
+            .map_err(|e| DevnetError::FeeTokens(e.to_string()))?;
+
+        let parent_block_hash = match parent_header.extra_data.map(Felt252Wrapper::try_from) {
+            Some(Ok(hash)) => hash,
+            _ => parent_header.hash::<H>(),
+        };
+        let block_timestamp = match self.block_interval {
+            // reproducible runs: the genesis timestamp plus a fixed interval per block
+            Some(interval) => parent_header.block_timestamp + interval.as_secs(),
+            None => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+                now.max(parent_header.block_timestamp)
+            }
+        };
+        let mut header = Header {
+            parent_block_hash: parent_block_hash.into(),
+            block_number,
+            block_timestamp,
+            sequencer_address: parent_header.sequencer_address,
+            l1_gas_price: mc_sync::gas_oracle::gas_prices().or_else(|| parent_header.l1_gas_price.clone()),
+            protocol_version: parent_header.protocol_version,
+            l1_da_mode: parent_header.l1_da_mode,
+            ..Default::default()
+        };
+        let execution_chain_id =
+            ChainId(chain_id.from_utf8().map_err(|e| DevnetError::Config(format!("invalid chain id: {e}")))?);
+        let block_context = header.into_block_context(fee_token_addresses, execution_chain_id);
+
+        let overrides = self.overrides.for_block_hash(self.client.as_ref(), parent_substrate_hash);
+        let mut state = CachedState::new(
+            DeoxysStateReader::new(
+                overrides.as_ref(),
+                &self.state_cache,
+                &self.overrides.class_cache,
+                parent_substrate_hash,
+                parent_header.block_number,
+            ),
+            GlobalContractCache::new(10),
+        );
+
+        let mut declared_classes = match genesis {
+            Some(genesis) => write_genesis(&mut state, genesis).map_err(|e| DevnetError::Genesis(e.to_string()))?,
+            None => Vec::new(),
+        };
+        let mut included = Vec::new();
+        let mut receipts = Vec::new();
+        for PendingTransaction { transaction, transaction_hash, abi, .. } in transactions {
+            let api_transaction = account_tx_to_api_tx(&transaction);
+            let declared_class = match &transaction {
+                AccountTransaction::Declare(tx) => Some((tx.tx().class_hash(), tx.contract_class())),
+                _ => None,
+            };
+
+            let execution_info = match transaction.execute(&mut state, &block_context, self.charge_fee, true) {
+                Ok(execution_info) => execution_info,
+                Err(e) => {
+                    log::warn!("🧪 Transaction {transaction_hash:#x} left out of block {block_number}: {e}");
+                    continue;
+                }
+            };
+
+            if let (Some((hash, contract)), Some(abi)) = (declared_class, abi) {
+                declared_classes
+                    .push(ContractClassData { hash, contract_class: ContractClassWrapper { contract, abi } });
+            }
+            let events = events(&execution_info);
+            receipts.push(receipt(transaction_hash, &execution_info, &events));
+            included.push((api_transaction, events));
+        }
+
+        let state_diff = state_diff(&mut state, &declared_classes, overrides.as_ref(), parent_substrate_hash);
+        let global_state_root = update_state_root(
+            build_commitment_state_diff(StateUpdateWrapper {
+                block_hash: None,
+                new_root: None,
+                old_root: parent_header.global_state_root.into(),
+                state_diff: state_diff.clone(),
+            }),
+            Arc::clone(&self.overrides),
+            block_number,
+            Some(parent_substrate_hash),
+            config.hashers,
+        );
+
+        header.global_state_root = global_state_root.into();
+        let (block, block_hash) = seal::<H>(header, included, &receipts, &state_diff, chain_id, config.hashers);
+
+        Ok(ArchivedBlock {
+            block,
+            state_update: StateUpdateWrapper {
+                block_hash: Some(block_hash),
+                new_root: Some(global_state_root),
+                old_root: parent_header.global_state_root.into(),
+                state_diff,
+            },
+            class_update: ClassUpdateWrapper(declared_classes),
+            receipts,
+        })
+    }
+}
+
+/// Completes `header`, whose state root is already set, with the commitments and the hash of the
+/// block made of the `included` transactions and their events.
+fn seal<H: HasherT>(
+    mut header: Header,
+    included: Vec<(Transaction, Vec<Event>)>,
+    receipts: &[TransactionReceiptWrapper],
+    state_diff: &StateDiffWrapper,
+    chain_id: Felt252Wrapper,
+    hashers: CommitmentHashers,
+) -> (DeoxysBlock, Felt252Wrapper) {
+    let (transactions, events): (Vec<_>, Vec<Vec<Event>>) = included.into_iter().unzip();
+    let event_count = events.iter().map(Vec::len).sum::<usize>();
+    let ordered_events: Vec<OrderedEvents> = events
+        .into_iter()
+        .enumerate()
+        .filter(|(_, events)| !events.is_empty())
+        .map(|(index, events)| OrderedEvents::new(index as u128, events))
+        .collect();
+    let (transaction_commitment, event_commitment) = calculate_commitments(
+        &transactions,
+        &ordered_events,
+        chain_id,
+        header.block_number,
+        &header.protocol_version,
+        hashers,
+    );
+
+    header.transaction_count = transactions.len() as u128;
+    header.transaction_commitment = transaction_commitment.into();
+    header.event_count = event_count as u128;
+    header.event_commitment = event_commitment.into();
+    if has_v0_13_2_commitments(&header.protocol_version) {
+        let (receipt_commitment, state_diff_commitment, state_diff_length) =
+            calculate_v0_13_2_commitments(receipts, state_diff);
+        header.receipt_commitment = Some(receipt_commitment.into());
+        header.state_diff_commitment = Some(state_diff_commitment.into());
+        header.state_diff_length = Some(state_diff_length);
+    }
+    let block_hash = header.hash::<H>();
+    header.extra_data = Some(U256::from_big_endian(&block_hash.0.to_bytes_be()));
+
+    (DeoxysBlock::new(header, transactions, ordered_events), block_hash)
+}
+
+/// Declares the classes, deploys the contracts and writes the storage of `genesis` in `state`,
+/// returning the declared classes.
+fn write_genesis(
+    state: &mut CachedState<DeoxysStateReader<'_>>,
+    genesis: &BuiltGenesis,
+) -> blockifier::state::errors::StateResult<Vec<ContractClassData>> {
+    let address = |address: FieldElement| ContractAddress(PatriciaKey(StarkFelt(address.to_bytes_be())));
+    let class_hash = |class_hash: FieldElement| ClassHash(StarkFelt(class_hash.to_bytes_be()));
+
+    let mut declared_classes = Vec::new();
+    for (hash, contract) in &genesis.classes {
+        state.set_contract_class(class_hash(hash.0), contract.clone())?;
+        // the ABI is not part of the compiled classes the genesis is made of
+        let abi = match contract {
+            ContractClass::V0(_) => ContractAbi::Cairo(None),
+            ContractClass::V1(_) => ContractAbi::Sierra(String::new()),
+        };
+        declared_classes.push(ContractClassData {
+            hash: class_hash(hash.0),
+            contract_class: ContractClassWrapper { contract: contract.clone(), abi },
+        });
+    }
+    for (sierra_class_hash, compiled_class_hash) in &genesis.data.sierra_class_hash_to_casm_class_hash {
+        state.set_compiled_class_hash(
+            class_hash(sierra_class_hash.0),
+            CompiledClassHash(StarkFelt(compiled_class_hash.0.to_bytes_be())),
+        )?;
+    }
+    for (contract_address, contract_class_hash) in &genesis.data.contracts {
+        state.set_class_hash_at(address(contract_address.0), class_hash(contract_class_hash.0))?;
+    }
+    for (contract_address, storage) in &genesis.data.storage {
+        for (key, value) in storage {
+            state.set_storage_at(
+                address(contract_address.0),
+                StorageKey(PatriciaKey(StarkFelt(key.0.to_bytes_be()))),
+                StarkFelt(value.0.to_bytes_be()),
+            )?;
+        }
+    }
+
+    Ok(declared_classes)
+}
+
+/// The state diff of the block executed in `state`, on top of the block `parent_substrate_hash`.
+fn state_diff(
+    state: &mut CachedState<DeoxysStateReader<'_>>,
+    declared_classes: &[ContractClassData],
+    overrides: &dyn mc_storage::StorageOverride<DBlockT>,
+    parent_substrate_hash: mp_types::block::DHashT,
+) -> StateDiffWrapper {
+    let diff = state.to_state_diff();
+
+    let mut storage_diffs: Vec<_> = diff
+        .storage_updates
+        .into_iter()
+        .map(|(address, updates)| {
+            let updates = updates
+                .into_iter()
+                .map(|(key, value)| StorageDiffWrapper { key: (*key.0.key()).into(), value: value.into() })
+                .collect();
+            (Felt252Wrapper::from(*address.0.key()), updates)
+        })
+        .collect();
+    storage_diffs.sort_by_key(|(address, _)| *address);
+
+    // a contract with no class at the parent block was deployed, the others had their class replaced
+    let (deployed_contracts, replaced_classes): (Vec<_>, Vec<_>) = diff
+        .address_to_class_hash
+        .into_iter()
+        .map(|(address, class_hash)| {
+            let previous = overrides.contract_class_hash_by_address(parent_substrate_hash, address);
+            let contract =
+                DeployedContractWrapper { address: (*address.0.key()).into(), class_hash: class_hash.0.into() };
+            (contract, previous.map_or(true, |previous| previous == ClassHash::default()))
+        })
+        .partition(|(_, deployed)| *deployed);
+
+    StateDiffWrapper {
+        storage_diffs,
+        deployed_contracts: deployed_contracts.into_iter().map(|(contract, _)| contract).collect(),
+        old_declared_contracts: declared_classes
+            .iter()
+            .filter(|class| matches!(class.contract_class.contract, ContractClass::V0(_)))
+            .map(|class| class.hash.0.into())
+            .collect(),
+        declared_classes: diff
+            .class_hash_to_compiled_class_hash
+            .into_iter()
+            .map(|(class_hash, compiled_class_hash)| DeclaredContractWrapper {
+                class_hash: class_hash.0.into(),
+                compiled_class_hash: compiled_class_hash.0.into(),
+            })
+            .collect(),
+        nonces: diff
+            .address_to_nonce
+            .into_iter()
+            .map(|(address, nonce)| ((*address.0.key()).into(), nonce.0.into()))
+            .collect(),
+        replaced_classes: replaced_classes.into_iter().map(|(contract, _)| contract).collect(),
+    }
+}
+
+/// The call infos of a transaction, in execution order.
+fn call_infos(execution_info: &TransactionExecutionInfo) -> impl Iterator<Item = &CallInfo> {
+    [&execution_info.validate_call_info, &execution_info.execute_call_info, &execution_info.fee_transfer_call_info]
+        .into_iter()
+        .flatten()
+}
+
+/// The events emitted by a transaction, in the order they were emitted.
+fn events(execution_info: &TransactionExecutionInfo) -> Vec<Event> {
+    fn collect(call_info: &CallInfo, events: &mut Vec<(usize, Event)>) {
+        events.extend(call_info.execution.events.iter().map(|ordered_event| {
+            (
+                ordered_event.order,
+                Event { from_address: call_info.call.storage_address, content: ordered_event.event.clone() },
+            )
+        }));
+        call_info.inner_calls.iter().for_each(|inner_call| collect(inner_call, events));
+    }
+
+    // orders restart at each call info of the transaction
+    call_infos(execution_info)
+        .flat_map(|call_info| {
+            let mut events = Vec::new();
+            collect(call_info, &mut events);
+            events.sort_by_key(|(order, _)| *order);
+            events.into_iter().map(|(_, event)| event)
+        })
+        .collect()
+}
+
+fn receipt(
+    transaction_hash: FieldElement,
+    execution_info: &TransactionExecutionInfo,
+    events: &[Event],
+) -> TransactionReceiptWrapper {
+    fn messages(call_info: &CallInfo) -> Vec<MessageToL1Wrapper> {
+        let sent = call_info.execution.l2_to_l1_messages.iter().map(|ordered_message| MessageToL1Wrapper {
+            from_address: Felt252Wrapper::from(*call_info.call.storage_address.0.key()),
+            to_address: Felt252Wrapper::from(
+                FieldElement::from_byte_slice_be(ordered_message.message.to_address.0.to_fixed_bytes().as_slice())
+                    .unwrap(),
+            ),
+            payload: ordered_message.message.payload.0.iter().copied().map(Felt252Wrapper::from).collect(),
+        });
+        sent.chain(call_info.inner_calls.iter().flat_map(messages)).collect()
+    }
+
+    // no execution resources for declare transactions
+    let execution_resources = execution_info.execute_call_info.as_ref().map_or_else(Default::default, |call_info| {
+        let resources = blockifier_call_info_to_starknet_resources(call_info).computation_resources;
+        ExecutionResourcesWrapper {
+            steps: resources.steps,
+            memory_holes: resources.memory_holes,
+            range_check_builtin_applications: resources.range_check_builtin_applications,
+            pedersen_builtin_applications: resources.pedersen_builtin_applications,
+            poseidon_builtin_applications: resources.poseidon_builtin_applications,
+            ec_op_builtin_applications: resources.ec_op_builtin_applications,
+            ecdsa_builtin_applications: resources.ecdsa_builtin_applications,
+            bitwise_builtin_applications: resources.bitwise_builtin_applications,
+            keccak_builtin_applications: resources.keccak_builtin_applications,
+            segment_arena_builtin: resources.segment_arena_builtin,
+            l1_gas: 0,
+            l1_data_gas: 0,
+        }
+    });
+
+    TransactionReceiptWrapper {
+        transaction_hash: transaction_hash.into(),
+        actual_fee: Felt252Wrapper::from(execution_info.actual_fee.0),
+        revert_error: execution_info.revert_error.clone(),
+        execution_resources,
+        messages_sent: execution_info.execute_call_info.as_ref().map(messages).unwrap_or_default(),
+        events: events
+            .iter()
+            .map(|event| EventWrapper {
+                from_address: Felt252Wrapper::from(*event.from_address.0.key()),
+                keys: event.content.keys.iter().map(|key| Felt252Wrapper::from(key.0)).collect(),
+                data: event.content.data.0.iter().copied().map(Felt252Wrapper::from).collect(),
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mp_hashers::pedersen::PedersenHasher;
+    use starknet_api::core::Nonce;
+    use starknet_api::transaction::{
+        Calldata, EventContent, EventData, EventKey, Fee, InvokeTransaction, InvokeTransactionV1, TransactionSignature,
+    };
+
+    use super::*;
+
+    fn invoke(nonce: u64) -> Transaction {
+        Transaction::Invoke(InvokeTransaction::V1(InvokeTransactionV1 {
+            max_fee: Fee(0),
+            signature: TransactionSignature(vec![]),
+            nonce: Nonce(StarkFelt::from(nonce)),
+            sender_address: ContractAddress::default(),
+            calldata: Calldata(Arc::new(vec![])),
+        }))
+    }
+
+    fn event(key: u64) -> Event {
+        Event {
+            from_address: ContractAddress::default(),
+            content: EventContent { keys: vec![EventKey(StarkFelt::from(key))], data: EventData(vec![]) },
+        }
+    }
+
+    #[test]
+    fn sealed_blocks_commit_to_their_content() {
+        let included = vec![(invoke(0), vec![event(1), event(2)]), (invoke(1), vec![]), (invoke(2), vec![event(3)])];
+        let receipts: Vec<_> = included
+            .iter()
+            .enumerate()
+            .map(|(index, (_, events))| {
+                receipt(FieldElement::from(index as u64), &TransactionExecutionInfo::default(), events)
+            })
+            .collect();
+        let state_diff = StateDiffWrapper {
+            storage_diffs: Default::default(),
+            deployed_contracts: Default::default(),
+            old_declared_contracts: Default::default(),
+            declared_classes: Default::default(),
+            nonces: Default::default(),
+            replaced_classes: Default::default(),
+        };
+        let header = Header {
+            block_number: 5,
+            global_state_root: StarkFelt::from(7u64),
+            protocol_version: Felt252Wrapper::try_from(&b"0.13.2"[..]).unwrap(),
+            ..Default::default()
+        };
+        let chain_id = Felt252Wrapper::try_from(&b"SN_SEPOLIA"[..]).unwrap();
+
+        let (block, block_hash) =
+            seal::<PedersenHasher>(header, included, &receipts, &state_diff, chain_id, CommitmentHashers::default());
+        let header = block.header();
+        assert_eq!(header.global_state_root, StarkFelt::from(7u64));
+        assert_eq!((header.transaction_count, header.event_count), (3, 3));
+        assert_eq!(block.transactions(), &[invoke(0), invoke(1), invoke(2)]);
+        // the events are grouped by the index of their transaction, leaving out the ones without
+        assert_eq!(block.events().iter().map(|ordered| ordered.index()).collect::<Vec<_>>(), [0, 2]);
+        assert_eq!(header.state_diff_length, Some(0));
+        assert!(header.receipt_commitment.is_some() && header.state_diff_commitment.is_some());
+
+        // the block hash is the one of the header before it is written to its extra data
+        assert_eq!(header.extra_data, Some(U256::from_big_endian(&block_hash.0.to_bytes_be())));
+        let mut unsealed = header.clone();
+        unsealed.extra_data = None;
+        assert_eq!(unsealed.hash::<PedersenHasher>(), block_hash);
+    }
+
+    #[test]
+    fn pre_v0_13_2_blocks_do_not_commit_to_their_receipts() {
+        let header =
+            Header { protocol_version: Felt252Wrapper::try_from(&b"0.13.1"[..]).unwrap(), ..Default::default() };
+        let state_diff = StateDiffWrapper {
+            storage_diffs: Default::default(),
+            deployed_contracts: Default::default(),
+            old_declared_contracts: Default::default(),
+            declared_classes: Default::default(),
+            nonces: Default::default(),
+            replaced_classes: Default::default(),
+        };
+        let chain_id = Felt252Wrapper::try_from(&b"SN_SEPOLIA"[..]).unwrap();
+
+        let (block, _) =
+            seal::<PedersenHasher>(header, vec![], &[], &state_diff, chain_id, CommitmentHashers::default());
+        let header = block.header();
+        assert_eq!((header.transaction_count, header.event_count), (0, 0));
+        assert_eq!(
+            (header.receipt_commitment, header.state_diff_commitment, header.state_diff_length),
+            (None, None, None)
+        );
+    }
+}
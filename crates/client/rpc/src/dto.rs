@@ -0,0 +1,861 @@
+//! Responses of the RPC methods, defined by the node instead of taken from `starknet_core::types`.
+//!
+//! The types of starknet-core follow the spec version of the release of the crate: a spec update
+//! or a breaking change upstream changes what the node serves, and has to be shipped along with the
+//! dependency bump. The responses defined here are serialized in the shape of the latest version
+//! served, [`RpcVersion::LATEST`], and each older version has a module holding the shape of the
+//! responses which changed since, e.g. [`v0_6`].
+//!
+//! Methods are moved to these types one at a time. The responses of the other methods are adapted
+//! to older versions by removing fields from their JSON, see [`RpcVersion::adapt_response`].
+use mp_felt::FeltHex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_with::serde_as;
+use starknet_core::serde::num_hex::NumAsHex;
+use starknet_core::types::{
+    BlockStatus, DataAvailabilityMode, FieldElement, L1DataAvailabilityMode, ResourceBoundsMapping,
+};
+
+use crate::versions::RpcVersion;
+
+/// A response whose shape changed since spec v0.6.
+pub(crate) trait Versioned: DeserializeOwned {
+    /// The response in the shape of spec v0.6.
+    type V0_6: Serialize;
+
+    fn into_v0_6(self) -> Self::V0_6;
+}
+
+impl<T: Versioned> Versioned for Vec<T> {
+    type V0_6 = Vec<T::V0_6>;
+
+    fn into_v0_6(self) -> Self::V0_6 {
+        self.into_iter().map(T::into_v0_6).collect()
+    }
+}
+
+/// Converts `result`, a serialized `T` of the latest version, to its shape in `version`.
+///
+/// Results which are not a `T`, such as errors, are left as is.
+pub(crate) fn adapt<T: Versioned>(version: RpcVersion, result: &mut Value) {
+    match version {
+        RpcVersion::V0_6 => {
+            let Ok(latest) = serde_json::from_value::<T>(result.clone()) else {
+                return;
+            };
+            match serde_json::to_value(latest.into_v0_6()) {
+                Ok(adapted) => *result = adapted,
+                Err(e) => log::error!("Failed to serialize a v0.6 response: {e}"),
+            }
+        }
+        RpcVersion::V0_7 => {}
+    }
+}
+
+/// Unit of a fee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum PriceUnit {
+    Wei,
+    Fri,
+}
+
+impl From<starknet_core::types::PriceUnit> for PriceUnit {
+    fn from(unit: starknet_core::types::PriceUnit) -> Self {
+        match unit {
+            starknet_core::types::PriceUnit::Wei => PriceUnit::Wei,
+            starknet_core::types::PriceUnit::Fri => PriceUnit::Fri,
+        }
+    }
+}
+
+/// Fee estimation of a transaction or an L1 message.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    /// L1 gas consumed by the transaction.
+    #[serde_as(as = "FeltHex")]
+    pub gas_consumed: FieldElement,
+    /// Price of the L1 gas, in `unit`.
+    #[serde_as(as = "FeltHex")]
+    pub gas_price: FieldElement,
+    /// L1 data gas consumed by the transaction, for its state diff posted as a blob.
+    #[serde_as(as = "FeltHex")]
+    pub data_gas_consumed: FieldElement,
+    /// Price of the L1 data gas, in `unit`.
+    #[serde_as(as = "FeltHex")]
+    pub data_gas_price: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub overall_fee: FieldElement,
+    pub unit: PriceUnit,
+}
+
+impl From<starknet_core::types::FeeEstimate> for FeeEstimate {
+    fn from(estimate: starknet_core::types::FeeEstimate) -> Self {
+        Self {
+            gas_consumed: estimate.gas_consumed,
+            gas_price: estimate.gas_price,
+            data_gas_consumed: estimate.data_gas_consumed,
+            data_gas_price: estimate.data_gas_price,
+            overall_fee: estimate.overall_fee,
+            unit: estimate.unit.into(),
+        }
+    }
+}
+
+impl Versioned for FeeEstimate {
+    type V0_6 = v0_6::FeeEstimate;
+
+    fn into_v0_6(self) -> Self::V0_6 {
+        v0_6::FeeEstimate {
+            gas_consumed: self.gas_consumed,
+            gas_price: self.gas_price,
+            overall_fee: self.overall_fee,
+            unit: self.unit,
+        }
+    }
+}
+
+/// A page of events, returned by `starknet_getEvents`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventsPage {
+    pub events: Vec<EmittedEvent>,
+    /// Token of the next page, `None` on the last page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuation_token: Option<String>,
+}
+
+impl From<starknet_core::types::EventsPage> for EventsPage {
+    fn from(page: starknet_core::types::EventsPage) -> Self {
+        Self {
+            events: page.events.into_iter().map(EmittedEvent::from).collect(),
+            continuation_token: page.continuation_token,
+        }
+    }
+}
+
+/// An event, along with the transaction and block which emitted it.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmittedEvent {
+    #[serde_as(as = "FeltHex")]
+    pub from_address: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub keys: Vec<FieldElement>,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub data: Vec<FieldElement>,
+    /// Hash of the block, `None` for the pending block.
+    #[serde_as(as = "Option<FeltHex>")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<FieldElement>,
+    /// Number of the block, `None` for the pending block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<u64>,
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+}
+
+impl From<starknet_core::types::EmittedEvent> for EmittedEvent {
+    fn from(event: starknet_core::types::EmittedEvent) -> Self {
+        Self {
+            from_address: event.from_address,
+            keys: event.keys,
+            data: event.data,
+            block_hash: event.block_hash,
+            block_number: event.block_number,
+            transaction_hash: event.transaction_hash,
+        }
+    }
+}
+
+/// A block with its transactions, returned by `starknet_getBlockWithTxs`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MaybePendingBlockWithTxs {
+    Block(BlockWithTxs),
+    PendingBlock(PendingBlockWithTxs),
+}
+
+impl From<starknet_core::types::MaybePendingBlockWithTxs> for MaybePendingBlockWithTxs {
+    fn from(block: starknet_core::types::MaybePendingBlockWithTxs) -> Self {
+        match block {
+            starknet_core::types::MaybePendingBlockWithTxs::Block(block) => Self::Block(block.into()),
+            starknet_core::types::MaybePendingBlockWithTxs::PendingBlock(block) => Self::PendingBlock(block.into()),
+        }
+    }
+}
+
+/// Price of a unit of gas.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourcePrice {
+    #[serde_as(as = "FeltHex")]
+    pub price_in_fri: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub price_in_wei: FieldElement,
+}
+
+impl From<starknet_core::types::ResourcePrice> for ResourcePrice {
+    fn from(price: starknet_core::types::ResourcePrice) -> Self {
+        Self { price_in_fri: price.price_in_fri, price_in_wei: price.price_in_wei }
+    }
+}
+
+/// A block of the chain with its transactions.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockWithTxs {
+    pub status: BlockStatus,
+    #[serde_as(as = "FeltHex")]
+    pub block_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub parent_hash: FieldElement,
+    pub block_number: u64,
+    #[serde_as(as = "FeltHex")]
+    pub new_root: FieldElement,
+    pub timestamp: u64,
+    #[serde_as(as = "FeltHex")]
+    pub sequencer_address: FieldElement,
+    pub l1_gas_price: ResourcePrice,
+    pub l1_data_gas_price: ResourcePrice,
+    pub l1_da_mode: L1DataAvailabilityMode,
+    pub starknet_version: String,
+    pub transactions: Vec<Transaction>,
+}
+
+impl From<starknet_core::types::BlockWithTxs> for BlockWithTxs {
+    fn from(block: starknet_core::types::BlockWithTxs) -> Self {
+        Self {
+            status: block.status,
+            block_hash: block.block_hash,
+            parent_hash: block.parent_hash,
+            block_number: block.block_number,
+            new_root: block.new_root,
+            timestamp: block.timestamp,
+            sequencer_address: block.sequencer_address,
+            l1_gas_price: block.l1_gas_price.into(),
+            l1_data_gas_price: block.l1_data_gas_price.into(),
+            l1_da_mode: block.l1_da_mode,
+            starknet_version: block.starknet_version,
+            transactions: block.transactions.into_iter().map(Transaction::from).collect(),
+        }
+    }
+}
+
+/// The pending block, with the transactions executed so far.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingBlockWithTxs {
+    #[serde_as(as = "FeltHex")]
+    pub parent_hash: FieldElement,
+    pub timestamp: u64,
+    #[serde_as(as = "FeltHex")]
+    pub sequencer_address: FieldElement,
+    pub l1_gas_price: ResourcePrice,
+    pub l1_data_gas_price: ResourcePrice,
+    pub l1_da_mode: L1DataAvailabilityMode,
+    pub starknet_version: String,
+    pub transactions: Vec<Transaction>,
+}
+
+impl From<starknet_core::types::PendingBlockWithTxs> for PendingBlockWithTxs {
+    fn from(block: starknet_core::types::PendingBlockWithTxs) -> Self {
+        Self {
+            parent_hash: block.parent_hash,
+            timestamp: block.timestamp,
+            sequencer_address: block.sequencer_address,
+            l1_gas_price: block.l1_gas_price.into(),
+            l1_data_gas_price: block.l1_data_gas_price.into(),
+            l1_da_mode: block.l1_da_mode,
+            starknet_version: block.starknet_version,
+            transactions: block.transactions.into_iter().map(Transaction::from).collect(),
+        }
+    }
+}
+
+/// A transaction of a block, tagged by its `type` and then by its `version`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Transaction {
+    Invoke(InvokeTransaction),
+    L1Handler(L1HandlerTransaction),
+    Declare(DeclareTransaction),
+    Deploy(DeployTransaction),
+    DeployAccount(DeployAccountTransaction),
+}
+
+impl From<starknet_core::types::Transaction> for Transaction {
+    fn from(tx: starknet_core::types::Transaction) -> Self {
+        match tx {
+            starknet_core::types::Transaction::Invoke(tx) => Self::Invoke(tx.into()),
+            starknet_core::types::Transaction::L1Handler(tx) => Self::L1Handler(tx.into()),
+            starknet_core::types::Transaction::Declare(tx) => Self::Declare(tx.into()),
+            starknet_core::types::Transaction::Deploy(tx) => Self::Deploy(tx.into()),
+            starknet_core::types::Transaction::DeployAccount(tx) => Self::DeployAccount(tx.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "version")]
+pub enum InvokeTransaction {
+    #[serde(rename = "0x0")]
+    V0(InvokeTransactionV0),
+    #[serde(rename = "0x1")]
+    V1(InvokeTransactionV1),
+    #[serde(rename = "0x3")]
+    V3(InvokeTransactionV3),
+}
+
+impl From<starknet_core::types::InvokeTransaction> for InvokeTransaction {
+    fn from(tx: starknet_core::types::InvokeTransaction) -> Self {
+        match tx {
+            starknet_core::types::InvokeTransaction::V0(tx) => Self::V0(InvokeTransactionV0 {
+                transaction_hash: tx.transaction_hash,
+                max_fee: tx.max_fee,
+                signature: tx.signature,
+                contract_address: tx.contract_address,
+                entry_point_selector: tx.entry_point_selector,
+                calldata: tx.calldata,
+            }),
+            starknet_core::types::InvokeTransaction::V1(tx) => Self::V1(InvokeTransactionV1 {
+                transaction_hash: tx.transaction_hash,
+                sender_address: tx.sender_address,
+                calldata: tx.calldata,
+                max_fee: tx.max_fee,
+                signature: tx.signature,
+                nonce: tx.nonce,
+            }),
+            starknet_core::types::InvokeTransaction::V3(tx) => Self::V3(InvokeTransactionV3 {
+                transaction_hash: tx.transaction_hash,
+                sender_address: tx.sender_address,
+                calldata: tx.calldata,
+                signature: tx.signature,
+                nonce: tx.nonce,
+                resource_bounds: tx.resource_bounds,
+                tip: tx.tip,
+                paymaster_data: tx.paymaster_data,
+                account_deployment_data: tx.account_deployment_data,
+                nonce_data_availability_mode: tx.nonce_data_availability_mode,
+                fee_data_availability_mode: tx.fee_data_availability_mode,
+            }),
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvokeTransactionV0 {
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub max_fee: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub signature: Vec<FieldElement>,
+    #[serde_as(as = "FeltHex")]
+    pub contract_address: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub entry_point_selector: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub calldata: Vec<FieldElement>,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvokeTransactionV1 {
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub sender_address: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub calldata: Vec<FieldElement>,
+    #[serde_as(as = "FeltHex")]
+    pub max_fee: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub signature: Vec<FieldElement>,
+    #[serde_as(as = "FeltHex")]
+    pub nonce: FieldElement,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvokeTransactionV3 {
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub sender_address: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub calldata: Vec<FieldElement>,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub signature: Vec<FieldElement>,
+    #[serde_as(as = "FeltHex")]
+    pub nonce: FieldElement,
+    pub resource_bounds: ResourceBoundsMapping,
+    #[serde_as(as = "NumAsHex")]
+    pub tip: u64,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub paymaster_data: Vec<FieldElement>,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub account_deployment_data: Vec<FieldElement>,
+    pub nonce_data_availability_mode: DataAvailabilityMode,
+    pub fee_data_availability_mode: DataAvailabilityMode,
+}
+
+/// A message sent from L1, executed by the L1 handler of its contract.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct L1HandlerTransaction {
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub version: FieldElement,
+    /// Nonce of the message on L1.
+    #[serde_as(as = "NumAsHex")]
+    pub nonce: u64,
+    #[serde_as(as = "FeltHex")]
+    pub contract_address: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub entry_point_selector: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub calldata: Vec<FieldElement>,
+}
+
+impl From<starknet_core::types::L1HandlerTransaction> for L1HandlerTransaction {
+    fn from(tx: starknet_core::types::L1HandlerTransaction) -> Self {
+        Self {
+            transaction_hash: tx.transaction_hash,
+            version: tx.version,
+            nonce: tx.nonce,
+            contract_address: tx.contract_address,
+            entry_point_selector: tx.entry_point_selector,
+            calldata: tx.calldata,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "version")]
+pub enum DeclareTransaction {
+    #[serde(rename = "0x0")]
+    V0(DeclareTransactionV0),
+    #[serde(rename = "0x1")]
+    V1(DeclareTransactionV1),
+    #[serde(rename = "0x2")]
+    V2(DeclareTransactionV2),
+    #[serde(rename = "0x3")]
+    V3(DeclareTransactionV3),
+}
+
+impl From<starknet_core::types::DeclareTransaction> for DeclareTransaction {
+    fn from(tx: starknet_core::types::DeclareTransaction) -> Self {
+        match tx {
+            starknet_core::types::DeclareTransaction::V0(tx) => Self::V0(DeclareTransactionV0 {
+                transaction_hash: tx.transaction_hash,
+                sender_address: tx.sender_address,
+                max_fee: tx.max_fee,
+                signature: tx.signature,
+                class_hash: tx.class_hash,
+            }),
+            starknet_core::types::DeclareTransaction::V1(tx) => Self::V1(DeclareTransactionV1 {
+                transaction_hash: tx.transaction_hash,
+                sender_address: tx.sender_address,
+                max_fee: tx.max_fee,
+                signature: tx.signature,
+                nonce: tx.nonce,
+                class_hash: tx.class_hash,
+            }),
+            starknet_core::types::DeclareTransaction::V2(tx) => Self::V2(DeclareTransactionV2 {
+                transaction_hash: tx.transaction_hash,
+                sender_address: tx.sender_address,
+                compiled_class_hash: tx.compiled_class_hash,
+                max_fee: tx.max_fee,
+                signature: tx.signature,
+                nonce: tx.nonce,
+                class_hash: tx.class_hash,
+            }),
+            starknet_core::types::DeclareTransaction::V3(tx) => Self::V3(DeclareTransactionV3 {
+                transaction_hash: tx.transaction_hash,
+                sender_address: tx.sender_address,
+                compiled_class_hash: tx.compiled_class_hash,
+                signature: tx.signature,
+                nonce: tx.nonce,
+                class_hash: tx.class_hash,
+                resource_bounds: tx.resource_bounds,
+                tip: tx.tip,
+                paymaster_data: tx.paymaster_data,
+                account_deployment_data: tx.account_deployment_data,
+                nonce_data_availability_mode: tx.nonce_data_availability_mode,
+                fee_data_availability_mode: tx.fee_data_availability_mode,
+            }),
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeclareTransactionV0 {
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub sender_address: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub max_fee: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub signature: Vec<FieldElement>,
+    #[serde_as(as = "FeltHex")]
+    pub class_hash: FieldElement,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeclareTransactionV1 {
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub sender_address: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub max_fee: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub signature: Vec<FieldElement>,
+    #[serde_as(as = "FeltHex")]
+    pub nonce: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub class_hash: FieldElement,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeclareTransactionV2 {
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub sender_address: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub compiled_class_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub max_fee: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub signature: Vec<FieldElement>,
+    #[serde_as(as = "FeltHex")]
+    pub nonce: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub class_hash: FieldElement,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeclareTransactionV3 {
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub sender_address: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub compiled_class_hash: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub signature: Vec<FieldElement>,
+    #[serde_as(as = "FeltHex")]
+    pub nonce: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub class_hash: FieldElement,
+    pub resource_bounds: ResourceBoundsMapping,
+    #[serde_as(as = "NumAsHex")]
+    pub tip: u64,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub paymaster_data: Vec<FieldElement>,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub account_deployment_data: Vec<FieldElement>,
+    pub nonce_data_availability_mode: DataAvailabilityMode,
+    pub fee_data_availability_mode: DataAvailabilityMode,
+}
+
+/// A legacy deploy transaction, found in the first blocks of the chain.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeployTransaction {
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub version: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub contract_address_salt: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub constructor_calldata: Vec<FieldElement>,
+    #[serde_as(as = "FeltHex")]
+    pub class_hash: FieldElement,
+}
+
+impl From<starknet_core::types::DeployTransaction> for DeployTransaction {
+    fn from(tx: starknet_core::types::DeployTransaction) -> Self {
+        Self {
+            transaction_hash: tx.transaction_hash,
+            version: tx.version,
+            contract_address_salt: tx.contract_address_salt,
+            constructor_calldata: tx.constructor_calldata,
+            class_hash: tx.class_hash,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "version")]
+pub enum DeployAccountTransaction {
+    #[serde(rename = "0x1")]
+    V1(DeployAccountTransactionV1),
+    #[serde(rename = "0x3")]
+    V3(DeployAccountTransactionV3),
+}
+
+impl From<starknet_core::types::DeployAccountTransaction> for DeployAccountTransaction {
+    fn from(tx: starknet_core::types::DeployAccountTransaction) -> Self {
+        match tx {
+            starknet_core::types::DeployAccountTransaction::V1(tx) => Self::V1(DeployAccountTransactionV1 {
+                transaction_hash: tx.transaction_hash,
+                max_fee: tx.max_fee,
+                signature: tx.signature,
+                nonce: tx.nonce,
+                contract_address_salt: tx.contract_address_salt,
+                constructor_calldata: tx.constructor_calldata,
+                class_hash: tx.class_hash,
+            }),
+            starknet_core::types::DeployAccountTransaction::V3(tx) => Self::V3(DeployAccountTransactionV3 {
+                transaction_hash: tx.transaction_hash,
+                signature: tx.signature,
+                nonce: tx.nonce,
+                contract_address_salt: tx.contract_address_salt,
+                constructor_calldata: tx.constructor_calldata,
+                class_hash: tx.class_hash,
+                resource_bounds: tx.resource_bounds,
+                tip: tx.tip,
+                paymaster_data: tx.paymaster_data,
+                nonce_data_availability_mode: tx.nonce_data_availability_mode,
+                fee_data_availability_mode: tx.fee_data_availability_mode,
+            }),
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeployAccountTransactionV1 {
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub max_fee: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub signature: Vec<FieldElement>,
+    #[serde_as(as = "FeltHex")]
+    pub nonce: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub contract_address_salt: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub constructor_calldata: Vec<FieldElement>,
+    #[serde_as(as = "FeltHex")]
+    pub class_hash: FieldElement,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeployAccountTransactionV3 {
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub signature: Vec<FieldElement>,
+    #[serde_as(as = "FeltHex")]
+    pub nonce: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub contract_address_salt: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub constructor_calldata: Vec<FieldElement>,
+    #[serde_as(as = "FeltHex")]
+    pub class_hash: FieldElement,
+    pub resource_bounds: ResourceBoundsMapping,
+    #[serde_as(as = "NumAsHex")]
+    pub tip: u64,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub paymaster_data: Vec<FieldElement>,
+    pub nonce_data_availability_mode: DataAvailabilityMode,
+    pub fee_data_availability_mode: DataAvailabilityMode,
+}
+
+/// Shape of the responses in spec v0.6.
+pub mod v0_6 {
+    use super::*;
+
+    /// Fee estimation, before the data gas was introduced in v0.7.
+    #[serde_as]
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct FeeEstimate {
+        #[serde_as(as = "FeltHex")]
+        pub gas_consumed: FieldElement,
+        #[serde_as(as = "FeltHex")]
+        pub gas_price: FieldElement,
+        #[serde_as(as = "FeltHex")]
+        pub overall_fee: FieldElement,
+        pub unit: PriceUnit,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use serde_json::json;
+    use starknet_core::types::{ResourceBounds, ResourcePrice as CoreResourcePrice};
+
+    use super::*;
+
+    /// A felt as large as the hashes and addresses of the chain, the bulk of the felts served.
+    fn felt(i: u64) -> FieldElement {
+        FieldElement::MAX - FieldElement::from(i)
+    }
+
+    fn felts(start: u64, len: u64) -> Vec<FieldElement> {
+        (start..start + len).map(felt).collect()
+    }
+
+    fn events_page(len: u64) -> starknet_core::types::EventsPage {
+        let events = (0..len)
+            .map(|i| starknet_core::types::EmittedEvent {
+                from_address: felt(i),
+                keys: felts(i, 2),
+                data: felts(i, 4),
+                block_hash: Some(felt(i / 100)),
+                block_number: Some(i / 100),
+                transaction_hash: felt(i / 10),
+            })
+            .collect();
+        starknet_core::types::EventsPage { events, continuation_token: Some("1-2-3".to_string()) }
+    }
+
+    fn block_with_txs() -> starknet_core::types::MaybePendingBlockWithTxs {
+        use starknet_core::types::{
+            InvokeTransaction as CoreInvoke, InvokeTransactionV1 as CoreInvokeV1, InvokeTransactionV3 as CoreInvokeV3,
+            Transaction as CoreTransaction,
+        };
+
+        let resource_bounds = ResourceBoundsMapping {
+            l1_gas: ResourceBounds { max_amount: 0x100, max_price_per_unit: 0x2000 },
+            l2_gas: ResourceBounds { max_amount: 0, max_price_per_unit: 0 },
+        };
+        let transactions = vec![
+            CoreTransaction::Invoke(CoreInvoke::V1(CoreInvokeV1 {
+                transaction_hash: felt(1),
+                sender_address: felt(2),
+                calldata: felts(3, 8),
+                max_fee: FieldElement::from(0xabc_u64),
+                signature: felts(4, 2),
+                nonce: FieldElement::from(5_u64),
+            })),
+            CoreTransaction::Invoke(CoreInvoke::V3(CoreInvokeV3 {
+                transaction_hash: felt(6),
+                sender_address: felt(7),
+                calldata: felts(8, 8),
+                signature: felts(9, 2),
+                nonce: FieldElement::ZERO,
+                resource_bounds,
+                tip: 0x10,
+                paymaster_data: vec![],
+                account_deployment_data: vec![],
+                nonce_data_availability_mode: DataAvailabilityMode::L1,
+                fee_data_availability_mode: DataAvailabilityMode::L1,
+            })),
+            CoreTransaction::L1Handler(starknet_core::types::L1HandlerTransaction {
+                transaction_hash: felt(10),
+                version: FieldElement::ZERO,
+                nonce: 0x42,
+                contract_address: felt(11),
+                entry_point_selector: felt(12),
+                calldata: felts(13, 3),
+            }),
+            CoreTransaction::Deploy(starknet_core::types::DeployTransaction {
+                transaction_hash: felt(14),
+                version: FieldElement::ZERO,
+                contract_address_salt: felt(15),
+                constructor_calldata: felts(16, 2),
+                class_hash: felt(17),
+            }),
+        ];
+        let price = CoreResourcePrice { price_in_fri: FieldElement::from(1_u64), price_in_wei: felt(18) };
+
+        starknet_core::types::MaybePendingBlockWithTxs::Block(starknet_core::types::BlockWithTxs {
+            status: BlockStatus::AcceptedOnL2,
+            block_hash: felt(19),
+            parent_hash: felt(20),
+            block_number: 650_000,
+            new_root: felt(21),
+            timestamp: 1_718_000_000,
+            sequencer_address: felt(22),
+            l1_gas_price: price.clone(),
+            l1_data_gas_price: price,
+            l1_da_mode: L1DataAvailabilityMode::Blob,
+            starknet_version: "0.13.1.1".to_string(),
+            transactions,
+        })
+    }
+
+    #[test]
+    fn responses_are_serialized_as_starknet_core_types() {
+        let page = events_page(10);
+        assert_eq!(serde_json::to_value(EventsPage::from(page.clone())).unwrap(), serde_json::to_value(page).unwrap());
+
+        let block = block_with_txs();
+        assert_eq!(
+            serde_json::to_value(MaybePendingBlockWithTxs::from(block.clone())).unwrap(),
+            serde_json::to_value(block).unwrap()
+        );
+    }
+
+    /// Average time taken by `serialize`.
+    fn time(serialize: impl Fn() -> Vec<u8>) -> Duration {
+        const RUNS: u32 = 20;
+        let start = Instant::now();
+        for _ in 0..RUNS {
+            std::hint::black_box(serialize());
+        }
+        start.elapsed() / RUNS
+    }
+
+    #[test]
+    #[ignore = "measurement, run with `cargo test --release -p mc-rpc serialization_time -- --ignored --nocapture`"]
+    fn serialization_time() {
+        let page = events_page(10_000);
+        let dto = EventsPage::from(page.clone());
+        println!(
+            "starknet_getEvents, 10000 events: starknet-core {:?}, node {:?}",
+            time(|| serde_json::to_vec(&page).unwrap()),
+            time(|| serde_json::to_vec(&dto).unwrap())
+        );
+
+        let block = block_with_txs();
+        let blocks = vec![block; 1_000];
+        let dtos: Vec<_> = blocks.iter().cloned().map(MaybePendingBlockWithTxs::from).collect();
+        println!(
+            "starknet_getBlockWithTxs, 1000 blocks: starknet-core {:?}, node {:?}",
+            time(|| serde_json::to_vec(&blocks).unwrap()),
+            time(|| serde_json::to_vec(&dtos).unwrap())
+        );
+    }
+
+    #[test]
+    fn fee_estimates_are_adapted_to_v0_6() {
+        let estimate = FeeEstimate {
+            gas_consumed: FieldElement::from(1_u64),
+            gas_price: FieldElement::from(2_u64),
+            data_gas_consumed: FieldElement::from(3_u64),
+            data_gas_price: FieldElement::from(4_u64),
+            overall_fee: FieldElement::from(14_u64),
+            unit: PriceUnit::Fri,
+        };
+        let mut result = serde_json::to_value(vec![estimate]).unwrap();
+
+        adapt::<Vec<FeeEstimate>>(RpcVersion::V0_6, &mut result);
+        assert_eq!(result, json!([{ "gas_consumed": "0x1", "gas_price": "0x2", "overall_fee": "0xe", "unit": "FRI" }]));
+
+        // errors are not estimates
+        let mut error = json!({ "code": 41, "message": "Transaction execution error" });
+        adapt::<Vec<FeeEstimate>>(RpcVersion::V0_6, &mut error);
+        assert_eq!(error, json!({ "code": 41, "message": "Transaction execution error" }));
+    }
+}
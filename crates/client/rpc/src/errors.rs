@@ -1,4 +1,5 @@
 use jsonrpsee::types::error::{CallError, ErrorObject};
+use mp_transactions::from_broadcasted_transactions::BroadcastedTransactionConversionError;
 use pallet_starknet_runtime_api::StarknetTransactionExecutionError;
 use starknet_core::types::StarknetError;
 
@@ -70,6 +71,30 @@ pub enum StarknetRpcApiError {
     UnimplementedMethod = 501,
     #[error("Too many storage keys requested")]
     ProofLimitExceeded = 10000,
+    #[error("Execution went over the memory limit")]
+    ExecutionMemoryLimitExceeded = 10001,
+    #[error("Too many requests")]
+    TooManyRequests = 10002,
+    #[error("Proofs are only available for the blocks the tries can still be reverted to")]
+    ProofUnavailable = 10003,
+    #[error("The pending block is not supported by this method")]
+    PendingBlockUnsupported = 10004,
+    #[error("The class of the sending account is not allowed")]
+    AccountClassNotAllowed = 10005,
+    #[error("Unknown database column")]
+    UnknownColumn = 10006,
+    #[error("A database compaction is already running")]
+    CompactionInProgress = 10007,
+    #[error("Modified contracts are not recorded for this block")]
+    ModifiedContractsUnavailable = 10008,
+    #[error("Unknown data source")]
+    UnknownDataSource = 10009,
+    #[error("The other data sources are all banned")]
+    LastDataSource = 10010,
+    #[error("The storage history of this block is not available yet")]
+    StorageHistoryUnavailable = 10011,
+    #[error("Too many storage slots were updated between the blocks")]
+    ContractDiffLimitExceeded = 10012,
 }
 
 impl From<StarknetTransactionExecutionError> for StarknetRpcApiError {
@@ -84,6 +109,27 @@ impl From<StarknetTransactionExecutionError> for StarknetRpcApiError {
     }
 }
 
+impl From<BroadcastedTransactionConversionError> for StarknetRpcApiError {
+    fn from(err: BroadcastedTransactionConversionError) -> Self {
+        match err {
+            BroadcastedTransactionConversionError::MaxFeeTooBig => StarknetRpcApiError::ValidationFailure,
+            BroadcastedTransactionConversionError::ProgramDecompressionFailed
+            | BroadcastedTransactionConversionError::ProgramDeserializationFailed
+            | BroadcastedTransactionConversionError::ClassHashComputationFailed
+            | BroadcastedTransactionConversionError::CasmContractClassConversionFailed => {
+                StarknetRpcApiError::InvalidContractClass
+            }
+            BroadcastedTransactionConversionError::InvalidCompiledClassHash => {
+                StarknetRpcApiError::CompiledClassHashMismatch
+            }
+            BroadcastedTransactionConversionError::SierraCompilationFailed => StarknetRpcApiError::CompilationFailed,
+            BroadcastedTransactionConversionError::UnsuportedTransactionVersion => {
+                StarknetRpcApiError::UnsupportedTxnVersion
+            }
+        }
+    }
+}
+
 impl From<StarknetRpcApiError> for jsonrpsee::core::Error {
     fn from(err: StarknetRpcApiError) -> Self {
         jsonrpsee::core::Error::Call(CallError::Custom(ErrorObject::owned(err as i32, err.to_string(), None::<()>)))
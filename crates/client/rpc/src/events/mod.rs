@@ -35,6 +35,15 @@ where
     /// * `(transaction_receipts: Vec<TransactionReceiptWrapper>, block: Block)` - A tuple of the
     ///   block transaction receipts with events in block_id and an instance of Block
     pub fn get_block_events(&self, block_id: BlockId) -> Result<Vec<EmittedEvent>, StarknetRpcApiError> {
+        Ok(self.get_block_indexed_events(block_id)?.into_iter().map(|(_, _, event)| event).collect())
+    }
+
+    /// Returns the events of a block along with their position: the index of the transaction
+    /// emitting them in the block, and their index among the events of that transaction.
+    pub(crate) fn get_block_indexed_events(
+        &self,
+        block_id: BlockId,
+    ) -> Result<Vec<(u64, u64, EmittedEvent)>, StarknetRpcApiError> {
         let starknet_block = match block_id {
             BlockId::Number(n) => self.get_block_by_number(n),
             BlockId::Tag(tag) => self.get_block_by_tag(tag),
@@ -47,12 +56,17 @@ where
             self.get_block_txs_hashes(&starknet_block)?
         };
 
-        let tx_hash_and_events: Vec<(Felt252Wrapper, _)> = starknet_block
+        let tx_hash_and_events: Vec<(u64, u64, Felt252Wrapper, _)> = starknet_block
             .events()
             .iter()
             .flat_map(|ordered_event| {
-                let tx_hash = txs_hashes[ordered_event.index() as usize];
-                ordered_event.events().iter().map(move |events| (tx_hash.into(), events.clone()))
+                let tx_n = ordered_event.index() as u64;
+                let tx_hash = txs_hashes[tx_n as usize];
+                ordered_event
+                    .events()
+                    .iter()
+                    .enumerate()
+                    .map(move |(event_n, event)| (tx_n, event_n as u64, tx_hash.into(), event.clone()))
             })
             .collect();
 
@@ -64,13 +78,16 @@ where
 
         let emitted_events = tx_hash_and_events
             .into_iter()
-            .map(|(tx_hash, event)| EmittedEvent {
-                from_address: Felt252Wrapper::from(event.from_address).0,
-                keys: event.content.keys.into_iter().map(|felt| Felt252Wrapper::from(felt).0).collect(),
-                data: event.content.data.0.into_iter().map(|felt| Felt252Wrapper::from(felt).0).collect(),
-                block_hash,
-                block_number,
-                transaction_hash: tx_hash.0,
+            .map(|(tx_n, event_n, tx_hash, event)| {
+                let emitted = EmittedEvent {
+                    from_address: Felt252Wrapper::from(event.from_address).0,
+                    keys: event.content.keys.into_iter().map(|felt| Felt252Wrapper::from(felt).0).collect(),
+                    data: event.content.data.0.into_iter().map(|felt| Felt252Wrapper::from(felt).0).collect(),
+                    block_hash,
+                    block_number,
+                    transaction_hash: tx_hash.0,
+                };
+                (tx_n, event_n, emitted)
             })
             .collect();
         Ok(emitted_events)
@@ -113,11 +130,7 @@ where
     }
 
     fn get_block_by_number(&self, block_number: u64) -> Result<DeoxysBlock, StarknetRpcApiError> {
-        let substrate_block_hash =
-            self.substrate_block_hash_from_starknet_block(BlockId::Number(block_number)).map_err(|e| {
-                log::error!("'{e}'");
-                StarknetRpcApiError::BlockNotFound
-            })?;
+        let substrate_block_hash = self.resolve_block_id(BlockId::Number(block_number))?.substrate_hash;
 
         let starknet_block = get_block_by_block_hash(self.client.as_ref(), substrate_block_hash).map_err(|e| {
             log::error!("'{e}'");
@@ -127,11 +140,7 @@ where
     }
 
     fn get_block_by_hash(&self, block_hash: FieldElement) -> Result<DeoxysBlock, StarknetRpcApiError> {
-        let substrate_block_hash =
-            self.substrate_block_hash_from_starknet_block(BlockId::Hash(block_hash)).map_err(|e| {
-                log::error!("'{e}'");
-                StarknetRpcApiError::BlockNotFound
-            })?;
+        let substrate_block_hash = self.resolve_block_id(BlockId::Hash(block_hash))?.substrate_hash;
 
         let starknet_block = get_block_by_block_hash(self.client.as_ref(), substrate_block_hash).map_err(|e| {
             log::error!("'{e}'");
@@ -142,12 +151,7 @@ where
 
     fn get_block_by_tag(&self, block_tag: BlockTag) -> Result<DeoxysBlock, StarknetRpcApiError> {
         if block_tag == BlockTag::Latest {
-            self.get_block_by_number(
-                self.substrate_block_number_from_starknet_block(BlockId::Tag(BlockTag::Latest)).map_err(|e| {
-                    log::error!("'{e}'");
-                    StarknetRpcApiError::BlockNotFound
-                })?,
-            )
+            self.get_block_by_number(self.resolve_block_id(BlockId::Tag(BlockTag::Latest))?.number)
         } else {
             match get_pending_block() {
                 Some(block) => Ok(block),
@@ -0,0 +1,197 @@
+//! Per-request memory accounting for blockifier executions.
+//!
+//! Traces, simulations, calls and fee estimations run the blockifier through the native runtime, on
+//! the thread serving the RPC request. [`TrackingAllocator`] keeps track of the memory allocated by
+//! a thread while it is inside of a [`with_memory_limit`] scope, and flags the scope as soon as it
+//! goes over the configured budget. The state reader of the executions then fails their next read,
+//! which stops them, and the request fails with a dedicated RPC error. Executions which keep
+//! allocating without reading the state are still bounded by the step limit of the blockifier.
+//!
+//! Allocations outside of the scopes only cost a relaxed load of the number of open scopes, the
+//! threads not running an execution being left alone.
+//!
+//! Accounting only happens if [`TrackingAllocator`] is installed as the `#[global_allocator]` of
+//! the binary, otherwise the limit never trips.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::errors::StarknetRpcApiError;
+
+/// Number of scopes currently open, on any thread.
+static OPEN_SCOPES: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Whether allocations made by the current thread are being accounted for.
+    static TRACKING: Cell<bool> = const { Cell::new(false) };
+    /// Bytes the current thread is allowed to allocate in the scope.
+    static LIMIT: Cell<usize> = const { Cell::new(usize::MAX) };
+    /// Bytes currently allocated by the current thread since the start of the scope.
+    static CURRENT: Cell<usize> = const { Cell::new(0) };
+    /// Highest value reached by `CURRENT` since the start of the scope.
+    static PEAK: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A [`System`] allocator wrapper accounting for the memory used by executions.
+pub struct TrackingAllocator;
+
+impl TrackingAllocator {
+    fn on_alloc(size: usize) {
+        if OPEN_SCOPES.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let _ = TRACKING.try_with(|tracking| {
+            if tracking.get() {
+                let current = CURRENT.with(|current| {
+                    let value = current.get().saturating_add(size);
+                    current.set(value);
+                    value
+                });
+                PEAK.with(|peak| peak.set(peak.get().max(current)));
+            }
+        });
+    }
+
+    fn on_dealloc(size: usize) {
+        if OPEN_SCOPES.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let _ = TRACKING.try_with(|tracking| {
+            if tracking.get() {
+                CURRENT.with(|current| current.set(current.get().saturating_sub(size)));
+            }
+        });
+    }
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::on_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::on_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::on_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Self::on_dealloc(layout.size());
+            Self::on_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Stops the accounting when dropped, including when the execution panics.
+struct ScopeGuard;
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        TRACKING.with(|tracking| tracking.set(false));
+        LIMIT.with(|limit| limit.set(usize::MAX));
+        OPEN_SCOPES.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Whether the execution running on the current thread went over its memory limit, in which case
+/// it should be stopped as soon as possible.
+pub fn limit_exceeded() -> bool {
+    PEAK.with(|peak| peak.get()) > LIMIT.with(|limit| limit.get())
+}
+
+/// Runs `f`, failing with [`StarknetRpcApiError::ExecutionMemoryLimitExceeded`] if the memory
+/// allocated by the current thread during the call went over `limit` bytes.
+///
+/// The execution is stopped at its next read of the state once over the limit, see
+/// [`limit_exceeded`]. Nested scopes are accounted for by the outermost one.
+///
+/// ### Arguments
+///
+/// * `limit` - The maximum number of bytes the execution is allowed to allocate, or `None` to
+///   disable the check.
+/// * `f` - The execution to account for.
+pub fn with_memory_limit<T>(limit: Option<usize>, f: impl FnOnce() -> T) -> Result<T, StarknetRpcApiError> {
+    let Some(limit) = limit else {
+        return Ok(f());
+    };
+
+    if TRACKING.with(|tracking| tracking.get()) {
+        return Ok(f());
+    }
+    CURRENT.with(|current| current.set(0));
+    PEAK.with(|peak| peak.set(0));
+    LIMIT.with(|scope_limit| scope_limit.set(limit));
+    OPEN_SCOPES.fetch_add(1, Ordering::Relaxed);
+    TRACKING.with(|tracking| tracking.set(true));
+
+    let (result, peak) = {
+        let _guard = ScopeGuard;
+        let result = f();
+        (result, PEAK.with(|peak| peak.get()))
+    };
+
+    if peak > limit {
+        log::error!("Execution allocated {peak} bytes, which is over the configured limit of {limit} bytes");
+        return Err(StarknetRpcApiError::ExecutionMemoryLimitExceeded);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[global_allocator]
+    static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+    #[test]
+    fn executions_over_the_limit_are_flagged_while_running() {
+        let within = with_memory_limit(Some(1 << 20), || {
+            let buffer = vec![0u8; 1 << 10];
+            assert!(!limit_exceeded());
+            buffer.len()
+        });
+        assert_eq!(within.unwrap(), 1 << 10);
+
+        let mut flagged = false;
+        let over = with_memory_limit(Some(1 << 20), || {
+            let mut buffers = Vec::new();
+            // stops as soon as it is flagged, as executions do on their next state read
+            while !limit_exceeded() && buffers.len() < 1024 {
+                buffers.push(vec![1u8; 1 << 12]);
+            }
+            flagged = limit_exceeded();
+        });
+        assert!(flagged);
+        assert!(matches!(over, Err(StarknetRpcApiError::ExecutionMemoryLimitExceeded)));
+
+        // the flag does not outlive the scope
+        assert!(!limit_exceeded());
+        let _buffer = vec![0u8; 1 << 22];
+        assert!(!limit_exceeded());
+    }
+
+    #[test]
+    fn nested_scopes_are_accounted_for_by_the_outermost_one() {
+        let result =
+            with_memory_limit(Some(1 << 20), || with_memory_limit(Some(1), || vec![0u8; 1 << 10].len()).unwrap());
+        assert_eq!(result.unwrap(), 1 << 10);
+        assert!(matches!(with_memory_limit(None, || vec![0u8; 1 << 22].len()), Ok(_)));
+    }
+}
@@ -0,0 +1,179 @@
+//! Persistent pool of the transactions which could not be forwarded to the gateway.
+//!
+//! Transactions sent to the node are forwarded to the gateway as they are received. When the
+//! gateway can't be reached, or rate limits the node, the transaction would be lost for the user
+//! even though it is valid. When a [`ForwardPool`] is set, such transactions are instead stored in
+//! the database and acknowledged with their locally computed hash, and a background worker
+//! forwards them once the gateway is back, retrying with exponential backoff, including across
+//! restarts.
+//!
+//! Transactions rejected by the gateway are not pooled: the error is returned to the user, or
+//! logged when it happens on a retry. Pooled transactions are retried independently of each other,
+//! so a retry may reach the gateway before another pooled transaction of the same account with a
+//! lower nonce.
+use std::future::Future;
+use std::time::Duration;
+
+use blockifier::transaction::account_transaction::AccountTransaction;
+use jsonrpsee::core::{async_trait, RpcResult};
+use mc_db::{DbError, DeoxysBackend, QueuedDelivery};
+use mc_delivery::{DeliveryError, DeliveryQueue, DeliverySink, DeliveryWorker, RetryPolicy};
+use mc_sync::utility::get_config;
+use mp_felt::{Felt252Wrapper, FeltHex};
+use mp_hashers::HasherT;
+use mp_transactions::from_broadcasted_transactions::ToAccountTransaction;
+use mp_types::block::DBlockT;
+use sc_client_api::backend::{Backend, StorageProvider};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::{BroadcastedTransaction, FieldElement};
+use starknet_providers::{Provider, ProviderError, SequencerGatewayProvider};
+
+use crate::errors::StarknetRpcApiError;
+use crate::utils::account_tx_hash;
+use crate::Starknet;
+
+/// Topic of the pool in the delivery queues of the database.
+const FORWARD_POOL_TOPIC: &str = "forward_pool";
+
+/// A transaction waiting to be forwarded, as stored in the pool.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PooledTransaction {
+    #[serde_as(as = "FeltHex")]
+    transaction_hash: FieldElement,
+    transaction: BroadcastedTransaction,
+}
+
+/// A transaction of the pool, as listed by [`ForwardPool::pending`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingForward {
+    pub transaction_hash: FieldElement,
+    /// Number of failed attempts to forward the transaction.
+    pub attempts: u32,
+    /// Unix time in milliseconds of the next attempt.
+    pub next_attempt: u64,
+}
+
+/// Handle used to pool the transactions which could not be forwarded.
+pub struct ForwardPool {
+    queue: DeliveryQueue,
+}
+
+impl ForwardPool {
+    /// Creates the pool, along with the worker forwarding its transactions to the gateway, which
+    /// must be spawned. A transaction is dropped after `max_attempts` failed attempts.
+    pub fn new(max_attempts: u32) -> (Self, DeliveryWorker<GatewaySink>) {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_attempts: Some(max_attempts),
+        };
+        let (worker, queue) = mc_delivery::new(FORWARD_POOL_TOPIC, GatewaySink, policy);
+        (Self { queue }, worker)
+    }
+
+    fn push(&self, transaction_hash: FieldElement, transaction: BroadcastedTransaction) -> Result<(), DbError> {
+        let payload = serde_json::to_vec(&PooledTransaction { transaction_hash, transaction })
+            .expect("Broadcasted transactions should serialize to JSON");
+        self.queue.push(payload).map(|_| ())
+    }
+
+    /// Returns the number of transactions in the pool, with the first `limit` of them.
+    pub fn pending(&self, limit: usize) -> Result<(usize, Vec<PendingForward>), DbError> {
+        let items = DeoxysBackend::delivery().due(FORWARD_POOL_TOPIC, u64::MAX, limit)?;
+        let pending = items
+            .into_iter()
+            .filter_map(|item: QueuedDelivery| {
+                let pooled: PooledTransaction = serde_json::from_slice(&item.payload).ok()?;
+                Some(PendingForward {
+                    transaction_hash: pooled.transaction_hash,
+                    attempts: item.attempts,
+                    next_attempt: item.not_before,
+                })
+            })
+            .collect();
+        Ok((self.queue.pending()?, pending))
+    }
+}
+
+/// Forwards the pooled transactions to the gateway.
+pub struct GatewaySink;
+
+#[async_trait]
+impl DeliverySink for GatewaySink {
+    async fn deliver(&self, payload: &[u8]) -> Result<(), DeliveryError> {
+        let pooled: PooledTransaction = serde_json::from_slice(payload)
+            .map_err(|e| DeliveryError::Rejected(format!("invalid transaction: {e}")))?;
+        let config = get_config().map_err(|e| DeliveryError::Transient(format!("failed to get config: {e}")))?;
+        let sequencer = SequencerGatewayProvider::new(config.feeder_gateway, config.gateway, config.chain_id, None);
+
+        let result = match pooled.transaction {
+            BroadcastedTransaction::Invoke(tx) => sequencer.add_invoke_transaction(tx).await.map(|_| ()),
+            BroadcastedTransaction::Declare(tx) => sequencer.add_declare_transaction(tx).await.map(|_| ()),
+            BroadcastedTransaction::DeployAccount(tx) => sequencer.add_deploy_account_transaction(tx).await.map(|_| ()),
+        };
+        match result {
+            Ok(()) => {
+                log::info!("📤 Forwarded pooled transaction {:#x}", pooled.transaction_hash);
+                Ok(())
+            }
+            Err(e) if is_transient(&e) => Err(DeliveryError::Transient(e.to_string())),
+            Err(e) => Err(DeliveryError::Rejected(format!("transaction {:#x}: {e}", pooled.transaction_hash))),
+        }
+    }
+}
+
+/// Whether the gateway may accept the transaction later: it rate limited us or failed to answer
+/// properly, rather than rejecting the transaction.
+fn is_transient(error: &ProviderError) -> bool {
+    matches!(error, ProviderError::RateLimited | ProviderError::Other(_))
+}
+
+impl<A: sc_transaction_pool::ChainApi, BE, G, C, P, H> Starknet<A, BE, G, C, P, H>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    H: HasherT,
+{
+    /// Forwards `transaction` to the gateway with `forward`, in nonce order if a nonce queue is
+    /// set.
+    ///
+    /// If the gateway can't be reached and a forward pool is set, the transaction is pooled to be
+    /// forwarded later, and the response is built by `pooled` from the transaction converted
+    /// locally and its hash.
+    pub(crate) async fn forward<T>(
+        &self,
+        transaction: BroadcastedTransaction,
+        forward: impl Future<Output = Result<T, ProviderError>>,
+        pooled: impl FnOnce(&AccountTransaction, FieldElement) -> T,
+    ) -> RpcResult<T> {
+        let error = match self.forward_in_nonce_order(&transaction, forward).await {
+            Ok(response) => return Ok(response),
+            Err(ProviderError::StarknetError(e)) => return Err(StarknetRpcApiError::from(e).into()),
+            Err(e) => e,
+        };
+        let Some(pool) = self.forward_pool.as_ref().filter(|_| is_transient(&error)) else {
+            log::error!("Failed to forward transaction to the gateway: {error}");
+            return Err(StarknetRpcApiError::InternalServerError.into());
+        };
+
+        let chain_id = get_config()
+            .map_err(|e| {
+                log::error!("Failed to get config: {e}");
+                StarknetRpcApiError::InternalServerError
+            })?
+            .chain_id;
+        let account_transaction = transaction.to_account_transaction().map_err(StarknetRpcApiError::from)?;
+        let transaction_hash = account_tx_hash::<H>(&account_transaction, Felt252Wrapper::from(chain_id));
+
+        pool.push(transaction_hash, transaction).map_err(|e| {
+            log::error!("Failed to pool transaction {transaction_hash:#x}: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?;
+        log::warn!("📤 Gateway unavailable ({error}), transaction {transaction_hash:#x} will be forwarded later");
+
+        Ok(pooled(&account_transaction, transaction_hash))
+    }
+}
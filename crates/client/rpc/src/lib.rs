@@ -2,52 +2,88 @@
 //!
 //! It uses the madara client and backend in order to answer queries.
 
+mod account_whitelist;
+mod block_id;
+mod config;
 mod constants;
+pub mod devnet;
+mod dto;
 mod errors;
 mod events;
+pub mod execution_memory;
+mod forward_pool;
 mod madara_backend_client;
 mod methods;
+mod metrics;
+mod nonce_queue;
+mod rate_limit;
+pub mod reexecution;
+mod spans;
+mod state_reader;
+mod transaction_versions;
+mod trie_reads;
 mod types;
 pub mod utils;
+mod versions;
 
 use std::marker::PhantomData;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
+use std::time::Duration;
 
 use errors::StarknetRpcApiError;
 use jsonrpsee::core::RpcResult;
 use jsonrpsee::proc_macros::rpc;
 use mc_db::DeoxysBackend;
 use mc_storage::OverrideHandle;
-use mp_felt::Felt252Wrapper;
+use mp_felt::{Felt252Wrapper, FeltHex};
 use mp_hashers::HasherT;
-use mp_types::block::{DBlockT, DHashT, DHeaderT};
+use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::StarknetRuntimeApi;
-use sc_network_sync::SyncingService;
 use sc_transaction_pool::{ChainApi, Pool};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use sp_api::ProvideRuntimeApi;
 use sp_arithmetic::traits::UniqueSaturatedInto;
 use sp_blockchain::HeaderBackend;
-use sp_core::H256;
-use sp_runtime::traits::Header as HeaderT;
 use starknet_api::block::BlockHash as APIBlockHash;
 use starknet_api::hash::StarkHash;
-use starknet_core::serde::unsigned_field_element::UfeHex;
 use starknet_core::types::{
     BlockHashAndNumber, BlockId, BroadcastedDeclareTransaction, BroadcastedDeployAccountTransaction,
     BroadcastedInvokeTransaction, BroadcastedTransaction, ContractClass, DeclareTransactionResult,
-    DeployAccountTransactionResult, EventFilterWithPage, EventsPage, FeeEstimate, FieldElement, FunctionCall,
-    InvokeTransactionResult, MaybePendingBlockWithReceipts, MaybePendingBlockWithTxHashes, MaybePendingBlockWithTxs,
-    MaybePendingStateUpdate, MsgFromL1, SimulatedTransaction, SimulationFlag, SimulationFlagForEstimateFee, StateDiff,
-    SyncStatusType, Transaction, TransactionReceiptWithBlockInfo, TransactionStatus, TransactionTraceWithHash,
+    DeployAccountTransactionResult, EventFilterWithPage, FieldElement, FunctionCall, InvokeTransactionResult,
+    MaybePendingBlockWithReceipts, MaybePendingBlockWithTxHashes, MaybePendingStateUpdate, MsgFromL1,
+    SimulatedTransaction, SimulationFlag, SimulationFlagForEstimateFee, StateDiff, SyncStatusType, Transaction,
+    TransactionReceiptWithBlockInfo, TransactionStatus, TransactionTraceWithHash,
 };
 
+pub use crate::account_whitelist::AccountClassWhitelist;
+use crate::block_id::BlockIdCache;
+pub use crate::block_id::ResolvedBlock;
+pub use crate::config::RpcConfig;
+use crate::constants::{BLOCK_ID_CACHE_SIZE, MAX_HEADERS_PER_SECOND, STATE_CACHE_SIZE};
+use crate::devnet::DevnetPool;
+pub use crate::dto::{v0_6, EventsPage, FeeEstimate, MaybePendingBlockWithTxs, PriceUnit};
+pub use crate::forward_pool::{ForwardPool, GatewaySink};
 use crate::methods::get_block::{
     get_block_with_tx_hashes_finalized, get_block_with_tx_hashes_pending, get_block_with_txs_finalized,
     get_block_with_txs_pending,
 };
+pub use crate::methods::write::dry_run::{AddTransactionResult, DryRunResult};
+pub use crate::metrics::register_metrics;
+pub use crate::nonce_queue::NonceQueue;
+use crate::rate_limit::RateLimiter;
+pub use crate::rate_limit::{MethodLimiters, MethodLimits};
+use crate::state_reader::StateCache;
+pub use crate::types::{
+    BlockLifecycle, ChainInfo, ClassDeclarationsPage, CompactHeader, CompiledClassSource, ContractData, ContractDiff,
+    ContractStorageKeys, ContractStorageValues, DataSource, DataSourceScores, DbColumnStats, DbStats, DeclaredClass,
+    DecodedTransaction, EdgePath, EventInclusionProof, FeltChange, GetProofOutput, HeadersPage, PoolStatus,
+    PooledTransactionStatus, ProofNode, QuarantinedClass, StorageSlotChange, TransactionEventsPage,
+    TransactionInclusionProof, VerificationStatus,
+};
 use crate::utils::*;
+pub use crate::versions::RpcVersion;
 
 // Starknet RPC API trait and types
 //
@@ -57,9 +93,12 @@ use crate::utils::*;
 
 #[serde_as]
 #[derive(Serialize, Deserialize, Clone, Copy)]
-pub struct Felt(#[serde_as(as = "UfeHex")] pub FieldElement);
+pub struct Felt(#[serde_as(as = "FeltHex")] pub FieldElement);
 
 /// Starknet write rpc interface.
+///
+/// All methods take an optional `dry_run` parameter (a Deoxys extension): when `true`, the
+/// transaction is validated and its fee estimated locally, but it is not submitted.
 #[rpc(server, namespace = "starknet")]
 pub trait StarknetWriteRpcApi {
     /// Submit a new transaction to be added to the chain
@@ -67,21 +106,132 @@ pub trait StarknetWriteRpcApi {
     async fn add_invoke_transaction(
         &self,
         invoke_transaction: BroadcastedInvokeTransaction,
-    ) -> RpcResult<InvokeTransactionResult>;
+        dry_run: Option<bool>,
+    ) -> RpcResult<AddTransactionResult<InvokeTransactionResult>>;
 
     /// Submit a new class declaration transaction
     #[method(name = "addDeployAccountTransaction")]
     async fn add_deploy_account_transaction(
         &self,
         deploy_account_transaction: BroadcastedDeployAccountTransaction,
-    ) -> RpcResult<DeployAccountTransactionResult>;
+        dry_run: Option<bool>,
+    ) -> RpcResult<AddTransactionResult<DeployAccountTransactionResult>>;
 
     /// Submit a new deploy account transaction
     #[method(name = "addDeclareTransaction")]
     async fn add_declare_transaction(
         &self,
         declare_transaction: BroadcastedDeclareTransaction,
-    ) -> RpcResult<DeclareTransactionResult>;
+        dry_run: Option<bool>,
+    ) -> RpcResult<AddTransactionResult<DeclareTransactionResult>>;
+}
+
+/// Deoxys specific rpc interface.
+#[rpc(server, namespace = "deoxys")]
+pub trait DeoxysRpcApi {
+    /// Get the headers of the blocks in the given range, paginated
+    #[method(name = "getHeaders")]
+    fn get_headers(&self, from: u64, to: u64) -> RpcResult<HeadersPage>;
+
+    /// Decode a broadcasted transaction, computing its hash without submitting it
+    #[method(name = "decodeTransaction")]
+    fn decode_transaction(&self, transaction: BroadcastedTransaction) -> RpcResult<DecodedTransaction>;
+
+    /// Get the classes declared in the given range of blocks, paginated
+    #[method(name = "getClassDeclarations")]
+    fn get_class_declarations(&self, from: u64, to: u64) -> RpcResult<ClassDeclarationsPage>;
+
+    /// Get the chain id, and the Starknet OS registered on L1
+    #[method(name = "getChainInfo")]
+    fn get_chain_info(&self) -> RpcResult<ChainInfo>;
+
+    /// Get the events emitted by a transaction, paginated
+    #[method(name = "getTransactionEvents")]
+    fn get_transaction_events(
+        &self,
+        transaction_hash: FieldElement,
+        continuation_token: Option<u64>,
+    ) -> RpcResult<TransactionEventsPage>;
+
+    /// Get the contracts whose storage, class or nonce were modified by a block
+    #[method(name = "getModifiedContracts")]
+    fn get_modified_contracts(&self, block_id: BlockId) -> RpcResult<Vec<FieldElement>>;
+
+    /// Get the values of many storage slots of many contracts at once
+    #[method(name = "getStorageAtBatch")]
+    async fn get_storage_at_batch(
+        &self,
+        block_id: BlockId,
+        contracts: Vec<ContractStorageKeys>,
+    ) -> RpcResult<Vec<ContractStorageValues>>;
+
+    /// Get the net changes made to a contract between two blocks
+    #[method(name = "getContractDiff")]
+    fn get_contract_diff(
+        &self,
+        contract_address: FieldElement,
+        from_block: BlockId,
+        to_block: BlockId,
+    ) -> RpcResult<ContractDiff>;
+
+    /// Get the last imported block and the last one verified against its header
+    #[method(name = "getVerificationStatus")]
+    fn get_verification_status(&self) -> RpcResult<VerificationStatus>;
+
+    /// Get the Merkle proofs of the inclusion of a transaction and of its events in their block
+    #[method(name = "getTransactionInclusionProof")]
+    fn get_transaction_inclusion_proof(&self, transaction_hash: FieldElement) -> RpcResult<TransactionInclusionProof>;
+}
+
+/// Deoxys administration rpc interface.
+///
+/// These methods are unsafe: they should only be served when unsafe RPC methods are allowed.
+#[rpc(server, namespace = "deoxys")]
+pub trait DeoxysAdminRpcApi {
+    /// Get the estimated size of each database column
+    #[method(name = "dbStats")]
+    fn db_stats(&self) -> RpcResult<DbStats>;
+
+    /// Start compacting the given database columns, or all of them, in the background
+    #[method(name = "compactDb")]
+    fn compact_db(&self, columns: Option<Vec<String>>) -> RpcResult<Vec<String>>;
+
+    /// Get the classes which do not match their class hash or declared compiled class hash
+    #[method(name = "getQuarantinedClasses")]
+    fn get_quarantined_classes(&self) -> RpcResult<Vec<QuarantinedClass>>;
+
+    /// Get the time spent by the last synced blocks in each step of the sync
+    #[method(name = "getBlockLifecycleReport")]
+    fn get_block_lifecycle_report(&self, blocks: u64) -> RpcResult<Vec<BlockLifecycle>>;
+
+    /// Get the data sources of the sync, with their scores
+    #[method(name = "getDataSources")]
+    fn get_data_sources(&self) -> RpcResult<Vec<DataSource>>;
+
+    /// Send all the requests of the sync to the given data source, or select them by score again
+    #[method(name = "pinDataSource")]
+    fn pin_data_source(&self, name: Option<String>) -> RpcResult<()>;
+
+    /// Stop or resume sending the requests of the sync to the given data source
+    #[method(name = "banDataSource")]
+    fn ban_data_source(&self, name: String, banned: bool) -> RpcResult<()>;
+
+    /// Get the transactions waiting to be forwarded to the gateway
+    #[method(name = "poolStatus")]
+    fn pool_status(&self) -> RpcResult<PoolStatus>;
+}
+
+/// Pathfinder compatible rpc interface.
+#[rpc(server, namespace = "pathfinder")]
+pub trait PathfinderRpcApi {
+    /// Get the Merkle proofs of a contract and of some of its storage keys
+    #[method(name = "getProof")]
+    async fn get_proof(
+        &self,
+        block_id: BlockId,
+        contract_address: FieldElement,
+        keys: Vec<FieldElement>,
+    ) -> RpcResult<GetProofOutput>;
 }
 
 #[rpc(server, namespace = "starknet")]
@@ -98,9 +248,9 @@ pub trait StarknetReadRpcApi {
     #[method(name = "blockHashAndNumber")]
     fn block_hash_and_number(&self) -> RpcResult<BlockHashAndNumber>;
 
-    /// Call a contract function at a given block id
+    /// Call a contract function at a given block id, or the default block of the node
     #[method(name = "call")]
-    fn call(&self, request: FunctionCall, block_id: BlockId) -> RpcResult<Vec<String>>;
+    fn call(&self, request: FunctionCall, block_id: Option<BlockId>) -> RpcResult<Vec<String>>;
 
     /// Get the chain id
     #[method(name = "chainId")]
@@ -110,18 +260,20 @@ pub trait StarknetReadRpcApi {
     #[method(name = "getBlockTransactionCount")]
     fn get_block_transaction_count(&self, block_id: BlockId) -> RpcResult<u128>;
 
-    /// Estimate the fee associated with transaction
+    /// Estimate the fee associated with transaction, at a given block id or the default block of
+    /// the node
     #[method(name = "estimateFee")]
     async fn estimate_fee(
         &self,
         request: Vec<BroadcastedTransaction>,
         simulation_flags: Vec<SimulationFlagForEstimateFee>,
-        block_id: BlockId,
+        block_id: Option<BlockId>,
     ) -> RpcResult<Vec<FeeEstimate>>;
 
-    /// Estimate the L2 fee of a message sent on L1
+    /// Estimate the L2 fee of a message sent on L1, at a given block id or the default block of
+    /// the node
     #[method(name = "estimateMessageFee")]
-    async fn estimate_message_fee(&self, message: MsgFromL1, block_id: BlockId) -> RpcResult<FeeEstimate>;
+    async fn estimate_message_fee(&self, message: MsgFromL1, block_id: Option<BlockId>) -> RpcResult<FeeEstimate>;
 
     /// Get block information with full transactions and receipts given the block id
     #[method(name = "getBlockWithReceipts")]
@@ -152,13 +304,20 @@ pub trait StarknetReadRpcApi {
     #[method(name = "getEvents")]
     async fn get_events(&self, filter: EventFilterWithPage) -> RpcResult<EventsPage>;
 
-    /// Get the nonce associated with the given address at the given block
+    /// Get the nonce associated with the given address at the given block, or the default block of
+    /// the node
     #[method(name = "getNonce")]
-    fn get_nonce(&self, block_id: BlockId, contract_address: FieldElement) -> RpcResult<Felt>;
+    fn get_nonce(&self, block_id: Option<BlockId>, contract_address: FieldElement) -> RpcResult<Felt>;
 
-    /// Get the value of the storage at the given address and key, at the given block id
+    /// Get the value of the storage at the given address and key, at the given block id or the
+    /// default block of the node
     #[method(name = "getStorageAt")]
-    fn get_storage_at(&self, contract_address: FieldElement, key: FieldElement, block_id: BlockId) -> RpcResult<Felt>;
+    async fn get_storage_at(
+        &self,
+        contract_address: FieldElement,
+        key: FieldElement,
+        block_id: Option<BlockId>,
+    ) -> RpcResult<Felt>;
 
     /// Get the details of a transaction by a given block id and index
     #[method(name = "getTransactionByBlockIdAndIndex")]
@@ -217,10 +376,26 @@ pub struct Starknet<A: ChainApi, BE, G, C, P, H> {
     pool: Arc<P>,
     #[allow(dead_code)]
     graph: Arc<Pool<A>>,
-    sync_service: Arc<SyncingService<DBlockT>>,
-    starting_block: <DHeaderT as HeaderT>::Number,
     #[allow(dead_code)]
     genesis_provider: Arc<G>,
+    /// Maximum number of bytes a single blockifier execution is allowed to allocate
+    execution_memory_limit: Option<usize>,
+    headers_rate_limiter: Arc<RateLimiter>,
+    /// Limiters of the proof and trace methods, shared by the servers of all versions
+    method_limiters: Arc<MethodLimiters>,
+    /// State values read by the executions of the node
+    state_cache: Arc<StateCache>,
+    /// Starknet numbers and hashes of recently resolved blocks
+    block_id_cache: Arc<BlockIdCache>,
+    /// Classes of the accounts allowed to send transactions, all if `None`
+    account_class_whitelist: Option<Arc<AccountClassWhitelist>>,
+    rpc_config: RpcConfig,
+    /// Transactions waiting for the next local block, in devnet mode
+    devnet_pool: Option<Arc<DevnetPool>>,
+    /// Next nonces of the accounts forwarding transactions, if the node orders them
+    nonce_queue: Option<Arc<NonceQueue>>,
+    /// Transactions waiting to be forwarded once the gateway is back, if they are pooled
+    forward_pool: Option<Arc<ForwardPool>>,
     _marker: PhantomData<(DBlockT, BE, H)>,
 }
 
@@ -231,11 +406,37 @@ impl<A: ChainApi, BE, G, C, P, H> Starknet<A, BE, G, C, P, H> {
         overrides: Arc<OverrideHandle<DBlockT>>,
         pool: Arc<P>,
         graph: Arc<Pool<A>>,
-        sync_service: Arc<SyncingService<DBlockT>>,
-        starting_block: <DHeaderT as HeaderT>::Number,
         genesis_provider: Arc<G>,
+        execution_memory_limit: Option<usize>,
+        account_class_whitelist: Option<Arc<AccountClassWhitelist>>,
+        rpc_config: RpcConfig,
+        devnet_pool: Option<Arc<DevnetPool>>,
+        method_limiters: Arc<MethodLimiters>,
+        nonce_queue: Option<Arc<NonceQueue>>,
+        forward_pool: Option<Arc<ForwardPool>>,
     ) -> Self {
-        Self { client, overrides, pool, graph, sync_service, starting_block, genesis_provider, _marker: PhantomData }
+        Self {
+            client,
+            overrides,
+            pool,
+            graph,
+            genesis_provider,
+            execution_memory_limit,
+            headers_rate_limiter: Arc::new(RateLimiter::new(MAX_HEADERS_PER_SECOND, Duration::from_secs(1))),
+            state_cache: Arc::new(StateCache::new(
+                NonZeroUsize::new(STATE_CACHE_SIZE).expect("State cache size should not be zero"),
+            )),
+            block_id_cache: Arc::new(BlockIdCache::new(
+                NonZeroUsize::new(BLOCK_ID_CACHE_SIZE).expect("Block id cache size should not be zero"),
+            )),
+            account_class_whitelist,
+            rpc_config,
+            devnet_pool,
+            method_limiters,
+            nonce_queue,
+            forward_pool,
+            _marker: PhantomData,
+        }
     }
 }
 
@@ -243,6 +444,15 @@ impl<A: ChainApi, BE, G, C, P, H> Starknet<A, BE, G, C, P, H> {
     fn chain_id(&self) -> RpcResult<Felt> {
         methods::read::chain_id::chain_id()
     }
+
+    /// Returns the configured chain id, as used in the execution context of blocks.
+    fn execution_chain_id(&self) -> RpcResult<starknet_api::core::ChainId> {
+        let chain_id = Felt252Wrapper(self.chain_id()?.0).from_utf8().map_err(|e| {
+            log::error!("Failed to decode the chain id: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?;
+        Ok(starknet_api::core::ChainId(chain_id))
+    }
 }
 
 impl<A: ChainApi, BE, G, C, P, H> Starknet<A, BE, G, C, P, H>
@@ -259,7 +469,7 @@ where
     C: HeaderBackend<DBlockT> + 'static,
 {
     pub fn current_spec_version(&self) -> RpcResult<String> {
-        Ok("0.7.0".to_string())
+        Ok(RpcVersion::LATEST.spec_version().to_string())
     }
 }
 
@@ -270,58 +480,6 @@ where
     C::Api: StarknetRuntimeApi<DBlockT>,
     H: HasherT + Send + Sync + 'static,
 {
-    pub fn current_block_hash(&self) -> Result<H256, StarknetRpcApiError> {
-        let substrate_block_hash = self.client.info().best_hash;
-
-        let starknet_block = match get_block_by_block_hash(self.client.as_ref(), substrate_block_hash) {
-            Ok(block) => block,
-            Err(_) => return Err(StarknetRpcApiError::BlockNotFound),
-        };
-        Ok(starknet_block.header().hash::<H>().into())
-    }
-
-    /// Returns the substrate block hash corresponding to the given Starknet block id
-    fn substrate_block_hash_from_starknet_block(&self, block_id: BlockId) -> Result<DHashT, StarknetRpcApiError> {
-        match block_id {
-            BlockId::Hash(h) => madara_backend_client::load_hash(self.client.as_ref(), Felt252Wrapper::from(h).into())
-                .map_err(|e| {
-                    log::error!("Failed to load Starknet block hash for Substrate block with hash '{h}': {e}");
-                    StarknetRpcApiError::BlockNotFound
-                })?,
-            BlockId::Number(n) => self
-                .client
-                .hash(UniqueSaturatedInto::unique_saturated_into(n))
-                .map_err(|_| StarknetRpcApiError::BlockNotFound)?,
-            BlockId::Tag(_) => Some(self.client.info().best_hash),
-        }
-        .ok_or(StarknetRpcApiError::BlockNotFound)
-    }
-
-    /// Helper function to get the substrate block number from a Starknet block id
-    ///
-    /// # Arguments
-    ///
-    /// * `block_id` - The Starknet block id
-    ///
-    /// # Returns
-    ///
-    /// * `u64` - The substrate block number
-    fn substrate_block_number_from_starknet_block(&self, block_id: BlockId) -> Result<u64, StarknetRpcApiError> {
-        // Short circuit on block number
-        if let BlockId::Number(x) = block_id {
-            return Ok(x);
-        }
-
-        let substrate_block_hash = self.substrate_block_hash_from_starknet_block(block_id)?;
-
-        let starknet_block = match get_block_by_block_hash(self.client.as_ref(), substrate_block_hash) {
-            Ok(block) => block,
-            Err(_) => return Err(StarknetRpcApiError::BlockNotFound),
-        };
-
-        Ok(starknet_block.header().block_number)
-    }
-
     /// Returns a list of all transaction hashes in the given block.
     ///
     /// # Arguments
@@ -0,0 +1,28 @@
+use jsonrpsee::core::RpcResult;
+use mc_sync::fetch::sources::{self, SourceError};
+
+use crate::errors::StarknetRpcApiError;
+
+/// Stop or resume sending the requests of the sync to a data source.
+///
+/// A banned source gets no requests, whatever its score, until it is unbanned or pinned. Banning
+/// the pinned source unpins it.
+///
+/// ### Arguments
+///
+/// * `name` - The name of the source, as returned by `deoxys_getDataSources`.
+/// * `banned` - Whether to ban or unban the source.
+///
+/// ### Errors
+///
+/// * `UNKNOWN_DATA_SOURCE` - If there is no source with this name.
+/// * `LAST_DATA_SOURCE` - If all the other sources are banned.
+pub fn ban_data_source(name: String, banned: bool) -> RpcResult<()> {
+    sources::ban(&name, banned).map_err(|e| {
+        log::debug!("Failed to ban data source {name}: {e}");
+        match e {
+            SourceError::Unknown(_) => StarknetRpcApiError::UnknownDataSource.into(),
+            SourceError::LastSource(_) => StarknetRpcApiError::LastDataSource.into(),
+        }
+    })
+}
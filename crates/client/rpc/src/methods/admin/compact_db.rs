@@ -0,0 +1,55 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use jsonrpsee::core::RpcResult;
+use mc_db::{Column, DeoxysBackend};
+
+use crate::errors::StarknetRpcApiError;
+
+/// Whether a compaction started through the RPC is running.
+static COMPACTING: AtomicBool = AtomicBool::new(false);
+
+/// Start compacting database columns in the background.
+///
+/// Compaction reclaims the space taken by overwritten and deleted values, which RocksDB would
+/// otherwise only reclaim gradually. It rewrites the compacted columns, so it can take hours and
+/// temporarily use more disk space on the largest ones.
+///
+/// ### Arguments
+///
+/// * `columns` - The names of the columns to compact, as returned by `deoxys_dbStats`. All the
+///   columns are compacted if not set.
+///
+/// ### Returns
+///
+/// Returns the names of the columns being compacted. Progress is reported in the node logs.
+///
+/// ### Errors
+///
+/// * `UNKNOWN_COLUMN` - If one of the columns does not exist.
+/// * `COMPACTION_IN_PROGRESS` - If a compaction started through this method is still running.
+pub fn compact_db(columns: Option<Vec<String>>) -> RpcResult<Vec<String>> {
+    let columns = match columns {
+        Some(names) => names
+            .iter()
+            .map(|name| {
+                Column::from_name(name).ok_or_else(|| {
+                    log::debug!("Unknown database column: {name}");
+                    StarknetRpcApiError::UnknownColumn
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Column::ALL.to_vec(),
+    };
+
+    if COMPACTING.swap(true, Ordering::SeqCst) {
+        return Err(StarknetRpcApiError::CompactionInProgress.into());
+    }
+
+    let names = columns.iter().map(Column::to_string).collect();
+    std::thread::spawn(move || {
+        DeoxysBackend::maintenance().compact(&columns);
+        COMPACTING.store(false, Ordering::SeqCst);
+    });
+
+    Ok(names)
+}
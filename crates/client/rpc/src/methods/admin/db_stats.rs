@@ -0,0 +1,40 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::DeoxysBackend;
+
+use crate::errors::StarknetRpcApiError;
+use crate::types::{DbColumnStats, DbStats};
+
+/// Get the estimated size of the database, by column.
+///
+/// This gives operators visibility into what the data directory is made of, and in particular
+/// how much of the bonsai storages is taken by the trie logs kept to revert the tries.
+///
+/// ### Returns
+///
+/// Returns the estimated size and number of keys of each column, as reported by RocksDB, along
+/// with the total size of the trie logs and their share of the bonsai storages size.
+pub fn db_stats() -> RpcResult<DbStats> {
+    let stats = DeoxysBackend::maintenance().column_stats().map_err(|e| {
+        log::error!("Failed to retrieve the database column stats: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+
+    let total_size = stats.iter().map(|column| column.sst_files_size).sum();
+    let bonsai_size: u64 =
+        stats.iter().filter(|column| column.column.is_bonsai()).map(|column| column.sst_files_size).sum();
+    let trie_log_size =
+        stats.iter().filter(|column| column.column.is_trie_log()).map(|column| column.sst_files_size).sum();
+    let trie_log_overhead = if bonsai_size == 0 { 0.0 } else { trie_log_size as f64 / bonsai_size as f64 };
+
+    let columns = stats
+        .into_iter()
+        .map(|column| DbColumnStats {
+            column: column.column.to_string(),
+            live_data_size: column.live_data_size,
+            sst_files_size: column.sst_files_size,
+            estimated_keys: column.estimated_keys,
+        })
+        .collect();
+
+    Ok(DbStats { columns, total_size, trie_log_size, trie_log_overhead })
+}
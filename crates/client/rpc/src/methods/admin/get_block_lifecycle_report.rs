@@ -0,0 +1,41 @@
+use jsonrpsee::core::RpcResult;
+use mc_sync::lifecycle::{self, Phase, LIFECYCLES_CAPACITY};
+
+use crate::types::BlockLifecycle;
+
+/// Get the time spent by the last synced blocks in each step of the sync.
+///
+/// This turns reports of a slow sync into data: the time each block spent being fetched, decoded,
+/// converted, verified, committed to the tries and to the database, sealed and mapped shows which
+/// of these steps holds the sync back.
+///
+/// ### Arguments
+///
+/// * `blocks` - The number of blocks to report, up to the last 1000 blocks which are kept.
+///
+/// ### Returns
+///
+/// Returns the time spent by each of the last `blocks` blocks in each step, in ascending block
+/// number order. Only the blocks synced since the node started are reported.
+pub fn get_block_lifecycle_report(blocks: u64) -> RpcResult<Vec<BlockLifecycle>> {
+    let count = usize::try_from(blocks).unwrap_or(usize::MAX).min(LIFECYCLES_CAPACITY);
+
+    Ok(lifecycle::last(count)
+        .into_iter()
+        .map(|block| {
+            let micros = |phase| block.phase(phase).map(|elapsed| elapsed.as_micros() as u64);
+            BlockLifecycle {
+                block_number: block.block_number,
+                fetch_us: micros(Phase::Fetch),
+                decode_us: micros(Phase::Decode),
+                convert_us: micros(Phase::Convert),
+                verify_us: micros(Phase::Verify),
+                trie_update_us: micros(Phase::TrieUpdate),
+                db_commit_us: micros(Phase::DbCommit),
+                seal_us: micros(Phase::Seal),
+                mapping_us: micros(Phase::Mapping),
+                total_us: block.total().as_micros() as u64,
+            }
+        })
+        .collect())
+}
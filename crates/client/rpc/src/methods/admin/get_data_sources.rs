@@ -0,0 +1,33 @@
+use jsonrpsee::core::RpcResult;
+use mc_sync::fetch::sources::{self, RequestKind};
+
+use crate::types::{DataSource, DataSourceScores};
+
+/// Get the data sources of the sync, with their scores.
+///
+/// The sync fetches from the primary gateway and the fallback gateways, if any are set. Each of
+/// them is scored from the latency of its responses, its rate of failures and how far behind the
+/// chain head it is, and each request goes to the source with the best score for its kind.
+///
+/// ### Returns
+///
+/// Returns the data sources used since the node started, the primary gateway first.
+pub fn get_data_sources() -> RpcResult<Vec<DataSource>> {
+    Ok(sources::report()
+        .into_iter()
+        .map(|source| DataSource {
+            name: source.name.to_string(),
+            url: source.url.clone(),
+            latency_ms: source.latency.map(|latency| latency.as_millis() as u64),
+            error_rate: source.error_rate,
+            head: source.head,
+            pinned: source.pinned,
+            banned: source.banned,
+            scores: DataSourceScores {
+                head: source.score(RequestKind::Head),
+                block: source.score(RequestKind::Block),
+                class: source.score(RequestKind::Class),
+            },
+        })
+        .collect())
+}
@@ -0,0 +1,57 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::{DeoxysBackend, QuarantineReason, QuarantineResolution};
+use mp_felt::Felt252Wrapper;
+use starknet_api::hash::StarkFelt;
+
+use crate::errors::StarknetRpcApiError;
+use crate::types::{CompiledClassSource, QuarantinedClass};
+
+/// Get the classes quarantined by the sync.
+///
+/// A Sierra class is quarantined when the node compiles it to a different compiled class hash than
+/// the one declared on chain, usually because the compiler of the node is not the one of the
+/// sequencer. The sync then uses the compiled class of the feeder gateway if it matches the
+/// declared hash, and the one compiled by the node otherwise.
+///
+/// A class is also quarantined when the feeder gateway serves a definition which does not hash to
+/// its class hash. The sync then uses the served class.
+///
+/// ### Returns
+///
+/// Returns the quarantined classes, in ascending declaration block order, along with the class
+/// used by the node for each of them.
+pub fn get_quarantined_classes() -> RpcResult<Vec<QuarantinedClass>> {
+    let classes = DeoxysBackend::class_quarantine().all().map_err(|e| {
+        log::error!("Failed to retrieve the quarantined classes: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+
+    let felt = |felt: StarkFelt| Felt252Wrapper::from(felt).into();
+    Ok(classes
+        .into_iter()
+        .map(|class| {
+            let mut quarantined = QuarantinedClass {
+                class_hash: felt(class.class_hash.0),
+                block_number: class.block_number,
+                computed_class_hash: None,
+                declared_compiled_class_hash: None,
+                computed_compiled_class_hash: None,
+                compiler_version: None,
+                compiled_class_source: match class.resolution {
+                    QuarantineResolution::GatewayCasm => CompiledClassSource::Gateway,
+                    QuarantineResolution::LocalCasm => CompiledClassSource::Local,
+                    QuarantineResolution::Rejected => CompiledClassSource::Rejected,
+                },
+            };
+            match class.reason {
+                QuarantineReason::CompiledClassHash { declared, computed, compiler_version } => {
+                    quarantined.declared_compiled_class_hash = Some(felt(declared.0));
+                    quarantined.computed_compiled_class_hash = Some(felt(computed.0));
+                    quarantined.compiler_version = Some(compiler_version);
+                }
+                QuarantineReason::ClassHash { computed } => quarantined.computed_class_hash = Some(felt(computed.0)),
+            }
+            quarantined
+        })
+        .collect())
+}
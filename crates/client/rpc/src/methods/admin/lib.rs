@@ -0,0 +1,67 @@
+use jsonrpsee::core::RpcResult;
+use mc_genesis_data_provider::GenesisProvider;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sc_transaction_pool::ChainApi;
+use sc_transaction_pool_api::TransactionPool;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+
+use super::ban_data_source::*;
+use super::compact_db::*;
+use super::db_stats::*;
+use super::get_block_lifecycle_report::*;
+use super::get_data_sources::*;
+use super::get_quarantined_classes::*;
+use super::pin_data_source::*;
+use super::pool_status::*;
+use crate::spans::traced;
+use crate::types::{BlockLifecycle, DataSource, DbStats, PoolStatus, QuarantinedClass};
+use crate::{DeoxysAdminRpcApiServer, Starknet};
+
+impl<A, BE, G, C, P, H> DeoxysAdminRpcApiServer for Starknet<A, BE, G, C, P, H>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    fn db_stats(&self) -> RpcResult<DbStats> {
+        traced("deoxys_dbStats", db_stats)
+    }
+
+    fn compact_db(&self, columns: Option<Vec<String>>) -> RpcResult<Vec<String>> {
+        traced("deoxys_compactDb", || compact_db(columns))
+    }
+
+    fn get_quarantined_classes(&self) -> RpcResult<Vec<QuarantinedClass>> {
+        traced("deoxys_getQuarantinedClasses", get_quarantined_classes)
+    }
+
+    fn get_block_lifecycle_report(&self, blocks: u64) -> RpcResult<Vec<BlockLifecycle>> {
+        traced("deoxys_getBlockLifecycleReport", || get_block_lifecycle_report(blocks))
+    }
+
+    fn get_data_sources(&self) -> RpcResult<Vec<DataSource>> {
+        traced("deoxys_getDataSources", get_data_sources)
+    }
+
+    fn pin_data_source(&self, name: Option<String>) -> RpcResult<()> {
+        traced("deoxys_pinDataSource", || pin_data_source(name))
+    }
+
+    fn ban_data_source(&self, name: String, banned: bool) -> RpcResult<()> {
+        traced("deoxys_banDataSource", || ban_data_source(name, banned))
+    }
+
+    fn pool_status(&self) -> RpcResult<PoolStatus> {
+        traced("deoxys_poolStatus", || pool_status(self))
+    }
+}
@@ -0,0 +1,9 @@
+pub mod ban_data_source;
+pub mod compact_db;
+pub mod db_stats;
+pub mod get_block_lifecycle_report;
+pub mod get_data_sources;
+pub mod get_quarantined_classes;
+pub mod lib;
+pub mod pin_data_source;
+pub mod pool_status;
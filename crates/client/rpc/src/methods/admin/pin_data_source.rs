@@ -0,0 +1,24 @@
+use jsonrpsee::core::RpcResult;
+use mc_sync::fetch::sources;
+
+use crate::errors::StarknetRpcApiError;
+
+/// Send all the requests of the sync to one data source.
+///
+/// Pinning a source overrides the selection by score, e.g. to keep using a gateway known to be
+/// reliable while another one looks faster. The pinned source is unbanned.
+///
+/// ### Arguments
+///
+/// * `name` - The name of the source, as returned by `deoxys_getDataSources`. The sources are
+///   selected by score again if not set.
+///
+/// ### Errors
+///
+/// * `UNKNOWN_DATA_SOURCE` - If there is no source with this name.
+pub fn pin_data_source(name: Option<String>) -> RpcResult<()> {
+    sources::pin(name.as_deref()).map_err(|e| {
+        log::debug!("Failed to pin a data source: {e}");
+        StarknetRpcApiError::UnknownDataSource.into()
+    })
+}
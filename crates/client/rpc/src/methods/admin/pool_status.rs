@@ -0,0 +1,43 @@
+use jsonrpsee::core::RpcResult;
+use sc_transaction_pool::ChainApi;
+
+use crate::constants::MAX_POOL_STATUS_TRANSACTIONS;
+use crate::errors::StarknetRpcApiError;
+use crate::types::{PoolStatus, PooledTransactionStatus};
+use crate::Starknet;
+
+/// Get the transactions waiting to be forwarded to the gateway.
+///
+/// When the transactions which could not be forwarded are pooled, with `--rpc-forward-retries`,
+/// they are retried with exponential backoff until the gateway accepts or rejects them.
+///
+/// ### Returns
+///
+/// Returns the number of pooled transactions, along with the oldest of them, up to 1000, and the
+/// state of their forwarding.
+pub fn pool_status<A, BE, G, C, P, H>(starknet: &Starknet<A, BE, G, C, P, H>) -> RpcResult<PoolStatus>
+where
+    A: ChainApi,
+{
+    let Some(pool) = &starknet.forward_pool else {
+        return Ok(PoolStatus { enabled: false, pending: 0, transactions: Vec::new() });
+    };
+
+    let (pending, transactions) = pool.pending(MAX_POOL_STATUS_TRANSACTIONS).map_err(|e| {
+        log::error!("Failed to read the forward pool: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+
+    Ok(PoolStatus {
+        enabled: true,
+        pending: pending as u64,
+        transactions: transactions
+            .into_iter()
+            .map(|forward| PooledTransactionStatus {
+                transaction_hash: forward.transaction_hash,
+                attempts: forward.attempts,
+                next_attempt: forward.next_attempt,
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,66 @@
+use blockifier::transaction::account_transaction::AccountTransaction;
+use jsonrpsee::core::RpcResult;
+use mc_sync::utility::get_config;
+use mp_convert::transaction::to_starknet_core_tx;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_transactions::from_broadcasted_transactions::ToAccountTransaction;
+use starknet_core::types::BroadcastedTransaction;
+
+use crate::errors::StarknetRpcApiError;
+use crate::types::DecodedTransaction;
+use crate::utils::{account_tx_hash, account_tx_to_api_tx};
+
+/// Decode a broadcasted transaction the way the node understands it.
+///
+/// This is meant for SDK developers, to check their serialization and hashing of transactions
+/// against the node's implementation without submitting anything.
+///
+/// ### Arguments
+///
+/// * `transaction` - The transaction, as it would be sent to one of the `add*Transaction` methods.
+///
+/// ### Returns
+///
+/// Returns the hash of the transaction on the chain the node is connected to, the address of the
+/// deployed account for deploy account transactions, the hash of the declared class for declare
+/// transactions, and the transaction in its normalized form.
+///
+/// ### Errors
+///
+/// * `INVALID_CONTRACT_CLASS` - If the class of a declare transaction can't be processed.
+/// * `COMPILED_CLASS_HASH_MISMATCH` - If the compiled class hash of a declare transaction does not
+///   match its class.
+/// * `UNSUPPORTED_TX_VERSION` - If the transaction version is not supported.
+pub fn decode_transaction<H>(transaction: BroadcastedTransaction) -> RpcResult<DecodedTransaction>
+where
+    H: HasherT + Send + Sync + 'static,
+{
+    let chain_id = get_config()
+        .map_err(|e| {
+            log::error!("Failed to get config: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .chain_id;
+
+    let account_transaction = transaction.to_account_transaction().map_err(|e| {
+        log::debug!("Failed to decode transaction: {e}");
+        StarknetRpcApiError::from(e)
+    })?;
+    let transaction_hash = account_tx_hash::<H>(&account_transaction, Felt252Wrapper::from(chain_id));
+
+    let (contract_address, class_hash) = match &account_transaction {
+        AccountTransaction::DeployAccount(tx) => {
+            (Some(Felt252Wrapper::from(*tx.contract_address.0.key()).into()), None)
+        }
+        AccountTransaction::Declare(tx) => (None, Some(Felt252Wrapper::from(tx.tx().class_hash().0).into())),
+        AccountTransaction::Invoke(_) => (None, None),
+    };
+
+    Ok(DecodedTransaction {
+        transaction_hash,
+        contract_address,
+        class_hash,
+        transaction: to_starknet_core_tx(account_tx_to_api_tx(&account_transaction), transaction_hash),
+    })
+}
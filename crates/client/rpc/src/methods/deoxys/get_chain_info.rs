@@ -0,0 +1,40 @@
+use jsonrpsee::core::RpcResult;
+use mc_sync::l1::get_os_config;
+use mc_sync::utility::get_config;
+use mp_felt::Felt252Wrapper;
+use starknet_core::types::BlockTag;
+
+use crate::errors::StarknetRpcApiError;
+use crate::types::ChainInfo;
+
+/// Get information about the chain followed by the node.
+///
+/// Along with the chain id, this returns the Starknet OS registered in the core contract on L1,
+/// which is the OS state updates are proven with. Operators can watch its program and config
+/// hashes to detect OS upgrades of the network before their node supports them.
+///
+/// ### Arguments
+///
+/// * `default_block` - The block read by the methods whose block id is omitted.
+///
+/// ### Returns
+///
+/// Returns the chain id, the address of the Starknet core contract, and the hashes of the
+/// Starknet OS program and config, which are `null` until they have been read from L1. The block
+/// read by the methods whose block id is omitted, `default_block`, is also returned, so that
+/// wallets don't have to special-case nodes.
+pub fn get_chain_info(default_block: BlockTag) -> RpcResult<ChainInfo> {
+    let config = get_config().map_err(|e| {
+        log::error!("Failed to get config: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+    let os_config = get_os_config();
+
+    Ok(ChainInfo {
+        chain_id: config.chain_id,
+        l1_core_contract_address: format!("{:#x}", config.l1_core_address),
+        os_program_hash: os_config.map(|os_config| Felt252Wrapper::from(os_config.program_hash).into()),
+        os_config_hash: os_config.map(|os_config| Felt252Wrapper::from(os_config.config_hash).into()),
+        default_block,
+    })
+}
@@ -0,0 +1,87 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::DeoxysBackend;
+use mc_genesis_data_provider::GenesisProvider;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sc_transaction_pool::ChainApi;
+use sc_transaction_pool_api::TransactionPool;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::{BlockId, BlockTag};
+
+use crate::constants::MAX_CLASS_DECLARATIONS_CHUNK_SIZE;
+use crate::errors::StarknetRpcApiError;
+use crate::types::{ClassDeclarationsPage, DeclaredClass};
+use crate::Starknet;
+
+/// Get the classes declared in a range of blocks.
+///
+/// This is meant for provers and indexers which need to know which classes to compile or fetch
+/// ahead of time, without going through the state update of every block.
+///
+/// ### Arguments
+///
+/// * `from` - The number of the first block of the range.
+/// * `to` - The number of the last block of the range, inclusive.
+///
+/// ### Returns
+///
+/// Returns a page of class declarations, in ascending block number order. Pages hold about
+/// `MAX_CLASS_DECLARATIONS_CHUNK_SIZE` classes, but are never split in the middle of a block. If
+/// the range could not be covered in a single page, `continuation_block` holds the `from` value to
+/// use to query the next page. Blocks past the latest block, or the last verified block when the
+/// node only serves verified blocks, are ignored.
+///
+/// ### Errors
+///
+/// * `BLOCK_NOT_FOUND` - If `from` is past the latest block, or the last verified block.
+pub fn get_class_declarations<A, BE, G, C, P, H>(
+    starknet: &Starknet<A, BE, G, C, P, H>,
+    from: u64,
+    to: u64,
+) -> RpcResult<ClassDeclarationsPage>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    if from > to {
+        return Ok(ClassDeclarationsPage { declarations: vec![], continuation_block: None });
+    }
+
+    // the last verified block when only verified blocks are served
+    let latest_block = starknet.resolve_block_id(BlockId::Tag(BlockTag::Latest))?.number;
+    if from > latest_block {
+        return Err(StarknetRpcApiError::BlockNotFound.into());
+    }
+
+    let to = to.min(latest_block);
+    let page = DeoxysBackend::class().declarations(from, to, MAX_CLASS_DECLARATIONS_CHUNK_SIZE).map_err(|e| {
+        log::error!("Failed to retrieve class declarations from block {from} to {to}: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+
+    let declarations = page
+        .declarations
+        .into_iter()
+        .map(|(block_number, declaration)| DeclaredClass {
+            block_number,
+            class_hash: Felt252Wrapper::from(declaration.class_hash.0).into(),
+            compiled_class_hash: declaration
+                .compiled_class_hash
+                .map(|compiled_class_hash| Felt252Wrapper::from(compiled_class_hash.0).into()),
+            size: declaration.size,
+        })
+        .collect();
+
+    Ok(ClassDeclarationsPage { declarations, continuation_block: page.continuation_block })
+}
@@ -0,0 +1,112 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::{ContractDiffError, DeoxysBackend};
+use mc_genesis_data_provider::GenesisProvider;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sc_transaction_pool::ChainApi;
+use sc_transaction_pool_api::TransactionPool;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_api::core::ContractAddress;
+use starknet_api::hash::StarkFelt;
+use starknet_core::types::{BlockId, FieldElement};
+
+use crate::constants::MAX_CONTRACT_DIFF_SLOTS;
+use crate::errors::StarknetRpcApiError;
+use crate::types::{ContractDiff, FeltChange, StorageSlotChange};
+use crate::Starknet;
+
+/// Get the net changes made to a contract between two blocks.
+///
+/// Only the storage slots, nonce and class hash whose value differs between the states right after
+/// the two blocks are returned: a slot set and then reset to its previous value in between is not
+/// part of the diff, and neither is a slot set to zero which was never set. The diff is computed
+/// from the flat storage, from a single snapshot of the database, by comparing the slots updated
+/// by the blocks in between.
+///
+/// ### Arguments
+///
+/// * `contract_address` - The address of the contract.
+/// * `from_block` - The hash, number or tag of the block after which the old values are read.
+/// * `to_block` - The hash, number or tag of the block after which the new values are read. It may
+///   come before `from_block`.
+///
+/// ### Returns
+///
+/// Returns the changed storage slots ordered by key, and the change of nonce and class hash if
+/// they changed. Values which were never set are zero.
+///
+/// ### Errors
+///
+/// * `BLOCK_NOT_FOUND` - If either block does not exist.
+/// * `PENDING_BLOCK_UNSUPPORTED` - If the pending block is requested.
+/// * `STORAGE_HISTORY_UNAVAILABLE` - If the flat storage was not backfilled up to the blocks yet,
+///   or did not index the storage updates of the blocks in between.
+/// * `CONTRACT_DIFF_LIMIT_EXCEEDED` - If more than [`MAX_CONTRACT_DIFF_SLOTS`] storage slots were
+///   updated in between.
+pub fn get_contract_diff<A, BE, G, C, P, H>(
+    starknet: &Starknet<A, BE, G, C, P, H>,
+    contract_address: FieldElement,
+    from_block: BlockId,
+    to_block: BlockId,
+) -> RpcResult<ContractDiff>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    let from_number = starknet.resolve_block_id(from_block)?.not_pending()?.number;
+    let to_number = starknet.resolve_block_id(to_block)?.not_pending()?.number;
+
+    let flat_storage = DeoxysBackend::contract_storage();
+    let available = flat_storage.is_available(from_number.max(to_number)).map_err(|e| {
+        log::error!("Failed to read the backfill progress of the flat storage: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+    if !available {
+        return Err(StarknetRpcApiError::StorageHistoryUnavailable.into());
+    }
+
+    let address: ContractAddress = Felt252Wrapper(contract_address).into();
+    let diff =
+        flat_storage.contract_diff(&address, from_number, to_number, MAX_CONTRACT_DIFF_SLOTS).map_err(|e| match e {
+            ContractDiffError::TooManySlots(_) => StarknetRpcApiError::ContractDiffLimitExceeded,
+            ContractDiffError::Unindexed(_) => StarknetRpcApiError::StorageHistoryUnavailable,
+            ContractDiffError::Db(e) => {
+                log::error!(
+                    "Failed to compute the diff of contract {contract_address:#x} between blocks {from_number} and \
+                     {to_number}: {e}"
+                );
+                StarknetRpcApiError::InternalServerError
+            }
+        })?;
+
+    Ok(ContractDiff {
+        contract_address,
+        storage_diffs: diff
+            .storage
+            .into_iter()
+            .map(|(key, change)| StorageSlotChange {
+                key: Felt252Wrapper::from(key.0.0).into(),
+                change: felt_change(change.before, change.after),
+            })
+            .collect(),
+        nonce: diff.nonce.map(|change| felt_change(change.before.map(|n| n.0), change.after.map(|n| n.0))),
+        class_hash: diff.class_hash.map(|change| felt_change(change.before.map(|c| c.0), change.after.map(|c| c.0))),
+    })
+}
+
+fn felt_change(before: Option<StarkFelt>, after: Option<StarkFelt>) -> FeltChange {
+    let to_field_element =
+        |value: Option<StarkFelt>| value.map_or(FieldElement::ZERO, |v| Felt252Wrapper::from(v).into());
+    FeltChange { old_value: to_field_element(before), new_value: to_field_element(after) }
+}
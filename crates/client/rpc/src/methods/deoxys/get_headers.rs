@@ -0,0 +1,105 @@
+use jsonrpsee::core::RpcResult;
+use mc_genesis_data_provider::GenesisProvider;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sc_transaction_pool::ChainApi;
+use sc_transaction_pool_api::TransactionPool;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::{BlockId, BlockTag};
+
+use crate::constants::MAX_HEADERS_CHUNK_SIZE;
+use crate::errors::StarknetRpcApiError;
+use crate::types::{CompactHeader, HeadersPage};
+use crate::utils::{
+    l1_da_mode, l1_data_gas_price, l1_gas_price, new_root, parent_hash, sequencer_address, starknet_version, status,
+    timestamp,
+};
+use crate::Starknet;
+
+/// Get the headers of a range of blocks.
+///
+/// This is meant for light clients and monitoring tools which only need header fields, and would
+/// otherwise have to issue a `getBlockWithTxHashes` call per block.
+///
+/// ### Arguments
+///
+/// * `from` - The number of the first block of the range.
+/// * `to` - The number of the last block of the range, inclusive.
+///
+/// ### Returns
+///
+/// Returns a page of at most `MAX_HEADERS_CHUNK_SIZE` headers, in ascending block number order. If
+/// the range could not be covered in a single page, `continuation_block` holds the `from` value to
+/// use to query the next page. Blocks past the latest block, or the last verified block when the
+/// node only serves verified blocks, are ignored.
+///
+/// ### Errors
+///
+/// * `BLOCK_NOT_FOUND` - If `from` is past the latest block, or the last verified block.
+/// * `TOO_MANY_REQUESTS` - If too many headers were requested recently.
+pub fn get_headers<A, BE, G, C, P, H>(
+    starknet: &Starknet<A, BE, G, C, P, H>,
+    from: u64,
+    to: u64,
+) -> RpcResult<HeadersPage>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    if from > to {
+        return Ok(HeadersPage { headers: vec![], continuation_block: None });
+    }
+
+    // the last verified block when only verified blocks are served
+    let latest_block = starknet.resolve_block_id(BlockId::Tag(BlockTag::Latest))?.number;
+    if from > latest_block {
+        return Err(StarknetRpcApiError::BlockNotFound.into());
+    }
+
+    let to = to.min(latest_block);
+    let last = to.min(from.saturating_add(MAX_HEADERS_CHUNK_SIZE - 1));
+
+    if !starknet.headers_rate_limiter.try_acquire(last - from + 1) {
+        log::error!("Rate limit reached while serving headers from block {from} to {last}");
+        return Err(StarknetRpcApiError::TooManyRequests.into());
+    }
+
+    let headers = (from..=last)
+        .map(|block_number| {
+            let block = starknet.resolve_block_id(BlockId::Number(block_number))?;
+            let block_hash = block.starknet_hash.into();
+            let block = starknet.starknet_block(block)?;
+            let header = block.header();
+
+            Ok(CompactHeader {
+                status: status(block_number),
+                block_hash,
+                parent_hash: parent_hash(&block),
+                block_number,
+                new_root: new_root(&block),
+                timestamp: timestamp(&block),
+                sequencer_address: sequencer_address(&block),
+                l1_gas_price: l1_gas_price(&block),
+                l1_data_gas_price: l1_data_gas_price(&block),
+                l1_da_mode: l1_da_mode(&block),
+                starknet_version: starknet_version(&block),
+                transaction_count: header.transaction_count,
+                event_count: header.event_count,
+            })
+        })
+        .collect::<Result<Vec<_>, StarknetRpcApiError>>()?;
+
+    let continuation_block = if last < to { Some(last + 1) } else { None };
+
+    Ok(HeadersPage { headers, continuation_block })
+}
@@ -0,0 +1,63 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::DeoxysBackend;
+use mc_genesis_data_provider::GenesisProvider;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sc_transaction_pool::ChainApi;
+use sc_transaction_pool_api::TransactionPool;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::{BlockId, FieldElement};
+
+use crate::errors::StarknetRpcApiError;
+use crate::Starknet;
+
+/// Get the contracts modified by a block.
+///
+/// A contract is modified by a block if the block updates its storage, deploys it, replaces its
+/// class or updates its nonce. This lets indexers only re-read the contracts touched by each
+/// block instead of diffing the whole state.
+///
+/// ### Arguments
+///
+/// * `block_id` - The hash, number or tag of the block.
+///
+/// ### Returns
+///
+/// Returns the addresses of the modified contracts, in ascending order.
+///
+/// ### Errors
+///
+/// * `BLOCK_NOT_FOUND` - If the block does not exist.
+/// * `PENDING_BLOCK_UNSUPPORTED` - If the pending block is requested.
+/// * `MODIFIED_CONTRACTS_UNAVAILABLE` - If the flat storage of the block is yet to be backfilled.
+pub fn get_modified_contracts<A, BE, G, C, P, H>(
+    starknet: &Starknet<A, BE, G, C, P, H>,
+    block_id: BlockId,
+) -> RpcResult<Vec<FieldElement>>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    let block_number = starknet.resolve_block_id(block_id)?.not_pending()?.number;
+
+    let contracts = DeoxysBackend::contract_storage()
+        .modified_contracts(block_number)
+        .map_err(|e| {
+            log::error!("Failed to read the contracts modified by block {block_number}: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .ok_or(StarknetRpcApiError::ModifiedContractsUnavailable)?;
+
+    Ok(contracts.into_iter().map(|address| Felt252Wrapper::from(address).into()).collect())
+}
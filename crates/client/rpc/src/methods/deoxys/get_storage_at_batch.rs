@@ -0,0 +1,108 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::storage::StorageHandler;
+use mc_db::DeoxysBackend;
+use mc_genesis_data_provider::GenesisProvider;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sc_transaction_pool::ChainApi;
+use sc_transaction_pool_api::TransactionPool;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_api::core::ContractAddress;
+use starknet_api::state::StorageKey;
+use starknet_core::types::{BlockId, FieldElement};
+
+use crate::constants::MAX_STORAGE_BATCH_KEYS;
+use crate::errors::StarknetRpcApiError;
+use crate::trie_reads::read_tries;
+use crate::types::{ContractStorageKeys, ContractStorageValues};
+use crate::Starknet;
+
+/// Get the values of many storage slots of many contracts at once.
+///
+/// Indexers reading thousands of slots per block would otherwise issue as many
+/// `starknet_getStorageAt` calls. When the flat storage holds the block, the slots are read from a
+/// single snapshot of the database.
+///
+/// ### Arguments
+///
+/// * `block_id` - The hash, number or tag of the block after which the storage is read.
+/// * `contracts` - The contracts to read, with the keys to read in each of them.
+///
+/// ### Returns
+///
+/// Returns the values of the slots, by contract and in the order of the requested keys. Slots
+/// which were never set are zero.
+///
+/// ### Errors
+///
+/// * `BLOCK_NOT_FOUND` - If the block does not exist.
+/// * `PENDING_BLOCK_UNSUPPORTED` - If the pending block is requested.
+/// * `PROOF_LIMIT_EXCEEDED` - If more than [`MAX_STORAGE_BATCH_KEYS`] keys are requested.
+pub async fn get_storage_at_batch<A, BE, G, C, P, H>(
+    starknet: &Starknet<A, BE, G, C, P, H>,
+    block_id: BlockId,
+    contracts: Vec<ContractStorageKeys>,
+) -> RpcResult<Vec<ContractStorageValues>>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    if contracts.iter().map(|contract| contract.keys.len()).sum::<usize>() > MAX_STORAGE_BATCH_KEYS {
+        return Err(StarknetRpcApiError::ProofLimitExceeded.into());
+    }
+    let block_number = starknet.resolve_block_id(block_id)?.not_pending()?.number;
+
+    let slots: Vec<(ContractAddress, StorageKey)> = contracts
+        .iter()
+        .flat_map(|contract| {
+            let contract_address: ContractAddress = Felt252Wrapper(contract.contract_address).into();
+            contract.keys.iter().map(move |key| (contract_address, Felt252Wrapper(*key).into()))
+        })
+        .collect();
+
+    // the flat storage is preferred, unless the block was not backfilled yet
+    let flat_storage = DeoxysBackend::contract_storage();
+    let values: Vec<Option<FieldElement>> = if flat_storage.is_available(block_number).unwrap_or_default() {
+        flat_storage
+            .get_many_at(slots.iter().map(|(contract_address, key)| (contract_address, key)), block_number)
+            .map_err(|e| {
+                log::error!("Failed to read {} storage slots at block {block_number}: {e}", slots.len());
+                StarknetRpcApiError::InternalServerError
+            })?
+            .into_iter()
+            .map(|value| value.map(|value| Felt252Wrapper::from(value).into()))
+            .collect()
+    } else {
+        read_tries(move || {
+            let storage = StorageHandler::contract_storage_at(block_number)?;
+            slots
+                .iter()
+                .map(|(contract_address, key)| storage.get(contract_address, key))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .await?
+        .into_iter()
+        .map(|value| value.map(|value| Felt252Wrapper::from(value).into()))
+        .collect()
+    };
+
+    let mut values = values.into_iter().map(|value| value.unwrap_or(FieldElement::ZERO));
+    Ok(contracts
+        .into_iter()
+        .map(|contract| ContractStorageValues {
+            contract_address: contract.contract_address,
+            values: values.by_ref().take(contract.keys.len()).collect(),
+        })
+        .collect())
+}
@@ -0,0 +1,70 @@
+use jsonrpsee::core::RpcResult;
+use mc_genesis_data_provider::GenesisProvider;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sc_transaction_pool::ChainApi;
+use sc_transaction_pool_api::TransactionPool;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::FieldElement;
+
+use crate::constants::MAX_TRANSACTION_EVENTS_CHUNK_SIZE;
+use crate::errors::StarknetRpcApiError;
+use crate::methods::read::get_transaction_receipt::transaction_events;
+use crate::types::TransactionEventsPage;
+use crate::Starknet;
+
+/// Get the events emitted by a transaction, paginated.
+///
+/// `starknet_getTransactionReceipt` returns all the events of a transaction at once, which makes
+/// the receipts of transactions emitting thousands of events very large. This serves the same
+/// events in pages instead.
+///
+/// ### Arguments
+///
+/// * `transaction_hash` - The hash of the transaction.
+/// * `continuation_token` - The index of the first event to return, as returned with the previous
+///   page. Starts from the first event if unset.
+///
+/// ### Returns
+///
+/// Returns a page of at most `MAX_TRANSACTION_EVENTS_CHUNK_SIZE` events, in emission order. If the
+/// transaction emitted more events, `continuation_token` holds the token of the next page.
+///
+/// ### Errors
+///
+/// * `TXN_HASH_NOT_FOUND` - If the transaction is not in a finalized block.
+/// * `INVALID_CONTINUATION_TOKEN` - If the token is past the events of the transaction.
+pub fn get_transaction_events<A, BE, G, C, P, H>(
+    starknet: &Starknet<A, BE, G, C, P, H>,
+    transaction_hash: FieldElement,
+    continuation_token: Option<u64>,
+) -> RpcResult<TransactionEventsPage>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    let mut events = transaction_events(starknet, transaction_hash)?;
+
+    // tokens are only returned when there are events left
+    if continuation_token.is_some_and(|token| token >= events.len() as u64) {
+        return Err(StarknetRpcApiError::InvalidContinuationToken.into());
+    }
+    let start = continuation_token.unwrap_or(0) as usize;
+
+    let end = events.len().min(start + MAX_TRANSACTION_EVENTS_CHUNK_SIZE);
+    let continuation_token = (end < events.len()).then_some(end as u64);
+    events.truncate(end);
+    events.drain(..start);
+
+    Ok(TransactionEventsPage { events, continuation_token })
+}
@@ -0,0 +1,148 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::DeoxysBackend;
+use mc_genesis_data_provider::GenesisProvider;
+use mc_sync::commitments::events::memory_event_proofs;
+use mc_sync::commitments::proofs::InclusionProof;
+use mc_sync::commitments::transactions::{memory_transaction_proof, transaction_hashes};
+use mc_sync::utility::get_config;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sc_transaction_pool::ChainApi;
+use sc_transaction_pool_api::TransactionPool;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::{BlockId, FieldElement};
+
+use crate::errors::StarknetRpcApiError;
+use crate::methods::pathfinder::get_proof::proof_node;
+use crate::methods::read::get_transaction_receipt::transaction_index;
+use crate::types::{EventInclusionProof, ProofNode, TransactionInclusionProof};
+use crate::utils::get_block_by_block_hash;
+use crate::Starknet;
+
+/// Get the Merkle proofs of the inclusion of a transaction and of its events in their block.
+///
+/// The transaction and event commitments of a block header are the roots of tries of the
+/// transaction hashes with signature, and of the event hashes, keyed by their index in the block.
+/// These proofs let external verifiers check that a transaction and its events are part of a block
+/// given only its header, without trusting the node.
+///
+/// ### Arguments
+///
+/// * `transaction_hash` - The hash of the transaction.
+///
+/// ### Returns
+///
+/// Returns the commitments of the block of the transaction, as found in its header, along with the
+/// proof of the transaction in the transaction commitment and one proof per event it emitted in the
+/// event commitment. The proofs list the nodes from the root down to the leaf.
+///
+/// ### Errors
+///
+/// * `TXN_HASH_NOT_FOUND` - If the transaction is not in a finalized block, or not in a verified
+///   block when the node only serves verified blocks.
+pub fn get_transaction_inclusion_proof<A, BE, G, C, P, H>(
+    starknet: &Starknet<A, BE, G, C, P, H>,
+    transaction_hash: FieldElement,
+) -> RpcResult<TransactionInclusionProof>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    let substrate_block_hash = DeoxysBackend::mapping()
+        .block_hash_from_transaction_hash(Felt252Wrapper::from(transaction_hash).into())
+        .map_err(|e| {
+            log::error!("Failed to retrieve substrate block hash: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .ok_or(StarknetRpcApiError::TxnHashNotFound)?;
+    let block_number = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)
+        .map_err(|e| {
+            log::error!("Failed to retrieve the block of transaction {transaction_hash:#x}: {e}");
+            StarknetRpcApiError::TxnHashNotFound
+        })?
+        .header()
+        .block_number;
+
+    // the block must still be in the local chain, and verified if only verified blocks are served
+    let resolved = starknet.resolve_block_id(BlockId::Number(block_number)).map_err(|e| match e {
+        StarknetRpcApiError::BlockNotFound => StarknetRpcApiError::TxnHashNotFound,
+        e => e,
+    })?;
+    if resolved.substrate_hash != substrate_block_hash {
+        return Err(StarknetRpcApiError::TxnHashNotFound.into());
+    }
+    let block = starknet.starknet_block(resolved)?;
+    let header = block.header();
+
+    let chain_id = starknet.chain_id()?;
+    let tx_index = transaction_index(starknet, chain_id, &block, resolved.starknet_hash, transaction_hash)?;
+
+    // the events are grouped by transaction, in block order
+    let mut event_count = 0;
+    let mut transaction_events = 0..0;
+    for ordered in block.events() {
+        if ordered.index() == tx_index as u128 {
+            transaction_events = event_count..event_count + ordered.events().len();
+        }
+        event_count += ordered.events().len();
+    }
+
+    let hasher = get_config()
+        .map_err(|e| {
+            log::error!("Failed to get config: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .hashers
+        .transaction;
+    let proof_error = |e| {
+        log::error!("Failed to prove the inclusion of transaction {transaction_hash:#x}: {e}");
+        StarknetRpcApiError::InternalServerError
+    };
+    let transaction_hashes = transaction_hashes(block.transactions(), Felt252Wrapper(chain_id.0), block_number, hasher);
+    let protocol_version = &header.protocol_version;
+    let transaction_proof = memory_transaction_proof(
+        block.transactions(),
+        &transaction_hashes,
+        block_number,
+        protocol_version,
+        hasher,
+        tx_index,
+    )
+    .map_err(proof_error)?;
+    let event_proofs =
+        memory_event_proofs(block.events(), &transaction_hashes, protocol_version, hasher, transaction_events.clone())
+            .map_err(proof_error)?;
+
+    Ok(TransactionInclusionProof {
+        block_number,
+        block_hash: resolved.starknet_hash.into(),
+        transaction_index: tx_index as u64,
+        transaction_commitment: Felt252Wrapper::from(header.transaction_commitment).into(),
+        transaction_leaf: transaction_proof.leaf.into(),
+        transaction_proof: nodes(transaction_proof),
+        event_commitment: Felt252Wrapper::from(header.event_commitment).into(),
+        event_proofs: transaction_events
+            .zip(event_proofs)
+            .map(|(event_index, proof)| EventInclusionProof {
+                event_index: event_index as u64,
+                leaf: proof.leaf.into(),
+                proof: nodes(proof),
+            })
+            .collect(),
+    })
+}
+
+fn nodes(proof: InclusionProof) -> Vec<ProofNode> {
+    proof.nodes.into_iter().map(proof_node).collect()
+}
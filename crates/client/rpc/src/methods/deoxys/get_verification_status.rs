@@ -0,0 +1,59 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::DeoxysBackend;
+use mc_genesis_data_provider::GenesisProvider;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sc_transaction_pool::ChainApi;
+use sc_transaction_pool_api::TransactionPool;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+
+use crate::errors::StarknetRpcApiError;
+use crate::types::VerificationStatus;
+use crate::Starknet;
+
+/// Get how far the imported blocks were verified.
+///
+/// Blocks are served as soon as they are imported, and verified against their header in the
+/// background: their transaction, event, receipt and state diff commitments and their hash are
+/// computed again from their content. Users can weigh the data served after the last verified
+/// block accordingly, or query a node only serving verified blocks.
+///
+/// ### Returns
+///
+/// Returns the last imported block, the last verified block, `null` if no block was verified yet,
+/// and whether the node only serves the verified blocks, as set with `--rpc-only-verified`.
+///
+/// ### Errors
+///
+/// * `INTERNAL_SERVER_ERROR` - If the last verified block can't be read.
+pub fn get_verification_status<A, BE, G, C, P, H>(
+    starknet: &Starknet<A, BE, G, C, P, H>,
+) -> RpcResult<VerificationStatus>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    let verified_block = DeoxysBackend::meta()
+        .verified_block()
+        .map_err(|e| {
+            log::error!("Failed to load the last verified block: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .map(|verified| verified.number);
+
+    Ok(VerificationStatus {
+        imported_block: starknet.current_block_number()?,
+        verified_block,
+        only_verified: starknet.rpc_config.only_verified,
+    })
+}
@@ -0,0 +1,95 @@
+use jsonrpsee::core::{async_trait, RpcResult};
+use mc_genesis_data_provider::GenesisProvider;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sc_transaction_pool::ChainApi;
+use sc_transaction_pool_api::TransactionPool;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::{BlockId, BroadcastedTransaction, FieldElement};
+
+use super::decode_transaction::*;
+use super::get_chain_info::*;
+use super::get_class_declarations::*;
+use super::get_contract_diff::*;
+use super::get_headers::*;
+use super::get_modified_contracts::*;
+use super::get_storage_at_batch::*;
+use super::get_transaction_events::*;
+use super::get_transaction_inclusion_proof::*;
+use super::get_verification_status::*;
+use crate::spans::{traced, traced_async};
+use crate::types::{
+    ChainInfo, ClassDeclarationsPage, ContractDiff, ContractStorageKeys, ContractStorageValues, DecodedTransaction,
+    HeadersPage, TransactionEventsPage, TransactionInclusionProof, VerificationStatus,
+};
+use crate::{DeoxysRpcApiServer, Starknet};
+
+#[async_trait]
+impl<A, BE, G, C, P, H> DeoxysRpcApiServer for Starknet<A, BE, G, C, P, H>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    fn get_headers(&self, from: u64, to: u64) -> RpcResult<HeadersPage> {
+        traced("deoxys_getHeaders", || get_headers(self, from, to))
+    }
+
+    fn decode_transaction(&self, transaction: BroadcastedTransaction) -> RpcResult<DecodedTransaction> {
+        traced("deoxys_decodeTransaction", || decode_transaction::<H>(transaction))
+    }
+
+    fn get_class_declarations(&self, from: u64, to: u64) -> RpcResult<ClassDeclarationsPage> {
+        traced("deoxys_getClassDeclarations", || get_class_declarations(self, from, to))
+    }
+
+    fn get_chain_info(&self) -> RpcResult<ChainInfo> {
+        traced("deoxys_getChainInfo", || get_chain_info(self.rpc_config.default_block))
+    }
+
+    fn get_transaction_events(
+        &self,
+        transaction_hash: FieldElement,
+        continuation_token: Option<u64>,
+    ) -> RpcResult<TransactionEventsPage> {
+        traced("deoxys_getTransactionEvents", || get_transaction_events(self, transaction_hash, continuation_token))
+    }
+
+    fn get_modified_contracts(&self, block_id: BlockId) -> RpcResult<Vec<FieldElement>> {
+        traced("deoxys_getModifiedContracts", || get_modified_contracts(self, block_id))
+    }
+
+    async fn get_storage_at_batch(
+        &self,
+        block_id: BlockId,
+        contracts: Vec<ContractStorageKeys>,
+    ) -> RpcResult<Vec<ContractStorageValues>> {
+        traced_async("deoxys_getStorageAtBatch", get_storage_at_batch(self, block_id, contracts)).await
+    }
+
+    fn get_contract_diff(
+        &self,
+        contract_address: FieldElement,
+        from_block: BlockId,
+        to_block: BlockId,
+    ) -> RpcResult<ContractDiff> {
+        traced("deoxys_getContractDiff", || get_contract_diff(self, contract_address, from_block, to_block))
+    }
+
+    fn get_verification_status(&self) -> RpcResult<VerificationStatus> {
+        traced("deoxys_getVerificationStatus", || get_verification_status(self))
+    }
+
+    fn get_transaction_inclusion_proof(&self, transaction_hash: FieldElement) -> RpcResult<TransactionInclusionProof> {
+        traced("deoxys_getTransactionInclusionProof", || get_transaction_inclusion_proof(self, transaction_hash))
+    }
+}
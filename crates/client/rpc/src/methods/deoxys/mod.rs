@@ -0,0 +1,11 @@
+pub mod decode_transaction;
+pub mod get_chain_info;
+pub mod get_class_declarations;
+pub mod get_contract_diff;
+pub mod get_headers;
+pub mod get_modified_contracts;
+pub mod get_storage_at_batch;
+pub mod get_transaction_inclusion_proof;
+pub mod get_transaction_events;
+pub mod get_verification_status;
+pub mod lib;
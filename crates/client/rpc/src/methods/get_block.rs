@@ -1,9 +1,12 @@
 use jsonrpsee::core::error::Error;
 use jsonrpsee::core::RpcResult;
+use mc_db::DeoxysBackend;
 use mc_genesis_data_provider::GenesisProvider;
 use mc_sync::l2::get_pending_block;
+use mp_block::DeoxysBlock;
+use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
-use mp_types::block::{DBlockT, DHashT};
+use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
 use sc_client_api::BlockBackend;
@@ -12,20 +15,56 @@ use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_core::types::{
-    BlockWithTxHashes, BlockWithTxs, MaybePendingBlockWithTxHashes, MaybePendingBlockWithTxs, PendingBlockWithTxHashes,
-    PendingBlockWithTxs,
+    BlockWithTxHashes, BlockWithTxs, FieldElement, MaybePendingBlockWithTxHashes, MaybePendingBlockWithTxs,
+    PendingBlockWithTxHashes, PendingBlockWithTxs,
 };
 
+use crate::block_id::ResolvedBlock;
+use crate::errors::StarknetRpcApiError;
 use crate::utils::{
-    get_block_by_block_hash, l1_data_gas_price, l1_gas_price, new_root, parent_hash, sequencer_address,
-    starknet_version, status, timestamp, tx_conv, tx_hash_compute, tx_hash_retrieve,
+    l1_data_gas_price, l1_gas_price, new_root, parent_hash, sequencer_address, starknet_version, status, timestamp,
+    tx_conv, tx_hash_compute, tx_hash_retrieve,
 };
 use crate::{l1_da_mode, Felt, Starknet};
 
+/// Hashes of the transactions of `starknet_block`, the imported block `block`.
+///
+/// They are read from the transaction index, then from the cache of transaction hashes, and only
+/// computed for the blocks indexed in neither.
+fn transaction_hashes<A, BE, G, C, P, H>(
+    server: &Starknet<A, BE, G, C, P, H>,
+    starknet_block: &DeoxysBlock,
+    block: ResolvedBlock,
+    chain_id: Felt,
+) -> RpcResult<Vec<FieldElement>>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    let indexed = DeoxysBackend::transaction().block_hashes(block.number).map_err(|e| {
+        log::error!("Failed to get the transaction hashes of block {} from transaction_db: {e}", block.number);
+        StarknetRpcApiError::InternalServerError
+    })?;
+    if indexed.len() == starknet_block.transactions().len() {
+        return Ok(indexed.into_iter().map(|hash| Felt252Wrapper::from(hash.0).into()).collect());
+    }
+
+    Ok(match server.get_cached_transaction_hashes(block.starknet_hash.into()) {
+        Some(tx_hashes) => tx_hash_retrieve(tx_hashes),
+        None => tx_hash_compute::<H>(starknet_block, chain_id),
+    })
+}
+
 pub(crate) fn get_block_with_tx_hashes_finalized<A, BE, G, C, P, H>(
     server: &Starknet<A, BE, G, C, P, H>,
     chain_id: Felt,
-    substrate_block_hash: DHashT,
+    block: ResolvedBlock,
 ) -> RpcResult<MaybePendingBlockWithTxHashes>
 where
     A: ChainApi<Block = DBlockT> + 'static,
@@ -37,14 +76,10 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let starknet_block = get_block_by_block_hash(server.client.as_ref(), substrate_block_hash)?;
+    let starknet_block = server.starknet_block(block)?;
 
-    let block_hash = starknet_block.header().hash::<H>();
-    let transactions = if let Some(tx_hashes) = server.get_cached_transaction_hashes(block_hash.into()) {
-        tx_hash_retrieve(tx_hashes)
-    } else {
-        tx_hash_compute::<H>(&starknet_block, chain_id)
-    };
+    let block_hash = block.starknet_hash;
+    let transactions = transaction_hashes(server, &starknet_block, block, chain_id)?;
 
     let block_number = starknet_block.header().block_number;
     let status = status(block_number);
@@ -108,7 +143,7 @@ where
 pub(crate) fn get_block_with_txs_finalized<A, BE, G, C, P, H>(
     server: &Starknet<A, BE, G, C, P, H>,
     chain_id: Felt,
-    substrate_block_hash: DHashT,
+    block: ResolvedBlock,
 ) -> RpcResult<MaybePendingBlockWithTxs>
 where
     A: ChainApi<Block = DBlockT> + 'static,
@@ -120,14 +155,10 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let starknet_block = get_block_by_block_hash(server.client.as_ref(), substrate_block_hash)?;
+    let starknet_block = server.starknet_block(block)?;
 
-    let block_hash = starknet_block.header().hash::<H>();
-    let tx_hashes = if let Some(tx_hashes) = server.get_cached_transaction_hashes(block_hash.into()) {
-        tx_hash_retrieve(tx_hashes)
-    } else {
-        tx_hash_compute::<H>(&starknet_block, chain_id)
-    };
+    let block_hash = block.starknet_hash;
+    let tx_hashes = transaction_hashes(server, &starknet_block, block, chain_id)?;
     let transactions = tx_conv(starknet_block.transactions(), tx_hashes);
 
     let block_number = starknet_block.header().block_number;
@@ -0,0 +1,143 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::storage::{self, StorageHandler};
+use mc_genesis_data_provider::GenesisProvider;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sc_transaction_pool::ChainApi;
+use sc_transaction_pool_api::TransactionPool;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::{BlockId, FieldElement};
+
+use crate::constants::MAX_PROOF_KEYS;
+use crate::errors::StarknetRpcApiError;
+use crate::trie_reads::read_tries;
+use crate::types::{ContractData, EdgePath, GetProofOutput, ProofNode};
+use crate::utils::new_root;
+use crate::Starknet;
+
+/// Get the Merkle proofs of a contract and of some of its storage keys, in the format of
+/// Pathfinder's `pathfinder_getProof`.
+///
+/// ### Arguments
+///
+/// * `block_id` - The block the proofs are requested for. The tries can only be read as of the
+///   blocks they can still be reverted to, the last `max_saved_trie_logs` ones.
+/// * `contract_address` - The address of the contract to prove.
+/// * `keys` - The storage keys of the contract to prove.
+///
+/// ### Returns
+///
+/// Returns the proof of the contract in the contract trie, and if the contract is deployed, its
+/// state along with one proof per requested key in its storage trie. Proofs of keys with no value
+/// are non-membership proofs.
+///
+/// ### Errors
+///
+/// * `BLOCK_NOT_FOUND` - If the specified block does not exist.
+/// * `PROOF_LIMIT_EXCEEDED` - If more than [`MAX_PROOF_KEYS`] keys are requested.
+/// * `PROOF_UNAVAILABLE` - If the tries can no longer be read as of the specified block.
+pub async fn get_proof<A, BE, G, C, P, H>(
+    starknet: &Starknet<A, BE, G, C, P, H>,
+    block_id: BlockId,
+    contract_address: FieldElement,
+    keys: Vec<FieldElement>,
+) -> RpcResult<GetProofOutput>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    if keys.len() > MAX_PROOF_KEYS {
+        return Err(StarknetRpcApiError::ProofLimitExceeded.into());
+    }
+
+    let resolved = starknet.resolve_block_id(block_id)?;
+    let substrate_block_hash = resolved.substrate_hash;
+    let block = starknet.starknet_block(resolved)?;
+    let block_number = block.header().block_number;
+
+    let contract_address = Felt252Wrapper(contract_address).into();
+    let state = starknet.overrides.for_block_hash(starknet.client.as_ref(), substrate_block_hash);
+    let contract_state = state
+        .contract_class_hash_by_address(substrate_block_hash, contract_address)
+        .map(|class_hash| (class_hash, state.nonce(substrate_block_hash, contract_address).unwrap_or_default()));
+    let deployed = contract_state.is_some();
+
+    // the proofs are read from the state of the block, even if the sync commits the next ones
+    let proofs = read_tries(move || {
+        // the state of the blocks older than the trie logs can't be opened
+        let (Ok(contract_trie), Ok(class_trie)) =
+            (StorageHandler::contract_at(block_number), StorageHandler::class_at(block_number))
+        else {
+            return Ok(None);
+        };
+        let contract_proof = contract_trie.get_proof(&contract_address)?;
+        let class_commitment = class_trie.root()?;
+        let storage = if deployed {
+            let Ok(storage) = StorageHandler::contract_storage_at(block_number) else {
+                return Ok(None);
+            };
+            let proofs = keys
+                .into_iter()
+                .map(|key| storage.get_proof(&contract_address, &Felt252Wrapper(key).into()))
+                .collect::<Result<Vec<_>, _>>()?;
+            Some((storage.root(&contract_address)?, proofs))
+        } else {
+            None
+        };
+        Ok(Some((contract_proof, class_commitment, storage)))
+    })
+    .await?;
+    let (contract_proof, class_commitment, storage) = proofs.ok_or_else(|| {
+        log::debug!("The tries can no longer be read as of block {block_number}");
+        StarknetRpcApiError::ProofUnavailable
+    })?;
+    let class_commitment = Felt252Wrapper::from(class_commitment).into();
+
+    let contract_data = contract_state.zip(storage).map(|((class_hash, nonce), (root, storage_proofs))| ContractData {
+        class_hash: Felt252Wrapper::from(class_hash).into(),
+        nonce: Felt252Wrapper::from(nonce).into(),
+        root: Felt252Wrapper::from(root).into(),
+        contract_state_hash_version: FieldElement::ZERO,
+        storage_proofs: storage_proofs.into_iter().map(|proof| proof.into_iter().map(proof_node).collect()).collect(),
+    });
+
+    Ok(GetProofOutput {
+        state_commitment: Some(new_root(&block)),
+        class_commitment: (class_commitment != FieldElement::ZERO).then_some(class_commitment),
+        contract_proof: contract_proof.into_iter().map(proof_node).collect(),
+        contract_data,
+    })
+}
+
+pub(crate) fn proof_node(node: storage::ProofNode) -> ProofNode {
+    match node {
+        storage::ProofNode::Binary { left, right } => {
+            ProofNode::Binary { left: Felt252Wrapper::from(left).into(), right: Felt252Wrapper::from(right).into() }
+        }
+        storage::ProofNode::Edge { child, path } => {
+            // The path is at most 251 bits long: its value is right-aligned in a big-endian felt
+            let len = path.0.len();
+            let mut bytes = [0u8; 32];
+            for (i, bit) in path.0.iter().enumerate() {
+                if *bit {
+                    let pos = 256 - len + i;
+                    bytes[pos / 8] |= 0x80 >> (pos % 8);
+                }
+            }
+            let value = FieldElement::from_bytes_be(&bytes).expect("Edge paths are at most 251 bits long");
+
+            ProofNode::Edge { child: Felt252Wrapper::from(child).into(), path: EdgePath { value, len } }
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use jsonrpsee::core::{async_trait, RpcResult};
+use mc_genesis_data_provider::GenesisProvider;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sc_transaction_pool::ChainApi;
+use sc_transaction_pool_api::TransactionPool;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::{BlockId, FieldElement};
+
+use super::get_proof::*;
+use crate::rate_limit::MethodClass;
+use crate::spans::traced_async;
+use crate::types::GetProofOutput;
+use crate::{PathfinderRpcApiServer, Starknet};
+
+#[async_trait]
+impl<A, BE, G, C, P, H> PathfinderRpcApiServer for Starknet<A, BE, G, C, P, H>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    async fn get_proof(
+        &self,
+        block_id: BlockId,
+        contract_address: FieldElement,
+        keys: Vec<FieldElement>,
+    ) -> RpcResult<GetProofOutput> {
+        let _permit = self.method_limiters.get(MethodClass::Proof).acquire().await?;
+        traced_async("pathfinder_getProof", get_proof(self, block_id, contract_address, keys)).await
+    }
+}
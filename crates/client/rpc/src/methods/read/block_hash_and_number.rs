@@ -9,7 +9,7 @@ use sc_transaction_pool::ChainApi;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use starknet_core::types::{BlockHashAndNumber, FieldElement};
+use starknet_core::types::{BlockHashAndNumber, BlockId, BlockTag};
 
 use crate::errors::StarknetRpcApiError;
 use crate::Starknet;
@@ -23,7 +23,7 @@ use crate::Starknet;
 /// ### Returns
 ///
 /// * `block_hash_and_number` - A tuple containing the latest block hash and number of the current
-///   network.
+///   network, the last verified block if only verified blocks are served.
 pub fn block_hash_and_number<A, BE, G, C, P, H>(starknet: &Starknet<A, BE, G, C, P, H>) -> RpcResult<BlockHashAndNumber>
 where
     A: ChainApi<Block = DBlockT> + 'static,
@@ -35,14 +35,10 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let block_number = starknet.current_block_number()?;
-    let block_hash = starknet.current_block_hash().map_err(|e| {
-        log::error!("Failed to retrieve the current block hash: {}", e);
+    let block = starknet.resolve_block_id(BlockId::Tag(BlockTag::Latest)).map_err(|e| {
+        log::error!("Failed to resolve the latest block: {e}");
         StarknetRpcApiError::NoBlocks
     })?;
 
-    Ok(BlockHashAndNumber {
-        block_hash: FieldElement::from_byte_slice_be(block_hash.as_bytes()).unwrap(),
-        block_number,
-    })
+    Ok(BlockHashAndNumber { block_hash: block.starknet_hash.into(), block_number: block.number })
 }
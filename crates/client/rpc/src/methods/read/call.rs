@@ -1,3 +1,8 @@
+use blockifier::context::TransactionContext;
+use blockifier::execution::entry_point::{CallEntryPoint, CallType, EntryPointExecutionContext};
+use blockifier::transaction::objects::{DeprecatedTransactionInfo, TransactionInfo};
+use blockifier::versioned_constants::VersionedConstants;
+use cairo_vm::vm::runners::cairo_runner::ExecutionResources;
 use jsonrpsee::core::RpcResult;
 use mc_genesis_data_provider::GenesisProvider;
 use mp_felt::Felt252Wrapper;
@@ -10,15 +15,21 @@ use sc_transaction_pool::ChainApi;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
+use starknet_api::core::ContractAddress;
+use starknet_api::deprecated_contract_class::EntryPointType;
 use starknet_api::transaction::Calldata;
 use starknet_core::types::{BlockId, FunctionCall};
 
 use crate::errors::StarknetRpcApiError;
-use crate::utils::convert_error;
+use crate::execution_memory::with_memory_limit;
+use crate::state_reader::DeoxysStateReader;
 use crate::{Arc, Starknet};
 
 /// Call a Function in a Contract Without Creating a Transaction
 ///
+/// The call is executed by the node against the state right after the requested block, with
+/// storage read from the tries at that block.
+///
 /// ### Arguments
 ///
 /// * `request` - The details of the function call to be made. This includes information such as the
@@ -52,28 +63,61 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
+    let block = starknet.resolve_block_id(block_id)?;
+    let (substrate_block_hash, block_number) = (block.substrate_hash, block.number);
+
+    let block_context = starknet.client.runtime_api().get_block_context(substrate_block_hash).map_err(|e| {
+        log::error!("Failed to get block context: {e}");
+        StarknetRpcApiError::InternalServerError
     })?;
 
-    let runtime_api = starknet.client.runtime_api();
+    let contract_address: ContractAddress = Felt252Wrapper(request.contract_address).into();
+    let overrides = starknet.overrides.for_block_hash(starknet.client.as_ref(), substrate_block_hash);
+    let class_hash =
+        overrides.contract_class_hash_by_address(substrate_block_hash, contract_address).ok_or_else(|| {
+            log::debug!("Failed to retrieve contract class hash at '{contract_address:?}'");
+            StarknetRpcApiError::ContractNotFound
+        })?;
 
     let calldata = Calldata(Arc::new(request.calldata.iter().map(|x| Felt252Wrapper::from(*x).into()).collect()));
+    let entrypoint = CallEntryPoint {
+        class_hash: Some(class_hash),
+        code_address: None,
+        entry_point_type: EntryPointType::External,
+        entry_point_selector: Felt252Wrapper(request.entry_point_selector).into(),
+        calldata,
+        storage_address: contract_address,
+        caller_address: ContractAddress::default(),
+        call_type: CallType::Call,
+        initial_gas: VersionedConstants::latest_constants().tx_initial_gas(),
+    };
 
-    let result = runtime_api
-        .call(
-            substrate_block_hash,
-            Felt252Wrapper(request.contract_address).into(),
-            Felt252Wrapper(request.entry_point_selector).into(),
-            calldata,
-        )
-        .map_err(|e| {
-            log::error!("Request parameters error: {e}");
-            StarknetRpcApiError::InternalServerError
-        })?;
+    let mut context = EntryPointExecutionContext::new_invoke(
+        Arc::new(TransactionContext {
+            block_context,
+            tx_info: TransactionInfo::Deprecated(DeprecatedTransactionInfo::default()),
+        }),
+        false,
+    )
+    .map_err(|e| {
+        log::error!("Failed to create execution context: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+    let mut state = DeoxysStateReader::new(
+        overrides.as_ref(),
+        &starknet.state_cache,
+        &starknet.overrides.class_cache,
+        substrate_block_hash,
+        block_number,
+    );
 
-    let result = convert_error(starknet.client.clone(), substrate_block_hash, result)?;
+    let call_info = with_memory_limit(starknet.execution_memory_limit, || {
+        entrypoint.execute(&mut state, &mut ExecutionResources::default(), &mut context)
+    })?
+    .map_err(|e| {
+        log::debug!("Failed to call contract '{contract_address:?}': {e}");
+        StarknetRpcApiError::ContractError
+    })?;
 
-    Ok(result.iter().map(|x| format!("{:#x}", x.0)).collect())
+    Ok(call_info.execution.retdata.0.iter().map(|x| format!("{:#x}", Felt252Wrapper::from(*x).0)).collect())
 }
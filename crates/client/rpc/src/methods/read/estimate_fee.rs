@@ -1,10 +1,17 @@
+use blockifier::context::BlockContext;
+use blockifier::fee::fee_utils::calculate_tx_fee;
+use blockifier::fee::gas_usage::estimate_minimal_gas_vector;
+use blockifier::state::cached_state::{CachedState, GlobalContractCache};
 use blockifier::transaction::account_transaction::AccountTransaction;
+use blockifier::transaction::objects::{FeeType, HasRelatedFeeType};
+use blockifier::transaction::transactions::ExecutableTransaction;
 use jsonrpsee::core::RpcResult;
 use mc_genesis_data_provider::GenesisProvider;
 use mp_hashers::HasherT;
-use mp_simulations::convert_flags;
 use mp_transactions::from_broadcasted_transactions::ToAccountTransaction;
 use mp_types::block::DBlockT;
+use pallet_starknet::simulations::from_tx_info_and_gas_price;
+use pallet_starknet::types::PriceUnit;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
 use sc_client_api::BlockBackend;
@@ -17,10 +24,16 @@ use starknet_core::types::{
 };
 
 use crate::errors::StarknetRpcApiError;
+use crate::execution_memory::with_memory_limit;
+use crate::state_reader::DeoxysStateReader;
+use crate::utils::with_oracle_gas_prices;
 use crate::Starknet;
 
 /// Estimate the fee associated with transaction
 ///
+/// The transactions are executed in the node, on top of the state of the block read from the
+/// database.
+///
 /// # Arguments
 ///
 /// * `request` - starknet transaction request
@@ -45,44 +58,129 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
+    estimate_fee_after(starknet, Vec::new(), request, simulation_flags, block_id).await
+}
 
-    let transactions = request
-        .into_iter()
-        .map(|tx| tx.to_account_transaction())
-        .collect::<Result<Vec<AccountTransaction>, _>>()
-        .map_err(|e| {
-            log::error!("Failed to convert BroadcastedTransaction to AccountTransaction: {e}");
-            StarknetRpcApiError::InternalServerError
-        })?;
+/// Estimate the fee of the transactions of `request`, executed after the `preceding` ones.
+///
+/// The preceding transactions are the ones waiting to be included in the next devnet block: like
+/// the block, they are executed without charging their fee, and the ones failing are left out.
+pub(crate) async fn estimate_fee_after<A, BE, G, C, P, H>(
+    starknet: &Starknet<A, BE, G, C, P, H>,
+    preceding: Vec<BroadcastedTransaction>,
+    request: Vec<BroadcastedTransaction>,
+    simulation_flags: Vec<EstimateFeeFlag>,
+    block_id: BlockId,
+) -> RpcResult<Vec<FeeEstimate>>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    let block = starknet.resolve_block_id(block_id)?;
+    let (substrate_block_hash, block_number) = (block.substrate_hash, block.number);
+    starknet.check_transaction_versions(substrate_block_hash, &request)?;
 
-    let account_transactions: Vec<AccountTransaction> =
-        transactions.into_iter().map(AccountTransaction::from).collect();
+    let to_account_transactions = |transactions: Vec<BroadcastedTransaction>| {
+        transactions
+            .into_iter()
+            .map(|tx| tx.to_account_transaction())
+            .collect::<Result<Vec<AccountTransaction>, _>>()
+            .map_err(|e| {
+                log::error!("Failed to convert BroadcastedTransaction to AccountTransaction: {e}");
+                StarknetRpcApiError::InternalServerError
+            })
+    };
+    let preceding = to_account_transactions(preceding)?;
+    let transactions = to_account_transactions(request)?;
+    let validate = !simulation_flags.contains(&EstimateFeeFlag::SkipValidate);
 
-    let simulation_flags = convert_flags(simulation_flags);
+    let starknet_block = starknet.starknet_block(block)?;
+    let fee_token_addresses = starknet.client.runtime_api().fee_token_addresses(substrate_block_hash).map_err(|e| {
+        log::error!("Failed to get the fee token addresses at block {block_number}: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+    let block_context = starknet_block.header().into_block_context(fee_token_addresses, starknet.execution_chain_id()?);
 
-    let fee_estimates = starknet
-        .client
-        .runtime_api()
-        .estimate_fee(substrate_block_hash, account_transactions, simulation_flags)
-        .map_err(|e| {
-            log::error!("Request parameters error: {e}");
-            StarknetRpcApiError::InternalServerError
-        })?
-        .map_err(|e| {
-            log::error!("Failed to call function: {:#?}", e);
-            StarknetRpcApiError::ContractError
-        })?;
+    let overrides = starknet.overrides.for_block_hash(starknet.client.as_ref(), substrate_block_hash);
+    let mut state = CachedState::new(
+        DeoxysStateReader::new(
+            overrides.as_ref(),
+            &starknet.state_cache,
+            &starknet.overrides.class_cache,
+            substrate_block_hash,
+            block_number,
+        ),
+        GlobalContractCache::new(10),
+    );
 
-    let estimates = fee_estimates
+    // the transactions are executed in order, each on top of the state left by the previous ones
+    let estimates = with_memory_limit(starknet.execution_memory_limit, || {
+        for transaction in preceding {
+            if let Err(e) = transaction.execute(&mut state, &block_context, false, true) {
+                log::debug!("Pending transaction left out of the fee estimation: {e}");
+            }
+        }
+        transactions
             .into_iter()
-			// FIXME: https://github.com/keep-starknet-strange/madara/issues/329
-            // TODO: reflect right estimation
-            .map(|x| FeeEstimate { gas_consumed: x.gas_consumed.0 , gas_price: x.gas_price.0, data_gas_consumed: x.data_gas_consumed.0, data_gas_price: x.data_gas_price.0, overall_fee: x.overall_fee.0, unit: x.unit.into()})
-            .collect();
+            .map(|transaction| estimate(&mut state, &block_context, transaction, validate))
+            .collect::<Result<Vec<_>, _>>()
+    })??;
+
+    Ok(estimates.into_iter().map(|estimate| with_oracle_gas_prices(estimate, block_id)).collect())
+}
+
+/// Executes `transaction` without charging its fee, and estimates the fee it would be charged.
+///
+/// Estimates are never below the minimal fee of the transaction, which the sequencer charges even
+/// if the execution costs less.
+fn estimate(
+    state: &mut CachedState<DeoxysStateReader<'_>>,
+    block_context: &BlockContext,
+    transaction: AccountTransaction,
+    validate: bool,
+) -> RpcResult<FeeEstimate> {
+    let fee_type = transaction.fee_type();
+    let gas_prices = &block_context.block_info().gas_prices;
+    let gas_price = gas_prices.get_gas_price_by_fee_type(&fee_type).get();
+    let data_gas_price = gas_prices.get_data_gas_price_by_fee_type(&fee_type).get();
+    let unit = match fee_type {
+        FeeType::Strk => PriceUnit::Fri,
+        FeeType::Eth => PriceUnit::Wei,
+    };
+
+    let minimal_gas = estimate_minimal_gas_vector(block_context, &transaction).map_err(|e| {
+        log::debug!("Failed to compute the minimal fee of the transaction: {e}");
+        StarknetRpcApiError::ContractError
+    })?;
+    let mut execution_info = transaction.execute(state, block_context, false, validate).map_err(|e| {
+        log::debug!("Transaction execution failed during fee estimation: {e}");
+        StarknetRpcApiError::ContractError
+    })?;
+    if let Some(revert_error) = &execution_info.revert_error {
+        log::debug!("Transaction reverted during fee estimation: {revert_error}");
+        return Err(StarknetRpcApiError::ContractError.into());
+    }
+    if execution_info.actual_fee.0 == 0 {
+        execution_info.actual_fee = calculate_tx_fee(&execution_info.actual_resources, block_context, &fee_type)
+            .map_err(|e| {
+                log::error!("Failed to compute the fee of the transaction: {e}");
+                StarknetRpcApiError::InternalServerError
+            })?;
+    }
 
-    Ok(estimates)
+    let estimate = from_tx_info_and_gas_price(&execution_info, gas_price, data_gas_price, unit, minimal_gas);
+    Ok(FeeEstimate {
+        gas_consumed: estimate.gas_consumed.0,
+        gas_price: estimate.gas_price.0,
+        data_gas_consumed: estimate.data_gas_consumed.0,
+        data_gas_price: estimate.data_gas_price.0,
+        overall_fee: estimate.overall_fee.0,
+        unit: estimate.unit.into(),
+    })
 }
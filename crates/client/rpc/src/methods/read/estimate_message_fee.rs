@@ -1,8 +1,10 @@
 use std::sync::Arc;
 
-use blockifier::transaction::transactions::L1HandlerTransaction;
+use blockifier::fee::fee_utils::calculate_tx_fee;
+use blockifier::state::cached_state::{CachedState, GlobalContractCache};
+use blockifier::transaction::objects::FeeType;
+use blockifier::transaction::transactions::{ExecutableTransaction, L1HandlerTransaction};
 use jsonrpsee::core::RpcResult;
-use log::error;
 use mc_genesis_data_provider::GenesisProvider;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
@@ -15,16 +17,23 @@ use sc_transaction_pool::ChainApi;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use starknet_api::core::Nonce;
+use starknet_api::core::{ChainId, ContractAddress, Nonce};
 use starknet_api::hash::StarkFelt;
 use starknet_api::transaction::{Calldata, Fee, TransactionVersion};
-use starknet_core::types::{BlockId, FeeEstimate, MsgFromL1};
+use starknet_core::types::{BlockId, FeeEstimate, FieldElement, MsgFromL1, PriceUnit};
 
 use crate::errors::StarknetRpcApiError;
+use crate::execution_memory::with_memory_limit;
+use crate::state_reader::DeoxysStateReader;
+use crate::utils::with_oracle_gas_prices;
 use crate::{Starknet, StarknetReadRpcApiServer};
 
 /// Estimate the L2 fee of a message sent on L1
 ///
+/// The message is converted into the L1 handler transaction the sequencer would include for it,
+/// which is executed by the node against the state right after the requested block, at the gas
+/// prices of that block.
+///
 /// # Arguments
 ///
 /// * `message` - the message to estimate
@@ -54,43 +63,74 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
-    let block_number = starknet.block_number().map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
+    let block = starknet.resolve_block_id(block_id)?;
+    let (substrate_block_hash, block_number) = (block.substrate_hash, block.number);
+
+    let starknet_block = starknet.starknet_block(block)?;
+    let fee_token_addresses = starknet.client.runtime_api().fee_token_addresses(substrate_block_hash).map_err(|e| {
+        log::error!("Failed to get the fee token addresses at block {block_number}: {e}");
+        StarknetRpcApiError::InternalServerError
     })?;
     let chain_id = Felt252Wrapper(starknet.chain_id()?.0);
+    let execution_chain_id = ChainId(chain_id.from_utf8().map_err(|e| {
+        log::error!("Failed to decode the chain id: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?);
+    let block_context = starknet_block.header().into_block_context(fee_token_addresses, execution_chain_id);
+
+    let contract_address: ContractAddress = Felt252Wrapper(message.to_address).into();
+    let overrides = starknet.overrides.for_block_hash(starknet.client.as_ref(), substrate_block_hash);
+    if overrides.contract_class_hash_by_address(substrate_block_hash, contract_address).is_none() {
+        return Err(StarknetRpcApiError::ContractNotFound.into());
+    }
 
     let transaction = convert_message_into_tx::<H>(message, chain_id, Some(block_number));
 
-    let message_fee = starknet
-        .client
-        .runtime_api()
-        .estimate_message_fee(substrate_block_hash, transaction)
-        .map_err(|e| {
-            error!("Runtime Api error: {e}");
-            StarknetRpcApiError::InternalServerError
-        })?
-        .map_err(|e| {
-            error!("Function execution failed: {:#?}", e);
-            StarknetRpcApiError::ContractError
-        })?;
+    let mut state = CachedState::new(
+        DeoxysStateReader::new(
+            overrides.as_ref(),
+            &starknet.state_cache,
+            &starknet.overrides.class_cache,
+            substrate_block_hash,
+            block_number,
+        ),
+        GlobalContractCache::new(10),
+    );
+    let execution_info = with_memory_limit(starknet.execution_memory_limit, || {
+        transaction.execute(&mut state, &block_context, false, true)
+    })?
+    .map_err(|e| {
+        log::debug!("Failed to execute the L1 handler of '{contract_address:?}': {e}");
+        StarknetRpcApiError::ContractError
+    })?;
+
+    // the fee of L1 handlers is paid on L1, so the blockifier does not charge it
+    let fee = calculate_tx_fee(&execution_info.actual_resources, &block_context, &FeeType::Eth).map_err(|e| {
+        log::error!("Failed to compute the fee of the L1 handler of '{contract_address:?}': {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+    let gas_prices = &block_context.block_info().gas_prices;
+    let gas_price = gas_prices.eth_l1_gas_price.get();
+    let data_gas_price = gas_prices.eth_l1_data_gas_price.get();
+    let data_gas_consumed = execution_info.da_gas.l1_data_gas;
+    let gas_consumed = fee.0.saturating_sub(data_gas_consumed.saturating_mul(data_gas_price)) / gas_price;
 
     let estimate_message_fee = FeeEstimate {
-        gas_consumed: message_fee.gas_consumed.0,
-        gas_price: message_fee.gas_price.0,
-        data_gas_consumed: message_fee.data_gas_consumed.0,
-        data_gas_price: message_fee.data_gas_price.0,
-        overall_fee: message_fee.overall_fee.0,
-        unit: message_fee.unit.into(),
+        gas_consumed: FieldElement::from(gas_consumed),
+        gas_price: FieldElement::from(gas_price),
+        data_gas_consumed: FieldElement::from(data_gas_consumed),
+        data_gas_price: FieldElement::from(data_gas_price),
+        overall_fee: FieldElement::from(fee.0),
+        unit: PriceUnit::Wei,
     };
+    let estimate_message_fee = with_oracle_gas_prices(estimate_message_fee, block_id);
 
     Ok(estimate_message_fee)
 }
 
+/// Converts a message sent on L1 into the L1 handler transaction consuming it on L2.
+///
+/// The L1 sender is passed to the handler as its first argument, followed by the payload.
 pub fn convert_message_into_tx<H: HasherT + Send + Sync + 'static>(
     message: MsgFromL1,
     chain_id: Felt252Wrapper,
@@ -108,5 +148,7 @@ pub fn convert_message_into_tx<H: HasherT + Send + Sync + 'static>(
     };
     let tx_hash = tx.compute_hash::<H>(chain_id, true, block_number);
 
-    L1HandlerTransaction { tx, tx_hash, paid_fee_on_l1: Fee(10) } //TODO: fix with real fee
+    // the fee paid on L1 is unknown until the message is sent: the blockifier only refuses
+    // handlers which were paid nothing
+    L1HandlerTransaction { tx, tx_hash, paid_fee_on_l1: Fee(1) }
 }
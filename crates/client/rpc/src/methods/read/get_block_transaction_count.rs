@@ -1,5 +1,7 @@
+use jsonrpsee::core::error::Error;
 use jsonrpsee::core::RpcResult;
 use mc_genesis_data_provider::GenesisProvider;
+use mc_sync::l2::get_pending_block;
 use mp_hashers::HasherT;
 use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
@@ -11,8 +13,6 @@ use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_core::types::BlockId;
 
-use crate::errors::StarknetRpcApiError;
-use crate::utils::get_block_by_block_hash;
 use crate::Starknet;
 
 /// Get the Number of Transactions in a Given Block
@@ -24,7 +24,8 @@ use crate::Starknet;
 ///
 /// ### Returns
 ///
-/// * `transaction_count` - The number of transactions in the specified block.
+/// * `transaction_count` - The number of transactions in the specified block, as recorded in its
+///   header, or the number of transactions received so far for the pending block.
 ///
 /// ### Errors
 ///
@@ -44,12 +45,15 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
+    let block = starknet.resolve_block_id(block_id)?;
 
-    let starknet_block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
+    if block.pending {
+        let pending_block = get_pending_block()
+            .ok_or(Error::Custom("Failed to retrieve pending block, node not yet synchronized".to_string()))?;
+        return Ok(pending_block.transactions().len() as u128);
+    }
+
+    let starknet_block = starknet.starknet_block(block)?;
 
     Ok(starknet_block.header().transaction_count)
 }
@@ -1,9 +1,9 @@
 use jsonrpsee::core::RpcResult;
 use mc_genesis_data_provider::GenesisProvider;
+use mp_convert::transaction::to_starknet_core_tx;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_transactions::compute_hash::ComputeTransactionHash;
-use mp_transactions::to_starknet_core_transaction::to_starknet_core_tx;
 use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
@@ -13,15 +13,13 @@ use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_core::types::{
-    BlockId, BlockTag, BlockWithReceipts, MaybePendingBlockWithReceipts, PendingBlockWithReceipts,
-    TransactionWithReceipt,
+    BlockId, BlockWithReceipts, MaybePendingBlockWithReceipts, PendingBlockWithReceipts, TransactionWithReceipt,
 };
 
 use super::get_transaction_receipt::get_transaction_receipt_finalized;
-use crate::errors::StarknetRpcApiError;
 use crate::utils::{
-    get_block_by_block_hash, l1_da_mode, l1_data_gas_price, l1_gas_price, new_root, parent_hash, sequencer_address,
-    starknet_version, status, timestamp,
+    l1_da_mode, l1_data_gas_price, l1_gas_price, new_root, parent_hash, sequencer_address, starknet_version, status,
+    timestamp,
 };
 use crate::Starknet;
 
@@ -39,17 +37,10 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
+    let block = starknet.resolve_block_id(block_id)?;
+    let (substrate_block_hash, is_pending) = (block.substrate_hash, block.pending);
 
-    let is_pending = matches!(block_id, BlockId::Tag(BlockTag::Pending));
-
-    let starknet_block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash).map_err(|e| {
-        log::error!("Failed to get block for block hash {substrate_block_hash}: '{e}'");
-        StarknetRpcApiError::InternalServerError
-    })?;
+    let starknet_block = starknet.starknet_block(block)?;
 
     let chain_id = starknet.chain_id()?;
 
@@ -9,9 +9,8 @@ use sc_transaction_pool::ChainApi;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use starknet_core::types::{BlockId, BlockTag, MaybePendingBlockWithTxHashes};
+use starknet_core::types::{BlockId, MaybePendingBlockWithTxHashes};
 
-use crate::errors::StarknetRpcApiError;
 use crate::{get_block_with_tx_hashes_finalized, get_block_with_tx_hashes_pending, Starknet};
 
 /// Get block information with transaction hashes given the block id.
@@ -41,13 +40,11 @@ where
     H: HasherT + Send + Sync + 'static,
 {
     let chain_id = starknet.chain_id()?;
-    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
+    let block = starknet.resolve_block_id(block_id)?;
 
-    match block_id {
-        BlockId::Tag(BlockTag::Pending) => get_block_with_tx_hashes_pending::<H>(chain_id),
-        _ => get_block_with_tx_hashes_finalized(starknet, chain_id, substrate_block_hash),
+    if block.pending {
+        get_block_with_tx_hashes_pending::<H>(chain_id)
+    } else {
+        get_block_with_tx_hashes_finalized(starknet, chain_id, block)
     }
 }
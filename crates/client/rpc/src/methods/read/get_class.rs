@@ -1,4 +1,5 @@
 use jsonrpsee::core::RpcResult;
+use mc_db::DeoxysBackend;
 use mc_genesis_data_provider::GenesisProvider;
 use mp_contract::class::ContractClassWrapper;
 use mp_felt::Felt252Wrapper;
@@ -27,7 +28,8 @@ use crate::Starknet;
 /// ### Returns
 ///
 /// Returns the contract class definition if found. In case of an error, returns a
-/// `StarknetRpcApiError` indicating either `BlockNotFound` or `ClassHashNotFound`.
+/// `StarknetRpcApiError` indicating either `BlockNotFound` or `ClassHashNotFound`. The latter is
+/// also returned if the class was declared after the requested block.
 pub fn get_class<A, BE, G, C, P, H>(
     starknet: &Starknet<A, BE, G, C, P, H>,
     block_id: BlockId,
@@ -43,13 +45,20 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
+    let block = starknet.resolve_block_id(block_id)?;
+    let (substrate_block_hash, block_number) = (block.substrate_hash, block.number);
 
     let class_hash = Felt252Wrapper(class_hash).into();
 
+    let declaration_block_number = DeoxysBackend::class().declaration_block_number(&class_hash).map_err(|e| {
+        log::error!("Failed to retrieve declaration block number for class hash '{class_hash}': {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+    if declaration_block_number.is_some_and(|declared_at| block_number < declared_at) {
+        log::error!("Class hash '{class_hash}' was not declared yet at block {block_number}");
+        return Err(StarknetRpcApiError::ClassHashNotFound.into());
+    }
+
     let contract_class = starknet
         .overrides
         .for_block_hash(starknet.client.as_ref(), substrate_block_hash)
@@ -42,10 +42,7 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
+    let substrate_block_hash = starknet.resolve_block_id(block_id)?.substrate_hash;
 
     let contract_address = Felt252Wrapper(contract_address).into();
     let class_hash = starknet
@@ -13,7 +13,7 @@ use sp_blockchain::HeaderBackend;
 use starknet_core::types::{BlockId, BlockTag, EmittedEvent, EventFilterWithPage, EventsPage};
 use starknet_ff::FieldElement;
 
-use crate::constants::{MAX_EVENTS_CHUNK_SIZE, MAX_EVENTS_KEYS};
+use crate::constants::MAX_EVENTS_KEYS;
 use crate::errors::StarknetRpcApiError;
 use crate::types::ContinuationToken;
 use crate::Starknet;
@@ -23,7 +23,9 @@ use crate::Starknet;
 /// This function retrieves all event objects that match the conditions specified in the
 /// provided event filter. The filter can include various criteria such as contract addresses,
 /// event types, and block ranges. The function supports pagination through the result page
-/// request schema.
+/// request schema: pages hold at most `max_events_chunk_size` events, and the continuation token
+/// holds the position of the next event in its block, so that pages can be resumed in the middle
+/// of a block, including after a restart of the node.
 ///
 /// ### Arguments
 ///
@@ -59,7 +61,7 @@ where
     if keys.len() > MAX_EVENTS_KEYS {
         return Err(StarknetRpcApiError::TooManyKeysInFilter.into());
     }
-    if chunk_size > MAX_EVENTS_CHUNK_SIZE as u64 {
+    if chunk_size > starknet.rpc_config.max_events_chunk_size as u64 {
         return Err(StarknetRpcApiError::PageSizeTooBig.into());
     }
 
@@ -72,48 +74,37 @@ where
             log::error!("Failed to parse continuation token: {:?}", e);
             StarknetRpcApiError::InvalidContinuationToken
         })?,
-        None => ContinuationToken { block_n: from_block, event_n: 0 },
+        None => ContinuationToken { block_n: from_block, tx_n: 0, event_n: 0 },
     };
 
     // Verify that the requested range is valid
     if from_block > to_block {
         return Ok(EventsPage { events: vec![], continuation_token: None });
     }
+    if continuation_token.block_n < from_block || continuation_token.block_n > to_block {
+        return Err(StarknetRpcApiError::InvalidContinuationToken.into());
+    }
 
-    let from_block = continuation_token.block_n;
     let mut filtered_events: Vec<EmittedEvent> = Vec::new();
 
-    for current_block in from_block..=to_block {
-        let block_filtered_events: Vec<EmittedEvent> = if current_block <= latest_block {
-            starknet.get_block_events(BlockId::Number(current_block))?
+    for current_block in continuation_token.block_n..=to_block {
+        let block_events = if current_block <= latest_block {
+            starknet.get_block_indexed_events(BlockId::Number(current_block))?
         } else {
-            starknet.get_block_events(BlockId::Tag(BlockTag::Pending))?
-        }
-        .into_iter()
-        .filter(|event| event_match_filter(event, from_address, &keys))
-        .collect();
-
-        if current_block == from_block && (block_filtered_events.len() as u64) < continuation_token.event_n {
-            return Err(StarknetRpcApiError::InvalidContinuationToken.into());
-        }
-
-        #[allow(clippy::iter_skip_zero)]
-        let block_filtered_reduced_events: Vec<EmittedEvent> = block_filtered_events
-            .into_iter()
-            .skip(if current_block == from_block { continuation_token.event_n as usize } else { 0 })
-            .take(chunk_size as usize - filtered_events.len())
-            .collect();
-
-        let num_events = block_filtered_reduced_events.len();
-
-        filtered_events.extend(block_filtered_reduced_events);
-
-        if filtered_events.len() == chunk_size as usize {
-            let event_n =
-                if current_block == from_block { continuation_token.event_n + chunk_size } else { num_events as u64 };
-            let token = Some(ContinuationToken { block_n: current_block, event_n }.to_string());
-
-            return Ok(EventsPage { events: filtered_events, continuation_token: token });
+            starknet.get_block_indexed_events(BlockId::Tag(BlockTag::Pending))?
+        };
+
+        for (tx_n, event_n, event) in block_events {
+            if continuation_token.is_after(current_block, tx_n, event_n)
+                || !event_match_filter(&event, from_address, &keys)
+            {
+                continue;
+            }
+            if filtered_events.len() == chunk_size as usize {
+                let token = ContinuationToken { block_n: current_block, tx_n, event_n };
+                return Ok(EventsPage { events: filtered_events, continuation_token: Some(token.to_string()) });
+            }
+            filtered_events.push(event);
         }
     }
     Ok(EventsPage { events: filtered_events, continuation_token: None })
@@ -144,25 +135,16 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let latest = starknet.substrate_block_number_from_starknet_block(BlockId::Tag(BlockTag::Latest)).map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
+    let latest = starknet.resolve_block_id(BlockId::Tag(BlockTag::Latest))?.number;
     let from = if from_block == Some(BlockId::Tag(BlockTag::Pending)) {
         latest + 1
     } else {
-        starknet.substrate_block_number_from_starknet_block(from_block.unwrap_or(BlockId::Number(0))).map_err(|e| {
-            log::error!("'{e}'");
-            StarknetRpcApiError::BlockNotFound
-        })?
+        starknet.resolve_block_id(from_block.unwrap_or(BlockId::Number(0)))?.number
     };
     let to = if to_block == Some(BlockId::Tag(BlockTag::Pending)) {
         latest + 1
     } else {
-        starknet.substrate_block_number_from_starknet_block(to_block.unwrap_or(BlockId::Number(0))).map_err(|e| {
-            log::error!("'{e}'");
-            StarknetRpcApiError::BlockNotFound
-        })?
+        starknet.resolve_block_id(to_block.unwrap_or(BlockId::Number(0)))?.number
     };
     Ok((from, to, latest))
 }
@@ -10,7 +10,7 @@ use sc_transaction_pool::ChainApi;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use starknet_core::types::{BlockId, FieldElement};
+use starknet_core::types::{BlockId, BlockTag, FieldElement, PendingStateUpdate};
 
 use crate::errors::StarknetRpcApiError;
 use crate::{Felt, Starknet};
@@ -24,6 +24,11 @@ use crate::{Felt, Starknet};
 /// * `contract_address` - The address of the contract whose nonce we're seeking. This is the unique
 ///   identifier of the contract in the Starknet network.
 ///
+/// On the pending block, the nonces updated by the pending state diff take precedence over the
+/// latest confirmed state, and contracts deployed in the pending block have a nonce of zero. When
+/// the node forwards transactions in nonce order, those it forwarded which are not included yet
+/// count as pending.
+///
 /// ### Returns
 ///
 /// Returns the contract's nonce at the requested state. The nonce is returned as a
@@ -46,10 +51,23 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
+    let forwarded_nonce = match block_id {
+        BlockId::Tag(BlockTag::Pending) => {
+            starknet.nonce_queue.as_ref().and_then(|queue| queue.next_nonce(contract_address))
+        }
+        _ => None,
+    };
+    let with_forwarded = |nonce: FieldElement| forwarded_nonce.map_or(nonce, |forwarded| forwarded.max(nonce));
+
+    if matches!(block_id, BlockId::Tag(BlockTag::Pending)) {
+        if let Some(nonce) =
+            mc_sync::l2::get_pending_state_update().and_then(|update| pending_nonce(&update, contract_address))
+        {
+            return Ok(Felt(with_forwarded(nonce)));
+        }
+    }
+
+    let substrate_block_hash = starknet.resolve_block_id(block_id)?.substrate_hash;
 
     let contract_address = Felt252Wrapper(contract_address).into();
 
@@ -62,5 +80,75 @@ where
             StarknetRpcApiError::ContractNotFound
         })?;
 
-    Ok(Felt(Felt252Wrapper::from(nonce).into()))
+    Ok(Felt(with_forwarded(Felt252Wrapper::from(nonce).into())))
+}
+
+/// Returns the nonce of `contract_address` set by the pending state diff, if any.
+pub(crate) fn pending_nonce(update: &PendingStateUpdate, contract_address: FieldElement) -> Option<FieldElement> {
+    let state_diff = &update.state_diff;
+    state_diff
+        .nonces
+        .iter()
+        .find(|update| update.contract_address == contract_address)
+        .map(|update| update.nonce)
+        .or_else(|| {
+            state_diff
+                .deployed_contracts
+                .iter()
+                .any(|deployed| deployed.address == contract_address)
+                .then_some(FieldElement::ZERO)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_core::types::{DeployedContractItem, NonceUpdate, StateDiff};
+
+    use super::*;
+
+    fn pending_update(deployed: &[u64], nonces: &[(u64, u64)]) -> PendingStateUpdate {
+        PendingStateUpdate {
+            old_root: FieldElement::ZERO,
+            state_diff: StateDiff {
+                storage_diffs: vec![],
+                deprecated_declared_classes: vec![],
+                declared_classes: vec![],
+                deployed_contracts: deployed
+                    .iter()
+                    .map(|&address| DeployedContractItem {
+                        address: FieldElement::from(address),
+                        class_hash: FieldElement::ONE,
+                    })
+                    .collect(),
+                replaced_classes: vec![],
+                nonces: nonces
+                    .iter()
+                    .map(|&(address, nonce)| NonceUpdate {
+                        contract_address: FieldElement::from(address),
+                        nonce: FieldElement::from(nonce),
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    #[test]
+    fn nonces_bumped_in_the_pending_block_are_served() {
+        let update = pending_update(&[], &[(1, 5), (2, 3)]);
+
+        assert_eq!(pending_nonce(&update, FieldElement::ONE), Some(FieldElement::from(5u64)));
+        assert_eq!(pending_nonce(&update, FieldElement::TWO), Some(FieldElement::THREE));
+        // not touched by the pending block, read from the latest confirmed state
+        assert_eq!(pending_nonce(&update, FieldElement::from(4u64)), None);
+    }
+
+    #[test]
+    fn contracts_deployed_in_the_pending_block_have_a_zero_nonce() {
+        let update = pending_update(&[1, 2], &[(2, 1)]);
+
+        assert_eq!(pending_nonce(&update, FieldElement::ONE), Some(FieldElement::ZERO));
+        // the nonce of a contract deployed and used in the pending block is the bumped one
+        assert_eq!(pending_nonce(&update, FieldElement::TWO), Some(FieldElement::ONE));
+        assert_eq!(pending_nonce(&update, FieldElement::THREE), None);
+    }
 }
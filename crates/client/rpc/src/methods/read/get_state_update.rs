@@ -6,7 +6,7 @@ use mc_sync::l2::get_pending_state_update;
 use mp_block::DeoxysBlock;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
-use mp_types::block::{DBlockT, DHashT};
+use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
 use sc_client_api::BlockBackend;
@@ -15,15 +15,15 @@ use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_api::block::BlockHash as APIBlockHash;
-use starknet_core::types::{BlockId, BlockTag, FieldElement, MaybePendingStateUpdate, StateDiff, StateUpdate};
+use starknet_core::types::{BlockId, FieldElement, MaybePendingStateUpdate, StateDiff, StateUpdate};
 
+use crate::block_id::ResolvedBlock;
 use crate::errors::StarknetRpcApiError;
-use crate::utils::get_block_by_block_hash;
 use crate::Starknet;
 
 fn get_state_update_finalized<A, BE, G, C, P, H>(
     server: &Starknet<A, BE, G, C, P, H>,
-    substrate_block_hash: DHashT,
+    block: ResolvedBlock,
 ) -> RpcResult<MaybePendingStateUpdate>
 where
     A: ChainApi<Block = DBlockT> + 'static,
@@ -35,9 +35,9 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let starknet_block = get_block_by_block_hash(server.client.as_ref(), substrate_block_hash)?;
+    let starknet_block = server.starknet_block(block)?;
 
-    let block_hash = starknet_block.header().hash::<H>().into();
+    let block_hash = block.starknet_hash.into();
 
     let new_root = Felt252Wrapper::from(starknet_block.header().global_state_root).into();
 
@@ -110,13 +110,7 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
+    let block = starknet.resolve_block_id(block_id)?;
 
-    match block_id {
-        BlockId::Tag(BlockTag::Pending) => get_state_update_pending(),
-        _ => get_state_update_finalized(starknet, substrate_block_hash),
-    }
+    if block.pending { get_state_update_pending() } else { get_state_update_finalized(starknet, block) }
 }
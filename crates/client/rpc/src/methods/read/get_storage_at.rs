@@ -1,6 +1,6 @@
 use jsonrpsee::core::RpcResult;
-use log::error;
 use mc_db::storage::StorageHandler;
+use mc_db::DeoxysBackend;
 use mc_genesis_data_provider::GenesisProvider;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
@@ -12,9 +12,11 @@ use sc_transaction_pool::ChainApi;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
+use starknet_api::hash::StarkFelt;
 use starknet_core::types::{BlockId, FieldElement};
 
 use crate::errors::StarknetRpcApiError;
+use crate::trie_reads::read_tries;
 use crate::{Felt, Starknet};
 
 /// Get the value of the storage at the given address and key.
@@ -46,7 +48,7 @@ use crate::{Felt, Starknet};
 ///   given `contract_address` in the specified block.
 /// * `STORAGE_KEY_NOT_FOUND` - If the specified storage key does not exist within the given
 ///   contract.
-pub fn get_storage_at<A, BE, G, C, P, H>(
+pub async fn get_storage_at<A, BE, G, C, P, H>(
     starknet: &Starknet<A, BE, G, C, P, H>,
     contract_address: FieldElement,
     key: FieldElement,
@@ -62,24 +64,35 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let block_number = starknet.substrate_block_number_from_starknet_block(block_id).map_err(|e| {
-        error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
+    let block_number = starknet.resolve_block_id(block_id)?.number;
 
     let contract_address = Felt252Wrapper(contract_address).into();
     let key = Felt252Wrapper(key).into();
 
     log::info!("block number: {block_number}");
 
-    let value = StorageHandler::contract_storage_mut(BlockId::Number(block_number))
-        .map_err(|_| StarknetRpcApiError::ContractNotFound)?
-        .get(&contract_address, &key)
-        .unwrap_or(None)
-        .ok_or_else(|| {
-            log::error!("Failed to retrieve storage at '{contract_address:?}' and '{key:?}'");
-            StarknetRpcApiError::ContractNotFound
-        })?;
+    // the flat storage is preferred, unless the block was not backfilled yet
+    let flat_storage = DeoxysBackend::contract_storage();
+    let value: Option<FieldElement> = if flat_storage.is_available(block_number).unwrap_or_default() {
+        flat_storage
+            .get_at(&contract_address, &key, block_number)
+            .map_err(|e| {
+                log::error!("Failed to read flat storage at '{contract_address:?}' and '{key:?}': {e}");
+                StarknetRpcApiError::InternalServerError
+            })?
+            // cleared slots are removed from the tries, and are not found there either
+            .filter(|value| *value != StarkFelt::ZERO)
+            .map(|value| Felt252Wrapper::from(value).into())
+    } else {
+        read_tries(move || StorageHandler::contract_storage_at(block_number)?.get(&contract_address, &key))
+            .await?
+            .map(|value| Felt252Wrapper::from(value).into())
+    };
+
+    let value = value.ok_or_else(|| {
+        log::error!("Failed to retrieve storage at '{contract_address:?}' and '{key:?}'");
+        StarknetRpcApiError::ContractNotFound
+    })?;
 
-    Ok(Felt(Felt252Wrapper::from(value).into()))
+    Ok(Felt(value))
 }
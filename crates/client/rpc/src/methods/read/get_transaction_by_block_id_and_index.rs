@@ -1,10 +1,11 @@
 use jsonrpsee::core::RpcResult;
 use jsonrpsee::types::error::CallError;
+use mc_db::DeoxysBackend;
 use mc_genesis_data_provider::GenesisProvider;
+use mp_convert::transaction::to_starknet_core_tx;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_transactions::compute_hash::ComputeTransactionHash;
-use mp_transactions::to_starknet_core_transaction::to_starknet_core_tx;
 use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
@@ -16,7 +17,6 @@ use sp_blockchain::HeaderBackend;
 use starknet_core::types::{BlockId, FieldElement, Transaction};
 
 use crate::errors::StarknetRpcApiError;
-use crate::utils::get_block_by_block_hash;
 use crate::Starknet;
 
 /// Get the details of a transaction by a given block id and index.
@@ -54,20 +54,21 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
+    let block = starknet.resolve_block_id(block_id)?;
 
-    let starknet_block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
+    let starknet_block = starknet.starknet_block(block)?;
 
     let transaction = starknet_block.transactions().get(index as usize).ok_or(StarknetRpcApiError::InvalidTxnIndex)?;
     let chain_id = starknet.chain_id()?;
 
-    let opt_cached_transaction_hashes =
-        starknet.get_cached_transaction_hashes(starknet_block.header().hash::<H>().into());
+    let indexed_transaction_hash = DeoxysBackend::transaction().hash_at(block.number, index).map_err(|e| {
+        log::error!("Failed to get transaction hash from transaction_db: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
 
-    let transaction_hash = if let Some(cached_tx_hashes) = opt_cached_transaction_hashes {
+    let transaction_hash = if let Some(transaction_hash) = indexed_transaction_hash {
+        Felt252Wrapper::from(transaction_hash.0).into()
+    } else if let Some(cached_tx_hashes) = starknet.get_cached_transaction_hashes(block.starknet_hash.into()) {
         cached_tx_hashes.get(index as usize).map(|&fe| FieldElement::from(Felt252Wrapper::from(fe))).ok_or(
             CallError::Failed(anyhow::anyhow!(
                 "Number of cached tx hashes does not match the number of transactions in block with id {:?}",
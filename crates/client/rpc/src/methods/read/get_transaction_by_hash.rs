@@ -1,10 +1,10 @@
 use jsonrpsee::core::RpcResult;
 use mc_db::DeoxysBackend;
 use mc_genesis_data_provider::GenesisProvider;
+use mp_convert::transaction::to_starknet_core_tx;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_transactions::compute_hash::ComputeTransactionHash;
-use mp_transactions::to_starknet_core_transaction::to_starknet_core_tx;
 use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
@@ -13,7 +13,8 @@ use sc_transaction_pool::ChainApi;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use starknet_core::types::{FieldElement, Transaction};
+use starknet_api::transaction::TransactionHash;
+use starknet_core::types::{BlockId, FieldElement, Transaction};
 
 use crate::errors::StarknetRpcApiError;
 use crate::utils::get_block_by_block_hash;
@@ -60,6 +61,23 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
+    let location = DeoxysBackend::transaction()
+        .checked_location(&TransactionHash(Felt252Wrapper::from(transaction_hash).into()))
+        .map_err(|e| {
+            log::error!("Failed to get transaction location from transaction_db: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?;
+
+    if let Some(location) = location {
+        let block = starknet.resolve_block_id(BlockId::Number(location.block_number))?;
+        let starknet_block = starknet.starknet_block(block)?;
+        let transaction =
+            starknet_block.transactions().get(location.index as usize).ok_or(StarknetRpcApiError::TxnHashNotFound)?;
+
+        return Ok(to_starknet_core_tx(transaction.clone(), transaction_hash));
+    }
+
+    // transactions which are not indexed yet, until the index is backfilled
     let substrate_block_hash_from_db = DeoxysBackend::mapping()
         .block_hash_from_transaction_hash(Felt252Wrapper::from(transaction_hash).into())
         .map_err(|e| {
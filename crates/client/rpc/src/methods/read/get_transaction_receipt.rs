@@ -1,14 +1,13 @@
 use blockifier::context::BlockContext;
-use blockifier::execution::contract_class::{ContractClass as ContractClassBf, ContractClassV1 as ContractClassV1Bf};
 use blockifier::transaction::objects::TransactionExecutionInfo;
 use blockifier::transaction::transaction_execution as btx;
 use jsonrpsee::core::RpcResult;
 use mc_db::DeoxysBackend;
 use mc_genesis_data_provider::GenesisProvider;
+use mp_block::receipt::{EventWrapper, TransactionReceiptWrapper};
 use mp_block::DeoxysBlock;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
-use mp_transactions::compute_hash::ComputeTransactionHash;
 use mp_types::block::{DBlockT, DHashT};
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
@@ -17,23 +16,22 @@ use sc_transaction_pool::ChainApi;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use starknet_api::core::{ClassHash, ContractAddress};
-use starknet_api::transaction::{
-    DeclareTransaction, DeployAccountTransaction, InvokeTransaction, L1HandlerTransaction, Transaction, TransactionHash,
-};
+use starknet_api::transaction::{Transaction, TransactionHash};
 use starknet_core::types::{
     BlockId, ComputationResources, DataAvailabilityResources, DataResources, DeclareTransactionReceipt,
-    DeployAccountTransactionReceipt, ExecutionResources, ExecutionResult, FieldElement, Hash256,
-    InvokeTransactionReceipt, L1HandlerTransactionReceipt, TransactionFinalityStatus, TransactionReceipt,
-    TransactionReceiptWithBlockInfo,
+    DeployAccountTransactionReceipt, DeployTransactionReceipt, Event, ExecutionResources, ExecutionResult, FeePayment,
+    FieldElement, Hash256, InvokeTransactionReceipt, L1HandlerTransactionReceipt, MsgToL1, PriceUnit,
+    TransactionFinalityStatus, TransactionReceipt, TransactionReceiptWithBlockInfo,
 };
 
 use crate::errors::StarknetRpcApiError;
+use crate::execution_memory::with_memory_limit;
+use crate::methods::trace::utils::convert_transaction;
 use crate::utils::{
     blockifier_call_info_to_starknet_resources, extract_events_from_call_info, extract_messages_from_call_info,
-    get_block_by_block_hash, tx_hash_compute, tx_hash_retrieve,
+    fee_unit, get_block_by_block_hash, tx_hash_compute, tx_hash_retrieve,
 };
-use crate::{Felt, Starknet};
+use crate::{metrics, Felt, Starknet};
 
 pub fn get_transaction_receipt_finalized<A, BE, G, C, P, H>(
     client: &Starknet<A, BE, G, C, P, H>,
@@ -56,92 +54,33 @@ where
     let block_number = block_header.block_number;
     let block_hash: Felt252Wrapper = block_header.hash::<H>();
 
-    // computes the previous SUBSTRATE block hash
-    let previous_block_hash = previous_block_hash(client, block_number)?;
-
-    let block_txs_hashes = if let Some(tx_hashes) = client.get_cached_transaction_hashes(block_hash.into()) {
-        tx_hash_retrieve(tx_hashes)
-    } else {
-        // WHY IN SAINT FUCK IS mc
-        tx_hash_compute::<H>(&block, chain_id)
-    };
-
-    let (tx_index, _) = block_txs_hashes.into_iter().enumerate().find(|(_, hash)| hash == &transaction_hash).unwrap();
+    let tx_index = transaction_index(client, chain_id, &block, block_hash, transaction_hash)?;
 
     let transaction = block.transactions().get(tx_index).ok_or_else(|| {
         log::error!("Failed to retrieve transaction at index {tx_index} from block with hash {block_hash:?}");
         StarknetRpcApiError::InternalServerError
     })?;
 
-    // deploy transaction was not supported by blockifier
-    if let Transaction::Deploy(_) = transaction {
-        log::error!("re executing a deploy transaction is not supported yet");
-        return Err(StarknetRpcApiError::UnimplementedMethod.into());
-    }
-
-    let transactions = transactions(client, substrate_block_hash, chain_id, &block, block_number, tx_index)?;
+    let stored_receipt = stored_receipt(transaction_hash)?;
 
-    let fee_token_address = client.client.runtime_api().fee_token_addresses(substrate_block_hash).map_err(|e| {
-        log::error!("Failed to retrieve fee token address: {e}");
-        StarknetRpcApiError::InternalServerError
-    })?;
-    // TODO: convert the real chain_id in String
-    let block_context =
-        block_header.into_block_context(fee_token_address, starknet_api::core::ChainId("SN_MAIN".to_string()));
-    let execution_infos = execution_infos(client, previous_block_hash, transactions, &block_context)?;
+    // receipts synced from the feeder gateway are complete, only re-execute the transaction if
+    // there is none
+    let ReceiptParts { actual_fee, execution_result, execution_resources, events, messages_sent } = match stored_receipt
+    {
+        Some(receipt) => receipt_parts_from_storage(receipt, fee_unit(transaction)),
+        None => receipt_parts_from_execution(client, chain_id, substrate_block_hash, &block, tx_index)?,
+    };
+    let event_count = events.len();
 
     // TODO(#1291): compute message hash correctly to L1HandlerTransactionReceipt
     let message_hash: Hash256 = Hash256::from_felt(&FieldElement::default());
 
-    // TODO: implement fee in Fri when Blockifier will support it
-    let actual_fee = starknet_core::types::FeePayment {
-        amount: execution_infos.actual_fee.0.into(),
-        unit: starknet_core::types::PriceUnit::Wei,
-    };
-
     let finality_status = if block_number <= mc_sync::l1::ETHEREUM_STATE_UPDATE.read().unwrap().block_number {
         TransactionFinalityStatus::AcceptedOnL1
     } else {
         TransactionFinalityStatus::AcceptedOnL2
     };
 
-    let execution_result = match execution_infos.revert_error.clone() {
-        Some(err) => ExecutionResult::Reverted { reason: err },
-        None => ExecutionResult::Succeeded,
-    };
-
-    // no execution resources for declare transactions
-    let execution_resources = match execution_infos.execute_call_info {
-        Some(ref call_info) => blockifier_call_info_to_starknet_resources(call_info),
-        None => ExecutionResources {
-            computation_resources: ComputationResources {
-                steps: 0,
-                memory_holes: None,
-                range_check_builtin_applications: None,
-                pedersen_builtin_applications: None,
-                poseidon_builtin_applications: None,
-                ec_op_builtin_applications: None,
-                ecdsa_builtin_applications: None,
-                bitwise_builtin_applications: None,
-                keccak_builtin_applications: None,
-                segment_arena_builtin: None,
-            },
-            data_resources: DataResources {
-                data_availability: DataAvailabilityResources { l1_gas: 0, l1_data_gas: 0 },
-            },
-        },
-    };
-
-    let events = match execution_infos.execute_call_info {
-        Some(ref call_info) => extract_events_from_call_info(call_info),
-        None => vec![],
-    };
-
-    let messages_sent = match execution_infos.execute_call_info {
-        Some(ref call_info) => extract_messages_from_call_info(call_info),
-        None => vec![],
-    };
-
     let receipt = match transaction {
         Transaction::Declare(_) => TransactionReceipt::Declare(DeclareTransactionReceipt {
             transaction_hash,
@@ -182,15 +121,36 @@ where
             execution_resources,
             execution_result,
         }),
-        _ => unreachable!("Deploy transactions are not supported"),
+        Transaction::Deploy(_) => TransactionReceipt::Deploy(DeployTransactionReceipt {
+            transaction_hash,
+            actual_fee,
+            finality_status,
+            messages_sent,
+            events,
+            execution_resources,
+            execution_result,
+            // TODO: retrieve contract address
+            contract_address: FieldElement::default(),
+        }),
     };
 
     let block_info = starknet_core::types::ReceiptBlock::Block { block_hash: block_hash.0, block_number };
 
-    Ok(TransactionReceiptWithBlockInfo { receipt, block: block_info })
+    let receipt = TransactionReceiptWithBlockInfo { receipt, block: block_info };
+    metrics::observe_receipt(event_count, &receipt);
+
+    Ok(receipt)
 }
 
-fn previous_block_hash<A, BE, G, C, P, H>(client: &Starknet<A, BE, G, C, P, H>, block_number: u64) -> RpcResult<DHashT>
+/// The events emitted by a transaction, as listed in its receipt.
+///
+/// ### Errors
+///
+/// * `TXN_HASH_NOT_FOUND` - If the transaction is not in a finalized block.
+pub(crate) fn transaction_events<A, BE, G, C, P, H>(
+    client: &Starknet<A, BE, G, C, P, H>,
+    transaction_hash: FieldElement,
+) -> RpcResult<Vec<Event>>
 where
     A: ChainApi<Block = DBlockT> + 'static,
     P: TransactionPool<Block = DBlockT> + 'static,
@@ -201,23 +161,81 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let previous_block_hash =
-        client.substrate_block_hash_from_starknet_block(BlockId::Number(block_number - 1)).map_err(|e| {
-            log::error!("Failed to retrieve previous substrate block hash: {e}");
+    if let Some(receipt) = stored_receipt(transaction_hash)? {
+        return Ok(events_from_storage(receipt.events));
+    }
+
+    let substrate_block_hash = DeoxysBackend::mapping()
+        .block_hash_from_transaction_hash(Felt252Wrapper::from(transaction_hash).into())
+        .map_err(|e| {
+            log::error!("Failed to retrieve substrate block hash: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .ok_or(StarknetRpcApiError::TxnHashNotFound)?;
+
+    let chain_id = client.chain_id()?;
+    let block = get_block_by_block_hash(client.client.as_ref(), substrate_block_hash)?;
+    if let Some(events) = indexed_events(&block, transaction_hash)? {
+        return Ok(events);
+    }
+
+    let block_hash: Felt252Wrapper = block.header().hash::<H>();
+    let tx_index = transaction_index(client, chain_id, &block, block_hash, transaction_hash)?;
+
+    Ok(receipt_parts_from_execution(client, chain_id, substrate_block_hash, &block, tx_index)?.events)
+}
+
+/// The events emitted by `transaction_hash` in `block`, read from the block events at the position
+/// recorded in the transaction events index, if the transaction was indexed.
+fn indexed_events(block: &DeoxysBlock, transaction_hash: FieldElement) -> RpcResult<Option<Vec<Event>>> {
+    let slice = DeoxysBackend::transaction()
+        .event_slice(&TransactionHash(Felt252Wrapper::from(transaction_hash).into()))
+        .map_err(|e| {
+            log::error!("Failed to retrieve the events position of transaction {transaction_hash:#x}: {e}");
             StarknetRpcApiError::InternalServerError
         })?;
+    let Some(slice) = slice.filter(|slice| slice.block_number == block.header().block_number) else {
+        return Ok(None);
+    };
+    if slice.count == 0 {
+        return Ok(Some(Vec::new()));
+    }
 
-    Ok(previous_block_hash)
+    let Some(ordered_events) = block.events().get(slice.position as usize) else {
+        log::error!("Transaction {transaction_hash:#x} has no events at position {} of its block", slice.position);
+        return Err(StarknetRpcApiError::InternalServerError.into());
+    };
+    Ok(Some(
+        ordered_events
+            .events()
+            .iter()
+            .map(|event| Event {
+                from_address: Felt252Wrapper::from(event.from_address).0,
+                keys: event.content.keys.iter().map(|felt| Felt252Wrapper::from(*felt).0).collect(),
+                data: event.content.data.0.iter().map(|felt| Felt252Wrapper::from(*felt).0).collect(),
+            })
+            .collect(),
+    ))
 }
 
-fn transactions<A, BE, G, C, P, H>(
+/// The receipt of `transaction_hash` as stored when its block was imported, if it was.
+pub(crate) fn stored_receipt(transaction_hash: FieldElement) -> RpcResult<Option<TransactionReceiptWrapper>> {
+    DeoxysBackend::receipt().get(&TransactionHash(Felt252Wrapper::from(transaction_hash).into())).map_err(|e| {
+        log::error!("Failed to retrieve receipt for transaction with hash {transaction_hash:#x}: {e}");
+        StarknetRpcApiError::InternalServerError.into()
+    })
+}
+
+/// The index of the transaction `transaction_hash` in `block`.
+///
+/// Indexed transactions are found without hashing the transactions of the block.
+pub(crate) fn transaction_index<A, BE, G, C, P, H>(
     client: &Starknet<A, BE, G, C, P, H>,
-    substrate_block_hash: DHashT,
     chain_id: Felt,
     block: &DeoxysBlock,
-    block_number: u64,
-    tx_index: usize,
-) -> RpcResult<Vec<btx::Transaction>>
+    block_hash: Felt252Wrapper,
+    transaction_hash: FieldElement,
+) -> RpcResult<usize>
 where
     A: ChainApi<Block = DBlockT> + 'static,
     P: TransactionPool<Block = DBlockT> + 'static,
@@ -228,60 +246,102 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let transactions = block
-            .transactions()
-            .iter()
-            .take(tx_index + 1)
-            .filter(|tx| !matches!(tx, Transaction::Deploy(_))) // deploy transaction was not supported by blockifier
-            .map(|tx| match tx {
-                Transaction::Invoke(invoke_tx) => {
-					tx_invoke_transaction(invoke_tx.clone(), invoke_tx.compute_hash::<H>(Felt252Wrapper::from(chain_id.0), false, Some(block_number)))
-                }
-                // TODO: add real contract address param here
-                Transaction::DeployAccount(deploy_account_tx) => {
-					tx_deploy_account(deploy_account_tx.clone(), deploy_account_tx.compute_hash::<H>(Felt252Wrapper::from(chain_id.0), false, Some(block_number)), ContractAddress::default())
-                }
-                Transaction::Declare(declare_tx) => {
-					tx_declare(client, substrate_block_hash, declare_tx.clone())
-                }
-                Transaction::L1Handler(l1_handler) => {
-					tx_l1_handler::<H>(chain_id, block_number, l1_handler.clone())
-                }
-                _ => unreachable!("Deploy transactions are not supported"),
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+    let location = DeoxysBackend::transaction()
+        .checked_location(&TransactionHash(Felt252Wrapper::from(transaction_hash).into()))
+        .map_err(|e| {
+            log::error!("Failed to retrieve the location of transaction {transaction_hash:#x}: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?;
+    if let Some(location) = location.filter(|location| location.block_number == block.header().block_number) {
+        return Ok(location.index as usize);
+    }
 
-    Ok(transactions)
+    let block_txs_hashes = if let Some(tx_hashes) = client.get_cached_transaction_hashes(block_hash.into()) {
+        tx_hash_retrieve(tx_hashes)
+    } else {
+        // WHY IN SAINT FUCK IS mc
+        tx_hash_compute::<H>(block, chain_id)
+    };
+
+    block_txs_hashes.into_iter().position(|hash| hash == transaction_hash).ok_or_else(|| {
+        log::error!("Transaction {transaction_hash:#x} is not in block {block_hash:?}");
+        StarknetRpcApiError::TxnHashNotFound.into()
+    })
 }
 
-fn tx_invoke_transaction(tx: InvokeTransaction, hash: TransactionHash) -> RpcResult<btx::Transaction> {
-    Ok(btx::Transaction::AccountTransaction(blockifier::transaction::account_transaction::AccountTransaction::Invoke(
-        blockifier::transaction::transactions::InvokeTransaction { tx, tx_hash: hash, only_query: false },
-    )))
+/// The parts of a receipt which are common to all transaction types.
+struct ReceiptParts {
+    actual_fee: FeePayment,
+    execution_result: ExecutionResult,
+    execution_resources: ExecutionResources,
+    events: Vec<Event>,
+    messages_sent: Vec<MsgToL1>,
 }
 
-fn tx_deploy_account(
-    tx: DeployAccountTransaction,
-    hash: TransactionHash,
-    contract_address: ContractAddress,
-) -> RpcResult<btx::Transaction> {
-    Ok(btx::Transaction::AccountTransaction(
-        blockifier::transaction::account_transaction::AccountTransaction::DeployAccount(
-            blockifier::transaction::transactions::DeployAccountTransaction {
-                tx,
-                tx_hash: hash,
-                only_query: false,
-                contract_address,
+fn receipt_parts_from_storage(receipt: TransactionReceiptWrapper, unit: PriceUnit) -> ReceiptParts {
+    let resources = receipt.execution_resources;
+
+    let actual_fee = FeePayment { amount: receipt.actual_fee.into(), unit };
+
+    let execution_result = match receipt.revert_error {
+        Some(reason) => ExecutionResult::Reverted { reason },
+        None => ExecutionResult::Succeeded,
+    };
+
+    let execution_resources = ExecutionResources {
+        computation_resources: ComputationResources {
+            steps: resources.steps,
+            memory_holes: resources.memory_holes,
+            range_check_builtin_applications: resources.range_check_builtin_applications,
+            pedersen_builtin_applications: resources.pedersen_builtin_applications,
+            poseidon_builtin_applications: resources.poseidon_builtin_applications,
+            ec_op_builtin_applications: resources.ec_op_builtin_applications,
+            ecdsa_builtin_applications: resources.ecdsa_builtin_applications,
+            bitwise_builtin_applications: resources.bitwise_builtin_applications,
+            keccak_builtin_applications: resources.keccak_builtin_applications,
+            segment_arena_builtin: resources.segment_arena_builtin,
+        },
+        data_resources: DataResources {
+            data_availability: DataAvailabilityResources {
+                l1_gas: resources.l1_gas,
+                l1_data_gas: resources.l1_data_gas,
             },
-        ),
-    ))
+        },
+    };
+
+    let events = events_from_storage(receipt.events);
+
+    let messages_sent = receipt
+        .messages_sent
+        .into_iter()
+        .map(|message| MsgToL1 {
+            from_address: message.from_address.into(),
+            to_address: message.to_address.into(),
+            payload: message.payload.into_iter().map(FieldElement::from).collect(),
+        })
+        .collect();
+
+    ReceiptParts { actual_fee, execution_result, execution_resources, events, messages_sent }
+}
+
+fn events_from_storage(events: Vec<EventWrapper>) -> Vec<Event> {
+    events
+        .into_iter()
+        .map(|event| Event {
+            from_address: event.from_address.into(),
+            keys: event.keys.into_iter().map(FieldElement::from).collect(),
+            data: event.data.into_iter().map(FieldElement::from).collect(),
+        })
+        .collect()
 }
 
-fn tx_declare<A, BE, G, C, P, H>(
+fn receipt_parts_from_execution<A, BE, G, C, P, H>(
     client: &Starknet<A, BE, G, C, P, H>,
+    chain_id: Felt,
     substrate_block_hash: DHashT,
-    declare_tx: DeclareTransaction,
-) -> RpcResult<btx::Transaction>
+    block: &DeoxysBlock,
+    tx_index: usize,
+) -> RpcResult<ReceiptParts>
 where
     A: ChainApi<Block = DBlockT> + 'static,
     P: TransactionPool<Block = DBlockT> + 'static,
@@ -292,23 +352,74 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let class_hash = ClassHash(Felt252Wrapper::from(*declare_tx.class_hash()).into());
+    let block_header = block.header().clone();
+    let block_number = block_header.block_number;
 
-    match declare_tx {
-        DeclareTransaction::V0(_) | DeclareTransaction::V1(_) => {
-            tx_declare_v0v1(client, substrate_block_hash, declare_tx, class_hash)
+    let unit = match block.transactions().get(tx_index) {
+        // deploy transaction was not supported by blockifier
+        Some(Transaction::Deploy(_)) => {
+            log::error!("re executing a deploy transaction is not supported yet");
+            return Err(StarknetRpcApiError::UnimplementedMethod.into());
         }
-        DeclareTransaction::V2(_) => tx_declare_v2(declare_tx, class_hash),
-        DeclareTransaction::V3(_) => todo!("implement DeclareTransaction::V3"),
-    }
+        Some(transaction) => fee_unit(transaction),
+        None => PriceUnit::Wei,
+    };
+
+    // computes the previous SUBSTRATE block hash
+    let previous_block_hash = previous_block_hash(client, block_number)?;
+
+    let transactions = transactions(client, substrate_block_hash, chain_id, block, block_number, tx_index)?;
+
+    let fee_token_address = client.client.runtime_api().fee_token_addresses(substrate_block_hash).map_err(|e| {
+        log::error!("Failed to retrieve fee token address: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+    let block_context = block_header.into_block_context(fee_token_address, client.execution_chain_id()?);
+    let execution_infos = execution_infos(client, previous_block_hash, transactions, &block_context)?;
+
+    let actual_fee = FeePayment { amount: execution_infos.actual_fee.0.into(), unit };
+
+    let execution_result = match execution_infos.revert_error.clone() {
+        Some(err) => ExecutionResult::Reverted { reason: err },
+        None => ExecutionResult::Succeeded,
+    };
+
+    // no execution resources for declare transactions
+    let execution_resources = match execution_infos.execute_call_info {
+        Some(ref call_info) => blockifier_call_info_to_starknet_resources(call_info),
+        None => ExecutionResources {
+            computation_resources: ComputationResources {
+                steps: 0,
+                memory_holes: None,
+                range_check_builtin_applications: None,
+                pedersen_builtin_applications: None,
+                poseidon_builtin_applications: None,
+                ec_op_builtin_applications: None,
+                ecdsa_builtin_applications: None,
+                bitwise_builtin_applications: None,
+                keccak_builtin_applications: None,
+                segment_arena_builtin: None,
+            },
+            data_resources: DataResources {
+                data_availability: DataAvailabilityResources { l1_gas: 0, l1_data_gas: 0 },
+            },
+        },
+    };
+
+    let events = match execution_infos.execute_call_info {
+        Some(ref call_info) => extract_events_from_call_info(call_info),
+        None => vec![],
+    };
+
+    let messages_sent = match execution_infos.execute_call_info {
+        Some(ref call_info) => extract_messages_from_call_info(call_info),
+        None => vec![],
+    };
+
+    Ok(ReceiptParts { actual_fee, execution_result, execution_resources, events, messages_sent })
 }
 
-fn tx_declare_v0v1<A, BE, G, C, P, H>(
-    client: &Starknet<A, BE, G, C, P, H>,
-    substrate_block_hash: DHashT,
-    declare_tx: DeclareTransaction,
-    class_hash: ClassHash,
-) -> RpcResult<btx::Transaction>
+fn previous_block_hash<A, BE, G, C, P, H>(client: &Starknet<A, BE, G, C, P, H>, block_number: u64) -> RpcResult<DHashT>
 where
     A: ChainApi<Block = DBlockT> + 'static,
     P: TransactionPool<Block = DBlockT> + 'static,
@@ -319,92 +430,53 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let _contract_class = client
-        .overrides
-        .for_block_hash(client.client.as_ref(), substrate_block_hash)
-        .contract_class_by_class_hash(substrate_block_hash, class_hash)
-        .ok_or_else(|| {
-            log::error!("Failed to retrieve contract class from hash '{class_hash}'");
+    let previous_block_hash = client
+        .resolve_block_id(BlockId::Number(block_number - 1))
+        .map_err(|e| {
+            log::error!("Failed to retrieve previous substrate block hash: {e}");
             StarknetRpcApiError::InternalServerError
-        })?;
-
-    // let class_info = ClassInfo::new(
-    //     &contract_class,
-    //     contract_class.
-    //     contract_class
-    // )
-    // .unwrap();
-
-    // Ok(btx::Transaction::AccountTransaction(
-    //     blockifier::transaction::account_transaction::AccountTransaction::Declare(
-    //     blockifier::transaction::transactions::DeclareTransaction::new(
-    //         declare_tx,
-    //         declare_tx.compute_hash::<H>(Felt252Wrapper::from(class_hash.0).into(), false, None),
-    //         class_info,
-    //     ).unwrap()
-    // )))
-    // TODO: Correct this that was used as a place holder to compile
-    match declare_tx {
-        DeclareTransaction::V0(_) | DeclareTransaction::V1(_) => {
-            tx_declare_v0v1(client, substrate_block_hash, declare_tx, class_hash)
-        }
-        DeclareTransaction::V2(_) => tx_declare_v2(declare_tx, class_hash),
-        DeclareTransaction::V3(_) => todo!("implement DeclareTransaction::V3"),
-    }
-}
-
-fn tx_declare_v2(declare_tx: DeclareTransaction, _class_hash: ClassHash) -> RpcResult<btx::Transaction> {
-    // Welcome to type hell! This 3-part conversion will take you through the extenses
-    // of a codebase so thick it might as well be pasta -yum!
-    // Also should no be a problem as a declare transaction *should* not be able to
-    // reference a contract class created on the same block (this kind of issue
-    // might otherwise arise for `pending` blocks)
-    // TODO: change this contract class to the correct one
-    let contract_class = starknet_api::state::ContractClass::default();
-
-    let contract_class = mp_transactions::utils::sierra_to_casm_contract_class(contract_class).map_err(|e| {
-        log::error!("Failed to convert the SierraContractClass to CasmContractClass: {e}");
-        StarknetRpcApiError::InternalServerError
-    })?;
+        })?
+        .substrate_hash;
 
-    let _contract_class = ContractClassBf::V1(ContractClassV1Bf::try_from(contract_class).map_err(|e| {
-        log::error!("Failed to convert the compiler CasmContractClass to blockifier CasmContractClass: {e}");
-        StarknetRpcApiError::InternalServerError
-    })?);
-
-    // Ok(btx::Transaction::AccountTransaction(
-    //     blockifier::transaction::account_transaction::AccountTransaction::Declare(
-    //     blockifier::transaction::transactions::DeclareTransaction::new(
-    //         declare_tx,
-    //         declare_tx.compute_hash::<PedersenHasher>(Felt252Wrapper::from(class_hash.0).into(),
-    // false, None),         contract_class,
-    //     ).unwrap()
-    // )))
-    // TODO: Correct this that was used as a place holder to compile
-    match declare_tx {
-        DeclareTransaction::V0(_) => todo!(),
-        DeclareTransaction::V1(_) => todo!(),
-        DeclareTransaction::V2(_) => tx_declare_v2(declare_tx, _class_hash),
-        DeclareTransaction::V3(_) => todo!("implement DeclareTransaction::V3"),
-    }
+    Ok(previous_block_hash)
 }
 
-fn tx_l1_handler<H>(chain_id: Felt, block_number: u64, l1_handler: L1HandlerTransaction) -> RpcResult<btx::Transaction>
+fn transactions<A, BE, G, C, P, H>(
+    client: &Starknet<A, BE, G, C, P, H>,
+    substrate_block_hash: DHashT,
+    chain_id: Felt,
+    block: &DeoxysBlock,
+    block_number: u64,
+    tx_index: usize,
+) -> RpcResult<Vec<btx::Transaction>>
 where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let chain_id = chain_id.0.into();
-    let tx_hash = l1_handler.compute_hash::<H>(chain_id, false, Some(block_number));
-    let paid_fee = DeoxysBackend::l1_handler_paid_fee().get_fee_paid_for_l1_handler_tx(tx_hash.0).map_err(|e| {
-        log::error!("Failed to retrieve fee paid on l1 for tx with hash `{tx_hash:?}`: {e}");
-        StarknetRpcApiError::InternalServerError
-    })?;
+    let transactions = block
+        .transactions()
+        .iter()
+        .take(tx_index + 1)
+        .filter(|tx| !matches!(tx, Transaction::Deploy(_))) // deploy transaction was not supported by blockifier
+        .map(|tx| {
+            convert_transaction::<_, _, H>(
+                tx,
+                client.client.as_ref(),
+                &client.overrides,
+                substrate_block_hash,
+                Felt252Wrapper::from(chain_id.0),
+                block_number,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-    Ok(btx::Transaction::L1HandlerTransaction(blockifier::transaction::transactions::L1HandlerTransaction {
-        tx: l1_handler,
-        tx_hash,
-        paid_fee_on_l1: paid_fee,
-    }))
+    Ok(transactions)
 }
 
 fn execution_infos<A, BE, G, C, P, H>(
@@ -428,23 +500,22 @@ where
         None => (transactions, vec![]),
     };
 
-    let execution_infos = client
-        .client
-        .runtime_api()
-        .re_execute_transactions(previous_block_hash, prev, last, block_context)
-        .map_err(|e| {
-            log::error!("Failed to execute runtime API call: {e}");
-            StarknetRpcApiError::InternalServerError
-        })?
-        .map_err(|e| {
-            log::error!("Failed to reexecute the transactions: {e:?}");
-            StarknetRpcApiError::InternalServerError
-        })?
-        .pop()
-        .ok_or_else(|| {
-            log::error!("No execution info returned for the last transaction");
-            StarknetRpcApiError::InternalServerError
-        })?;
+    let execution_infos = with_memory_limit(client.execution_memory_limit, || {
+        client.client.runtime_api().re_execute_transactions(previous_block_hash, prev, last, block_context)
+    })?
+    .map_err(|e| {
+        log::error!("Failed to execute runtime API call: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?
+    .map_err(|e| {
+        log::error!("Failed to reexecute the transactions: {e:?}");
+        StarknetRpcApiError::InternalServerError
+    })?
+    .pop()
+    .ok_or_else(|| {
+        log::error!("No execution info returned for the last transaction");
+        StarknetRpcApiError::InternalServerError
+    })?;
 
     Ok(execution_infos)
 }
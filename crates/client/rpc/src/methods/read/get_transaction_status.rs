@@ -1,10 +1,10 @@
 use jsonrpsee::core::RpcResult;
 use mc_db::DeoxysBackend;
 use mc_genesis_data_provider::GenesisProvider;
+use mp_convert::transaction::to_starknet_core_tx;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_transactions::compute_hash::ComputeTransactionHash;
-use mp_transactions::to_starknet_core_transaction::to_starknet_core_tx;
 use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
@@ -16,6 +16,7 @@ use sp_blockchain::HeaderBackend;
 use starknet_core::types::{FieldElement, TransactionExecutionStatus, TransactionStatus};
 
 use crate::errors::StarknetRpcApiError;
+use crate::methods::read::get_transaction_receipt::stored_receipt;
 use crate::utils::get_block_by_block_hash;
 use crate::Starknet;
 
@@ -36,7 +37,8 @@ use crate::Starknet;
 ///   - `finality_status`: The finality status of the transaction, indicating whether it is
 ///     confirmed, pending, or rejected.
 ///   - `execution_status`: The execution status of the transaction, providing details on the
-///     execution outcome if the transaction has been processed.
+///     execution outcome if the transaction has been processed. Transactions reverted by the
+///     sequencer are reported as such from their stored receipt.
 pub fn get_transaction_status<A, BE, G, C, P, H>(
     starknet: &Starknet<A, BE, G, C, P, H>,
     transaction_hash: FieldElement,
@@ -81,7 +83,15 @@ where
                 .map(|tx| to_starknet_core_tx(tx.clone(), transaction_hash))
         };
 
-    let execution_status = {
+    // receipts stored with the block tell whether the transaction was reverted, blocks imported
+    // without them are checked against the runtime
+    let execution_status = if let Some(receipt) = stored_receipt(transaction_hash)? {
+        if receipt.is_reverted() {
+            TransactionExecutionStatus::Reverted
+        } else {
+            TransactionExecutionStatus::Succeeded
+        }
+    } else {
         let revert_error = starknet
             .client
             .runtime_api()
@@ -10,10 +10,9 @@ use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_core::types::{
-    BlockHashAndNumber, BlockId, BroadcastedTransaction, ContractClass, EventFilterWithPage, EventsPage, FeeEstimate,
-    FieldElement, FunctionCall, MaybePendingBlockWithReceipts, MaybePendingBlockWithTxHashes, MaybePendingBlockWithTxs,
-    MaybePendingStateUpdate, MsgFromL1, SimulationFlagForEstimateFee, SyncStatusType, Transaction,
-    TransactionReceiptWithBlockInfo, TransactionStatus,
+    BlockHashAndNumber, BlockId, BroadcastedTransaction, ContractClass, EventFilterWithPage, FieldElement,
+    FunctionCall, MaybePendingBlockWithReceipts, MaybePendingBlockWithTxHashes, MaybePendingStateUpdate, MsgFromL1,
+    SimulationFlagForEstimateFee, SyncStatusType, Transaction, TransactionReceiptWithBlockInfo, TransactionStatus,
 };
 
 use super::block_hash_and_number::*;
@@ -36,7 +35,8 @@ use super::get_transaction_by_hash::*;
 use super::get_transaction_receipt::*;
 use super::get_transaction_status::*;
 use super::syncing::*;
-use crate::{Felt, Starknet, StarknetReadRpcApiServer};
+use crate::spans::{traced, traced_async};
+use crate::{EventsPage, FeeEstimate, Felt, MaybePendingBlockWithTxs, Starknet, StarknetReadRpcApiServer};
 
 #[async_trait]
 impl<A, BE, G, C, P, H> StarknetReadRpcApiServer for Starknet<A, BE, G, C, P, H>
@@ -51,102 +51,118 @@ where
     H: HasherT + Send + Sync + 'static,
 {
     fn block_number(&self) -> RpcResult<u64> {
-        self.current_block_number()
+        traced("starknet_blockNumber", || Ok(block_hash_and_number(self)?.block_number))
     }
 
     fn spec_version(&self) -> RpcResult<String> {
-        self.current_spec_version()
+        traced("starknet_specVersion", || self.current_spec_version())
     }
 
     fn block_hash_and_number(&self) -> RpcResult<BlockHashAndNumber> {
-        block_hash_and_number(self)
+        traced("starknet_blockHashAndNumber", || block_hash_and_number(self))
     }
 
-    fn call(&self, request: FunctionCall, block_id: BlockId) -> RpcResult<Vec<String>> {
-        call(self, request, block_id)
+    fn call(&self, request: FunctionCall, block_id: Option<BlockId>) -> RpcResult<Vec<String>> {
+        let block_id = self.rpc_config.block_id_or_default(block_id);
+        traced("starknet_call", || call(self, request, block_id))
     }
 
     fn chain_id(&self) -> RpcResult<Felt> {
-        self.chain_id()
+        traced("starknet_chainId", || self.chain_id())
     }
 
     fn get_block_transaction_count(&self, block_id: BlockId) -> RpcResult<u128> {
-        get_block_transaction_count(self, block_id)
+        traced("starknet_getBlockTransactionCount", || get_block_transaction_count(self, block_id))
     }
 
     async fn estimate_fee(
         &self,
         request: Vec<BroadcastedTransaction>,
         simulation_flags: Vec<SimulationFlagForEstimateFee>,
-        block_id: BlockId,
+        block_id: Option<BlockId>,
     ) -> RpcResult<Vec<FeeEstimate>> {
-        estimate_fee(self, request, simulation_flags, block_id).await
+        let block_id = self.rpc_config.block_id_or_default(block_id);
+        let estimates =
+            traced_async("starknet_estimateFee", estimate_fee(self, request, simulation_flags, block_id)).await?;
+        Ok(estimates.into_iter().map(FeeEstimate::from).collect())
     }
 
-    async fn estimate_message_fee(&self, message: MsgFromL1, block_id: BlockId) -> RpcResult<FeeEstimate> {
-        estimate_message_fee(self, message, block_id).await
+    async fn estimate_message_fee(&self, message: MsgFromL1, block_id: Option<BlockId>) -> RpcResult<FeeEstimate> {
+        let block_id = self.rpc_config.block_id_or_default(block_id);
+        traced_async("starknet_estimateMessageFee", estimate_message_fee(self, message, block_id))
+            .await
+            .map(FeeEstimate::from)
     }
 
     async fn get_block_with_receipts(&self, block_id: BlockId) -> RpcResult<MaybePendingBlockWithReceipts> {
-        get_block_with_receipts(self, block_id)
+        traced("starknet_getBlockWithReceipts", || get_block_with_receipts(self, block_id))
     }
 
     fn get_block_with_tx_hashes(&self, block_id: BlockId) -> RpcResult<MaybePendingBlockWithTxHashes> {
-        get_block_with_tx_hashes(self, block_id)
+        traced("starknet_getBlockWithTxHashes", || get_block_with_tx_hashes(self, block_id))
     }
 
     fn get_block_with_txs(&self, block_id: BlockId) -> RpcResult<MaybePendingBlockWithTxs> {
-        get_block_with_txs(self, block_id)
+        traced("starknet_getBlockWithTxs", || get_block_with_txs(self, block_id)).map(MaybePendingBlockWithTxs::from)
     }
 
     fn get_class_at(&self, block_id: BlockId, contract_address: FieldElement) -> RpcResult<ContractClass> {
-        get_class_at(self, block_id, contract_address)
+        traced("starknet_getClassAt", || get_class_at(self, block_id, contract_address))
     }
 
     fn get_class_hash_at(&self, block_id: BlockId, contract_address: FieldElement) -> RpcResult<Felt> {
-        get_class_hash_at(self, block_id, contract_address)
+        traced("starknet_getClassHashAt", || get_class_hash_at(self, block_id, contract_address))
     }
 
     fn get_class(&self, block_id: BlockId, class_hash: FieldElement) -> RpcResult<ContractClass> {
-        get_class(self, block_id, class_hash)
+        traced("starknet_getClass", || get_class(self, block_id, class_hash))
     }
 
     async fn get_events(&self, filter: EventFilterWithPage) -> RpcResult<EventsPage> {
-        get_events(self, filter).await
+        traced_async("starknet_getEvents", get_events(self, filter)).await.map(EventsPage::from)
     }
 
-    fn get_nonce(&self, block_id: BlockId, contract_address: FieldElement) -> RpcResult<Felt> {
-        get_nonce(self, block_id, contract_address)
+    fn get_nonce(&self, block_id: Option<BlockId>, contract_address: FieldElement) -> RpcResult<Felt> {
+        let block_id = self.rpc_config.block_id_or_default(block_id);
+        traced("starknet_getNonce", || get_nonce(self, block_id, contract_address))
     }
 
-    fn get_storage_at(&self, contract_address: FieldElement, key: FieldElement, block_id: BlockId) -> RpcResult<Felt> {
-        get_storage_at(self, contract_address, key, block_id)
+    async fn get_storage_at(
+        &self,
+        contract_address: FieldElement,
+        key: FieldElement,
+        block_id: Option<BlockId>,
+    ) -> RpcResult<Felt> {
+        let block_id = self.rpc_config.block_id_or_default(block_id);
+        traced_async("starknet_getStorageAt", get_storage_at(self, contract_address, key, block_id)).await
     }
 
     fn get_transaction_by_block_id_and_index(&self, block_id: BlockId, index: u64) -> RpcResult<Transaction> {
-        get_transaction_by_block_id_and_index(self, block_id, index)
+        traced("starknet_getTransactionByBlockIdAndIndex", || {
+            get_transaction_by_block_id_and_index(self, block_id, index)
+        })
     }
 
     fn get_transaction_by_hash(&self, transaction_hash: FieldElement) -> RpcResult<Transaction> {
-        get_transaction_by_hash(self, transaction_hash)
+        traced("starknet_getTransactionByHash", || get_transaction_by_hash(self, transaction_hash))
     }
 
     async fn get_transaction_receipt(
         &self,
         transaction_hash: FieldElement,
     ) -> RpcResult<TransactionReceiptWithBlockInfo> {
-        get_transaction_receipt(self, transaction_hash).await
+        traced_async("starknet_getTransactionReceipt", get_transaction_receipt(self, transaction_hash)).await
     }
 
     fn get_transaction_status(&self, transaction_hash: FieldElement) -> RpcResult<TransactionStatus> {
-        get_transaction_status(self, transaction_hash)
+        traced("starknet_getTransactionStatus", || get_transaction_status(self, transaction_hash))
     }
 
     async fn syncing(&self) -> RpcResult<SyncStatusType> {
-        syncing(self).await
+        traced_async("starknet_syncing", syncing(self)).await
     }
 
     fn get_state_update(&self, block_id: BlockId) -> RpcResult<MaybePendingStateUpdate> {
-        get_state_update(self, block_id)
+        traced("starknet_getStateUpdate", || get_state_update(self, block_id))
     }
 }
@@ -1,6 +1,6 @@
 use jsonrpsee::core::RpcResult;
 use mc_genesis_data_provider::GenesisProvider;
-use mc_sync::l2::get_highest_block_hash_and_number;
+use mc_sync::l2::{get_highest_block_hash_and_number, get_sync_progress, SyncProgress};
 use mp_hashers::HasherT;
 use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
@@ -9,11 +9,10 @@ use sc_client_api::BlockBackend;
 use sc_transaction_pool::ChainApi;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
-use sp_arithmetic::traits::UniqueSaturatedInto;
 use sp_blockchain::HeaderBackend;
 use starknet_core::types::{SyncStatus, SyncStatusType};
 
-use crate::{madara_backend_client, Starknet};
+use crate::Starknet;
 
 /// Returns an object about the sync status, or false if the node is not synching
 ///
@@ -26,10 +25,10 @@ use crate::{madara_backend_client, Starknet};
 /// * `Syncing` - An Enum that can either be a `mc_rpc_core::SyncStatus` struct representing the
 ///   sync status, or a `Boolean` (`false`) indicating that the node is not currently synchronizing.
 ///
-/// This is an asynchronous function due to its reliance on `sync_service.best_seen_block()`,
-/// which potentially involves network communication and processing to determine the best block
-/// seen by the sync service.
-pub async fn syncing<A, BE, G, C, P, H>(starknet: &Starknet<A, BE, G, C, P, H>) -> RpcResult<SyncStatusType>
+/// The starting and current blocks come from the progress of the sync worker, while the highest
+/// block is the latest block of the feeder gateway, which is polled at a regular interval by the
+/// sync worker. The node is considered as not syncing once it has caught up with the gateway.
+pub async fn syncing<A, BE, G, C, P, H>(_starknet: &Starknet<A, BE, G, C, P, H>) -> RpcResult<SyncStatusType>
 where
     A: ChainApi<Block = DBlockT> + 'static,
     P: TransactionPool<Block = DBlockT> + 'static,
@@ -40,58 +39,28 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    // obtain best seen (highest) block number
-    match starknet.sync_service.best_seen_block().await {
-        Ok(best_seen_block) => {
-            let best_number = starknet.client.info().best_number;
-            let highest_number = best_seen_block.unwrap_or(best_number);
+    // no block has been applied by the sync worker yet
+    let Some(SyncProgress { starting_block, current_block }) = get_sync_progress() else {
+        return Ok(SyncStatusType::NotSyncing);
+    };
 
-            // get a starknet block from the starting substrate block number
-            let starting_block = madara_backend_client::starknet_block_from_substrate_hash(
-                starknet.client.as_ref(),
-                starknet.starting_block,
-            );
+    // Get the highest block number and hash from the global variable update in l2 sync()
+    let (highest_block_hash, highest_block_num) = get_highest_block_hash_and_number();
 
-            // get a starknet block from the current substrate block number
-            let current_block =
-                madara_backend_client::starknet_block_from_substrate_hash(starknet.client.as_ref(), best_number);
+    let (starting_block_hash, starting_block_num) = starting_block;
+    let (current_block_hash, current_block_num) = current_block;
 
-            // get a starknet block from the highest substrate block number
-            let highest_block =
-                madara_backend_client::starknet_block_from_substrate_hash(starknet.client.as_ref(), highest_number);
-
-            if starting_block.is_ok() && current_block.is_ok() && highest_block.is_ok() {
-                // Convert block numbers and hashes to the respective type required by the `syncing` endpoint.
-                let starting_block_num = UniqueSaturatedInto::<u64>::unique_saturated_into(starknet.starting_block);
-                let starting_block_hash = starting_block?.header().hash::<H>().0;
-
-                let current_block_num = UniqueSaturatedInto::<u64>::unique_saturated_into(best_number);
-                let current_block_hash = current_block?.header().hash::<H>().0;
-
-                // Get the highest block number and hash from the global variable update in l2 sync()
-                let (highest_block_hash, highest_block_num) = get_highest_block_hash_and_number();
-
-                // Build the `SyncStatus` struct with the respective syn information
-                Ok(SyncStatusType::Syncing(SyncStatus {
-                    starting_block_num,
-                    starting_block_hash,
-                    current_block_num,
-                    current_block_hash,
-                    highest_block_num,
-                    highest_block_hash,
-                }))
-            } else {
-                // If there was an error when getting a starknet block, then we return `false`,
-                // as per the endpoint specification
-                log::error!("Failed to load Starknet block");
-                Ok(SyncStatusType::NotSyncing)
-            }
-        }
-        Err(_) => {
-            // If there was an error when getting a starknet block, then we return `false`,
-            // as per the endpoint specification
-            log::error!("`SyncingEngine` shut down");
-            Ok(SyncStatusType::NotSyncing)
-        }
+    if current_block_num >= highest_block_num {
+        return Ok(SyncStatusType::NotSyncing);
     }
+
+    // Build the `SyncStatus` struct with the respective syn information
+    Ok(SyncStatusType::Syncing(SyncStatus {
+        starting_block_num,
+        starting_block_hash,
+        current_block_num,
+        current_block_hash,
+        highest_block_num,
+        highest_block_hash,
+    }))
 }
@@ -19,6 +19,8 @@ use super::simulate_transactions::simulate_transactions;
 use super::trace_block_transactions::trace_block_transactions;
 use super::trace_transaction::trace_transaction;
 use crate::errors::StarknetRpcApiError;
+use crate::rate_limit::MethodClass;
+use crate::spans::traced_async;
 use crate::{Starknet, StarknetTraceRpcApiServer};
 
 #[async_trait]
@@ -39,15 +41,22 @@ where
         transactions: Vec<BroadcastedTransaction>,
         simulation_flags: Vec<SimulationFlag>,
     ) -> RpcResult<Vec<SimulatedTransaction>> {
-        simulate_transactions(self, block_id, transactions, simulation_flags).await
+        let _permit = self.method_limiters.get(MethodClass::Trace).acquire().await?;
+        traced_async(
+            "starknet_simulateTransactions",
+            simulate_transactions(self, block_id, transactions, simulation_flags),
+        )
+        .await
     }
 
     async fn trace_block_transactions(&self, block_id: BlockId) -> RpcResult<Vec<TransactionTraceWithHash>> {
-        trace_block_transactions(self, block_id).await
+        let _permit = self.method_limiters.get(MethodClass::Trace).acquire().await?;
+        traced_async("starknet_traceBlockTransactions", trace_block_transactions(self, block_id)).await
     }
 
     async fn trace_transaction(&self, transaction_hash: FieldElement) -> RpcResult<TransactionTraceWithHash> {
-        trace_transaction(self, transaction_hash).await
+        let _permit = self.method_limiters.get(MethodClass::Trace).acquire().await?;
+        traced_async("starknet_traceTransaction", trace_transaction(self, transaction_hash)).await
     }
 }
 
@@ -1,7 +1,10 @@
+use blockifier::state::cached_state::{CachedState, GlobalContractCache};
 use blockifier::transaction::objects::TransactionExecutionInfo;
+use blockifier::transaction::transactions::ExecutableTransaction;
 use jsonrpsee::core::RpcResult;
 use mc_genesis_data_provider::GenesisProvider;
 use mc_storage::StorageOverride;
+use mp_block::DeoxysBlock;
 use mp_hashers::HasherT;
 use mp_simulations::{PlaceHolderErrorTypeForFailedStarknetExecution, SimulationFlags};
 use mp_transactions::from_broadcasted_transactions::ToAccountTransaction;
@@ -21,6 +24,9 @@ use starknet_core::types::{
 use super::lib::ConvertCallInfoToExecuteInvocationError;
 use super::utils::tx_execution_infos_to_tx_trace;
 use crate::errors::StarknetRpcApiError;
+use crate::execution_memory::with_memory_limit;
+use crate::state_reader::DeoxysStateReader;
+use crate::utils::{account_tx_to_api_tx, fee_unit, gas_prices_in};
 use crate::Starknet;
 
 pub async fn simulate_transactions<A, BE, G, C, P, H>(
@@ -39,8 +45,10 @@ where
     P: TransactionPool<Block = DBlockT> + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let substrate_block_hash =
-        starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|_e| StarknetRpcApiError::BlockNotFound)?;
+    let block = starknet.resolve_block_id(block_id)?;
+    let (substrate_block_hash, block_number) = (block.substrate_hash, block.number);
+    starknet.check_account_classes(&transactions)?;
+    starknet.check_transaction_versions(substrate_block_hash, &transactions)?;
 
     let tx_type_and_tx_iterator = transactions.into_iter().map(|tx| match tx {
         BroadcastedTransaction::Invoke(_) => tx.to_account_transaction().map(|tx| (TxType::Invoke, tx)),
@@ -55,25 +63,53 @@ where
             },
         )?;
 
+    let fee_units = user_transactions.iter().map(|tx| fee_unit(&account_tx_to_api_tx(tx))).collect();
+
+    let block = starknet.starknet_block(block)?;
+    let fee_token_addresses = starknet.client.runtime_api().fee_token_addresses(substrate_block_hash).map_err(|e| {
+        log::error!("Failed to get the fee token addresses at block {block_number}: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+    let block_context = block.header().into_block_context(fee_token_addresses, starknet.execution_chain_id()?);
+
     let simulation_flags = SimulationFlags::from(simulation_flags);
 
-    let res = starknet
-        .client
-        .runtime_api()
-        .simulate_transactions(substrate_block_hash, user_transactions, simulation_flags)
-        .map_err(|e| {
-            log::error!("Request parameters error: {e}");
-            StarknetRpcApiError::InternalServerError
-        })?
-        .map_err(|e| {
-            log::error!("Failed to call function: {:#?}", e);
-            StarknetRpcApiError::ContractError
-        })?;
+    let overrides = starknet.overrides.for_block_hash(starknet.client.as_ref(), substrate_block_hash);
+    let mut state = CachedState::new(
+        DeoxysStateReader::new(
+            overrides.as_ref(),
+            &starknet.state_cache,
+            &starknet.overrides.class_cache,
+            substrate_block_hash,
+            block_number,
+        ),
+        GlobalContractCache::new(10),
+    );
+
+    // the transactions are executed in order, each on top of the state left by the previous ones
+    let res = with_memory_limit(starknet.execution_memory_limit, || {
+        user_transactions
+            .into_iter()
+            .map(|tx| {
+                tx.execute(&mut state, &block_context, simulation_flags.charge_fee, simulation_flags.validate).map_err(
+                    |e| {
+                        log::debug!("Transaction execution failed during simulation: {e}");
+                        PlaceHolderErrorTypeForFailedStarknetExecution
+                    },
+                )
+            })
+            .collect::<Vec<_>>()
+    })?;
 
-    let storage_override = starknet.overrides.for_block_hash(starknet.client.as_ref(), substrate_block_hash);
-    let simulated_transactions =
-        tx_execution_infos_to_simulated_transactions(&**storage_override, substrate_block_hash, tx_types, res)
-            .map_err(StarknetRpcApiError::from)?;
+    let simulated_transactions = tx_execution_infos_to_simulated_transactions(
+        overrides.as_ref(),
+        substrate_block_hash,
+        &block,
+        tx_types,
+        fee_units,
+        res,
+    )
+    .map_err(StarknetRpcApiError::from)?;
 
     Ok(simulated_transactions)
 }
@@ -81,38 +117,36 @@ where
 fn tx_execution_infos_to_simulated_transactions<B: BlockT>(
     storage_override: &dyn StorageOverride<B>,
     substrate_block_hash: B::Hash,
+    block: &DeoxysBlock,
     tx_types: Vec<TxType>,
+    fee_units: Vec<PriceUnit>,
     transaction_execution_results: Vec<
         Result<TransactionExecutionInfo, PlaceHolderErrorTypeForFailedStarknetExecution>,
     >,
 ) -> Result<Vec<SimulatedTransaction>, ConvertCallInfoToExecuteInvocationError> {
     let mut results = vec![];
-    for (tx_type, res) in tx_types.into_iter().zip(transaction_execution_results.into_iter()) {
+    for ((tx_type, unit), res) in tx_types.into_iter().zip(fee_units).zip(transaction_execution_results) {
         match res {
             Ok(tx_exec_info) => {
                 let transaction_trace =
                     tx_execution_infos_to_tx_trace(storage_override, substrate_block_hash, tx_type, &tx_exec_info)?;
-                let gas = tx_exec_info.execute_call_info.as_ref().map(|x| x.execution.gas_consumed).unwrap_or_default();
-                let fee = tx_exec_info.actual_fee.0;
-                // TODO: Shouldn't the gas price be taken from the block header instead?
-                let price = if gas > 0 { fee / gas as u128 } else { 0 };
-
-                let gas_consumed = gas.into();
-                let gas_price = price.into();
-                let overall_fee = fee.into();
 
-                let unit: PriceUnit = PriceUnit::Wei; //TODO(Tbelleng) : Get Price Unit from Tx
-                let data_gas_consumed = tx_exec_info.da_gas.l1_data_gas.into();
-                let data_gas_price = tx_exec_info.da_gas.l1_gas.into();
+                // the fee is split between L1 gas and L1 data gas, priced in the unit of the
+                // transaction
+                let (gas_price, data_gas_price) = gas_prices_in(block, unit);
+                let fee = tx_exec_info.actual_fee.0;
+                let data_gas_consumed = tx_exec_info.da_gas.l1_data_gas;
+                let gas_consumed =
+                    fee.saturating_sub(data_gas_consumed.saturating_mul(data_gas_price)) / gas_price.max(1);
 
                 results.push(SimulatedTransaction {
                     transaction_trace,
                     fee_estimation: FeeEstimate {
-                        gas_consumed,
-                        data_gas_consumed,
-                        data_gas_price,
-                        gas_price,
-                        overall_fee,
+                        gas_consumed: gas_consumed.into(),
+                        data_gas_consumed: data_gas_consumed.into(),
+                        data_gas_price: data_gas_price.into(),
+                        gas_price: gas_price.into(),
+                        overall_fee: fee.into(),
                         unit,
                     },
                 });
@@ -14,10 +14,10 @@ use sp_blockchain::HeaderBackend;
 use starknet_core::types::{BlockId, TransactionTraceWithHash};
 
 use super::utils::{
-    get_previous_block_substrate_hash, map_transaction_to_user_transaction, tx_execution_infos_to_tx_trace,
+    get_previous_block_substrate_hash, map_transaction_to_user_transaction, re_execute_transactions,
+    tx_execution_infos_to_tx_trace,
 };
 use crate::errors::StarknetRpcApiError;
-use crate::utils::get_block_by_block_hash;
 use crate::Starknet;
 
 pub async fn trace_block_transactions<A, BE, G, C, P, H>(
@@ -34,49 +34,31 @@ where
     P: TransactionPool<Block = DBlockT> + 'static,
     H: HasherT + Send + Sync + 'static,
 {
-    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
-        log::error!("Block not found: '{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
+    let block = starknet.resolve_block_id(block_id)?;
+    let substrate_block_hash = block.substrate_hash;
+
+    let starknet_block = starknet.starknet_block(block)?;
+    let chain_id = Felt252Wrapper(starknet.chain_id()?.0);
 
-    let starknet_block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash).map_err(|e| {
-        log::error!("Failed to get block for block hash {substrate_block_hash}: '{e}'");
+    let fee_token_address = starknet.client.runtime_api().fee_token_addresses(substrate_block_hash).map_err(|e| {
+        log::error!("Failed to retrieve fee token address: '{e}'");
         StarknetRpcApiError::InternalServerError
     })?;
-    let chain_id = Felt252Wrapper(starknet.chain_id()?.0);
+    let block_context = starknet_block.header().into_block_context(fee_token_address, starknet.execution_chain_id()?);
 
     let (block_transactions, empty_transactions) =
         map_transaction_to_user_transaction(starknet, starknet_block, substrate_block_hash, chain_id, None)?;
 
     let previous_block_substrate_hash = get_previous_block_substrate_hash(starknet, substrate_block_hash)?;
 
-    let fee_token_address = starknet.client.runtime_api().fee_token_addresses(substrate_block_hash).map_err(|e| {
-        log::error!("Failed to retrieve fee token address: '{e}'");
-        StarknetRpcApiError::InternalServerError
-    })?;
-    let block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
-    let block_header = block.header();
-    // TODO: convert the real chain_id in String
-    let block_context =
-        block_header.into_block_context(fee_token_address, starknet_api::core::ChainId("SN_MAIN".to_string()));
-
-    let execution_infos = starknet
-        .client
-        .runtime_api()
-        .re_execute_transactions(
-            previous_block_substrate_hash,
-            empty_transactions.clone(),
-            block_transactions.clone(),
-            &block_context,
-        )
-        .map_err(|e| {
-            log::error!("Failed to execute runtime API call: {e}");
-            StarknetRpcApiError::InternalServerError
-        })?
-        .map_err(|e| {
-            log::error!("Failed to reexecute the block transactions: {e:?}");
-            StarknetRpcApiError::InternalServerError
-        })?;
+    let execution_infos = re_execute_transactions(
+        starknet,
+        previous_block_substrate_hash,
+        block.number - 1,
+        empty_transactions,
+        block_transactions.clone(),
+        &block_context,
+    )?;
 
     let storage_override = starknet.overrides.for_block_hash(starknet.client.as_ref(), substrate_block_hash);
 
@@ -15,7 +15,8 @@ use starknet_core::types::TransactionTraceWithHash;
 use starknet_ff::FieldElement;
 
 use super::utils::{
-    get_previous_block_substrate_hash, map_transaction_to_user_transaction, tx_execution_infos_to_tx_trace,
+    get_previous_block_substrate_hash, map_transaction_to_user_transaction, re_execute_transactions,
+    tx_execution_infos_to_tx_trace,
 };
 use crate::errors::StarknetRpcApiError;
 use crate::utils::get_block_by_block_hash;
@@ -62,27 +63,16 @@ where
         log::error!("Failed to retrieve fee token address: {e}");
         StarknetRpcApiError::InternalServerError
     })?;
-    // TODO: convert the real chain_id in String
-    let block_context =
-        block_header.into_block_context(fee_token_address, starknet_api::core::ChainId("SN_MAIN".to_string()));
+    let block_context = block_header.into_block_context(fee_token_address, starknet.execution_chain_id()?);
 
-    let execution_infos = starknet
-        .client
-        .runtime_api()
-        .re_execute_transactions(
-            previous_block_substrate_hash,
-            txs_to_execute_before.clone(),
-            tx_to_trace.clone(),
-            &block_context,
-        )
-        .map_err(|e| {
-            log::error!("Failed to execute runtime API call: {e}");
-            StarknetRpcApiError::InternalServerError
-        })?
-        .map_err(|e| {
-            log::error!("Failed to reexecute the block transactions: {e:?}");
-            StarknetRpcApiError::InternalServerError
-        })?;
+    let execution_infos = re_execute_transactions(
+        starknet,
+        previous_block_substrate_hash,
+        block_header.block_number - 1,
+        txs_to_execute_before,
+        tx_to_trace.clone(),
+        &block_context,
+    )?;
 
     let storage_override = starknet.overrides.for_block_hash(starknet.client.as_ref(), substrate_block_hash);
     let _chain_id = Felt252Wrapper(starknet.chain_id()?.0);
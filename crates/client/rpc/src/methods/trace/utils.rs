@@ -1,15 +1,17 @@
 use std::collections::HashMap;
 
+use blockifier::context::BlockContext;
 use blockifier::execution::call_info::CallInfo;
-use blockifier::execution::contract_class::{ClassInfo, ContractClass, ContractClassV1};
-use blockifier::transaction as btx;
-use blockifier::transaction::account_transaction::AccountTransaction;
+use blockifier::execution::contract_class::{ClassInfo, ContractClass};
+use blockifier::state::cached_state::{CachedState, GlobalContractCache};
+use blockifier::transaction::errors::TransactionExecutionError;
 use blockifier::transaction::objects::TransactionExecutionInfo;
 use blockifier::transaction::transaction_execution::Transaction;
-use blockifier::transaction::transactions::L1HandlerTransaction;
+use blockifier::transaction::transactions::ExecutableTransaction;
 use mc_db::DeoxysBackend;
-use mc_storage::StorageOverride;
+use mc_storage::{OverrideHandle, StorageOverride};
 use mp_block::DeoxysBlock;
+use mp_convert::executable::to_executable_transaction;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_transactions::compute_hash::ComputeTransactionHash;
@@ -32,6 +34,8 @@ use starknet_ff::FieldElement;
 
 use super::lib::*;
 use crate::errors::StarknetRpcApiError;
+use crate::execution_memory::with_memory_limit;
+use crate::state_reader::DeoxysStateReader;
 use crate::utils::get_block_by_block_hash;
 use crate::Starknet;
 
@@ -301,11 +305,25 @@ where
         let current_tx_hash = tx.compute_hash::<H>(chain_id, false, Some(block_number));
 
         if Some(Felt252Wrapper::from(current_tx_hash)) == target_transaction_hash {
-            let converted_tx = convert_transaction(tx, starknet, substrate_block_hash, chain_id, block_number)?;
+            let converted_tx = convert_transaction::<_, _, H>(
+                tx,
+                starknet.client.as_ref(),
+                &starknet.overrides,
+                substrate_block_hash,
+                chain_id,
+                block_number,
+            )?;
             transaction_to_trace.push(converted_tx);
             break;
         } else {
-            let converted_tx = convert_transaction(tx, starknet, substrate_block_hash, chain_id, block_number)?;
+            let converted_tx = convert_transaction::<_, _, H>(
+                tx,
+                starknet.client.as_ref(),
+                &starknet.overrides,
+                substrate_block_hash,
+                chain_id,
+                block_number,
+            )?;
             transactions.push(converted_tx);
         }
     }
@@ -313,113 +331,88 @@ where
     Ok((transactions, transaction_to_trace))
 }
 
-fn convert_transaction<A, BE, G, C, P, H>(
+/// Converts a transaction of block `block_number` to a transaction executable by blockifier.
+pub(crate) fn convert_transaction<C, BE, H>(
     tx: &stx::Transaction,
-    starknet: &Starknet<A, BE, G, C, P, H>,
+    client: &C,
+    overrides: &OverrideHandle<DBlockT>,
     substrate_block_hash: DHashT,
     chain_id: Felt252Wrapper,
     block_number: u64,
 ) -> Result<Transaction, StarknetRpcApiError>
 where
-    A: ChainApi<Block = DBlockT> + 'static,
-    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
-    H: HasherT + Send + Sync + 'static,
-    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + StorageProvider<DBlockT, BE>,
+    H: HasherT,
+    BE: Backend<DBlockT>,
 {
-    match tx {
-        stx::Transaction::Invoke(invoke_tx) => {
-            let tx = btx::transactions::InvokeTransaction {
-                tx: invoke_tx.clone(),
-                tx_hash: invoke_tx.compute_hash::<H>(chain_id, false, Some(block_number)),
-                // TODO: Check if this is correct
-                only_query: false,
-            };
-            Ok(Transaction::AccountTransaction(AccountTransaction::Invoke(tx)))
-        }
-        stx::Transaction::DeployAccount(deploy_account_tx) => {
-            let tx = btx::transactions::DeployAccountTransaction {
-                tx: deploy_account_tx.clone(),
-                tx_hash: deploy_account_tx.compute_hash::<H>(chain_id, false, Some(block_number)),
-                // TODO: Fill this with non default contract address
-                contract_address: ContractAddress::default(),
-                // TODO: Check if this is correct
-                only_query: false,
-            };
-            Ok(Transaction::AccountTransaction(AccountTransaction::DeployAccount(tx)))
-        }
+    let tx_hash = tx.compute_hash::<H>(chain_id, false, Some(block_number));
+    let class_info = match tx {
         stx::Transaction::Declare(declare_tx) => {
             let class_hash = ClassHash::from(Felt252Wrapper::from(*declare_tx.class_hash()));
-
-            match declare_tx {
-                stx::DeclareTransaction::V0(_) | stx::DeclareTransaction::V1(_) => {
-                    let contract_class = starknet
-                        .overrides
-                        .for_block_hash(starknet.client.as_ref(), substrate_block_hash)
-                        .contract_class_by_class_hash(substrate_block_hash, class_hash)
-                        .ok_or_else(|| {
-                            log::error!("Failed to retrieve contract class from hash '{class_hash}'");
-                            StarknetRpcApiError::InternalServerError
-                        })?;
-
-                    // TODO: fix class info declaration with non defaulted values
-                    let class_info = ClassInfo::new(&contract_class, 10, 10).unwrap();
-
-                    let tx = btx::transactions::DeclareTransaction::new(
-                        declare_tx.clone(),
-                        declare_tx.compute_hash::<H>(chain_id, false, Some(block_number)),
-                        class_info,
-                    )
-                    .unwrap();
-
-                    Ok(Transaction::AccountTransaction(AccountTransaction::Declare(tx)))
-                }
-                stx::DeclareTransaction::V2(_tx) => {
-                    // TODO: change this contract class to the correct one
-                    let contract_class = starknet_api::state::ContractClass::default();
-                    let contract_class = mp_transactions::utils::sierra_to_casm_contract_class(contract_class)
-                        .map_err(|e| {
-                            log::error!("Failed to convert the SierraContractClass to CasmContractClass: {e}");
-                            StarknetRpcApiError::InternalServerError
-                        })?;
-                    let contract_class = ContractClass::V1(ContractClassV1::try_from(contract_class).map_err(|e| {
-                        log::error!(
-                            "Failed to convert the compiler CasmContractClass to blockifier CasmContractClass: {e}"
-                        );
-                        StarknetRpcApiError::InternalServerError
-                    })?);
-
-                    // TODO: fix class info declaration with non defaulted values
-                    let class_info = ClassInfo::new(&contract_class, 10, 10).unwrap();
-
-                    let tx = btx::transactions::DeclareTransaction::new(
-                        declare_tx.clone(),
-                        declare_tx.compute_hash::<H>(chain_id, false, Some(block_number)),
-                        class_info,
-                    )
-                    .unwrap();
-
-                    Ok(Transaction::AccountTransaction(AccountTransaction::Declare(tx)))
-                }
-                stx::DeclareTransaction::V3(_) => todo!(),
-            }
+            Some(class_info(client, overrides, substrate_block_hash, block_number, class_hash)?)
         }
-        stx::Transaction::L1Handler(handle_l1_message_tx) => {
-            let tx_hash = handle_l1_message_tx.compute_hash::<H>(chain_id, false, Some(block_number));
-            let paid_fee_on_l1: starknet_api::transaction::Fee = DeoxysBackend::l1_handler_paid_fee()
-                .get_fee_paid_for_l1_handler_tx(Felt252Wrapper::from(tx_hash).into())
-                .map_err(|e| {
-                    log::error!("Failed to retrieve fee paid on l1 for tx with hash `{tx_hash:?}`: {e}");
-                    StarknetRpcApiError::InternalServerError
-                })?;
-
-            Ok(Transaction::L1HandlerTransaction(L1HandlerTransaction {
-                tx: handle_l1_message_tx.clone(),
-                tx_hash,
-                paid_fee_on_l1,
-            }))
+        _ => None,
+    };
+    let paid_fee_on_l1 = match tx {
+        stx::Transaction::L1Handler(_) => {
+            Some(DeoxysBackend::l1_handler_paid_fee().get_fee_paid_for_l1_handler_tx(tx_hash.0).map_err(|e| {
+                log::error!("Failed to retrieve fee paid on l1 for tx with hash `{tx_hash:?}`: {e}");
+                StarknetRpcApiError::InternalServerError
+            })?)
         }
-        stx::Transaction::Deploy(_) => todo!(),
-    }
+        _ => None,
+    };
+
+    to_executable_transaction(tx, tx_hash, class_info, paid_fee_on_l1).map_err(|e| {
+        log::error!("Failed to convert transaction {} of block {block_number}: {e}", tx_hash.0);
+        StarknetRpcApiError::InternalServerError
+    })
+}
+
+/// Returns the class info of the class declared with `class_hash` in block `block_number`.
+///
+/// The lengths of Sierra classes are stored when the sync fetches them. Classes fetched before, or
+/// imported as artifacts, get the smallest lengths accepted by blockifier, which only changes the
+/// fee of their declaration.
+fn class_info<C, BE>(
+    client: &C,
+    overrides: &OverrideHandle<DBlockT>,
+    substrate_block_hash: DHashT,
+    block_number: u64,
+    class_hash: ClassHash,
+) -> Result<ClassInfo, StarknetRpcApiError>
+where
+    C: HeaderBackend<DBlockT> + StorageProvider<DBlockT, BE>,
+    BE: Backend<DBlockT>,
+{
+    let contract_class = overrides
+        .class_cache
+        .get_or_load(class_hash, block_number, || {
+            overrides
+                .for_block_hash(client, substrate_block_hash)
+                .contract_class_by_class_hash(substrate_block_hash, class_hash)
+        })
+        .ok_or_else(|| {
+            log::error!("Failed to retrieve contract class from hash '{class_hash}'");
+            StarknetRpcApiError::InternalServerError
+        })?;
+
+    let (sierra_program_length, abi_length) = match &contract_class {
+        ContractClass::V0(_) => (0, 0),
+        ContractClass::V1(_) => match DeoxysBackend::class().lengths(&class_hash) {
+            Ok(Some(lengths)) => (lengths.sierra_program_length as usize, lengths.abi_length as usize),
+            Ok(None) => (1, 0),
+            Err(e) => {
+                log::error!("Failed to read the lengths of class {class_hash}: {e}");
+                return Err(StarknetRpcApiError::InternalServerError);
+            }
+        },
+    };
+
+    ClassInfo::new(&contract_class, sierra_program_length, abi_length).map_err(|e| {
+        log::error!("Invalid class info for class {class_hash}: {e}");
+        StarknetRpcApiError::InternalServerError
+    })
 }
 
 pub fn get_previous_block_substrate_hash<A, BE, G, C, P, H>(
@@ -443,11 +436,59 @@ where
     if previous_block_number == 0 {
         previous_block_number = 0;
     }
-    let substrate_block_hash =
-        starknet.substrate_block_hash_from_starknet_block(BlockId::Number(previous_block_number)).map_err(|e| {
+    let substrate_block_hash = starknet
+        .resolve_block_id(BlockId::Number(previous_block_number))
+        .map_err(|e| {
             log::error!("Failed to retrieve previous block substrate hash: {e}");
             StarknetRpcApiError::InternalServerError
-        })?;
+        })?
+        .substrate_hash;
 
     Ok(substrate_block_hash)
 }
+
+/// Re-executes the transactions of a block on top of the state of the previous block: the
+/// `transactions_before` to rebuild the state they ran on, then the `transactions_to_trace`, whose
+/// execution infos are returned.
+pub fn re_execute_transactions<A, BE, G, C, P, H>(
+    starknet: &Starknet<A, BE, G, C, P, H>,
+    previous_substrate_block_hash: DHashT,
+    previous_block_number: u64,
+    transactions_before: Vec<Transaction>,
+    transactions_to_trace: Vec<Transaction>,
+    block_context: &BlockContext,
+) -> Result<Vec<TransactionExecutionInfo>, StarknetRpcApiError>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    BE: Backend<DBlockT> + 'static,
+{
+    // blocks with a gas price of 1 were executed without charging fees
+    let charge_fee = block_context.block_info().gas_prices.eth_l1_gas_price.get() != 1;
+
+    let overrides = starknet.overrides.for_block_hash(starknet.client.as_ref(), previous_substrate_block_hash);
+    let mut state = CachedState::new(
+        DeoxysStateReader::new(
+            overrides.as_ref(),
+            &starknet.state_cache,
+            &starknet.overrides.class_cache,
+            previous_substrate_block_hash,
+            previous_block_number,
+        ),
+        GlobalContractCache::new(10),
+    );
+
+    with_memory_limit(starknet.execution_memory_limit, || -> Result<Vec<_>, TransactionExecutionError> {
+        for transaction in transactions_before {
+            transaction.execute(&mut state, block_context, charge_fee, false)?;
+        }
+        transactions_to_trace
+            .into_iter()
+            .map(|transaction| transaction.execute(&mut state, block_context, charge_fee, false))
+            .collect()
+    })?
+    .map_err(|e| {
+        log::error!("Failed to re-execute the block transactions: {e}");
+        StarknetRpcApiError::InternalServerError
+    })
+}
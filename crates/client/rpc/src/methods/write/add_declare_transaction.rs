@@ -1,6 +1,8 @@
+use blockifier::transaction::account_transaction::AccountTransaction;
 use jsonrpsee::core::RpcResult;
 use mc_genesis_data_provider::GenesisProvider;
 use mc_sync::utility::get_config;
+use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
@@ -10,9 +12,13 @@ use sc_transaction_pool::ChainApi;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use starknet_core::types::{BroadcastedDeclareTransaction, DeclareTransactionResult};
-use starknet_providers::{Provider, ProviderError, SequencerGatewayProvider};
+use starknet_core::types::{
+    BroadcastedDeclareTransaction, BroadcastedTransaction, DeclareTransactionResult, FieldElement,
+};
+use starknet_providers::{Provider, SequencerGatewayProvider};
 
+use super::dry_run::{validate_locally, AddTransactionResult};
+use crate::devnet::{declared_class_abi, PendingTransaction};
 use crate::errors::StarknetRpcApiError;
 use crate::Starknet;
 
@@ -21,14 +27,21 @@ use crate::Starknet;
 /// # Arguments
 ///
 /// * `declare_transaction` - the declare transaction to be added to the chain
+/// * `dry_run` - if `true`, only validate the transaction and estimate its fee locally, without
+///   submitting it
+///
+/// In devnet mode, the transaction is validated locally and included in the next local block.
+/// Otherwise, it is forwarded to the gateway, or pooled to be forwarded later if the gateway is
+/// unavailable.
 ///
 /// # Returns
 ///
 /// * `declare_transaction_result` - the result of the declare transaction
 pub async fn add_declare_transaction<A, BE, G, C, P, H>(
-    _starknet: &Starknet<A, BE, G, C, P, H>,
+    starknet: &Starknet<A, BE, G, C, P, H>,
     declare_transaction: BroadcastedDeclareTransaction,
-) -> RpcResult<DeclareTransactionResult>
+    dry_run: Option<bool>,
+) -> RpcResult<AddTransactionResult<DeclareTransactionResult>>
 where
     A: ChainApi<Block = DBlockT> + 'static,
     P: TransactionPool<Block = DBlockT> + 'static,
@@ -39,22 +52,47 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
+    let transactions = [BroadcastedTransaction::Declare(declare_transaction.clone())];
+    starknet.check_account_classes(&transactions)?;
+    starknet.check_transaction_versions(starknet.client.info().best_hash, &transactions)?;
+
+    if dry_run.unwrap_or(false) {
+        let validated = validate_locally(starknet, BroadcastedTransaction::Declare(declare_transaction)).await?;
+        let result = declare_result(&validated.transaction, validated.transaction_hash);
+        return Ok(validated.into_result(result));
+    }
+
+    if let Some(pool) = &starknet.devnet_pool {
+        let abi = declared_class_abi(&declare_transaction);
+        let validated = validate_locally(starknet, BroadcastedTransaction::Declare(declare_transaction)).await?;
+        let result = declare_result(&validated.transaction, validated.transaction_hash);
+        let transaction_hash = validated.transaction_hash;
+        pool.submit(PendingTransaction {
+            transaction: validated.transaction,
+            broadcasted: validated.broadcasted,
+            transaction_hash,
+            abi: Some(abi),
+        });
+        return Ok(AddTransactionResult::Submitted(result));
+    }
+
     let config = get_config().map_err(|e| {
         log::error!("Failed to get config: {e}");
         StarknetRpcApiError::InternalServerError
     })?;
     let sequencer = SequencerGatewayProvider::new(config.feeder_gateway, config.gateway, config.chain_id, None);
 
-    let sequencer_response = match sequencer.add_declare_transaction(declare_transaction).await {
-        Ok(response) => response,
-        Err(ProviderError::StarknetError(e)) => {
-            return Err(StarknetRpcApiError::from(e).into());
-        }
-        Err(e) => {
-            log::error!("Failed to add invoke transaction to sequencer: {e}");
-            return Err(StarknetRpcApiError::InternalServerError.into());
-        }
-    };
+    let transaction = BroadcastedTransaction::Declare(declare_transaction.clone());
+    let forward = sequencer.add_declare_transaction(declare_transaction);
+    let sequencer_response = starknet.forward(transaction, forward, declare_result).await?;
+
+    Ok(AddTransactionResult::Submitted(sequencer_response))
+}
 
-    Ok(sequencer_response)
+/// The result of the declare transaction converted to `transaction`, with the given hash.
+fn declare_result(transaction: &AccountTransaction, transaction_hash: FieldElement) -> DeclareTransactionResult {
+    let AccountTransaction::Declare(tx) = transaction else {
+        unreachable!("a declare transaction is converted to a `Declare` account transaction")
+    };
+    DeclareTransactionResult { transaction_hash, class_hash: Felt252Wrapper::from(tx.tx().class_hash().0).into() }
 }
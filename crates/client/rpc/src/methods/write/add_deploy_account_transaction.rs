@@ -1,6 +1,8 @@
+use blockifier::transaction::account_transaction::AccountTransaction;
 use jsonrpsee::core::RpcResult;
 use mc_genesis_data_provider::GenesisProvider;
 use mc_sync::utility::get_config;
+use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
@@ -10,9 +12,13 @@ use sc_transaction_pool::ChainApi;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use starknet_core::types::{BroadcastedDeployAccountTransaction, DeployAccountTransactionResult};
-use starknet_providers::{Provider, ProviderError, SequencerGatewayProvider};
+use starknet_core::types::{
+    BroadcastedDeployAccountTransaction, BroadcastedTransaction, DeployAccountTransactionResult, FieldElement,
+};
+use starknet_providers::{Provider, SequencerGatewayProvider};
 
+use super::dry_run::{validate_locally, AddTransactionResult};
+use crate::devnet::PendingTransaction;
 use crate::errors::StarknetRpcApiError;
 use crate::Starknet;
 
@@ -21,15 +27,22 @@ use crate::Starknet;
 /// # Arguments
 ///
 /// * `deploy account transaction` - <https://docs.starknet.io/documentation/architecture_and_concepts/Blocks/transactions/#deploy_account_transaction>
+/// * `dry_run` - if `true`, only validate the transaction and estimate its fee locally, without
+///   submitting it
+///
+/// In devnet mode, the transaction is validated locally and included in the next local block.
+/// Otherwise, it is forwarded to the gateway, or pooled to be forwarded later if the gateway is
+/// unavailable.
 ///
 /// # Returns
 ///
 /// * `transaction_hash` - transaction hash corresponding to the invocation
 /// * `contract_address` - address of the deployed contract account
 pub async fn add_deploy_account_transaction<A, BE, G, C, P, H>(
-    _starknet: &Starknet<A, BE, G, C, P, H>,
+    starknet: &Starknet<A, BE, G, C, P, H>,
     deploy_account_transaction: BroadcastedDeployAccountTransaction,
-) -> RpcResult<DeployAccountTransactionResult>
+    dry_run: Option<bool>,
+) -> RpcResult<AddTransactionResult<DeployAccountTransactionResult>>
 where
     A: ChainApi<Block = DBlockT> + 'static,
     P: TransactionPool<Block = DBlockT> + 'static,
@@ -40,22 +53,54 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
+    let transactions = [BroadcastedTransaction::DeployAccount(deploy_account_transaction.clone())];
+    starknet.check_account_classes(&transactions)?;
+    starknet.check_transaction_versions(starknet.client.info().best_hash, &transactions)?;
+
+    if dry_run.unwrap_or(false) {
+        let validated =
+            validate_locally(starknet, BroadcastedTransaction::DeployAccount(deploy_account_transaction)).await?;
+        let result = deploy_account_result(&validated.transaction, validated.transaction_hash);
+        return Ok(validated.into_result(result));
+    }
+
+    if let Some(pool) = &starknet.devnet_pool {
+        let validated =
+            validate_locally(starknet, BroadcastedTransaction::DeployAccount(deploy_account_transaction)).await?;
+        let result = deploy_account_result(&validated.transaction, validated.transaction_hash);
+        let transaction_hash = validated.transaction_hash;
+        pool.submit(PendingTransaction {
+            transaction: validated.transaction,
+            broadcasted: validated.broadcasted,
+            transaction_hash,
+            abi: None,
+        });
+        return Ok(AddTransactionResult::Submitted(result));
+    }
+
     let config = get_config().map_err(|e| {
         log::error!("Failed to get config: {e}");
         StarknetRpcApiError::InternalServerError
     })?;
     let sequencer = SequencerGatewayProvider::new(config.feeder_gateway, config.gateway, config.chain_id, None);
 
-    let sequencer_response = match sequencer.add_deploy_account_transaction(deploy_account_transaction).await {
-        Ok(response) => response,
-        Err(ProviderError::StarknetError(e)) => {
-            return Err(StarknetRpcApiError::from(e).into());
-        }
-        Err(e) => {
-            log::error!("Failed to add invoke transaction to sequencer: {e}");
-            return Err(StarknetRpcApiError::InternalServerError.into());
-        }
-    };
+    let transaction = BroadcastedTransaction::DeployAccount(deploy_account_transaction.clone());
+    let forward = sequencer.add_deploy_account_transaction(deploy_account_transaction);
+    let sequencer_response = starknet.forward(transaction, forward, deploy_account_result).await?;
+
+    Ok(AddTransactionResult::Submitted(sequencer_response))
+}
 
-    Ok(sequencer_response)
+/// The result of the account deployment converted to `transaction`, with the given hash.
+fn deploy_account_result(
+    transaction: &AccountTransaction,
+    transaction_hash: FieldElement,
+) -> DeployAccountTransactionResult {
+    let AccountTransaction::DeployAccount(tx) = transaction else {
+        unreachable!("a deploy account transaction is converted to a `DeployAccount` account transaction")
+    };
+    DeployAccountTransactionResult {
+        transaction_hash,
+        contract_address: Felt252Wrapper::from(*tx.contract_address.0.key()).into(),
+    }
 }
@@ -10,9 +10,11 @@ use sc_transaction_pool::ChainApi;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use starknet_core::types::{BroadcastedInvokeTransaction, InvokeTransactionResult};
-use starknet_providers::{Provider, ProviderError, SequencerGatewayProvider};
+use starknet_core::types::{BroadcastedInvokeTransaction, BroadcastedTransaction, InvokeTransactionResult};
+use starknet_providers::{Provider, SequencerGatewayProvider};
 
+use super::dry_run::{validate_locally, AddTransactionResult};
+use crate::devnet::PendingTransaction;
 use crate::errors::StarknetRpcApiError;
 use crate::Starknet;
 
@@ -21,14 +23,21 @@ use crate::Starknet;
 /// # Arguments
 ///
 /// * `invoke tx` - <https://docs.starknet.io/documentation/architecture_and_concepts/Blocks/transactions/#invoke_transaction>
+/// * `dry_run` - if `true`, only validate the transaction and estimate its fee locally, without
+///   submitting it
+///
+/// In devnet mode, the transaction is validated locally and included in the next local block.
+/// Otherwise, it is forwarded to the gateway, after the transactions of the same account with lower
+/// nonces if the node orders them, or pooled to be forwarded later if the gateway is unavailable.
 ///
 /// # Returns
 ///
 /// * `transaction_hash` - transaction hash corresponding to the invocation
 pub async fn add_invoke_transaction<A, BE, G, C, P, H>(
-    _starknet: &Starknet<A, BE, G, C, P, H>,
+    starknet: &Starknet<A, BE, G, C, P, H>,
     invoke_transaction: BroadcastedInvokeTransaction,
-) -> RpcResult<InvokeTransactionResult>
+    dry_run: Option<bool>,
+) -> RpcResult<AddTransactionResult<InvokeTransactionResult>>
 where
     A: ChainApi<Block = DBlockT> + 'static,
     P: TransactionPool<Block = DBlockT> + 'static,
@@ -39,22 +48,38 @@ where
     G: GenesisProvider + Send + Sync + 'static,
     H: HasherT + Send + Sync + 'static,
 {
+    let transactions = [BroadcastedTransaction::Invoke(invoke_transaction.clone())];
+    starknet.check_account_classes(&transactions)?;
+    starknet.check_transaction_versions(starknet.client.info().best_hash, &transactions)?;
+
+    if dry_run.unwrap_or(false) {
+        let validated = validate_locally(starknet, BroadcastedTransaction::Invoke(invoke_transaction)).await?;
+        return Ok(validated.into_result(InvokeTransactionResult { transaction_hash: validated.transaction_hash }));
+    }
+
+    if let Some(pool) = &starknet.devnet_pool {
+        let validated = validate_locally(starknet, BroadcastedTransaction::Invoke(invoke_transaction)).await?;
+        let transaction_hash = validated.transaction_hash;
+        pool.submit(PendingTransaction {
+            transaction: validated.transaction,
+            broadcasted: validated.broadcasted,
+            transaction_hash,
+            abi: None,
+        });
+        return Ok(AddTransactionResult::Submitted(InvokeTransactionResult { transaction_hash }));
+    }
+
     let config = get_config().map_err(|e| {
         log::error!("Failed to get config: {e}");
         StarknetRpcApiError::InternalServerError
     })?;
     let sequencer = SequencerGatewayProvider::new(config.feeder_gateway, config.gateway, config.chain_id, None);
 
-    let sequencer_response = match sequencer.add_invoke_transaction(invoke_transaction).await {
-        Ok(response) => response,
-        Err(ProviderError::StarknetError(e)) => {
-            return Err(StarknetRpcApiError::from(e).into());
-        }
-        Err(e) => {
-            log::error!("Failed to add invoke transaction to sequencer: {e}");
-            return Err(StarknetRpcApiError::InternalServerError.into());
-        }
-    };
+    let transaction = BroadcastedTransaction::Invoke(invoke_transaction.clone());
+    let forward = sequencer.add_invoke_transaction(invoke_transaction);
+    let sequencer_response = starknet
+        .forward(transaction, forward, |_, transaction_hash| InvokeTransactionResult { transaction_hash })
+        .await?;
 
-    Ok(sequencer_response)
+    Ok(AddTransactionResult::Submitted(sequencer_response))
 }
@@ -0,0 +1,97 @@
+use blockifier::transaction::account_transaction::AccountTransaction;
+use jsonrpsee::core::RpcResult;
+use mc_genesis_data_provider::GenesisProvider;
+use mc_sync::utility::get_config;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_transactions::from_broadcasted_transactions::ToAccountTransaction;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sc_transaction_pool::ChainApi;
+use sc_transaction_pool_api::TransactionPool;
+use serde::Serialize;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::{BlockId, BlockTag, BroadcastedTransaction, FeeEstimate, FieldElement};
+
+use crate::errors::StarknetRpcApiError;
+use crate::methods::read::estimate_fee::estimate_fee_after;
+use crate::utils::account_tx_hash;
+use crate::Starknet;
+
+/// Result of an add-transaction method.
+///
+/// When called with `dry_run` (a Deoxys extension), the transaction is not forwarded to the
+/// gateway: the result is computed locally and comes with the fee estimate of the transaction.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum AddTransactionResult<T> {
+    Submitted(T),
+    DryRun(DryRunResult<T>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DryRunResult<T> {
+    /// What the gateway would have returned had the transaction been submitted.
+    #[serde(flatten)]
+    pub result: T,
+    /// Always `true`, so that dry runs can't be mistaken for actual submissions.
+    pub dry_run: bool,
+    pub fee_estimate: FeeEstimate,
+}
+
+/// A transaction which went through local validation and fee estimation.
+pub(crate) struct LocallyValidated {
+    pub transaction: AccountTransaction,
+    pub broadcasted: BroadcastedTransaction,
+    pub transaction_hash: FieldElement,
+    pub fee_estimate: FeeEstimate,
+}
+
+impl LocallyValidated {
+    pub fn into_result<T>(self, result: T) -> AddTransactionResult<T> {
+        AddTransactionResult::DryRun(DryRunResult { result, dry_run: true, fee_estimate: self.fee_estimate })
+    }
+}
+
+/// Runs the checks the transaction would go through before being accepted, without submitting it.
+///
+/// The transaction is converted (which checks the declared class hashes), then executed on top of
+/// the latest block, including the account validation, to estimate its fee. In devnet mode, it is
+/// executed after the transactions waiting for the next block, so that an account can send several
+/// transactions per block.
+pub(crate) async fn validate_locally<A, BE, G, C, P, H>(
+    starknet: &Starknet<A, BE, G, C, P, H>,
+    transaction: BroadcastedTransaction,
+) -> RpcResult<LocallyValidated>
+where
+    A: ChainApi<Block = DBlockT> + 'static,
+    P: TransactionPool<Block = DBlockT> + 'static,
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    G: GenesisProvider + Send + Sync + 'static,
+    H: HasherT + Send + Sync + 'static,
+{
+    let chain_id = get_config()
+        .map_err(|e| {
+            log::error!("Failed to get config: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .chain_id;
+
+    let account_transaction = transaction.to_account_transaction().map_err(StarknetRpcApiError::from)?;
+    let transaction_hash = account_tx_hash::<H>(&account_transaction, Felt252Wrapper::from(chain_id));
+
+    let pending = starknet.devnet_pool.as_ref().map(|pool| pool.pending_transactions()).unwrap_or_default();
+    let fee_estimate =
+        estimate_fee_after(starknet, pending, vec![transaction.clone()], vec![], BlockId::Tag(BlockTag::Latest))
+            .await?
+            .pop()
+            .ok_or(StarknetRpcApiError::InternalServerError)?;
+
+    Ok(LocallyValidated { transaction: account_transaction, broadcasted: transaction, transaction_hash, fee_estimate })
+}
@@ -17,6 +17,8 @@ use starknet_core::types::{
 use super::add_declare_transaction::*;
 use super::add_deploy_account_transaction::*;
 use super::add_invoke_transaction::*;
+use super::dry_run::AddTransactionResult;
+use crate::spans::traced_async;
 use crate::{Starknet, StarknetWriteRpcApiServer};
 
 #[async_trait]
@@ -34,21 +36,29 @@ where
     async fn add_declare_transaction(
         &self,
         declare_transaction: BroadcastedDeclareTransaction,
-    ) -> RpcResult<DeclareTransactionResult> {
-        add_declare_transaction(self, declare_transaction).await
+        dry_run: Option<bool>,
+    ) -> RpcResult<AddTransactionResult<DeclareTransactionResult>> {
+        traced_async("starknet_addDeclareTransaction", add_declare_transaction(self, declare_transaction, dry_run))
+            .await
     }
 
     async fn add_deploy_account_transaction(
         &self,
         deploy_account_transaction: BroadcastedDeployAccountTransaction,
-    ) -> RpcResult<DeployAccountTransactionResult> {
-        add_deploy_account_transaction(self, deploy_account_transaction).await
+        dry_run: Option<bool>,
+    ) -> RpcResult<AddTransactionResult<DeployAccountTransactionResult>> {
+        traced_async(
+            "starknet_addDeployAccountTransaction",
+            add_deploy_account_transaction(self, deploy_account_transaction, dry_run),
+        )
+        .await
     }
 
     async fn add_invoke_transaction(
         &self,
         invoke_transaction: BroadcastedInvokeTransaction,
-    ) -> RpcResult<InvokeTransactionResult> {
-        add_invoke_transaction(self, invoke_transaction).await
+        dry_run: Option<bool>,
+    ) -> RpcResult<AddTransactionResult<InvokeTransactionResult>> {
+        traced_async("starknet_addInvokeTransaction", add_invoke_transaction(self, invoke_transaction, dry_run)).await
     }
 }
@@ -1,4 +1,5 @@
 pub mod add_declare_transaction;
 pub mod add_deploy_account_transaction;
 pub mod add_invoke_transaction;
+pub mod dry_run;
 pub mod lib;
@@ -0,0 +1,106 @@
+//! Prometheus metrics of the responses of the RPC.
+//!
+//! Some transactions emit thousands of events, making their receipts, which hold all the events,
+//! very large. The size of the receipts served by `starknet_getTransactionReceipt` is measured to
+//! quantify how often this happens. Clients can page through the events of such transactions with
+//! `deoxys_getTransactionEvents` instead.
+//!
+//! The time proof and trace requests wait for a slot, and the number of those refused, tell how
+//! close to their limits these methods are served.
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use prometheus_endpoint::{
+    exponential_buckets, register, CounterVec, Histogram, HistogramOpts, HistogramVec, Opts, PrometheusError, Registry,
+    U64,
+};
+use starknet_core::types::TransactionReceiptWithBlockInfo;
+
+use crate::rate_limit::MethodClass;
+
+static METRICS: OnceLock<ReceiptMetrics> = OnceLock::new();
+static LIMITER_METRICS: OnceLock<LimiterMetrics> = OnceLock::new();
+
+/// Prometheus metrics of the receipts served.
+struct ReceiptMetrics {
+    events: Histogram,
+    size: Histogram,
+}
+
+/// Prometheus metrics of the limiters of the expensive methods.
+struct LimiterMetrics {
+    queue_time: HistogramVec,
+    rejected: CounterVec<U64>,
+}
+
+/// Registers the RPC metrics in `registry`.
+pub fn register_metrics(registry: &Registry) -> Result<(), PrometheusError> {
+    let events = register(
+        Histogram::with_opts(
+            HistogramOpts::new(
+                "deoxys_rpc_receipt_events",
+                "Number of events of the receipts served by starknet_getTransactionReceipt",
+            )
+            .buckets(exponential_buckets(1.0, 4.0, 9)?),
+        )?,
+        registry,
+    )?;
+    let size = register(
+        Histogram::with_opts(
+            HistogramOpts::new(
+                "deoxys_rpc_receipt_size_bytes",
+                "Size of the receipts served by starknet_getTransactionReceipt, serialized in JSON",
+            )
+            .buckets(exponential_buckets(256.0, 4.0, 10)?),
+        )?,
+        registry,
+    )?;
+    let _ = METRICS.set(ReceiptMetrics { events, size });
+
+    let queue_time = register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "deoxys_rpc_queue_time_seconds",
+                "Time the proof and trace requests waited to be served",
+            )
+            .buckets(exponential_buckets(0.001, 4.0, 8)?),
+            &["class"],
+        )?,
+        registry,
+    )?;
+    let rejected = register(
+        CounterVec::new(
+            Opts::new("deoxys_rpc_rejected_requests_total", "Number of proof and trace requests refused"),
+            &["class", "reason"],
+        )?,
+        registry,
+    )?;
+    let _ = LIMITER_METRICS.set(LimiterMetrics { queue_time, rejected });
+    Ok(())
+}
+
+/// Records a receipt served, holding `event_count` events.
+pub(crate) fn observe_receipt(event_count: usize, receipt: &TransactionReceiptWithBlockInfo) {
+    let Some(metrics) = METRICS.get() else {
+        return;
+    };
+    metrics.events.observe(event_count as f64);
+    match serde_json::to_vec(receipt) {
+        Ok(serialized) => metrics.size.observe(serialized.len() as f64),
+        Err(e) => log::debug!("Failed to serialize a receipt to measure its size: {e}"),
+    }
+}
+
+/// Records the time a request of `class` waited for a slot.
+pub(crate) fn observe_queue_time(class: MethodClass, waited: Duration) {
+    if let Some(metrics) = LIMITER_METRICS.get() {
+        metrics.queue_time.with_label_values(&[class.as_str()]).observe(waited.as_secs_f64());
+    }
+}
+
+/// Records a request of `class` refused because of its rate (`rate`) or its queue time (`queue`).
+pub(crate) fn observe_rejection(class: MethodClass, reason: &str) {
+    if let Some(metrics) = LIMITER_METRICS.get() {
+        metrics.rejected.with_label_values(&[class.as_str(), reason]).inc();
+    }
+}
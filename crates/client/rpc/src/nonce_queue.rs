@@ -0,0 +1,200 @@
+//! Ordering of the transactions forwarded to the gateway by each account.
+//!
+//! Bots sending bursts of transactions from a single account submit them in quick succession, and
+//! concurrent requests may reach the gateway out of order: the gateway then rejects the
+//! transactions whose nonce is ahead of the account's. When a [`NonceQueue`] is set, a transaction
+//! whose nonce is ahead of the next nonce of its sender waits, up to a configured time, for the
+//! transactions with lower nonces to be forwarded first.
+//!
+//! The nonces of the forwarded transactions are tracked until they are included in a block, so
+//! that `starknet_getNonce` on the pending block returns the nonce to sign the next transaction of
+//! the burst with. Nonces are part of the signed transactions: the node orders them, but cannot
+//! assign them.
+use std::collections::HashMap;
+use std::pin::pin;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use mp_felt::Felt252Wrapper;
+use mp_types::block::DBlockT;
+use sc_client_api::backend::{Backend, StorageProvider};
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::{
+    BroadcastedDeclareTransaction, BroadcastedInvokeTransaction, BroadcastedTransaction, FieldElement,
+};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::constants::NONCE_TRACKING_TIME;
+use crate::methods::read::get_nonce::pending_nonce;
+use crate::Starknet;
+
+/// The nonce following the last transaction forwarded by an account.
+struct TrackedNonce {
+    next_nonce: FieldElement,
+    forwarded_at: Instant,
+}
+
+/// Next nonces of the accounts which recently forwarded transactions.
+pub struct NonceQueue {
+    /// Maximum time a transaction waits for those with lower nonces.
+    max_wait: Duration,
+    accounts: Mutex<HashMap<FieldElement, TrackedNonce>>,
+    forwarded: Notify,
+}
+
+impl NonceQueue {
+    pub fn new(max_wait: Duration) -> Self {
+        Self { max_wait, accounts: Mutex::new(HashMap::new()), forwarded: Notify::new() }
+    }
+
+    /// The next nonce of `sender` counting its forwarded transactions, if it forwarded any
+    /// recently.
+    pub fn next_nonce(&self, sender: FieldElement) -> Option<FieldElement> {
+        self.accounts
+            .lock()
+            .expect("Failed to acquire lock on nonce queue")
+            .get(&sender)
+            .filter(|tracked| tracked.forwarded_at.elapsed() < NONCE_TRACKING_TIME)
+            .map(|tracked| tracked.next_nonce)
+    }
+
+    /// Waits until the transactions of `sender` with a nonce lower than `nonce` were forwarded, or
+    /// for the maximum wait time. `state_nonce` is the nonce of the sender in the state.
+    ///
+    /// Transactions which are not ahead of the sender are not delayed: the gateway decides what to
+    /// do with them.
+    pub async fn wait_turn(&self, sender: FieldElement, nonce: FieldElement, state_nonce: FieldElement) {
+        let deadline = Instant::now() + self.max_wait;
+        loop {
+            // registered before checking, so that no notification is missed in between
+            let mut notified = pin!(self.forwarded.notified());
+            notified.as_mut().enable();
+
+            let expected = self.next_nonce(sender).map_or(state_nonce, |tracked| tracked.max(state_nonce));
+            if nonce <= expected {
+                return;
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                log::debug!("Forwarding transaction {nonce:#x} of {sender:#x} before those with lower nonces");
+                return;
+            }
+        }
+    }
+
+    /// Records that the transaction of `sender` with the given nonce was forwarded.
+    pub fn forwarded(&self, sender: FieldElement, nonce: FieldElement) {
+        let mut accounts = self.accounts.lock().expect("Failed to acquire lock on nonce queue");
+        accounts.retain(|_, tracked| tracked.forwarded_at.elapsed() < NONCE_TRACKING_TIME);
+
+        let next_nonce = nonce + FieldElement::ONE;
+        let tracked = accounts.entry(sender).or_insert(TrackedNonce { next_nonce, forwarded_at: Instant::now() });
+        tracked.next_nonce = tracked.next_nonce.max(next_nonce);
+        tracked.forwarded_at = Instant::now();
+        drop(accounts);
+
+        self.forwarded.notify_waiters();
+    }
+}
+
+/// The sender and nonce of the transactions ordered by the queue.
+///
+/// Account deployments are not ordered: they are the first transaction of their account.
+pub(crate) fn sender_and_nonce(transaction: &BroadcastedTransaction) -> Option<(FieldElement, FieldElement)> {
+    match transaction {
+        BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V1(tx)) => Some((tx.sender_address, tx.nonce)),
+        BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V3(tx)) => Some((tx.sender_address, tx.nonce)),
+        BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V1(tx)) => Some((tx.sender_address, tx.nonce)),
+        BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V2(tx)) => Some((tx.sender_address, tx.nonce)),
+        BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V3(tx)) => Some((tx.sender_address, tx.nonce)),
+        BroadcastedTransaction::DeployAccount(_) => None,
+    }
+}
+
+impl<A: sc_transaction_pool::ChainApi, BE, G, C, P, H> Starknet<A, BE, G, C, P, H>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+{
+    /// Forwards `transaction` with `forward` once the transactions of its sender with lower nonces
+    /// were forwarded, if a nonce queue is set.
+    pub(crate) async fn forward_in_nonce_order<T, E>(
+        &self,
+        transaction: &BroadcastedTransaction,
+        forward: impl std::future::Future<Output = Result<T, E>>,
+    ) -> Result<T, E> {
+        let (Some(queue), Some((sender, nonce))) = (&self.nonce_queue, sender_and_nonce(transaction)) else {
+            return forward.await;
+        };
+
+        queue.wait_turn(sender, nonce, self.state_nonce(sender)).await;
+        let result = forward.await;
+        if result.is_ok() {
+            queue.forwarded(sender, nonce);
+        }
+        result
+    }
+
+    /// The nonce of `sender` in the pending state, zero for addresses holding no contract yet.
+    fn state_nonce(&self, sender: FieldElement) -> FieldElement {
+        if let Some(nonce) = mc_sync::l2::get_pending_state_update().and_then(|update| pending_nonce(&update, sender)) {
+            return nonce;
+        }
+
+        let latest_block_hash = self.client.info().best_hash;
+        self.overrides
+            .for_block_hash(self.client.as_ref(), latest_block_hash)
+            .nonce(latest_block_hash, Felt252Wrapper(sender).into())
+            .map_or(FieldElement::ZERO, |nonce| Felt252Wrapper::from(nonce).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn felt(value: u64) -> FieldElement {
+        FieldElement::from(value)
+    }
+
+    #[tokio::test]
+    async fn transactions_ahead_wait_for_lower_nonces() {
+        let queue = Arc::new(NonceQueue::new(Duration::from_secs(5)));
+        let sender = felt(1);
+
+        // nonce 6 arrives before nonce 5, which is the nonce of the account
+        let waiting = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move { queue.wait_turn(sender, felt(6), felt(5)).await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+
+        queue.wait_turn(sender, felt(5), felt(5)).await;
+        queue.forwarded(sender, felt(5));
+        waiting.await.unwrap();
+
+        assert_eq!(queue.next_nonce(sender), Some(felt(6)));
+        assert_eq!(queue.next_nonce(felt(2)), None);
+    }
+
+    #[tokio::test]
+    async fn transactions_are_forwarded_after_the_maximum_wait() {
+        let queue = NonceQueue::new(Duration::from_millis(10));
+
+        // the transaction with nonce 5 never comes
+        queue.wait_turn(felt(1), felt(6), felt(5)).await;
+        assert_eq!(queue.next_nonce(felt(1)), None);
+    }
+
+    #[test]
+    fn forwarded_nonces_never_go_back() {
+        let queue = NonceQueue::new(Duration::from_secs(5));
+
+        queue.forwarded(felt(1), felt(7));
+        queue.forwarded(felt(1), felt(3));
+        assert_eq!(queue.next_nonce(felt(1)), Some(felt(8)));
+    }
+}
@@ -0,0 +1,252 @@
+//! Rate limiting of the RPC methods expensive to serve.
+//!
+//! Proofs and traces are served from dedicated buckets, so that a consumer hammering them cannot
+//! starve the node of the resources serving the cheap reads. Each bucket limits the number of
+//! requests started per second and the number of requests served at once, requests waiting for a
+//! slot for at most a bounded time. Requests refused by a bucket fail with `TOO_MANY_REQUESTS`,
+//! advising in their data when to retry.
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use jsonrpsee::types::error::{CallError, ErrorObject};
+use serde::Serialize;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::constants::{
+    MAX_CONCURRENT_PROOFS, MAX_CONCURRENT_TRACES, MAX_EXPENSIVE_QUEUE_TIME, MAX_PROOFS_PER_SECOND,
+    MAX_TRACES_PER_SECOND,
+};
+use crate::errors::StarknetRpcApiError;
+use crate::metrics;
+
+/// A fixed-window rate limiter.
+///
+/// Allows up to `limit` units of work to be acquired per `window`, across all callers.
+pub struct RateLimiter {
+    limit: u64,
+    window: Duration,
+    state: Mutex<(Instant, u64)>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u64, window: Duration) -> Self {
+        Self { limit, window, state: Mutex::new((Instant::now(), 0)) }
+    }
+
+    /// Tries to acquire `cost` units of work in the current window.
+    ///
+    /// Returns `false` if this would go over the limit, in which case nothing is acquired.
+    pub fn try_acquire(&self, cost: u64) -> bool {
+        let mut state = self.state.lock().expect("Failed to acquire lock on rate limiter state");
+        let (window_start, used) = &mut *state;
+
+        let now = Instant::now();
+        if now.duration_since(*window_start) >= self.window {
+            *window_start = now;
+            *used = 0;
+        }
+
+        if used.saturating_add(cost) > self.limit {
+            return false;
+        }
+
+        *used += cost;
+        true
+    }
+
+    /// Time left before the current window ends, and units of work can be acquired again.
+    pub fn retry_after(&self) -> Duration {
+        let state = self.state.lock().expect("Failed to acquire lock on rate limiter state");
+        self.window.saturating_sub(state.0.elapsed())
+    }
+}
+
+/// The groups of RPC methods limited together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MethodClass {
+    /// `pathfinder_getProof`.
+    Proof,
+    /// `starknet_traceTransaction`, `starknet_traceBlockTransactions` and
+    /// `starknet_simulateTransactions`.
+    Trace,
+}
+
+impl MethodClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MethodClass::Proof => "proof",
+            MethodClass::Trace => "trace",
+        }
+    }
+}
+
+/// Limits of a [`MethodClass`], set by the operator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MethodLimits {
+    /// Maximum number of requests served at once.
+    pub max_concurrent: usize,
+    /// Maximum number of requests started per second.
+    pub max_per_second: u64,
+    /// Maximum time a request waits for one of the `max_concurrent` slots.
+    pub max_queue_time: Duration,
+}
+
+impl MethodLimits {
+    /// The default limits of `class`.
+    pub fn default_for(class: MethodClass) -> Self {
+        let (max_concurrent, max_per_second) = match class {
+            MethodClass::Proof => (MAX_CONCURRENT_PROOFS, MAX_PROOFS_PER_SECOND),
+            MethodClass::Trace => (MAX_CONCURRENT_TRACES, MAX_TRACES_PER_SECOND),
+        };
+        Self { max_concurrent, max_per_second, max_queue_time: MAX_EXPENSIVE_QUEUE_TIME }
+    }
+}
+
+/// A request refused by a [`MethodLimiter`].
+#[derive(Debug, PartialEq)]
+pub struct Overloaded {
+    /// Time after which the request is likely to be accepted.
+    pub retry_after: Duration,
+}
+
+#[derive(Serialize)]
+struct OverloadedData {
+    retry_after: u64,
+}
+
+impl From<Overloaded> for jsonrpsee::core::Error {
+    fn from(overloaded: Overloaded) -> Self {
+        let error = StarknetRpcApiError::TooManyRequests;
+        // rounded up, so that retrying right on time is not refused again
+        let retry_after = overloaded.retry_after.as_secs() + u64::from(overloaded.retry_after.subsec_nanos() > 0);
+        jsonrpsee::core::Error::Call(CallError::Custom(ErrorObject::owned(
+            error as i32,
+            error.to_string(),
+            Some(OverloadedData { retry_after: retry_after.max(1) }),
+        )))
+    }
+}
+
+/// Rate and concurrency limits of a [`MethodClass`].
+pub struct MethodLimiter {
+    class: MethodClass,
+    rate: RateLimiter,
+    slots: Semaphore,
+    max_queue_time: Duration,
+}
+
+impl MethodLimiter {
+    pub fn new(class: MethodClass, limits: &MethodLimits) -> Self {
+        Self {
+            class,
+            rate: RateLimiter::new(limits.max_per_second, Duration::from_secs(1)),
+            slots: Semaphore::new(limits.max_concurrent),
+            max_queue_time: limits.max_queue_time,
+        }
+    }
+
+    /// Waits for a slot to serve a request, holding it until the returned permit is dropped.
+    ///
+    /// Fails right away when the rate limit is reached, and once `max_queue_time` elapsed without
+    /// a slot being freed.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, Overloaded> {
+        if !self.rate.try_acquire(1) {
+            metrics::observe_rejection(self.class, "rate");
+            return Err(Overloaded { retry_after: self.rate.retry_after() });
+        }
+
+        let start = Instant::now();
+        let permit = tokio::time::timeout(self.max_queue_time, self.slots.acquire()).await;
+        metrics::observe_queue_time(self.class, start.elapsed());
+        match permit {
+            Ok(Ok(permit)) => Ok(permit),
+            // the semaphore is never closed, only the timeout can be reached
+            Ok(Err(_)) | Err(_) => {
+                metrics::observe_rejection(self.class, "queue");
+                Err(Overloaded { retry_after: self.max_queue_time })
+            }
+        }
+    }
+}
+
+/// The limiters of every [`MethodClass`], shared by the RPC servers of all versions.
+pub struct MethodLimiters {
+    proof: MethodLimiter,
+    trace: MethodLimiter,
+}
+
+impl MethodLimiters {
+    pub fn new(proof: &MethodLimits, trace: &MethodLimits) -> Self {
+        Self {
+            proof: MethodLimiter::new(MethodClass::Proof, proof),
+            trace: MethodLimiter::new(MethodClass::Trace, trace),
+        }
+    }
+
+    pub fn get(&self, class: MethodClass) -> &MethodLimiter {
+        match class {
+            MethodClass::Proof => &self.proof,
+            MethodClass::Trace => &self.trace,
+        }
+    }
+}
+
+impl Default for MethodLimiters {
+    fn default() -> Self {
+        Self::new(&MethodLimits::default_for(MethodClass::Proof), &MethodLimits::default_for(MethodClass::Trace))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limiter_refuses_over_limit() {
+        let limiter = RateLimiter::new(10, Duration::from_secs(3600));
+
+        assert!(limiter.try_acquire(6));
+        assert!(!limiter.try_acquire(5));
+        assert!(limiter.try_acquire(4));
+        assert!(!limiter.try_acquire(1));
+    }
+
+    #[test]
+    fn rate_limiter_resets_after_window() {
+        let limiter = RateLimiter::new(1, Duration::ZERO);
+
+        assert!(limiter.try_acquire(1));
+        assert!(limiter.try_acquire(1));
+    }
+
+    #[test]
+    fn rate_limiter_retry_after_is_within_window() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(3600));
+
+        assert!(limiter.try_acquire(1));
+        let retry_after = limiter.retry_after();
+        assert!(retry_after > Duration::from_secs(3590) && retry_after <= Duration::from_secs(3600));
+    }
+
+    #[tokio::test]
+    async fn method_limiter_refuses_once_queue_time_elapsed() {
+        let limits = MethodLimits { max_concurrent: 1, max_per_second: 10, max_queue_time: Duration::from_millis(10) };
+        let limiter = MethodLimiter::new(MethodClass::Proof, &limits);
+
+        let permit = limiter.acquire().await.expect("a slot is free");
+        assert_eq!(limiter.acquire().await.unwrap_err(), Overloaded { retry_after: Duration::from_millis(10) });
+
+        drop(permit);
+        assert!(limiter.acquire().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn method_limiter_refuses_over_rate() {
+        let limits = MethodLimits { max_concurrent: 10, max_per_second: 1, max_queue_time: Duration::from_secs(1) };
+        let limiter = MethodLimiter::new(MethodClass::Trace, &limits);
+
+        let _permit = limiter.acquire().await.expect("under the rate limit");
+        let retry_after = limiter.acquire().await.unwrap_err().retry_after;
+        assert!(retry_after <= Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,176 @@
+//! Re-execution of imported blocks, for their verification by the node.
+//!
+//! The transactions of a block are executed again on top of the state of its parent, the way they
+//! are for traces, and each execution is checked against the receipt of the transaction: a block
+//! whose transactions fail to execute, or do not execute the way their receipts report, does not
+//! match, see [`ReExecutionError::is_mismatch`].
+//!
+//! Blocks holding `Deploy` transactions, which blockifier can't execute, are not re-executed.
+use std::sync::Arc;
+
+use blockifier::state::cached_state::{CachedState, GlobalContractCache};
+use blockifier::transaction::transactions::ExecutableTransaction;
+use mc_storage::OverrideHandle;
+use mp_block::receipt::TransactionReceiptWrapper;
+use mp_block::DeoxysBlock;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::{DBlockT, DHashT};
+use pallet_starknet_runtime_api::StarknetRuntimeApi;
+use sc_client_api::backend::{Backend, StorageProvider};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::traits::Header as HeaderT;
+use starknet_api::core::ChainId;
+use starknet_api::transaction::Transaction;
+use starknet_ff::FieldElement;
+use thiserror::Error;
+
+use crate::methods::trace::utils::convert_transaction;
+use crate::state_reader::{DeoxysStateReader, StateCache};
+
+#[derive(Debug, Error)]
+pub enum ReExecutionError {
+    #[error("failed to prepare the re-execution of block {block_number}: {reason}")]
+    Setup { block_number: u64, reason: String },
+    #[error("failed to re-execute transaction {index} of block {block_number}: {reason}")]
+    Execution { block_number: u64, index: usize, reason: String },
+    #[error(
+        "transaction {index} of block {block_number} has {field} {executed} when re-executed, {receipt} in its receipt"
+    )]
+    Mismatch { block_number: u64, index: usize, field: &'static str, executed: String, receipt: String },
+}
+
+impl ReExecutionError {
+    /// Whether the block does not match its re-execution, as opposed to failing to prepare it.
+    pub fn is_mismatch(&self) -> bool {
+        matches!(self, ReExecutionError::Execution { .. } | ReExecutionError::Mismatch { .. })
+    }
+}
+
+/// Re-executes imported blocks against the state of the node.
+pub struct BlockReExecutor<C, BE> {
+    client: Arc<C>,
+    overrides: Arc<OverrideHandle<DBlockT>>,
+    /// State values read by the executions, shared by all blocks
+    state_cache: StateCache,
+    _marker: std::marker::PhantomData<BE>,
+}
+
+impl<C, BE> BlockReExecutor<C, BE>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + StorageProvider<DBlockT, BE> + ProvideRuntimeApi<DBlockT> + 'static,
+    C::Api: StarknetRuntimeApi<DBlockT>,
+{
+    pub fn new(client: Arc<C>, overrides: Arc<OverrideHandle<DBlockT>>) -> Self {
+        Self {
+            client,
+            overrides,
+            state_cache: StateCache::new(
+                std::num::NonZeroUsize::new(crate::constants::STATE_CACHE_SIZE)
+                    .expect("State cache size should not be zero"),
+            ),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Re-executes the transactions of `block`, wrapped in the Substrate block
+    /// `substrate_block_hash`, and checks their revert status and fee against `receipts`.
+    pub fn check_block<H: HasherT>(
+        &self,
+        block: &DeoxysBlock,
+        substrate_block_hash: DHashT,
+        receipts: &[TransactionReceiptWrapper],
+        chain_id: Felt252Wrapper,
+    ) -> Result<(), ReExecutionError> {
+        let header = block.header();
+        let block_number = header.block_number;
+        let setup_error = |reason: String| ReExecutionError::Setup { block_number, reason };
+
+        if block.transactions().iter().any(|tx| matches!(tx, Transaction::Deploy(_))) {
+            log::debug!("Block {block_number} holds deploy transactions, which can't be re-executed");
+            return Ok(());
+        }
+        if receipts.len() != block.transactions().len() {
+            return Err(setup_error(format!(
+                "{} receipts for {} transactions",
+                receipts.len(),
+                block.transactions().len()
+            )));
+        }
+
+        let parent_substrate_hash = self
+            .client
+            .header(substrate_block_hash)
+            .map_err(|e| setup_error(e.to_string()))?
+            .map(|header| *header.parent_hash())
+            .ok_or_else(|| setup_error(format!("Substrate block {substrate_block_hash} not found")))?;
+        let fee_token_addresses = self
+            .client
+            .runtime_api()
+            .fee_token_addresses(substrate_block_hash)
+            .map_err(|e| setup_error(e.to_string()))?;
+        let execution_chain_id =
+            ChainId(chain_id.from_utf8().map_err(|e| setup_error(format!("invalid chain id: {e}")))?);
+        let block_context = header.into_block_context(fee_token_addresses, execution_chain_id);
+        // blocks with a gas price of 1 were executed without charging fees
+        let charge_fee = block_context.block_info().gas_prices.eth_l1_gas_price.get() != 1;
+
+        let transactions = block
+            .transactions()
+            .iter()
+            .map(|tx| {
+                convert_transaction::<_, _, H>(
+                    tx,
+                    self.client.as_ref(),
+                    &self.overrides,
+                    substrate_block_hash,
+                    chain_id,
+                    block_number,
+                )
+                .map_err(|e| setup_error(format!("failed to convert a transaction: {e}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let overrides = self.overrides.for_block_hash(self.client.as_ref(), parent_substrate_hash);
+        let mut state = CachedState::new(
+            DeoxysStateReader::new(
+                overrides.as_ref(),
+                &self.state_cache,
+                &self.overrides.class_cache,
+                parent_substrate_hash,
+                block_number.saturating_sub(1),
+            ),
+            GlobalContractCache::new(10),
+        );
+
+        for (index, (transaction, receipt)) in transactions.into_iter().zip(receipts).enumerate() {
+            let execution_info = transaction
+                .execute(&mut state, &block_context, charge_fee, false)
+                .map_err(|e| ReExecutionError::Execution { block_number, index, reason: e.to_string() })?;
+
+            if execution_info.is_reverted() != receipt.is_reverted() {
+                let status = |reverted: bool| if reverted { "reverted" } else { "succeeded" }.to_string();
+                return Err(ReExecutionError::Mismatch {
+                    block_number,
+                    index,
+                    field: "status",
+                    executed: status(execution_info.is_reverted()),
+                    receipt: status(receipt.is_reverted()),
+                });
+            }
+            let actual_fee = Felt252Wrapper::from(execution_info.actual_fee.0);
+            if charge_fee && actual_fee != receipt.actual_fee {
+                return Err(ReExecutionError::Mismatch {
+                    block_number,
+                    index,
+                    field: "fee",
+                    executed: format!("{:#x}", FieldElement::from(actual_fee)),
+                    receipt: format!("{:#x}", FieldElement::from(receipt.actual_fee)),
+                });
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+//! OpenTelemetry spans and latency of the RPC requests.
+
+use std::future::Future;
+use std::time::Instant;
+
+use mc_otel::KeyValue;
+
+fn attributes(method: &'static str) -> Vec<KeyValue> {
+    vec![KeyValue::new("rpc.system", "jsonrpc"), KeyValue::new("rpc.method", method)]
+}
+
+/// Runs the handler of the RPC `method` inside of a span.
+///
+/// The latency of the request is recorded for the admission control of the sync, see
+/// [`mc_sync::admission`].
+pub(crate) fn traced<T>(method: &'static str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = mc_otel::in_span(method, attributes(method), f);
+    mc_sync::admission::record_rpc_latency(start.elapsed());
+    result
+}
+
+/// Runs the async handler of the RPC `method` inside of a span.
+pub(crate) async fn traced_async<F: Future>(method: &'static str, future: F) -> F::Output {
+    let start = Instant::now();
+    let result = mc_otel::in_span_async(method, attributes(method), future).await;
+    mc_sync::admission::record_rpc_latency(start.elapsed());
+    result
+}
@@ -0,0 +1,343 @@
+//! Blockifier state of a past block, read from the database, for executions served by the node
+//! itself.
+//!
+//! Contract storage, nonces and class hashes are read from the flat storage of [`mc_db`], without
+//! going through the runtime. When the flat storage was not backfilled that far yet, storage is
+//! read from a snapshot of the storage trie at the requested block, and nonces and class hashes
+//! from the Substrate state at that block. Classes are loaded from the imported class artifacts or
+//! the Substrate state, once the class declarations show they exist at that block.
+//!
+//! Values read are kept in a [`StateCache`] shared by all requests: a value at a given block never
+//! changes, so hot contracts (tokens, oracles...) are only read from the database once. Classes are
+//! likewise served from the [`ClassCache`] of the node.
+use std::cell::OnceCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use blockifier::execution::contract_class::ContractClass;
+use blockifier::state::errors::StateError;
+use blockifier::state::state_api::{State, StateReader, StateResult};
+use lru::LruCache;
+use mc_db::storage::{ContractStorageTrieSnapshot, StorageHandler};
+use mc_db::DeoxysBackend;
+use mc_storage::{ClassCache, StorageOverride};
+use mp_types::block::{DBlockT, DHashT};
+use starknet_api::core::{ClassHash, CompiledClassHash, ContractAddress, Nonce};
+use starknet_api::hash::StarkFelt;
+use starknet_api::state::StorageKey;
+
+use crate::execution_memory;
+
+/// Number of independently locked parts of a [`StateCache`], so that concurrent executions rarely
+/// wait on each other.
+const STATE_CACHE_SHARDS: usize = 16;
+
+/// A value of the state of a contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum StateKey {
+    Storage(ContractAddress, StorageKey),
+    Nonce(ContractAddress),
+    ClassHash(ContractAddress),
+}
+
+/// State values recently read by executions, by block number.
+pub struct StateCache(Vec<Mutex<LruCache<(u64, StateKey), StarkFelt>>>);
+
+impl StateCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        let shard_capacity = NonZeroUsize::new(capacity.get() / STATE_CACHE_SHARDS).unwrap_or(NonZeroUsize::MIN);
+        Self((0..STATE_CACHE_SHARDS).map(|_| Mutex::new(LruCache::new(shard_capacity))).collect())
+    }
+
+    fn shard(&self, key: &(u64, StateKey)) -> &Mutex<LruCache<(u64, StateKey), StarkFelt>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.0[hasher.finish() as usize % self.0.len()]
+    }
+
+    /// Returns the cached value of `key` at `block_number`, reading it with `read` if it is not
+    /// cached. The cache is not locked while reading.
+    fn get_or_read(
+        &self,
+        block_number: u64,
+        key: StateKey,
+        read: impl FnOnce() -> StateResult<StarkFelt>,
+    ) -> StateResult<StarkFelt> {
+        let key = (block_number, key);
+        let shard = self.shard(&key);
+        if let Some(value) = shard.lock().expect("Failed to acquire lock on state cache").get(&key) {
+            return Ok(*value);
+        }
+
+        let value = read()?;
+        shard.lock().expect("Failed to acquire lock on state cache").put(key, value);
+        Ok(value)
+    }
+}
+
+/// The state right after a given block, as seen by the blockifier.
+///
+/// Writes are kept in memory and discarded with the state: this is only meant to run calls,
+/// simulations and traces.
+pub struct DeoxysStateReader<'a> {
+    overrides: &'a dyn StorageOverride<DBlockT>,
+    cache: &'a StateCache,
+    classes: &'a ClassCache,
+    substrate_block_hash: DHashT,
+    block_number: u64,
+    /// Whether the flat storage holds the block.
+    flat: bool,
+    /// Opened on the first storage read which misses the cache, if the flat storage does not hold
+    /// the block.
+    storage: OnceCell<ContractStorageTrieSnapshot>,
+    storage_update: HashMap<(ContractAddress, StorageKey), StarkFelt>,
+    nonce_update: HashMap<ContractAddress, Nonce>,
+    class_hash_update: HashMap<ContractAddress, ClassHash>,
+    compiled_class_hash_update: HashMap<ClassHash, CompiledClassHash>,
+    contract_class_update: HashMap<ClassHash, ContractClass>,
+    visited_pcs: HashMap<ClassHash, HashSet<usize>>,
+}
+
+impl<'a> DeoxysStateReader<'a> {
+    pub fn new(
+        overrides: &'a dyn StorageOverride<DBlockT>,
+        cache: &'a StateCache,
+        classes: &'a ClassCache,
+        substrate_block_hash: DHashT,
+        block_number: u64,
+    ) -> Self {
+        Self {
+            overrides,
+            cache,
+            classes,
+            substrate_block_hash,
+            block_number,
+            flat: DeoxysBackend::contract_storage().is_available(block_number).unwrap_or_default(),
+            storage: OnceCell::new(),
+            storage_update: HashMap::default(),
+            nonce_update: HashMap::default(),
+            class_hash_update: HashMap::default(),
+            compiled_class_hash_update: HashMap::default(),
+            contract_class_update: HashMap::default(),
+            visited_pcs: HashMap::default(),
+        }
+    }
+
+    fn storage(&self) -> StateResult<&ContractStorageTrieSnapshot> {
+        if let Some(storage) = self.storage.get() {
+            return Ok(storage);
+        }
+
+        let storage = StorageHandler::contract_storage_at(self.block_number).map_err(|e| {
+            StateError::StateReadError(format!("Failed to open storage at block {}: {e}", self.block_number))
+        })?;
+        Ok(self.storage.get_or_init(|| storage))
+    }
+
+    fn read_storage_at(&self, contract_address: ContractAddress, key: StorageKey) -> StateResult<StarkFelt> {
+        if self.flat {
+            return Ok(DeoxysBackend::contract_storage()
+                .get_at(&contract_address, &key, self.block_number)
+                .map_err(|e| {
+                    StateError::StateReadError(format!(
+                        "Failed to retrieve storage value for contract {} at key {}: {e}",
+                        contract_address.0.0, key.0.0
+                    ))
+                })?
+                .unwrap_or_default());
+        }
+
+        match self.storage()?.get(&contract_address, &key) {
+            Ok(Some(value)) => Ok(StarkFelt(value.to_bytes_be())),
+            Ok(None) => Ok(StarkFelt::default()),
+            Err(_) => Err(StateError::StateReadError(format!(
+                "Failed to retrieve storage value for contract {} at key {}",
+                contract_address.0.0, key.0.0
+            ))),
+        }
+    }
+
+    fn read_nonce_at(&self, contract_address: ContractAddress) -> StateResult<StarkFelt> {
+        if !self.flat {
+            return Ok(self.overrides.nonce(self.substrate_block_hash, contract_address).unwrap_or_default().0);
+        }
+
+        let nonce = DeoxysBackend::contract_storage().nonce_at(&contract_address, self.block_number).map_err(|e| {
+            StateError::StateReadError(format!("Failed to retrieve nonce of contract {}: {e}", contract_address.0.0))
+        })?;
+        Ok(nonce.unwrap_or_default().0)
+    }
+
+    fn read_class_hash_at(&self, contract_address: ContractAddress) -> StateResult<StarkFelt> {
+        if !self.flat {
+            return Ok(self
+                .overrides
+                .contract_class_hash_by_address(self.substrate_block_hash, contract_address)
+                .unwrap_or_default()
+                .0);
+        }
+
+        let class_hash =
+            DeoxysBackend::contract_storage().class_hash_at(&contract_address, self.block_number).map_err(|e| {
+                StateError::StateReadError(format!(
+                    "Failed to retrieve class hash of contract {}: {e}",
+                    contract_address.0.0
+                ))
+            })?;
+        Ok(class_hash.unwrap_or_default().0)
+    }
+
+    /// Loads a class which is not cached, if it is declared at the block.
+    fn load_class(&self, class_hash: ClassHash) -> Option<ContractClass> {
+        match DeoxysBackend::class().declaration_block_number(&class_hash) {
+            Ok(Some(declared_at)) if declared_at > self.block_number => return None,
+            Ok(_) => {}
+            Err(e) => log::error!("Failed to read the declaration of class {}: {e}", class_hash.0),
+        }
+
+        match DeoxysBackend::class_artifact().get(&class_hash) {
+            Ok(Some(artifact)) => Some(artifact.contract),
+            _ => self.overrides.contract_class_by_class_hash(self.substrate_block_hash, class_hash),
+        }
+    }
+}
+
+/// Fails the reads of executions which went over their memory limit, to stop them.
+fn check_memory_limit() -> StateResult<()> {
+    if execution_memory::limit_exceeded() {
+        return Err(StateError::StateReadError("execution memory limit exceeded".to_string()));
+    }
+    Ok(())
+}
+
+impl StateReader for DeoxysStateReader<'_> {
+    fn get_storage_at(&self, contract_address: ContractAddress, key: StorageKey) -> StateResult<StarkFelt> {
+        check_memory_limit()?;
+        if let Some(value) = self.storage_update.get(&(contract_address, key)) {
+            return Ok(*value);
+        }
+
+        self.cache.get_or_read(self.block_number, StateKey::Storage(contract_address, key), || {
+            self.read_storage_at(contract_address, key)
+        })
+    }
+
+    fn get_nonce_at(&self, contract_address: ContractAddress) -> StateResult<Nonce> {
+        check_memory_limit()?;
+        if let Some(nonce) = self.nonce_update.get(&contract_address) {
+            return Ok(*nonce);
+        }
+
+        self.cache
+            .get_or_read(self.block_number, StateKey::Nonce(contract_address), || self.read_nonce_at(contract_address))
+            .map(Nonce)
+    }
+
+    fn get_class_hash_at(&self, contract_address: ContractAddress) -> StateResult<ClassHash> {
+        check_memory_limit()?;
+        if let Some(class_hash) = self.class_hash_update.get(&contract_address) {
+            return Ok(*class_hash);
+        }
+
+        self.cache
+            .get_or_read(self.block_number, StateKey::ClassHash(contract_address), || {
+                self.read_class_hash_at(contract_address)
+            })
+            .map(ClassHash)
+    }
+
+    fn get_compiled_contract_class(&self, class_hash: ClassHash) -> StateResult<ContractClass> {
+        check_memory_limit()?;
+        match self.contract_class_update.get(&class_hash) {
+            Some(contract_class) => Ok(contract_class.clone()),
+            None => self
+                .classes
+                .get_or_load(class_hash, self.block_number, || self.load_class(class_hash))
+                .ok_or(StateError::UndeclaredClassHash(class_hash)),
+        }
+    }
+
+    fn get_compiled_class_hash(&self, class_hash: ClassHash) -> StateResult<CompiledClassHash> {
+        // compiled class hashes are only needed to declare classes, which calls don't do
+        self.compiled_class_hash_update.get(&class_hash).copied().ok_or(StateError::UndeclaredClassHash(class_hash))
+    }
+}
+
+impl State for DeoxysStateReader<'_> {
+    fn set_storage_at(
+        &mut self,
+        contract_address: ContractAddress,
+        key: StorageKey,
+        value: StarkFelt,
+    ) -> StateResult<()> {
+        self.storage_update.insert((contract_address, key), value);
+
+        Ok(())
+    }
+
+    fn increment_nonce(&mut self, contract_address: ContractAddress) -> StateResult<()> {
+        let nonce = self.get_nonce_at(contract_address)?.try_increment().map_err(StateError::StarknetApiError)?;
+
+        self.nonce_update.insert(contract_address, nonce);
+
+        Ok(())
+    }
+
+    fn set_class_hash_at(&mut self, contract_address: ContractAddress, class_hash: ClassHash) -> StateResult<()> {
+        self.class_hash_update.insert(contract_address, class_hash);
+
+        Ok(())
+    }
+
+    fn set_contract_class(&mut self, class_hash: ClassHash, contract_class: ContractClass) -> StateResult<()> {
+        self.contract_class_update.insert(class_hash, contract_class);
+
+        Ok(())
+    }
+
+    fn set_compiled_class_hash(
+        &mut self,
+        class_hash: ClassHash,
+        compiled_class_hash: CompiledClassHash,
+    ) -> StateResult<()> {
+        self.compiled_class_hash_update.insert(class_hash, compiled_class_hash);
+
+        Ok(())
+    }
+
+    fn add_visited_pcs(&mut self, class_hash: ClassHash, pcs: &HashSet<usize>) {
+        self.visited_pcs.entry(class_hash).or_default().extend(pcs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_api::core::PatriciaKey;
+
+    use super::*;
+
+    #[test]
+    fn state_cache_is_keyed_by_block_and_value() {
+        let cache = StateCache::new(NonZeroUsize::new(1000).unwrap());
+        let address = ContractAddress(PatriciaKey(StarkFelt::from(1u128)));
+        let key = StorageKey(PatriciaKey(StarkFelt::from(2u128)));
+        let read = |value: u128| move || Ok(StarkFelt::from(value));
+
+        assert_eq!(cache.get_or_read(10, StateKey::Storage(address, key), read(3)).unwrap(), StarkFelt::from(3u128));
+        assert_eq!(cache.get_or_read(10, StateKey::Storage(address, key), read(4)).unwrap(), StarkFelt::from(3u128));
+        assert_eq!(cache.get_or_read(11, StateKey::Storage(address, key), read(4)).unwrap(), StarkFelt::from(4u128));
+
+        // the nonce and class hash of a contract are cached separately
+        assert_eq!(cache.get_or_read(10, StateKey::Nonce(address), read(5)).unwrap(), StarkFelt::from(5u128));
+        assert_eq!(cache.get_or_read(10, StateKey::ClassHash(address), read(6)).unwrap(), StarkFelt::from(6u128));
+
+        // failed reads are not cached
+        let failed = cache
+            .get_or_read(12, StateKey::Nonce(address), || Err(StateError::StateReadError("unavailable".to_string())));
+        assert!(failed.is_err());
+        assert_eq!(cache.get_or_read(12, StateKey::Nonce(address), read(7)).unwrap(), StarkFelt::from(7u128));
+    }
+}
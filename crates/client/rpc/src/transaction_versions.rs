@@ -0,0 +1,57 @@
+//! Rejection of the transactions whose version is not accepted by the Starknet version of the
+//! block they are executed on.
+//!
+//! The versions accepted by each Starknet version are listed in [`mp_convert::versions`], which the
+//! sync checks the synced transactions against as well. The `add*Transaction`, `estimateFee` and
+//! `simulateTransactions` methods reject the other versions with `UNSUPPORTED_TX_VERSION`, the
+//! reason being given in the error data.
+use jsonrpsee::core::RpcResult;
+use jsonrpsee::types::error::{CallError, ErrorObject};
+use mp_convert::versions::{broadcasted_transaction_version, check_transaction_version};
+use mp_types::block::{DBlockT, DHashT};
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::BroadcastedTransaction;
+
+use crate::errors::StarknetRpcApiError;
+use crate::utils::get_block_by_block_hash;
+use crate::Starknet;
+
+impl<A: sc_transaction_pool::ChainApi, BE, G, C, P, H> Starknet<A, BE, G, C, P, H>
+where
+    C: HeaderBackend<DBlockT> + 'static,
+{
+    /// Checks that the versions of the transactions are accepted by the Starknet version of the
+    /// block `substrate_block_hash`, on top of which they are executed.
+    ///
+    /// ### Errors
+    ///
+    /// * `BLOCK_NOT_FOUND` - If the block can't be read.
+    /// * `UNSUPPORTED_TX_VERSION` - If the version of a transaction is not accepted, with the
+    ///   Starknet versions accepting it as data.
+    pub(crate) fn check_transaction_versions(
+        &self,
+        substrate_block_hash: DHashT,
+        transactions: &[BroadcastedTransaction],
+    ) -> RpcResult<()> {
+        let block = get_block_by_block_hash(self.client.as_ref(), substrate_block_hash).map_err(|e| {
+            log::error!("Failed to retrieve block with hash {substrate_block_hash}: {e}");
+            StarknetRpcApiError::BlockNotFound
+        })?;
+        // blocks which do not report their version predate all the gated transaction versions
+        let protocol = block.header().protocol_version.from_utf8().ok().filter(|version| !version.is_empty());
+
+        for transaction in transactions {
+            let (kind, version) = broadcasted_transaction_version(transaction);
+            if let Err(e) = check_transaction_version(kind, version, protocol.as_deref()) {
+                log::debug!("Rejected transaction: {e}");
+                let error = StarknetRpcApiError::UnsupportedTxnVersion;
+                return Err(jsonrpsee::core::Error::Call(CallError::Custom(ErrorObject::owned(
+                    error as i32,
+                    error.to_string(),
+                    Some(e.to_string()),
+                ))));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+//! Reads of the bonsai tries from the async RPC handlers.
+//!
+//! The tries are behind locks taken by the sync for the whole commit of a block, which takes
+//! seconds on large blocks: reading them from an executor thread would stall all the requests it
+//! serves in the meantime. The handlers reading the tries go through [`read_tries`] instead, which
+//! runs the reads on the blocking thread pool, and read them through the snapshots of
+//! [`StorageHandler`](mc_db::storage::StorageHandler), which only hold the locks while being
+//! opened.
+use mc_db::storage::DeoxysStorageError;
+
+use crate::errors::StarknetRpcApiError;
+
+/// Runs `read` on the blocking thread pool, reporting its errors as `INTERNAL_SERVER_ERROR`.
+pub(crate) async fn read_tries<T, F>(read: F) -> Result<T, StarknetRpcApiError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, DeoxysStorageError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(read)
+        .await
+        .map_err(|e| {
+            log::error!("Trie read failed to complete: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .map_err(|e| {
+            log::error!("{e}");
+            StarknetRpcApiError::InternalServerError
+        })
+}
@@ -1,9 +1,23 @@
 use std::num::ParseIntError;
 use std::{fmt, u64};
 
+use mp_felt::FeltHex;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use starknet_core::types::{
+    BlockStatus, BlockTag, Event, FieldElement, L1DataAvailabilityMode, ResourcePrice, Transaction,
+};
+
+/// Position of the next event to return by `starknet_getEvents`.
+///
+/// The position is the one of the event in its block, regardless of the filter, so that tokens
+/// stay valid across node restarts and pages can end in the middle of a block or transaction.
 #[derive(PartialEq, Eq, Debug, Default)]
 pub struct ContinuationToken {
     pub block_n: u64,
+    /// Index of the transaction emitting the event in the block.
+    pub tx_n: u64,
+    /// Index of the event among the ones emitted by the transaction.
     pub event_n: u64,
 }
 
@@ -15,23 +29,438 @@ pub enum ParseTokenError {
 
 impl fmt::Display for ContinuationToken {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}-{}", self.block_n, self.event_n)
+        write!(f, "{:x},{},{}", self.block_n, self.tx_n, self.event_n)
     }
 }
 
 impl ContinuationToken {
     pub fn parse(token: String) -> Result<Self, ParseTokenError> {
-        let arr: Vec<&str> = token.split('-').collect();
-        if arr.len() != 2 {
+        let arr: Vec<&str> = token.split(',').collect();
+        if arr.len() != 3 {
             return Err(ParseTokenError::WrongToken);
         }
-        let block_n = arr[0].parse::<u64>().map_err(ParseTokenError::ParseFailed)?;
-        let event_n = arr[1].parse::<u64>().map_err(ParseTokenError::ParseFailed)?;
+        let block_n = u64::from_str_radix(arr[0], 16).map_err(ParseTokenError::ParseFailed)?;
+        let tx_n = arr[1].parse::<u64>().map_err(ParseTokenError::ParseFailed)?;
+        let event_n = arr[2].parse::<u64>().map_err(ParseTokenError::ParseFailed)?;
+
+        Ok(ContinuationToken { block_n, tx_n, event_n })
+    }
 
-        Ok(ContinuationToken { block_n, event_n })
+    /// Whether this position comes after the event `event_n` of transaction `tx_n` of block
+    /// `block_n`, i.e. whether that event was already returned.
+    pub fn is_after(&self, block_n: u64, tx_n: u64, event_n: u64) -> bool {
+        (block_n, tx_n, event_n) < (self.block_n, self.tx_n, self.event_n)
     }
 }
 
+/// A block header, without the transactions, as returned by `deoxys_getHeaders`.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CompactHeader {
+    pub status: BlockStatus,
+    #[serde_as(as = "FeltHex")]
+    pub block_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub parent_hash: FieldElement,
+    pub block_number: u64,
+    #[serde_as(as = "FeltHex")]
+    pub new_root: FieldElement,
+    pub timestamp: u64,
+    #[serde_as(as = "FeltHex")]
+    pub sequencer_address: FieldElement,
+    pub l1_gas_price: ResourcePrice,
+    pub l1_data_gas_price: ResourcePrice,
+    pub l1_da_mode: L1DataAvailabilityMode,
+    pub starknet_version: String,
+    pub transaction_count: u128,
+    pub event_count: u128,
+}
+
+/// A page of consecutive block headers.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HeadersPage {
+    pub headers: Vec<CompactHeader>,
+    /// The number of the first block of the next page, if the requested range was not fully
+    /// covered.
+    pub continuation_block: Option<u64>,
+}
+
+/// The estimated size of a database column, as returned by `deoxys_dbStats`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DbColumnStats {
+    pub column: String,
+    /// The size of the live data, excluding the overwritten and deleted values not compacted yet.
+    pub live_data_size: u64,
+    /// The size of the column files on disk.
+    pub sst_files_size: u64,
+    pub estimated_keys: u64,
+}
+
+/// The estimated size of the database, as returned by `deoxys_dbStats`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DbStats {
+    pub columns: Vec<DbColumnStats>,
+    /// The size of the files of all the columns on disk.
+    pub total_size: u64,
+    /// The size of the trie logs, kept to revert the bonsai tries.
+    pub trie_log_size: u64,
+    /// The share of the bonsai storages size taken by the trie logs, between 0 and 1.
+    pub trie_log_overhead: f64,
+}
+
+/// The chain followed by the node, as returned by `deoxys_getChainInfo`.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChainInfo {
+    #[serde_as(as = "FeltHex")]
+    pub chain_id: FieldElement,
+    /// The address of the Starknet core contract on L1.
+    pub l1_core_contract_address: String,
+    /// The hash of the Starknet OS program registered in the core contract, once read from L1.
+    #[serde_as(as = "Option<FeltHex>")]
+    pub os_program_hash: Option<FieldElement>,
+    /// The hash of the Starknet OS config registered in the core contract, once read from L1.
+    #[serde_as(as = "Option<FeltHex>")]
+    pub os_config_hash: Option<FieldElement>,
+    /// The block read by the call, fee estimation, nonce and storage methods when their block id
+    /// is omitted, as configured by the operator.
+    pub default_block: BlockTag,
+}
+
+/// How far the imported blocks were verified, as returned by `deoxys_getVerificationStatus`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerificationStatus {
+    /// The last imported block, served by the node unless it only serves verified blocks.
+    pub imported_block: u64,
+    /// The last block whose content was verified against its header, if any.
+    pub verified_block: Option<u64>,
+    /// Whether the node only serves the blocks up to the last verified one.
+    pub only_verified: bool,
+}
+
+/// The class used by the node for a quarantined class.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompiledClassSource {
+    /// Fetched from the feeder gateway, matching the declared compiled class hash.
+    Gateway,
+    /// Compiled by the node.
+    Local,
+    /// None, the class served by the feeder gateway does not match its class hash and was rejected.
+    Rejected,
+}
+
+/// A class which does not match its class hash or its declared compiled class hash, as returned by
+/// `deoxys_getQuarantinedClasses`.
+///
+/// The compiled class hashes and compiler version are only set for classes compiled to another
+/// hash, and the computed class hash for classes which do not match their class hash.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct QuarantinedClass {
+    #[serde_as(as = "FeltHex")]
+    pub class_hash: FieldElement,
+    /// The block in which the class was declared.
+    pub block_number: u64,
+    /// The hash of the class served by the feeder gateway.
+    #[serde_as(as = "Option<FeltHex>")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub computed_class_hash: Option<FieldElement>,
+    #[serde_as(as = "Option<FeltHex>")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub declared_compiled_class_hash: Option<FieldElement>,
+    /// The hash of the class compiled by the node.
+    #[serde_as(as = "Option<FeltHex>")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub computed_compiled_class_hash: Option<FieldElement>,
+    /// The version of the compiler of the node.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub compiler_version: Option<String>,
+    pub compiled_class_source: CompiledClassSource,
+}
+
+/// The time spent by a block in each step of the sync, in microseconds, as returned by
+/// `deoxys_getBlockLifecycleReport`.
+///
+/// Steps the block did not go through, such as the verification when it is disabled, are omitted.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BlockLifecycle {
+    pub block_number: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch_us: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decode_us: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub convert_us: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify_us: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trie_update_us: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub db_commit_us: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seal_us: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mapping_us: Option<u64>,
+    /// The time spent in all the steps. Steps of consecutive blocks overlap, so this is not the
+    /// time between the download of the block and its mapping.
+    pub total_us: u64,
+}
+
+/// A data source of the sync, as returned by `deoxys_getDataSources`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DataSource {
+    pub name: String,
+    pub url: String,
+    /// Moving average of the latency of the source, in milliseconds, if it answered yet.
+    pub latency_ms: Option<u64>,
+    /// Moving average of the share of requests which failed, between 0 and 1.
+    pub error_rate: f64,
+    /// The highest block the source is known to have.
+    pub head: Option<u64>,
+    pub pinned: bool,
+    pub banned: bool,
+    /// The score of the source for each kind of request, the requests go to the source with the
+    /// best score.
+    pub scores: DataSourceScores,
+}
+
+/// Scores of a data source, by kind of request.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DataSourceScores {
+    /// Polling the chain head and the pending block.
+    pub head: f64,
+    /// Fetching blocks and state updates.
+    pub block: f64,
+    /// Fetching classes.
+    pub class: f64,
+}
+
+/// The transactions waiting to be forwarded to the gateway, as returned by `deoxys_poolStatus`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PoolStatus {
+    /// Whether the transactions which could not be forwarded are pooled.
+    pub enabled: bool,
+    /// The number of transactions in the pool.
+    pub pending: u64,
+    /// The oldest transactions of the pool.
+    pub transactions: Vec<PooledTransactionStatus>,
+}
+
+/// A transaction waiting to be forwarded to the gateway.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PooledTransactionStatus {
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+    /// The number of failed attempts to forward the transaction.
+    pub attempts: u32,
+    /// The unix time in milliseconds of the next attempt.
+    pub next_attempt: u64,
+}
+
+/// A class declared on chain, as returned by `deoxys_getClassDeclarations`.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DeclaredClass {
+    /// The block in which the class was declared.
+    pub block_number: u64,
+    #[serde_as(as = "FeltHex")]
+    pub class_hash: FieldElement,
+    /// The hash of the compiled class, for Sierra classes.
+    #[serde_as(as = "Option<FeltHex>")]
+    pub compiled_class_hash: Option<FieldElement>,
+    /// The size of the class definition as stored by the node, in bytes, if known.
+    pub size: Option<u64>,
+}
+
+/// A page of class declarations, ordered by block number.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ClassDeclarationsPage {
+    pub declarations: Vec<DeclaredClass>,
+    /// The number of the first block of the next page, if the requested range was not fully
+    /// covered.
+    pub continuation_block: Option<u64>,
+}
+
+/// A page of the events emitted by a transaction, in emission order, as returned by
+/// `deoxys_getTransactionEvents`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TransactionEventsPage {
+    pub events: Vec<Event>,
+    /// The index of the first event of the next page, if the transaction emitted more events.
+    pub continuation_token: Option<u64>,
+}
+
+/// Storage keys of a contract, to read with `deoxys_getStorageAtBatch`.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContractStorageKeys {
+    #[serde_as(as = "FeltHex")]
+    pub contract_address: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub keys: Vec<FieldElement>,
+}
+
+/// Storage values of a contract, in the order of the requested keys, as returned by
+/// `deoxys_getStorageAtBatch`. Slots which were never set are zero.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContractStorageValues {
+    #[serde_as(as = "FeltHex")]
+    pub contract_address: FieldElement,
+    #[serde_as(as = "Vec<FeltHex>")]
+    pub values: Vec<FieldElement>,
+}
+
+/// A value of the state changed between two blocks, as returned by `deoxys_getContractDiff`. Values
+/// which were never set are zero.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FeltChange {
+    #[serde_as(as = "FeltHex")]
+    pub old_value: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub new_value: FieldElement,
+}
+
+/// A storage slot changed between two blocks, as returned by `deoxys_getContractDiff`.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StorageSlotChange {
+    #[serde_as(as = "FeltHex")]
+    pub key: FieldElement,
+    #[serde(flatten)]
+    pub change: FeltChange,
+}
+
+/// The net changes made to a contract between two blocks, as returned by `deoxys_getContractDiff`.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContractDiff {
+    #[serde_as(as = "FeltHex")]
+    pub contract_address: FieldElement,
+    /// The changed storage slots, ordered by key.
+    pub storage_diffs: Vec<StorageSlotChange>,
+    pub nonce: Option<FeltChange>,
+    /// The change of class hash, zero when the contract is not deployed.
+    pub class_hash: Option<FeltChange>,
+}
+
+/// A broadcasted transaction as understood by the node, as returned by `deoxys_decodeTransaction`.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DecodedTransaction {
+    #[serde_as(as = "FeltHex")]
+    pub transaction_hash: FieldElement,
+    /// The address of the deployed account, for deploy account transactions.
+    #[serde_as(as = "Option<FeltHex>")]
+    pub contract_address: Option<FieldElement>,
+    /// The hash of the declared class, for declare transactions.
+    #[serde_as(as = "Option<FeltHex>")]
+    pub class_hash: Option<FieldElement>,
+    pub transaction: Transaction,
+}
+
+/// A node of a Merkle-Patricia trie proof, as returned by `pathfinder_getProof`.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofNode {
+    Binary {
+        #[serde_as(as = "FeltHex")]
+        left: FieldElement,
+        #[serde_as(as = "FeltHex")]
+        right: FieldElement,
+    },
+    Edge {
+        #[serde_as(as = "FeltHex")]
+        child: FieldElement,
+        path: EdgePath,
+    },
+}
+
+/// The path of an edge node: `len` bits, the value of which is `value`.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EdgePath {
+    #[serde_as(as = "FeltHex")]
+    pub value: FieldElement,
+    pub len: usize,
+}
+
+/// The state of a contract, along with the proofs of the requested storage keys.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContractData {
+    #[serde_as(as = "FeltHex")]
+    pub class_hash: FieldElement,
+    #[serde_as(as = "FeltHex")]
+    pub nonce: FieldElement,
+    /// Root of the storage trie of the contract.
+    #[serde_as(as = "FeltHex")]
+    pub root: FieldElement,
+    /// Version of the contract state hash, always 0 for now.
+    #[serde_as(as = "FeltHex")]
+    pub contract_state_hash_version: FieldElement,
+    /// One proof per requested key, in the same order as the keys.
+    pub storage_proofs: Vec<Vec<ProofNode>>,
+}
+
+/// Proof of the state of a contract, as returned by `pathfinder_getProof`.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GetProofOutput {
+    /// The global state commitment of the block.
+    #[serde_as(as = "Option<FeltHex>")]
+    pub state_commitment: Option<FieldElement>,
+    /// Root of the class trie, absent if no Sierra class was ever declared.
+    #[serde_as(as = "Option<FeltHex>")]
+    pub class_commitment: Option<FieldElement>,
+    /// Proof of the contract in the contract trie, a non-membership proof if it is not deployed.
+    pub contract_proof: Vec<ProofNode>,
+    /// Absent if the contract is not deployed.
+    pub contract_data: Option<ContractData>,
+}
+
+/// Proof of the inclusion of an event in the event commitment of its block.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EventInclusionProof {
+    /// Index of the event among all the events of the block.
+    pub event_index: u64,
+    /// The event hash, leaf of the event commitment trie.
+    #[serde_as(as = "FeltHex")]
+    pub leaf: FieldElement,
+    /// The nodes from the event commitment down to the leaf.
+    pub proof: Vec<ProofNode>,
+}
+
+/// Proof of the inclusion of a transaction and of its events in their block, as returned by
+/// `deoxys_getTransactionInclusionProof`.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TransactionInclusionProof {
+    pub block_number: u64,
+    #[serde_as(as = "FeltHex")]
+    pub block_hash: FieldElement,
+    /// Index of the transaction in the block.
+    pub transaction_index: u64,
+    /// The transaction commitment of the block header.
+    #[serde_as(as = "FeltHex")]
+    pub transaction_commitment: FieldElement,
+    /// The transaction hash with signature, leaf of the transaction commitment trie.
+    #[serde_as(as = "FeltHex")]
+    pub transaction_leaf: FieldElement,
+    /// The nodes from the transaction commitment down to the leaf.
+    pub transaction_proof: Vec<ProofNode>,
+    /// The event commitment of the block header.
+    #[serde_as(as = "FeltHex")]
+    pub event_commitment: FieldElement,
+    /// One proof per event emitted by the transaction, in emission order.
+    pub event_proofs: Vec<EventInclusionProof>,
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
@@ -39,39 +468,51 @@ mod tests {
     use crate::types::*;
 
     #[rstest]
-    #[case(0, 0, "0,0")]
-    #[case(1, 4, "1,4")]
-    #[case(2, 4, "2,4")]
-    #[case(30, 4, "1e,4")]
-    #[case(0, 4, "0,4")]
-    fn to_string_works(#[case] block_n: u64, #[case] event_n: u64, #[case] expected: String) {
-        let token = ContinuationToken { block_n, event_n };
+    #[case(0, 0, 0, "0,0,0")]
+    #[case(1, 2, 4, "1,2,4")]
+    #[case(2, 0, 4, "2,0,4")]
+    #[case(30, 12, 4, "1e,12,4")]
+    #[case(0, 0, 4, "0,0,4")]
+    fn to_string_works(#[case] block_n: u64, #[case] tx_n: u64, #[case] event_n: u64, #[case] expected: String) {
+        let token = ContinuationToken { block_n, tx_n, event_n };
         assert_eq!(expected, token.to_string())
     }
 
     #[rstest]
-    #[case("0,0", 0, 0)]
-    #[case("1,4", 1, 4)]
-    #[case("2,4", 2, 4)]
-    #[case("1e,4", 30, 4)]
-    #[case("244,1", 2*16*16+4*16+4, 1)]
-    fn parse_works(#[case] string_token: String, #[case] block_n: u64, #[case] event_n: u64) {
-        let expected = ContinuationToken { block_n, event_n };
+    #[case("0,0,0", 0, 0, 0)]
+    #[case("1,2,4", 1, 2, 4)]
+    #[case("2,0,4", 2, 0, 4)]
+    #[case("1e,12,4", 30, 12, 4)]
+    #[case("244,0,1", 2*16*16+4*16+4, 0, 1)]
+    fn parse_works(#[case] string_token: String, #[case] block_n: u64, #[case] tx_n: u64, #[case] event_n: u64) {
+        let expected = ContinuationToken { block_n, tx_n, event_n };
         assert_eq!(expected, ContinuationToken::parse(string_token).unwrap());
     }
 
+    #[test]
+    fn positions_are_ordered_by_block_transaction_and_event() {
+        let token = ContinuationToken { block_n: 5, tx_n: 2, event_n: 1 };
+        assert!(token.is_after(4, 9, 9));
+        assert!(token.is_after(5, 1, 7));
+        assert!(token.is_after(5, 2, 0));
+        assert!(!token.is_after(5, 2, 1));
+        assert!(!token.is_after(5, 3, 0));
+        assert!(!token.is_after(6, 0, 0));
+    }
+
     #[rstest]
     #[case("100")]
     #[case("0,")]
-    #[case("0,0,0")]
+    #[case("0,0")]
+    #[case("0,0,0,0")]
     fn parse_should_fail(#[case] string_token: String) {
         let result = ContinuationToken::parse(string_token);
         assert!(result.is_err());
     }
 
     #[rstest]
-    #[case("2y,4")]
-    #[case("30,255g")]
+    #[case("2y,0,4")]
+    #[case("30,0,255g")]
     #[case("1,1,")]
     fn parse_u64_should_fail(#[case] string_token: String) {
         let result = ContinuationToken::parse(string_token);
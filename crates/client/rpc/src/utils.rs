@@ -6,15 +6,17 @@ use std::sync::Arc;
 use anyhow::{anyhow, Result};
 use blockifier::execution::call_info::CallInfo;
 use blockifier::execution::contract_class::ContractClass as BlockifierContractClass;
+use blockifier::transaction::account_transaction::AccountTransaction;
 use cairo_lang_starknet_classes::casm_contract_class::{
     CasmContractClass, CasmContractEntryPoint, CasmContractEntryPoints,
 };
 use mc_sync::l1::ETHEREUM_STATE_UPDATE;
 use mp_block::DeoxysBlock;
+use mp_convert::transaction::to_starknet_core_tx;
 use mp_digest_log::find_starknet_block;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
-use mp_transactions::to_starknet_core_transaction::to_starknet_core_tx;
+use mp_transactions::compute_hash::ComputeTransactionHash;
 use mp_types::block::{DBlockT, DHashT};
 use num_bigint::BigUint;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
@@ -27,11 +29,11 @@ use starknet_api::state::ThinStateDiff;
 use starknet_api::transaction as stx;
 use starknet_core::types::contract::{CompiledClass, CompiledClassEntrypoint, CompiledClassEntrypointList};
 use starknet_core::types::{
-    BlockStatus, CompressedLegacyContractClass, ComputationResources, ContractClass, ContractStorageDiffItem,
+    BlockId, BlockStatus, CompressedLegacyContractClass, ComputationResources, ContractClass, ContractStorageDiffItem,
     DataAvailabilityResources, DataResources, DeclaredClassItem, DeployedContractItem, EntryPointsByType, Event,
-    ExecutionResources, FieldElement, FlattenedSierraClass, FromByteArrayError, L1DataAvailabilityMode,
-    LegacyContractEntryPoint, LegacyEntryPointsByType, MsgToL1, NonceUpdate, ReplacedClassItem, ResourcePrice,
-    StateDiff, StorageEntry,
+    ExecutionResources, FeeEstimate, FieldElement, FlattenedSierraClass, FromByteArrayError, L1DataAvailabilityMode,
+    LegacyContractEntryPoint, LegacyEntryPointsByType, MsgToL1, NonceUpdate, PriceUnit, ReplacedClassItem,
+    ResourcePrice, StateDiff, StorageEntry,
 };
 
 use crate::errors::StarknetRpcApiError;
@@ -153,6 +155,64 @@ pub(crate) fn tx_conv(
     txs.iter().zip(tx_hashes).map(|(tx, hash)| to_starknet_core_tx(tx.clone(), hash)).collect()
 }
 
+/// Returns the Starknet transaction wrapped in an account transaction.
+pub(crate) fn account_tx_to_api_tx(tx: &AccountTransaction) -> stx::Transaction {
+    match tx {
+        AccountTransaction::Declare(tx) => stx::Transaction::Declare(tx.tx().clone()),
+        AccountTransaction::DeployAccount(tx) => stx::Transaction::DeployAccount(tx.tx.clone()),
+        AccountTransaction::Invoke(tx) => stx::Transaction::Invoke(tx.tx.clone()),
+    }
+}
+
+/// Computes the hash of an account transaction on the given chain.
+///
+/// The hash set when converting a broadcasted transaction does not account for the chain id, so it
+/// can't be used as is.
+pub(crate) fn account_tx_hash<H: HasherT>(tx: &AccountTransaction, chain_id: Felt252Wrapper) -> FieldElement {
+    FieldElement::from(Felt252Wrapper::from(account_tx_to_api_tx(tx).compute_hash::<H>(chain_id, false, None)))
+}
+
+/// Returns the unit the fee of a transaction is paid in.
+///
+/// V3 transactions pay their fee in STRK (FRI), the older ones and L1 handlers in ETH (WEI).
+pub(crate) fn fee_unit(tx: &stx::Transaction) -> PriceUnit {
+    match tx {
+        stx::Transaction::Declare(stx::DeclareTransaction::V3(_))
+        | stx::Transaction::DeployAccount(stx::DeployAccountTransaction::V3(_))
+        | stx::Transaction::Invoke(stx::InvokeTransaction::V3(_)) => PriceUnit::Fri,
+        _ => PriceUnit::Wei,
+    }
+}
+
+/// Returns the L1 gas and L1 data gas prices of a block, in the given unit.
+pub(crate) fn gas_prices_in(block: &DeoxysBlock, unit: PriceUnit) -> (u128, u128) {
+    match (&block.header().l1_gas_price, unit) {
+        (Some(prices), PriceUnit::Fri) => (prices.strk_l1_gas_price.get(), prices.strk_l1_data_gas_price.get()),
+        (Some(prices), PriceUnit::Wei) => (prices.eth_l1_gas_price.get(), prices.eth_l1_data_gas_price.get()),
+        (None, _) => (0, 0),
+    }
+}
+
+/// Reprices a fee estimate at the tip of the chain with the prices of the gas oracle, when it
+/// runs. Estimates against past blocks keep the prices they were executed with.
+pub(crate) fn with_oracle_gas_prices(estimate: FeeEstimate, block_id: BlockId) -> FeeEstimate {
+    let (BlockId::Tag(_), Some(prices)) = (block_id, mc_sync::gas_oracle::gas_prices()) else {
+        return estimate;
+    };
+    let (gas_price, data_gas_price) = match estimate.unit {
+        PriceUnit::Fri => (prices.strk_l1_gas_price.get(), prices.strk_l1_data_gas_price.get()),
+        PriceUnit::Wei => (prices.eth_l1_gas_price.get(), prices.eth_l1_data_gas_price.get()),
+    };
+    let (gas_price, data_gas_price) = (FieldElement::from(gas_price), FieldElement::from(data_gas_price));
+
+    FeeEstimate {
+        overall_fee: estimate.gas_consumed * gas_price + estimate.data_gas_consumed * data_gas_price,
+        gas_price,
+        data_gas_price,
+        ..estimate
+    }
+}
+
 pub(crate) fn status(block_number: u64) -> BlockStatus {
     if block_number <= ETHEREUM_STATE_UPDATE.read().unwrap().block_number {
         BlockStatus::AcceptedOnL1
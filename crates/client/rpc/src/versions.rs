@@ -0,0 +1,126 @@
+//! Versions of the Starknet RPC spec served by the node.
+//!
+//! Methods are implemented against the latest spec version. Responses for older versions are
+//! derived from the latest ones with [`RpcVersion::adapt_response`], so that clients which have not
+//! upgraded yet can keep using the node. Responses defined in [`crate::dto`] are converted to their
+//! type in the older version, while the fields introduced since (e.g. the data gas fields of v0.7)
+//! are removed from the others.
+use serde_json::Value;
+
+use crate::dto::{self, FeeEstimate};
+
+/// A version of the Starknet RPC spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcVersion {
+    V0_6,
+    V0_7,
+}
+
+impl RpcVersion {
+    /// The version the methods are implemented against.
+    pub const LATEST: RpcVersion = RpcVersion::V0_7;
+
+    /// The version returned by `starknet_specVersion`.
+    pub fn spec_version(self) -> &'static str {
+        match self {
+            RpcVersion::V0_6 => "0.6.0",
+            RpcVersion::V0_7 => "0.7.0",
+        }
+    }
+
+    /// Returns the version served under the given path, e.g. `/rpc/v0_6`.
+    pub fn from_path(path: &str) -> Option<Self> {
+        match path.trim_end_matches('/') {
+            "/rpc/v0_6" => Some(RpcVersion::V0_6),
+            "/rpc/v0_7" => Some(RpcVersion::V0_7),
+            _ => None,
+        }
+    }
+
+    /// Converts the result of a method, as returned for the latest version, to its shape in this
+    /// version.
+    pub fn adapt_response(self, method: &str, result: &mut Value) {
+        if method == "starknet_specVersion" {
+            *result = Value::String(self.spec_version().to_string());
+            return;
+        }
+        match method {
+            "starknet_estimateFee" => return dto::adapt::<Vec<FeeEstimate>>(self, result),
+            "starknet_estimateMessageFee" => return dto::adapt::<FeeEstimate>(self, result),
+            _ => {}
+        }
+        if self != RpcVersion::V0_6 {
+            return;
+        }
+
+        match method {
+            "starknet_getBlockWithTxHashes" | "starknet_getBlockWithTxs" => {
+                remove_fields(result, &["l1_data_gas_price", "l1_da_mode"]);
+            }
+            "starknet_getTransactionReceipt" => {
+                if let Some(resources) = result.get_mut("execution_resources") {
+                    remove_fields(resources, &["data_availability"]);
+                }
+            }
+            "starknet_simulateTransactions" => {
+                for simulated in result.as_array_mut().into_iter().flatten() {
+                    if let Some(estimate) = simulated.get_mut("fee_estimation") {
+                        remove_fields(estimate, &["data_gas_consumed", "data_gas_price"]);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn remove_fields(value: &mut Value, fields: &[&str]) {
+    if let Value::Object(object) = value {
+        for field in fields {
+            object.remove(*field);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn v0_6_responses_drop_data_gas_fields() {
+        assert_eq!(RpcVersion::from_path("/rpc/v0_6/"), Some(RpcVersion::V0_6));
+        assert_eq!(RpcVersion::from_path("/rpc/v0_8"), None);
+
+        let mut receipt = json!({
+            "transaction_hash": "0x1",
+            "execution_resources": { "steps": 10, "data_availability": { "l1_gas": 0, "l1_data_gas": 128 } },
+        });
+        RpcVersion::V0_6.adapt_response("starknet_getTransactionReceipt", &mut receipt);
+        assert_eq!(receipt, json!({ "transaction_hash": "0x1", "execution_resources": { "steps": 10 } }));
+
+        let estimate = json!([{
+            "gas_consumed": "0x1",
+            "gas_price": "0x2",
+            "data_gas_consumed": "0x3",
+            "data_gas_price": "0x4",
+            "overall_fee": "0xe",
+            "unit": "WEI",
+        }]);
+        let mut adapted = estimate.clone();
+        RpcVersion::V0_6.adapt_response("starknet_estimateFee", &mut adapted);
+        assert_eq!(
+            adapted,
+            json!([{ "gas_consumed": "0x1", "gas_price": "0x2", "overall_fee": "0xe", "unit": "WEI" }])
+        );
+
+        let mut latest = estimate.clone();
+        RpcVersion::V0_7.adapt_response("starknet_estimateFee", &mut latest);
+        assert_eq!(latest, estimate);
+
+        let mut spec_version = json!("0.7.0");
+        RpcVersion::V0_6.adapt_response("starknet_specVersion", &mut spec_version);
+        assert_eq!(spec_version, json!("0.6.0"));
+    }
+}
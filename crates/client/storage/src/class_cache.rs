@@ -0,0 +1,99 @@
+//! Classes recently loaded for executions, shared by all the blockifier state readers of the node.
+//!
+//! Loading a class decodes it from the Substrate state, which is expensive for the large Sierra
+//! classes compiled to CASM. The sync, which checks the classes of each block against the stored
+//! ones, and the RPC, which executes calls and traces, load the same hot classes over and over:
+//! they are kept in a single cache instead. A class never changes for a given class hash, so cached
+//! classes are served for any block at which they are declared.
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, OnceLock};
+
+use blockifier::execution::contract_class::ContractClass;
+use lru::LruCache;
+use mc_db::DeoxysBackend;
+use prometheus_endpoint::{register, Counter, PrometheusError, Registry, U64};
+use starknet_api::core::ClassHash;
+
+/// Number of classes kept in the cache.
+pub const CLASS_CACHE_SIZE: usize = 256;
+
+static METRICS: OnceLock<ClassCacheMetrics> = OnceLock::new();
+
+/// Prometheus metrics of the class cache.
+struct ClassCacheMetrics {
+    hits: Counter<U64>,
+    misses: Counter<U64>,
+}
+
+/// Registers the class cache metrics in `registry`.
+pub fn register_metrics(registry: &Registry) -> Result<(), PrometheusError> {
+    let hits = register(
+        Counter::new("deoxys_class_cache_hits_total", "Number of classes served from the class cache")?,
+        registry,
+    )?;
+    let misses = register(
+        Counter::new("deoxys_class_cache_misses_total", "Number of classes loaded from the state")?,
+        registry,
+    )?;
+    let _ = METRICS.set(ClassCacheMetrics { hits, misses });
+    Ok(())
+}
+
+/// Classes recently loaded, by class hash.
+pub struct ClassCache(Mutex<LruCache<ClassHash, ContractClass>>);
+
+impl ClassCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self(Mutex::new(LruCache::new(capacity)))
+    }
+
+    /// Returns the class with the given hash as of block `block_number`, loading it with `load` if
+    /// it is not cached.
+    ///
+    /// Cached classes are only served if they are known to be declared at that block. Otherwise,
+    /// `load` decides whether the class exists at that block.
+    pub fn get_or_load(
+        &self,
+        class_hash: ClassHash,
+        block_number: u64,
+        load: impl FnOnce() -> Option<ContractClass>,
+    ) -> Option<ContractClass> {
+        if is_declared_at(&class_hash, block_number) {
+            if let Some(class) = self.get(&class_hash) {
+                if let Some(metrics) = METRICS.get() {
+                    metrics.hits.inc();
+                }
+                return Some(class);
+            }
+        }
+
+        if let Some(metrics) = METRICS.get() {
+            metrics.misses.inc();
+        }
+        let class = load()?;
+        self.0.lock().expect("Failed to acquire lock on class cache").put(class_hash, class.clone());
+        Some(class)
+    }
+
+    fn get(&self, class_hash: &ClassHash) -> Option<ContractClass> {
+        self.0.lock().expect("Failed to acquire lock on class cache").get(class_hash).cloned()
+    }
+}
+
+impl Default for ClassCache {
+    fn default() -> Self {
+        Self::new(NonZeroUsize::new(CLASS_CACHE_SIZE).expect("Class cache size should not be zero"))
+    }
+}
+
+/// Whether the class was declared at or before block `block_number`, as far as the class
+/// declarations db knows.
+fn is_declared_at(class_hash: &ClassHash, block_number: u64) -> bool {
+    match DeoxysBackend::class().declaration_block_number(class_hash) {
+        Ok(declared_at) => declared_at.is_some_and(|declared_at| declared_at <= block_number),
+        Err(e) => {
+            log::warn!("Failed to read the declaration block of class {}: {e}", class_hash.0);
+            false
+        }
+    }
+}
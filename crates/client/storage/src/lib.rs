@@ -5,12 +5,16 @@
 //! The `OverrideHandle` make it possible to use the later, more efficient way, while keeping the
 //! first one as a fallback.
 //! It can also support multiple versions of the pallet storage.
+//!
+//! The classes loaded through the handle are kept in a [`ClassCache`] shared by all its users.
 
+pub mod class_cache;
 pub mod overrides;
 
 use std::collections::BTreeMap;
 use std::sync::Arc;
 
+pub use class_cache::{register_metrics, ClassCache};
 use mp_storage::{StarknetStorageSchemaVersion, PALLET_STARKNET_SCHEMA};
 pub use overrides::*;
 use pallet_starknet_runtime_api::StarknetRuntimeApi;
@@ -39,6 +43,7 @@ where
     Arc::new(OverrideHandle {
         schemas: overrides_map,
         fallback: Box::new(RuntimeApiStorageOverride::<B, C>::new(client)),
+        class_cache: ClassCache::default(),
     })
 }
 
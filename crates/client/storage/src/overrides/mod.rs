@@ -16,7 +16,7 @@ use starknet_api::core::{ClassHash, ContractAddress, Nonce};
 mod schema_v1_override;
 
 pub use self::schema_v1_override::SchemaV1Override;
-use crate::onchain_storage_schema;
+use crate::{onchain_storage_schema, ClassCache};
 
 /// A handle containing multiple entities implementing `StorageOverride`
 pub struct OverrideHandle<B: BlockT> {
@@ -24,6 +24,8 @@ pub struct OverrideHandle<B: BlockT> {
     pub schemas: BTreeMap<StarknetStorageSchemaVersion, Box<dyn StorageOverride<B>>>,
     /// A non-failing way to retrieve the storage data
     pub fallback: Box<dyn StorageOverride<B>>,
+    /// Classes recently loaded through this handle
+    pub class_cache: ClassCache,
 }
 
 #[allow(clippy::borrowed_box)]
@@ -0,0 +1,162 @@
+//! Admission control of the historical blocks, so that a node catching up keeps serving queries.
+//!
+//! Syncing old blocks competes with the RPC for the CPU and the database. While the node is far
+//! behind the tip of the chain, the fetch of each block is delayed as long as the node is
+//! overloaded, that is when either:
+//!
+//! * the 99th percentile of the latency of the RPC requests served over the last [`LATENCY_WINDOW`]
+//!   exceeds [`AdmissionConfig::max_rpc_p99`],
+//! * the 1 minute load average per CPU exceeds [`AdmissionConfig::max_load`].
+//!
+//! The delay doubles while the node stays overloaded, and resets once it recovers. Blocks close to
+//! the tip are never delayed, so that a node which caught up keeps following the chain.
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use sysinfo::System;
+
+/// Period over which the RPC latencies are measured.
+pub const LATENCY_WINDOW: Duration = Duration::from_secs(30);
+/// Maximum number of RPC latencies kept, the oldest ones are dropped under heavy traffic.
+const MAX_LATENCY_SAMPLES: usize = 10_000;
+/// Minimum number of RPC requests over the window for the latency to be considered.
+const MIN_LATENCY_SAMPLES: usize = 20;
+/// Blocks closer than this to the tip of the chain are never delayed.
+const HISTORICAL_DISTANCE: u64 = 64;
+const MIN_DELAY: Duration = Duration::from_millis(250);
+const MAX_DELAY: Duration = Duration::from_secs(8);
+
+/// Thresholds above which the sync of historical blocks is slowed down. Disabled when unset.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AdmissionConfig {
+    /// Maximum 99th percentile of the RPC latency.
+    pub max_rpc_p99: Option<Duration>,
+    /// Maximum 1 minute load average, divided by the number of CPUs.
+    pub max_load: Option<f64>,
+}
+
+impl AdmissionConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.max_rpc_p99.is_some() || self.max_load.is_some()
+    }
+}
+
+lazy_static! {
+    static ref RPC_LATENCIES: Mutex<LatencyWindow> = Mutex::new(LatencyWindow::default());
+}
+
+/// Records the latency of an RPC request.
+pub fn record_rpc_latency(latency: Duration) {
+    RPC_LATENCIES.lock().expect("Failed to acquire lock on RPC_LATENCIES").record(Instant::now(), latency);
+}
+
+/// Latencies of the RPC requests served recently.
+#[derive(Default)]
+struct LatencyWindow {
+    samples: VecDeque<(Instant, Duration)>,
+}
+
+impl LatencyWindow {
+    fn record(&mut self, now: Instant, latency: Duration) {
+        if self.samples.len() == MAX_LATENCY_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now, latency));
+    }
+
+    /// 99th percentile of the latencies over the last [`LATENCY_WINDOW`], `None` if too few
+    /// requests were served to tell.
+    fn p99(&mut self, now: Instant) -> Option<Duration> {
+        while self.samples.front().is_some_and(|(time, _)| now.duration_since(*time) > LATENCY_WINDOW) {
+            self.samples.pop_front();
+        }
+        if self.samples.len() < MIN_LATENCY_SAMPLES {
+            return None;
+        }
+
+        let mut latencies: Vec<Duration> = self.samples.iter().map(|(_, latency)| *latency).collect();
+        let index = (latencies.len() * 99).div_ceil(100) - 1;
+        Some(*latencies.select_nth_unstable(index).1)
+    }
+}
+
+/// Delays the fetch of historical blocks while the node is overloaded.
+pub struct AdmissionController {
+    config: AdmissionConfig,
+    cpus: f64,
+    delay: Mutex<Duration>,
+}
+
+impl AdmissionController {
+    pub fn new(config: AdmissionConfig) -> Self {
+        let cpus = std::thread::available_parallelism().map_or(1, |cpus| cpus.get()) as f64;
+        Self { config, cpus, delay: Mutex::new(Duration::ZERO) }
+    }
+
+    /// Waits until block `block_n` can be fetched, `tip` being the latest block of the chain.
+    pub async fn admit(&self, block_n: u64, tip: u64) {
+        if !self.config.is_enabled() || block_n.saturating_add(HISTORICAL_DISTANCE) >= tip {
+            return;
+        }
+
+        while let Some(reason) = self.overloaded() {
+            let delay = {
+                let mut delay = self.delay.lock().expect("Failed to acquire lock on the admission delay");
+                if delay.is_zero() {
+                    log::info!("🐢 Slowing down the sync, {reason}");
+                }
+                *delay = (*delay * 2).clamp(MIN_DELAY, MAX_DELAY);
+                *delay
+            };
+            tokio::time::sleep(delay).await;
+        }
+
+        let mut delay = self.delay.lock().expect("Failed to acquire lock on the admission delay");
+        if !delay.is_zero() {
+            log::info!("🐇 Load is back to normal, resuming the sync at full speed");
+            *delay = Duration::ZERO;
+        }
+    }
+
+    /// Why the node is overloaded, if it is.
+    fn overloaded(&self) -> Option<String> {
+        if let Some(max_p99) = self.config.max_rpc_p99 {
+            let p99 = RPC_LATENCIES.lock().expect("Failed to acquire lock on RPC_LATENCIES").p99(Instant::now());
+            if let Some(p99) = p99.filter(|p99| *p99 > max_p99) {
+                return Some(format!("the RPC p99 latency is {} ms (max {} ms)", p99.as_millis(), max_p99.as_millis()));
+            }
+        }
+        if let Some(max_load) = self.config.max_load {
+            let load = System::load_average().one / self.cpus;
+            if load > max_load {
+                return Some(format!("the load average per CPU is {load:.2} (max {max_load:.2})"));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn p99_is_computed_over_the_recent_requests() {
+        let mut window = LatencyWindow::default();
+        let start = Instant::now();
+        for latency in 1..=100 {
+            window.record(start, Duration::from_millis(latency));
+        }
+        assert_eq!(window.p99(start), Some(Duration::from_millis(99)));
+
+        let later = start + LATENCY_WINDOW + Duration::from_secs(1);
+        for _ in 0..MIN_LATENCY_SAMPLES - 1 {
+            window.record(later, Duration::from_millis(1));
+        }
+        assert_eq!(window.p99(later), None);
+        window.record(later, Duration::from_millis(1));
+        assert_eq!(window.p99(later), Some(Duration::from_millis(1)));
+    }
+}
@@ -0,0 +1,288 @@
+//! Alerts sent to the node operator when the node runs into a critical condition.
+//!
+//! Alerts are delivered to a webhook, as a JSON `POST` with the alert kind and a `text` field
+//! (accepted as is by Slack compatible webhooks), and/or to a shell command, which receives the
+//! alert in the `DEOXYS_ALERT` and `DEOXYS_ALERT_MESSAGE` environment variables. They are set in a
+//! JSON file passed with `--alerts-config`, e.g.
+//!
+//! ```json
+//! {
+//!     "webhook": "https://hooks.slack.com/services/...",
+//!     "command": "/usr/local/bin/page-me",
+//!     "sync_stalled_after_minutes": 10,
+//!     "min_free_disk_mib": 10240
+//! }
+//! ```
+//!
+//! State root mismatches, L1 divergences, Starknet OS upgrades, unsupported Starknet versions,
+//! unexpected block timestamps, blocks failing their verification and state diffs not matching the
+//! data published on L1 are raised by the sync as they are detected, while stalled syncs and low
+//! disk space are checked periodically by [`run`].
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use reqwest::header::CONTENT_TYPE;
+use serde::Deserialize;
+use starknet_api::hash::StarkHash;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+
+use crate::disk_guard::available_space;
+use crate::l1::StarknetOsConfig;
+use crate::l2::{get_highest_block_hash_and_number, get_sync_progress};
+use crate::timestamps::TimestampAnomaly;
+
+/// Interval between two checks of the sync progress and disk space.
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Queue of the alerts raised by the sync, set once [`run`] is started.
+static ALERT_SENDER: OnceLock<mpsc::UnboundedSender<Alert>> = OnceLock::new();
+
+/// Where and when to send alerts.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertConfig {
+    /// URL alerts are posted to.
+    #[serde(default)]
+    pub webhook: Option<String>,
+    /// Shell command run for each alert.
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Minutes without a new block, while the chain is ahead of the node, before the sync is
+    /// reported as stalled.
+    #[serde(default = "default_sync_stalled_after_minutes")]
+    pub sync_stalled_after_minutes: u64,
+    /// Free space, in MiB, below which the disk holding the database is reported as low.
+    #[serde(default = "default_min_free_disk_mib")]
+    pub min_free_disk_mib: u64,
+    /// Minimum number of minutes between two alerts of the same kind.
+    #[serde(default = "default_repeat_after_minutes")]
+    pub repeat_after_minutes: u64,
+}
+
+fn default_sync_stalled_after_minutes() -> u64 {
+    10
+}
+
+fn default_min_free_disk_mib() -> u64 {
+    10 * 1024
+}
+
+fn default_repeat_after_minutes() -> u64 {
+    60
+}
+
+/// A critical condition the operator should be told about.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Alert {
+    /// The state root computed for a block differs from the one given by the feeder gateway.
+    StateRootMismatch { block_number: u64, computed: StarkHash, fetched: StarkHash },
+    /// No block was imported for a while although the chain is ahead of the node.
+    SyncStalled { block_number: Option<u64>, highest_block_number: u64, minutes: u64 },
+    /// The disk holding the database is running out of space.
+    DiskLow { path: PathBuf, available_mib: u64 },
+    /// A block verified on L1 is not the one the node synced at the same height.
+    L1Divergence { block_number: u64, l1_block_hash: StarkHash },
+    /// The Starknet OS registered in the core contract changed, the node may need an upgrade.
+    OsChanged { previous: StarknetOsConfig, current: StarknetOsConfig },
+    /// A block uses a Starknet version this build does not support, the sync is stopped.
+    UnsupportedStarknetVersion { block_number: u64, version: String },
+    /// A block is older than its parent, or too far from the local clock at the tip of the chain.
+    TimestampAnomaly { block_number: u64, anomaly: TimestampAnomaly },
+    /// An imported block does not match its header, its verification is stopped.
+    VerificationFailed { block_number: u64, reason: String },
+    /// The state diff published on L1 up to a block does not match the synced one.
+    DataAvailabilityMismatch { block_number: u64, reason: String },
+}
+
+impl Alert {
+    /// Identifies the condition, independently of its details.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Alert::StateRootMismatch { .. } => "state_root_mismatch",
+            Alert::SyncStalled { .. } => "sync_stalled",
+            Alert::DiskLow { .. } => "disk_low",
+            Alert::L1Divergence { .. } => "l1_divergence",
+            Alert::OsChanged { .. } => "os_changed",
+            Alert::UnsupportedStarknetVersion { .. } => "unsupported_starknet_version",
+            Alert::TimestampAnomaly { .. } => "timestamp_anomaly",
+            Alert::VerificationFailed { .. } => "verification_failed",
+            Alert::DataAvailabilityMismatch { .. } => "data_availability_mismatch",
+        }
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alert::StateRootMismatch { block_number, computed, fetched } => write!(
+                f,
+                "State root mismatch at block {block_number}: computed {computed}, but the feeder gateway returned \
+                 {fetched}"
+            ),
+            Alert::SyncStalled { block_number, highest_block_number, minutes } => {
+                let block_number = block_number.map_or("none".to_string(), |n| n.to_string());
+                write!(
+                    f,
+                    "Sync stalled for {minutes} minutes at block {block_number}, while the chain is at block \
+                     {highest_block_number}"
+                )
+            }
+            Alert::DiskLow { path, available_mib } => {
+                write!(f, "Low disk space: {available_mib} MiB left for the database at {}", path.display())
+            }
+            Alert::L1Divergence { block_number, l1_block_hash } => write!(
+                f,
+                "L1 divergence at block {block_number}: the block verified on L1 ({l1_block_hash}) is not the synced \
+                 one"
+            ),
+            Alert::OsChanged { previous, current } => write!(
+                f,
+                "Starknet OS upgraded on L1: program hash {} -> {}, config hash {} -> {}, check that the node \
+                 supports it",
+                previous.program_hash, current.program_hash, previous.config_hash, current.config_hash
+            ),
+            Alert::UnsupportedStarknetVersion { block_number, version } => write!(
+                f,
+                "Sync stopped at block {block_number}: Starknet version {version} is not supported, the node must be \
+                 upgraded"
+            ),
+            Alert::TimestampAnomaly { block_number, anomaly } => {
+                write!(f, "Unexpected timestamp at block {block_number}: {anomaly}")
+            }
+            Alert::VerificationFailed { block_number, reason } => {
+                write!(f, "Verification stopped at block {block_number}: {reason}, its data should not be trusted")
+            }
+            Alert::DataAvailabilityMismatch { block_number, reason } => {
+                write!(
+                    f,
+                    "The state diff published on L1 up to block {block_number} does not match the synced one: {reason}"
+                )
+            }
+        }
+    }
+}
+
+/// Sends an alert to the operator.
+///
+/// Does nothing if alerts are not configured. The alert is delivered in the background, so this
+/// can be called from any thread.
+pub fn raise(alert: Alert) {
+    if let Some(sender) = ALERT_SENDER.get() {
+        let _ = sender.send(alert);
+    }
+}
+
+/// Delivers the alerts raised by the sync, and checks for stalled syncs and low disk space on the
+/// disk holding `database_path`.
+pub async fn run(config: AlertConfig, database_path: PathBuf) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    if ALERT_SENDER.set(sender).is_err() {
+        log::error!("Alerts are already running");
+        return;
+    }
+
+    let client = reqwest::Client::new();
+    let repeat_after = Duration::from_secs(config.repeat_after_minutes * 60);
+    let stalled_after = Duration::from_secs(config.sync_stalled_after_minutes * 60);
+    let mut last_sent: HashMap<&'static str, Instant> = HashMap::new();
+
+    let mut last_block_number = get_sync_progress().map(|progress| progress.current_block.1);
+    let mut last_progress_at = Instant::now();
+
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        let alert = tokio::select! {
+            Some(alert) = receiver.recv() => alert,
+            _ = interval.tick() => {
+                let block_number = get_sync_progress().map(|progress| progress.current_block.1);
+                let (_, highest_block_number) = get_highest_block_hash_and_number();
+                if block_number != last_block_number {
+                    last_block_number = block_number;
+                    last_progress_at = Instant::now();
+                }
+
+                let stalled = last_progress_at.elapsed();
+                if stalled >= stalled_after && block_number.unwrap_or(0) < highest_block_number {
+                    raise(Alert::SyncStalled { block_number, highest_block_number, minutes: stalled.as_secs() / 60 });
+                }
+
+                match available_space(&database_path) {
+                    Some(available) if available / (1024 * 1024) < config.min_free_disk_mib => raise(Alert::DiskLow {
+                        path: database_path.clone(),
+                        available_mib: available / (1024 * 1024),
+                    }),
+                    Some(_) => {}
+                    None => log::debug!("Failed to read the free disk space at {}", database_path.display()),
+                }
+                continue;
+            }
+        };
+
+        if last_sent.get(alert.kind()).is_some_and(|sent_at| sent_at.elapsed() < repeat_after) {
+            log::debug!("Skipping repeated alert: {alert}");
+            continue;
+        }
+        last_sent.insert(alert.kind(), Instant::now());
+
+        log::warn!("🚨 Alert: {alert}");
+        deliver(&config, &client, &alert).await;
+    }
+}
+
+async fn deliver(config: &AlertConfig, client: &reqwest::Client, alert: &Alert) {
+    if let Some(webhook) = &config.webhook {
+        let body = serde_json::json!({ "alert": alert.kind(), "text": alert.to_string() });
+        let response = client
+            .post(webhook)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = response {
+            log::error!("Failed to send the alert to the webhook: {e}");
+        }
+    }
+
+    if let Some(command) = &config.command {
+        let status = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("DEOXYS_ALERT", alert.kind())
+            .env("DEOXYS_ALERT_MESSAGE", alert.to_string())
+            .status()
+            .await;
+        match status {
+            Ok(status) if !status.success() => log::error!("The alert command exited with {status}"),
+            Ok(_) => {}
+            Err(e) => log::error!("Failed to run the alert command: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_defaults_are_applied() {
+        let config: AlertConfig = serde_json::from_str(r#"{ "command": "echo alert" }"#).unwrap();
+        assert_eq!(
+            config,
+            AlertConfig {
+                webhook: None,
+                command: Some("echo alert".to_string()),
+                sync_stalled_after_minutes: 10,
+                min_free_disk_mib: 10 * 1024,
+                repeat_after_minutes: 60,
+            }
+        );
+
+        assert!(serde_json::from_str::<AlertConfig>(r#"{ "webhooks": "https://example.com" }"#).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Portable archives of chain data, used to share data between nodes.
+//!
+//! An archive is a gzip stream holding a header, identifying the kind of data it holds and the
+//! version of the format, followed by SCALE encoded entries, each prefixed with its length as a
+//! little endian `u32`:
+//!
+//! ```text
+//! "DXARCHIV" | version: u32 | kind: u8 | (len: u32 | entry)*
+//! ```
+//!
+//! The version is bumped whenever the encoding of the entries changes, so that archives produced
+//! by another release of the node are rejected instead of being misread.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use parity_scale_codec::{Decode, Encode};
+
+const MAGIC: &[u8; 8] = b"DXARCHIV";
+/// Version of the archive format written by this node.
+///
+/// Version 2: block headers hold the receipt and state diff commitments of Starknet 0.13.2.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// The kind of data held by an archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// Compiled classes, see `export-classes`.
+    Classes = 1,
+    /// Blocks along with their state diff, classes and receipts, see `export-blocks`.
+    Blocks = 2,
+    /// Blocks along with the trie deltas of their commits, see the snapshots published with
+    /// `--snapshot-bucket`.
+    Snapshot = 3,
+}
+
+impl ArchiveKind {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(ArchiveKind::Classes),
+            2 => Some(ArchiveKind::Blocks),
+            3 => Some(ArchiveKind::Snapshot),
+            _ => None,
+        }
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Writes the entries of an archive.
+pub struct ArchiveWriter<W: Write> {
+    encoder: GzEncoder<W>,
+}
+
+impl ArchiveWriter<BufWriter<File>> {
+    /// Creates an archive at `path`, overwriting any existing file.
+    pub fn create(path: &Path, kind: ArchiveKind) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), kind)
+    }
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    pub fn new(writer: W, kind: ArchiveKind) -> io::Result<Self> {
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        encoder.write_all(MAGIC)?;
+        encoder.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        encoder.write_all(&[kind as u8])?;
+        Ok(Self { encoder })
+    }
+
+    pub fn write<T: Encode>(&mut self, entry: &T) -> io::Result<()> {
+        let entry = entry.encode();
+        let len = u32::try_from(entry.len()).map_err(|_| invalid_data("Archive entry too large".to_string()))?;
+        self.encoder.write_all(&len.to_le_bytes())?;
+        self.encoder.write_all(&entry)
+    }
+
+    /// Completes the archive. Archives which are not finished are truncated.
+    pub fn finish(self) -> io::Result<W> {
+        let mut writer = self.encoder.finish()?;
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+/// Reads the entries of an archive.
+pub struct ArchiveReader<R: Read> {
+    decoder: GzDecoder<R>,
+}
+
+impl ArchiveReader<BufReader<File>> {
+    /// Opens the archive at `path`, checking that it holds data of the expected kind.
+    pub fn open(path: &Path, kind: ArchiveKind) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?), kind)
+    }
+
+    /// Opens the archive at `path`, checking that it holds data of one of the expected kinds, and
+    /// returns it along with its kind.
+    pub fn open_any(path: &Path, kinds: &[ArchiveKind]) -> io::Result<(Self, ArchiveKind)> {
+        Self::new_any(BufReader::new(File::open(path)?), kinds)
+    }
+}
+
+impl<R: Read> ArchiveReader<R> {
+    pub fn new(reader: R, kind: ArchiveKind) -> io::Result<Self> {
+        Self::new_any(reader, &[kind]).map(|(archive, _)| archive)
+    }
+
+    pub fn new_any(reader: R, kinds: &[ArchiveKind]) -> io::Result<(Self, ArchiveKind)> {
+        let mut decoder = GzDecoder::new(reader);
+
+        let mut header = [0u8; MAGIC.len() + 5];
+        decoder.read_exact(&mut header)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(invalid_data("Not a Deoxys archive".to_string()));
+        }
+        let version = u32::from_le_bytes(header[MAGIC.len()..MAGIC.len() + 4].try_into().expect("4 bytes"));
+        if version != FORMAT_VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported archive format version {version}, this node reads version {FORMAT_VERSION}"
+            )));
+        }
+        match ArchiveKind::from_byte(header[MAGIC.len() + 4]) {
+            Some(archive_kind) if kinds.contains(&archive_kind) => Ok((Self { decoder }, archive_kind)),
+            archive_kind => Err(invalid_data(format!("Expected an archive of {kinds:?}, got {archive_kind:?}"))),
+        }
+    }
+
+    /// Returns the next entry, or `None` at the end of the archive.
+    pub fn next<T: Decode>(&mut self) -> io::Result<Option<T>> {
+        let mut len = [0u8; 4];
+        match self.decoder.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let mut entry = vec![0u8; u32::from_le_bytes(len) as usize];
+        self.decoder.read_exact(&mut entry)?;
+        T::decode(&mut &entry[..]).map(Some).map_err(|e| invalid_data(format!("Invalid archive entry: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_roundtrip_and_kind_is_checked() {
+        let mut writer = ArchiveWriter::new(Vec::new(), ArchiveKind::Blocks).unwrap();
+        writer.write(&(1u64, vec![1u8, 2, 3])).unwrap();
+        writer.write(&(2u64, Vec::<u8>::new())).unwrap();
+        let archive = writer.finish().unwrap();
+
+        let mut reader = ArchiveReader::new(&archive[..], ArchiveKind::Blocks).unwrap();
+        assert_eq!(reader.next::<(u64, Vec<u8>)>().unwrap(), Some((1, vec![1, 2, 3])));
+        assert_eq!(reader.next::<(u64, Vec<u8>)>().unwrap(), Some((2, vec![])));
+        assert_eq!(reader.next::<(u64, Vec<u8>)>().unwrap(), None);
+
+        let error = ArchiveReader::new(&archive[..], ArchiveKind::Classes).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        let (_, kind) = ArchiveReader::new_any(&archive[..], &[ArchiveKind::Snapshot, ArchiveKind::Blocks]).unwrap();
+        assert_eq!(kind, ArchiveKind::Blocks);
+    }
+}
@@ -0,0 +1,759 @@
+//! Light verification of the synced state diffs against the data published on L1.
+//!
+//! Since Starknet 0.13.1, the state diffs proven on L1 are published in EIP-4844 blobs, sent along
+//! the transactions updating the state of the core contract. When an L1 beacon node is set with
+//! `--l1-beacon-url`, [`run`] follows the state updates of the core contract, downloads their blobs
+//! from the beacon node and checks the state diffs they hold against the state synced from the
+//! feeder gateway, so that the state does not rest on the trust of the gateway alone. The blobs are
+//! checked against the KZG commitments of the L1 transactions, so the beacon node is not trusted
+//! either.
+//!
+//! A blob holds 4096 elements of the BLS12-381 scalar field: the evaluations, on the roots of
+//! unity in bit reversed order, of the polynomial whose coefficients are the felts of the state
+//! diff, which are recovered with an inverse FFT, see [`blob_felts`]. The state diff of a state
+//! update covers all the blocks since the previous one and only holds the final values: each of
+//! them is checked against the synced state at the block of the state update, and each change of
+//! the synced state diffs of these blocks must be part of it, see [`check_state_diff`].
+//!
+//! Only the uncompressed encoding of Starknet 0.13.1 and 0.13.2 is decoded: the state diffs which
+//! can't be decoded are skipped with a warning, while the ones which do not match the synced state
+//! are reported with an alert.
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use mc_db::{DbError, DeoxysBackend};
+use primitive_types::{H256, U256, U512};
+use reqwest::Url;
+use serde_json::Value;
+use starknet_api::core::{ClassHash, CompiledClassHash, ContractAddress, Nonce, PatriciaKey};
+use starknet_api::hash::StarkFelt;
+use starknet_api::state::StorageKey;
+use thiserror::Error;
+
+use crate::alerts::{self, Alert};
+use crate::l1::EthereumClient;
+use crate::l2::get_sync_progress;
+
+/// Interval between two polls of the L1 node for new state updates.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// Number of L1 blocks searched for state updates on startup, about a day.
+const LOOKBACK: u64 = 6000;
+/// Duration of a beacon chain slot, in seconds.
+const SECONDS_PER_SLOT: u64 = 12;
+/// Number of field elements in a blob.
+const BLOB_ELEMENTS: usize = 4096;
+/// Version byte of the versioned hashes of KZG commitments.
+const VERSIONED_HASH_VERSION_KZG: u8 = 1;
+
+/// Modulus of the BLS12-381 scalar field, which the blob elements belong to.
+const BLS_MODULUS: U256 = U256([0xffffffff00000001, 0x53bda402fffe5bfe, 0x3339d80809a1d805, 0x73eda753299d7d48]);
+/// Generator of the multiplicative group of the scalar field, the roots of unity being its powers.
+const PRIMITIVE_ROOT: u64 = 7;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BlobError {
+    #[error("blob of {0} bytes, expected {}", BLOB_ELEMENTS * 32)]
+    InvalidLength(usize),
+    #[error("blob element out of the scalar field")]
+    InvalidElement,
+    #[error("state diff value out of the Starknet field")]
+    InvalidFelt,
+    #[error("truncated state diff")]
+    Truncated,
+    #[error("invalid contract header {0:#x}")]
+    InvalidContractHeader(U256),
+    #[error("state diff followed by non zero data")]
+    TrailingData,
+}
+
+/// A change of the synced state which does not match the state diff published on L1.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DaMismatch {
+    #[error("storage slot {key} of contract {contract} is {published} on L1, but {synced} was synced")]
+    Storage { contract: StarkFelt, key: StarkFelt, published: StarkFelt, synced: StarkFelt },
+    #[error("nonce of contract {contract} is {published} on L1, but {synced} was synced")]
+    Nonce { contract: StarkFelt, published: StarkFelt, synced: StarkFelt },
+    #[error("class of contract {contract} is {published} on L1, but {synced} was synced")]
+    ClassHash { contract: StarkFelt, published: StarkFelt, synced: StarkFelt },
+    #[error("contract {0} was changed, but is not part of the state diff published on L1")]
+    MissingContract(StarkFelt),
+    #[error(
+        "storage slot {key} of contract {contract} was changed, but is not part of the state diff published on L1"
+    )]
+    MissingStorage { contract: StarkFelt, key: StarkFelt },
+    #[error("class of contract {0} was changed, but is not part of the state diff published on L1")]
+    MissingClassHash(StarkFelt),
+}
+
+#[derive(Error, Debug)]
+pub enum CheckError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error("the flat storage or the state diff of block {0} is not available")]
+    Unavailable(u64),
+}
+
+/// The changes made to a contract, as published on L1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractUpdate {
+    pub address: ContractAddress,
+    /// The nonce of the contract after the changes, whether it changed or not.
+    pub nonce: Nonce,
+    /// The new class of the contract, if it was deployed or its class replaced.
+    pub class_hash: Option<ClassHash>,
+    pub storage: Vec<(StorageKey, StarkFelt)>,
+}
+
+/// A state diff, as published on L1.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlobStateDiff {
+    pub contracts: Vec<ContractUpdate>,
+    pub declared_classes: Vec<(ClassHash, CompiledClassHash)>,
+}
+
+/// Follows the state updates of the core contract through the L1 node at `l1_url`, checking the
+/// state diffs published in their blobs, downloaded from the beacon node at `beacon_url`.
+pub async fn run(l1_url: Url, beacon_url: Url) {
+    let client = match EthereumClient::new(l1_url).await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to start the L1 data availability checks: {e}");
+            return;
+        }
+    };
+    let beacon = match BeaconClient::new(beacon_url).await {
+        Ok(beacon) => beacon,
+        Err(e) => {
+            log::error!("Failed to start the L1 data availability checks, the beacon node can't be reached: {e}");
+            return;
+        }
+    };
+    log::info!("🔭 Checking the synced state diffs against the blobs published on L1");
+
+    // block of the previous state update, the next state diff covering the blocks after it
+    let mut previous: Option<u64> = None;
+    let mut next_l1_block = None;
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+
+        let latest = match client.get_latest_block_number().await {
+            Ok(latest) => latest.as_u64(),
+            Err(e) => {
+                log::warn!("Failed to read the latest L1 block: {e}");
+                continue;
+            }
+        };
+        let from = next_l1_block.unwrap_or(latest.saturating_sub(LOOKBACK));
+        let state_updates = match client.get_state_update_logs(from, latest).await {
+            Ok(state_updates) => state_updates,
+            Err(e) => {
+                log::warn!("Failed to read the state updates of L1 blocks {from} to {latest}: {e}");
+                continue;
+            }
+        };
+
+        let mut resume_from = latest + 1;
+        for (l1_block, transaction_hash, state_update) in state_updates {
+            let block_n = state_update.block_number;
+            let from_block = match previous {
+                Some(previous) if block_n <= previous => continue,
+                Some(previous) => previous,
+                None => {
+                    previous = Some(block_n);
+                    continue;
+                }
+            };
+            // checked again once the node synced the blocks of the state update
+            if get_sync_progress().map_or(true, |progress| progress.current_block.1 < block_n) {
+                resume_from = l1_block;
+                break;
+            }
+
+            check_state_update(&client, &beacon, l1_block, transaction_hash, from_block, block_n).await;
+            previous = Some(block_n);
+        }
+        next_l1_block = Some(resume_from);
+    }
+}
+
+/// Checks the state diff published by the state update of block `to`, sent in transaction
+/// `transaction_hash` of L1 block `l1_block`, against the changes made by the blocks after `from`.
+async fn check_state_update(
+    client: &EthereumClient,
+    beacon: &BeaconClient,
+    l1_block: u64,
+    transaction_hash: H256,
+    from: u64,
+    to: u64,
+) {
+    let blobs = match download_blobs(client, beacon, l1_block, transaction_hash).await {
+        Ok(blobs) => blobs,
+        Err(e) => {
+            log::warn!("Failed to download the blobs of the state update of block {to}: {e}");
+            return;
+        }
+    };
+    if blobs.is_empty() {
+        log::debug!("The state diff of block {to} was published as calldata, it is not checked");
+        return;
+    }
+    let state_diff = match decode_blobs(&blobs) {
+        Ok(state_diff) => state_diff,
+        Err(e) => {
+            log::warn!("🔭 Skipping the state diff of block {to} published on L1, which can't be decoded: {e}");
+            return;
+        }
+    };
+
+    let checked = tokio::task::spawn_blocking(move || {
+        synced_state_diff(&state_diff, from, to).map(|synced| check_state_diff(&state_diff, &synced))
+    });
+    match checked.await.expect("join error") {
+        Ok(None) => {
+            log::info!("🔭 Checked the state diff of blocks {} to {to} against the blobs published on L1", from + 1)
+        }
+        Ok(Some(mismatch)) => {
+            log::error!(
+                "🚨 The state diff of blocks {} to {to} does not match the one published on L1: {mismatch}",
+                from + 1
+            );
+            alerts::raise(Alert::DataAvailabilityMismatch { block_number: to, reason: mismatch.to_string() });
+        }
+        Err(e) => log::warn!("Failed to check the state diff of block {to} against the one published on L1: {e}"),
+    }
+}
+
+/// Downloads the blobs sent along an L1 transaction, in order.
+async fn download_blobs(
+    client: &EthereumClient,
+    beacon: &BeaconClient,
+    l1_block: u64,
+    transaction_hash: H256,
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    let versioned_hashes = client.get_blob_versioned_hashes(transaction_hash).await?;
+    if versioned_hashes.is_empty() {
+        return Ok(Vec::new());
+    }
+    let timestamp = client.get_block_timestamp(l1_block).await?;
+    beacon.blobs(timestamp, &versioned_hashes).await
+}
+
+/// Client of the beacon node the blobs are downloaded from.
+struct BeaconClient {
+    http: reqwest::Client,
+    url: String,
+    genesis_time: u64,
+}
+
+impl BeaconClient {
+    async fn new(url: Url) -> anyhow::Result<Self> {
+        let http = reqwest::Client::new();
+        let url = url.as_str().trim_end_matches('/').to_string();
+        let genesis: Value =
+            http.get(format!("{url}/eth/v1/beacon/genesis")).send().await?.error_for_status()?.json().await?;
+        let genesis_time = genesis
+            .pointer("/data/genesis_time")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow::anyhow!("no genesis time"))?
+            .parse()?;
+        Ok(Self { http, url, genesis_time })
+    }
+
+    /// Downloads the blobs with the given versioned hashes, from the L1 block with the given
+    /// timestamp.
+    async fn blobs(&self, timestamp: u64, versioned_hashes: &[H256]) -> anyhow::Result<Vec<Vec<u8>>> {
+        let slot = timestamp.saturating_sub(self.genesis_time) / SECONDS_PER_SLOT;
+        let sidecars: Value = self
+            .http
+            .get(format!("{}/eth/v1/beacon/blob_sidecars/{slot}", self.url))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let mut blobs = HashMap::new();
+        for sidecar in sidecars.get("data").and_then(Value::as_array).into_iter().flatten() {
+            let field = |name: &str| {
+                let value = sidecar.get(name).and_then(Value::as_str).ok_or_else(|| anyhow::anyhow!("no {name}"))?;
+                Ok::<_, anyhow::Error>(hex::decode(value.trim_start_matches("0x"))?)
+            };
+            let (blob, commitment) = (field("blob")?, field("kzg_commitment")?);
+            verify_blob(&blob, &commitment, &field("kzg_proof")?)?;
+            blobs.insert(versioned_hash(&commitment), blob);
+        }
+        versioned_hashes
+            .iter()
+            .map(|hash| blobs.remove(hash).ok_or_else(|| anyhow::anyhow!("blob {hash:#x} not found at slot {slot}")))
+            .collect()
+    }
+}
+
+/// Checks that `blob` is the one committed to by `commitment`, as proven by `proof`, so that the
+/// beacon node can't serve other blobs than the ones sent to L1.
+fn verify_blob(blob: &[u8], commitment: &[u8], proof: &[u8]) -> anyhow::Result<()> {
+    let kzg_error = |e: c_kzg::Error| anyhow::anyhow!("invalid blob sidecar: {e:?}");
+    let blob = c_kzg::Blob::from_bytes(blob).map_err(kzg_error)?;
+    let commitment = c_kzg::Bytes48::from_bytes(commitment).map_err(kzg_error)?;
+    let proof = c_kzg::Bytes48::from_bytes(proof).map_err(kzg_error)?;
+    let settings = c_kzg::ethereum_kzg_settings();
+    if !c_kzg::KzgProof::verify_blob_kzg_proof(&blob, &commitment, &proof, settings).map_err(kzg_error)? {
+        anyhow::bail!("blob does not match its KZG commitment");
+    }
+    Ok(())
+}
+
+/// The versioned hash identifying a blob by its KZG commitment.
+fn versioned_hash(commitment: &[u8]) -> H256 {
+    let mut hash = sp_core::hashing::sha2_256(commitment);
+    hash[0] = VERSIONED_HASH_VERSION_KZG;
+    H256(hash)
+}
+
+/// Decodes the state diff held by the blobs of a state update.
+pub fn decode_blobs(blobs: &[Vec<u8>]) -> Result<BlobStateDiff, BlobError> {
+    let mut felts = Vec::with_capacity(blobs.len() * BLOB_ELEMENTS);
+    for blob in blobs {
+        felts.extend(blob_felts(blob)?);
+    }
+    decode_state_diff(&felts)
+}
+
+/// Recovers the felts held by a blob, the coefficients of the polynomial it holds the evaluations
+/// of.
+pub fn blob_felts(blob: &[u8]) -> Result<Vec<StarkFelt>, BlobError> {
+    if blob.len() != BLOB_ELEMENTS * 32 {
+        return Err(BlobError::InvalidLength(blob.len()));
+    }
+    let evaluations = blob
+        .chunks(32)
+        .map(|element| {
+            let element = U256::from_big_endian(element);
+            if element < BLS_MODULUS { Ok(element) } else { Err(BlobError::InvalidElement) }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    interpolate(evaluations)
+        .into_iter()
+        .map(|coefficient| {
+            let mut bytes = [0; 32];
+            coefficient.to_big_endian(&mut bytes);
+            StarkFelt::new(bytes).map_err(|_| BlobError::InvalidFelt)
+        })
+        .collect()
+}
+
+/// Decodes a state diff from its uncompressed encoding, followed by zeros:
+///
+/// ```text
+/// n_contracts | (address | header | class_hash? | (key | value){n_updates})*
+/// | n_classes | (class_hash | compiled_class_hash)*
+/// ```
+///
+/// where the header packs `n_updates + nonce * 2^64 + class_flag * 2^128`, the class hash being
+/// only present when the class flag is set.
+pub fn decode_state_diff(felts: &[StarkFelt]) -> Result<BlobStateDiff, BlobError> {
+    let mut felts = felts.iter().copied();
+
+    let mut state_diff = BlobStateDiff::default();
+    for _ in 0..next_count(&mut felts)? {
+        let address = ContractAddress(PatriciaKey(next_felt(&mut felts)?));
+        let header = U256::from_big_endian(next_felt(&mut felts)?.bytes());
+        let n_updates = header.low_u64();
+        let nonce = (header >> 64).low_u64();
+        let class_hash = match header >> 128 {
+            flag if flag.is_zero() => None,
+            flag if flag == U256::one() => Some(ClassHash(next_felt(&mut felts)?)),
+            _ => return Err(BlobError::InvalidContractHeader(header)),
+        };
+
+        let mut storage = Vec::new();
+        for _ in 0..n_updates {
+            let key = StorageKey(PatriciaKey(next_felt(&mut felts)?));
+            storage.push((key, next_felt(&mut felts)?));
+        }
+        state_diff.contracts.push(ContractUpdate {
+            address,
+            nonce: Nonce(StarkFelt::from(nonce)),
+            class_hash,
+            storage,
+        });
+    }
+    for _ in 0..next_count(&mut felts)? {
+        let class_hash = ClassHash(next_felt(&mut felts)?);
+        state_diff.declared_classes.push((class_hash, CompiledClassHash(next_felt(&mut felts)?)));
+    }
+
+    if felts.any(|felt| felt != StarkFelt::ZERO) {
+        return Err(BlobError::TrailingData);
+    }
+    Ok(state_diff)
+}
+
+fn next_felt(felts: &mut impl Iterator<Item = StarkFelt>) -> Result<StarkFelt, BlobError> {
+    felts.next().ok_or(BlobError::Truncated)
+}
+
+fn next_count(felts: &mut impl Iterator<Item = StarkFelt>) -> Result<usize, BlobError> {
+    let count = U256::from_big_endian(next_felt(felts)?.bytes());
+    // a count can't exceed the number of felts of the blobs, so that a corrupted one fails fast
+    if count > U256::from(BLOB_ELEMENTS * 64) { Err(BlobError::Truncated) } else { Ok(count.as_usize()) }
+}
+
+/// The changes made by the synced blocks covered by a state update, read by [`synced_state_diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncedStateDiff {
+    pub contracts: HashMap<ContractAddress, SyncedContract>,
+}
+
+/// A contract changed by the synced blocks of a state update, or published in its state diff.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncedContract {
+    /// The nonce of the contract right after the last block.
+    pub nonce: Nonce,
+    /// The class of the contract right after the last block, zero if it is not deployed.
+    pub class_hash: ClassHash,
+    /// The values right after the last block of the storage slots updated by the blocks or
+    /// published on L1.
+    pub storage: HashMap<StorageKey, StarkFelt>,
+    /// The storage slots whose value was changed by the blocks.
+    pub changed_storage: HashSet<StorageKey>,
+    pub nonce_changed: bool,
+    pub class_changed: bool,
+}
+
+impl SyncedContract {
+    fn is_changed(&self) -> bool {
+        !self.changed_storage.is_empty() || self.nonce_changed || self.class_changed
+    }
+}
+
+/// Reads the changes made by the synced blocks after `from`, up to `to`, from their state diffs,
+/// along with the synced values of the contracts and storage slots published in `published`.
+///
+/// Changes which cancel out over the blocks are not reported as changes.
+pub fn synced_state_diff(published: &BlobStateDiff, from: u64, to: u64) -> Result<SyncedStateDiff, CheckError> {
+    let storage = DeoxysBackend::contract_storage();
+    if !storage.is_available(to)? {
+        return Err(CheckError::Unavailable(to));
+    }
+
+    let mut contracts: HashMap<ContractAddress, SyncedContract> = HashMap::new();
+    for block_n in from + 1..=to {
+        for address in storage.modified_contracts(block_n)?.ok_or(CheckError::Unavailable(block_n))? {
+            let updates = storage.storage_updates(&address, block_n)?.ok_or(CheckError::Unavailable(block_n))?;
+            contracts.entry(address).or_default().storage.extend(updates);
+        }
+    }
+    for (address, contract) in &mut contracts {
+        for (key, value) in &contract.storage {
+            if storage.get_at(address, key, from)?.unwrap_or_default() != *value {
+                contract.changed_storage.insert(*key);
+            }
+        }
+    }
+
+    for update in &published.contracts {
+        let contract = contracts.entry(update.address).or_default();
+        for (key, _) in &update.storage {
+            if !contract.storage.contains_key(key) {
+                contract.storage.insert(*key, storage.get_at(&update.address, key, to)?.unwrap_or_default());
+            }
+        }
+    }
+    for (address, contract) in &mut contracts {
+        let nonce = |block_n| storage.nonce_at(address, block_n).map(Option::unwrap_or_default);
+        let class_hash = |block_n| storage.class_hash_at(address, block_n).map(Option::unwrap_or_default);
+        contract.nonce = nonce(to)?;
+        contract.nonce_changed = contract.nonce != nonce(from)?;
+        contract.class_hash = class_hash(to)?;
+        contract.class_changed = contract.class_hash != class_hash(from)?;
+    }
+    Ok(SyncedStateDiff { contracts })
+}
+
+/// Checks a state diff published on L1 against the changes made by the synced blocks it covers,
+/// returning the first mismatch.
+///
+/// The published values must be the synced ones right after the last block, and the contracts
+/// changed by the blocks must be part of the state diff along with their changed storage slots and
+/// class.
+pub fn check_state_diff(published: &BlobStateDiff, synced: &SyncedStateDiff) -> Option<DaMismatch> {
+    let unchanged = SyncedContract::default();
+    for update in &published.contracts {
+        let contract = *update.address.0.key();
+        let synced = synced.contracts.get(&update.address).unwrap_or(&unchanged);
+        for (key, published) in &update.storage {
+            let synced = synced.storage.get(key).copied().unwrap_or_default();
+            if synced != *published {
+                return Some(DaMismatch::Storage { contract, key: *key.0.key(), published: *published, synced });
+            }
+        }
+        if synced.nonce != update.nonce {
+            return Some(DaMismatch::Nonce { contract, published: update.nonce.0, synced: synced.nonce.0 });
+        }
+        if let Some(published) = update.class_hash {
+            if synced.class_hash != published {
+                return Some(DaMismatch::ClassHash { contract, published: published.0, synced: synced.class_hash.0 });
+            }
+        }
+    }
+
+    let published: HashMap<ContractAddress, &ContractUpdate> =
+        published.contracts.iter().map(|update| (update.address, update)).collect();
+    for (address, synced) in synced.contracts.iter().filter(|(_, synced)| synced.is_changed()) {
+        let contract = *address.0.key();
+        let Some(update) = published.get(address) else {
+            return Some(DaMismatch::MissingContract(contract));
+        };
+
+        let published_keys: HashSet<StorageKey> = update.storage.iter().map(|(key, _)| *key).collect();
+        if let Some(key) = synced.changed_storage.iter().find(|key| !published_keys.contains(key)) {
+            return Some(DaMismatch::MissingStorage { contract, key: *key.0.key() });
+        }
+        if synced.class_changed && update.class_hash.is_none() {
+            return Some(DaMismatch::MissingClassHash(contract));
+        }
+    }
+    None
+}
+
+fn add(a: U256, b: U256) -> U256 {
+    // both are below the modulus, which is below 2^255, so the sum does not overflow
+    let sum = a + b;
+    if sum >= BLS_MODULUS { sum - BLS_MODULUS } else { sum }
+}
+
+fn sub(a: U256, b: U256) -> U256 {
+    if a >= b { a - b } else { BLS_MODULUS - b + a }
+}
+
+fn mul(a: U256, b: U256) -> U256 {
+    let product = a.full_mul(b) % U512::from(BLS_MODULUS);
+    U256::try_from(product).expect("reduced modulo a 256 bits modulus")
+}
+
+fn pow(mut base: U256, mut exponent: U256) -> U256 {
+    let mut result = U256::one();
+    while !exponent.is_zero() {
+        if exponent.bit(0) {
+            result = mul(result, base);
+        }
+        base = mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// A primitive `n`-th root of unity of the scalar field, `n` being a power of two.
+fn root_of_unity(n: usize) -> U256 {
+    pow(U256::from(PRIMITIVE_ROOT), (BLS_MODULUS - 1) / n)
+}
+
+/// Recovers the coefficients of a polynomial from its evaluations on the `n`-th roots of unity in
+/// bit reversed order, `n` being the number of evaluations.
+///
+/// The inverse FFT takes its input in bit reversed order to produce its output in natural order,
+/// so the evaluations are transformed in place.
+fn interpolate(mut values: Vec<U256>) -> Vec<U256> {
+    let n = values.len();
+    let inverse_root = pow(root_of_unity(n), BLS_MODULUS - 2);
+
+    let mut len = 2;
+    while len <= n {
+        let step = pow(inverse_root, U256::from(n / len));
+        for start in (0..n).step_by(len) {
+            let mut twiddle = U256::one();
+            for i in start..start + len / 2 {
+                let (u, v) = (values[i], mul(values[i + len / 2], twiddle));
+                values[i] = add(u, v);
+                values[i + len / 2] = sub(u, v);
+                twiddle = mul(twiddle, step);
+            }
+        }
+        len *= 2;
+    }
+
+    let inverse_n = pow(U256::from(n), BLS_MODULUS - 2);
+    values.into_iter().map(|value| mul(value, inverse_n)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn felt(value: u64) -> StarkFelt {
+        StarkFelt::from(value)
+    }
+
+    /// Evaluates the polynomial with the given coefficients on the roots of unity, in bit reversed
+    /// order, as a blob does.
+    fn evaluations(coefficients: &[U256]) -> Vec<U256> {
+        let n = coefficients.len();
+        let root = root_of_unity(n);
+        let bits = n.trailing_zeros();
+        (0..n)
+            .map(|i| {
+                let point = pow(root, U256::from(i.reverse_bits() >> (usize::BITS - bits)));
+                coefficients.iter().rev().fold(U256::zero(), |acc, coefficient| add(mul(acc, point), *coefficient))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn roots_of_unity_have_the_blob_order() {
+        let root = root_of_unity(BLOB_ELEMENTS);
+        assert_eq!(pow(root, U256::from(BLOB_ELEMENTS)), U256::one());
+        assert_ne!(pow(root, U256::from(BLOB_ELEMENTS / 2)), U256::one());
+    }
+
+    #[test]
+    fn interpolation_recovers_the_coefficients() {
+        let coefficients: Vec<U256> =
+            [3, 0, 0x1234, u64::MAX, 7, 0, 0, 0].into_iter().map(U256::from).chain([BLS_MODULUS - 1]).collect();
+        let coefficients = [coefficients, vec![U256::zero(); 7]].concat();
+        assert_eq!(interpolate(evaluations(&coefficients)), coefficients);
+    }
+
+    #[test]
+    fn blobs_hold_their_felts() {
+        let mut coefficients = vec![U256::zero(); BLOB_ELEMENTS];
+        coefficients[..4].copy_from_slice(&[1, 2, 3, 4].map(U256::from));
+        let blob: Vec<u8> = evaluations(&coefficients)
+            .into_iter()
+            .flat_map(|element| {
+                let mut bytes = [0; 32];
+                element.to_big_endian(&mut bytes);
+                bytes
+            })
+            .collect();
+
+        let felts = blob_felts(&blob).unwrap();
+        assert_eq!(felts[..5], [felt(1), felt(2), felt(3), felt(4), felt(0)]);
+        assert_eq!(blob_felts(&blob[..64]), Err(BlobError::InvalidLength(64)));
+    }
+
+    #[test]
+    fn state_diffs_are_decoded() {
+        let header = |n_updates: u64, nonce: u64, class_flag: u64| {
+            let header = U256::from(n_updates) + (U256::from(nonce) << 64) + (U256::from(class_flag) << 128);
+            let mut bytes = [0; 32];
+            header.to_big_endian(&mut bytes);
+            StarkFelt::new(bytes).unwrap()
+        };
+        let felts = [
+            felt(2),
+            // a deployed contract, with a storage update
+            felt(0x100),
+            header(1, 0, 1),
+            felt(0xc1),
+            felt(5),
+            felt(6),
+            // an account which sent transactions
+            felt(0x200),
+            header(0, 3, 0),
+            felt(1),
+            felt(0xc2),
+            felt(0xcc2),
+            felt(0),
+            felt(0),
+        ];
+
+        let address = |value| ContractAddress(PatriciaKey(felt(value)));
+        assert_eq!(
+            decode_state_diff(&felts).unwrap(),
+            BlobStateDiff {
+                contracts: vec![
+                    ContractUpdate {
+                        address: address(0x100),
+                        nonce: Nonce(felt(0)),
+                        class_hash: Some(ClassHash(felt(0xc1))),
+                        storage: vec![(StorageKey(PatriciaKey(felt(5))), felt(6))],
+                    },
+                    ContractUpdate {
+                        address: address(0x200),
+                        nonce: Nonce(felt(3)),
+                        class_hash: None,
+                        storage: vec![]
+                    },
+                ],
+                declared_classes: vec![(ClassHash(felt(0xc2)), CompiledClassHash(felt(0xcc2)))],
+            }
+        );
+
+        assert_eq!(decode_state_diff(&felts[..5]), Err(BlobError::Truncated));
+        assert_eq!(decode_state_diff(&[&felts[..], &[felt(1)]].concat()), Err(BlobError::TrailingData));
+        let invalid_header = [felt(1), felt(0x100), header(0, 0, 2)];
+        assert!(matches!(decode_state_diff(&invalid_header), Err(BlobError::InvalidContractHeader(_))));
+    }
+
+    #[test]
+    fn state_diffs_are_checked_against_the_synced_changes() {
+        let address = |value| ContractAddress(PatriciaKey(felt(value)));
+        let key = |value| StorageKey(PatriciaKey(felt(value)));
+        let published = BlobStateDiff {
+            contracts: vec![
+                ContractUpdate {
+                    address: address(0x100),
+                    nonce: Nonce(felt(0)),
+                    class_hash: Some(ClassHash(felt(0xc1))),
+                    storage: vec![(key(5), felt(6)), (key(7), felt(8))],
+                },
+                ContractUpdate { address: address(0x200), nonce: Nonce(felt(3)), class_hash: None, storage: vec![] },
+            ],
+            declared_classes: vec![],
+        };
+        let synced = SyncedStateDiff {
+            contracts: HashMap::from([
+                (
+                    address(0x100),
+                    SyncedContract {
+                        nonce: Nonce(felt(0)),
+                        class_hash: ClassHash(felt(0xc1)),
+                        // slot 7 was published without being changed by the blocks
+                        storage: HashMap::from([(key(5), felt(6)), (key(7), felt(8))]),
+                        changed_storage: HashSet::from([key(5)]),
+                        nonce_changed: false,
+                        class_changed: true,
+                    },
+                ),
+                (address(0x200), SyncedContract { nonce: Nonce(felt(3)), nonce_changed: true, ..Default::default() }),
+                // changes which cancel out do not need to be published
+                (address(0x300), SyncedContract { storage: HashMap::from([(key(1), felt(0))]), ..Default::default() }),
+            ]),
+        };
+        assert_eq!(check_state_diff(&published, &synced), None);
+
+        let mut wrong_value = synced.clone();
+        wrong_value.contracts.get_mut(&address(0x100)).unwrap().storage.insert(key(5), felt(9));
+        assert_eq!(
+            check_state_diff(&published, &wrong_value),
+            Some(DaMismatch::Storage { contract: felt(0x100), key: felt(5), published: felt(6), synced: felt(9) })
+        );
+
+        let mut wrong_nonce = synced.clone();
+        wrong_nonce.contracts.get_mut(&address(0x200)).unwrap().nonce = Nonce(felt(4));
+        assert_eq!(
+            check_state_diff(&published, &wrong_nonce),
+            Some(DaMismatch::Nonce { contract: felt(0x200), published: felt(3), synced: felt(4) })
+        );
+
+        let mut missing_contract = synced.clone();
+        missing_contract.contracts.get_mut(&address(0x300)).unwrap().nonce_changed = true;
+        assert_eq!(check_state_diff(&published, &missing_contract), Some(DaMismatch::MissingContract(felt(0x300))));
+
+        let mut missing_storage = synced.clone();
+        missing_storage.contracts.get_mut(&address(0x200)).unwrap().changed_storage.insert(key(1));
+        assert_eq!(
+            check_state_diff(&published, &missing_storage),
+            Some(DaMismatch::MissingStorage { contract: felt(0x200), key: felt(1) })
+        );
+
+        let mut missing_class = synced.clone();
+        missing_class.contracts.get_mut(&address(0x200)).unwrap().class_changed = true;
+        assert_eq!(check_state_diff(&published, &missing_class), Some(DaMismatch::MissingClassHash(felt(0x200))));
+    }
+}
@@ -0,0 +1,126 @@
+//! Trusted checkpoint up to which the commitments of the synced blocks are not verified.
+//!
+//! Computing the transaction and event commitments of every block, and committing its state diff
+//! to the tries, is most of the initial sync. Operators who trust a block hash, e.g. one published
+//! by a party they trust, can set it as a [`TrustedCheckpoint`]: the blocks before it take their
+//! commitments from the feeder gateway, and their state diffs are committed to the tries in
+//! batches rather than one block at a time. The blocks from the checkpoint onwards are fully
+//! verified.
+//!
+//! The trusted blocks are anchored to the checkpoint by their hashes, computed from their headers:
+//! each block must hash to the parent hash of the next one, and the block at the height of the
+//! checkpoint must hash to it, see [`TrustedCheckpoint::check`]. A gateway serving other headers
+//! makes the sync fail, at the latest when it reaches the checkpoint. The transactions and events
+//! of the trusted blocks are checked against their commitments by the verification of the node,
+//! in the background.
+use starknet_ff::FieldElement;
+use starknet_providers::sequencer::models::BlockId;
+
+use crate::errors::{ConversionError, SyncError};
+use crate::fetch::gateway::GatewayProvider;
+
+/// A block hash up to which the synced blocks are trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrustedCheckpoint {
+    pub block_number: u64,
+    pub block_hash: FieldElement,
+}
+
+/// The hashes of a synced block, checked by [`TrustedCheckpoint::check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHashes {
+    /// The hash computed from the header of the block.
+    pub computed: FieldElement,
+    /// The hash of the block according to the feeder gateway.
+    pub fetched: FieldElement,
+    /// The parent hash in the header of the block.
+    pub parent: FieldElement,
+}
+
+impl TrustedCheckpoint {
+    /// Looks up the number of the block with hash `block_hash` on the feeder gateway.
+    pub async fn resolve(provider: &GatewayProvider, block_hash: FieldElement) -> Result<Self, SyncError> {
+        let block = provider.get_block(BlockId::Hash(block_hash)).await?;
+        let block_number = block.block_number.ok_or(ConversionError::MissingField("block number"))?;
+
+        Ok(Self { block_number, block_hash })
+    }
+
+    /// Whether the commitments of block `block_n` are taken from the feeder gateway.
+    pub fn trusts(&self, block_n: u64) -> bool {
+        block_n < self.block_number
+    }
+
+    /// Checks that block `block_n`, with the given `hashes`, chains up to the checkpoint.
+    ///
+    /// The blocks up to the checkpoint must hash to the hash the gateway returned for them, and to
+    /// the hash of the checkpoint for the block at its height. Their parent hash must be
+    /// `parent_hash`, the hash computed for the previous block, when it is known. The blocks after
+    /// the checkpoint are not checked.
+    pub fn check(&self, block_n: u64, hashes: BlockHashes, parent_hash: Option<FieldElement>) -> Result<(), SyncError> {
+        if block_n > self.block_number {
+            return Ok(());
+        }
+        if hashes.computed != hashes.fetched {
+            return Err(SyncError::BlockMismatch { block_number: block_n, field: "block hash" });
+        }
+        if let Some(parent_hash) = parent_hash.filter(|parent_hash| *parent_hash != hashes.parent) {
+            return Err(SyncError::ParentHashMismatch {
+                block_number: block_n,
+                parent_block_hash: hashes.parent,
+                expected: parent_hash,
+            });
+        }
+        if block_n == self.block_number && hashes.computed != self.block_hash {
+            return Err(SyncError::CheckpointMismatch {
+                block_number: block_n,
+                expected: self.block_hash,
+                computed: hashes.computed,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(computed: u64, parent: u64) -> BlockHashes {
+        BlockHashes { computed: computed.into(), fetched: computed.into(), parent: parent.into() }
+    }
+
+    #[test]
+    fn blocks_before_the_checkpoint_are_trusted() {
+        let checkpoint = TrustedCheckpoint { block_number: 10, block_hash: FieldElement::from(0xabcu64) };
+
+        assert!(checkpoint.trusts(9));
+        assert!(!checkpoint.trusts(10));
+        assert!(!checkpoint.trusts(11));
+    }
+
+    #[test]
+    fn trusted_blocks_chain_up_to_the_checkpoint() {
+        let checkpoint = TrustedCheckpoint { block_number: 10, block_hash: FieldElement::from(0xabcu64) };
+
+        assert!(checkpoint.check(9, hashes(0x9, 0x8), Some(FieldElement::from(0x8u64))).is_ok());
+        assert!(checkpoint.check(9, hashes(0x9, 0x8), None).is_ok());
+        assert!(checkpoint.check(10, hashes(0xabc, 0x9), Some(FieldElement::from(0x9u64))).is_ok());
+
+        // the gateway returned another hash than the one of the header
+        let lying = BlockHashes { fetched: FieldElement::from(0xabcu64), ..hashes(0x1, 0x9) };
+        assert!(matches!(checkpoint.check(10, lying, None), Err(SyncError::BlockMismatch { block_number: 10, .. })));
+        // the block does not follow the previous one
+        assert!(matches!(
+            checkpoint.check(9, hashes(0x9, 0x7), Some(FieldElement::from(0x8u64))),
+            Err(SyncError::ParentHashMismatch { block_number: 9, .. })
+        ));
+        // the block at the height of the checkpoint is another block
+        assert!(matches!(
+            checkpoint.check(10, hashes(0x1, 0x9), Some(FieldElement::from(0x9u64))),
+            Err(SyncError::CheckpointMismatch { block_number: 10, .. })
+        ));
+        // the blocks after the checkpoint are verified by the node
+        assert!(checkpoint.check(11, BlockHashes { fetched: FieldElement::ONE, ..hashes(0x1, 0x2) }, None).is_ok());
+    }
+}
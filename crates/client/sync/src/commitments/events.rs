@@ -1,16 +1,19 @@
-use bitvec::vec::BitVec;
-use bonsai_trie::databases::HashMapDb;
-use bonsai_trie::id::{BasicId, BasicIdBuilder};
-use bonsai_trie::{BonsaiStorage, BonsaiStorageConfig};
+use std::ops::Range;
+
 use mc_db::storage::bonsai_identifier;
+use mp_block::OrderedEvents;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::pedersen::PedersenHasher;
+use mp_hashers::poseidon::PoseidonHasher;
 use mp_hashers::HasherT;
 use rayon::prelude::*;
 use starknet_api::transaction::Event;
 use starknet_ff::FieldElement;
-use starknet_types_core::felt::Felt;
-use starknet_types_core::hash::Pedersen;
+use starknet_types_core::hash::{Pedersen, Poseidon};
+
+use super::hashers::HasherKind;
+use super::proofs::{commitment_trie, inclusion_proofs, InclusionProof};
+use super::versions::has_v0_13_2_commitments;
 
 /// Calculate the hash of the event.
 ///
@@ -43,49 +46,192 @@ pub fn calculate_event_hash<H: HasherT>(event: &Event) -> FieldElement {
     H::compute_hash_on_elements(&[from_address, keys_hash, data_hash])
 }
 
+/// Calculate the hash of an event, as committed to from Starknet 0.13.2.
+///
+/// The event is hashed with Poseidon along with the hash of the transaction which emitted it, its
+/// keys and data being preceded by their length.
+///
+/// # Arguments
+///
+/// * `event` - The event we want to calculate the hash of.
+/// * `transaction_hash` - The hash of the transaction which emitted the event.
+///
+/// # Returns
+///
+/// The event hash as `FieldElement`.
+pub fn calculate_event_hash_v0_13_2(event: &Event, transaction_hash: FieldElement) -> FieldElement {
+    let keys = &event.content.keys;
+    let data = &event.content.data.0;
+
+    let mut elements = Vec::with_capacity(keys.len() + data.len() + 4);
+    elements.push(FieldElement::from(Felt252Wrapper::from(event.from_address.0.0)));
+    elements.push(transaction_hash);
+    elements.push(FieldElement::from(keys.len() as u64));
+    elements.extend(keys.iter().map(|key| FieldElement::from(Felt252Wrapper::from(key.0))));
+    elements.push(FieldElement::from(data.len() as u64));
+    elements.extend(data.iter().map(|data| FieldElement::from(Felt252Wrapper::from(*data))));
+    PoseidonHasher::compute_hash_on_elements(&elements)
+}
+
+/// Computes the leaves of the event commitment trie, and the hash function of the trie.
+///
+/// Before Starknet 0.13.2, the leaves are the hashes of [`calculate_event_hash`] and the trie is
+/// hashed with `hasher`. From Starknet 0.13.2, they are the hashes of
+/// [`calculate_event_hash_v0_13_2`], in a Poseidon trie.
+fn event_leaves(
+    events: &[OrderedEvents],
+    transaction_hashes: &[FieldElement],
+    protocol_version: &Felt252Wrapper,
+    hasher: HasherKind,
+) -> Result<(Vec<FieldElement>, HasherKind), String> {
+    if has_v0_13_2_commitments(protocol_version) {
+        let events = events
+            .iter()
+            .map(|ordered| {
+                let transaction_hash = transaction_hashes
+                    .get(ordered.index() as usize)
+                    .ok_or_else(|| format!("No transaction {} emitting events in the block", ordered.index()))?;
+                Ok(ordered.events().iter().map(move |event| (event, *transaction_hash)))
+            })
+            .collect::<Result<Vec<_>, String>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        let leaves = events
+            .par_iter()
+            .map(|(event, transaction_hash)| calculate_event_hash_v0_13_2(event, *transaction_hash))
+            .collect();
+        return Ok((leaves, HasherKind::Poseidon));
+    }
+
+    let events = events.iter().flat_map(|ordered| ordered.events()).collect::<Vec<_>>();
+    let leaves = match hasher {
+        HasherKind::Pedersen => events.par_iter().map(|event| calculate_event_hash::<PedersenHasher>(event)).collect(),
+        HasherKind::Poseidon => events.par_iter().map(|event| calculate_event_hash::<PoseidonHasher>(event)).collect(),
+    };
+    Ok((leaves, hasher))
+}
+
 /// Calculate the event commitment in memory using HashMapDb (which is more efficient for this
 /// usecase).
 ///
 /// # Arguments
 ///
-/// * `events` - The events of the block
+/// * `events` - The events of the block, grouped by the transaction which emitted them
+/// * `transaction_hashes` - The hashes of the transactions of the block
+/// * `protocol_version` - The Starknet version of the block, as stored in its header
+/// * `hasher` - The hash function of the event hashes and commitment trie before Starknet 0.13.2
 ///
 /// # Returns
 ///
 /// The event commitment as `Felt252Wrapper`.
-pub fn memory_event_commitment(events: &[Event]) -> Result<Felt252Wrapper, String> {
+pub fn memory_event_commitment(
+    events: &[OrderedEvents],
+    transaction_hashes: &[FieldElement],
+    protocol_version: &Felt252Wrapper,
+    hasher: HasherKind,
+) -> Result<Felt252Wrapper, String> {
     // TODO @cchudant refacto/optimise this function
-    if events.is_empty() {
+    if events.iter().all(|ordered| ordered.events().is_empty()) {
         return Ok(Felt252Wrapper::ZERO);
     }
 
-    let config = BonsaiStorageConfig::default();
-    let bonsai_db = HashMapDb::<BasicId>::default();
-    let mut bonsai_storage =
-        BonsaiStorage::<_, _, Pedersen>::new(bonsai_db, config).expect("Failed to create bonsai storage");
     let identifier = bonsai_identifier::EVENT;
-
     // event hashes are computed in parallel
-    let events = events.par_iter().map(calculate_event_hash::<PedersenHasher>).collect::<Vec<_>>();
-
-    // once event hashes have finished computing, they are inserted into the local Bonsai db
-    for (i, event_hash) in events.into_iter().enumerate() {
-        let key = BitVec::from_vec(i.to_be_bytes().to_vec());
-        let value = Felt::from(Felt252Wrapper::from(event_hash));
-        bonsai_storage.insert(identifier, key.as_bitslice(), &value).expect("Failed to insert into bonsai storage");
-    }
+    let (leaves, trie_hasher) = event_leaves(events, transaction_hashes, protocol_version, hasher)?;
 
     // Note that committing changes still has the greatest performance hit
     // as this is where the root hash is calculated. Due to the Merkle structure
     // of Bonsai Tries, this results in a trie size that grows very rapidly with
     // each new insertion. It seems that the only vector of optimization here
     // would be to optimize the tree traversal and hash computation.
-    let mut id_builder = BasicIdBuilder::new();
-    let id = id_builder.new_id();
-
-    // run in a blocking-safe thread to avoid starving the thread pool
-    bonsai_storage.commit(id).expect("Failed to commit to bonsai storage");
-    let root_hash = bonsai_storage.root_hash(identifier).expect("Failed to get root hash");
+    let root_hash = match trie_hasher {
+        HasherKind::Pedersen => commitment_trie::<Pedersen>(identifier, leaves).root_hash(identifier),
+        HasherKind::Poseidon => commitment_trie::<Poseidon>(identifier, leaves).root_hash(identifier),
+    }
+    .expect("Failed to get root hash");
 
     Ok(Felt252Wrapper::from(root_hash))
 }
+
+/// Proves the inclusion of the events at `indices` in the event commitment of their block.
+///
+/// # Arguments
+///
+/// * `events` - The events of the block, grouped by the transaction which emitted them
+/// * `transaction_hashes` - The hashes of the transactions of the block
+/// * `protocol_version` - The Starknet version of the block, as stored in its header
+/// * `hasher` - The hash function of the event hashes and commitment trie before Starknet 0.13.2
+/// * `indices` - The indices of the events in the block
+///
+/// # Returns
+///
+/// The proofs of the event hashes in the event commitment trie, in the order of `indices`.
+pub fn memory_event_proofs(
+    events: &[OrderedEvents],
+    transaction_hashes: &[FieldElement],
+    protocol_version: &Felt252Wrapper,
+    hasher: HasherKind,
+    indices: Range<usize>,
+) -> Result<Vec<InclusionProof>, String> {
+    if indices.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let identifier = bonsai_identifier::EVENT;
+    let (leaves, trie_hasher) = event_leaves(events, transaction_hashes, protocol_version, hasher)?;
+    match trie_hasher {
+        HasherKind::Pedersen => inclusion_proofs::<Pedersen>(identifier, leaves, indices),
+        HasherKind::Poseidon => inclusion_proofs::<Poseidon>(identifier, leaves, indices),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_api::core::{ContractAddress, PatriciaKey};
+    use starknet_api::hash::StarkFelt;
+    use starknet_api::transaction::{EventContent, EventData, EventKey};
+
+    use super::*;
+
+    fn event(keys: Vec<u128>, data: Vec<u128>) -> Event {
+        Event {
+            from_address: ContractAddress(PatriciaKey(StarkFelt::from(1u128))),
+            content: EventContent {
+                keys: keys.into_iter().map(|key| EventKey(StarkFelt::from(key))).collect(),
+                data: EventData(data.into_iter().map(StarkFelt::from).collect()),
+            },
+        }
+    }
+
+    #[test]
+    fn v0_13_2_leaves_hash_the_transaction_of_the_events() {
+        let events = vec![
+            OrderedEvents::new(0, vec![event(vec![2], vec![3, 4])]),
+            OrderedEvents::new(2, vec![event(vec![], vec![5]), event(vec![6, 7], vec![])]),
+        ];
+        let hashes: Vec<FieldElement> = (10u64..13).map(FieldElement::from).collect();
+        let felts = |values: &[u64]| values.iter().map(|&value| FieldElement::from(value)).collect::<Vec<_>>();
+        let expected = vec![
+            PoseidonHasher::compute_hash_on_elements(&felts(&[1, 10, 1, 2, 2, 3, 4])),
+            PoseidonHasher::compute_hash_on_elements(&felts(&[1, 12, 0, 1, 5])),
+            PoseidonHasher::compute_hash_on_elements(&felts(&[1, 12, 2, 6, 7, 0])),
+        ];
+
+        let version = Felt252Wrapper::try_from(&b"0.13.2"[..]).unwrap();
+        let leaves = event_leaves(&events, &hashes, &version, HasherKind::Pedersen).unwrap();
+        assert_eq!(leaves, (expected, HasherKind::Poseidon));
+
+        // events must be emitted by a transaction of the block
+        assert!(event_leaves(&events, &hashes[..2], &version, HasherKind::Pedersen).is_err());
+    }
+
+    #[test]
+    fn legacy_leaves_ignore_the_transactions() {
+        let events = vec![OrderedEvents::new(3, vec![event(vec![2], vec![3])])];
+        let version = Felt252Wrapper::try_from(&b"0.13.1"[..]).unwrap();
+        let leaves = event_leaves(&events, &[], &version, HasherKind::Pedersen).unwrap();
+        let expected = vec![calculate_event_hash::<PedersenHasher>(&events[0].events()[0])];
+        assert_eq!(leaves, (expected, HasherKind::Pedersen));
+    }
+}
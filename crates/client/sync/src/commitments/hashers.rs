@@ -0,0 +1,80 @@
+//! Selection of the hash functions used by the block commitments.
+//!
+//! Starknet hashes transactions and events with Pedersen, and combines the trie roots into the
+//! state commitment with Poseidon. Appchains may use other hash functions: these are set in the
+//! `commitmentHashers` property of the chain spec, e.g.
+//!
+//! ```json
+//! "properties": {
+//!     "commitmentHashers": { "transaction": "poseidon", "state": "poseidon" }
+//! }
+//! ```
+//!
+//! The contract and class tries stored in the database keep using the Starknet hash functions.
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// Name of the chain spec property holding the [`CommitmentHashers`].
+pub const CHAIN_SPEC_PROPERTY: &str = "commitmentHashers";
+
+/// A hash function implementing [`mp_hashers::HasherT`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HasherKind {
+    Pedersen,
+    Poseidon,
+}
+
+/// The hash functions used to compute the commitments of a chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommitmentHashers {
+    /// Hashes the transactions and events, and their commitment tries.
+    pub transaction: HasherKind,
+    /// Combines the contract and class trie roots into the state commitment.
+    pub state: HasherKind,
+}
+
+impl Default for CommitmentHashers {
+    fn default() -> Self {
+        Self { transaction: HasherKind::Pedersen, state: HasherKind::Poseidon }
+    }
+}
+
+impl CommitmentHashers {
+    /// Reads the hashers from the chain spec properties, defaulting to the Starknet ones.
+    pub fn from_properties(properties: &Map<String, Value>) -> Result<Self, serde_json::Error> {
+        match properties.get(CHAIN_SPEC_PROPERTY) {
+            Some(hashers) => serde_json::from_value(hashers.clone()),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn hashers_are_read_from_chain_spec_properties() {
+        let properties = json!({ "tokenSymbol": "ETH" }).as_object().unwrap().clone();
+        assert_eq!(CommitmentHashers::from_properties(&properties).unwrap(), CommitmentHashers::default());
+
+        let properties = json!({ "commitmentHashers": { "transaction": "poseidon", "state": "pedersen" } })
+            .as_object()
+            .unwrap()
+            .clone();
+        assert_eq!(
+            CommitmentHashers::from_properties(&properties).unwrap(),
+            CommitmentHashers { transaction: HasherKind::Poseidon, state: HasherKind::Pedersen }
+        );
+
+        let properties = json!({ "commitmentHashers": { "transaction": "keccak", "state": "poseidon" } })
+            .as_object()
+            .unwrap()
+            .clone();
+        assert!(CommitmentHashers::from_properties(&properties).is_err());
+    }
+}
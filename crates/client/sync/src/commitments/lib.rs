@@ -5,7 +5,9 @@ use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use mc_db::storage::{DeoxysStorageError, StorageHandler};
 use mc_storage::OverrideHandle;
-use mp_block::state_update::StateUpdateWrapper;
+use mp_block::receipt::TransactionReceiptWrapper;
+use mp_block::state_update::{StateDiffWrapper, StateUpdateWrapper};
+use mp_block::OrderedEvents;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::pedersen::PedersenHasher;
 use mp_hashers::poseidon::PoseidonHasher;
@@ -19,35 +21,52 @@ use sp_runtime::OpaqueExtrinsic;
 use starknet_api::core::{ClassHash, CompiledClassHash, ContractAddress, Nonce};
 use starknet_api::hash::StarkFelt;
 use starknet_api::state::StorageKey;
-use starknet_api::transaction::{Event, Transaction};
+use starknet_api::transaction::Transaction;
 use starknet_core::types::BlockId;
 use starknet_ff::FieldElement;
 use starknet_types_core::felt::Felt;
 
 use super::events::memory_event_commitment;
-use super::transactions::memory_transaction_commitment;
+use super::hashers::{CommitmentHashers, HasherKind};
+use super::receipts::memory_receipt_commitment;
+use super::state_diff::{calculate_state_diff_commitment, state_diff_length};
+use super::transactions::{memory_transaction_commitment, transaction_hashes};
 
 /// Calculate the transaction and event commitment.
 ///
 /// # Arguments
 ///
 /// * `transactions` - The transactions of the block
-/// * `events` - The events of the block
+/// * `events` - The events of the block, grouped by the transaction which emitted them
 /// * `chain_id` - The current chain id
 /// * `block_number` - The current block number
+/// * `protocol_version` - The Starknet version of the block, as stored in its header
+/// * `hashers` - The hash functions of the chain
 ///
 /// # Returns
 ///
 /// The transaction and the event commitment as `Felt252Wrapper`.
 pub fn calculate_commitments(
     transactions: &[Transaction],
-    events: &[Event],
+    events: &[OrderedEvents],
     chain_id: Felt252Wrapper,
     block_number: u64,
+    protocol_version: &Felt252Wrapper,
+    hashers: CommitmentHashers,
 ) -> (Felt252Wrapper, Felt252Wrapper) {
+    // the event leaves commit to the hash of their transaction from Starknet 0.13.2
+    let transaction_hashes = transaction_hashes(transactions, chain_id, block_number, hashers.transaction);
     let (commitment_tx, commitment_event) = rayon::join(
-        || memory_transaction_commitment(transactions, chain_id, block_number),
-        || memory_event_commitment(events),
+        || {
+            memory_transaction_commitment(
+                transactions,
+                &transaction_hashes,
+                block_number,
+                protocol_version,
+                hashers.transaction,
+            )
+        },
+        || memory_event_commitment(events, &transaction_hashes, protocol_version, hashers.transaction),
     );
     (
         commitment_tx.expect("Failed to calculate transaction commitment"),
@@ -55,6 +74,30 @@ pub fn calculate_commitments(
     )
 }
 
+/// Calculate the receipt commitment, the state diff commitment and the state diff length, which
+/// the blocks of Starknet 0.13.2 and later commit to.
+///
+/// # Arguments
+///
+/// * `receipts` - The receipts of the transactions of the block
+/// * `state_diff` - The state diff of the block
+///
+/// # Returns
+///
+/// The receipt and state diff commitments as `Felt252Wrapper`, and the state diff length.
+pub fn calculate_v0_13_2_commitments(
+    receipts: &[TransactionReceiptWrapper],
+    state_diff: &StateDiffWrapper,
+) -> (Felt252Wrapper, Felt252Wrapper, u64) {
+    let (commitment_receipt, commitment_state_diff) =
+        rayon::join(|| memory_receipt_commitment(receipts), || calculate_state_diff_commitment(state_diff));
+    (
+        commitment_receipt.expect("Failed to calculate receipt commitment"),
+        commitment_state_diff,
+        state_diff_length(state_diff),
+    )
+}
+
 /// Builds a `CommitmentStateDiff` from the `StateUpdateWrapper`.
 ///
 /// # Arguments
@@ -109,6 +152,17 @@ pub fn build_commitment_state_diff(state_update_wrapper: StateUpdateWrapper) ->
     commitment_state_diff
 }
 
+/// Merges the state diff of a block into `merged`, the state diff of the blocks before it, so that
+/// the state diffs of several blocks are committed to the tries at once.
+pub fn merge_commitment_state_diffs(merged: &mut CommitmentStateDiff, state_diff: CommitmentStateDiff) {
+    merged.address_to_class_hash.extend(state_diff.address_to_class_hash);
+    merged.address_to_nonce.extend(state_diff.address_to_nonce);
+    for (contract_address, updates) in state_diff.storage_updates {
+        merged.storage_updates.entry(contract_address).or_default().extend(updates);
+    }
+    merged.class_hash_to_compiled_class_hash.extend(state_diff.class_hash_to_compiled_class_hash);
+}
+
 /// Calculate state commitment hash value.
 ///
 /// The state commitment is the digest that uniquely (up to hash collisions) encodes the state.
@@ -152,6 +206,7 @@ where
 ///
 /// * `CommitmentStateDiff` - The commitment state diff inducing unprocessed state changes.
 /// * `BonsaiDb` - The database responsible for storing computing the state tries.
+/// * `hashers` - The hash functions of the chain.
 ///
 /// # Returns
 ///
@@ -161,6 +216,7 @@ pub fn update_state_root(
     overrides: Arc<OverrideHandle<Block<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
     block_number: u64,
     substrate_block_hash: Option<H256>,
+    hashers: CommitmentHashers,
 ) -> Felt252Wrapper {
     // Update contract and its storage tries
     let (contract_trie_root, class_trie_root) = rayon::join(
@@ -171,7 +227,33 @@ pub fn update_state_root(
         || class_trie_root(&csd, block_number).expect("Failed to compute class root"),
     );
 
-    calculate_state_root::<PoseidonHasher>(contract_trie_root, class_trie_root)
+    match hashers.state {
+        HasherKind::Pedersen => calculate_state_root::<PedersenHasher>(contract_trie_root, class_trie_root),
+        HasherKind::Poseidon => calculate_state_root::<PoseidonHasher>(contract_trie_root, class_trie_root),
+    }
+}
+
+/// The state root of the latest state of the tries.
+pub fn latest_state_root(hashers: CommitmentHashers) -> Result<Felt252Wrapper, DeoxysStorageError> {
+    let contract_trie_root = StorageHandler::contract()?.root()?.into();
+    let class_trie_root = StorageHandler::class()?.root()?.into();
+
+    Ok(match hashers.state {
+        HasherKind::Pedersen => calculate_state_root::<PedersenHasher>(contract_trie_root, class_trie_root),
+        HasherKind::Poseidon => calculate_state_root::<PoseidonHasher>(contract_trie_root, class_trie_root),
+    })
+}
+
+/// The state root right after block `block_number` was applied, read from the snapshots of the
+/// tries at that block.
+pub fn state_root_at(block_number: u64, hashers: CommitmentHashers) -> Result<Felt252Wrapper, DeoxysStorageError> {
+    let contract_trie_root = StorageHandler::contract_at(block_number)?.root()?.into();
+    let class_trie_root = StorageHandler::class_at(block_number)?.root()?.into();
+
+    Ok(match hashers.state {
+        HasherKind::Pedersen => calculate_state_root::<PedersenHasher>(contract_trie_root, class_trie_root),
+        HasherKind::Poseidon => calculate_state_root::<PoseidonHasher>(contract_trie_root, class_trie_root),
+    })
 }
 
 /// Calculates the contract trie root
@@ -199,10 +281,8 @@ fn contract_trie_root(
 
     // Tries need to be initialised before values are inserted
     contract_write.init()?;
-    let start1 = std::time::Instant::now();
 
     // First we insert the contract storage changes
-    let start = std::time::Instant::now();
     for (contract_address, updates) in csd.storage_updates.iter() {
         storage_write.init(contract_address)?;
 
@@ -210,18 +290,14 @@ fn contract_trie_root(
             storage_write.insert(contract_address, key, *value)?;
         }
     }
-    log::debug!("contract_trie_root update_storage_trie: {:?}", std::time::Instant::now() - start);
 
     // Then we commit them
-    let start = std::time::Instant::now();
     storage_write.commit(block_number + 1)?;
     // NOTE: handler changes act as separate, mutable instances over storage and need to
     // be manually merged back into the backend.
     storage_write.apply_changes()?;
-    log::debug!("contract_trie_root bonsai_contract_storage.commit: {:?}", std::time::Instant::now() - start);
 
     // Then we compute the leaf hashes retrieving the corresponding storage root
-    let start = std::time::Instant::now();
     let storage_read = StorageHandler::contract_storage()?;
     let updates = csd
         .storage_updates
@@ -234,17 +310,11 @@ fn contract_trie_root(
             (contract_address, leaf_hash)
         })
         .collect::<Vec<_>>();
-    log::debug!("contract_trie_root updates: {:?}", std::time::Instant::now() - start);
 
-    let start = std::time::Instant::now();
     contract_write.update(updates)?;
-    log::debug!("contract_trie_root bonsai_contract.commit: {:?}", std::time::Instant::now() - start);
 
-    let start = std::time::Instant::now();
     contract_write.commit(block_number + 1)?;
     contract_write.apply_changes()?;
-    log::debug!("contract_trie_root bonsai_contract.commit: {:?}", std::time::Instant::now() - start);
-    log::debug!("contract_trie_root: {:?}", std::time::Instant::now() - start1);
 
     let contract_read = StorageHandler::contract()?;
     Ok(contract_read.root()?.into())
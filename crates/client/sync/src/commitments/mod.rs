@@ -1,3 +1,9 @@
 pub mod events;
+pub mod hashers;
+#[cfg(feature = "substrate")]
 pub mod lib;
+pub mod proofs;
+pub mod receipts;
+pub mod state_diff;
 pub mod transactions;
+pub mod versions;
@@ -0,0 +1,159 @@
+//! Merkle proofs of inclusion in the transaction and event commitments.
+//!
+//! Both commitments are the roots of Patricia tries of height 64, whose leaves are the transaction
+//! hashes with signature, and the event hashes, keyed by their index in the block. A proof lists
+//! the nodes on the path from the root to a leaf, so that the inclusion of a transaction or an
+//! event can be checked against the commitment of the block header alone, see
+//! [`InclusionProof::verify`].
+use bitvec::prelude::*;
+use bonsai_trie::databases::HashMapDb;
+use bonsai_trie::id::{BasicId, BasicIdBuilder};
+use bonsai_trie::{BonsaiStorage, BonsaiStorageConfig, ProofNode};
+use mp_felt::Felt252Wrapper;
+use starknet_ff::FieldElement;
+use starknet_types_core::felt::Felt;
+use starknet_types_core::hash::StarkHash;
+
+/// Height of the commitment tries, their keys being 64 bits indices.
+const COMMITMENT_TRIE_HEIGHT: usize = 64;
+
+/// Proof of the inclusion of a leaf in a commitment trie.
+#[derive(Debug, Clone)]
+pub struct InclusionProof {
+    /// The leaf, the transaction hash with signature or the event hash.
+    pub leaf: Felt252Wrapper,
+    /// The nodes on the path from the root to the leaf, root first.
+    pub nodes: Vec<ProofNode>,
+}
+
+impl InclusionProof {
+    /// Whether the proof proves the leaf at `index` in the trie whose root is `root`, with `T`
+    /// the hash function of the trie.
+    pub fn verify<T: StarkHash>(&self, index: u64, root: Felt252Wrapper) -> bool {
+        let key = |depth: usize| (index >> (COMMITMENT_TRIE_HEIGHT - 1 - depth)) & 1 == 1;
+
+        let mut expected = Felt::from(root);
+        let mut depth = 0;
+        for node in &self.nodes {
+            match node {
+                ProofNode::Binary { left, right } => {
+                    if depth >= COMMITMENT_TRIE_HEIGHT || T::hash(left, right) != expected {
+                        return false;
+                    }
+                    expected = if key(depth) { *right } else { *left };
+                    depth += 1;
+                }
+                ProofNode::Edge { child, path } => {
+                    let len = path.0.len();
+                    if depth + len > COMMITMENT_TRIE_HEIGHT
+                        || path.0.iter().enumerate().any(|(i, bit)| *bit != key(depth + i))
+                    {
+                        return false;
+                    }
+                    let path_value = path.0.iter().fold(Felt::ZERO, |value, bit| {
+                        if *bit { value * Felt::TWO + Felt::ONE } else { value * Felt::TWO }
+                    });
+                    if T::hash(child, &path_value) + Felt::from(len as u64) != expected {
+                        return false;
+                    }
+                    expected = *child;
+                    depth += len;
+                }
+            }
+        }
+
+        depth == COMMITMENT_TRIE_HEIGHT && expected == Felt::from(self.leaf)
+    }
+}
+
+/// Builds the commitment trie holding `leaves` under `identifier`, in memory.
+pub(crate) fn commitment_trie<T>(
+    identifier: &[u8],
+    leaves: Vec<FieldElement>,
+) -> BonsaiStorage<BasicId, HashMapDb<BasicId>, T>
+where
+    T: StarkHash + Send + Sync,
+{
+    let config = BonsaiStorageConfig::default();
+    let bonsai_db = HashMapDb::<BasicId>::default();
+    let mut bonsai_storage = BonsaiStorage::<_, _, T>::new(bonsai_db, config).expect("Failed to create bonsai storage");
+
+    for (i, leaf) in leaves.into_iter().enumerate() {
+        let key = BitVec::from_vec(i.to_be_bytes().to_vec());
+        let value = Felt::from(Felt252Wrapper::from(leaf));
+        bonsai_storage.insert(identifier, key.as_bitslice(), &value).expect("Failed to insert into bonsai storage");
+    }
+
+    let mut id_builder = BasicIdBuilder::new();
+    let id = id_builder.new_id();
+    bonsai_storage.commit(id).expect("Failed to commit to bonsai storage");
+    bonsai_storage
+}
+
+/// Proves the inclusion of the leaves at `indices` in the commitment trie holding `leaves`.
+pub(crate) fn inclusion_proofs<T>(
+    identifier: &[u8],
+    leaves: Vec<FieldElement>,
+    indices: impl IntoIterator<Item = usize>,
+) -> Result<Vec<InclusionProof>, String>
+where
+    T: StarkHash + Send + Sync,
+{
+    let count = leaves.len();
+    let proved: Vec<(usize, FieldElement)> = indices
+        .into_iter()
+        .map(|index| leaves.get(index).map(|leaf| (index, *leaf)).ok_or(format!("No leaf {index} in {count} leaves")))
+        .collect::<Result<_, _>>()?;
+
+    let bonsai_storage = commitment_trie::<T>(identifier, leaves);
+    proved
+        .into_iter()
+        .map(|(index, leaf)| {
+            let key = BitVec::from_vec(index.to_be_bytes().to_vec());
+            let nodes = bonsai_storage
+                .get_proof(identifier, key.as_bitslice())
+                .map_err(|e| format!("Failed to prove leaf {index}: {e:?}"))?;
+            Ok(InclusionProof { leaf: Felt252Wrapper::from(leaf), nodes })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use mc_db::storage::bonsai_identifier;
+    use starknet_types_core::hash::{Pedersen, Poseidon};
+
+    use super::*;
+
+    fn check_proofs<T: StarkHash + Send + Sync>(count: u64) {
+        let leaves: Vec<FieldElement> = (1..=count).map(|i| FieldElement::from(i * 1_000_003)).collect();
+        let root = Felt252Wrapper::from(
+            commitment_trie::<T>(bonsai_identifier::TRANSACTION, leaves.clone())
+                .root_hash(bonsai_identifier::TRANSACTION)
+                .unwrap(),
+        );
+
+        let proofs = inclusion_proofs::<T>(bonsai_identifier::TRANSACTION, leaves, 0..count as usize).unwrap();
+        for (index, proof) in proofs.iter().enumerate() {
+            assert!(proof.verify::<T>(index as u64, root), "leaf {index} of {count}");
+            assert!(!proof.verify::<T>(index as u64 + 1, root));
+
+            let forged = InclusionProof { leaf: Felt252Wrapper::from(FieldElement::from(7u64)), ..proof.clone() };
+            assert!(!forged.verify::<T>(index as u64, root));
+        }
+    }
+
+    #[test]
+    fn proofs_verify_against_the_commitment() {
+        for count in [1, 2, 5, 64] {
+            check_proofs::<Pedersen>(count);
+            check_proofs::<Poseidon>(count);
+        }
+    }
+
+    #[test]
+    fn leaves_out_of_range_are_not_proved() {
+        let leaves = vec![FieldElement::ONE, FieldElement::TWO];
+        assert!(inclusion_proofs::<Pedersen>(bonsai_identifier::EVENT, leaves, [2]).is_err());
+    }
+}
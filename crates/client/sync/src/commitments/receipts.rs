@@ -0,0 +1,147 @@
+use bitvec::vec::BitVec;
+use bonsai_trie::databases::HashMapDb;
+use bonsai_trie::id::{BasicId, BasicIdBuilder};
+use bonsai_trie::{BonsaiStorage, BonsaiStorageConfig};
+use mc_db::storage::bonsai_identifier;
+use mp_block::receipt::TransactionReceiptWrapper;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::poseidon::PoseidonHasher;
+use mp_hashers::HasherT;
+use rayon::prelude::*;
+use starknet_core::utils::starknet_keccak;
+use starknet_ff::FieldElement;
+use starknet_types_core::felt::Felt;
+use starknet_types_core::hash::Poseidon;
+
+/// Calculate the hash of a transaction receipt, as committed to from Starknet 0.13.2.
+///
+/// The receipt is hashed with Poseidon, along with the messages it sent, the keccak of its revert
+/// reason and the L1 gas and data gas it consumed.
+///
+/// # Arguments
+///
+/// * `receipt` - The receipt we want to calculate the hash of.
+///
+/// # Returns
+///
+/// The receipt hash as `FieldElement`.
+pub fn calculate_receipt_hash(receipt: &TransactionReceiptWrapper) -> FieldElement {
+    let mut messages = vec![FieldElement::from(receipt.messages_sent.len() as u64)];
+    for message in &receipt.messages_sent {
+        messages.push(message.from_address.into());
+        messages.push(message.to_address.into());
+        messages.push(FieldElement::from(message.payload.len() as u64));
+        messages.extend(message.payload.iter().map(|&value| FieldElement::from(value)));
+    }
+    // the keccak of the revert reason is truncated to 250 bits, as the selectors
+    let revert_reason_hash =
+        receipt.revert_error.as_deref().map_or(FieldElement::ZERO, |reason| starknet_keccak(reason.as_bytes()));
+
+    PoseidonHasher::compute_hash_on_elements(&[
+        receipt.transaction_hash.into(),
+        receipt.actual_fee.into(),
+        PoseidonHasher::compute_hash_on_elements(&messages),
+        revert_reason_hash,
+        FieldElement::ZERO, // L2 gas consumed
+        FieldElement::from(receipt.execution_resources.l1_gas),
+        FieldElement::from(receipt.execution_resources.l1_data_gas),
+    ])
+}
+
+/// Calculate the receipt commitment in memory using HashMapDb (which is more efficient for this
+/// usecase).
+///
+/// Blocks commit to their receipts from Starknet 0.13.2, in a Poseidon trie.
+///
+/// # Arguments
+///
+/// * `receipts` - The receipts of the transactions of the block
+///
+/// # Returns
+///
+/// The receipt commitment as `Felt252Wrapper`.
+pub fn memory_receipt_commitment(receipts: &[TransactionReceiptWrapper]) -> Result<Felt252Wrapper, String> {
+    if receipts.is_empty() {
+        return Ok(Felt252Wrapper::ZERO);
+    }
+
+    let config = BonsaiStorageConfig::default();
+    let bonsai_db = HashMapDb::<BasicId>::default();
+    let mut bonsai_storage =
+        BonsaiStorage::<_, _, Poseidon>::new(bonsai_db, config).expect("Failed to create bonsai storage");
+    let identifier = bonsai_identifier::RECEIPT;
+
+    // receipt hashes are computed in parallel
+    let receipts = receipts.par_iter().map(calculate_receipt_hash).collect::<Vec<_>>();
+
+    for (i, receipt_hash) in receipts.into_iter().enumerate() {
+        let key = BitVec::from_vec(i.to_be_bytes().to_vec());
+        let value = Felt::from(Felt252Wrapper::from(receipt_hash));
+        bonsai_storage.insert(identifier, key.as_bitslice(), &value).expect("Failed to insert into bonsai storage");
+    }
+
+    let mut id_builder = BasicIdBuilder::new();
+    let id = id_builder.new_id();
+
+    bonsai_storage.commit(id).expect("Failed to commit to bonsai storage");
+    let root_hash = bonsai_storage.root_hash(identifier).expect("Failed to get root hash");
+
+    Ok(Felt252Wrapper::from(root_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use mp_block::receipt::{ExecutionResourcesWrapper, MessageToL1Wrapper};
+
+    use super::*;
+
+    fn receipt(transaction_hash: u64) -> TransactionReceiptWrapper {
+        TransactionReceiptWrapper {
+            transaction_hash: Felt252Wrapper::from(transaction_hash),
+            actual_fee: Felt252Wrapper::from(10u64),
+            revert_error: None,
+            execution_resources: ExecutionResourcesWrapper { l1_gas: 20, l1_data_gas: 30, ..Default::default() },
+            messages_sent: vec![],
+            events: vec![],
+        }
+    }
+
+    #[test]
+    fn receipt_hashes_cover_messages_and_revert_reasons() {
+        let base = receipt(1);
+        assert_eq!(
+            calculate_receipt_hash(&base),
+            PoseidonHasher::compute_hash_on_elements(&[
+                FieldElement::ONE,
+                FieldElement::from(10u64),
+                PoseidonHasher::compute_hash_on_elements(&[FieldElement::ZERO]),
+                FieldElement::ZERO,
+                FieldElement::ZERO,
+                FieldElement::from(20u64),
+                FieldElement::from(30u64),
+            ])
+        );
+
+        let mut reverted = receipt(1);
+        reverted.revert_error = Some("out of gas".to_string());
+        assert_ne!(calculate_receipt_hash(&reverted), calculate_receipt_hash(&base));
+
+        let mut sending = receipt(1);
+        sending.messages_sent.push(MessageToL1Wrapper {
+            from_address: Felt252Wrapper::from(2u64),
+            to_address: Felt252Wrapper::from(3u64),
+            payload: vec![Felt252Wrapper::from(4u64)],
+        });
+        assert_ne!(calculate_receipt_hash(&sending), calculate_receipt_hash(&base));
+    }
+
+    #[test]
+    fn receipt_commitments_depend_on_the_order_of_receipts() {
+        assert_eq!(memory_receipt_commitment(&[]).unwrap(), Felt252Wrapper::ZERO);
+
+        let commitment = memory_receipt_commitment(&[receipt(1), receipt(2)]).unwrap();
+        assert_ne!(commitment, Felt252Wrapper::ZERO);
+        assert_eq!(memory_receipt_commitment(&[receipt(1), receipt(2)]).unwrap(), commitment);
+        assert_ne!(memory_receipt_commitment(&[receipt(2), receipt(1)]).unwrap(), commitment);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use mp_block::state_update::StateDiffWrapper;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::poseidon::PoseidonHasher;
+use mp_hashers::HasherT;
+
+/// Calculate the state diff commitment, as committed to from Starknet 0.13.2.
+///
+/// The state diff is hashed with Poseidon, each kind of entry sorted by address or class hash, so
+/// that the commitment doesn't depend on the order the entries are listed in.
+///
+/// # Arguments
+///
+/// * `state_diff` - The state diff of the block
+///
+/// # Returns
+///
+/// The state diff commitment as `Felt252Wrapper`.
+pub fn calculate_state_diff_commitment(state_diff: &StateDiffWrapper) -> Felt252Wrapper {
+    let deployed_contracts: BTreeMap<_, _> = state_diff
+        .deployed_contracts
+        .iter()
+        .chain(&state_diff.replaced_classes)
+        .map(|contract| (contract.address, contract.class_hash))
+        .collect();
+    let declared_classes: BTreeMap<_, _> =
+        state_diff.declared_classes.iter().map(|class| (class.class_hash, class.compiled_class_hash)).collect();
+    let old_declared_classes: BTreeSet<_> = state_diff.old_declared_contracts.iter().copied().collect();
+    let storage_diffs: BTreeMap<_, BTreeMap<_, _>> = state_diff
+        .storage_diffs
+        .iter()
+        .filter(|(_, diffs)| !diffs.is_empty())
+        .map(|(address, diffs)| (*address, diffs.iter().map(|diff| (diff.key, diff.value)).collect()))
+        .collect();
+    let nonces: BTreeMap<_, _> = state_diff.nonces.iter().copied().collect();
+
+    let mut data = vec![Felt252Wrapper::try_from(&b"STARKNET_STATE_DIFF0"[..]).unwrap()];
+    data.push((deployed_contracts.len() as u64).into());
+    for (address, class_hash) in deployed_contracts {
+        data.extend([address, class_hash]);
+    }
+    data.push((declared_classes.len() as u64).into());
+    for (class_hash, compiled_class_hash) in declared_classes {
+        data.extend([class_hash, compiled_class_hash]);
+    }
+    data.push((old_declared_classes.len() as u64).into());
+    data.extend(old_declared_classes);
+    // placeholder for the data availability mode of the storage diffs and nonces
+    data.extend([Felt252Wrapper::ONE, Felt252Wrapper::ZERO]);
+    data.push((storage_diffs.len() as u64).into());
+    for (address, diffs) in storage_diffs {
+        data.extend([address, (diffs.len() as u64).into()]);
+        for (key, value) in diffs {
+            data.extend([key, value]);
+        }
+    }
+    data.push((nonces.len() as u64).into());
+    for (address, nonce) in nonces {
+        data.extend([address, nonce]);
+    }
+
+    PoseidonHasher::compute_hash_on_wrappers(&data)
+}
+
+/// Calculate the number of entries of a state diff, as counted in the block hash from Starknet
+/// 0.13.2: each storage update, deployed contract, replaced class, declared class and nonce update.
+pub fn state_diff_length(state_diff: &StateDiffWrapper) -> u64 {
+    let storage_updates: usize = state_diff.storage_diffs.iter().map(|(_, diffs)| diffs.len()).sum();
+    (storage_updates
+        + state_diff.deployed_contracts.len()
+        + state_diff.replaced_classes.len()
+        + state_diff.declared_classes.len()
+        + state_diff.old_declared_contracts.len()
+        + state_diff.nonces.len()) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use mp_block::state_update::{DeclaredContractWrapper, DeployedContractWrapper, StorageDiffWrapper};
+
+    use super::*;
+
+    fn felt(value: u64) -> Felt252Wrapper {
+        Felt252Wrapper::from(value)
+    }
+
+    fn state_diff(reversed: bool) -> StateDiffWrapper {
+        let mut storage_diffs = vec![
+            (felt(1), vec![StorageDiffWrapper { key: felt(2), value: felt(3) }]),
+            (
+                felt(4),
+                vec![
+                    StorageDiffWrapper { key: felt(5), value: felt(6) },
+                    StorageDiffWrapper { key: felt(7), value: felt(8) },
+                ],
+            ),
+        ];
+        let mut nonces = vec![(felt(1), felt(9)), (felt(4), felt(10))];
+        if reversed {
+            storage_diffs.reverse();
+            storage_diffs[0].1.reverse();
+            nonces.reverse();
+        }
+        StateDiffWrapper {
+            storage_diffs,
+            deployed_contracts: vec![DeployedContractWrapper { address: felt(4), class_hash: felt(11) }],
+            old_declared_contracts: vec![felt(12)],
+            declared_classes: vec![DeclaredContractWrapper { class_hash: felt(13), compiled_class_hash: felt(14) }],
+            nonces,
+            replaced_classes: vec![DeployedContractWrapper { address: felt(1), class_hash: felt(15) }],
+        }
+    }
+
+    #[test]
+    fn state_diff_commitments_hash_sorted_entries() {
+        let commitment = calculate_state_diff_commitment(&state_diff(false));
+        let expected = PoseidonHasher::compute_hash_on_wrappers(
+            &[
+                &[Felt252Wrapper::try_from(&b"STARKNET_STATE_DIFF0"[..]).unwrap()][..],
+                &[felt(2), felt(1), felt(15), felt(4), felt(11)], // deployed contracts and replaced classes
+                &[felt(1), felt(13), felt(14)],                   // declared classes
+                &[felt(1), felt(12)],                             // old declared classes
+                &[felt(1), felt(0)],                              // data availability mode
+                &[felt(2), felt(1), felt(1), felt(2), felt(3)],   // storage diffs
+                &[felt(4), felt(2), felt(5), felt(6), felt(7), felt(8)],
+                &[felt(2), felt(1), felt(9), felt(4), felt(10)], // nonces
+            ]
+            .concat(),
+        );
+
+        assert_eq!(commitment, expected);
+        assert_eq!(calculate_state_diff_commitment(&state_diff(true)), commitment);
+        assert_eq!(state_diff_length(&state_diff(false)), 9);
+    }
+}
@@ -1,17 +1,19 @@
-use bitvec::prelude::*;
-use bonsai_trie::databases::HashMapDb;
-use bonsai_trie::id::{BasicId, BasicIdBuilder};
-use bonsai_trie::{BonsaiStorage, BonsaiStorageConfig};
+use std::collections::{HashMap, HashSet};
+
 use mc_db::storage::bonsai_identifier;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::pedersen::PedersenHasher;
+use mp_hashers::poseidon::PoseidonHasher;
 use mp_hashers::HasherT;
 use mp_transactions::compute_hash::ComputeTransactionHash;
 use rayon::prelude::*;
-use starknet_api::transaction::Transaction;
+use starknet_api::transaction::{Transaction, TransactionSignature};
 use starknet_ff::FieldElement;
-use starknet_types_core::felt::Felt;
-use starknet_types_core::hash::Pedersen;
+use starknet_types_core::hash::{Pedersen, Poseidon};
+
+use super::hashers::HasherKind;
+use super::proofs::{commitment_trie, inclusion_proofs, InclusionProof};
+use super::versions::has_v0_13_2_commitments;
 
 /// Compute the combined hash of the transaction hash and the signature.
 ///
@@ -34,50 +36,139 @@ pub fn calculate_transaction_hash_with_signature<H: HasherT>(
 where
     H: HasherT,
 {
+    let signature_hash = calculate_signature_hash::<H>(&committed_signature(transaction, block_number));
+    H::hash_elements(transaction_hash::<H>(transaction, chain_id, block_number), signature_hash)
+}
+
+/// The signature of `transaction` as committed to: signatures of declarations and deploy accounts
+/// are only included from block 61394 (mainnet), and L1 handlers have none.
+fn committed_signature(transaction: &Transaction, block_number: u64) -> TransactionSignature {
     let include_signature = block_number >= 61394;
 
-    let signature_hash = match transaction {
-        Transaction::Invoke(invoke_tx) => {
-            // Include signatures for Invoke transactions or for all transactions
-            let signature = invoke_tx.signature();
+    match transaction {
+        Transaction::Invoke(invoke_tx) => invoke_tx.signature(),
+        Transaction::Declare(declare_tx) if include_signature => declare_tx.signature(),
+        Transaction::DeployAccount(deploy_account_tx) if include_signature => deploy_account_tx.signature(),
+        _ => TransactionSignature::default(),
+    }
+}
+
+/// Compute the leaf of a transaction in the transaction commitment of the blocks of Starknet 0.13.2
+/// and later: the Poseidon hash of the transaction hash followed by its signature, or by zero when
+/// it has none.
+///
+/// # Arguments
+///
+/// * `transaction_hash` - The hash of the transaction.
+/// * `signature` - The signature of the transaction.
+///
+/// # Returns
+///
+/// The leaf of the transaction.
+pub fn calculate_transaction_leaf_v0_13_2(
+    transaction_hash: FieldElement,
+    signature: &TransactionSignature,
+) -> FieldElement {
+    let mut elements = Vec::with_capacity(signature.0.len().max(1) + 1);
+    elements.push(transaction_hash);
+    if signature.0.is_empty() {
+        elements.push(FieldElement::ZERO);
+    } else {
+        elements.extend(signature.0.iter().map(|x| FieldElement::from(Felt252Wrapper::from(*x))));
+    }
+    PoseidonHasher::compute_hash_on_elements(&elements)
+}
+
+fn calculate_signature_hash<H: HasherT>(signature: &TransactionSignature) -> FieldElement {
+    H::compute_hash_on_elements(
+        &signature.0.iter().map(|x| Felt252Wrapper::from(*x).into()).collect::<Vec<FieldElement>>(),
+    )
+}
+
+fn transaction_hash<H: HasherT>(
+    transaction: &Transaction,
+    chain_id: Felt252Wrapper,
+    block_number: u64,
+) -> FieldElement {
+    Felt252Wrapper::from(transaction.compute_hash::<H>(chain_id, false, Some(block_number)).0).into()
+}
 
-            H::compute_hash_on_elements(
-                &signature.0.iter().map(|x| Felt252Wrapper::from(*x).into()).collect::<Vec<FieldElement>>(),
-            )
+/// Computes the hashes of `transactions` in parallel, with the hash function of the chain.
+///
+/// The hashes are shared by the transaction and event commitments, whose leaves commit to the
+/// hash of the transaction which emitted each event from Starknet 0.13.2.
+pub fn transaction_hashes(
+    transactions: &[Transaction],
+    chain_id: Felt252Wrapper,
+    block_number: u64,
+    hasher: HasherKind,
+) -> Vec<FieldElement> {
+    match hasher {
+        HasherKind::Pedersen => {
+            transactions.par_iter().map(|tx| transaction_hash::<PedersenHasher>(tx, chain_id, block_number)).collect()
         }
-        Transaction::Declare(declare_tx) => {
-            // Include signatures for Declare transactions if the block number is greater than 61394 (mainnet)
-            if include_signature {
-                let signature = declare_tx.signature();
-
-                H::compute_hash_on_elements(
-                    &signature.0.iter().map(|x| Felt252Wrapper::from(*x).into()).collect::<Vec<FieldElement>>(),
-                )
-            } else {
-                H::compute_hash_on_elements(&[])
-            }
+        HasherKind::Poseidon => {
+            transactions.par_iter().map(|tx| transaction_hash::<PoseidonHasher>(tx, chain_id, block_number)).collect()
         }
-        Transaction::DeployAccount(deploy_account_tx) => {
-            // Include signatures for DeployAccount transactions if the block number is greater than 61394
-            // (mainnet)
-            if include_signature {
-                let signature = deploy_account_tx.signature();
-
-                H::compute_hash_on_elements(
-                    &signature.0.iter().map(|x| Felt252Wrapper::from(*x).into()).collect::<Vec<FieldElement>>(),
-                )
-            } else {
-                H::compute_hash_on_elements(&[])
-            }
+    }
+}
+
+/// Computes the leaves of the transaction commitment trie, and the hash function of the trie.
+///
+/// Before Starknet 0.13.2, the leaves are the transaction hashes with signature of
+/// [`calculate_transaction_hash_with_signature`] and the trie is hashed with `hasher`. From
+/// Starknet 0.13.2, they are the leaves of [`calculate_transaction_leaf_v0_13_2`], in a Poseidon
+/// trie.
+fn transaction_leaves(
+    transactions: &[Transaction],
+    transaction_hashes: &[FieldElement],
+    block_number: u64,
+    protocol_version: &Felt252Wrapper,
+    hasher: HasherKind,
+) -> (Vec<FieldElement>, HasherKind) {
+    if has_v0_13_2_commitments(protocol_version) {
+        let leaves = transactions
+            .par_iter()
+            .zip(transaction_hashes.par_iter())
+            .map(|(tx, tx_hash)| calculate_transaction_leaf_v0_13_2(*tx_hash, &committed_signature(tx, block_number)))
+            .collect();
+        return (leaves, HasherKind::Poseidon);
+    }
+
+    let leaves = match hasher {
+        HasherKind::Pedersen => {
+            legacy_transaction_leaves::<PedersenHasher>(transactions, transaction_hashes, block_number)
+        }
+        HasherKind::Poseidon => {
+            legacy_transaction_leaves::<PoseidonHasher>(transactions, transaction_hashes, block_number)
         }
-        Transaction::L1Handler(_) => H::compute_hash_on_elements(&[]),
-        _ => H::compute_hash_on_elements(&[]),
     };
+    (leaves, hasher)
+}
 
-    H::hash_elements(
-        Felt252Wrapper::from(transaction.compute_hash::<H>(chain_id, false, Some(block_number)).0).into(),
-        signature_hash,
-    )
+/// Computes the transaction hashes with signature of `transactions`, given their hashes.
+///
+/// Identical signatures, such as the empty signatures of L1 handlers and of old declarations, are
+/// hashed once for the whole block, then the leaves are hashed in a single parallel pass.
+fn legacy_transaction_leaves<H: HasherT>(
+    transactions: &[Transaction],
+    transaction_hashes: &[FieldElement],
+    block_number: u64,
+) -> Vec<FieldElement> {
+    let signatures: Vec<TransactionSignature> =
+        transactions.iter().map(|tx| committed_signature(tx, block_number)).collect();
+
+    let unique_signatures: Vec<&TransactionSignature> = signatures.iter().collect::<HashSet<_>>().into_iter().collect();
+    let signature_hashes: HashMap<&TransactionSignature, FieldElement> = unique_signatures
+        .into_par_iter()
+        .map(|signature| (signature, calculate_signature_hash::<H>(signature)))
+        .collect();
+
+    transaction_hashes
+        .par_iter()
+        .zip(signatures.par_iter())
+        .map(|(tx_hash, signature)| H::hash_elements(*tx_hash, signature_hashes[signature]))
+        .collect()
 }
 
 /// Calculate the transaction commitment in memory using HashMapDb (which is more efficient for this
@@ -86,42 +177,144 @@ where
 /// # Arguments
 ///
 /// * `transactions` - The transactions of the block
-/// * `chain_id` - The current chain id
+/// * `transaction_hashes` - The hashes of the transactions, see [`transaction_hashes`]
 /// * `block_number` - The current block number
+/// * `protocol_version` - The Starknet version of the block, as stored in its header
+/// * `hasher` - The hash function of the transaction hashes and, before Starknet 0.13.2, of the
+///   commitment trie
 ///
 /// # Returns
 ///
 /// The transaction commitment as `Felt252Wrapper`.
 pub fn memory_transaction_commitment(
     transactions: &[Transaction],
-    chain_id: Felt252Wrapper,
+    transaction_hashes: &[FieldElement],
     block_number: u64,
+    protocol_version: &Felt252Wrapper,
+    hasher: HasherKind,
 ) -> Result<Felt252Wrapper, String> {
     // TODO @cchudant refacto/optimise this function
-    let config = BonsaiStorageConfig::default();
-    let bonsai_db = HashMapDb::<BasicId>::default();
-    let mut bonsai_storage =
-        BonsaiStorage::<_, _, Pedersen>::new(bonsai_db, config).expect("Failed to create bonsai storage");
     let identifier = bonsai_identifier::TRANSACTION;
 
-    // transaction hashes are computed in parallel
-    let txs = transactions
-        .par_iter()
-        .map(|tx| calculate_transaction_hash_with_signature::<PedersenHasher>(tx, chain_id, block_number))
-        .collect::<Vec<_>>();
-
     // once transaction hashes have finished computing, they are inserted into the local Bonsai db
-    for (i, tx_hash) in txs.into_iter().enumerate() {
-        let key = BitVec::from_vec(i.to_be_bytes().to_vec());
-        let value = Felt::from(Felt252Wrapper::from(tx_hash));
-        bonsai_storage.insert(identifier, key.as_bitslice(), &value).expect("Failed to insert into bonsai storage");
+    let (leaves, trie_hasher) =
+        transaction_leaves(transactions, transaction_hashes, block_number, protocol_version, hasher);
+    let root_hash = match trie_hasher {
+        HasherKind::Pedersen => commitment_trie::<Pedersen>(identifier, leaves).root_hash(identifier),
+        HasherKind::Poseidon => commitment_trie::<Poseidon>(identifier, leaves).root_hash(identifier),
     }
+    .expect("Failed to get root hash");
 
-    let mut id_builder = BasicIdBuilder::new();
-    let id = id_builder.new_id();
+    Ok(Felt252Wrapper::from(root_hash))
+}
 
-    bonsai_storage.commit(id).expect("Failed to commit to bonsai storage");
-    let root_hash = bonsai_storage.root_hash(identifier).expect("Failed to get root hash");
+/// Proves the inclusion of the transaction at `index` in the transaction commitment of its block.
+///
+/// # Arguments
+///
+/// * `transactions` - The transactions of the block
+/// * `transaction_hashes` - The hashes of the transactions, see [`transaction_hashes`]
+/// * `block_number` - The current block number
+/// * `protocol_version` - The Starknet version of the block, as stored in its header
+/// * `hasher` - The hash function of the transaction hashes and, before Starknet 0.13.2, of the
+///   commitment trie
+/// * `index` - The index of the transaction in the block
+///
+/// # Returns
+///
+/// The proof of the leaf of the transaction in the transaction commitment trie.
+pub fn memory_transaction_proof(
+    transactions: &[Transaction],
+    transaction_hashes: &[FieldElement],
+    block_number: u64,
+    protocol_version: &Felt252Wrapper,
+    hasher: HasherKind,
+    index: usize,
+) -> Result<InclusionProof, String> {
+    let identifier = bonsai_identifier::TRANSACTION;
+    let (leaves, trie_hasher) =
+        transaction_leaves(transactions, transaction_hashes, block_number, protocol_version, hasher);
+    let proofs = match trie_hasher {
+        HasherKind::Pedersen => inclusion_proofs::<Pedersen>(identifier, leaves, [index]),
+        HasherKind::Poseidon => inclusion_proofs::<Poseidon>(identifier, leaves, [index]),
+    };
+    Ok(proofs?.remove(0))
+}
 
-    Ok(Felt252Wrapper::from(root_hash))
+#[cfg(test)]
+mod tests {
+    use starknet_api::core::{ClassHash, ContractAddress, EntryPointSelector, Nonce, PatriciaKey};
+    use starknet_api::hash::StarkFelt;
+    use starknet_api::transaction::{
+        Calldata, DeclareTransaction, DeclareTransactionV0V1, Fee, InvokeTransaction, InvokeTransactionV1,
+        L1HandlerTransaction, TransactionVersion,
+    };
+
+    use super::*;
+
+    fn invoke(nonce: u128, signature: Vec<u128>) -> Transaction {
+        Transaction::Invoke(InvokeTransaction::V1(InvokeTransactionV1 {
+            max_fee: Fee(1),
+            signature: TransactionSignature(signature.into_iter().map(StarkFelt::from).collect()),
+            nonce: Nonce(StarkFelt::from(nonce)),
+            sender_address: ContractAddress(PatriciaKey(StarkFelt::from(2u128))),
+            calldata: Calldata(vec![StarkFelt::from(3u128)].into()),
+        }))
+    }
+
+    #[test]
+    fn batched_leaves_match_the_transaction_hashes_with_signature() {
+        let declare = Transaction::Declare(DeclareTransaction::V1(DeclareTransactionV0V1 {
+            max_fee: Fee(1),
+            signature: TransactionSignature(vec![StarkFelt::from(4u128)]),
+            nonce: Nonce(StarkFelt::ZERO),
+            class_hash: ClassHash(StarkFelt::from(5u128)),
+            sender_address: ContractAddress(PatriciaKey(StarkFelt::from(2u128))),
+        }));
+        let l1_handler = Transaction::L1Handler(L1HandlerTransaction {
+            version: TransactionVersion(StarkFelt::ZERO),
+            nonce: Nonce(StarkFelt::ZERO),
+            contract_address: ContractAddress(PatriciaKey(StarkFelt::from(6u128))),
+            entry_point_selector: EntryPointSelector(StarkFelt::from(7u128)),
+            calldata: Calldata(vec![].into()),
+        });
+        let transactions = vec![
+            invoke(0, vec![8, 9]),
+            invoke(1, vec![8, 9]),
+            invoke(2, vec![]),
+            declare,
+            l1_handler,
+            invoke(3, vec![10]),
+        ];
+        let chain_id = Felt252Wrapper::from(StarkFelt::from(0x534e5f474f45524c49u128));
+
+        for block_number in [100, 61394] {
+            let expected: Vec<_> = transactions
+                .iter()
+                .map(|tx| calculate_transaction_hash_with_signature::<PedersenHasher>(tx, chain_id, block_number))
+                .collect();
+            let hashes = transaction_hashes(&transactions, chain_id, block_number, HasherKind::Pedersen);
+            assert_eq!(legacy_transaction_leaves::<PedersenHasher>(&transactions, &hashes, block_number), expected);
+        }
+    }
+
+    #[test]
+    fn v0_13_2_leaves_hash_the_transaction_hash_and_signature() {
+        let transactions = vec![invoke(0, vec![8, 9]), invoke(1, vec![])];
+        let chain_id = Felt252Wrapper::from(StarkFelt::from(0x534e5f4d41494eu128));
+        let hashes = transaction_hashes(&transactions, chain_id, 700_000, HasherKind::Pedersen);
+
+        let expected = vec![
+            PoseidonHasher::compute_hash_on_elements(&[hashes[0], FieldElement::from(8u64), FieldElement::from(9u64)]),
+            // an empty signature is hashed as a zero
+            PoseidonHasher::compute_hash_on_elements(&[hashes[1], FieldElement::ZERO]),
+        ];
+        let version = Felt252Wrapper::try_from(&b"0.13.2"[..]).unwrap();
+        let (leaves, trie_hasher) = transaction_leaves(&transactions, &hashes, 700_000, &version, HasherKind::Pedersen);
+        assert_eq!((leaves, trie_hasher), (expected, HasherKind::Poseidon));
+
+        let version = Felt252Wrapper::try_from(&b"0.13.1"[..]).unwrap();
+        let (_, trie_hasher) = transaction_leaves(&transactions, &hashes, 700_000, &version, HasherKind::Pedersen);
+        assert_eq!(trie_hasher, HasherKind::Pedersen);
+    }
 }
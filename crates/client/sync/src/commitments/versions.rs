@@ -0,0 +1,18 @@
+//! The Starknet versions which changed how blocks commit to their content.
+use mp_block::versioned_constants::StarknetVersion;
+use mp_felt::Felt252Wrapper;
+
+/// Whether the blocks of a Starknet version commit to their receipts and state diff, which they do
+/// from Starknet 0.13.2.
+///
+/// # Arguments
+///
+/// * `protocol_version` - The Starknet version of the block, as stored in its header
+pub fn has_v0_13_2_commitments(protocol_version: &Felt252Wrapper) -> bool {
+    let v0_13_2: StarknetVersion = "0.13.2".parse().expect("Invalid Starknet version");
+    protocol_version
+        .from_utf8()
+        .ok()
+        .and_then(|version| version.parse::<StarknetVersion>().ok())
+        .is_some_and(|version| version >= v0_13_2)
+}
@@ -0,0 +1,145 @@
+//! Guard stopping the sync before the disks holding the database fill up.
+//!
+//! RocksDB does not recover gracefully from a full disk: writes fail halfway through a block, and
+//! the database may need to be repaired. When a [`DiskWatermark`] is set, the free space of the
+//! disks holding the database is checked while blocks are applied, and the sync is stopped with a
+//! [`SyncError::DiskLow`] once it drops below the watermark, leaving room for the node to keep
+//! serving queries and shut down cleanly.
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use prometheus_endpoint::{register, Gauge, PrometheusError, Registry, U64};
+use sysinfo::Disks;
+
+use crate::errors::SyncError;
+
+/// Minimum interval between two checks of the free space.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+static METRICS: OnceLock<DiskGuardMetrics> = OnceLock::new();
+
+/// Prometheus metrics of the disk guard.
+struct DiskGuardMetrics {
+    available: Gauge<U64>,
+    stopped: Gauge<U64>,
+}
+
+/// Registers the disk guard metrics in `registry`.
+pub fn register_metrics(registry: &Registry) -> Result<(), PrometheusError> {
+    let available = register(
+        Gauge::new("deoxys_disk_available_bytes", "Free space on the fullest disk holding the database")?,
+        registry,
+    )?;
+    let stopped = register(
+        Gauge::new("deoxys_sync_stopped_disk_low", "Whether the sync was stopped as the disk is running out of space")?,
+        registry,
+    )?;
+    let _ = METRICS.set(DiskGuardMetrics { available, stopped });
+    Ok(())
+}
+
+/// Free space the sync must leave on the disks holding the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskWatermark {
+    /// Directories of the database, on each of the disks to watch.
+    pub paths: Vec<PathBuf>,
+    /// Free space, in MiB, below which the sync is stopped.
+    pub min_free_mib: u64,
+}
+
+/// Checks the free space against a [`DiskWatermark`], at most every [`CHECK_INTERVAL`].
+pub struct DiskGuard {
+    watermark: Option<DiskWatermark>,
+    last_check: Option<Instant>,
+}
+
+impl DiskGuard {
+    pub fn new(watermark: Option<DiskWatermark>) -> Self {
+        Self { watermark, last_check: None }
+    }
+
+    /// Fails if a disk holding the database has less free space than the watermark, in which case
+    /// block `block_n` must not be applied.
+    pub fn check(&mut self, block_n: u64) -> Result<(), SyncError> {
+        let Some(watermark) = &self.watermark else {
+            return Ok(());
+        };
+        if self.last_check.is_some_and(|last_check| last_check.elapsed() < CHECK_INTERVAL) {
+            return Ok(());
+        }
+        self.last_check = Some(Instant::now());
+
+        let disks = Disks::new_with_refreshed_list();
+        let fullest = watermark
+            .paths
+            .iter()
+            .filter_map(|path| match available_space_in(&disks, path) {
+                Some(available) => Some((path, available)),
+                None => {
+                    log::debug!("Failed to read the free disk space at {}", path.display());
+                    None
+                }
+            })
+            .min_by_key(|(_, available)| *available);
+        let Some((path, available)) = fullest else {
+            return Ok(());
+        };
+
+        let stopped = available / (1024 * 1024) < watermark.min_free_mib;
+        if let Some(metrics) = METRICS.get() {
+            metrics.available.set(available);
+            metrics.stopped.set(stopped as u64);
+        }
+        if stopped {
+            return Err(SyncError::DiskLow {
+                block_number: block_n,
+                path: path.clone(),
+                available_mib: available / (1024 * 1024),
+                min_free_mib: watermark.min_free_mib,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Space available on the disk holding `path`, in bytes.
+pub(crate) fn available_space(path: &Path) -> Option<u64> {
+    available_space_in(&Disks::new_with_refreshed_list(), path)
+}
+
+fn available_space_in(disks: &Disks, path: &Path) -> Option<u64> {
+    let path = path.canonicalize().ok()?;
+
+    // the disk mounted the closest to the path
+    disks
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_is_stopped_below_the_watermark() {
+        let path = std::env::temp_dir();
+        let Some(available) = available_space(&path) else {
+            // the disks can't be listed in this environment
+            return;
+        };
+
+        let mut guard = DiskGuard::new(Some(DiskWatermark {
+            paths: vec![path.clone()],
+            min_free_mib: available / (1024 * 1024) + 1,
+        }));
+        assert!(matches!(guard.check(7), Err(SyncError::DiskLow { block_number: 7, .. })));
+
+        let mut guard = DiskGuard::new(Some(DiskWatermark { paths: vec![path], min_free_mib: 0 }));
+        assert!(guard.check(7).is_ok());
+        assert!(DiskGuard::new(None).check(7).is_ok());
+    }
+}
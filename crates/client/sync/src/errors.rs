@@ -0,0 +1,74 @@
+//! Errors of the sync pipeline.
+//!
+//! Fetching, converting and verifying a block return a [`SyncError`], whose variant tells callers
+//! what went wrong so that they can decide to retry, skip or stop, instead of the node crashing on
+//! a single malformed block.
+use std::path::PathBuf;
+
+use mc_db::DbError;
+pub use mp_convert::ConversionError;
+use starknet_api::hash::StarkHash;
+use starknet_core::types::StarknetError;
+use starknet_ff::FieldElement;
+use starknet_providers::ProviderError;
+use thiserror::Error;
+
+use crate::ordering::SequencingError;
+
+#[derive(Error, Debug)]
+pub enum SyncError {
+    #[error("gateway error: {0}")]
+    Gateway(#[from] ProviderError),
+    #[error("conversion error: {0}")]
+    Conversion(#[from] ConversionError),
+    #[error("state root mismatch at block {block_number}: computed {computed}, fetched {fetched}")]
+    CommitmentMismatch { block_number: u64, computed: StarkHash, fetched: StarkHash },
+    #[error("{field} of block {block_number} does not match its content")]
+    BlockMismatch { block_number: u64, field: &'static str },
+    #[error("{field} of block {block_number} is {in_header} in its header, but its {body} hold {in_body}")]
+    CountMismatch { block_number: u64, field: &'static str, in_header: u128, body: &'static str, in_body: u128 },
+    #[error("database error: {0}")]
+    Db(#[from] DbError),
+    #[error("block {block_number} uses Starknet version {version}, which is not supported by this build")]
+    UnsupportedStarknetVersion { block_number: u64, version: String },
+    #[error(
+        "block {block_number} not applied: {available_mib} MiB left on the disk holding {}, below the minimum of \
+         {min_free_mib} MiB",
+        path.display()
+    )]
+    DiskLow { block_number: u64, path: PathBuf, available_mib: u64, min_free_mib: u64 },
+    #[error("class {class_hash:#x}, fetched for block {block_number}, hashes to {computed:#x}")]
+    ClassHashMismatch { block_number: u64, class_hash: FieldElement, computed: FieldElement },
+    #[error("block {block_number} hashes to {computed:#x}, but the trusted checkpoint is {expected:#x}")]
+    CheckpointMismatch { block_number: u64, expected: FieldElement, computed: FieldElement },
+    #[error(
+        "block {block_number} has parent hash {parent_block_hash:#x}, but the previous block hashes to {expected:#x}"
+    )]
+    ParentHashMismatch { block_number: u64, parent_block_hash: FieldElement, expected: FieldElement },
+    #[error("failed to import the blocks of {}: {reason}", path.display())]
+    Import { path: PathBuf, reason: String },
+    #[error("fetched blocks out of order: {0}")]
+    Sequencing(#[from] SequencingError),
+    #[error("block {block_number} not applied: the {worker} stopped")]
+    WorkerStopped { block_number: u64, worker: &'static str },
+    #[error("failed to create block {block_number}: {reason}")]
+    BlockCreation { block_number: u64, reason: String },
+    #[error("the chain was reorganized more than {max_depth} blocks below the local head {head}")]
+    ReorgTooDeep { head: u64, max_depth: u64 },
+    #[error("failed to revert the local chain to block {common_ancestor}: {reason}")]
+    Revert { common_ancestor: u64, reason: String },
+}
+
+impl SyncError {
+    /// Whether the gateway does not know the requested block yet, i.e. the sync reached the tip of
+    /// the chain.
+    pub fn is_block_not_found(&self) -> bool {
+        matches!(self, SyncError::Gateway(ProviderError::StarknetError(StarknetError::BlockNotFound)))
+    }
+
+    /// Whether the same request may succeed later. Errors returned by the gateway itself and
+    /// malformed data are not expected to go away.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, SyncError::Gateway(e) if !matches!(e, ProviderError::StarknetError(_)))
+    }
+}
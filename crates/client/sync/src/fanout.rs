@@ -0,0 +1,430 @@
+//! Fan-out of the notifications of the sync, such as the [head events](crate::head) and the
+//! [progress events](crate::progress), to their subscribers.
+//!
+//! Each subscriber of a [`FanOut`] gets its own bounded queue: a consumer which stopped reading,
+//! e.g. a stalled `/heads` client, holds at most a queue worth of notifications and does not hold
+//! back the other subscribers. When the queue of a subscriber is full, the
+//! [`SlowSubscriberPolicy`] either drops its oldest notification or disconnects it. Dropped
+//! notifications and disconnected subscribers are counted in the metrics, by stream.
+//!
+//! The subscriptions made on behalf of a [`Client`] count against its [`SubscriberLimits`], across
+//! all the streams: a client opening too many subscriptions on one connection, or from one
+//! identity, is refused with a [`SubscribeError`] telling which limit it hit.
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+use lazy_static::lazy_static;
+use prometheus_endpoint::{register, CounterVec, Opts, PrometheusError, Registry, U64};
+use thiserror::Error;
+use tokio::sync::Notify;
+
+static METRICS: OnceLock<FanOutMetrics> = OnceLock::new();
+static CONFIG: OnceLock<FanOutConfig> = OnceLock::new();
+static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    static ref ACTIVE: Mutex<ActiveSubscriptions> = Mutex::new(ActiveSubscriptions::default());
+}
+
+/// Prometheus metrics of the notification fan-out.
+struct FanOutMetrics {
+    dropped: CounterVec<U64>,
+    disconnected: CounterVec<U64>,
+    refused: CounterVec<U64>,
+}
+
+/// Registers the notification fan-out metrics in `registry`.
+pub fn register_metrics(registry: &Registry) -> Result<(), PrometheusError> {
+    let dropped = register(
+        CounterVec::new(
+            Opts::new("deoxys_notifications_dropped_total", "Number of notifications dropped for slow subscribers"),
+            &["stream"],
+        )?,
+        registry,
+    )?;
+    let disconnected = register(
+        CounterVec::new(
+            Opts::new(
+                "deoxys_notification_subscribers_disconnected_total",
+                "Number of subscribers disconnected for falling behind",
+            ),
+            &["stream"],
+        )?,
+        registry,
+    )?;
+    let refused = register(
+        CounterVec::new(
+            Opts::new(
+                "deoxys_notification_subscriptions_refused_total",
+                "Number of subscriptions refused for going over a subscriber limit",
+            ),
+            &["stream", "limit"],
+        )?,
+        registry,
+    )?;
+    let _ = METRICS.set(FanOutMetrics { dropped, disconnected, refused });
+    Ok(())
+}
+
+/// What to do with a subscriber whose queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlowSubscriberPolicy {
+    /// Drop the oldest notification of the queue to make room for the new one.
+    #[default]
+    DropOldest,
+    /// Disconnect the subscriber, which receives the notifications already queued.
+    Disconnect,
+}
+
+/// Maximum number of active subscriptions of a single client, across all the fan-outs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SubscriberLimits {
+    /// Per connection, unlimited if `None`.
+    pub per_connection: Option<usize>,
+    /// Per identity, i.e. per IP address, unlimited if `None`.
+    pub per_identity: Option<usize>,
+}
+
+/// Queue capacity, slow subscriber policy and subscriber limits of all the fan-outs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FanOutConfig {
+    /// Capacity of the queue of each subscriber, the default of each stream if `None`.
+    pub queue_capacity: Option<usize>,
+    pub policy: SlowSubscriberPolicy,
+    pub limits: SubscriberLimits,
+}
+
+/// Sets the configuration of the fan-outs, which applies to the subscriptions made afterwards.
+/// Only the first configuration is kept.
+pub fn configure(config: FanOutConfig) {
+    let _ = CONFIG.set(config);
+}
+
+/// Identifies a connection of a client, such as a TCP connection, which may carry several
+/// subscriptions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectionId(u64);
+
+impl ConnectionId {
+    /// A new identifier, distinct from all the previous ones.
+    pub fn next() -> Self {
+        Self(NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// The client a subscription is made for, which the [`SubscriberLimits`] apply to.
+///
+/// The streams are not authenticated, so clients are identified by their IP address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Client {
+    pub connection: ConnectionId,
+    pub identity: IpAddr,
+}
+
+/// Why a subscription was refused.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SubscribeError {
+    #[error("too many subscriptions on this connection, at most {limit} are allowed")]
+    ConnectionLimit { limit: usize },
+    #[error("too many subscriptions from {identity}, at most {limit} are allowed")]
+    IdentityLimit { identity: IpAddr, limit: usize },
+}
+
+impl SubscribeError {
+    /// Stable code of the error, returned to the clients along with its message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SubscribeError::ConnectionLimit { .. } => "connection_subscription_limit",
+            SubscribeError::IdentityLimit { .. } => "identity_subscription_limit",
+        }
+    }
+}
+
+/// Number of active subscriptions of the clients, by connection and by identity.
+#[derive(Default)]
+struct ActiveSubscriptions {
+    by_connection: HashMap<ConnectionId, usize>,
+    by_identity: HashMap<IpAddr, usize>,
+}
+
+/// Counts a subscription of `client` until it is dropped.
+struct Admission {
+    client: Client,
+}
+
+impl Admission {
+    /// Counts a new subscription of `client`, unless it would go over `limits`.
+    fn new(client: Client, limits: SubscriberLimits) -> Result<Self, SubscribeError> {
+        let mut active = ACTIVE.lock().expect("Failed to acquire lock on active subscriptions");
+        let connection = active.by_connection.get(&client.connection).copied().unwrap_or(0);
+        let identity = active.by_identity.get(&client.identity).copied().unwrap_or(0);
+        if let Some(limit) = limits.per_connection.filter(|limit| connection >= *limit) {
+            return Err(SubscribeError::ConnectionLimit { limit });
+        }
+        if let Some(limit) = limits.per_identity.filter(|limit| identity >= *limit) {
+            return Err(SubscribeError::IdentityLimit { identity: client.identity, limit });
+        }
+        *active.by_connection.entry(client.connection).or_default() += 1;
+        *active.by_identity.entry(client.identity).or_default() += 1;
+        Ok(Self { client })
+    }
+}
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        let mut active = ACTIVE.lock().expect("Failed to acquire lock on active subscriptions");
+        release(&mut active.by_connection, self.client.connection);
+        release(&mut active.by_identity, self.client.identity);
+    }
+}
+
+/// Decrements the count of `key`, forgetting it once it has no subscription left.
+fn release<K: std::hash::Hash + Eq>(counts: &mut HashMap<K, usize>, key: K) {
+    if let Entry::Occupied(mut count) = counts.entry(key) {
+        *count.get_mut() -= 1;
+        if *count.get() == 0 {
+            count.remove();
+        }
+    }
+}
+
+/// The queue of a subscriber.
+struct Queue<T> {
+    notifications: VecDeque<T>,
+    /// Number of notifications dropped since the last one received.
+    dropped: u64,
+    disconnected: bool,
+}
+
+struct Subscriber<T> {
+    capacity: usize,
+    policy: SlowSubscriberPolicy,
+    queue: Mutex<Queue<T>>,
+    published: Notify,
+}
+
+/// Sends each notification published on a stream to all its subscribers.
+pub struct FanOut<T> {
+    /// Name of the stream, used in the logs and metrics.
+    stream: &'static str,
+    default_capacity: usize,
+    subscribers: Mutex<Vec<Weak<Subscriber<T>>>>,
+}
+
+impl<T: Clone> FanOut<T> {
+    pub const fn new(stream: &'static str, default_capacity: usize) -> Self {
+        Self { stream, default_capacity, subscribers: Mutex::new(Vec::new()) }
+    }
+
+    /// Subscribes to the notifications published after this call, with the configured queue
+    /// capacity and policy.
+    pub fn subscribe(&self) -> Subscription<T> {
+        let config = CONFIG.get().copied().unwrap_or_default();
+        self.subscribe_with(config.queue_capacity.unwrap_or(self.default_capacity), config.policy)
+    }
+
+    /// Subscribes on behalf of `client`, as [`subscribe`](Self::subscribe) does, unless it would
+    /// go over the configured subscriber limits. The subscription counts against them until it
+    /// is dropped.
+    pub fn subscribe_as(&self, client: Client) -> Result<Subscription<T>, SubscribeError> {
+        let config = CONFIG.get().copied().unwrap_or_default();
+        let capacity = config.queue_capacity.unwrap_or(self.default_capacity);
+        self.subscribe_limited(client, config.limits, capacity, config.policy)
+    }
+
+    fn subscribe_limited(
+        &self,
+        client: Client,
+        limits: SubscriberLimits,
+        capacity: usize,
+        policy: SlowSubscriberPolicy,
+    ) -> Result<Subscription<T>, SubscribeError> {
+        let admission = Admission::new(client, limits).map_err(|e| {
+            log::debug!("Refused a {} subscription from {}: {e}", self.stream, client.identity);
+            self.count_refused(&e);
+            e
+        })?;
+        let mut subscription = self.subscribe_with(capacity, policy);
+        subscription._admission = Some(admission);
+        Ok(subscription)
+    }
+
+    fn subscribe_with(&self, capacity: usize, policy: SlowSubscriberPolicy) -> Subscription<T> {
+        let subscriber = Arc::new(Subscriber {
+            capacity: capacity.max(1),
+            policy,
+            queue: Mutex::new(Queue { notifications: VecDeque::new(), dropped: 0, disconnected: false }),
+            published: Notify::new(),
+        });
+        self.subscribers.lock().expect("Failed to acquire lock on subscribers").push(Arc::downgrade(&subscriber));
+        Subscription { stream: self.stream, subscriber, _admission: None }
+    }
+
+    /// Queues `notification` for each subscriber.
+    pub fn publish(&self, notification: T) {
+        let mut subscribers = self.subscribers.lock().expect("Failed to acquire lock on subscribers");
+        subscribers.retain(|subscriber| {
+            // dropped subscriptions
+            let Some(subscriber) = subscriber.upgrade() else {
+                return false;
+            };
+            let mut queue = subscriber.queue.lock().expect("Failed to acquire lock on subscriber queue");
+
+            if queue.notifications.len() >= subscriber.capacity {
+                self.count(|metrics| &metrics.dropped);
+                match subscriber.policy {
+                    SlowSubscriberPolicy::DropOldest => {
+                        queue.notifications.pop_front();
+                        queue.dropped += 1;
+                    }
+                    SlowSubscriberPolicy::Disconnect => {
+                        log::warn!("Disconnecting a {} subscriber which fell behind", self.stream);
+                        self.count(|metrics| &metrics.disconnected);
+                        queue.disconnected = true;
+                        drop(queue);
+                        subscriber.published.notify_one();
+                        return false;
+                    }
+                }
+            }
+            queue.notifications.push_back(notification.clone());
+            drop(queue);
+            subscriber.published.notify_one();
+            true
+        });
+    }
+
+    fn count(&self, counter: impl FnOnce(&FanOutMetrics) -> &CounterVec<U64>) {
+        if let Some(metrics) = METRICS.get() {
+            counter(metrics).with_label_values(&[self.stream]).inc();
+        }
+    }
+
+    fn count_refused(&self, e: &SubscribeError) {
+        if let Some(metrics) = METRICS.get() {
+            let limit = match e {
+                SubscribeError::ConnectionLimit { .. } => "connection",
+                SubscribeError::IdentityLimit { .. } => "identity",
+            };
+            metrics.refused.with_label_values(&[self.stream, limit]).inc();
+        }
+    }
+}
+
+/// The notifications of a [`FanOut`] published after the subscription.
+pub struct Subscription<T> {
+    stream: &'static str,
+    subscriber: Arc<Subscriber<T>>,
+    /// Counts the subscription against the limits of its client, if made on behalf of one.
+    _admission: Option<Admission>,
+}
+
+impl<T> Subscription<T> {
+    /// Waits for the next notification, `None` once the subscriber was disconnected for falling
+    /// behind and received the notifications queued before.
+    pub async fn next(&mut self) -> Option<T> {
+        loop {
+            {
+                let mut queue = self.subscriber.queue.lock().expect("Failed to acquire lock on subscriber queue");
+                if let Some(notification) = queue.notifications.pop_front() {
+                    if queue.dropped > 0 {
+                        log::warn!(
+                            "A {} subscriber is lagging behind, skipped {} notifications",
+                            self.stream,
+                            queue.dropped
+                        );
+                        queue.dropped = 0;
+                    }
+                    return Some(notification);
+                }
+                if queue.disconnected {
+                    return None;
+                }
+            }
+            // a notification published in between leaves a permit, the wait then returns at once
+            self.subscriber.published.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn slow_subscribers_miss_the_oldest_notifications() {
+        let fanout = FanOut::new("test", 2);
+        let mut slow = fanout.subscribe_with(2, SlowSubscriberPolicy::DropOldest);
+        let mut fast = fanout.subscribe_with(8, SlowSubscriberPolicy::DropOldest);
+
+        for notification in 1..=3 {
+            fanout.publish(notification);
+        }
+
+        assert_eq!(slow.next().await, Some(2));
+        assert_eq!(slow.next().await, Some(3));
+        for notification in 1..=3 {
+            assert_eq!(fast.next().await, Some(notification));
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_subscribers_are_disconnected() {
+        let fanout = FanOut::new("test", 2);
+        let mut slow = fanout.subscribe_with(2, SlowSubscriberPolicy::Disconnect);
+
+        for notification in 1..=3 {
+            fanout.publish(notification);
+        }
+        fanout.publish(4);
+
+        assert_eq!(slow.next().await, Some(1));
+        assert_eq!(slow.next().await, Some(2));
+        assert_eq!(slow.next().await, None);
+        assert!(fanout.subscribers.lock().unwrap().is_empty());
+    }
+
+    fn client(connection: ConnectionId, identity: [u8; 4]) -> Client {
+        Client { connection, identity: IpAddr::from(identity) }
+    }
+
+    #[test]
+    fn subscriptions_are_limited_per_connection() {
+        let fanout = FanOut::<u64>::new("test", 2);
+        let limits = SubscriberLimits { per_connection: Some(2), per_identity: None };
+        let connection = ConnectionId::next();
+        let subscribe = |client| fanout.subscribe_limited(client, limits, 2, SlowSubscriberPolicy::DropOldest);
+
+        let first = subscribe(client(connection, [10, 0, 0, 1])).unwrap();
+        let _second = subscribe(client(connection, [10, 0, 0, 1])).unwrap();
+        assert_eq!(
+            subscribe(client(connection, [10, 0, 0, 1])).err(),
+            Some(SubscribeError::ConnectionLimit { limit: 2 })
+        );
+        // other connections of the same client are not affected
+        assert!(subscribe(client(ConnectionId::next(), [10, 0, 0, 1])).is_ok());
+
+        drop(first);
+        assert!(subscribe(client(connection, [10, 0, 0, 1])).is_ok());
+    }
+
+    #[test]
+    fn subscriptions_are_limited_per_identity() {
+        let fanout = FanOut::<u64>::new("test", 2);
+        let limits = SubscriberLimits { per_connection: None, per_identity: Some(1) };
+        let subscribe = |client| fanout.subscribe_limited(client, limits, 2, SlowSubscriberPolicy::DropOldest);
+
+        let first = subscribe(client(ConnectionId::next(), [10, 0, 0, 2])).unwrap();
+        assert_eq!(
+            subscribe(client(ConnectionId::next(), [10, 0, 0, 2])).err(),
+            Some(SubscribeError::IdentityLimit { identity: IpAddr::from([10, 0, 0, 2]), limit: 1 })
+        );
+        assert!(subscribe(client(ConnectionId::next(), [10, 0, 0, 3])).is_ok());
+
+        drop(first);
+        assert!(subscribe(client(ConnectionId::next(), [10, 0, 0, 2])).is_ok());
+    }
+}
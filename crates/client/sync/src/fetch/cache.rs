@@ -0,0 +1,142 @@
+//! On-disk cache of the feeder gateway responses.
+//!
+//! Syncing from genesis takes days, mostly spent waiting for the feeder gateway. With a cache
+//! directory set, the blocks, state updates and classes fetched from the gateway are also written
+//! to disk, so that a resync (e.g. after a database corruption) replays them at disk speed.
+//!
+//! Only blocks accepted on L1 are cached, as the others may still be reverted. Classes are cached
+//! by hash, their definition can't change. Entries are written to a temporary file first and then
+//! renamed, so that an interrupted write never leaves a truncated entry behind.
+//!
+//! ```text
+//! <dir>/blocks/<block_number / 10000>/<block_number>.block.json
+//! <dir>/blocks/<block_number / 10000>/<block_number>.state_update.json
+//! <dir>/classes/<class_hash>.json
+//! ```
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use starknet_core::types::ContractClass;
+use starknet_ff::FieldElement;
+use starknet_providers::sequencer::models::{Block, BlockStatus, StateUpdate};
+
+/// Number of blocks stored per subdirectory, to keep directories reasonably small.
+const BLOCKS_PER_DIR: u64 = 10_000;
+
+/// A directory holding the responses of the feeder gateway.
+#[derive(Debug, Clone)]
+pub struct GatewayCache {
+    dir: PathBuf,
+}
+
+impl GatewayCache {
+    /// Opens the cache in `dir`, creating the directory if needed.
+    pub fn new(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(dir.join("blocks"))?;
+        fs::create_dir_all(dir.join("classes"))?;
+        Ok(Self { dir })
+    }
+
+    /// Returns the block and state update of `block_number`, if cached.
+    pub fn block(&self, block_number: u64) -> Option<(Block, StateUpdate)> {
+        let block = read(&self.block_path(block_number, "block"))?;
+        let state_update = read(&self.block_path(block_number, "state_update"))?;
+        Some((block, state_update))
+    }
+
+    /// Stores the block and state update of `block_number`, if the block is accepted on L1.
+    pub fn put_block(&self, block_number: u64, block: &Block, state_update: &StateUpdate) {
+        if block.status != BlockStatus::AcceptedOnL1 {
+            return;
+        }
+        // the state update is written first, a block entry alone is never read
+        write(&self.block_path(block_number, "state_update"), state_update);
+        write(&self.block_path(block_number, "block"), block);
+    }
+
+    /// Returns the definition of `class_hash`, if cached.
+    pub fn class(&self, class_hash: FieldElement) -> Option<ContractClass> {
+        read(&self.class_path(class_hash))
+    }
+
+    /// Stores the definition of `class_hash`.
+    pub fn put_class(&self, class_hash: FieldElement, class: &ContractClass) {
+        write(&self.class_path(class_hash), class);
+    }
+
+    fn block_path(&self, block_number: u64, kind: &str) -> PathBuf {
+        self.dir
+            .join("blocks")
+            .join((block_number / BLOCKS_PER_DIR).to_string())
+            .join(format!("{block_number}.{kind}.json"))
+    }
+
+    fn class_path(&self, class_hash: FieldElement) -> PathBuf {
+        self.dir.join("classes").join(format!("{class_hash:#x}.json"))
+    }
+}
+
+/// Reads a cache entry, treating unreadable entries as missing.
+fn read<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            log::warn!("⚠️ Failed to read gateway cache entry {}: {e}", path.display());
+            return None;
+        }
+    };
+
+    match serde_json::from_slice(&bytes) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            log::warn!("⚠️ Ignoring invalid gateway cache entry {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Writes a cache entry. Failures are logged and otherwise ignored, the cache is only an
+/// optimization.
+fn write<T: Serialize>(path: &Path, value: &T) {
+    let result = (|| -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(value)?)?;
+        fs::rename(&tmp, path)
+    })();
+
+    if let Err(e) = result {
+        log::warn!("⚠️ Failed to write gateway cache entry {}: {e}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    #[test]
+    fn entries_roundtrip_and_invalid_ones_are_ignored() {
+        let dir = std::env::temp_dir().join(format!("deoxys-gateway-cache-{}", std::process::id()));
+        let cache = GatewayCache::new(dir.clone()).unwrap();
+
+        let path = cache.block_path(123_456, "block");
+        assert!(path.ends_with("blocks/12/123456.block.json"));
+        assert_eq!(read::<Value>(&path), None);
+
+        write(&path, &json!({ "block_number": 123456 }));
+        assert_eq!(read::<Value>(&path), Some(json!({ "block_number": 123456 })));
+        assert!(!path.with_extension("json.tmp").exists());
+
+        fs::write(&path, b"{ \"block_num").unwrap();
+        assert_eq!(read::<Value>(&path), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+//! Compilation of the Sierra classes fetched from the feeder gateway, and quarantine of the classes
+//! which do not match the hashes declared on chain.
+//!
+//! The hash of the compiled class is declared on chain along with the Sierra class, and committed
+//! to in the state root. When the node compiles a class to a different hash, typically because its
+//! compiler version differs from the sequencer's, executing the class locally could diverge from
+//! the chain. Such classes are quarantined instead of stopping the sync:
+//!
+//! * the compiled class is fetched from the feeder gateway, and used if it matches the declared
+//!   hash. Otherwise the node keeps the class it compiled itself.
+//! * a [`QuarantinedClass`] report is stored in the database, and served by the
+//!   `deoxys_getQuarantinedClasses` admin RPC method.
+//! * the `deoxys_quarantined_classes_total` metric is incremented.
+//!
+//! Classes served by the feeder gateway which do not hash to their class hash are never stored:
+//! they are reported the same way, and rejected, see [`reject_served_class`].
+use std::sync::{Arc, OnceLock};
+
+use cairo_lang_starknet_classes::casm_contract_class::CasmContractClass;
+use mc_db::{DeoxysBackend, QuarantineReason, QuarantineResolution, QuarantinedClass};
+use mp_contract::class::ContractClassWrapper;
+use mp_contract::ContractAbi;
+use mp_convert::contract::{casm_compiled_class_hash, casm_from_compiled_class, from_casm_contract_class};
+use mp_felt::Felt252Wrapper;
+use mp_transactions::from_broadcasted_transactions::flattened_sierra_to_casm_contract_class;
+use prometheus_endpoint::{register, CounterVec, Opts, PrometheusError, Registry, U64};
+use starknet_api::core::{ClassHash, CompiledClassHash};
+use starknet_core::types::FlattenedSierraClass;
+use starknet_ff::FieldElement;
+use starknet_providers::sequencer::models::BlockId;
+
+use super::gateway::GatewayProvider;
+use crate::errors::{ConversionError, SyncError};
+
+static METRICS: OnceLock<CompilationMetrics> = OnceLock::new();
+
+/// Prometheus metrics of the compilation of Sierra classes.
+struct CompilationMetrics {
+    quarantined: CounterVec<U64>,
+}
+
+/// Registers the class compilation metrics in `registry`.
+pub fn register_metrics(registry: &Registry) -> Result<(), PrometheusError> {
+    let quarantined = register(
+        CounterVec::new(
+            Opts::new(
+                "deoxys_quarantined_classes_total",
+                "Number of classes which do not match their class hash or declared compiled class hash",
+            ),
+            &["resolution"],
+        )?,
+        registry,
+    )?;
+    let _ = METRICS.set(CompilationMetrics { quarantined });
+    Ok(())
+}
+
+/// Compiles a Sierra class, checking the result against `declared`, the compiled class hash
+/// declared on chain, if known.
+///
+/// `block_number` and `block_hash` identify the block in which the class was declared.
+pub(crate) async fn compile_sierra_class(
+    class_hash: FieldElement,
+    class: FlattenedSierraClass,
+    declared: Option<FieldElement>,
+    block_number: u64,
+    block_hash: FieldElement,
+    provider: &GatewayProvider,
+) -> Result<ContractClassWrapper, SyncError> {
+    let abi = ContractAbi::Sierra(class.abi.clone());
+    let mut casm = flattened_sierra_to_casm_contract_class(&Arc::new(class))
+        .map_err(|e| ConversionError::ContractClass(e.to_string()))?;
+
+    if let Some(declared) = declared {
+        let computed = casm_compiled_class_hash(&casm).map_err(|e| ConversionError::ContractClass(e.to_string()))?;
+        if computed != declared {
+            casm = quarantine(class_hash, casm, computed, declared, block_number, block_hash, provider).await;
+        }
+    }
+
+    let contract = from_casm_contract_class(casm).map_err(|e| ConversionError::ContractClass(e.to_string()))?;
+    Ok(ContractClassWrapper { contract, abi })
+}
+
+/// Reports a class compiled to `computed` instead of `declared`, and returns the compiled class to
+/// use for it.
+async fn quarantine(
+    class_hash: FieldElement,
+    local_casm: CasmContractClass,
+    computed: FieldElement,
+    declared: FieldElement,
+    block_number: u64,
+    block_hash: FieldElement,
+    provider: &GatewayProvider,
+) -> CasmContractClass {
+    let gateway_casm = match provider.get_compiled_class(BlockId::Hash(block_hash), class_hash).await {
+        Ok(compiled_class) => match compiled_class.class_hash() {
+            Ok(hash) if hash == declared => match casm_from_compiled_class(&compiled_class) {
+                Ok(casm) => Some(casm),
+                Err(e) => {
+                    log::warn!("⚠️ Failed to convert the compiled class {class_hash:#x} of the gateway: {e}");
+                    None
+                }
+            },
+            Ok(hash) => {
+                log::warn!("⚠️ The gateway compiled class {class_hash:#x} hashes to {hash:#x}, not {declared:#x}");
+                None
+            }
+            Err(e) => {
+                log::warn!("⚠️ Failed to hash the compiled class {class_hash:#x} of the gateway: {e}");
+                None
+            }
+        },
+        Err(e) => {
+            log::warn!("⚠️ Failed to fetch the compiled class {class_hash:#x} from the gateway: {e}");
+            None
+        }
+    };
+
+    let resolution =
+        if gateway_casm.is_some() { QuarantineResolution::GatewayCasm } else { QuarantineResolution::LocalCasm };
+    log::warn!(
+        "⚠️ Class {class_hash:#x} declared in block {block_number} compiles to {computed:#x} with compiler {}, \
+         instead of {declared:#x}, quarantined (using {})",
+        local_casm.compiler_version,
+        match resolution {
+            QuarantineResolution::GatewayCasm => "the gateway compiled class",
+            _ => "the local compiled class",
+        }
+    );
+
+    report(QuarantinedClass {
+        class_hash: ClassHash(Felt252Wrapper::from(class_hash).into()),
+        block_number,
+        reason: QuarantineReason::CompiledClassHash {
+            declared: CompiledClassHash(Felt252Wrapper::from(declared).into()),
+            computed: CompiledClassHash(Felt252Wrapper::from(computed).into()),
+            compiler_version: local_casm.compiler_version.clone(),
+        },
+        resolution,
+    });
+
+    gateway_casm.unwrap_or(local_casm)
+}
+
+/// Reports a class declared in block `block_number` whose definition, served by the feeder gateway,
+/// hashes to `computed` instead of `class_hash`, and returns the error rejecting it.
+pub(crate) fn reject_served_class(class_hash: FieldElement, computed: FieldElement, block_number: u64) -> SyncError {
+    report(QuarantinedClass {
+        class_hash: ClassHash(Felt252Wrapper::from(class_hash).into()),
+        block_number,
+        reason: QuarantineReason::ClassHash { computed: ClassHash(Felt252Wrapper::from(computed).into()) },
+        resolution: QuarantineResolution::Rejected,
+    });
+    SyncError::ClassHashMismatch { block_number, class_hash, computed }
+}
+
+/// Stores the quarantine report of a class and counts it in the metrics.
+fn report(report: QuarantinedClass) {
+    if let Err(e) = DeoxysBackend::class_quarantine().store(&report) {
+        log::error!("Failed to store the quarantine report of class {:#x}: {e}", report.class_hash.0);
+    }
+    if let Some(metrics) = METRICS.get() {
+        let label = match report.resolution {
+            QuarantineResolution::GatewayCasm => "gateway_casm",
+            QuarantineResolution::LocalCasm => "local_casm",
+            QuarantineResolution::Rejected => "rejected",
+        };
+        metrics.quarantined.with_label_values(&[label]).inc();
+    }
+}
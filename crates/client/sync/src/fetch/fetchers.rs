@@ -1,13 +1,17 @@
 //! Contains the code required to fetch data from the network efficiently.
-use core::time::Duration;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use itertools::Itertools;
+use mc_db::{ClassLengths, DeoxysBackend};
+use mc_p2p::P2pHandle;
 use mc_storage::OverrideHandle;
 use mp_block::DeoxysBlock;
 use mp_contract::class::{ContractClassData, ContractClassWrapper};
 use mp_felt::Felt252Wrapper;
 use mp_storage::StarknetStorageSchemaVersion;
+use mp_transactions::from_broadcasted_transactions::legacy_class_hash;
 use mp_types::block::DBlockT;
 use sp_blockchain::HeaderBackend;
 use sp_core::{H160, H256};
@@ -15,18 +19,33 @@ use sp_runtime::generic::{Block as RuntimeBlock, Header};
 use sp_runtime::traits::BlakeTwo256;
 use sp_runtime::OpaqueExtrinsic;
 use starknet_api::core::ClassHash;
-use starknet_core::types::BlockId as BlockIdCore;
+use starknet_core::types::{BlockId as BlockIdCore, ContractClass};
 use starknet_ff::FieldElement;
 use starknet_providers::sequencer::models as p;
 use starknet_providers::sequencer::models::state_update::{DeclaredContract, DeployedContract};
 use starknet_providers::sequencer::models::{BlockId, StateUpdate};
-use starknet_providers::{Provider, ProviderError, SequencerGatewayProvider};
 use tokio::task::JoinSet;
 use url::Url;
 
-use crate::l2::L2SyncError;
+use super::compile::{compile_sierra_class, reject_served_class};
+use super::gateway::GatewayProvider;
+use super::p2p::P2pFetcher;
+use crate::admission::AdmissionConfig;
+use crate::commitments::hashers::CommitmentHashers;
+use crate::disk_guard::DiskWatermark;
+use crate::errors::{ConversionError, SyncError};
+use crate::replication::PrimaryNode;
 use crate::utility::{block_hash_deoxys, block_hash_substrate};
 
+/// The URLs of a gateway.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GatewayUrls {
+    /// The URL of the sequencer gateway.
+    pub gateway: Url,
+    /// The URL of the feeder gateway.
+    pub feeder_gateway: Url,
+}
+
 /// The configuration of the worker responsible for fetching new blocks and state updates from the
 /// feeder.
 #[derive(Clone, Debug)]
@@ -43,13 +62,50 @@ pub struct FetchConfig {
     pub sound: bool,
     /// The L1 contract core address
     pub l1_core_address: H160,
-    /// Whether to check the root of the state update
+    /// Whether to commit the state diffs to the tries, whose roots are then checked against the
+    /// block headers by the verification of the node.
     pub verify: bool,
+    /// Whether the verification of the node also executes the transactions of the imported blocks
+    /// again, checking them against their receipts.
+    pub verify_execution: bool,
+    /// Whether to keep syncing blocks of Starknet versions newer than the ones supported by this
+    /// build, which is only sound when the state root is not verified.
+    pub force_unsupported: bool,
     /// The optional API_KEY to avoid rate limiting from the sequencer gateway.
     pub api_key: Option<String>,
+    /// The gateways to use when the primary one keeps failing, in order of preference.
+    pub fallback_gateways: Vec<GatewayUrls>,
+    /// The maximum number of requests per second sent to the gateway, unlimited if `None`.
+    pub gateway_rate_limit: Option<u32>,
+    /// The maximum number of fetched blocks waiting to be applied. Fetching pauses while the queue
+    /// is full, so that memory stays bounded when applying blocks falls behind.
+    pub block_queue_capacity: usize,
+    /// The directory where the gateway responses are cached, not cached if `None`.
+    pub gateway_cache: Option<PathBuf>,
+    /// The archives of blocks, produced by `export-blocks` or published as snapshots, imported in
+    /// order before syncing from the gateway.
+    pub import_archives: Vec<PathBuf>,
+    /// The primary node to follow, see [`crate::replication`]. Blocks are replicated from it
+    /// instead of being fetched from the gateway.
+    pub replicate_from: Option<PrimaryNode>,
+    /// The load thresholds above which the sync of historical blocks is slowed down.
+    pub admission: AdmissionConfig,
+    /// The hash functions used to compute the block commitments.
+    pub hashers: CommitmentHashers,
+    /// The maximum drift between the timestamp of the blocks at the tip of the chain and the local
+    /// clock before it is reported, see [`crate::timestamps`].
+    pub timestamp_drift_tolerance: Duration,
+    /// The free space to leave on the disks holding the database, see [`crate::disk_guard`].
+    pub disk_watermark: Option<DiskWatermark>,
+    /// The hash of the block up to which the block commitments are taken from the feeder gateway
+    /// instead of being computed, see [`crate::checkpoint`].
+    pub trusted_checkpoint: Option<FieldElement>,
+    /// The p2p service whose bootnodes the state diffs and classes of the blocks are pulled from
+    /// before falling back to the gateway, see [`super::p2p`].
+    pub p2p: Option<P2pHandle>,
 }
 
-pub async fn fetch_block(client: &SequencerGatewayProvider, block_number: u64) -> Result<p::Block, L2SyncError> {
+pub async fn fetch_block(client: &GatewayProvider, block_number: u64) -> Result<p::Block, SyncError> {
     let block = client.get_block(BlockId::Number(block_number)).await?;
 
     Ok(block)
@@ -57,69 +113,83 @@ pub async fn fetch_block(client: &SequencerGatewayProvider, block_number: u64) -
 
 pub async fn fetch_block_and_updates<C>(
     block_n: u64,
-    provider: Arc<SequencerGatewayProvider>,
+    provider: Arc<GatewayProvider>,
     overrides: Arc<OverrideHandle<RuntimeBlock<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
     client: Arc<C>,
-) -> Result<(p::Block, StateUpdate, Vec<ContractClassData>), L2SyncError>
+    p2p: Option<Arc<P2pFetcher>>,
+) -> Result<(p::Block, StateUpdate, Vec<ContractClassData>), SyncError>
 where
     C: HeaderBackend<DBlockT>,
 {
-    const MAX_RETRY: u32 = 15;
-    let mut attempt = 0;
-    let base_delay = Duration::from_secs(1);
-
-    loop {
-        log::debug!("fetch_block_and_updates {}", block_n);
-        let block = fetch_block(&provider, block_n);
-        let state_update = fetch_state_and_class_update(&provider, block_n, &overrides, client.as_ref());
-        let (block, state_update) = tokio::join!(block, state_update);
-        log::debug!("fetch_block_and_updates: done {block_n}");
-
-        match block.as_ref().err().or(state_update.as_ref().err()) {
-            Some(L2SyncError::Provider(ProviderError::RateLimited)) => {
-                log::info!("The fetching process has been rate limited");
-                log::debug!("The fetching process has been rate limited, retrying in {:?} seconds", base_delay);
-                attempt += 1;
-                if attempt >= MAX_RETRY {
-                    return Err(L2SyncError::FetchRetryLimit);
-                }
-                // Exponential backoff with a cap on the delay
-                let delay = base_delay * 2_u32.pow(attempt - 1).min(6); // Cap to prevent overly long delays
-                tokio::time::sleep(delay).await;
-            }
-            _ => {
-                let (block, (state_update, class_update)) = (block?, state_update?);
-                return Ok((block, state_update, class_update));
-            }
+    // rate limiting and retries are handled by the gateway provider
+    log::debug!("fetch_block_and_updates {}", block_n);
+    if let Some((block, state_update)) = provider.cache().and_then(|cache| cache.block(block_n)) {
+        log::debug!("fetch_block_and_updates: cached {block_n}");
+        let (state_update, class_update) =
+            with_class_update(&provider, state_update, &overrides, block_n, client.as_ref()).await?;
+        return Ok((block, state_update, class_update));
+    }
+
+    let (block, (state_update, class_update)) = match p2p {
+        // the state diffs of peers are checked against the block hash, so the block comes first
+        Some(p2p) => {
+            let block = fetch_block(&provider, block_n).await?;
+            let state_update = match p2p.state_update(&block).await {
+                Some(state_update) => state_update,
+                None => fetch_state_update(&provider, block_n).await?,
+            };
+            (block, with_class_update(&provider, state_update, &overrides, block_n, client.as_ref()).await?)
         }
+        None => {
+            let block = fetch_block(&provider, block_n);
+            let state_update = fetch_state_and_class_update(&provider, block_n, &overrides, client.as_ref());
+            let (block, state_update) = tokio::join!(block, state_update);
+            (block?, state_update?)
+        }
+    };
+    log::debug!("fetch_block_and_updates: done {block_n}");
+
+    if let Some(cache) = provider.cache() {
+        cache.put_block(block_n, &block, &state_update);
     }
+    Ok((block, state_update, class_update))
 }
 
 pub async fn fetch_apply_genesis_block(config: FetchConfig) -> Result<DeoxysBlock, String> {
-    let client = SequencerGatewayProvider::new(
-        config.gateway.clone(),
-        config.feeder_gateway.clone(),
-        config.chain_id,
-        config.api_key.clone(),
-    );
+    let client = GatewayProvider::new(&config, None);
     let block = client.get_block(BlockId::Number(0)).await.map_err(|e| format!("failed to get block: {e}"))?;
 
-    Ok(crate::convert::block(block).await)
+    crate::convert::block(block).await.map_err(|e| format!("failed to convert block: {e}"))
 }
 
 #[allow(clippy::too_many_arguments)]
 async fn fetch_state_and_class_update<C>(
-    provider: &SequencerGatewayProvider,
+    provider: &Arc<GatewayProvider>,
     block_number: u64,
     overrides: &Arc<OverrideHandle<RuntimeBlock<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
     client: &C,
-) -> Result<(StateUpdate, Vec<ContractClassData>), L2SyncError>
+) -> Result<(StateUpdate, Vec<ContractClassData>), SyncError>
+where
+    C: HeaderBackend<DBlockT>,
+{
+    let state_update = fetch_state_update(provider, block_number).await?;
+    with_class_update(provider, state_update, overrides, block_number, client).await
+}
+
+/// Fetches the classes declared by `state_update`.
+async fn with_class_update<C>(
+    provider: &Arc<GatewayProvider>,
+    state_update: StateUpdate,
+    overrides: &Arc<OverrideHandle<RuntimeBlock<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
+    block_number: u64,
+    client: &C,
+) -> Result<(StateUpdate, Vec<ContractClassData>), SyncError>
 where
     C: HeaderBackend<DBlockT>,
 {
     // Children tasks need StateUpdate as an Arc, because of task spawn 'static requirement
     // We make an Arc, and then unwrap the StateUpdate out of the Arc
-    let state_update = Arc::new(fetch_state_update(provider, block_number).await?);
+    let state_update = Arc::new(state_update);
     let class_update = fetch_class_update(provider, &state_update, overrides, block_number, client).await?;
     let state_update = Arc::try_unwrap(state_update).expect("arc should not be aliased");
 
@@ -127,10 +197,7 @@ where
 }
 
 /// retrieves state update from Starknet sequencer
-async fn fetch_state_update(
-    provider: &SequencerGatewayProvider,
-    block_number: u64,
-) -> Result<StateUpdate, L2SyncError> {
+async fn fetch_state_update(provider: &GatewayProvider, block_number: u64) -> Result<StateUpdate, SyncError> {
     let state_update = provider.get_state_update(BlockId::Number(block_number)).await?;
 
     Ok(state_update)
@@ -138,27 +205,32 @@ async fn fetch_state_update(
 
 /// retrieves class updates from Starknet sequencer
 async fn fetch_class_update<C>(
-    provider: &SequencerGatewayProvider,
+    provider: &Arc<GatewayProvider>,
     state_update: &Arc<StateUpdate>,
     overrides: &Arc<OverrideHandle<RuntimeBlock<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
     block_number: u64,
     client: &C,
-) -> Result<Vec<ContractClassData>, L2SyncError>
+) -> Result<Vec<ContractClassData>, SyncError>
 where
     C: HeaderBackend<DBlockT>,
 {
     // defaults to downloading ALL classes if a substrate block hash could not be determined
     let missing_classes = match block_hash_substrate(client, block_number) {
-        Some(block_hash_substrate) => fetch_missing_classes(state_update, overrides, block_hash_substrate),
+        Some(block_hash_substrate) => {
+            fetch_missing_classes(state_update, overrides, block_number, block_hash_substrate)
+        }
         None => aggregate_classes(state_update),
     };
 
-    let arc_provider = Arc::new(provider.clone());
     let mut task_set = missing_classes.into_iter().fold(JoinSet::new(), |mut set, class_hash| {
-        let provider = Arc::clone(&arc_provider);
+        let provider = Arc::clone(provider);
         let state_update = Arc::clone(state_update);
         let class_hash = *class_hash;
-        set.spawn(async move { fetch_class(class_hash, block_hash_deoxys(&state_update), &provider).await });
+        set.spawn(async move {
+            let compiled_class_hash = declared_compiled_class_hash(&state_update, class_hash);
+            fetch_class(class_hash, compiled_class_hash, block_number, block_hash_deoxys(&state_update), &provider)
+                .await
+        });
         set
     });
 
@@ -173,20 +245,123 @@ where
     Ok(classes)
 }
 
-/// Downloads a class definition from the Starknet sequencer. Note that because
-/// of the current type hell this needs to be converted into a blockifier equivalent
+/// Downloads a class definition from the Starknet sequencer, or reads it from the gateway cache.
+/// Note that because of the current type hell this needs to be converted into a blockifier
+/// equivalent
+///
+/// Class artifacts imported from another node are used as is, skipping both the download and the
+/// compilation. Classes which do not match their class hash are fetched again from the gateway,
+/// then rejected if they still don't, so that they never reach the class store. Sierra classes
+/// declared in the block are checked against their declared `compiled_class_hash`, see
+/// [`compile`](super::compile).
 async fn fetch_class(
     class_hash: FieldElement,
+    compiled_class_hash: Option<FieldElement>,
+    block_number: u64,
     block_hash: FieldElement,
-    provider: &SequencerGatewayProvider,
-) -> Result<ContractClassData, L2SyncError> {
+    provider: &GatewayProvider,
+) -> Result<ContractClassData, SyncError> {
+    let hash = ClassHash(Felt252Wrapper::from(class_hash).into());
+    match DeoxysBackend::class_artifact().get(&hash) {
+        Ok(Some(contract_class)) => return Ok(ContractClassData { hash, contract_class }),
+        Ok(None) => {}
+        Err(e) => log::warn!("⚠️ Failed to read the artifact of class {class_hash:#x}: {e}"),
+    }
+
+    let check_legacy = checks_legacy_class_hash(provider.chain_id(), block_number);
+    let cached = provider.cache().and_then(|cache| cache.class(class_hash));
+    let from = if cached.is_some() { "the gateway cache" } else { "the gateway" };
+    let mut core_class = match cached {
+        Some(core_class) => core_class,
+        None => fetch_class_definition(class_hash, block_hash, provider).await?,
+    };
+    if let Some(computed) = class_hash_mismatch(class_hash, &core_class, check_legacy)? {
+        log::warn!("⚠️ Class {class_hash:#x} from {from} hashes to {computed:#x}, fetching it again");
+        core_class = fetch_class_definition(class_hash, block_hash, provider).await?;
+        if let Some(computed) = class_hash_mismatch(class_hash, &core_class, check_legacy)? {
+            log::error!(
+                "❗ Class {class_hash:#x} declared in block {block_number} still hashes to {computed:#x} once fetched \
+                 again, rejected"
+            );
+            return Err(reject_served_class(class_hash, computed, block_number));
+        }
+    }
+
+    let contract_class = match core_class {
+        ContractClass::Sierra(class) => {
+            let lengths = ClassLengths {
+                sierra_program_length: class.sierra_program.len() as u64,
+                abi_length: class.abi.len() as u64,
+            };
+            if let Err(e) = DeoxysBackend::class().store_lengths(&hash, lengths) {
+                log::warn!("⚠️ Failed to store the lengths of class {class_hash:#x}: {e}");
+            }
+            compile_sierra_class(class_hash, class, compiled_class_hash, block_number, block_hash, provider).await?
+        }
+        core_class => {
+            ContractClassWrapper::try_from(core_class).map_err(|e| ConversionError::ContractClass(e.to_string()))?
+        }
+    };
+    Ok(ContractClassData { hash, contract_class })
+}
+
+/// Downloads a class definition from the Starknet sequencer, replacing it in the gateway cache.
+async fn fetch_class_definition(
+    class_hash: FieldElement,
+    block_hash: FieldElement,
+    provider: &GatewayProvider,
+) -> Result<ContractClass, SyncError> {
     let core_class = provider.get_class(BlockIdCore::Hash(block_hash), class_hash).await?;
-    Ok(ContractClassData {
-        hash: ClassHash(Felt252Wrapper::from(class_hash).into()),
-        // TODO: remove this expect when ContractClassWrapper::try_from does proper error handling using
-        // thiserror
-        contract_class: ContractClassWrapper::try_from(core_class).expect("converting contract class"),
-    })
+    if let Some(cache) = provider.cache() {
+        cache.put_class(class_hash, &core_class);
+    }
+    Ok(core_class)
+}
+
+/// Returns the hash of `class`, downloaded for `class_hash`, if it hashes to another class hash:
+/// the gateway, or its cache, served a tampered class.
+///
+/// The hash of legacy classes is only checked if `check_legacy` is set, see
+/// [`checks_legacy_class_hash`].
+fn class_hash_mismatch(
+    class_hash: FieldElement,
+    class: &ContractClass,
+    check_legacy: bool,
+) -> Result<Option<FieldElement>, SyncError> {
+    let computed = match class {
+        ContractClass::Sierra(class) => class.class_hash(),
+        ContractClass::Legacy(_) if !check_legacy => return Ok(None),
+        ContractClass::Legacy(class) => {
+            legacy_class_hash(class).map_err(|e| ConversionError::ContractClass(format!("{class_hash:#x}: {e}")))?
+        }
+    };
+    Ok(if computed != class_hash { Some(computed) } else { None })
+}
+
+/// The mainnet block from which the hash of legacy classes is checked. It comes after the blocks
+/// of the Starknet versions older than 0.10.0, whose classes were compiled with Cairo versions
+/// which the node does not always hash the way the sequencer did.
+const MAINNET_CHECKED_LEGACY_CLASSES_FROM: u64 = 10_000;
+
+/// Whether the hash of the legacy classes first fetched for block `block_number` of chain
+/// `chain_id` is checked.
+///
+/// This only depends on the chain configured by the operator and on the block the class is fetched
+/// for, never on the class itself, so that a gateway can't skip the check by tampering with it.
+/// The other networks started after Cairo 0.10, all of their classes are checked.
+fn checks_legacy_class_hash(chain_id: FieldElement, block_number: u64) -> bool {
+    let mainnet = FieldElement::from_byte_slice_be(b"SN_MAIN").expect("valid chain id");
+    chain_id != mainnet || block_number >= MAINNET_CHECKED_LEGACY_CLASSES_FROM
+}
+
+/// Returns the compiled class hash of `class_hash`, if it is declared in the state update.
+fn declared_compiled_class_hash(state_update: &StateUpdate, class_hash: FieldElement) -> Option<FieldElement> {
+    state_update
+        .state_diff
+        .declared_classes
+        .iter()
+        .find(|declared| declared.class_hash == class_hash)
+        .map(|declared| declared.compiled_class_hash)
 }
 
 /// Filters out class declarations in the Starknet sequencer state update
@@ -194,11 +369,14 @@ async fn fetch_class(
 fn fetch_missing_classes<'a>(
     state_update: &'a StateUpdate,
     overrides: &Arc<OverrideHandle<RuntimeBlock<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
+    block_number: u64,
     block_hash_substrate: H256,
 ) -> Vec<&'a FieldElement> {
     aggregate_classes(state_update)
         .into_iter()
-        .filter(|class_hash| is_missing_class(overrides, block_hash_substrate, Felt252Wrapper::from(**class_hash)))
+        .filter(|class_hash| {
+            is_missing_class(overrides, block_number, block_hash_substrate, Felt252Wrapper::from(**class_hash))
+        })
         .collect()
 }
 
@@ -227,14 +405,84 @@ fn aggregate_classes(state_update: &StateUpdate) -> Vec<&FieldElement> {
 /// Check if a class is stored in the local Substrate db.
 ///
 /// Since a change in class definition will result in a change in class hash,
-/// this means we only need to check for class hashes in the db.
+/// this means we only need to check for class hashes in the db. Classes loaded for the check are
+/// kept in the class cache shared with the RPC.
 fn is_missing_class(
     overrides: &Arc<OverrideHandle<RuntimeBlock<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
+    block_number: u64,
     block_hash_substrate: H256,
     class_hash: Felt252Wrapper,
 ) -> bool {
+    let class_hash = ClassHash::from(class_hash);
     overrides
-        .for_schema_version(&StarknetStorageSchemaVersion::Undefined)
-        .contract_class_by_class_hash(block_hash_substrate, ClassHash::from(class_hash))
+        .class_cache
+        .get_or_load(class_hash, block_number, || {
+            overrides
+                .for_schema_version(&StarknetStorageSchemaVersion::Undefined)
+                .contract_class_by_class_hash(block_hash_substrate, class_hash)
+        })
         .is_none()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use starknet_core::types::{
+        CompressedLegacyContractClass, EntryPointsByType, FlattenedSierraClass, LegacyEntryPointsByType,
+    };
+
+    use super::*;
+
+    fn legacy_class(program: &str) -> CompressedLegacyContractClass {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(program.as_bytes()).unwrap();
+        CompressedLegacyContractClass {
+            program: encoder.finish().unwrap(),
+            entry_points_by_type: LegacyEntryPointsByType { constructor: vec![], external: vec![], l1_handler: vec![] },
+            abi: None,
+        }
+    }
+
+    #[test]
+    fn legacy_hashes_are_checked_from_the_chain_and_block() {
+        let mainnet = FieldElement::from_byte_slice_be(b"SN_MAIN").unwrap();
+        let sepolia = FieldElement::from_byte_slice_be(b"SN_SEPOLIA").unwrap();
+
+        assert!(!checks_legacy_class_hash(mainnet, 0));
+        assert!(!checks_legacy_class_hash(mainnet, MAINNET_CHECKED_LEGACY_CLASSES_FROM - 1));
+        assert!(checks_legacy_class_hash(mainnet, MAINNET_CHECKED_LEGACY_CLASSES_FROM));
+        assert!(checks_legacy_class_hash(sepolia, 0));
+    }
+
+    #[test]
+    fn legacy_classes_are_checked_whatever_their_compiler_version() {
+        // a tampered class can't opt out of the check by claiming an old compiler
+        for program in [r#"{"data": []}"#, r#"{"compiler_version": "0.8.2"}"#] {
+            let class = ContractClass::Legacy(legacy_class(program));
+            assert!(!matches!(class_hash_mismatch(FieldElement::ONE, &class, true), Ok(None)));
+        }
+    }
+
+    #[test]
+    fn unchecked_legacy_classes_never_mismatch() {
+        let class = ContractClass::Legacy(legacy_class(r#"{"compiler_version": "0.8.2"}"#));
+        assert_eq!(class_hash_mismatch(FieldElement::ONE, &class, false).unwrap(), None);
+    }
+
+    #[test]
+    fn sierra_classes_are_checked_against_their_hash() {
+        let class = FlattenedSierraClass {
+            sierra_program: vec![FieldElement::ONE, FieldElement::TWO],
+            contract_class_version: "0.1.0".to_string(),
+            entry_points_by_type: EntryPointsByType { constructor: vec![], external: vec![], l1_handler: vec![] },
+            abi: String::new(),
+        };
+        let class_hash = class.class_hash();
+        let class = ContractClass::Sierra(class);
+
+        assert_eq!(class_hash_mismatch(class_hash, &class, false).unwrap(), None);
+        assert_eq!(class_hash_mismatch(FieldElement::ONE, &class, false).unwrap(), Some(class_hash));
+    }
+}
@@ -0,0 +1,594 @@
+//! A feeder gateway client with rate limiting, retries, metrics and source selection.
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use prometheus_endpoint::{
+    register, CounterVec, GaugeVec, HistogramOpts, HistogramVec, Opts, PrometheusError, Registry, F64, U64,
+};
+use starknet_core::types::contract::CompiledClass;
+use starknet_core::types::{BlockId as BlockIdCore, ContractClass};
+use starknet_ff::FieldElement;
+use starknet_providers::sequencer::models::{Block, BlockId, StateUpdate};
+use starknet_providers::{Provider, ProviderError, SequencerGatewayProvider};
+use url::Url;
+
+use super::cache::GatewayCache;
+use super::fetchers::FetchConfig;
+use super::resolver::EndpointResolver;
+use super::sources::{self, RequestKind};
+
+/// Upper bound for the delay between two retries.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// Names of the fallback gateway sources, in order of preference. Fallbacks past these are ignored.
+const FALLBACK_SOURCES: [&str; 4] = ["fallback", "fallback_2", "fallback_3", "fallback_4"];
+/// Interval between two health checks of the gateways which get no requests.
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// Time after which a health check is failed.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The feeder gateway endpoints used by the sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Endpoint {
+    GetBlock,
+    GetStateUpdate,
+    GetClass,
+    GetCompiledClass,
+    GetBlockIdByHash,
+}
+
+impl Endpoint {
+    fn name(&self) -> &'static str {
+        match self {
+            Endpoint::GetBlock => "get_block",
+            Endpoint::GetStateUpdate => "get_state_update",
+            Endpoint::GetClass => "get_class_by_hash",
+            Endpoint::GetCompiledClass => "get_compiled_class_by_class_hash",
+            Endpoint::GetBlockIdByHash => "get_block_id_by_hash",
+        }
+    }
+
+    /// The kind of the requests to this endpoint, for the selection of the gateway they are sent
+    /// to.
+    fn kind(&self) -> RequestKind {
+        match self {
+            Endpoint::GetBlock | Endpoint::GetStateUpdate => RequestKind::Block,
+            Endpoint::GetClass | Endpoint::GetCompiledClass => RequestKind::Class,
+            Endpoint::GetBlockIdByHash => RequestKind::Head,
+        }
+    }
+
+    /// How requests to this endpoint are retried on transient failures.
+    fn retry_policy(&self) -> RetryPolicy {
+        match self {
+            // blocks and state updates are on the critical path of the sync, be persistent
+            Endpoint::GetBlock | Endpoint::GetStateUpdate => {
+                RetryPolicy { max_retries: 15, base_delay: Duration::from_secs(1) }
+            }
+            // classes can be large, leave more time to the gateway
+            Endpoint::GetClass => RetryPolicy { max_retries: 10, base_delay: Duration::from_secs(2) },
+            // only fetched for quarantined classes, which can do without it
+            Endpoint::GetCompiledClass => RetryPolicy { max_retries: 3, base_delay: Duration::from_secs(2) },
+            // only used to poll the chain head, the next poll will retry anyway
+            Endpoint::GetBlockIdByHash => RetryPolicy { max_retries: 2, base_delay: Duration::from_millis(500) },
+        }
+    }
+}
+
+/// Exponential backoff retry policy.
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+}
+
+impl RetryPolicy {
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay.saturating_mul(2_u32.saturating_pow(attempt)).min(MAX_RETRY_DELAY)
+    }
+}
+
+/// Token bucket limiting the rate of requests sent to the gateway.
+struct TokenBucket {
+    capacity: f64,
+    refill_per_second: f64,
+    /// Available tokens, as of the given instant.
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(requests_per_second: u32) -> Self {
+        let capacity = f64::from(requests_per_second.max(1));
+        Self { capacity, refill_per_second: capacity, state: Mutex::new((capacity, Instant::now())) }
+    }
+
+    /// Takes a token, returning how long to wait before the request can be sent.
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut state = self.state.lock().expect("Failed to acquire lock on token bucket");
+        let (tokens, last_refill) = &mut *state;
+
+        let elapsed = now.saturating_duration_since(*last_refill).as_secs_f64();
+        *tokens = (*tokens + elapsed * self.refill_per_second).min(self.capacity) - 1.0;
+        *last_refill = now;
+
+        if *tokens >= 0.0 { Duration::ZERO } else { Duration::from_secs_f64(-*tokens / self.refill_per_second) }
+    }
+
+    async fn acquire(&self) {
+        let wait = self.reserve(Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Prometheus metrics of the requests sent to the gateway.
+#[derive(Clone)]
+pub struct GatewayMetrics {
+    requests: CounterVec<U64>,
+    request_duration: HistogramVec,
+    failovers: CounterVec<U64>,
+    source_score: GaugeVec<F64>,
+    source_head: GaugeVec<U64>,
+    health_checks: CounterVec<U64>,
+    blocks_served: CounterVec<U64>,
+    last_served_block: GaugeVec<U64>,
+}
+
+impl GatewayMetrics {
+    pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+        Ok(Self {
+            requests: register(
+                CounterVec::new(
+                    Opts::new("deoxys_gateway_requests_total", "Number of requests sent to the feeder gateway"),
+                    &["endpoint", "gateway", "outcome"],
+                )?,
+                registry,
+            )?,
+            request_duration: register(
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "deoxys_gateway_request_duration_seconds",
+                        "Duration of the requests sent to the feeder gateway",
+                    ),
+                    &["endpoint"],
+                )?,
+                registry,
+            )?,
+            failovers: register(
+                CounterVec::new(
+                    Opts::new(
+                        "deoxys_gateway_failovers_total",
+                        "Number of switches of the gateway requests are sent to",
+                    ),
+                    &["endpoint"],
+                )?,
+                registry,
+            )?,
+            source_score: register(
+                GaugeVec::new(
+                    Opts::new("deoxys_data_source_score", "Score of each data source for each kind of request"),
+                    &["source", "request"],
+                )?,
+                registry,
+            )?,
+            source_head: register(
+                GaugeVec::new(
+                    Opts::new("deoxys_data_source_head", "Highest block each data source is known to have"),
+                    &["source"],
+                )?,
+                registry,
+            )?,
+            health_checks: register(
+                CounterVec::new(
+                    Opts::new(
+                        "deoxys_gateway_health_checks_total",
+                        "Number of health checks of the gateways which get no requests",
+                    ),
+                    &["gateway", "outcome"],
+                )?,
+                registry,
+            )?,
+            blocks_served: register(
+                CounterVec::new(
+                    Opts::new("deoxys_gateway_blocks_served_total", "Number of blocks fetched from each gateway"),
+                    &["gateway"],
+                )?,
+                registry,
+            )?,
+            last_served_block: register(
+                GaugeVec::new(
+                    Opts::new("deoxys_gateway_last_served_block", "Number of the last block fetched from each gateway"),
+                    &["gateway"],
+                )?,
+                registry,
+            )?,
+        })
+    }
+}
+
+/// Whether the error is worth retrying: the gateway rate limited us or failed to answer properly
+/// (5xx responses, which the provider reports as network or deserialization errors).
+fn is_transient(error: &ProviderError) -> bool {
+    matches!(error, ProviderError::RateLimited | ProviderError::Other(_))
+}
+
+fn outcome(result: &Result<impl Sized, ProviderError>) -> &'static str {
+    match result {
+        Ok(_) => "success",
+        Err(ProviderError::RateLimited) => "rate_limited",
+        Err(ProviderError::Other(_)) => "server_error",
+        Err(_) => "error",
+    }
+}
+
+/// A range of blocks fetched from the same gateway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ServedRange {
+    source: usize,
+    first: u64,
+    last: u64,
+}
+
+impl ServedRange {
+    /// Adds block `block_n`, fetched from `source`, to the range `current`. Returns the previous
+    /// range if the block was fetched from another source, which starts a new range.
+    fn extend(current: &mut Option<ServedRange>, source: usize, block_n: u64) -> Option<ServedRange> {
+        match current {
+            // blocks are fetched concurrently, and so served out of order
+            Some(range) if range.source == source => {
+                range.first = range.first.min(block_n);
+                range.last = range.last.max(block_n);
+                None
+            }
+            current => current.replace(ServedRange { source, first: block_n, last: block_n }),
+        }
+    }
+}
+
+/// A gateway requests can be sent to.
+struct Gateway {
+    /// The index of its source in [`sources`].
+    source: usize,
+    provider: SequencerGatewayProvider,
+    /// The endpoint of its health checks.
+    is_alive: Url,
+}
+
+impl Gateway {
+    fn new(source: usize, feeder_gateway: &Url, provider: SequencerGatewayProvider) -> Self {
+        let is_alive = format!("{}/is_alive", feeder_gateway.as_str().trim_end_matches('/'))
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid feeder gateway URL {feeder_gateway}"));
+        Self { source, provider, is_alive }
+    }
+}
+
+/// A [`SequencerGatewayProvider`] wrapper used by the sync to talk to the feeder gateway.
+///
+/// * Requests are rate limited with a token bucket, shared by all the fetching tasks.
+/// * Transient failures are retried with exponential backoff, following per-endpoint policies.
+/// * Requests are sent to the primary or fallback gateway with the best score, see [`sources`]. The
+///   gateways which get no requests are health checked with [`GatewayProvider::probe_idle`], and
+///   the ranges of blocks fetched from each gateway are logged and exported as metrics.
+/// * Gateway hostnames are resolved with an [`EndpointResolver`], so that connections go to the
+///   fastest of their addresses.
+/// * Responses can be kept in a [`GatewayCache`], see [`GatewayProvider::cache`].
+pub struct GatewayProvider {
+    /// The gateways, primary first, then the fallbacks in order of preference.
+    gateways: Vec<Gateway>,
+    /// The indices of the sources of `gateways`.
+    sources: Vec<usize>,
+    client: reqwest::Client,
+    resolver: EndpointResolver,
+    rate_limiter: Option<TokenBucket>,
+    metrics: Option<GatewayMetrics>,
+    cache: Option<GatewayCache>,
+    /// The range of blocks fetched from the same gateway as the last block.
+    served: Mutex<Option<ServedRange>>,
+    /// The ID of the chain served by the gateways, as configured by the operator.
+    chain_id: FieldElement,
+}
+
+impl GatewayProvider {
+    pub fn new(config: &FetchConfig, metrics: Option<GatewayMetrics>) -> Self {
+        let resolver = EndpointResolver::new(config.feeder_gateway.port_or_known_default().unwrap_or(443));
+        let client = reqwest::Client::builder()
+            .dns_resolver(Arc::new(resolver.clone()))
+            .build()
+            .expect("Failed to build the gateway HTTP client");
+
+        let mut gateways = vec![Gateway::new(
+            sources::register("primary", config.feeder_gateway.as_str()),
+            &config.feeder_gateway,
+            SequencerGatewayProvider::new_with_client(
+                config.gateway.clone(),
+                config.feeder_gateway.clone(),
+                config.chain_id,
+                client.clone(),
+                config.api_key.clone(),
+            ),
+        )];
+        if config.fallback_gateways.len() > FALLBACK_SOURCES.len() {
+            log::warn!(
+                "⚠️ Only the first {} fallback gateways are used, {} were given",
+                FALLBACK_SOURCES.len(),
+                config.fallback_gateways.len()
+            );
+        }
+        for (name, urls) in FALLBACK_SOURCES.into_iter().zip(&config.fallback_gateways) {
+            gateways.push(Gateway::new(
+                sources::register(name, urls.feeder_gateway.as_str()),
+                &urls.feeder_gateway,
+                SequencerGatewayProvider::new_with_client(
+                    urls.gateway.clone(),
+                    urls.feeder_gateway.clone(),
+                    config.chain_id,
+                    client.clone(),
+                    None,
+                ),
+            ));
+        }
+
+        let cache = config.gateway_cache.as_ref().and_then(|dir| match GatewayCache::new(dir.clone()) {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                log::warn!("⚠️ Failed to open the gateway cache in {}, it is disabled: {e}", dir.display());
+                None
+            }
+        });
+
+        Self {
+            sources: gateways.iter().map(|gateway| gateway.source).collect(),
+            gateways,
+            client,
+            resolver,
+            rate_limiter: config.gateway_rate_limit.map(TokenBucket::new),
+            metrics,
+            cache,
+            served: Mutex::new(None),
+            chain_id: config.chain_id,
+        }
+    }
+
+    /// The ID of the chain served by the gateways, as configured by the operator rather than
+    /// reported by the gateways.
+    pub fn chain_id(&self) -> FieldElement {
+        self.chain_id
+    }
+
+    /// The on-disk cache of the gateway responses, if enabled. The provider itself does not use
+    /// it: the fetchers decide which responses are worth caching.
+    pub fn cache(&self) -> Option<&GatewayCache> {
+        self.cache.as_ref()
+    }
+
+    pub async fn get_block(&self, block_id: BlockId) -> Result<Block, ProviderError> {
+        let kind = head_or(block_id, Endpoint::GetBlock);
+        self.request(
+            Endpoint::GetBlock,
+            kind,
+            |block: &Block| block.block_number,
+            |provider| provider.get_block(block_id),
+        )
+        .await
+    }
+
+    pub async fn get_state_update(&self, block_id: BlockId) -> Result<StateUpdate, ProviderError> {
+        let kind = head_or(block_id, Endpoint::GetStateUpdate);
+        self.request(Endpoint::GetStateUpdate, kind, |_| None, |provider| provider.get_state_update(block_id)).await
+    }
+
+    pub async fn get_class(
+        &self,
+        block_id: BlockIdCore,
+        class_hash: FieldElement,
+    ) -> Result<ContractClass, ProviderError> {
+        self.request(
+            Endpoint::GetClass,
+            Endpoint::GetClass.kind(),
+            |_| None,
+            |provider| provider.get_class(block_id, class_hash),
+        )
+        .await
+    }
+
+    pub async fn get_compiled_class(
+        &self,
+        block_id: BlockId,
+        class_hash: FieldElement,
+    ) -> Result<CompiledClass, ProviderError> {
+        self.request(
+            Endpoint::GetCompiledClass,
+            Endpoint::GetCompiledClass.kind(),
+            |_| None,
+            |provider| provider.get_compiled_class_by_class_hash(class_hash, block_id),
+        )
+        .await
+    }
+
+    pub async fn get_block_id_by_hash(&self, block_hash: FieldElement) -> Result<u64, ProviderError> {
+        self.request(
+            Endpoint::GetBlockIdByHash,
+            Endpoint::GetBlockIdByHash.kind(),
+            |number: &u64| Some(*number),
+            |provider| provider.get_block_id_by_hash(block_hash),
+        )
+        .await
+    }
+
+    /// Sends a request of `kind` to the gateway with the best score, retrying transient failures.
+    /// `head_of` returns the block a response shows the gateway has, if any.
+    async fn request<'a, T, F, Fut>(
+        &'a self,
+        endpoint: Endpoint,
+        kind: RequestKind,
+        head_of: fn(&T) -> Option<u64>,
+        f: F,
+    ) -> Result<T, ProviderError>
+    where
+        F: Fn(&'a SequencerGatewayProvider) -> Fut,
+        Fut: Future<Output = Result<T, ProviderError>>,
+    {
+        let policy = endpoint.retry_policy();
+        let mut attempt = 0;
+
+        loop {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire().await;
+            }
+
+            let selection = sources::select(&self.sources, kind);
+            let gateway = sources::name(selection.index);
+            if let Some(previous) = selection.switched_from {
+                log::warn!(
+                    "🔁 Sending the {} requests to the {gateway} gateway instead of the {} one",
+                    kind.name(),
+                    sources::name(previous)
+                );
+                if let Some(metrics) = &self.metrics {
+                    metrics.failovers.with_label_values(&[endpoint.name()]).inc();
+                }
+            }
+            let provider = self
+                .gateways
+                .iter()
+                .find_map(|gateway| (gateway.source == selection.index).then_some(&gateway.provider))
+                .expect("Selected source should be one of the gateways");
+
+            let start = Instant::now();
+            let result = f(provider).await;
+            let elapsed = start.elapsed();
+
+            let transient = result.as_ref().err().is_some_and(is_transient);
+            if transient {
+                // the address we stick to may be the one failing, race them again on reconnection
+                self.resolver.reset();
+            }
+            sources::record(selection.index, elapsed, transient);
+            if let Some(head) = result.as_ref().ok().and_then(head_of) {
+                sources::record_head(selection.index, head);
+                // the head of a block response is the fetched block
+                if endpoint == Endpoint::GetBlock && kind == RequestKind::Block {
+                    self.record_served(selection.index, head);
+                }
+            }
+
+            if let Some(metrics) = &self.metrics {
+                metrics.requests.with_label_values(&[endpoint.name(), gateway, outcome(&result)]).inc();
+                metrics.request_duration.with_label_values(&[endpoint.name()]).observe(elapsed.as_secs_f64());
+                metrics.source_score.with_label_values(&[gateway, kind.name()]).set(sources::score(
+                    selection.index,
+                    &self.sources,
+                    kind,
+                ));
+                if let Some(head) = result.as_ref().ok().and_then(head_of) {
+                    metrics.source_head.with_label_values(&[gateway]).set(head);
+                }
+            }
+
+            match result {
+                Err(e) if transient && attempt < policy.max_retries => {
+                    let delay = policy.delay(attempt);
+                    log::debug!("{} request failed ({e}), retrying in {delay:?}", endpoint.name());
+                    attempt += 1;
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Health checks the gateways which get no requests, so that the ones which recovered from
+    /// their failures get requests again. Does nothing with a single gateway.
+    pub async fn probe_idle(&self) {
+        if self.gateways.len() < 2 {
+            return;
+        }
+
+        let idle = sources::idle(&self.sources);
+        for gateway in self.gateways.iter().filter(|gateway| idle.contains(&gateway.source)) {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire().await;
+            }
+            let name = sources::name(gateway.source);
+            let result = self.client.get(gateway.is_alive.clone()).timeout(HEALTH_CHECK_TIMEOUT).send().await;
+            let failed = match result {
+                Ok(response) if response.status().is_success() => false,
+                Ok(response) => {
+                    log::debug!("Health check of the {name} gateway failed with status {}", response.status());
+                    true
+                }
+                Err(e) => {
+                    log::debug!("Health check of the {name} gateway failed: {e}");
+                    true
+                }
+            };
+
+            sources::record_probe(gateway.source, failed);
+            if let Some(metrics) = &self.metrics {
+                let outcome = if failed { "failure" } else { "success" };
+                metrics.health_checks.with_label_values(&[name, outcome]).inc();
+            }
+        }
+    }
+
+    /// Records that block `block_n` was fetched from the source at `index`, logging the range of
+    /// blocks fetched from the previous gateway when it changes.
+    fn record_served(&self, index: usize, block_n: u64) {
+        let gateway = sources::name(index);
+        if let Some(metrics) = &self.metrics {
+            metrics.blocks_served.with_label_values(&[gateway]).inc();
+            metrics.last_served_block.with_label_values(&[gateway]).set(block_n);
+        }
+
+        let mut served = self.served.lock().expect("Failed to acquire lock on served blocks");
+        if let Some(previous) = ServedRange::extend(&mut served, index, block_n) {
+            log::info!(
+                "🔁 Blocks {} to {} were fetched from the {} gateway, the next ones from the {gateway} gateway",
+                previous.first,
+                previous.last,
+                sources::name(previous.source)
+            );
+        }
+    }
+}
+
+/// The kind of a request for `block_id` to `endpoint`: requests for the pending or latest block
+/// follow the chain head.
+fn head_or(block_id: BlockId, endpoint: Endpoint) -> RequestKind {
+    match block_id {
+        BlockId::Pending | BlockId::Latest => RequestKind::Head,
+        BlockId::Hash(_) | BlockId::Number(_) => endpoint.kind(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn served_ranges_follow_the_gateway_of_the_blocks() {
+        let mut current = None;
+        assert_eq!(ServedRange::extend(&mut current, 0, 10), None);
+        // fetched out of order
+        assert_eq!(ServedRange::extend(&mut current, 0, 12), None);
+        assert_eq!(ServedRange::extend(&mut current, 0, 11), None);
+        assert_eq!(current, Some(ServedRange { source: 0, first: 10, last: 12 }));
+
+        assert_eq!(ServedRange::extend(&mut current, 1, 13), Some(ServedRange { source: 0, first: 10, last: 12 }));
+        assert_eq!(current, Some(ServedRange { source: 1, first: 13, last: 13 }));
+    }
+
+    #[test]
+    fn token_bucket_delays_requests_over_rate() {
+        let bucket = TokenBucket::new(2);
+        let now = Instant::now();
+
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::from_millis(500));
+        // refilled
+        assert_eq!(bucket.reserve(now + Duration::from_secs(10)), Duration::ZERO);
+    }
+}
@@ -1 +1,7 @@
+pub mod cache;
+pub mod compile;
 pub mod fetchers;
+pub mod gateway;
+pub mod p2p;
+pub mod resolver;
+pub mod sources;
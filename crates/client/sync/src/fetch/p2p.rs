@@ -0,0 +1,361 @@
+//! Pulls the state diffs and classes of blocks from the bootnodes of the p2p subsystem, see
+//! [`mc_p2p`].
+//!
+//! The p2p streams served by Deoxys do not carry transactions yet, so blocks are still fetched from
+//! the feeder gateway. Their state diff and the classes they declare, most of the data of a block,
+//! are pulled from a bootnode instead, the gateway serving anything the bootnode does not.
+//!
+//! A state diff is only used if the hash of the block fetched from the gateway commits to it, which
+//! is the case from Starknet 0.13.2: older blocks are not pulled. Classes are served compiled, so
+//! their hash cannot be checked against their definition. Like the classes of imported archives
+//! they are trusted, which is why only the bootnodes chosen by the operator are pulled from, and
+//! only the classes declared in the checked state diff are kept.
+//!
+//! The p2p state diffs do not tell deployed contracts from replaced classes, both are reported as
+//! deployed contracts. The contracts which were already deployed before the block are moved to the
+//! replaced classes by [`split_replaced_classes`] as the block is committed, looking up their class
+//! in the flat storage: state diffs are only pulled once it holds all the blocks synced before.
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use mc_db::{DbError, DeoxysBackend};
+use mc_p2p::proto::state_diffs_response::StateDiffMessage;
+use mc_p2p::proto::{self, Direction};
+use mc_p2p::{P2pHandle, PeerId};
+use mp_block::state_update::StateDiffWrapper;
+use mp_contract::class::{ContractClassData, ContractClassWrapper};
+use mp_felt::Felt252Wrapper;
+use mp_hashers::pedersen::PedersenHasher;
+use parity_scale_codec::Decode;
+use starknet_api::core::{ClassHash, ContractAddress};
+use starknet_ff::FieldElement;
+use starknet_providers::sequencer::models as p;
+use starknet_providers::sequencer::models::state_update::{DeclaredContract, DeployedContract, StateDiff, StorageDiff};
+use starknet_providers::sequencer::models::StateUpdate;
+
+use crate::commitments::versions::has_v0_13_2_commitments;
+
+/// Pulls the state diffs and classes of blocks from the bootnodes, in turn.
+pub struct P2pFetcher {
+    handle: P2pHandle,
+    next_peer: AtomicUsize,
+}
+
+impl P2pFetcher {
+    /// Returns `None` if no bootnode address holds the peer id of the bootnode.
+    pub fn new(handle: P2pHandle) -> Option<Self> {
+        if handle.bootnode_peers().is_empty() {
+            return None;
+        }
+        Some(Self { handle, next_peer: AtomicUsize::new(0) })
+    }
+
+    fn peer(&self) -> PeerId {
+        let peers = self.handle.bootnode_peers();
+        peers[self.next_peer.fetch_add(1, Ordering::Relaxed) % peers.len()]
+    }
+
+    /// Pulls the state update of `block` from a bootnode, storing the artifacts of the classes it
+    /// declares so that they are not fetched from the gateway.
+    ///
+    /// Returns `None` if the block does not commit to its state diff, or the bootnode did not serve
+    /// the state diff it commits to.
+    pub async fn state_update(&self, block: &p::Block) -> Option<StateUpdate> {
+        let (block_number, block_hash) = (block.block_number?, block.block_hash?);
+        let version =
+            block.starknet_version.as_deref().and_then(|version| Felt252Wrapper::try_from(version.as_bytes()).ok());
+        if !version.is_some_and(|version| has_v0_13_2_commitments(&version)) {
+            return None;
+        }
+        // the replaced classes are told from the deployed contracts with the flat storage
+        if !DeoxysBackend::contract_storage().backfill_range().is_ok_and(|missing| missing.is_empty()) {
+            return None;
+        }
+
+        let peer = self.peer();
+        match self.pull(peer, block, block_number, block_hash).await {
+            Ok((state_update, classes)) => {
+                if let Err(e) = DeoxysBackend::class_artifact().store(&classes) {
+                    log::warn!("⚠️ Failed to store the classes of block {block_number} pulled from {peer}: {e}");
+                }
+                Some(state_update)
+            }
+            Err(e) => {
+                log::debug!(
+                    "Fetching the state update of block {block_number} from the gateway, {peer} did not serve it: {e}"
+                );
+                None
+            }
+        }
+    }
+
+    async fn pull(
+        &self,
+        peer: PeerId,
+        block: &p::Block,
+        block_number: u64,
+        block_hash: FieldElement,
+    ) -> Result<(StateUpdate, Vec<ContractClassData>), String> {
+        let iteration = |start_block, direction: Direction, limit| proto::Iteration {
+            start_block,
+            direction: direction as i32,
+            limit,
+            step: 1,
+        };
+        // the parent header holds the state root before the block
+        let (headers, messages, classes) = tokio::join!(
+            self.handle.headers(peer, iteration(block_number, Direction::Backward, 2)),
+            self.handle.state_diffs(peer, iteration(block_number, Direction::Forward, 1)),
+            self.handle.classes(peer, iteration(block_number, Direction::Forward, 1)),
+        );
+
+        let state_diff = state_diff(messages.map_err(|e| e.to_string())?)?;
+        check_state_diff(block, block_hash, &state_diff)?;
+
+        let old_root = match headers.map_err(|e| e.to_string())?.as_slice() {
+            [header, parent] if header.number == block_number => {
+                felt(parent.state_root.as_ref().map(|root| &root.elements))?
+            }
+            [header] if header.number == 0 => FieldElement::ZERO,
+            _ => {
+                return Err(format!(
+                    "missing the headers of blocks {}..={block_number}",
+                    block_number.saturating_sub(1)
+                ));
+            }
+        };
+        let classes = declared_classes(&state_diff, classes.map_err(|e| e.to_string())?)?;
+        Ok((StateUpdate { block_hash: Some(block_hash), new_root: block.state_root, old_root, state_diff }, classes))
+    }
+}
+
+/// Fails if `block_hash`, the hash of `block`, does not commit to `state_diff`.
+fn check_state_diff(block: &p::Block, block_hash: FieldElement, state_diff: &StateDiff) -> Result<(), String> {
+    let header =
+        crate::convert::trusted_header(block, &StateDiffWrapper::from(state_diff)).map_err(|e| e.to_string())?;
+    if header.state_diff_commitment.is_none() {
+        return Err("the block does not commit to its state diff".to_string());
+    }
+
+    if header.hash::<PedersenHasher>() != Felt252Wrapper::from(block_hash) {
+        return Err("the state diff does not match the block hash".to_string());
+    }
+    Ok(())
+}
+
+/// The state diff made of the `messages` of a state diffs stream.
+fn state_diff(messages: Vec<StateDiffMessage>) -> Result<StateDiff, String> {
+    let mut storage_diffs: HashMap<FieldElement, Vec<StorageDiff>> = HashMap::new();
+    let mut nonces = HashMap::new();
+    let mut deployed_contracts = Vec::new();
+    let mut old_declared_contracts = Vec::new();
+    let mut declared_classes = Vec::new();
+
+    for message in messages {
+        match message {
+            StateDiffMessage::ContractDiff(diff) => {
+                let address = felt(diff.address.as_ref().map(|address| &address.elements))?;
+                if let Some(nonce) = &diff.nonce {
+                    nonces.insert(address, felt(Some(&nonce.elements))?);
+                }
+                if let Some(class_hash) = &diff.class_hash {
+                    deployed_contracts
+                        .push(DeployedContract { address, class_hash: felt(Some(&class_hash.elements))? });
+                }
+                for value in &diff.values {
+                    let key = felt(value.key.as_ref().map(|key| &key.elements))?;
+                    let value = felt(value.value.as_ref().map(|value| &value.elements))?;
+                    storage_diffs.entry(address).or_default().push(StorageDiff { key, value });
+                }
+            }
+            StateDiffMessage::DeclaredClass(declared) => {
+                let class_hash = felt(declared.class_hash.as_ref().map(|class_hash| &class_hash.elements))?;
+                match &declared.compiled_class_hash {
+                    Some(compiled_class_hash) => declared_classes.push(DeclaredContract {
+                        class_hash,
+                        compiled_class_hash: felt(Some(&compiled_class_hash.elements))?,
+                    }),
+                    None => old_declared_contracts.push(class_hash),
+                }
+            }
+            StateDiffMessage::Fin(_) => {}
+        }
+    }
+
+    Ok(StateDiff {
+        storage_diffs,
+        deployed_contracts,
+        old_declared_contracts,
+        declared_classes,
+        nonces,
+        replaced_classes: Vec::new(),
+    })
+}
+
+/// Moves the contracts of `state_diff` which were deployed before `block_number` from its deployed
+/// contracts to its replaced classes.
+///
+/// The previous blocks must be in the flat storage. The deployed contracts of the state diffs
+/// fetched from the gateway are left as they are, since a contract is never deployed twice.
+pub fn split_replaced_classes(block_number: u64, state_diff: &mut StateDiffWrapper) -> Result<(), DbError> {
+    let Some(parent) = block_number.checked_sub(1) else {
+        return Ok(());
+    };
+    let storage = DeoxysBackend::contract_storage();
+    split_deployed(state_diff, |address| Ok(storage.class_hash_at(&ContractAddress::from(*address), parent)?.is_some()))
+}
+
+/// Moves the contracts of `state_diff` for which `was_deployed` holds to its replaced classes.
+fn split_deployed(
+    state_diff: &mut StateDiffWrapper,
+    was_deployed: impl Fn(&Felt252Wrapper) -> Result<bool, DbError>,
+) -> Result<(), DbError> {
+    let mut deployed = Vec::with_capacity(state_diff.deployed_contracts.len());
+    for contract in std::mem::take(&mut state_diff.deployed_contracts) {
+        if was_deployed(&contract.address)? {
+            state_diff.replaced_classes.push(contract);
+        } else {
+            deployed.push(contract);
+        }
+    }
+    state_diff.deployed_contracts = deployed;
+    Ok(())
+}
+
+/// The artifacts of `classes` declared in `state_diff`.
+fn declared_classes(state_diff: &StateDiff, classes: Vec<proto::Class>) -> Result<Vec<ContractClassData>, String> {
+    let is_declared = |class_hash: &FieldElement| {
+        state_diff.old_declared_contracts.contains(class_hash)
+            || state_diff.declared_classes.iter().any(|declared| declared.class_hash == *class_hash)
+    };
+
+    let mut declared = Vec::with_capacity(classes.len());
+    for class in classes {
+        let class_hash = felt(class.class_hash.as_ref().map(|class_hash| &class_hash.elements))?;
+        if !is_declared(&class_hash) {
+            return Err(format!("class {class_hash:#x} is not declared in the block"));
+        }
+        let contract_class = ContractClassWrapper::decode(&mut class.definition.as_slice())
+            .map_err(|e| format!("invalid class {class_hash:#x}: {e}"))?;
+        declared.push(ContractClassData { hash: ClassHash(Felt252Wrapper::from(class_hash).into()), contract_class });
+    }
+    Ok(declared)
+}
+
+fn felt(bytes: Option<&Vec<u8>>) -> Result<FieldElement, String> {
+    let bytes = bytes.ok_or("missing field element")?;
+    FieldElement::from_byte_slice_be(bytes).map_err(|_| format!("invalid field element {bytes:x?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use mp_block::state_update::DeployedContractWrapper;
+
+    use super::*;
+
+    fn bytes(value: u64) -> Vec<u8> {
+        FieldElement::from(value).to_bytes_be().to_vec()
+    }
+
+    fn contract_diff(
+        address: u64,
+        nonce: Option<u64>,
+        class_hash: Option<u64>,
+        values: &[(u64, u64)],
+    ) -> StateDiffMessage {
+        StateDiffMessage::ContractDiff(proto::ContractDiff {
+            address: Some(proto::Address { elements: bytes(address) }),
+            nonce: nonce.map(|nonce| proto::Felt252 { elements: bytes(nonce) }),
+            class_hash: class_hash.map(|class_hash| proto::Hash { elements: bytes(class_hash) }),
+            values: values
+                .iter()
+                .map(|(key, value)| proto::ContractStoredValue {
+                    key: Some(proto::Felt252 { elements: bytes(*key) }),
+                    value: Some(proto::Felt252 { elements: bytes(*value) }),
+                })
+                .collect(),
+        })
+    }
+
+    fn declared_class(class_hash: u64, compiled_class_hash: Option<u64>) -> StateDiffMessage {
+        StateDiffMessage::DeclaredClass(proto::DeclaredClass {
+            class_hash: Some(proto::Hash { elements: bytes(class_hash) }),
+            compiled_class_hash: compiled_class_hash.map(|hash| proto::Hash { elements: bytes(hash) }),
+        })
+    }
+
+    #[test]
+    fn state_diff_from_messages() {
+        let state_diff = state_diff(vec![
+            contract_diff(1, Some(2), None, &[(3, 4), (5, 6)]),
+            contract_diff(7, None, Some(8), &[]),
+            declared_class(9, Some(10)),
+            declared_class(11, None),
+            StateDiffMessage::Fin(proto::Fin {}),
+        ])
+        .unwrap();
+
+        let felt = FieldElement::from;
+        let storage_diffs = &state_diff.storage_diffs[&felt(1u64)];
+        assert_eq!(
+            storage_diffs.iter().map(|diff| (diff.key, diff.value)).collect::<Vec<_>>(),
+            [(felt(3u64), felt(4u64)), (felt(5u64), felt(6u64))]
+        );
+        assert_eq!(state_diff.storage_diffs.len(), 1);
+        assert_eq!(state_diff.nonces, HashMap::from([(felt(1u64), felt(2u64))]));
+        assert_eq!(
+            state_diff
+                .deployed_contracts
+                .iter()
+                .map(|contract| (contract.address, contract.class_hash))
+                .collect::<Vec<_>>(),
+            [(felt(7u64), felt(8u64))]
+        );
+        assert_eq!(
+            state_diff
+                .declared_classes
+                .iter()
+                .map(|class| (class.class_hash, class.compiled_class_hash))
+                .collect::<Vec<_>>(),
+            [(felt(9u64), felt(10u64))]
+        );
+        assert_eq!(state_diff.old_declared_contracts, [felt(11u64)]);
+    }
+
+    #[test]
+    fn malformed_state_diffs_are_rejected() {
+        let no_address = StateDiffMessage::ContractDiff(proto::ContractDiff { address: None, ..Default::default() });
+        assert!(state_diff(vec![no_address]).is_err());
+
+        let too_long = StateDiffMessage::DeclaredClass(proto::DeclaredClass {
+            class_hash: Some(proto::Hash { elements: vec![0xff; 33] }),
+            compiled_class_hash: None,
+        });
+        assert!(state_diff(vec![too_long]).is_err());
+    }
+
+    #[test]
+    fn contracts_deployed_before_the_block_are_replaced_classes() {
+        let mut state_diff = StateDiffWrapper::from(
+            &state_diff(vec![contract_diff(1, None, Some(2), &[]), contract_diff(3, None, Some(4), &[])]).unwrap(),
+        );
+
+        // contract 3 was deployed by a previous block, its class is replaced
+        split_deployed(&mut state_diff, |address| Ok(*address == Felt252Wrapper::from(3u64))).unwrap();
+        let contracts = |contracts: &[DeployedContractWrapper]| {
+            contracts.iter().map(|contract| (contract.address, contract.class_hash)).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            contracts(&state_diff.deployed_contracts),
+            [(Felt252Wrapper::from(1u64), Felt252Wrapper::from(2u64))]
+        );
+        assert_eq!(contracts(&state_diff.replaced_classes), [(Felt252Wrapper::from(3u64), Felt252Wrapper::from(4u64))]);
+    }
+
+    #[test]
+    fn undeclared_classes_are_rejected() {
+        let state_diff = state_diff(vec![declared_class(1, None)]).unwrap();
+        let class = proto::Class { class_hash: Some(proto::Hash { elements: bytes(2) }), definition: vec![] };
+        assert!(declared_classes(&state_diff, vec![class]).unwrap_err().contains("not declared"));
+    }
+}
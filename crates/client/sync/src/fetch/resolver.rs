@@ -0,0 +1,164 @@
+//! DNS resolution of the gateway hostnames, preferring the fastest of their addresses.
+//!
+//! Gateway hostnames resolve to several addresses, some of which can be much slower to reach
+//! depending on the region the node runs in. When a hostname is resolved, connections to all of
+//! its addresses are raced happy-eyeballs style: attempts are started one after the other, spaced
+//! by [`CONNECTION_ATTEMPT_DELAY`], and the first address to accept the connection wins. The
+//! winner then sticks for [`STICKINESS`], so that following connections go straight to it.
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use tokio::net::TcpStream;
+
+/// Delay between two connection attempts, as recommended by RFC 8305.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+/// Maximum time given to a connection attempt.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long the fastest address is preferred before the addresses are raced again.
+const STICKINESS: Duration = Duration::from_secs(5 * 60);
+
+/// Latency and preferred address of a hostname.
+#[derive(Debug, Default)]
+struct HostState {
+    /// Smoothed connection latency of each address. Failed attempts count as [`CONNECT_TIMEOUT`].
+    latencies: HashMap<IpAddr, Duration>,
+    /// The preferred address, and until when it is preferred.
+    sticky: Option<(IpAddr, Instant)>,
+}
+
+impl HostState {
+    fn record(&mut self, ip: IpAddr, latency: Option<Duration>) {
+        let sample = latency.unwrap_or(CONNECT_TIMEOUT);
+        self.latencies.entry(ip).and_modify(|latency| *latency = (*latency * 3 + sample) / 4).or_insert(sample);
+    }
+
+    fn sticky(&self, now: Instant) -> Option<IpAddr> {
+        self.sticky.filter(|(_, until)| now < *until).map(|(ip, _)| ip)
+    }
+
+    /// Orders the addresses from the most to the least promising: the preferred address first,
+    /// then by increasing latency. Addresses never tried before come right after the fast ones.
+    fn order(&self, mut ips: Vec<IpAddr>, now: Instant) -> Vec<IpAddr> {
+        let sticky = self.sticky(now);
+        ips.sort_by_key(|ip| {
+            (Some(*ip) != sticky, self.latencies.get(ip).copied().unwrap_or(CONNECTION_ATTEMPT_DELAY))
+        });
+        ips
+    }
+}
+
+/// A [`Resolve`] implementation keeping track of the latency of each address of the resolved
+/// hostnames, and sticking to the fastest one.
+#[derive(Clone)]
+pub struct EndpointResolver {
+    /// Port the connection attempts are made to.
+    probe_port: u16,
+    hosts: Arc<Mutex<HashMap<String, HostState>>>,
+}
+
+impl EndpointResolver {
+    pub fn new(probe_port: u16) -> Self {
+        Self { probe_port, hosts: Default::default() }
+    }
+
+    /// Forgets the preferred addresses, so that they are raced again on the next resolution.
+    ///
+    /// This is called when the gateway starts failing, in case the preferred address is the
+    /// culprit.
+    pub fn reset(&self) {
+        let mut hosts = self.hosts.lock().expect("Failed to acquire lock on resolver state");
+        hosts.values_mut().for_each(|host| host.sticky = None);
+    }
+
+    async fn resolve_host(&self, host: String) -> std::io::Result<Vec<SocketAddr>> {
+        let mut ips: Vec<IpAddr> =
+            tokio::net::lookup_host((host.as_str(), self.probe_port)).await?.map(|addr| addr.ip()).collect();
+        ips.dedup();
+
+        let (ips, sticky) = {
+            let hosts = self.hosts.lock().expect("Failed to acquire lock on resolver state");
+            let state = hosts.get(&host);
+            let now = Instant::now();
+            match state {
+                Some(state) => (state.order(ips, now), state.sticky(now)),
+                None => (ips, None),
+            }
+        };
+
+        if sticky.is_none() && ips.len() > 1 {
+            self.race(&host, &ips).await;
+        }
+
+        let hosts = self.hosts.lock().expect("Failed to acquire lock on resolver state");
+        let ips = match hosts.get(&host) {
+            Some(state) => state.order(ips, Instant::now()),
+            None => ips,
+        };
+        Ok(ips.into_iter().map(|ip| SocketAddr::new(ip, self.probe_port)).collect())
+    }
+
+    /// Races connections to the given addresses, and makes the first one to connect the preferred
+    /// address of `host`.
+    async fn race(&self, host: &str, ips: &[IpAddr]) {
+        let port = self.probe_port;
+        let mut attempts: FuturesUnordered<_> = ips
+            .iter()
+            .enumerate()
+            .map(|(i, ip)| async move {
+                tokio::time::sleep(CONNECTION_ATTEMPT_DELAY * i as u32).await;
+                let start = Instant::now();
+                let connected = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((*ip, port))).await;
+                (*ip, matches!(connected, Ok(Ok(_))).then(|| start.elapsed()))
+            })
+            .collect();
+
+        while let Some((ip, latency)) = attempts.next().await {
+            let mut hosts = self.hosts.lock().expect("Failed to acquire lock on resolver state");
+            let state = hosts.entry(host.to_string()).or_default();
+            state.record(ip, latency);
+
+            if let Some(latency) = latency {
+                log::debug!("🌐 Using {ip} for {host} ({latency:?})");
+                state.sticky = Some((ip, Instant::now() + STICKINESS));
+                return;
+            }
+        }
+        log::debug!("🌐 Failed to connect to any address of {host}");
+    }
+}
+
+impl Resolve for EndpointResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let addrs = resolver.resolve_host(name.as_str().to_string()).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_are_ordered_by_preference() {
+        let (fast, slow, unknown, sticky) =
+            ([10, 0, 0, 1].into(), [10, 0, 0, 2].into(), [10, 0, 0, 3].into(), [10, 0, 0, 4].into());
+        let now = Instant::now();
+
+        let mut state = HostState::default();
+        state.record(fast, Some(Duration::from_millis(20)));
+        state.record(slow, Some(Duration::from_millis(900)));
+        state.record(sticky, None);
+        state.sticky = Some((sticky, now + STICKINESS));
+
+        assert_eq!(state.order(vec![slow, unknown, fast, sticky], now), vec![sticky, fast, unknown, slow]);
+        // once expired, the preferred address only counts for its latency
+        assert_eq!(state.order(vec![slow, unknown, fast, sticky], now + STICKINESS), vec![fast, unknown, slow, sticky]);
+    }
+}
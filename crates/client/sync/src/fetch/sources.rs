@@ -0,0 +1,439 @@
+//! Scoring of the data sources of the sync, and selection of the source each request is sent to.
+//!
+//! The sync can fetch the same data from several sources, currently the primary gateway and the
+//! fallback gateways, in order of preference. Each source is scored from the latency of its
+//! responses, its rate of transient failures and how far its chain head is behind the other
+//! sources. How much each of these counts depends on the [`RequestKind`]: polling the chain head
+//! favors the sources closest to the tip, fetching historical blocks favors the fast and reliable
+//! ones.
+//!
+//! Requests go to the source with the best score. To avoid flapping between sources of similar
+//! scores, the sync only moves to another source once its score is [`SWITCH_MARGIN`] times the one
+//! of the current source. Sources of equal scores, e.g. the ones which were not used yet, are
+//! preferred in the order they were registered. Failures are forgotten over time, and the sources
+//! which get no requests are probed by the gateway provider, so that a source which recovered gets
+//! requests again.
+//!
+//! Operators can pin a source, which then gets all the requests, or ban sources, through
+//! `deoxys_pinDataSource` and `deoxys_banDataSource`.
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Weight of the last sample in the moving averages of the latency and error rate.
+const ALPHA: f64 = 0.2;
+/// Latency assumed for the sources which did not answer yet.
+const PRIOR_LATENCY: Duration = Duration::from_secs(1);
+/// Time after which half of the failures of a source are forgotten.
+const ERROR_HALF_LIFE: Duration = Duration::from_secs(30);
+/// How much better than the current source another source has to score to get the requests.
+pub const SWITCH_MARGIN: f64 = 1.2;
+
+static SCOREBOARD: Mutex<Scoreboard> = Mutex::new(Scoreboard::new());
+
+/// The kinds of requests sent to the data sources, which weigh the components of the score
+/// differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    /// Polling the chain head and the pending block.
+    Head,
+    /// Fetching blocks and state updates.
+    Block,
+    /// Fetching classes.
+    Class,
+}
+
+impl RequestKind {
+    pub const ALL: [RequestKind; 3] = [RequestKind::Head, RequestKind::Block, RequestKind::Class];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RequestKind::Head => "head",
+            RequestKind::Block => "block",
+            RequestKind::Class => "class",
+        }
+    }
+
+    fn weights(&self) -> Weights {
+        match self {
+            // a source lagging a block behind is worth half as much
+            RequestKind::Head => Weights { latency: 0.5, errors: 1.0, head_lag: 1.0 },
+            // historical blocks are served by all the sources
+            RequestKind::Block => Weights { latency: 1.0, errors: 2.0, head_lag: 0.0 },
+            // classes are large and slow to serve anyway
+            RequestKind::Class => Weights { latency: 0.25, errors: 2.0, head_lag: 0.0 },
+        }
+    }
+}
+
+/// Weights of the components of a score.
+#[derive(Debug, Clone, Copy)]
+struct Weights {
+    /// Per second of average latency.
+    latency: f64,
+    /// Exponent of the success rate.
+    errors: f64,
+    /// Per block behind the most advanced source.
+    head_lag: f64,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum SourceError {
+    #[error("Unknown data source {0}")]
+    Unknown(String),
+    #[error("The data source {0} is the last one which is not banned")]
+    LastSource(String),
+}
+
+/// What is known of a data source.
+#[derive(Debug, Clone)]
+struct Source {
+    name: &'static str,
+    url: String,
+    /// Moving average of the latency, in seconds.
+    latency: Option<f64>,
+    /// Moving average of the transient failures, as of `errors_at`.
+    errors: f64,
+    errors_at: Option<Instant>,
+    /// The highest block the source is known to have.
+    head: Option<u64>,
+    banned: bool,
+}
+
+impl Source {
+    fn errors(&self, now: Instant) -> f64 {
+        match self.errors_at {
+            Some(at) => {
+                let half_lives = now.saturating_duration_since(at).as_secs_f64() / ERROR_HALF_LIFE.as_secs_f64();
+                self.errors * 0.5_f64.powf(half_lives)
+            }
+            None => self.errors,
+        }
+    }
+
+    fn score(&self, kind: RequestKind, best_head: Option<u64>, now: Instant) -> f64 {
+        let weights = kind.weights();
+        let latency = self.latency.unwrap_or(PRIOR_LATENCY.as_secs_f64());
+        let lag = match (best_head, self.head) {
+            (Some(best), Some(head)) => best.saturating_sub(head) as f64,
+            _ => 0.0,
+        };
+
+        (1.0 - self.errors(now)).powf(weights.errors)
+            / (1.0 + latency * weights.latency)
+            / (1.0 + lag * weights.head_lag)
+    }
+}
+
+/// A source as reported by `deoxys_getDataSources`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceReport {
+    pub name: &'static str,
+    pub url: String,
+    pub latency: Option<Duration>,
+    /// Moving average of the share of requests which failed, between 0 and 1.
+    pub error_rate: f64,
+    pub head: Option<u64>,
+    pub pinned: bool,
+    pub banned: bool,
+    scores: [f64; RequestKind::ALL.len()],
+}
+
+impl SourceReport {
+    /// The score of the source for requests of `kind`.
+    pub fn score(&self, kind: RequestKind) -> f64 {
+        self.scores[kind as usize]
+    }
+}
+
+/// The source selected for a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub index: usize,
+    /// The source the previous requests of the same kind were sent to, if it was another one.
+    pub switched_from: Option<usize>,
+}
+
+struct Scoreboard {
+    sources: Vec<Source>,
+    pinned: Option<usize>,
+    /// The source selected for the last request of each kind.
+    selected: [Option<usize>; RequestKind::ALL.len()],
+}
+
+impl Scoreboard {
+    const fn new() -> Self {
+        Self { sources: Vec::new(), pinned: None, selected: [None; RequestKind::ALL.len()] }
+    }
+
+    fn register(&mut self, name: &'static str, url: &str) -> usize {
+        if let Some(index) = self.sources.iter().position(|source| source.name == name) {
+            self.sources[index].url = url.to_string();
+            return index;
+        }
+        self.sources.push(Source {
+            name,
+            url: url.to_string(),
+            latency: None,
+            errors: 0.0,
+            errors_at: None,
+            head: None,
+            banned: false,
+        });
+        self.sources.len() - 1
+    }
+
+    fn best_head(&self, candidates: &[usize]) -> Option<u64> {
+        candidates.iter().filter_map(|&index| self.sources[index].head).max()
+    }
+
+    fn score(&self, index: usize, candidates: &[usize], kind: RequestKind, now: Instant) -> f64 {
+        self.sources[index].score(kind, self.best_head(candidates), now)
+    }
+
+    fn select(&mut self, candidates: &[usize], kind: RequestKind, now: Instant) -> Selection {
+        let allowed: Vec<usize> = candidates.iter().copied().filter(|&index| !self.sources[index].banned).collect();
+        // a provider without a fallback keeps using its only source, even when it is banned
+        let allowed = if allowed.is_empty() { candidates.to_vec() } else { allowed };
+
+        let current = self.selected[kind as usize].filter(|index| allowed.contains(index));
+        let index = match self.pinned.filter(|index| allowed.contains(index)) {
+            Some(pinned) => pinned,
+            None => {
+                let score = |index: usize| self.score(index, &allowed, kind, now);
+                let best = allowed
+                    .iter()
+                    .copied()
+                    .max_by(|&a, &b| score(a).total_cmp(&score(b)).then(b.cmp(&a)))
+                    .expect("Providers have at least one source");
+                match current {
+                    Some(current) if score(best) < score(current) * SWITCH_MARGIN => current,
+                    _ => best,
+                }
+            }
+        };
+
+        let previous = self.selected[kind as usize].replace(index);
+        Selection { index, switched_from: previous.filter(|&previous| previous != index) }
+    }
+
+    fn record(&mut self, index: usize, latency: Duration, failed: bool, now: Instant) {
+        let source = &mut self.sources[index];
+        let latency = latency.as_secs_f64();
+        source.latency = Some(source.latency.map_or(latency, |average| average + ALPHA * (latency - average)));
+        self.record_probe(index, failed, now);
+    }
+
+    fn record_probe(&mut self, index: usize, failed: bool, now: Instant) {
+        let source = &mut self.sources[index];
+        let errors = source.errors(now);
+        let sample = if failed { 1.0 } else { 0.0 };
+        source.errors = errors + ALPHA * (sample - errors);
+        source.errors_at = Some(now);
+    }
+
+    fn idle(&self, candidates: &[usize]) -> Vec<usize> {
+        candidates
+            .iter()
+            .copied()
+            .filter(|index| !self.sources[*index].banned && !self.selected.contains(&Some(*index)))
+            .collect()
+    }
+
+    fn record_head(&mut self, index: usize, block_n: u64) {
+        let head = &mut self.sources[index].head;
+        *head = Some(head.map_or(block_n, |head| head.max(block_n)));
+    }
+
+    fn find(&self, name: &str) -> Result<usize, SourceError> {
+        self.sources.iter().position(|source| source.name == name).ok_or_else(|| SourceError::Unknown(name.to_string()))
+    }
+
+    fn pin(&mut self, name: Option<&str>) -> Result<(), SourceError> {
+        self.pinned = name.map(|name| self.find(name)).transpose()?;
+        if let Some(index) = self.pinned {
+            self.sources[index].banned = false;
+        }
+        Ok(())
+    }
+
+    fn ban(&mut self, name: &str, banned: bool) -> Result<(), SourceError> {
+        let index = self.find(name)?;
+        if banned && self.sources.iter().enumerate().all(|(other, source)| other == index || source.banned) {
+            return Err(SourceError::LastSource(name.to_string()));
+        }
+        self.sources[index].banned = banned;
+        if banned && self.pinned == Some(index) {
+            self.pinned = None;
+        }
+        Ok(())
+    }
+
+    fn report(&self, now: Instant) -> Vec<SourceReport> {
+        let all: Vec<usize> = (0..self.sources.len()).collect();
+        self.sources
+            .iter()
+            .enumerate()
+            .map(|(index, source)| SourceReport {
+                name: source.name,
+                url: source.url.clone(),
+                latency: source.latency.map(Duration::from_secs_f64),
+                error_rate: source.errors(now),
+                head: source.head,
+                pinned: self.pinned == Some(index),
+                banned: source.banned,
+                scores: RequestKind::ALL.map(|kind| self.score(index, &all, kind, now)),
+            })
+            .collect()
+    }
+}
+
+fn scoreboard() -> std::sync::MutexGuard<'static, Scoreboard> {
+    SCOREBOARD.lock().expect("Failed to acquire lock on data sources")
+}
+
+/// Registers the data source `name`, returning its index. Registering a name again returns the
+/// same source, whose URL is updated.
+pub fn register(name: &'static str, url: &str) -> usize {
+    scoreboard().register(name, url)
+}
+
+/// The name of the source at `index`.
+pub fn name(index: usize) -> &'static str {
+    scoreboard().sources[index].name
+}
+
+/// Selects the source among `candidates` the next request of `kind` is sent to.
+pub fn select(candidates: &[usize], kind: RequestKind) -> Selection {
+    scoreboard().select(candidates, kind, Instant::now())
+}
+
+/// Records the outcome of a request sent to the source at `index`.
+pub fn record(index: usize, latency: Duration, failed: bool) {
+    scoreboard().record(index, latency, failed, Instant::now())
+}
+
+/// Records the outcome of a health check of the source at `index`. Only its error rate is updated:
+/// health checks are much faster than requests, their latency says little about the source.
+pub fn record_probe(index: usize, failed: bool) {
+    scoreboard().record_probe(index, failed, Instant::now())
+}
+
+/// The `candidates` which are not banned and which no kind of request is sent to, to be probed.
+pub fn idle(candidates: &[usize]) -> Vec<usize> {
+    scoreboard().idle(candidates)
+}
+
+/// Records that the source at `index` has the block `block_n`.
+pub fn record_head(index: usize, block_n: u64) {
+    scoreboard().record_head(index, block_n)
+}
+
+/// The score of the source at `index` for requests of `kind`, compared to the other `candidates`.
+pub fn score(index: usize, candidates: &[usize], kind: RequestKind) -> f64 {
+    scoreboard().score(index, candidates, kind, Instant::now())
+}
+
+/// Sends all the requests to the source `name`, unbanning it, or lets the sources be selected by
+/// score again if `None`.
+pub fn pin(name: Option<&str>) -> Result<(), SourceError> {
+    scoreboard().pin(name)
+}
+
+/// Bans the source `name`, which gets no requests until unbanned, unless it is the last source not
+/// banned.
+pub fn ban(name: &str, banned: bool) -> Result<(), SourceError> {
+    scoreboard().ban(name, banned)
+}
+
+/// The registered sources, with their scores.
+pub fn report() -> Vec<SourceReport> {
+    scoreboard().report(Instant::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_sources() -> (Scoreboard, [usize; 2]) {
+        let mut scoreboard = Scoreboard::new();
+        let primary = scoreboard.register("primary", "https://primary");
+        let fallback = scoreboard.register("fallback", "https://fallback");
+        (scoreboard, [primary, fallback])
+    }
+
+    #[test]
+    fn failing_sources_are_avoided_until_they_recover() {
+        let (mut scoreboard, sources @ [primary, fallback]) = two_sources();
+        let now = Instant::now();
+        scoreboard.record(primary, Duration::from_millis(300), false, now);
+
+        assert_eq!(scoreboard.select(&sources, RequestKind::Block, now).index, primary);
+        scoreboard.record(primary, Duration::from_millis(300), true, now);
+        scoreboard.record(primary, Duration::from_millis(300), true, now);
+        let selection = scoreboard.select(&sources, RequestKind::Block, now);
+        assert_eq!(selection, Selection { index: fallback, switched_from: Some(primary) });
+
+        // the fallback is as fast as the primary was, the primary has to score much better to be used again
+        scoreboard.record(fallback, Duration::from_millis(300), false, now);
+        assert_eq!(scoreboard.select(&sources, RequestKind::Block, now + ERROR_HALF_LIFE * 10).index, fallback);
+        scoreboard.record(fallback, Duration::from_secs(2), true, now);
+        assert_eq!(scoreboard.select(&sources, RequestKind::Block, now + ERROR_HALF_LIFE * 10).index, primary);
+    }
+
+    #[test]
+    fn lagging_sources_are_avoided_for_the_head() {
+        let (mut scoreboard, sources @ [primary, fallback]) = two_sources();
+        let now = Instant::now();
+        scoreboard.record(primary, Duration::from_millis(100), false, now);
+        scoreboard.record(fallback, Duration::from_millis(500), false, now);
+        scoreboard.record_head(primary, 100);
+        scoreboard.record_head(fallback, 110);
+
+        assert_eq!(scoreboard.select(&sources, RequestKind::Block, now).index, primary);
+        assert_eq!(scoreboard.select(&sources, RequestKind::Head, now).index, fallback);
+    }
+
+    #[test]
+    fn idle_sources_recover_through_probes() {
+        let (mut scoreboard, sources @ [primary, fallback]) = two_sources();
+        let now = Instant::now();
+        assert_eq!(scoreboard.idle(&sources), vec![primary, fallback]);
+
+        scoreboard.record(primary, Duration::from_millis(300), true, now);
+        scoreboard.record(primary, Duration::from_millis(300), true, now);
+        assert_eq!(scoreboard.select(&sources, RequestKind::Block, now).index, fallback);
+        scoreboard.record(fallback, Duration::from_millis(300), false, now);
+        assert_eq!(scoreboard.idle(&sources), vec![primary]);
+
+        // probes only count for the error rate
+        for _ in 0..20 {
+            scoreboard.record_probe(primary, false, now);
+        }
+        assert_eq!(scoreboard.sources[primary].latency, Some(0.3));
+        assert_eq!(scoreboard.select(&sources, RequestKind::Block, now).index, fallback);
+
+        // without the probes, the primary would be as bad as the failing fallback
+        scoreboard.record(fallback, Duration::from_millis(300), true, now);
+        scoreboard.record(fallback, Duration::from_millis(300), true, now);
+        assert_eq!(scoreboard.select(&sources, RequestKind::Block, now).index, primary);
+    }
+
+    #[test]
+    fn pinned_and_banned_sources() {
+        let (mut scoreboard, sources @ [primary, fallback]) = two_sources();
+        let now = Instant::now();
+        scoreboard.record(primary, Duration::from_millis(100), false, now);
+
+        scoreboard.pin(Some("fallback")).unwrap();
+        assert_eq!(scoreboard.select(&sources, RequestKind::Block, now).index, fallback);
+        scoreboard.pin(None).unwrap();
+        scoreboard.ban("primary", true).unwrap();
+        assert_eq!(scoreboard.select(&sources, RequestKind::Block, now).index, fallback);
+
+        assert_eq!(scoreboard.ban("fallback", true), Err(SourceError::LastSource("fallback".to_string())));
+        assert_eq!(scoreboard.pin(Some("replica")), Err(SourceError::Unknown("replica".to_string())));
+        // pinning unbans
+        scoreboard.pin(Some("primary")).unwrap();
+        assert!(!scoreboard.report(now)[primary].banned);
+    }
+}
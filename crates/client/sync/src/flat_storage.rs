@@ -0,0 +1,124 @@
+//! Maintenance of the flat contract storage, a copy of the contract storage tries which can be
+//! read without traversing them.
+//!
+//! The storage updates of each block, along with the nonces and classes of the contracts it
+//! modified and the list of these contracts, are stored as the block is imported. Databases created
+//! before the flat storage existed, or before it recorded nonces and classes, are missing the
+//! updates of the blocks synced until then: these
+//! are fetched again from the feeder gateway by [`backfill`], which runs alongside the sync and
+//! resumes where it stopped after a restart.
+use futures::prelude::*;
+use mc_db::{DbError, DeoxysBackend};
+use mp_block::state_update::StateDiffWrapper;
+use mp_felt::Felt252Wrapper;
+use starknet_api::core::{ClassHash, ContractAddress, Nonce};
+use starknet_api::hash::StarkFelt;
+use starknet_api::state::StorageKey;
+use starknet_providers::sequencer::models::BlockId;
+
+use crate::fetch::gateway::GatewayProvider;
+
+/// Number of state updates fetched in parallel by the backfill, kept low so that it does not slow
+/// the sync down.
+const BACKFILL_WORKERS: usize = 2;
+
+/// Stores the storage, nonce and class updates of a block in the flat storage.
+pub fn store_state_diff(block_number: u64, state_diff: &StateDiffWrapper) -> Result<(), DbError> {
+    let updates: Vec<(ContractAddress, StorageKey, StarkFelt)> = state_diff
+        .storage_diffs
+        .iter()
+        .flat_map(|(address, diffs)| {
+            let address = ContractAddress::from(*address);
+            diffs.iter().map(move |diff| (address, StorageKey::from(diff.key), StarkFelt::from(diff.value)))
+        })
+        .collect();
+
+    let nonces: Vec<(ContractAddress, Nonce)> = state_diff
+        .nonces
+        .iter()
+        .map(|(address, nonce)| (ContractAddress::from(*address), Nonce(StarkFelt::from(*nonce))))
+        .collect();
+    let class_hashes: Vec<(ContractAddress, ClassHash)> = state_diff
+        .deployed_contracts
+        .iter()
+        .chain(&state_diff.replaced_classes)
+        .map(|contract| (ContractAddress::from(contract.address), ClassHash(StarkFelt::from(contract.class_hash))))
+        .collect();
+
+    let storage = DeoxysBackend::contract_storage();
+    storage.store_block(block_number, updates.iter().map(|(address, key, value)| (address, key, value)))?;
+    storage.store_contracts(
+        block_number,
+        nonces.iter().map(|(address, nonce)| (address, nonce)),
+        class_hashes.iter().map(|(address, class_hash)| (address, class_hash)),
+    )
+}
+
+/// Stores the contracts whose storage, class or nonce are modified by a block.
+pub fn store_modified_contracts(block_number: u64, state_diff: &StateDiffWrapper) -> Result<(), DbError> {
+    let mut addresses: Vec<Felt252Wrapper> = state_diff
+        .storage_diffs
+        .iter()
+        .map(|(address, _)| *address)
+        .chain(state_diff.deployed_contracts.iter().map(|contract| contract.address))
+        .chain(state_diff.replaced_classes.iter().map(|contract| contract.address))
+        .chain(state_diff.nonces.iter().map(|(address, _)| *address))
+        .collect();
+    addresses.sort();
+    addresses.dedup();
+
+    let contracts: Vec<ContractAddress> = addresses.into_iter().map(ContractAddress::from).collect();
+    DeoxysBackend::contract_storage().store_modified_contracts(block_number, &contracts)
+}
+
+/// Stores the updates of the blocks synced before the flat storage existed.
+///
+/// Does nothing if there is nothing to backfill. Failures are logged and stop the backfill, which
+/// is resumed on the next startup: reads at the missing blocks keep being served from the tries in
+/// the meantime.
+pub async fn backfill(provider: &GatewayProvider) {
+    let storage = DeoxysBackend::contract_storage();
+    let missing = match storage.backfill_range() {
+        Ok(missing) => missing,
+        Err(e) => {
+            log::error!("Failed to read the flat storage backfill progress: {e}");
+            return;
+        }
+    };
+    if missing.is_empty() {
+        return;
+    }
+
+    log::info!("🗄️ Backfilling the flat contract storage from block {} to {}", missing.start, missing.end - 1);
+
+    let end = missing.end;
+    let mut state_updates = stream::iter(missing)
+        .map(|block_n| async move { (block_n, provider.get_state_update(BlockId::Number(block_n)).await) })
+        .buffered(BACKFILL_WORKERS);
+
+    while let Some((block_n, state_update)) = state_updates.next().await {
+        let state_update = match state_update {
+            Ok(state_update) => state_update,
+            Err(e) => {
+                log::error!("Failed to fetch the state update of block {block_n} for the flat storage backfill: {e}");
+                return;
+            }
+        };
+
+        // the state updates are received in order, so all the previous blocks are backfilled
+        let state_diff = StateDiffWrapper::from(&state_update.state_diff);
+        if let Err(e) = store_state_diff(block_n, &state_diff)
+            .and_then(|()| store_modified_contracts(block_n, &state_diff))
+            .and_then(|()| storage.set_backfilled(block_n + 1))
+        {
+            log::error!("Failed to backfill the flat storage at block {block_n}: {e}");
+            return;
+        }
+
+        if block_n % 10_000 == 0 {
+            log::info!("🗄️ Flat contract storage backfilled up to block {block_n}/{}", end - 1);
+        }
+    }
+
+    log::info!("🗄️ Flat contract storage backfill complete");
+}
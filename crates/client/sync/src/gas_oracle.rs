@@ -0,0 +1,191 @@
+//! Gas price oracle, supplying the L1 gas prices used to estimate fees at the tip of the chain.
+//!
+//! The runtime executes transactions with constant gas prices. When the oracle runs, it polls the
+//! base fee and the blob base fee of the latest L1 block, and smooths them with an exponential
+//! moving average, as the sequencer does with the L1 prices it charges for. The prices in STRK are
+//! derived from the ones in ETH with the STRK/ETH rate of a [`StrkRateSource`].
+//!
+//! The latest prices are read with [`gas_prices`], `None` until the first L1 block was polled.
+use std::num::NonZeroU128;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use blockifier::blockifier::block::GasPrices;
+use lazy_static::lazy_static;
+use reqwest::Url;
+
+use crate::head;
+use crate::l1::EthereumClient;
+
+lazy_static! {
+    /// Latest gas prices computed by the oracle
+    static ref GAS_PRICES: RwLock<Option<GasPrices>> = RwLock::new(None);
+}
+
+/// Where the number of STRK an ETH is worth is read from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StrkRateSource {
+    /// The ratio between the STRK and ETH gas prices of the latest synced block.
+    Chain,
+    /// A constant rate.
+    Fixed(f64),
+    /// A JSON document fetched from `url`, the rate being the number at the JSON `pointer`, e.g.
+    /// `/ethereum/strk`. The whole document is the rate when the pointer is empty.
+    Url { url: Url, pointer: String },
+}
+
+/// How the gas prices are computed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GasOracleConfig {
+    /// Interval between two polls of the L1 fees and of the STRK/ETH rate.
+    pub poll_interval: Duration,
+    /// Number of polls the moving average is computed over.
+    pub ema_window: u32,
+    /// Source of the STRK/ETH rate.
+    pub strk_rate: StrkRateSource,
+}
+
+/// The latest gas prices computed by the oracle, if it runs.
+pub fn gas_prices() -> Option<GasPrices> {
+    GAS_PRICES.read().expect("Failed to acquire read lock on GAS_PRICES").clone()
+}
+
+/// Polls the L1 node at `l1_url` and the STRK/ETH rate source, updating the gas prices.
+pub async fn run(l1_url: Url, config: GasOracleConfig) {
+    let client = match EthereumClient::new(l1_url).await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to start the gas price oracle: {e}");
+            return;
+        }
+    };
+    let http = reqwest::Client::new();
+
+    let mut l1_gas_price = Ema::new(config.ema_window);
+    let mut l1_data_gas_price = Ema::new(config.ema_window);
+    let mut strk_per_eth = None;
+
+    let mut interval = tokio::time::interval(config.poll_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+
+        match client.get_fees().await {
+            Ok((base_fee, blob_base_fee)) => {
+                l1_gas_price.update(base_fee as f64);
+                // the state diffs are posted as calldata before blobs are supported
+                l1_data_gas_price.update(blob_base_fee.unwrap_or(base_fee) as f64);
+            }
+            Err(e) => log::warn!("Gas price oracle failed to read the L1 fees: {e}"),
+        }
+        match strk_rate(&config.strk_rate, &http).await {
+            Ok(rate) => strk_per_eth = Some(rate),
+            Err(e) => log::warn!("Gas price oracle failed to read the STRK/ETH rate: {e}"),
+        }
+
+        if let (Some(l1_gas_price), Some(l1_data_gas_price), Some(strk_per_eth)) =
+            (l1_gas_price.value(), l1_data_gas_price.value(), strk_per_eth)
+        {
+            let prices = to_gas_prices(l1_gas_price, l1_data_gas_price, strk_per_eth);
+            log::debug!("⛽ Gas prices updated: {prices:?}");
+            *GAS_PRICES.write().expect("Failed to acquire write lock on GAS_PRICES") = Some(prices);
+        }
+    }
+}
+
+/// Reads the number of STRK an ETH is worth from `source`.
+async fn strk_rate(source: &StrkRateSource, http: &reqwest::Client) -> anyhow::Result<f64> {
+    let rate = match source {
+        StrkRateSource::Fixed(rate) => *rate,
+        StrkRateSource::Chain => {
+            let header = head::latest_head().borrow().clone();
+            let prices = header
+                .and_then(|header| header.l1_gas_price)
+                .ok_or_else(|| anyhow::anyhow!("no synced block with gas prices yet"))?;
+            prices.strk_l1_gas_price.get() as f64 / prices.eth_l1_gas_price.get() as f64
+        }
+        StrkRateSource::Url { url, pointer } => {
+            let document: serde_json::Value = http.get(url.clone()).send().await?.error_for_status()?.json().await?;
+            let value = document.pointer(pointer).ok_or_else(|| anyhow::anyhow!("nothing at {pointer}"))?;
+            // price APIs commonly return decimals as strings
+            match value {
+                serde_json::Value::String(rate) => rate.parse()?,
+                value => value.as_f64().ok_or_else(|| anyhow::anyhow!("{value} is not a number"))?,
+            }
+        }
+    };
+
+    if !rate.is_finite() || rate <= 0.0 {
+        anyhow::bail!("invalid rate {rate}");
+    }
+    Ok(rate)
+}
+
+/// The gas prices for the given L1 prices in wei, one ETH being worth `strk_per_eth` STRK.
+fn to_gas_prices(l1_gas_price: f64, l1_data_gas_price: f64, strk_per_eth: f64) -> GasPrices {
+    // prices are stored as NonZeroU128, 1 standing for 0
+    let price = |price: f64| NonZeroU128::new(price.round() as u128).unwrap_or(NonZeroU128::MIN);
+    GasPrices {
+        eth_l1_gas_price: price(l1_gas_price),
+        strk_l1_gas_price: price(l1_gas_price * strk_per_eth),
+        eth_l1_data_gas_price: price(l1_data_gas_price),
+        strk_l1_data_gas_price: price(l1_data_gas_price * strk_per_eth),
+    }
+}
+
+/// Exponential moving average over `window` samples.
+struct Ema {
+    alpha: f64,
+    value: Option<f64>,
+}
+
+impl Ema {
+    fn new(window: u32) -> Self {
+        Self { alpha: 2.0 / (f64::from(window.max(1)) + 1.0), value: None }
+    }
+
+    fn update(&mut self, sample: f64) {
+        self.value = Some(match self.value {
+            Some(value) => value + self.alpha * (sample - value),
+            None => sample,
+        });
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ema_follows_the_samples() {
+        let mut ema = Ema::new(3);
+        assert_eq!(ema.value(), None);
+
+        ema.update(100.0);
+        assert_eq!(ema.value(), Some(100.0));
+        ema.update(200.0);
+        assert_eq!(ema.value(), Some(150.0));
+        ema.update(150.0);
+        assert_eq!(ema.value(), Some(150.0));
+
+        // a window of one is the latest sample
+        let mut ema = Ema::new(1);
+        ema.update(100.0);
+        ema.update(300.0);
+        assert_eq!(ema.value(), Some(300.0));
+    }
+
+    #[test]
+    fn strk_prices_follow_the_rate() {
+        let prices = to_gas_prices(30e9, 0.0, 2500.0);
+
+        assert_eq!(prices.eth_l1_gas_price.get(), 30_000_000_000);
+        assert_eq!(prices.strk_l1_gas_price.get(), 75_000_000_000_000);
+        assert_eq!(prices.eth_l1_data_gas_price, NonZeroU128::MIN);
+        assert_eq!(prices.strk_l1_data_gas_price, NonZeroU128::MIN);
+    }
+}
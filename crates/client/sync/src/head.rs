@@ -0,0 +1,101 @@
+//! Notifications of the changes of the chain head, for the services following the chain.
+//!
+//! The sync worker publishes an event each time it imports a block, detects a reorg, or sees a new
+//! state update verified on L1. Services either subscribe to all the events with [`subscribe`], or
+//! only watch the latest imported header with [`latest_head`], instead of polling the database.
+use lazy_static::lazy_static;
+use mp_block::Header;
+use starknet_ff::FieldElement;
+use tokio::sync::watch;
+
+use crate::fanout::{Client, FanOut, SubscribeError, Subscription};
+use crate::l1::L1StateUpdate;
+
+/// Number of events queued for each subscriber, unless configured otherwise.
+const HEAD_EVENTS_CAPACITY: usize = 256;
+
+/// A change of the chain head.
+#[derive(Debug, Clone)]
+pub enum HeadEvent {
+    /// A block was imported on top of the chain.
+    NewHead(Header),
+    /// The chain was reorganized: the blocks after `common_ancestor` were replaced.
+    Reorg {
+        /// Number of the last block kept.
+        common_ancestor: u64,
+        /// Hash and number of the head before the reorg.
+        previous_head: (FieldElement, u64),
+    },
+    /// A new state update was verified on L1.
+    L1Confirmed(L1StateUpdate),
+}
+
+static HEAD_EVENTS: FanOut<HeadEvent> = FanOut::new("head", HEAD_EVENTS_CAPACITY);
+
+lazy_static! {
+    static ref LATEST_HEAD: watch::Sender<Option<Header>> = watch::channel(None).0;
+}
+
+/// Stream of the [`HeadEvent`]s published after it was created.
+pub struct NewHeadStream {
+    subscription: Subscription<HeadEvent>,
+}
+
+impl NewHeadStream {
+    /// Waits for the next event, `None` once the subscriber was disconnected for falling behind.
+    ///
+    /// Depending on the [slow subscriber policy](crate::fanout::SlowSubscriberPolicy),
+    /// subscribers falling more than a few hundred events behind either miss the oldest ones, or
+    /// are disconnected. [`latest_head`] can be used to catch up.
+    pub async fn next(&mut self) -> Option<HeadEvent> {
+        self.subscription.next().await
+    }
+}
+
+/// Subscribes to the changes of the chain head.
+pub fn subscribe() -> NewHeadStream {
+    NewHeadStream { subscription: HEAD_EVENTS.subscribe() }
+}
+
+/// Subscribes to the changes of the chain head on behalf of an external `client`, within its
+/// [subscriber limits](crate::fanout::SubscriberLimits).
+pub fn subscribe_as(client: Client) -> Result<NewHeadStream, SubscribeError> {
+    Ok(NewHeadStream { subscription: HEAD_EVENTS.subscribe_as(client)? })
+}
+
+/// Watches the header of the latest imported block, `None` until the first block is imported.
+pub fn latest_head() -> watch::Receiver<Option<Header>> {
+    LATEST_HEAD.subscribe()
+}
+
+/// Publishes a change of the chain head.
+pub(crate) fn publish(event: HeadEvent) {
+    if let HeadEvent::NewHead(header) = &event {
+        LATEST_HEAD.send_replace(Some(header.clone()));
+    }
+    HEAD_EVENTS.publish(event);
+}
+
+/// Serializes the tests publishing events, which would otherwise receive each other's events.
+#[cfg(test)]
+pub(crate) static PUBLISH_TEST_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn subscribers_receive_published_events() {
+        let _lock = PUBLISH_TEST_LOCK.lock().await;
+        let mut events = subscribe();
+        let head = latest_head();
+
+        let header = Header { block_number: 3, ..Default::default() };
+        publish(HeadEvent::NewHead(header));
+        publish(HeadEvent::Reorg { common_ancestor: 2, previous_head: (FieldElement::ONE, 3) });
+
+        assert!(matches!(events.next().await, Some(HeadEvent::NewHead(header)) if header.block_number == 3));
+        assert!(matches!(events.next().await, Some(HeadEvent::Reorg { common_ancestor: 2, .. })));
+        assert_eq!(head.borrow().as_ref().map(|header| header.block_number), Some(3));
+    }
+}
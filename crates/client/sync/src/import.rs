@@ -0,0 +1,474 @@
+//! Import of blocks from an archive produced by `export-blocks`.
+//!
+//! New nodes can bootstrap from the dump of a trusted peer instead of fetching and converting
+//! every block from the feeder gateway. Archived blocks go through the same steps as the synced
+//! ones, except for the conversion: the state root is still computed, and checked against the one
+//! of the archived block when verification is enabled.
+//!
+//! Snapshots, published by nodes started with `--snapshot-bucket`, also hold the trie deltas of the
+//! blocks: these are applied to the tries instead of recomputing them from the state diffs, and the
+//! resulting state root is checked against the header of each block.
+//!
+//! Blocks produced locally, in devnet mode, are checked with [`verify_block`] before being
+//! imported, so that a produced block is always one a fresh node can import from an archive.
+use std::path::Path;
+
+use mc_db::storage::DeoxysStorageError;
+use mc_db::{DbError, DeoxysBackend, TrieDelta};
+use mp_block::receipt::TransactionReceiptWrapper;
+use mp_block::state_update::StateUpdateWrapper;
+use mp_block::DeoxysBlock;
+use mp_contract::class::ClassUpdateWrapper;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use parity_scale_codec::{Decode, Encode};
+use sp_blockchain::HeaderBackend;
+use sp_core::{H256, U256};
+use starknet_api::hash::StarkHash;
+use starknet_api::transaction::TransactionHash;
+use thiserror::Error;
+
+use crate::archive::{ArchiveKind, ArchiveReader};
+use crate::commitments::hashers::CommitmentHashers;
+use crate::commitments::lib::{calculate_commitments, calculate_v0_13_2_commitments, latest_state_root};
+use crate::commitments::versions::has_v0_13_2_commitments;
+use crate::errors::{ConversionError, SyncError};
+use crate::head::{self, HeadEvent};
+use crate::l2::{
+    create_block, store_class_declarations, update_l2, update_sync_progress, verify_l2, L2StateUpdate, SenderConfig,
+};
+use crate::utility::block_hash_substrate;
+use crate::{flat_storage, l2};
+
+/// A block as stored in an archive, with everything needed to import it without the gateway.
+#[derive(Debug, Encode, Decode)]
+pub struct ArchivedBlock {
+    pub block: DeoxysBlock,
+    pub state_update: StateUpdateWrapper,
+    pub class_update: ClassUpdateWrapper,
+    pub receipts: Vec<TransactionReceiptWrapper>,
+}
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("failed to read the archive: {0}")]
+    Archive(#[from] std::io::Error),
+    #[error("failed to store the block data: {0}")]
+    Db(#[from] DbError),
+    #[error("expected block {expected} in the archive, found block {found}")]
+    MissingBlock { expected: u64, found: u64 },
+    #[error("failed to verify the block: {0}")]
+    Sync(#[from] SyncError),
+    #[error("failed to create block {block_number}: {reason}")]
+    BlockCreation { block_number: u64, reason: String },
+    #[error("failed to apply the trie delta of block {block_number}: {source}")]
+    TrieDelta { block_number: u64, source: DeoxysStorageError },
+}
+
+/// Imports the blocks of the archive or snapshot at `path`, starting at `first_block`. Blocks
+/// before it are skipped, as they were already imported.
+///
+/// Returns the number of the block following the last imported one, from which the sync resumes.
+pub async fn import_blocks<C>(
+    path: &Path,
+    first_block: u64,
+    sender_config: &mut SenderConfig,
+    client: &C,
+    verify: bool,
+    hashers: CommitmentHashers,
+) -> Result<u64, ImportError>
+where
+    C: HeaderBackend<DBlockT>,
+{
+    let (mut archive, kind) = ArchiveReader::open_any(path, &[ArchiveKind::Blocks, ArchiveKind::Snapshot])?;
+    let mut last_block_hash = None;
+    let mut next_block = first_block;
+
+    log::info!("📦 Importing blocks from {}", path.display());
+    loop {
+        let (archived, tries) = match kind {
+            ArchiveKind::Snapshot => match archive.next::<(ArchivedBlock, Option<TrieDelta>)>()? {
+                Some(entry) => entry,
+                None => break,
+            },
+            _ => match archive.next::<ArchivedBlock>()? {
+                Some(archived) => (archived, None),
+                None => break,
+            },
+        };
+        let block_n = archived.block.header().block_number;
+        if block_n < next_block {
+            continue;
+        }
+        if block_n > next_block {
+            return Err(ImportError::MissingBlock { expected: next_block, found: block_n });
+        }
+
+        // the tries of the blocks without a delta are recomputed along with their import
+        let recompute_tries = match tries {
+            Some(tries) => {
+                apply_trie_delta(&archived, &tries, hashers)?;
+                false
+            }
+            None => verify,
+        };
+        import_block(archived, sender_config, &mut last_block_hash, client, recompute_tries, hashers).await?;
+        if block_n % 10_000 == 0 {
+            log::info!("📦 Imported blocks up to {block_n}");
+        }
+        next_block = block_n + 1;
+    }
+
+    if next_block > first_block {
+        log::info!("📦 Imported blocks {first_block} to {}, resuming the sync from the gateway", next_block - 1);
+    } else {
+        log::info!("📦 No block to import from {}", path.display());
+    }
+    Ok(next_block)
+}
+
+/// Checks that the header of `archived` matches its content: the transaction and event counts and
+/// commitments, the receipt and state diff commitments from Starknet 0.13.2, the state root of its
+/// state update and the block hash.
+///
+/// The state root itself is checked against the tries when the block is imported with
+/// verification, see [`import_block`].
+pub fn verify_block<H: HasherT>(
+    archived: &ArchivedBlock,
+    chain_id: Felt252Wrapper,
+    hashers: CommitmentHashers,
+) -> Result<(), SyncError> {
+    let ArchivedBlock { block, state_update, receipts, .. } = archived;
+    let header = block.header();
+    let block_number = header.block_number;
+    let mismatch = |field| SyncError::BlockMismatch { block_number, field };
+
+    let transactions = block.transactions();
+    if header.transaction_count != transactions.len() as u128 || receipts.len() != transactions.len() {
+        return Err(mismatch("transaction count"));
+    }
+    let event_count = block.events().iter().map(|ordered| ordered.events().len()).sum::<usize>();
+    if header.event_count != event_count as u128 {
+        return Err(mismatch("event count"));
+    }
+
+    let (transaction_commitment, event_commitment) =
+        calculate_commitments(transactions, block.events(), chain_id, block_number, &header.protocol_version, hashers);
+    if Felt252Wrapper::from(header.transaction_commitment) != transaction_commitment {
+        return Err(mismatch("transaction commitment"));
+    }
+    if Felt252Wrapper::from(header.event_commitment) != event_commitment {
+        return Err(mismatch("event commitment"));
+    }
+
+    // the blocks of Starknet 0.13.2 and later also commit to their receipts and state diff
+    let (receipt_commitment, state_diff_commitment, state_diff_length) =
+        if has_v0_13_2_commitments(&header.protocol_version) {
+            let (receipt_commitment, state_diff_commitment, state_diff_length) =
+                calculate_v0_13_2_commitments(receipts, &state_update.state_diff);
+            (
+                Some(StarkHash::from(receipt_commitment)),
+                Some(StarkHash::from(state_diff_commitment)),
+                Some(state_diff_length),
+            )
+        } else {
+            (None, None, None)
+        };
+    if header.receipt_commitment != receipt_commitment {
+        return Err(mismatch("receipt commitment"));
+    }
+    if header.state_diff_commitment != state_diff_commitment || header.state_diff_length != state_diff_length {
+        return Err(mismatch("state diff commitment"));
+    }
+
+    if state_update.new_root != Some(header.global_state_root.into()) {
+        return Err(mismatch("state root"));
+    }
+    let block_hash = header.hash::<H>();
+    if state_update.block_hash != Some(block_hash)
+        || header.extra_data != Some(U256::from_big_endian(&block_hash.0.to_bytes_be()))
+    {
+        return Err(mismatch("block hash"));
+    }
+    Ok(())
+}
+
+/// Stores and creates an archived block, on top of the block created last, whose hash is kept in
+/// `last_block_hash`.
+pub async fn import_block<C>(
+    archived: ArchivedBlock,
+    sender_config: &mut SenderConfig,
+    last_block_hash: &mut Option<H256>,
+    client: &C,
+    verify: bool,
+    hashers: CommitmentHashers,
+) -> Result<(), ImportError>
+where
+    C: HeaderBackend<DBlockT>,
+{
+    let SenderConfig { block_sender, state_update_sender, class_sender, command_sink, overrides, .. } = sender_config;
+    let ArchivedBlock { block, state_update, class_update, receipts } = archived;
+    let block_n = block.header().block_number;
+
+    store_class_declarations(block_n, &state_update.state_diff, Some(&class_update.0[..]))?;
+    flat_storage::store_state_diff(block_n, &state_update.state_diff)?;
+    flat_storage::store_modified_contracts(block_n, &state_update.state_diff)?;
+    DeoxysBackend::receipt().store_receipts(&receipts)?;
+    let transaction_hashes: Vec<TransactionHash> =
+        receipts.iter().map(|receipt| TransactionHash(receipt.transaction_hash.into())).collect();
+    DeoxysBackend::transaction().store_block(block_n, &transaction_hashes)?;
+    DeoxysBackend::transaction().store_events(block_n, &transaction_hashes, block.events())?;
+
+    if verify {
+        let substrate_block_hash = block_hash_substrate(client, block_n - 1);
+        verify_l2(block_n, &state_update, overrides, substrate_block_hash, hashers)?;
+        let computed =
+            l2::STARKNET_STATE_UPDATE.read().expect("Failed to acquire read lock on STARKNET_STATE_UPDATE").global_root;
+        let fetched = block.header().global_state_root;
+        if computed != fetched {
+            return Err(SyncError::CommitmentMismatch { block_number: block_n, computed, fetched }.into());
+        }
+    }
+
+    let header = block.header().clone();
+    let starknet_block_hash = state_update.block_hash.unwrap_or_default();
+    block_sender.send(block).await.expect("block reciever channel is closed");
+    state_update_sender.send(state_update).await.expect("state updater is not running");
+    class_sender.send(class_update).await.expect("class updater is not running");
+
+    create_block(command_sink, last_block_hash)
+        .await
+        .map_err(|reason| ImportError::BlockCreation { block_number: block_n, reason })?;
+    update_sync_progress(starknet_block_hash.into(), block_n);
+    head::publish(HeadEvent::NewHead(header));
+    Ok(())
+}
+
+/// Applies the trie delta of `archived`, computed by the node it comes from, then checks the
+/// resulting state root against the header of the block and updates the L2 state with it.
+pub fn apply_trie_delta(
+    archived: &ArchivedBlock,
+    tries: &TrieDelta,
+    hashers: CommitmentHashers,
+) -> Result<(), ImportError> {
+    let header = archived.block.header();
+    let block_number = header.block_number;
+    let delta_error = |source| ImportError::TrieDelta { block_number, source };
+
+    tries.apply(block_number).map_err(delta_error)?;
+    let computed: StarkHash = latest_state_root(hashers).map_err(delta_error)?.into();
+    let fetched = header.global_state_root;
+    if computed != fetched {
+        return Err(SyncError::CommitmentMismatch { block_number, computed, fetched }.into());
+    }
+
+    let block_hash =
+        archived.state_update.block_hash.ok_or(ConversionError::MissingField("block hash")).map_err(SyncError::from)?;
+    update_l2(L2StateUpdate {
+        block_number,
+        global_root: computed,
+        block_hash: Felt252Wrapper::from(block_hash).into(),
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mp_block::receipt::ExecutionResourcesWrapper;
+    use mp_block::state_update::StateDiffWrapper;
+    use mp_block::{Header, OrderedEvents};
+    use mp_hashers::pedersen::PedersenHasher;
+    use starknet_api::core::{ContractAddress, EntryPointSelector, Nonce, PatriciaKey};
+    use starknet_api::hash::StarkFelt;
+    use starknet_api::transaction::{
+        Calldata, Event, EventContent, EventData, EventKey, L1HandlerTransaction, Transaction, TransactionVersion,
+    };
+
+    use super::*;
+    use crate::archive::ArchiveWriter;
+
+    fn chain_id() -> Felt252Wrapper {
+        Felt252Wrapper::from(StarkFelt::from(0x534e5f474f45524c49u128))
+    }
+
+    /// Builds a block the way the devnet does: the header is sealed with the commitments and the
+    /// hash of its content.
+    fn sealed_block(block_number: u64) -> ArchivedBlock {
+        let address = ContractAddress(PatriciaKey::try_from(StarkFelt::from(3u128)).unwrap());
+        let transactions = vec![Transaction::L1Handler(L1HandlerTransaction {
+            version: TransactionVersion(StarkFelt::ZERO),
+            nonce: Nonce(StarkFelt::from(1u128)),
+            contract_address: address,
+            entry_point_selector: EntryPointSelector(StarkFelt::from(2u128)),
+            calldata: Calldata(vec![StarkFelt::from(4u128)].into()),
+        })];
+        let events = vec![Event {
+            from_address: address,
+            content: EventContent {
+                keys: vec![EventKey(StarkFelt::from(5u128))],
+                data: EventData(vec![StarkFelt::from(6u128)]),
+            },
+        }];
+        let events = vec![OrderedEvents::new(0, events)];
+        let hashers = CommitmentHashers::default();
+        let (transaction_commitment, event_commitment) =
+            calculate_commitments(&transactions, &events, chain_id(), block_number, &Felt252Wrapper::ZERO, hashers);
+
+        let mut header = Header {
+            block_number,
+            global_state_root: StarkFelt::from(7u128),
+            transaction_count: 1,
+            transaction_commitment: transaction_commitment.into(),
+            event_count: 1,
+            event_commitment: event_commitment.into(),
+            ..Default::default()
+        };
+        let block_hash = header.hash::<PedersenHasher>();
+        header.extra_data = Some(U256::from_big_endian(&block_hash.0.to_bytes_be()));
+
+        ArchivedBlock {
+            block: DeoxysBlock::new(header, transactions, events),
+            state_update: StateUpdateWrapper {
+                block_hash: Some(block_hash),
+                new_root: Some(StarkFelt::from(7u128).into()),
+                old_root: Felt252Wrapper::ZERO,
+                state_diff: StateDiffWrapper {
+                    storage_diffs: vec![],
+                    deployed_contracts: vec![],
+                    old_declared_contracts: vec![],
+                    declared_classes: vec![],
+                    nonces: vec![],
+                    replaced_classes: vec![],
+                },
+            },
+            class_update: ClassUpdateWrapper(vec![]),
+            receipts: vec![TransactionReceiptWrapper {
+                transaction_hash: StarkFelt::from(8u128).into(),
+                actual_fee: Felt252Wrapper::ZERO,
+                revert_error: None,
+                execution_resources: ExecutionResourcesWrapper::default(),
+                messages_sent: vec![],
+                events: vec![],
+            }],
+        }
+    }
+
+    #[test]
+    fn produced_blocks_are_verified_after_an_archive_roundtrip() {
+        let hashers = CommitmentHashers::default();
+        let mut writer = ArchiveWriter::new(Vec::new(), ArchiveKind::Blocks).unwrap();
+        writer.write(&sealed_block(1)).unwrap();
+        writer.write(&sealed_block(900)).unwrap();
+        let archive = writer.finish().unwrap();
+
+        let mut reader = ArchiveReader::new(&archive[..], ArchiveKind::Blocks).unwrap();
+        for block_number in [1, 900] {
+            let archived = reader.next::<ArchivedBlock>().unwrap().unwrap();
+            assert_eq!(archived.block.header().block_number, block_number);
+            verify_block::<PedersenHasher>(&archived, chain_id(), hashers).unwrap();
+        }
+        assert!(reader.next::<ArchivedBlock>().unwrap().is_none());
+    }
+
+    #[test]
+    fn blocks_not_matching_their_header_are_rejected() {
+        let hashers = CommitmentHashers::default();
+        let mismatch = |archived: ArchivedBlock| match verify_block::<PedersenHasher>(&archived, chain_id(), hashers) {
+            Err(SyncError::BlockMismatch { field, .. }) => field,
+            result => panic!("unexpected result {result:?}"),
+        };
+
+        let mut archived = sealed_block(900);
+        archived.receipts.clear();
+        assert_eq!(mismatch(archived), "transaction count");
+
+        let mut archived = sealed_block(900);
+        archived.block = DeoxysBlock::new(
+            archived.block.header().clone(),
+            archived.block.transactions().clone(),
+            vec![OrderedEvents::new(0, vec![])],
+        );
+        assert_eq!(mismatch(archived), "event count");
+
+        let archived = sealed_block(900);
+        let mut events = archived.block.events()[0].events().clone();
+        events[0].content.data = EventData(vec![StarkFelt::from(9u128)]);
+        let tampered = ArchivedBlock {
+            block: DeoxysBlock::new(
+                archived.block.header().clone(),
+                archived.block.transactions().clone(),
+                vec![OrderedEvents::new(0, events)],
+            ),
+            ..archived
+        };
+        assert_eq!(mismatch(tampered), "event commitment");
+
+        let mut archived = sealed_block(900);
+        archived.state_update.new_root = Some(Felt252Wrapper::ONE);
+        assert_eq!(mismatch(archived), "state root");
+
+        let mut archived = sealed_block(900);
+        archived.state_update.block_hash = Some(Felt252Wrapper::ONE);
+        assert_eq!(mismatch(archived), "block hash");
+    }
+
+    #[test]
+    fn v0_13_2_blocks_are_verified_against_their_receipts_and_state_diff() {
+        let hashers = CommitmentHashers::default();
+        // the header of the block as of Starknet 0.13.2, whose transaction and event leaves changed
+        let upgraded = |archived: &ArchivedBlock| {
+            let protocol_version = Felt252Wrapper::try_from(&b"0.13.2"[..]).unwrap();
+            let block = &archived.block;
+            let (transaction_commitment, event_commitment) = calculate_commitments(
+                block.transactions(),
+                block.events(),
+                chain_id(),
+                900,
+                &protocol_version,
+                hashers,
+            );
+            Header {
+                protocol_version,
+                transaction_commitment: transaction_commitment.into(),
+                event_commitment: event_commitment.into(),
+                ..block.header().clone()
+            }
+        };
+        let seal = |mut archived: ArchivedBlock, receipt_commitment: Option<StarkHash>| {
+            let (computed_receipt_commitment, state_diff_commitment, state_diff_length) =
+                calculate_v0_13_2_commitments(&archived.receipts, &archived.state_update.state_diff);
+            let mut header = Header {
+                receipt_commitment: Some(receipt_commitment.unwrap_or(computed_receipt_commitment.into())),
+                state_diff_commitment: Some(state_diff_commitment.into()),
+                state_diff_length: Some(state_diff_length),
+                extra_data: None,
+                ..upgraded(&archived)
+            };
+            let block_hash = header.hash::<PedersenHasher>();
+            header.extra_data = Some(U256::from_big_endian(&block_hash.0.to_bytes_be()));
+            archived.block =
+                DeoxysBlock::new(header, archived.block.transactions().clone(), archived.block.events().clone());
+            archived.state_update.block_hash = Some(block_hash);
+            archived
+        };
+
+        verify_block::<PedersenHasher>(&seal(sealed_block(900), None), chain_id(), hashers).unwrap();
+
+        let tampered = seal(sealed_block(900), Some(StarkHash::from(1u128)));
+        match verify_block::<PedersenHasher>(&tampered, chain_id(), hashers) {
+            Err(SyncError::BlockMismatch { field, .. }) => assert_eq!(field, "receipt commitment"),
+            result => panic!("unexpected result {result:?}"),
+        }
+
+        // blocks of Starknet 0.13.2 and later must hold the new commitments
+        let mut archived = sealed_block(900);
+        let header = upgraded(&archived);
+        archived.block =
+            DeoxysBlock::new(header, archived.block.transactions().clone(), archived.block.events().clone());
+        match verify_block::<PedersenHasher>(&archived, chain_id(), hashers) {
+            Err(SyncError::BlockMismatch { field, .. }) => assert_eq!(field, "receipt commitment"),
+            result => panic!("unexpected result {result:?}"),
+        }
+    }
+}
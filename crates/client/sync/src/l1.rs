@@ -10,6 +10,7 @@ use ethers::types::{Address, BlockNumber as EthBlockNumber, Filter, TransactionR
 use ethers::utils::hex::decode;
 use futures::stream::StreamExt;
 use lazy_static::lazy_static;
+use mc_db::DeoxysBackend;
 use mp_felt::Felt252Wrapper;
 use primitive_types::H256;
 use reqwest::Url;
@@ -17,10 +18,16 @@ use serde::Deserialize;
 use serde_json::Value;
 use starknet_api::hash::StarkHash;
 
-use crate::l2::STARKNET_STATE_UPDATE;
-use crate::utility::{convert_log_state_update, get_config, get_state_update_at};
+use crate::alerts::{self, Alert};
+use crate::head::{self, HeadEvent};
+use crate::l2::{get_sync_progress, STARKNET_STATE_UPDATE};
+use crate::utility::{convert_log_state_update, get_config, get_state_update_at, u256_to_starkfelt};
 use crate::utils::constant::LOG_STATE_UPDTATE_TOPIC;
 
+/// Number of blocks the node must have synced past an L1 state update before checking that the
+/// block verified on L1 is in the database, which is indexed in the background.
+const L1_DIVERGENCE_MIN_DEPTH: u64 = 64;
+
 lazy_static! {
     /// Shared latest L2 state update verified on L1
     pub static ref ETHEREUM_STATE_UPDATE: Arc<RwLock<L1StateUpdate>> = Arc::new(RwLock::new(L1StateUpdate {
@@ -30,6 +37,24 @@ lazy_static! {
     }));
 }
 
+lazy_static! {
+    /// Shared Starknet OS registered in the core contract, `None` until read from L1
+    static ref STARKNET_OS_CONFIG: RwLock<Option<StarknetOsConfig>> = RwLock::new(None);
+}
+
+/// The Starknet OS registered in the core contract: state updates are only accepted on L1 with a
+/// proof of its execution.
+///
+/// The network upgrading its OS changes these hashes, which nodes can watch to know that they have
+/// to be upgraded as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StarknetOsConfig {
+    /// The hash of the Starknet OS program.
+    pub program_hash: StarkHash,
+    /// The hash of the configuration of the Starknet OS.
+    pub config_hash: StarkHash,
+}
+
 /// Contains the Starknet verified state on L1
 #[derive(Debug, Clone, Deserialize)]
 pub struct L1StateUpdate {
@@ -135,6 +160,73 @@ impl EthereumClient {
         Ok(StarkHash::from(Felt252Wrapper::from_hex_be(&result.to_string()).expect("Failed to parse block hash")))
     }
 
+    /// Get the Starknet OS currently registered in the core contract
+    pub async fn get_os_config(&self) -> Result<StarknetOsConfig> {
+        let address: Address = get_config().expect("Failed to get config").l1_core_address;
+        abigen!(StarknetCore, "crates/client/sync/src/utils/abis/starknet_core.json");
+        let contract = StarknetCore::new(address, Arc::clone(&self.provider));
+
+        let program_hash = contract.program_hash().call().await?;
+        let config_hash = contract.config_hash().call().await?;
+        Ok(StarknetOsConfig {
+            program_hash: u256_to_starkfelt(program_hash).map_err(anyhow::Error::msg)?,
+            config_hash: u256_to_starkfelt(config_hash).map_err(anyhow::Error::msg)?,
+        })
+    }
+
+    /// Get the base fee of the latest L1 block, and its blob base fee if the node supports
+    /// `eth_blobBaseFee`, in wei
+    pub async fn get_fees(&self) -> Result<(u128, Option<u128>)> {
+        let block =
+            self.provider.get_block(EthBlockNumber::Latest).await?.ok_or_else(|| anyhow::anyhow!("No latest block"))?;
+        let base_fee = block.base_fee_per_gas.ok_or_else(|| anyhow::anyhow!("No base fee in the latest block"))?;
+        let blob_base_fee: Option<U256> = self.provider.request("eth_blobBaseFee", ()).await.ok();
+
+        Ok((base_fee.low_u128(), blob_base_fee.map(|fee| fee.low_u128())))
+    }
+
+    /// Get the state updates of the core contract sent between two L1 blocks, included, along
+    /// with the L1 block and transaction each was sent in
+    pub async fn get_state_update_logs(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<(u64, H256, L1StateUpdate)>> {
+        let topic = H256::from_slice(&hex::decode(&LOG_STATE_UPDTATE_TOPIC[2..])?);
+        let address = get_config().map_err(anyhow::Error::msg)?.l1_core_address;
+        let filter = Filter::new().from_block(from_block).to_block(to_block).address(vec![address]).topic0(topic);
+
+        let mut state_updates = Vec::new();
+        for log in self.provider.get_logs(&filter).await? {
+            let (Some(block_number), Some(transaction_hash)) = (log.block_number, log.transaction_hash) else {
+                continue;
+            };
+            let state_update =
+                convert_log_state_update(LogStateUpdate::decode_log(&log.into())?).map_err(anyhow::Error::msg)?;
+            state_updates.push((block_number.as_u64(), transaction_hash, state_update));
+        }
+        Ok(state_updates)
+    }
+
+    /// Get the versioned hashes of the blobs sent along an L1 transaction, empty if it has none
+    pub async fn get_blob_versioned_hashes(&self, transaction_hash: H256) -> Result<Vec<H256>> {
+        let transaction: Value = self.provider.request("eth_getTransactionByHash", [transaction_hash]).await?;
+        match transaction.get("blobVersionedHashes") {
+            Some(hashes) => Ok(serde_json::from_value(hashes.clone())?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Get the timestamp of an L1 block
+    pub async fn get_block_timestamp(&self, block_number: u64) -> Result<u64> {
+        let block = self
+            .provider
+            .get_block(block_number)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("No L1 block {block_number}"))?;
+        Ok(block.timestamp.as_u64())
+    }
+
     /// Get the last Starknet state update verified on the L1
     pub async fn get_initial_state(client: &EthereumClient) -> Result<L1StateUpdate, ()> {
         let block_number = client.get_last_block_number().await.map_err(|e| {
@@ -172,6 +264,8 @@ impl EthereumClient {
                     let format_event =
                         convert_log_state_update(log.clone()).expect("Failed to format event into an L1StateUpdate");
                     update_l1(format_event);
+                    // OS upgrades take effect with state updates
+                    self.refresh_os_config().await;
                 }
                 Err(e) => log::error!("Error while listening for events: {:?}", e),
             }
@@ -179,6 +273,44 @@ impl EthereumClient {
 
         Ok(())
     }
+
+    /// Reads the Starknet OS registered in the core contract, and records it if it changed
+    pub async fn refresh_os_config(&self) {
+        match self.get_os_config().await {
+            Ok(os_config) => update_os_config(os_config),
+            Err(e) => log::error!("Failed to get the Starknet OS program and config hashes: {e}"),
+        }
+    }
+}
+
+/// Returns the Starknet OS registered in the core contract, if it was read from L1 already
+pub fn get_os_config() -> Option<StarknetOsConfig> {
+    *STARKNET_OS_CONFIG.read().expect("Failed to acquire read lock on STARKNET_OS_CONFIG")
+}
+
+/// Records the Starknet OS registered in the core contract, reporting upgrades to the operator
+fn update_os_config(os_config: StarknetOsConfig) {
+    let previous =
+        STARKNET_OS_CONFIG.write().expect("Failed to acquire write lock on STARKNET_OS_CONFIG").replace(os_config);
+
+    match previous {
+        None => log::info!(
+            "🧬 Starknet OS registered on L1: program hash {}, config hash {}",
+            os_config.program_hash,
+            os_config.config_hash
+        ),
+        Some(previous) if previous != os_config => {
+            log::warn!(
+                "🚨 The Starknet OS registered on L1 changed: program hash {} -> {}, config hash {} -> {}",
+                previous.program_hash,
+                os_config.program_hash,
+                previous.config_hash,
+                os_config.config_hash
+            );
+            alerts::raise(Alert::OsChanged { previous, current: os_config });
+        }
+        Some(_) => {}
+    }
 }
 
 /// Update the L1 state with the latest data
@@ -196,6 +328,37 @@ pub fn update_l1(state_update: L1StateUpdate) {
             last_state_update.write().expect("Failed to acquire write lock on ETHEREUM_STATE_UPDATE");
         *new_state_update = state_update.clone();
     }
+    head::publish(HeadEvent::L1Confirmed(state_update.clone()));
+
+    let last_synced_block = get_sync_progress().map(|progress| progress.current_block.1);
+    if last_synced_block.is_some_and(|last| state_update.block_number + L1_DIVERGENCE_MIN_DEPTH <= last) {
+        match DeoxysBackend::mapping().block_hash(state_update.block_hash) {
+            Ok(Some(_)) => {}
+            Ok(None) => report_l1_divergence(&state_update),
+            Err(e) => log::error!("Failed to look up the block verified on L1: {e}"),
+        }
+    }
+}
+
+/// Checks a block synced by the L2 sync against the latest state update verified on L1, when
+/// they are at the same height.
+pub fn check_synced_block(block_number: u64, block_hash: StarkHash) {
+    let state_update = ETHEREUM_STATE_UPDATE.read().expect("Failed to acquire read lock on ETHEREUM_STATE_UPDATE");
+    if state_update.block_number == block_number && state_update.block_hash != block_hash {
+        report_l1_divergence(&state_update);
+    }
+}
+
+fn report_l1_divergence(state_update: &L1StateUpdate) {
+    log::error!(
+        "🚨 The block verified on L1 at #{} ({}) was not synced by the node",
+        state_update.block_number,
+        state_update.block_hash
+    );
+    alerts::raise(Alert::L1Divergence {
+        block_number: state_update.block_number,
+        l1_block_hash: state_update.block_hash,
+    });
 }
 
 /// Verify the L1 state with the latest data
@@ -243,6 +406,7 @@ pub async fn sync(l1_url: Url) {
         Err(_) => return,
     };
     update_l1(initial_state);
+    client.refresh_os_config().await;
 
     // Listen to LogStateUpdate (0x77552641) update and send changes continusly
     let start_block =
@@ -1,37 +1,77 @@
 //! Contains the code required to sync data from the feeder efficiently.
+use std::ops::Range;
 use std::pin::pin;
 use std::str::FromStr;
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Once, RwLock};
 
+use blockifier::state::cached_state::CommitmentStateDiff;
 use futures::prelude::*;
 use lazy_static::lazy_static;
+use mc_db::{ClassDeclaration, DbError, DeoxysBackend};
+use mc_otel::KeyValue;
 use mc_storage::OverrideHandle;
-use mp_block::state_update::StateUpdateWrapper;
+use mp_block::receipt::TransactionReceiptWrapper;
+use mp_block::state_update::{StateDiffWrapper, StateUpdateWrapper};
+use mp_block::versioned_constants::StarknetVersion;
 use mp_block::DeoxysBlock;
-use mp_contract::class::ClassUpdateWrapper;
+use mp_contract::class::{ClassUpdateWrapper, ContractClassData};
+use mp_digest_log::find_starknet_block;
 use mp_felt::Felt252Wrapper;
+use mp_hashers::pedersen::PedersenHasher;
 use mp_types::block::{DBlockT, DHashT};
+use parity_scale_codec::Encode;
+use prometheus_endpoint::Registry;
 use serde::Deserialize;
 use sp_blockchain::HeaderBackend;
 use sp_core::H256;
 use sp_runtime::generic::{Block as RuntimeBlock, Header};
-use sp_runtime::traits::BlakeTwo256;
+use sp_runtime::traits::{BlakeTwo256, Header as HeaderT};
 use sp_runtime::OpaqueExtrinsic;
+use starknet_api::core::{ClassHash, CompiledClassHash};
 use starknet_api::hash::StarkHash;
-use starknet_core::types::{PendingStateUpdate, StarknetError};
+use starknet_api::transaction::TransactionHash;
+use starknet_core::types::PendingStateUpdate;
 use starknet_ff::FieldElement;
-use starknet_providers::sequencer::models::{BlockId, StateUpdate};
-use starknet_providers::{ProviderError, SequencerGatewayProvider};
-use thiserror::Error;
-use tokio::sync::mpsc;
+use starknet_providers::sequencer::models::{self as p, BlockId, StateUpdate};
 use tokio::sync::mpsc::Sender;
+use tokio::sync::{mpsc, watch};
 use tokio::time::Duration;
 
-use crate::commitments::lib::{build_commitment_state_diff, update_state_root};
+use crate::admission::AdmissionController;
+use crate::alerts::{self, Alert};
+use crate::checkpoint::{BlockHashes, TrustedCheckpoint};
+use crate::commitments::hashers::CommitmentHashers;
+use crate::commitments::lib::{build_commitment_state_diff, merge_commitment_state_diffs, update_state_root};
+use crate::disk_guard::{self, DiskGuard};
+use crate::errors::{ConversionError, SyncError};
+use crate::fetch::compile;
 use crate::fetch::fetchers::{fetch_block_and_updates, FetchConfig};
-use crate::l1::ETHEREUM_STATE_UPDATE;
+use crate::fetch::gateway::{GatewayMetrics, GatewayProvider, HEALTH_CHECK_INTERVAL};
+use crate::fetch::p2p::{self, P2pFetcher};
+use crate::head::{self, HeadEvent};
+use crate::l1::{check_synced_block, ETHEREUM_STATE_UPDATE};
+use crate::lifecycle::{self, Phase};
+use crate::ordering::BlockSequencer;
+use crate::progress::{self, ProgressEvent, Queue, Stage};
+use crate::timestamps::{self, TimestampMonitor};
 use crate::utility::block_hash_substrate;
-use crate::CommandSink;
+use crate::utils::constant::MAX_SUPPORTED_STARKNET_VERSION;
+use crate::{
+    fanout, flat_storage, import, reorgs, replication, transaction_index, trie_metrics, ChainReverter, CommandSink,
+};
+
+/// Number of blocks each worker applying blocks can get ahead of the next one.
+const PIPELINE_DEPTH: usize = 4;
+
+/// Number of blocks before the trusted checkpoint whose state diffs are committed to the tries at
+/// once, see [`crate::checkpoint`].
+const TRUSTED_TRIE_BATCH: usize = 256;
+
+/// Delay before fetching again a block whose fetch failed with a transient error, once the gateway
+/// provider gave up retrying. It doubles with each failure, up to [`MAX_FETCH_RETRY_DELAY`].
+const FETCH_RETRY_DELAY: Duration = Duration::from_secs(2);
+const MAX_FETCH_RETRY_DELAY: Duration = Duration::from_secs(60);
 
 async fn spawn_compute<F, R>(func: F) -> R
 where
@@ -47,15 +87,6 @@ where
     rx.await.expect("tokio channel closed")
 }
 
-// TODO: add more error variants, which are more explicit
-#[derive(Error, Debug)]
-pub enum L2SyncError {
-    #[error("provider error")]
-    Provider(#[from] ProviderError),
-    #[error("fetch retry limit exceeded")]
-    FetchRetryLimit,
-}
-
 /// Contains the latest Starknet verified state on L2
 #[derive(Debug, Clone, Deserialize)]
 pub struct L2StateUpdate {
@@ -96,6 +127,20 @@ lazy_static! {
     pub static ref STARKNET_HIGHEST_BLOCK_HASH_AND_NUMBER: RwLock<(FieldElement, u64)> = RwLock::new((FieldElement::default(), 0));
 }
 
+/// Progress of the sync worker since the node was started.
+#[derive(Debug, Clone, Copy)]
+pub struct SyncProgress {
+    /// Hash and number of the first block applied by the sync worker.
+    pub starting_block: (FieldElement, u64),
+    /// Hash and number of the last block applied by the sync worker.
+    pub current_block: (FieldElement, u64),
+}
+
+lazy_static! {
+    /// Shared sync worker progress, `None` until the first block has been applied
+    static ref SYNC_PROGRESS: RwLock<Option<SyncProgress>> = RwLock::new(None);
+}
+
 lazy_static! {
     /// Shared pending block data, using a RwLock to allow for concurrent reads and exclusive writes
     static ref STARKNET_PENDING_BLOCK: RwLock<Option<DeoxysBlock>> = RwLock::new(None);
@@ -112,6 +157,21 @@ pub fn get_highest_block_hash_and_number() -> (FieldElement, u64) {
         .expect("Failed to acquire read lock on STARKNET_HIGHEST_BLOCK_HASH_AND_NUMBER")
 }
 
+pub fn get_sync_progress() -> Option<SyncProgress> {
+    *SYNC_PROGRESS.read().expect("Failed to acquire read lock on SYNC_PROGRESS")
+}
+
+/// Registers that the block with the given hash and number has been applied by the sync worker.
+pub(crate) fn update_sync_progress(block_hash: FieldElement, block_number: u64) {
+    let mut progress = SYNC_PROGRESS.write().expect("Failed to acquire write lock on SYNC_PROGRESS");
+    let current_block = (block_hash, block_number);
+
+    match progress.as_mut() {
+        Some(progress) => progress.current_block = current_block,
+        None => *progress = Some(SyncProgress { starting_block: current_block, current_block }),
+    }
+}
+
 pub fn get_pending_block() -> Option<DeoxysBlock> {
     STARKNET_PENDING_BLOCK.read().expect("Failed to acquire read lock on STARKNET_PENDING_BLOCK").clone()
 }
@@ -134,46 +194,122 @@ pub struct SenderConfig {
     pub command_sink: CommandSink,
     // Storage overrides for accessing stored classes
     pub overrides: Arc<OverrideHandle<RuntimeBlock<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
+    /// Reverts the blocks at the top of the local chain, replaced by a reorg.
+    pub revert_chain: ChainReverter,
 }
 
 /// Spawns workers to fetch blocks and state updates from the feeder.
 /// `n_blocks` is optionally the total number of blocks to sync, for debugging/benchmark purposes.
+/// Gateway request, class compilation, block timestamp and trie commit metrics are registered in
+/// `prometheus_registry` if provided.
 pub async fn sync<C>(
     mut sender_config: SenderConfig,
     fetch_config: FetchConfig,
     first_block: u64,
     n_blocks: Option<usize>,
     client: Arc<C>,
+    prometheus_registry: Option<Registry>,
 ) where
     C: HeaderBackend<DBlockT> + 'static,
 {
-    let SenderConfig { block_sender, state_update_sender, class_sender, command_sink, overrides } = &mut sender_config;
-    let provider = Arc::new(SequencerGatewayProvider::new(
-        fetch_config.gateway.clone(),
-        fetch_config.feeder_gateway.clone(),
-        fetch_config.chain_id,
-        fetch_config.api_key,
-    ));
+    let metrics = prometheus_registry.as_ref().and_then(|registry| match GatewayMetrics::register(registry) {
+        Ok(metrics) => Some(metrics),
+        Err(e) => {
+            log::error!("Failed to register gateway metrics: {e}");
+            None
+        }
+    });
+    if let Some(Err(e)) = prometheus_registry.as_ref().map(compile::register_metrics) {
+        log::error!("Failed to register class compilation metrics: {e}");
+    }
+    if let Some(Err(e)) = prometheus_registry.as_ref().map(timestamps::register_metrics) {
+        log::error!("Failed to register block timestamp metrics: {e}");
+    }
+    if let Some(Err(e)) = prometheus_registry.as_ref().map(disk_guard::register_metrics) {
+        log::error!("Failed to register disk guard metrics: {e}");
+    }
+    if let Some(Err(e)) = prometheus_registry.as_ref().map(fanout::register_metrics) {
+        log::error!("Failed to register notification fan-out metrics: {e}");
+    }
+    if let Some(Err(e)) = prometheus_registry.as_ref().map(trie_metrics::register_metrics) {
+        log::error!("Failed to register trie commit metrics: {e}");
+    }
+    let provider = Arc::new(GatewayProvider::new(&fetch_config, metrics));
     let mut last_block_hash = None;
 
-    // TODO: move this somewhere else
-    if first_block == 1 {
-        let state_update =
-            provider.get_state_update(BlockId::Number(0)).await.expect("getting state update for genesis block");
-        verify_l2(0, &state_update, overrides, None).expect("verifying genesis block");
+    if let Err(e) = init_state(&provider, first_block, &sender_config.overrides, fetch_config.hashers).await {
+        log::error!("❗ Failed to initialize the state, the sync is stopped: {e}");
+        return;
     }
 
-    let fetch_stream = (first_block..).map(|block_n| {
-        let provider = Arc::clone(&provider);
-        let overrides = Arc::clone(overrides);
-        let client = Arc::clone(&client);
-        async move {
-            tokio::spawn(fetch_block_and_updates(block_n, provider, overrides, client)).await.expect("tokio join error")
+    let mut first_block = first_block;
+    for path in &fetch_config.import_archives {
+        let imported = import::import_blocks(
+            path,
+            first_block,
+            &mut sender_config,
+            client.as_ref(),
+            fetch_config.verify,
+            fetch_config.hashers,
+        )
+        .await;
+        first_block = match imported {
+            Ok(next_block) => next_block,
+            Err(e) => {
+                let e = SyncError::Import { path: path.clone(), reason: e.to_string() };
+                log::error!("❗ {e}, the sync is stopped");
+                return;
+            }
+        };
+    }
+
+    if let Some(primary) = &fetch_config.replicate_from {
+        let e = replication::follow(
+            primary,
+            first_block,
+            &mut sender_config,
+            client.as_ref(),
+            fetch_config.verify,
+            fetch_config.hashers,
+        )
+        .await;
+        log::error!("❗ Stopped replicating the blocks of the primary node at {}: {e}", primary.address);
+        return;
+    }
+
+    let checkpoint = match fetch_config.trusted_checkpoint {
+        Some(block_hash) => {
+            let checkpoint = match TrustedCheckpoint::resolve(&provider, block_hash).await {
+                Ok(checkpoint) => checkpoint,
+                Err(e) => {
+                    log::error!(
+                        "❗ Failed to resolve the trusted checkpoint {block_hash:#x}, the sync is stopped: {e}"
+                    );
+                    return;
+                }
+            };
+            if first_block <= checkpoint.block_number {
+                // the tries will not hold the state roots of the trusted blocks
+                if let Err(e) = DeoxysBackend::meta().set_trusted_checkpoint(checkpoint.block_number) {
+                    log::error!("❗ Failed to store the trusted checkpoint, the sync is stopped: {e}");
+                    return;
+                }
+                log::info!(
+                    "🛡️ Trusting the commitments of the blocks up to the checkpoint {} ({block_hash:#x})",
+                    checkpoint.block_number
+                );
+            }
+            Some(checkpoint)
         }
-    });
-    // Have 10 fetches in parallel at once, using futures Buffered
-    let fetch_stream = stream::iter(fetch_stream.take(n_blocks.unwrap_or(usize::MAX))).buffered(10);
-    let (fetch_stream_sender, mut fetch_stream_receiver) = mpsc::channel(10);
+        None => None,
+    };
+
+    let admission = Arc::new(AdmissionController::new(fetch_config.admission));
+    let p2p = fetch_config.p2p.clone().and_then(P2pFetcher::new).map(Arc::new);
+    if p2p.is_some() {
+        log::info!("🌐 Pulling the state diffs and classes of the blocks from the p2p bootnodes");
+    }
+    let last_block = n_blocks.map_or(u64::MAX, |n_blocks| first_block.saturating_add(n_blocks as u64));
 
     tokio::select!(
         // update highest block hash and number
@@ -187,104 +323,732 @@ pub async fn sync<C>(
                 }
             }
         } => {},
-        // fetch blocks and updates in parallel
+        // health check the gateways which get no requests, so that the recovered ones get them again
         _ = async {
-            fetch_stream.for_each(|val| async {
-                fetch_stream_sender.send(val).await.expect("receiver is closed");
-            }).await;
-
-            drop(fetch_stream_sender); // dropping the channel makes the recieving task stop once the queue is empty.
-
+            let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                provider.probe_idle().await;
+            }
+        } => {},
+        // fill in the flat storage of databases created before it existed
+        _ = async {
+            flat_storage::backfill(&provider).await;
             std::future::pending().await
         } => {},
-        // apply blocks and updates sequentially
+        // index the transactions of databases created before the transaction index existed
         _ = async {
-            let mut block_n = first_block;
-            while let Some(val) = pin!(fetch_stream_receiver.recv()).await {
-                if matches!(val, Err(L2SyncError::Provider(ProviderError::StarknetError(StarknetError::BlockNotFound)))) {
-                    break;
+            transaction_index::backfill(Arc::clone(&client), fetch_config.chain_id.into()).await;
+            std::future::pending().await
+        } => {},
+        // fetch and apply the blocks, from the common ancestor of the new chain after a reorg
+        _ = async {
+            let mut first_block = first_block;
+            let sources = BlockSources { provider: &provider, client: &client, admission: &admission, p2p: &p2p };
+            while let Some(reorg_at) = apply_blocks(
+                first_block..last_block,
+                &mut sender_config,
+                &fetch_config,
+                checkpoint.as_ref(),
+                &sources,
+                &mut last_block_hash,
+            )
+            .await
+            {
+                let revert_chain = &sender_config.revert_chain;
+                match reorgs::lib::handle_reorg(&provider, client.as_ref(), reorg_at, revert_chain).await {
+                    Ok(common_ancestor) => first_block = common_ancestor + 1,
+                    Err(e) => {
+                        report_sync_failure(reorg_at, e);
+                        break;
+                    }
                 }
+            }
+        } => {},
+    );
 
-                let (block, state_update, class_update) = val.expect("fetching block");
-
-                let block_hash = block_hash_substrate(client.as_ref(), block_n - 1);
-
-                let (state_update, block_conv) = {
-                    let verify = fetch_config.verify;
-                    let overrides = Arc::clone(overrides);
-                    let state_update = Arc::new(state_update);
-                    let state_update_1 = Arc::clone(&state_update);
-
-                    let block_conv = spawn_compute(move || {
-                        let convert_block = |block| {
-                            let start = std::time::Instant::now();
-                            let block_conv = crate::convert::convert_block_sync(block);
-                            log::debug!("convert::convert_block_sync: {:?}", std::time::Instant::now() - start);
-                            block_conv
-                        };
-                        let ver_l2 = || {
-                            let start = std::time::Instant::now();
-                            verify_l2(block_n, &state_update, &overrides, block_hash)
-                                .expect("verifying block");
-                            log::debug!("verify_l2: {:?}", std::time::Instant::now() - start);
-                        };
-
-                        if verify {
-                            let (_, block_conv) = rayon::join(ver_l2, || convert_block(block));
-                            let last_l2_state_update =
-                                STARKNET_STATE_UPDATE.read().expect("Failed to acquire read lock on STARKNET_STATE_UPDATE");
-                            if (block_conv.header().global_state_root) != last_l2_state_update.global_root {
-                                log::info!(
-                                    "❗ Verified state: {} doesn't match fetched state: {}",
-                                    last_l2_state_update.global_root,
-                                    block_conv.header().global_state_root
-                                );
-                            }
-                            block_conv
-                        } else {
-                            convert_block(block)
-                        }
-                    })
-                    .await;
+    log::debug!("L2 sync finished :)");
+}
+
+/// Where the blocks applied by [`apply_blocks`] are fetched from.
+struct BlockSources<'a, C> {
+    provider: &'a Arc<GatewayProvider>,
+    client: &'a Arc<C>,
+    admission: &'a Arc<AdmissionController>,
+    p2p: &'a Option<Arc<P2pFetcher>>,
+}
+
+/// Fetches the blocks of `range` and applies them in a pipeline: blocks are converted, committed to
+/// the tries and written to the database by separate workers, so that converting a block and
+/// computing its commitments overlaps with the trie update and database write of the previous
+/// blocks.
+///
+/// Each block must have the previous one as parent, the first one the head of the local chain.
+/// Returns the number of the first block which does not, once the blocks before it are applied, so
+/// that the caller can handle the reorg. Returns `None` once the sync stopped.
+async fn apply_blocks<C>(
+    range: Range<u64>,
+    sender_config: &mut SenderConfig,
+    fetch_config: &FetchConfig,
+    checkpoint: Option<&TrustedCheckpoint>,
+    sources: &BlockSources<'_, C>,
+    last_block_hash: &mut Option<H256>,
+) -> Option<u64>
+where
+    C: HeaderBackend<DBlockT> + 'static,
+{
+    let BlockSources { provider, client, admission, p2p } = *sources;
+    let SenderConfig { block_sender, state_update_sender, class_sender, command_sink, overrides, .. } = sender_config;
+    let first_block = range.start;
 
-                    (Arc::try_unwrap(state_update_1).expect("arc should not be aliased"), block_conv)
+    let fetch_stream = range.map(|block_n| {
+        let provider = Arc::clone(provider);
+        let overrides = Arc::clone(overrides);
+        let client = Arc::clone(client);
+        let admission = Arc::clone(admission);
+        let p2p = p2p.clone();
+        async move {
+            admission.admit(block_n, get_highest_block_hash_and_number().1).await;
+            let start = std::time::Instant::now();
+            let val = fetch_block_until_available(block_n, provider, overrides, client, p2p).await;
+            if val.is_ok() {
+                progress::publish(ProgressEvent::stage(Stage::Fetch, block_n, start.elapsed()));
+                lifecycle::record(Phase::Fetch, block_n, start.elapsed());
+            }
+            (block_n, val)
+        }
+    });
+    // Have 10 fetches in parallel at once, using futures Buffered
+    let fetch_stream = stream::iter(fetch_stream).buffered(10);
+    // bounded, so that fetching is throttled down to the pace at which blocks are applied
+    let queue_capacity = fetch_config.block_queue_capacity.max(1);
+    let (fetch_stream_sender, mut fetch_stream_receiver) = mpsc::channel(queue_capacity);
+    // number of fetched blocks in the channel, reported in the progress events
+    let fetched_queue_depth = AtomicUsize::new(0);
+
+    // fetch blocks and updates in parallel
+    let fetch = async {
+        let mut fetch_stream = pin!(fetch_stream);
+        while let Some(val) = fetch_stream.next().await {
+            // counted before being sent, so that the receiver never sees a negative depth
+            fetched_queue_depth.fetch_add(1, Ordering::Relaxed);
+            if fetch_stream_sender.send(val).await.is_err() {
+                // the blocks are no longer applied, the sync stopped
+                break;
+            }
+        }
+
+        drop(fetch_stream_sender); // dropping the channel makes the recieving task stop once the queue is empty.
+
+        std::future::pending::<()>().await
+    };
+
+    let (converted_sender, converted_receiver) = mpsc::channel(PIPELINE_DEPTH);
+    let (verified_sender, verified_receiver) = mpsc::channel(PIPELINE_DEPTH);
+    // number of the next block to seal, so that trie updates can wait for the parent block
+    let (sealed_sender, sealed_receiver) = watch::channel(first_block);
+
+    let convert = async {
+        // fetches may complete in any order, blocks are applied by strictly increasing number
+        let mut sequencer = BlockSequencer::new(first_block);
+        let mut disk_guard = DiskGuard::new(fetch_config.disk_watermark.clone());
+        // hash of the last converted block, which the next one must have as parent hash up to the
+        // checkpoint
+        let mut parent_hash = checkpoint
+            .filter(|checkpoint| first_block <= checkpoint.block_number)
+            .and_then(|_| local_block_hash(client.as_ref(), first_block - 1));
+        // hash of the head of the chain, the last block converted or else the last block of the local
+        // chain, which the next block has as parent unless the chain was reorganized
+        let mut head_hash = first_block.checked_sub(1).and_then(|head| local_block_hash(client.as_ref(), head));
+        let mut reorg_at = None;
+        'fetched: while let Some((fetched_n, val)) = pin!(fetch_stream_receiver.recv()).await {
+            if let Err(e) = sequencer.push(fetched_n, val) {
+                report_sync_failure(fetched_n, e.into());
+                break 'fetched;
+            }
+            let depth = fetched_queue_depth.fetch_sub(1, Ordering::Relaxed) - 1;
+            progress::publish(ProgressEvent::Queue { queue: Queue::Fetched, depth, capacity: Some(queue_capacity) });
+            progress::publish(ProgressEvent::Queue {
+                queue: Queue::Reordering,
+                depth: sequencer.buffered(),
+                capacity: None,
+            });
+
+            while let Some((block_n, val)) = sequencer.pop() {
+                if let Err(e) = disk_guard.check(block_n) {
+                    report_sync_failure(block_n, e);
+                    break 'fetched;
+                }
+                if let Ok((block, ..)) = &val
+                    && head_hash.is_some_and(|head_hash| head_hash != block.parent_block_hash)
+                {
+                    reorg_at = Some(block_n);
+                    break 'fetched;
+                }
+                let converted = match val {
+                    Ok((block, state_update, class_update)) => {
+                        convert_block(
+                            block_n,
+                            block,
+                            state_update,
+                            class_update,
+                            fetch_config.verify,
+                            fetch_config.force_unsupported,
+                            checkpoint,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e),
                 };
+                let converted = converted.and_then(|converted| match checkpoint {
+                    Some(checkpoint) => {
+                        parent_hash = Some(check_checkpoint(checkpoint, &converted, parent_hash)?);
+                        Ok(converted)
+                    }
+                    None => Ok(converted),
+                });
+                match converted {
+                    Ok(converted) => {
+                        head_hash = Some(converted.starknet_block_hash);
+                        if converted_sender.send(converted).await.is_err() {
+                            break 'fetched;
+                        }
+                    }
+                    Err(e) => {
+                        report_sync_failure(block_n, e);
+                        break 'fetched;
+                    }
+                }
+            }
+        }
+        // the next workers stop once they are done with the blocks converted so far
+        drop(converted_sender);
+        reorg_at
+    };
+
+    let apply = async {
+        let (reorg_at, ..) = tokio::join!(
+            convert,
+            update_tries(
+                converted_receiver,
+                verified_sender,
+                sealed_receiver,
+                Arc::clone(client),
+                Arc::clone(overrides),
+                fetch_config.hashers,
+            ),
+            commit_blocks(
+                verified_receiver,
+                sealed_sender,
+                block_sender,
+                state_update_sender,
+                class_sender,
+                command_sink,
+                last_block_hash,
+                TimestampMonitor::new(fetch_config.timestamp_drift_tolerance),
+            ),
+        );
+        reorg_at
+    };
+
+    tokio::select!(
+        _ = fetch => None,
+        reorg_at = apply => reorg_at,
+    )
+}
+
+/// A block converted to the node types, flowing through the workers applying it.
+struct ConvertedBlock {
+    block_n: u64,
+    block: DeoxysBlock,
+    starknet_block_hash: FieldElement,
+    state_update: StateUpdateWrapper,
+    /// The changes to commit to the tries, if the state root is verified.
+    state_diff: Option<CommitmentStateDiff>,
+    class_update: Vec<ContractClassData>,
+    receipts: Vec<TransactionReceiptWrapper>,
+    /// Whether the block is before the trusted checkpoint, see [`crate::checkpoint`].
+    trusted: bool,
+}
+
+/// Checks that `block` chains up to the `checkpoint`, given the hash computed for its parent if
+/// known, and returns its hash computed from its header.
+fn check_checkpoint(
+    checkpoint: &TrustedCheckpoint,
+    block: &ConvertedBlock,
+    parent_hash: Option<FieldElement>,
+) -> Result<FieldElement, SyncError> {
+    let header = block.block.header();
+    let hashes = BlockHashes {
+        computed: header.hash::<PedersenHasher>().into(),
+        fetched: block.starknet_block_hash,
+        parent: Felt252Wrapper::from(header.parent_block_hash).into(),
+    };
+    checkpoint.check(block.block_n, hashes, parent_hash)?;
+    Ok(hashes.computed)
+}
+
+/// The hash of block `block_n` of the local chain, computed from its header, if it is stored.
+pub(crate) fn local_block_hash<C>(client: &C, block_n: u64) -> Option<FieldElement>
+where
+    C: HeaderBackend<DBlockT>,
+{
+    Some(local_block(client, block_n)?.header().hash::<PedersenHasher>().into())
+}
+
+/// The block `block_n` of the local chain, if it was imported.
+pub(crate) fn local_block<C>(client: &C, block_n: u64) -> Option<DeoxysBlock>
+where
+    C: HeaderBackend<DBlockT>,
+{
+    let substrate_block_hash = client.hash(u32::try_from(block_n).ok()?).ok()??;
+    let header = client.header(substrate_block_hash).ok()??;
+    find_starknet_block(header.digest()).ok()
+}
 
-                let block_sender = &*block_sender;
-                tokio::join!(
-                    async move {
-                        block_sender.send(block_conv).await.expect("block reciever channel is closed");
-                    },
-                    async {
-                        // Now send state_update, which moves it. This will be received
-                        // by QueryBlockConsensusDataProvider in deoxys/crates/node/src/service.rs
-                        state_update_sender
-                            .send(StateUpdateWrapper::from(state_update))
-                            .await
-                            .expect("state updater is not running");
-                    },
-                    async {
-                        // do the same to class update
-                        class_sender
-                            .send(ClassUpdateWrapper(class_update))
-                            .await
-                            .expect("class updater is not running");
+/// Converts a fetched block to the node types, computing its transaction and event commitments,
+/// along with the state diff to commit to the tries if `verify` is set.
+///
+/// Blocks of Starknet versions newer than the ones supported by this build are rejected, unless
+/// `force_unsupported` is set. The commitments of the blocks before the `checkpoint` are taken
+/// from the feeder gateway.
+async fn convert_block(
+    block_n: u64,
+    block: p::Block,
+    state_update: StateUpdate,
+    class_update: Vec<ContractClassData>,
+    verify: bool,
+    force_unsupported: bool,
+    checkpoint: Option<&TrustedCheckpoint>,
+) -> Result<ConvertedBlock, SyncError> {
+    check_starknet_version(block_n, block.starknet_version.as_deref(), force_unsupported)?;
+
+    let starknet_block_hash = block.block_hash.unwrap_or_default();
+    let trusted = checkpoint.is_some_and(|checkpoint| checkpoint.trusts(block_n));
+    let start = std::time::Instant::now();
+    let receipts = block.transaction_receipts.iter().map(TransactionReceiptWrapper::from).collect();
+    let state_update = StateUpdateWrapper::from(state_update);
+    lifecycle::record(Phase::Decode, block_n, start.elapsed());
+
+    spawn_compute(move || -> Result<ConvertedBlock, SyncError> {
+        let start = std::time::Instant::now();
+        let (block, state_diff) = rayon::join(
+            || {
+                timed(Phase::Convert, block_n, || {
+                    if trusted {
+                        crate::convert::convert_trusted_block_sync(block, &state_update.state_diff)
+                    } else {
+                        crate::convert::convert_block_sync(block, &state_update.state_diff)
                     }
-                );
+                })
+            },
+            || verify.then(|| timed(Phase::Verify, block_n, || build_commitment_state_diff(state_update.clone()))),
+        );
+        log::debug!("convert_block: {:?}", start.elapsed());
+        progress::publish(ProgressEvent::stage(Stage::Convert, block_n, start.elapsed()));
+
+        Ok(ConvertedBlock {
+            block_n,
+            block: block?,
+            starknet_block_hash,
+            state_update,
+            state_diff,
+            class_update,
+            receipts,
+            trusted,
+        })
+    })
+    .await
+}
 
-                let start = std::time::Instant::now();
-                create_block(command_sink, &mut last_block_hash).await.expect("creating block");
-                log::debug!("end create_block: {:?}", std::time::Instant::now() - start);
-                block_n += 1;
+/// Fetches block `block_n` along with its state update and classes.
+///
+/// The gateway provider already retries the failed requests a few times. When it gives up on a
+/// [transient](SyncError::is_transient) error, e.g. while the gateway is unreachable, the block is
+/// fetched again after a growing delay instead of stopping the sync, which only stops on the errors
+/// which won't go away by themselves.
+async fn fetch_block_until_available<C>(
+    block_n: u64,
+    provider: Arc<GatewayProvider>,
+    overrides: Arc<OverrideHandle<RuntimeBlock<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
+    client: Arc<C>,
+    p2p: Option<Arc<P2pFetcher>>,
+) -> Result<(p::Block, StateUpdate, Vec<ContractClassData>), SyncError>
+where
+    C: HeaderBackend<DBlockT> + 'static,
+{
+    let mut delay = FETCH_RETRY_DELAY;
+    loop {
+        let attributes = vec![KeyValue::new("block_number", block_n as i64)];
+        let fetch = fetch_block_and_updates(
+            block_n,
+            Arc::clone(&provider),
+            Arc::clone(&overrides),
+            Arc::clone(&client),
+            p2p.clone(),
+        );
+        let fetched =
+            tokio::spawn(mc_otel::in_span_async("gateway_fetch", attributes, fetch)).await.expect("tokio join error");
+        match fetched {
+            Err(e) if e.is_transient() => {
+                log::warn!("⚠️ Failed to fetch block {block_n}, fetching it again in {delay:?}: {e}");
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_FETCH_RETRY_DELAY);
             }
-        } => {},
+            fetched => return fetched,
+        }
+    }
+}
+
+/// Runs `func`, recording the time it took as `phase` of block `block_n`.
+fn timed<R>(phase: Phase, block_n: u64, func: impl FnOnce() -> R) -> R {
+    let start = std::time::Instant::now();
+    let result = func();
+    lifecycle::record(phase, block_n, start.elapsed());
+    result
+}
+
+/// Commits the state diffs of the converted blocks to the tries, in order. The resulting state
+/// roots are checked against the block headers once the blocks are imported, by the verification of
+/// the node, so that the import does not wait for it.
+///
+/// The state diffs of the blocks before the trusted checkpoint are merged and committed at once,
+/// [`TRUSTED_TRIE_BATCH`] blocks at a time: the tries then only hold the state of the last block of
+/// each batch. The blocks are only forwarded once their state diff is in the tries, so that a
+/// restarted sync never misses one.
+///
+/// The class of the contracts whose storage is updated is read from the state of the parent block,
+/// so the trie update of a block waits for its parent to be sealed, as reported by `next_to_seal`.
+async fn update_tries<C>(
+    mut converted: mpsc::Receiver<ConvertedBlock>,
+    verified: mpsc::Sender<ConvertedBlock>,
+    mut next_to_seal: watch::Receiver<u64>,
+    client: Arc<C>,
+    overrides: Arc<OverrideHandle<RuntimeBlock<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
+    hashers: CommitmentHashers,
+) where
+    C: HeaderBackend<DBlockT> + 'static,
+{
+    let mut batch = TrieBatch::default();
+    while let Some(mut block) = converted.recv().await {
+        let Some(state_diff) = block.state_diff.take() else {
+            if !batch.commit(&verified, &mut next_to_seal, client.as_ref(), &overrides, hashers).await
+                || verified.send(block).await.is_err()
+            {
+                break;
+            }
+            continue;
+        };
+
+        // the state roots of the blocks after the checkpoint are verified, one block at a time
+        let trusted = block.trusted;
+        if !trusted && !batch.commit(&verified, &mut next_to_seal, client.as_ref(), &overrides, hashers).await {
+            break;
+        }
+        batch.push(block, state_diff);
+        if (!trusted || batch.blocks.len() >= TRUSTED_TRIE_BATCH)
+            && !batch.commit(&verified, &mut next_to_seal, client.as_ref(), &overrides, hashers).await
+        {
+            break;
+        }
+    }
+    batch.commit(&verified, &mut next_to_seal, client.as_ref(), &overrides, hashers).await;
+}
+
+/// Blocks whose state diffs are committed to the tries together.
+#[derive(Default)]
+struct TrieBatch {
+    blocks: Vec<ConvertedBlock>,
+    state_diff: Option<CommitmentStateDiff>,
+}
+
+impl TrieBatch {
+    fn push(&mut self, block: ConvertedBlock, state_diff: CommitmentStateDiff) {
+        match self.state_diff.as_mut() {
+            Some(merged) => merge_commitment_state_diffs(merged, state_diff),
+            None => self.state_diff = Some(state_diff),
+        }
+        // the contracts updated later in the batch have the class they were replaced with
+        if let Some(merged) = self.state_diff.as_mut().filter(|_| block.trusted) {
+            for replaced in &block.state_update.state_diff.replaced_classes {
+                merged.address_to_class_hash.insert(replaced.address.into(), replaced.class_hash.into());
+            }
+        }
+        self.blocks.push(block);
+    }
+
+    /// Commits the state diffs of the batch as the state of its last block, then forwards its
+    /// blocks to `verified`. Returns `false` if the sync stopped.
+    async fn commit<C>(
+        &mut self,
+        verified: &mpsc::Sender<ConvertedBlock>,
+        next_to_seal: &mut watch::Receiver<u64>,
+        client: &C,
+        overrides: &Arc<OverrideHandle<RuntimeBlock<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
+        hashers: CommitmentHashers,
+    ) -> bool
+    where
+        C: HeaderBackend<DBlockT>,
+    {
+        let (Some(first), Some(last), Some(state_diff)) =
+            (self.blocks.first(), self.blocks.last(), self.state_diff.take())
+        else {
+            return true;
+        };
+        let (first_n, block_n, block_hash) = (first.block_n, last.block_n, last.starknet_block_hash);
+        if next_to_seal.wait_for(|next| *next >= first_n).await.is_err() {
+            // the database writer stopped
+            return false;
+        }
+
+        let substrate_block_hash = block_hash_substrate(client, first_n - 1);
+        let overrides = Arc::clone(overrides);
+        let start = std::time::Instant::now();
+        spawn_compute(move || {
+            commit_state_diff(block_n, block_hash, state_diff, &overrides, substrate_block_hash, hashers)
+        })
+        .await;
+        log::debug!("update_tries: {:?}", start.elapsed());
+        progress::publish(ProgressEvent::stage(Stage::Verify, block_n, start.elapsed()));
+        lifecycle::record(Phase::TrieUpdate, block_n, start.elapsed());
+
+        for block in self.blocks.drain(..) {
+            if verified.send(block).await.is_err() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Writes the verified blocks to the database and seals them, in order, reporting the next block
+/// to seal in `next_to_seal`.
+async fn commit_blocks(
+    mut verified: mpsc::Receiver<ConvertedBlock>,
+    next_to_seal: watch::Sender<u64>,
+    block_sender: &Sender<DeoxysBlock>,
+    state_update_sender: &Sender<StateUpdateWrapper>,
+    class_sender: &Sender<ClassUpdateWrapper>,
+    command_sink: &mut CommandSink,
+    last_block_hash: &mut Option<H256>,
+    mut timestamps: TimestampMonitor,
+) {
+    while let Some(block) = verified.recv().await {
+        let block_n = block.block_n;
+        timestamps.observe(block_n, block.block.header().block_timestamp, get_highest_block_hash_and_number().1);
+        let committed =
+            commit_block(block, block_sender, state_update_sender, class_sender, command_sink, last_block_hash).await;
+        if let Err(e) = committed {
+            report_sync_failure(block_n, e);
+            break;
+        }
+        next_to_seal.send_replace(block_n + 1);
+    }
+}
+
+async fn commit_block(
+    block: ConvertedBlock,
+    block_sender: &Sender<DeoxysBlock>,
+    state_update_sender: &Sender<StateUpdateWrapper>,
+    class_sender: &Sender<ClassUpdateWrapper>,
+    command_sink: &mut CommandSink,
+    last_block_hash: &mut Option<H256>,
+) -> Result<(), SyncError> {
+    let ConvertedBlock { block_n, block, starknet_block_hash, mut state_update, class_update, receipts, .. } = block;
+    // ends once the block has been created
+    let _import_span = mc_otel::start_span("block_import", vec![KeyValue::new("block_number", block_n as i64)]);
+
+    check_counts(block_n, &block, &receipts)?;
+    let start = std::time::Instant::now();
+    // the previous blocks are in the flat storage
+    p2p::split_replaced_classes(block_n, &mut state_update.state_diff)?;
+    store_class_declarations(block_n, &state_update.state_diff, Some(&class_update[..]))?;
+    flat_storage::store_state_diff(block_n, &state_update.state_diff)?;
+    flat_storage::store_modified_contracts(block_n, &state_update.state_diff)?;
+    DeoxysBackend::receipt().store_receipts(&receipts)?;
+    let transaction_hashes: Vec<TransactionHash> =
+        receipts.iter().map(|receipt| TransactionHash(receipt.transaction_hash.into())).collect();
+    DeoxysBackend::transaction().store_block(block_n, &transaction_hashes)?;
+    DeoxysBackend::transaction().store_events(block_n, &transaction_hashes, block.events())?;
+    progress::publish(ProgressEvent::stage(Stage::Store, block_n, start.elapsed()));
+    lifecycle::record(Phase::DbCommit, block_n, start.elapsed());
+
+    let header = block.header().clone();
+    let stopped = |worker| SyncError::WorkerStopped { block_number: block_n, worker };
+    let (block_sent, state_update_sent, class_update_sent) = tokio::join!(
+        block_sender.send(block),
+        // Now send state_update, which moves it. This will be received
+        // by QueryBlockConsensusDataProvider in deoxys/crates/node/src/service.rs
+        state_update_sender.send(state_update),
+        // do the same to class update
+        class_sender.send(ClassUpdateWrapper(class_update)),
     );
+    block_sent.map_err(|_| stopped("block receiver"))?;
+    state_update_sent.map_err(|_| stopped("state updater"))?;
+    class_update_sent.map_err(|_| stopped("class updater"))?;
 
-    log::debug!("L2 sync finished :)");
+    let start = std::time::Instant::now();
+    create_block(command_sink, last_block_hash)
+        .await
+        .map_err(|reason| SyncError::BlockCreation { block_number: block_n, reason })?;
+    log::debug!("end create_block: {:?}", std::time::Instant::now() - start);
+    progress::publish(ProgressEvent::stage(Stage::Seal, block_n, start.elapsed()));
+    lifecycle::record(Phase::Seal, block_n, start.elapsed());
+    update_sync_progress(starknet_block_hash, block_n);
+    head::publish(HeadEvent::NewHead(header));
+    check_synced_block(block_n, Felt252Wrapper::from(starknet_block_hash).into());
+    Ok(())
+}
+
+/// Checks that the Starknet version of block `block_n` is supported by this build.
+///
+/// Blocks of older versions may not report their version, they are always supported. Newer
+/// versions may change the rules used to compute hashes and commitments, so syncing them could
+/// corrupt the tries: they are only accepted when `force_unsupported` is set.
+fn check_starknet_version(block_n: u64, version: Option<&str>, force_unsupported: bool) -> Result<(), SyncError> {
+    static FORCED: Once = Once::new();
+
+    let Some(version) = version else {
+        return Ok(());
+    };
+    let supported = StarknetVersion::from_str(MAX_SUPPORTED_STARKNET_VERSION).expect("valid supported version");
+    // versions which can't be parsed are newer than the ones this build knows about
+    if StarknetVersion::from_str(version).is_ok_and(|version| version <= supported) {
+        return Ok(());
+    }
+
+    if force_unsupported {
+        FORCED.call_once(|| {
+            log::warn!(
+                "⚠️ Block {block_n} uses Starknet version {version}, newer than {MAX_SUPPORTED_STARKNET_VERSION} \
+                 supported by this build, syncing it anyway as --force-unsupported is set"
+            )
+        });
+        return Ok(());
+    }
+    Err(SyncError::UnsupportedStarknetVersion { block_number: block_n, version: version.to_string() })
+}
+
+/// Checks that the transaction and event counts of the header of `block` match the receipts fetched
+/// with it, from which the transaction hashes are stored.
+///
+/// The transaction count of the header is the length of the transaction list of the gateway block
+/// and its event count that of the events converted into the block, while the receipts are
+/// converted on their own from the receipt list of the gateway block.
+fn check_counts(block_n: u64, block: &DeoxysBlock, receipts: &[TransactionReceiptWrapper]) -> Result<(), SyncError> {
+    let header = block.header();
+    let event_count: usize = receipts.iter().map(|receipt| receipt.events.len()).sum();
+    let counts = [
+        ("transaction count", header.transaction_count, "receipts", receipts.len()),
+        ("event count", header.event_count, "receipt events", event_count),
+    ];
+    for (field, in_header, body, in_body) in counts {
+        if in_header != in_body as u128 {
+            return Err(SyncError::CountMismatch {
+                block_number: block_n,
+                field,
+                in_header,
+                body,
+                in_body: in_body as u128,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Logs the error which stopped the sync at `block_n`.
+fn report_sync_failure(block_n: u64, e: SyncError) {
+    // the sync reached the tip of the chain
+    if e.is_block_not_found() {
+        return;
+    }
+    if let SyncError::UnsupportedStarknetVersion { block_number, version } = &e {
+        log::error!(
+            "🛑 Starknet was upgraded to version {version} at block {block_number}, this build only supports versions \
+             up to {MAX_SUPPORTED_STARKNET_VERSION}. The sync is stopped so that blocks are not committed with the \
+             wrong hash and commitment rules: upgrade the node to a release supporting Starknet {version}, or restart \
+             it with --disable-root --force-unsupported to keep syncing without verifying the state root."
+        );
+        alerts::raise(Alert::UnsupportedStarknetVersion { block_number: *block_number, version: version.clone() });
+        return;
+    }
+    if let SyncError::DiskLow { path, available_mib, min_free_mib, .. } = &e {
+        log::error!(
+            "🛑 Sync stopped at block {block_n}: only {available_mib} MiB are left on the disk holding {}, below the \
+             minimum of {min_free_mib} MiB set by --sync-min-free-disk. Free some space, or move the receipts and \
+             trie logs to another drive with --cold-storage-path, then restart the node.",
+            path.display()
+        );
+        alerts::raise(Alert::DiskLow { path: path.clone(), available_mib: *available_mib });
+        return;
+    }
+    // blocks are applied in order, the sync can't go past a block it failed to apply. Transient
+    // errors never get here, the blocks failing with them are fetched again
+    log::error!("❗ Failed to sync block {block_n}, stopping the sync: {e}");
+}
+
+/// Registers the classes declared in the given state update, so that they are not served for
+/// blocks prior to their declaration.
+///
+/// The size of the classes is recorded when their definition is provided in `classes`.
+pub(crate) fn store_class_declarations(
+    block_number: u64,
+    state_diff: &StateDiffWrapper,
+    classes: Option<&[ContractClassData]>,
+) -> Result<(), DbError> {
+    let size = |class_hash: &ClassHash| {
+        classes?.iter().find(|class| class.hash == *class_hash).map(|class| class.contract_class.encoded_size() as u64)
+    };
+
+    let sierra = state_diff.declared_classes.iter().map(|declared_class| {
+        let class_hash = ClassHash(declared_class.class_hash.into());
+        let compiled_class_hash = CompiledClassHash(declared_class.compiled_class_hash.into());
+        (class_hash, Some(compiled_class_hash))
+    });
+    let legacy = state_diff.old_declared_contracts.iter().map(|class_hash| (ClassHash((*class_hash).into()), None));
+
+    let declarations: Vec<ClassDeclaration> = sierra
+        .chain(legacy)
+        .map(|(class_hash, compiled_class_hash)| ClassDeclaration {
+            class_hash,
+            compiled_class_hash,
+            size: size(&class_hash),
+        })
+        .collect();
+
+    DeoxysBackend::class().store_declarations(block_number, &declarations)
+}
+
+/// Prepares the state for the blocks applied from `first_block`: the state of the genesis block,
+/// which is part of the chain spec, is stored when starting from scratch, and the flat storage and
+/// transaction index are updated from there on.
+///
+/// The gateway is only queried when starting from scratch, for the state diff of the genesis block.
+pub async fn init_state(
+    provider: &GatewayProvider,
+    first_block: u64,
+    overrides: &Arc<OverrideHandle<RuntimeBlock<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
+    hashers: CommitmentHashers,
+) -> Result<(), SyncError> {
+    if first_block == 1 {
+        let state_update = StateUpdateWrapper::from(provider.get_state_update(BlockId::Number(0)).await?);
+        verify_l2(0, &state_update, overrides, None, hashers)?;
+        store_class_declarations(0, &state_update.state_diff, None)?;
+        flat_storage::store_state_diff(0, &state_update.state_diff)?;
+        flat_storage::store_modified_contracts(0, &state_update.state_diff)?;
+    }
+
+    // blocks synced before the flat storage existed are backfilled separately
+    let live_from = if first_block == 1 { 0 } else { first_block };
+    DeoxysBackend::contract_storage().start_live_updates(live_from)?;
+    // the genesis block is part of the local chain, its transactions are backfilled with the others
+    DeoxysBackend::transaction().start_live_indexing(first_block)?;
+    Ok(())
 }
 
 /// Notifies the consensus engine that a new block should be created.
-async fn create_block(cmds: &mut CommandSink, parent_hash: &mut Option<H256>) -> Result<(), String> {
+pub(crate) async fn create_block(cmds: &mut CommandSink, parent_hash: &mut Option<H256>) -> Result<(), String> {
     let (sender, receiver) = futures::channel::oneshot::channel();
 
     cmds.try_send(sc_consensus_manual_seal::rpc::EngineCommand::SealNewBlock {
@@ -293,7 +1057,7 @@ async fn create_block(cmds: &mut CommandSink, parent_hash: &mut Option<H256>) ->
         parent_hash: None,
         sender: Some(sender),
     })
-    .unwrap();
+    .map_err(|err| format!("failed to request a new block: {err}"))?;
 
     let create_block_info = receiver
         .await
@@ -321,26 +1085,37 @@ pub fn update_l2(state_update: L2StateUpdate) {
 /// Verify and update the L2 state according to the latest state update
 pub fn verify_l2(
     block_number: u64,
-    state_update: &StateUpdate,
+    state_update: &StateUpdateWrapper,
     overrides: &Arc<OverrideHandle<RuntimeBlock<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
     substrate_block_hash: Option<H256>,
-) -> Result<(), L2SyncError> {
-    let state_update_wrapper = StateUpdateWrapper::from(state_update);
+    hashers: CommitmentHashers,
+) -> Result<(), SyncError> {
+    let csd = build_commitment_state_diff(state_update.clone());
+    let block_hash = state_update.block_hash.ok_or(ConversionError::MissingField("block hash"))?;
+    commit_state_diff(block_number, block_hash.into(), csd, overrides, substrate_block_hash, hashers);
 
-    let csd = build_commitment_state_diff(state_update_wrapper.clone());
-    let state_root = update_state_root(csd, Arc::clone(overrides), block_number, substrate_block_hash);
-    let block_hash = state_update.block_hash.expect("Block hash not found in state update");
+    Ok(())
+}
 
-    update_l2(L2StateUpdate {
-        block_number,
-        global_root: state_root.into(),
-        block_hash: Felt252Wrapper::from(block_hash).into(),
-    });
+/// Commits the state diff of a block to the tries, and updates the L2 state with the resulting
+/// state root.
+fn commit_state_diff(
+    block_number: u64,
+    block_hash: FieldElement,
+    csd: CommitmentStateDiff,
+    overrides: &Arc<OverrideHandle<RuntimeBlock<Header<u32, BlakeTwo256>, OpaqueExtrinsic>>>,
+    substrate_block_hash: Option<H256>,
+    hashers: CommitmentHashers,
+) {
+    let start = std::time::Instant::now();
+    let state_root = update_state_root(csd, Arc::clone(overrides), block_number, substrate_block_hash, hashers);
+    trie_metrics::record(block_number, start.elapsed());
+    let global_root: StarkHash = state_root.into();
 
-    Ok(())
+    update_l2(L2StateUpdate { block_number, global_root, block_hash: Felt252Wrapper::from(block_hash).into() });
 }
 
-async fn update_starknet_data<C>(provider: &SequencerGatewayProvider, client: &C) -> Result<(), String>
+async fn update_starknet_data<C>(provider: &GatewayProvider, client: &C) -> Result<(), String>
 where
     C: HeaderBackend<DBlockT>,
 {
@@ -361,15 +1136,16 @@ where
             .map_err(|e| format!("Failed to get pending state update: {e}"))?;
 
         *STARKNET_PENDING_BLOCK.write().expect("Failed to acquire write lock on STARKNET_PENDING_BLOCK") =
-            Some(crate::convert::block(block).await);
+            Some(crate::convert::block(block).await.map_err(|e| format!("Failed to convert pending block: {e}"))?);
 
         *STARKNET_PENDING_STATE_UPDATE.write().expect("Failed to aquire write lock on STARKNET_PENDING_STATE_UPDATE") =
-            Some(crate::convert::state_update(state_update));
+            Some(mp_convert::gateway::state_update(state_update));
     }
 
     *STARKNET_HIGHEST_BLOCK_HASH_AND_NUMBER
         .write()
         .expect("Failed to acquire write lock on STARKNET_HIGHEST_BLOCK_HASH_AND_NUMBER") = (hash_current, number);
+    progress::publish(ProgressEvent::Target { block_number: number });
 
     log::debug!(
         "update_starknet_data: latest_block_number: {}, latest_block_hash: 0x{:x}, best_hash: {}",
@@ -379,3 +1155,39 @@ where
     );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use mp_block::receipt::{EventWrapper, ExecutionResourcesWrapper};
+    use mp_block::Header;
+
+    use super::*;
+
+    fn receipt(events: usize) -> TransactionReceiptWrapper {
+        let event = EventWrapper { from_address: Felt252Wrapper::ONE, keys: vec![], data: vec![] };
+        TransactionReceiptWrapper {
+            transaction_hash: Felt252Wrapper::ONE,
+            actual_fee: Felt252Wrapper::ZERO,
+            revert_error: None,
+            execution_resources: ExecutionResourcesWrapper::default(),
+            messages_sent: vec![],
+            events: vec![event; events],
+        }
+    }
+
+    #[test]
+    fn counts_are_checked_against_the_receipts() {
+        let header = Header { transaction_count: 2, event_count: 3, ..Default::default() };
+        let block = DeoxysBlock::new(header, vec![], vec![]);
+
+        assert!(check_counts(7, &block, &[receipt(1), receipt(2)]).is_ok());
+        assert!(matches!(
+            check_counts(7, &block, &[receipt(3)]),
+            Err(SyncError::CountMismatch { block_number: 7, body: "receipts", in_header: 2, in_body: 1, .. })
+        ));
+        assert!(matches!(
+            check_counts(7, &block, &[receipt(1), receipt(1)]),
+            Err(SyncError::CountMismatch { body: "receipt events", in_header: 3, in_body: 2, .. })
+        ));
+    }
+}
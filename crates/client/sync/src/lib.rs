@@ -5,23 +5,76 @@
 // use sp_runtime::traits::Block as BlockT;
 // use reqwest::Url;
 
+pub mod archive;
 pub mod commitments;
+pub mod errors;
+pub mod ordering;
+pub mod types;
+pub mod utils;
+
+// the sync worker and everything depending on it drive the Substrate client
+#[cfg(feature = "substrate")]
+pub mod admission;
+#[cfg(feature = "substrate")]
+pub mod alerts;
+#[cfg(feature = "substrate")]
+pub mod blob_da;
+#[cfg(feature = "substrate")]
+pub mod checkpoint;
+#[cfg(feature = "substrate")]
+pub mod disk_guard;
+#[cfg(feature = "substrate")]
+pub mod fanout;
+#[cfg(feature = "substrate")]
 pub mod fetch;
+#[cfg(feature = "substrate")]
+pub mod flat_storage;
+#[cfg(feature = "substrate")]
+pub mod gas_oracle;
+#[cfg(feature = "substrate")]
+pub mod head;
+#[cfg(feature = "substrate")]
+pub mod import;
+#[cfg(feature = "substrate")]
 pub mod l1;
+#[cfg(feature = "substrate")]
 pub mod l2;
+#[cfg(feature = "substrate")]
+pub mod lifecycle;
+#[cfg(feature = "substrate")]
+pub mod progress;
+#[cfg(feature = "substrate")]
 pub mod reorgs;
-pub mod types;
-pub mod utils;
+#[cfg(feature = "substrate")]
+pub mod replication;
+#[cfg(feature = "substrate")]
+pub mod timestamps;
+#[cfg(feature = "substrate")]
+pub mod transaction_index;
+#[cfg(feature = "substrate")]
+pub mod trie_metrics;
 
+#[cfg(feature = "substrate")]
 pub use l2::SenderConfig;
 pub use mp_types::block::{DBlockT, DHashT};
-pub use utils::{convert, m, utility};
+#[cfg(feature = "m")]
+pub use utils::m;
+#[cfg(feature = "substrate")]
+pub use utils::{convert, utility};
 
+#[cfg(feature = "substrate")]
 type CommandSink = futures::channel::mpsc::Sender<sc_consensus_manual_seal::rpc::EngineCommand<sp_core::H256>>;
 
+/// Reverts the given number of blocks at the top of the local chain, returning the number of blocks
+/// actually reverted.
+#[cfg(feature = "substrate")]
+type ChainReverter = std::sync::Arc<dyn Fn(u64) -> Result<u64, String> + Send + Sync>;
+
+#[cfg(feature = "substrate")]
 pub mod starknet_sync_worker {
     use std::sync::Arc;
 
+    use prometheus_endpoint::Registry;
     use reqwest::Url;
     use sp_blockchain::HeaderBackend;
 
@@ -34,6 +87,7 @@ pub mod starknet_sync_worker {
         l1_url: Url,
         client: Arc<C>,
         starting_block: u32,
+        prometheus_registry: Option<Registry>,
     ) where
         C: HeaderBackend<DBlockT> + 'static,
     {
@@ -41,7 +95,7 @@ pub mod starknet_sync_worker {
 
         let _ = tokio::join!(
             l1::sync(l1_url.clone()),
-            l2::sync(sender_config, fetch_config.clone(), starting_block.into(), None, client)
+            l2::sync(sender_config, fetch_config.clone(), starting_block.into(), None, client, prometheus_registry)
         );
     }
 }
@@ -0,0 +1,135 @@
+//! Time spent by the recent blocks in each step of the sync, served by
+//! `deoxys_getBlockLifecycleReport`.
+//!
+//! "The sync is slow" is hard to act on without knowing which step is slow. The sync records the
+//! time each block spends in each [`Phase`], from its download to the mapping of its hashes, and
+//! the lifecycles of the last [`LIFECYCLES_CAPACITY`] blocks are kept in memory.
+//!
+//! Blocks go through the phases in a pipeline: the phases of consecutive blocks overlap, so the
+//! total of a block is the work done for it, not the time between its download and its mapping.
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Number of blocks whose lifecycle is kept.
+pub const LIFECYCLES_CAPACITY: usize = 1000;
+
+/// A step of the sync of a block, as reported in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Downloading the block, state update and classes from the feeder gateway.
+    Fetch,
+    /// Reading the fetched state update and receipts into the node types.
+    Decode,
+    /// Converting the block to the node types, computing its transaction and event commitments.
+    Convert,
+    /// Building the state diff whose commitment is checked against the fetched state root.
+    Verify,
+    /// Committing the state diff to the tries and computing the state root.
+    TrieUpdate,
+    /// Storing the class declarations, storage diffs, receipts and transactions in the database.
+    DbCommit,
+    /// Sealing the Substrate block.
+    Seal,
+    /// Writing the mapping between the Starknet and Substrate hashes of the block.
+    Mapping,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 8] = [
+        Phase::Fetch,
+        Phase::Decode,
+        Phase::Convert,
+        Phase::Verify,
+        Phase::TrieUpdate,
+        Phase::DbCommit,
+        Phase::Seal,
+        Phase::Mapping,
+    ];
+}
+
+/// The time spent by a block in each phase of the sync.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockLifecycle {
+    pub block_number: u64,
+    phases: [Option<Duration>; Phase::ALL.len()],
+}
+
+impl BlockLifecycle {
+    fn new(block_number: u64) -> Self {
+        Self { block_number, phases: [None; Phase::ALL.len()] }
+    }
+
+    /// The time spent in `phase`, if the block went through it.
+    pub fn phase(&self, phase: Phase) -> Option<Duration> {
+        self.phases[phase as usize]
+    }
+
+    /// The time spent in all the phases.
+    pub fn total(&self) -> Duration {
+        self.phases.iter().flatten().sum()
+    }
+}
+
+/// Lifecycles of the last blocks, in ascending block number order.
+static LIFECYCLES: Mutex<VecDeque<BlockLifecycle>> = Mutex::new(VecDeque::new());
+
+/// Records that block `block_number` spent `elapsed` in `phase`.
+///
+/// Phases run again for a block, e.g. after a reorg, replace the previous time.
+pub fn record(phase: Phase, block_number: u64, elapsed: Duration) {
+    let mut lifecycles = LIFECYCLES.lock().expect("Failed to acquire lock on block lifecycles");
+
+    // blocks are mostly recorded in order, the search starts from the latest ones
+    let position = lifecycles.iter().rposition(|lifecycle| lifecycle.block_number <= block_number);
+    let index = match position {
+        Some(index) if lifecycles[index].block_number == block_number => index,
+        Some(index) => {
+            lifecycles.insert(index + 1, BlockLifecycle::new(block_number));
+            index + 1
+        }
+        // older than the oldest block kept
+        None if lifecycles.len() >= LIFECYCLES_CAPACITY => return,
+        None => {
+            lifecycles.push_front(BlockLifecycle::new(block_number));
+            0
+        }
+    };
+    lifecycles[index].phases[phase as usize] = Some(elapsed);
+
+    if lifecycles.len() > LIFECYCLES_CAPACITY {
+        lifecycles.pop_front();
+    }
+}
+
+/// The lifecycles of the last `count` blocks, in ascending block number order.
+pub fn last(count: usize) -> Vec<BlockLifecycle> {
+    let lifecycles = LIFECYCLES.lock().expect("Failed to acquire lock on block lifecycles");
+    lifecycles.iter().skip(lifecycles.len().saturating_sub(count)).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phases_are_recorded_by_block() {
+        let millis = Duration::from_millis;
+        // the lifecycles are global, the blocks used here are far from any other test
+        let base = u64::MAX - 10;
+
+        record(Phase::Fetch, base + 2, millis(30));
+        record(Phase::Fetch, base + 1, millis(20));
+        record(Phase::Convert, base + 1, millis(5));
+        record(Phase::Mapping, base + 2, millis(1));
+        record(Phase::Fetch, base + 2, millis(40));
+
+        let lifecycles = last(2);
+        assert_eq!(lifecycles.iter().map(|lifecycle| lifecycle.block_number).collect::<Vec<_>>(), [base + 1, base + 2]);
+        assert_eq!(lifecycles[0].phase(Phase::Convert), Some(millis(5)));
+        assert_eq!(lifecycles[0].phase(Phase::Seal), None);
+        assert_eq!(lifecycles[0].total(), millis(25));
+        assert_eq!(lifecycles[1].phase(Phase::Fetch), Some(millis(40)));
+        assert_eq!(lifecycles[1].total(), millis(41));
+    }
+}
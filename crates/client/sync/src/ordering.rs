@@ -0,0 +1,107 @@
+//! Ordering of the blocks handed from the fetch stage to the import stage.
+//!
+//! Blocks are fetched by several workers at once and may complete in any order, but they must be
+//! applied to the tries and imported strictly one after the other. The [`BlockSequencer`] sits
+//! between the two stages: fetched blocks are pushed to it as they arrive, and it only releases
+//! them once all the blocks before them were released.
+use std::collections::BTreeMap;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum SequencingError {
+    #[error("block {0} was received after it was released")]
+    AlreadyReleased(u64),
+    #[error("block {0} was received twice")]
+    Duplicate(u64),
+}
+
+/// Reorders blocks received out of order, releasing them by strictly increasing block number with
+/// no gaps.
+#[derive(Debug)]
+pub struct BlockSequencer<T> {
+    /// Number of the next block to release.
+    next: u64,
+    /// Blocks received ahead of their turn.
+    pending: BTreeMap<u64, T>,
+}
+
+impl<T> BlockSequencer<T> {
+    /// Creates a sequencer releasing blocks starting from `first_block`.
+    pub fn new(first_block: u64) -> Self {
+        Self { next: first_block, pending: BTreeMap::new() }
+    }
+
+    /// Number of the next block to be released.
+    pub fn next_block(&self) -> u64 {
+        self.next
+    }
+
+    /// Number of blocks received which are waiting for a previous block.
+    pub fn buffered(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Adds a fetched block. Receiving a block twice, or after it was released, is an error as it
+    /// means two workers fetched the same block.
+    pub fn push(&mut self, block_n: u64, block: T) -> Result<(), SequencingError> {
+        if block_n < self.next {
+            return Err(SequencingError::AlreadyReleased(block_n));
+        }
+        if self.pending.contains_key(&block_n) {
+            return Err(SequencingError::Duplicate(block_n));
+        }
+
+        self.pending.insert(block_n, block);
+        Ok(())
+    }
+
+    /// Releases the next block, if it was received.
+    pub fn pop(&mut self) -> Option<(u64, T)> {
+        let block = self.pending.remove(&self.next)?;
+        let block_n = self.next;
+        self.next += 1;
+        Some((block_n, block))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_order_blocks_are_released_in_order() {
+        let mut sequencer = BlockSequencer::new(10);
+
+        sequencer.push(12, "c").unwrap();
+        sequencer.push(11, "b").unwrap();
+        // nothing can be released until block 10 arrives
+        assert_eq!(sequencer.pop(), None);
+        assert_eq!(sequencer.buffered(), 2);
+
+        sequencer.push(10, "a").unwrap();
+        sequencer.push(14, "e").unwrap();
+        let released: Vec<_> = std::iter::from_fn(|| sequencer.pop()).collect();
+        assert_eq!(released, vec![(10, "a"), (11, "b"), (12, "c")]);
+
+        // block 13 is missing, block 14 is held back
+        assert_eq!(sequencer.next_block(), 13);
+        assert_eq!(sequencer.buffered(), 1);
+        sequencer.push(13, "d").unwrap();
+        assert_eq!(sequencer.pop(), Some((13, "d")));
+        assert_eq!(sequencer.pop(), Some((14, "e")));
+        assert_eq!(sequencer.pop(), None);
+    }
+
+    #[test]
+    fn blocks_are_released_once() {
+        let mut sequencer = BlockSequencer::new(0);
+
+        sequencer.push(1, ()).unwrap();
+        assert_eq!(sequencer.push(1, ()), Err(SequencingError::Duplicate(1)));
+
+        sequencer.push(0, ()).unwrap();
+        assert_eq!(sequencer.pop(), Some((0, ())));
+        assert_eq!(sequencer.push(0, ()), Err(SequencingError::AlreadyReleased(0)));
+        assert_eq!(sequencer.pop(), Some((1, ())));
+        assert_eq!(sequencer.push(1, ()), Err(SequencingError::AlreadyReleased(1)));
+    }
+}
@@ -0,0 +1,121 @@
+//! Machine-readable progress of the sync, for dashboards such as `deoxys top`.
+//!
+//! The sync worker publishes a [`ProgressEvent`] each time a stage is done with a block, along
+//! with the depth of its queues and the latest block of the chain. Events serialize to JSON, e.g.
+//!
+//! ```json
+//! {"event":"stage","stage":"fetch","block_number":612,"elapsed_ms":431}
+//! {"event":"queue","queue":"fetched","depth":8,"capacity":10}
+//! {"event":"target","block_number":650000}
+//! ```
+//!
+//! Unlike the [head events](crate::head), progress events are a best-effort feed: subscribers
+//! falling behind miss the oldest ones, or are disconnected, depending on the
+//! [slow subscriber policy](crate::fanout::SlowSubscriberPolicy).
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::fanout::{Client, FanOut, SubscribeError, Subscription};
+
+/// Number of events queued for each subscriber, unless configured otherwise.
+const PROGRESS_EVENTS_CAPACITY: usize = 1024;
+
+/// A step of the sync of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Downloading the block, state update and classes from the feeder gateway.
+    Fetch,
+    /// Storing the class declarations, storage diffs, receipts and transactions in the database.
+    Store,
+    /// Converting the block to the node types.
+    Convert,
+    /// Computing the state root.
+    Verify,
+    /// Sealing the Substrate block.
+    Seal,
+}
+
+/// A queue of blocks between two stages of the sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Queue {
+    /// Fetched blocks waiting to be applied.
+    Fetched,
+    /// Blocks fetched ahead of the next block to apply, waiting for it.
+    Reordering,
+}
+
+/// A step forward of the sync.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    /// A stage is done with a block.
+    Stage { stage: Stage, block_number: u64, elapsed_ms: u64 },
+    /// Number of blocks in a queue, and the maximum it can hold if it is bounded.
+    Queue {
+        queue: Queue,
+        depth: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        capacity: Option<usize>,
+    },
+    /// Latest block of the chain, according to the feeder gateway.
+    Target { block_number: u64 },
+}
+
+impl ProgressEvent {
+    pub(crate) fn stage(stage: Stage, block_number: u64, elapsed: Duration) -> Self {
+        ProgressEvent::Stage { stage, block_number, elapsed_ms: elapsed.as_millis() as u64 }
+    }
+}
+
+static PROGRESS_EVENTS: FanOut<ProgressEvent> = FanOut::new("progress", PROGRESS_EVENTS_CAPACITY);
+
+/// Stream of the [`ProgressEvent`]s published after it was created.
+pub struct ProgressStream {
+    subscription: Subscription<ProgressEvent>,
+}
+
+impl ProgressStream {
+    /// Waits for the next event, `None` once the subscriber was disconnected for falling behind.
+    pub async fn next(&mut self) -> Option<ProgressEvent> {
+        self.subscription.next().await
+    }
+}
+
+/// Subscribes to the progress of the sync.
+pub fn subscribe() -> ProgressStream {
+    ProgressStream { subscription: PROGRESS_EVENTS.subscribe() }
+}
+
+/// Subscribes to the progress of the sync on behalf of an external `client`, within its
+/// [subscriber limits](crate::fanout::SubscriberLimits).
+pub fn subscribe_as(client: Client) -> Result<ProgressStream, SubscribeError> {
+    Ok(ProgressStream { subscription: PROGRESS_EVENTS.subscribe_as(client)? })
+}
+
+/// Publishes a step forward of the sync.
+pub(crate) fn publish(event: ProgressEvent) {
+    PROGRESS_EVENTS.publish(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn events_serialize_to_tagged_json() {
+        let event = ProgressEvent::stage(Stage::Fetch, 612, Duration::from_micros(431_900));
+        assert_eq!(
+            serde_json::to_value(event).unwrap(),
+            json!({ "event": "stage", "stage": "fetch", "block_number": 612, "elapsed_ms": 431 })
+        );
+        assert_eq!(
+            serde_json::to_value(ProgressEvent::Queue { queue: Queue::Reordering, depth: 3, capacity: None }).unwrap(),
+            json!({ "event": "queue", "queue": "reordering", "depth": 3 })
+        );
+    }
+}
@@ -1,35 +1,170 @@
-use starknet_providers::sequencer::models::Block as StarknetBlock;
+use mc_db::storage::StorageHandler;
+use mc_db::DeoxysBackend;
+use mp_types::block::DBlockT;
+use sp_blockchain::HeaderBackend;
+use starknet_ff::FieldElement;
+use starknet_providers::sequencer::models::BlockId;
 
-use crate::utility::get_highest_block_hash_and_number;
+use crate::errors::SyncError;
+use crate::fetch::gateway::GatewayProvider;
+use crate::head::{self, HeadEvent};
+use crate::l2::{local_block_hash, update_sync_progress};
+use crate::ChainReverter;
 
-/// Check for a reorg on Starknet and fix the current state if detected.
+/// Number of blocks a reorg can replace at most, the sync stops on deeper ones.
+pub const MAX_REORG_DEPTH: u64 = 64;
+
+/// Handles a reorg on Starknet, detected by the sync worker when block `block_n` fetched from the
+/// gateway does not have the head of the local chain as parent.
 ///
 /// On Starknet with the current system relying on a single sequencer it's rare to detect a reorg,
 /// but if the L1 reorgs we must handle it the following way:
 ///
-/// 1. The last fetched block parent hash is not equal to the last synced block by Deoxys: a reorg
-///    is detected.
-/// 2. We remove the last synced substrate digest and the associated classes/state_update we stored
-///    until we reach the last common ancestor.
-///
-/// ### Arguments
+/// 1. The last block the local chain has in common with the chain of the gateway is found, looking
+///    at most [`MAX_REORG_DEPTH`] blocks back.
+/// 2. The local chain is reverted to that common ancestor with [`revert_to`].
 ///
-/// * `block` - The last fetched block from the sequencer (before beeing converted).
+/// Returns the common ancestor, from which the sync fetches the blocks of the new chain.
+pub(crate) async fn handle_reorg<C>(
+    provider: &GatewayProvider,
+    client: &C,
+    block_n: u64,
+    revert_chain: &ChainReverter,
+) -> Result<u64, SyncError>
+where
+    C: HeaderBackend<DBlockT>,
+{
+    let head = u64::from(client.info().best_number);
+    let common_ancestor = find_common_ancestor(provider, client, head.min(block_n.saturating_sub(1))).await?;
+    if common_ancestor < head {
+        log::warn!(
+            "↩️ Reorg detected at block {block_n}, reverting the local chain from block {head} to {common_ancestor}"
+        );
+    }
+
+    let previous_head = (local_block_hash(client, head).unwrap_or_default(), head);
+    revert_to(common_ancestor, previous_head, revert_chain)?;
+    if let Some(ancestor_hash) = local_block_hash(client, common_ancestor) {
+        update_sync_progress(ancestor_hash, common_ancestor);
+    }
+    Ok(common_ancestor)
+}
+
+/// Finds the last block up to `head` which the local chain has in common with the chain of the
+/// gateway.
+async fn find_common_ancestor<C>(provider: &GatewayProvider, client: &C, head: u64) -> Result<u64, SyncError>
+where
+    C: HeaderBackend<DBlockT>,
+{
+    for block_n in (head.saturating_sub(MAX_REORG_DEPTH)..=head).rev() {
+        let block_hash = provider.get_block(BlockId::Number(block_n)).await?.block_hash;
+        if block_hash.is_some() && block_hash == local_block_hash(client, block_n) {
+            return Ok(block_n);
+        }
+    }
+    Err(SyncError::ReorgTooDeep { head, max_depth: MAX_REORG_DEPTH })
+}
+
+/// Reverts the local chain from `previous_head` to `common_ancestor`: the blocks after it are
+/// reverted with `revert_chain`, the state tries are rewound to their state right after it and
+/// the transactions of the reverted blocks and their events are removed from the index, before the
+/// reorg is published to the [head subscribers](crate::head).
 ///
-/// ### Returns
-/// This function will return a `Bool` returning `true` if a reorg was detected and `false` if not.
-pub async fn reorg(block: StarknetBlock) -> bool {
-    let last_synced_block_hash = get_highest_block_hash_and_number().0;
-    if block.parent_block_hash != last_synced_block_hash {
-        let mut new_lsbh = last_synced_block_hash;
-        while block.parent_block_hash != new_lsbh {
-            // 1. Remove the last synced block in the digest
-            // 2. Remove all the downloaded stuff from the state updates
-            new_lsbh = get_highest_block_hash_and_number().0;
+/// Does nothing if the local chain is not past `common_ancestor`.
+pub(crate) fn revert_to(
+    common_ancestor: u64,
+    previous_head: (FieldElement, u64),
+    revert_chain: &ChainReverter,
+) -> Result<(), SyncError> {
+    let (_, head) = previous_head;
+    if head <= common_ancestor {
+        return Ok(());
+    }
+
+    let blocks = head - common_ancestor;
+    let reverted = revert_chain(blocks).map_err(|reason| SyncError::Revert { common_ancestor, reason })?;
+    if reverted < blocks {
+        let reason = format!("only {reverted} of the {blocks} blocks could be reverted");
+        return Err(SyncError::Revert { common_ancestor, reason });
+    }
+    StorageHandler::rewind_to(common_ancestor)
+        .map_err(|e| SyncError::Revert { common_ancestor, reason: e.to_string() })?;
+    DeoxysBackend::transaction()
+        .remove_blocks_after(common_ancestor)
+        .map_err(|e| SyncError::Revert { common_ancestor, reason: e.to_string() })?;
+
+    head::publish(HeadEvent::Reorg { common_ancestor, previous_head });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use mp_block::OrderedEvents;
+    use starknet_api::core::{ContractAddress, PatriciaKey};
+    use starknet_api::hash::StarkFelt;
+    use starknet_api::transaction::{Event, TransactionHash};
+    use starknet_core::types::{BlockId, BlockTag};
+    use starknet_types_core::felt::Felt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reverted_blocks_are_rewound_in_the_tries() {
+        let _lock = head::PUBLISH_TEST_LOCK.lock().await;
+        DeoxysBackend::open_for_testing();
+
+        // the state of blocks 0 to 3, committed as the sync does
+        let address = ContractAddress(PatriciaKey(StarkFelt::from(1u128)));
+        let mut roots = Vec::new();
+        for block_n in 0..4u64 {
+            let mut contracts = StorageHandler::contract_mut(BlockId::Tag(BlockTag::Latest)).unwrap();
+            contracts.update(vec![(&address, Felt::from(block_n + 10))]).unwrap();
+            contracts.commit(block_n + 1).unwrap();
+            roots.push(StorageHandler::contract().unwrap().root().unwrap());
+        }
+        let transaction_hash = |block_n: u64| TransactionHash(StarkFelt::from(block_n + 100));
+        for block_n in 0..4u64 {
+            DeoxysBackend::transaction().store_block(block_n, &[transaction_hash(block_n)]).unwrap();
+            let events = vec![OrderedEvents::new(0, vec![Event::default()])];
+            DeoxysBackend::transaction().store_events(block_n, &[transaction_hash(block_n)], &events).unwrap();
         }
-        // 3. Revert the state commitment tries to the correct block number
-        true
-    } else {
-        false
+
+        let reverted = Arc::new(Mutex::new(Vec::new()));
+        let revert_chain: ChainReverter = {
+            let reverted = Arc::clone(&reverted);
+            Arc::new(move |blocks| {
+                reverted.lock().unwrap().push(blocks);
+                Ok(blocks)
+            })
+        };
+        let mut events = head::subscribe();
+
+        // the local chain is not past the common ancestor
+        revert_to(3, (FieldElement::THREE, 3), &revert_chain).unwrap();
+        assert!(reverted.lock().unwrap().is_empty());
+
+        revert_to(1, (FieldElement::THREE, 3), &revert_chain).unwrap();
+        assert_eq!(*reverted.lock().unwrap(), vec![2]);
+        assert_eq!(StorageHandler::contract().unwrap().root().unwrap(), roots[1]);
+        assert_eq!(StorageHandler::contract().unwrap().get(&address).unwrap(), Some(Felt::from(11u64)));
+        assert!(DeoxysBackend::transaction().location(&transaction_hash(1)).unwrap().is_some());
+        assert_eq!(DeoxysBackend::transaction().location(&transaction_hash(2)).unwrap(), None);
+        assert!(DeoxysBackend::transaction().event_slice(&transaction_hash(1)).unwrap().is_some());
+        assert_eq!(DeoxysBackend::transaction().event_slice(&transaction_hash(2)).unwrap(), None);
+        assert_eq!(DeoxysBackend::transaction().hash_at(3, 0).unwrap(), None);
+        assert!(matches!(
+            events.next().await,
+            Some(HeadEvent::Reorg { common_ancestor: 1, previous_head }) if previous_head == (FieldElement::THREE, 3)
+        ));
+
+        // the tries are left as they are if the chain can't be reverted
+        let failing: ChainReverter = Arc::new(|_| Err("pruned".to_string()));
+        assert!(matches!(
+            revert_to(0, (FieldElement::TWO, 1), &failing),
+            Err(SyncError::Revert { common_ancestor: 0, .. })
+        ));
+        assert_eq!(StorageHandler::contract().unwrap().root().unwrap(), roots[1]);
     }
 }
@@ -0,0 +1,327 @@
+//! Streaming replication of the synced blocks, from a primary node to read replicas.
+//!
+//! Read replicas follow a primary node over TCP instead of each fetching the blocks from the
+//! gateway. The primary and its replicas share a 32 bytes secret. When a replica connects, the
+//! primary sends a header identifying the protocol and the version of the encoding of the blocks,
+//! along with a random challenge. The replica answers with the BLAKE2b-256 digest of the secret
+//! followed by the challenge, and the number of the next block it needs. Once the replica is
+//! authenticated, the primary streams its blocks from that one onwards, and keeps streaming the new
+//! blocks as it imports them:
+//!
+//! ```text
+//! primary: "DXREPLIC" | protocol: u32 | version: u32 | challenge: [u8; 32]
+//! replica: response: [u8; 32] | next_block: u64
+//! primary: (tag: u8 | message)*
+//! ```
+//!
+//! Integers are little endian. Blocks are sent as [`ArchivedBlock`]s, the same encoding as the
+//! block archives, so the version is the [archive format version](crate::archive::FORMAT_VERSION).
+//! Each message is either:
+//!
+//! * `0 | len: u32 | (block, tries)`: the next block and, if the primary still has the trie logs of
+//!   the block, the [`TrieDelta`] of its commit, SCALE encoded. Messages are at most
+//!   [`MAX_MESSAGE_LEN`] bytes long.
+//! * `1 | common_ancestor: u64`: the primary reorganized its chain, the blocks after
+//!   `common_ancestor` were replaced.
+//!
+//! Replicas store the blocks through the same steps as the blocks imported from an archive, and
+//! apply the trie deltas to their tries instead of recomputing them from the state diffs, checking
+//! the resulting state root against the header of each block. The tries of the blocks sent without
+//! a delta are recomputed, if the replica verifies the blocks. Replicas can't revert their blocks,
+//! so they stop following a primary which reorganized below their head, and must then be synced
+//! again.
+//!
+//! Only the replicas are authenticated, and the stream is not encrypted: the primary should only be
+//! reachable from a private network.
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use mc_db::TrieDelta;
+use mp_types::block::DBlockT;
+use parity_scale_codec::{Decode, Encode};
+use sp_blockchain::HeaderBackend;
+use sp_core::hashing::blake2_256;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::archive::FORMAT_VERSION;
+use crate::commitments::hashers::CommitmentHashers;
+use crate::import::{apply_trie_delta, import_block, ArchivedBlock, ImportError};
+use crate::l2::SenderConfig;
+
+const MAGIC: &[u8; 8] = b"DXREPLIC";
+const PROTOCOL_VERSION: u32 = 2;
+const BLOCK_TAG: u8 = 0;
+const REORG_TAG: u8 = 1;
+
+/// Length of the secret shared by the primary and its replicas, and of the challenges.
+pub const SECRET_LEN: usize = 32;
+
+/// Maximum length of a message, checked before the message is read.
+pub const MAX_MESSAGE_LEN: u32 = 256 << 20;
+
+/// Delay before reconnecting to the primary after the connection was lost, doubled after each
+/// failed attempt up to [`MAX_RECONNECT_DELAY`].
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// The secret shared by a primary node and its read replicas.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ReplicationSecret(pub [u8; SECRET_LEN]);
+
+impl ReplicationSecret {
+    /// The response of a replica to `challenge`.
+    fn respond(&self, challenge: &[u8; SECRET_LEN]) -> [u8; SECRET_LEN] {
+        blake2_256(&[&self.0[..], &challenge[..]].concat())
+    }
+
+    /// Whether `response` is the response to `challenge`, compared in constant time.
+    pub fn check(&self, challenge: &[u8; SECRET_LEN], response: &[u8; SECRET_LEN]) -> bool {
+        self.respond(challenge).iter().zip(response).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+}
+
+impl fmt::Debug for ReplicationSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ReplicationSecret(..)")
+    }
+}
+
+/// The primary node followed by a read replica.
+#[derive(Clone, Debug)]
+pub struct PrimaryNode {
+    pub address: SocketAddr,
+    pub secret: ReplicationSecret,
+}
+
+/// A message streamed by the primary.
+#[derive(Debug)]
+pub enum ReplicationMessage {
+    Block { block: Box<ArchivedBlock>, tries: Option<TrieDelta> },
+    Reorg { common_ancestor: u64 },
+}
+
+#[derive(Error, Debug)]
+pub enum ReplicationError {
+    #[error("the primary reorganized its chain after block {common_ancestor}, below block {head} of the replica")]
+    Diverged { common_ancestor: u64, head: u64 },
+    #[error("expected block {expected} from the primary, got block {found}")]
+    UnexpectedBlock { expected: u64, found: u64 },
+    #[error("failed to import the block: {0}")]
+    Import(#[from] ImportError),
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Reads the response of a replica to `challenge`, and the number of the next block it requests.
+///
+/// Fails if the replica does not know the secret.
+pub async fn read_request<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    secret: &ReplicationSecret,
+    challenge: &[u8; SECRET_LEN],
+) -> io::Result<u64> {
+    let mut response = [0u8; SECRET_LEN];
+    reader.read_exact(&mut response).await?;
+    if !secret.check(challenge, &response) {
+        return Err(io::Error::new(ErrorKind::PermissionDenied, "Invalid replication secret"));
+    }
+    reader.read_u64_le().await
+}
+
+/// Writes the header of the stream sent to a replica, with the challenge it must answer.
+pub async fn write_header<W: AsyncWrite + Unpin>(writer: &mut W, challenge: &[u8; SECRET_LEN]) -> io::Result<()> {
+    writer.write_all(MAGIC).await?;
+    writer.write_u32_le(PROTOCOL_VERSION).await?;
+    writer.write_u32_le(FORMAT_VERSION).await?;
+    writer.write_all(challenge).await?;
+    writer.flush().await
+}
+
+/// Writes a block to a replica, along with the delta of its commit to the tries.
+pub async fn write_block<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    block: &ArchivedBlock,
+    tries: Option<&TrieDelta>,
+) -> io::Result<()> {
+    let message = (block, tries).encode();
+    let len = u32::try_from(message.len())
+        .ok()
+        .filter(|&len| len <= MAX_MESSAGE_LEN)
+        .ok_or_else(|| invalid_data(format!("Replicated block too large: {} bytes", message.len())))?;
+    writer.write_u8(BLOCK_TAG).await?;
+    writer.write_u32_le(len).await?;
+    writer.write_all(&message).await?;
+    writer.flush().await
+}
+
+/// Notifies a replica that the blocks after `common_ancestor` were replaced.
+pub async fn write_reorg<W: AsyncWrite + Unpin>(writer: &mut W, common_ancestor: u64) -> io::Result<()> {
+    writer.write_u8(REORG_TAG).await?;
+    writer.write_u64_le(common_ancestor).await?;
+    writer.flush().await
+}
+
+/// Reads and checks the header of the stream sent by the primary, returning its challenge.
+async fn read_header<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<[u8; SECRET_LEN]> {
+    let mut magic = [0u8; MAGIC.len()];
+    reader.read_exact(&mut magic).await?;
+    if &magic != MAGIC {
+        return Err(invalid_data("Not a Deoxys replication stream".to_string()));
+    }
+    let protocol = reader.read_u32_le().await?;
+    if protocol != PROTOCOL_VERSION {
+        return Err(invalid_data(format!(
+            "The primary replicates with protocol {protocol}, this node with protocol {PROTOCOL_VERSION}"
+        )));
+    }
+    let version = reader.read_u32_le().await?;
+    if version != FORMAT_VERSION {
+        return Err(invalid_data(format!(
+            "The primary sends blocks in version {version}, this node reads version {FORMAT_VERSION}"
+        )));
+    }
+    let mut challenge = [0u8; SECRET_LEN];
+    reader.read_exact(&mut challenge).await?;
+    Ok(challenge)
+}
+
+/// Reads the next message sent by the primary.
+async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<ReplicationMessage> {
+    match reader.read_u8().await? {
+        BLOCK_TAG => {
+            let len = reader.read_u32_le().await?;
+            if len > MAX_MESSAGE_LEN {
+                return Err(invalid_data(format!("Replicated block too large: {len} bytes")));
+            }
+            let mut message = vec![0u8; len as usize];
+            reader.read_exact(&mut message).await?;
+            let (block, tries) = <(ArchivedBlock, Option<TrieDelta>)>::decode(&mut &message[..])
+                .map_err(|e| invalid_data(format!("Invalid replicated block: {e}")))?;
+            Ok(ReplicationMessage::Block { block: Box::new(block), tries })
+        }
+        REORG_TAG => Ok(ReplicationMessage::Reorg { common_ancestor: reader.read_u64_le().await? }),
+        tag => Err(invalid_data(format!("Unknown replication message {tag}"))),
+    }
+}
+
+/// Follows `primary`, importing its blocks from `first_block` onwards.
+///
+/// Reconnects when the connection is lost, backing off while the primary can't be reached, and
+/// only returns when the blocks of the primary can't be imported.
+pub async fn follow<C>(
+    primary: &PrimaryNode,
+    first_block: u64,
+    sender_config: &mut SenderConfig,
+    client: &C,
+    verify: bool,
+    hashers: CommitmentHashers,
+) -> ReplicationError
+where
+    C: HeaderBackend<DBlockT>,
+{
+    let mut last_block_hash = None;
+    let mut next_block = first_block;
+    let mut reconnect_delay = MIN_RECONNECT_DELAY;
+
+    log::info!("🔁 Following the primary node at {} from block {first_block}", primary.address);
+    loop {
+        let reason = match connect(primary, next_block).await {
+            Ok(mut stream) => loop {
+                let (block, tries) = match read_message(&mut stream).await {
+                    Ok(ReplicationMessage::Block { block, tries }) => (block, tries),
+                    Ok(ReplicationMessage::Reorg { common_ancestor }) if common_ancestor + 1 >= next_block => continue,
+                    Ok(ReplicationMessage::Reorg { common_ancestor }) => {
+                        return ReplicationError::Diverged { common_ancestor, head: next_block - 1 };
+                    }
+                    Err(e) => break e,
+                };
+                reconnect_delay = MIN_RECONNECT_DELAY;
+
+                let block_n = block.block.header().block_number;
+                if block_n != next_block {
+                    return ReplicationError::UnexpectedBlock { expected: next_block, found: block_n };
+                }
+                // the tries of the blocks without a delta are recomputed along with their import
+                let recompute_tries = match tries {
+                    Some(tries) => match apply_trie_delta(&block, &tries, hashers) {
+                        Ok(()) => false,
+                        Err(e) => return e.into(),
+                    },
+                    None => verify,
+                };
+                if let Err(e) =
+                    import_block(*block, sender_config, &mut last_block_hash, client, recompute_tries, hashers).await
+                {
+                    return e.into();
+                }
+                next_block = block_n + 1;
+            },
+            Err(e) => e,
+        };
+
+        log::warn!(
+            "⚠️ Lost the connection to the primary node at {}: {reason}, reconnecting in {}s",
+            primary.address,
+            reconnect_delay.as_secs()
+        );
+        tokio::time::sleep(reconnect_delay).await;
+        reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+async fn connect(primary: &PrimaryNode, next_block: u64) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(primary.address).await?;
+    let challenge = read_header(&mut stream).await?;
+    stream.write_all(&primary.secret.respond(&challenge)).await?;
+    stream.write_u64_le(next_block).await?;
+    stream.flush().await?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn header_and_reorgs_roundtrip() {
+        let challenge = [7u8; SECRET_LEN];
+        let mut stream = Vec::new();
+        write_header(&mut stream, &challenge).await.unwrap();
+        write_reorg(&mut stream, 42).await.unwrap();
+
+        let mut reader = &stream[..];
+        assert_eq!(read_header(&mut reader).await.unwrap(), challenge);
+        assert!(matches!(read_message(&mut reader).await.unwrap(), ReplicationMessage::Reorg { common_ancestor: 42 }));
+        assert!(read_message(&mut reader).await.is_err());
+
+        stream[0] = b'X';
+        assert!(read_header(&mut &stream[..]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn replicas_must_know_the_secret() {
+        let secret = ReplicationSecret([1; SECRET_LEN]);
+        let challenge = [7u8; SECRET_LEN];
+        let request = |secret: &ReplicationSecret| [&secret.respond(&challenge)[..], &42u64.to_le_bytes()].concat();
+
+        assert_eq!(read_request(&mut &request(&secret)[..], &secret, &challenge).await.unwrap(), 42);
+        let forged = request(&ReplicationSecret([2; SECRET_LEN]));
+        let error = read_request(&mut &forged[..], &secret, &challenge).await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+        assert!(read_request(&mut &request(&secret)[..], &secret, &[8; SECRET_LEN]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn oversized_messages_are_rejected_before_being_read() {
+        let mut stream = vec![BLOCK_TAG];
+        stream.extend((MAX_MESSAGE_LEN + 1).to_le_bytes());
+        let error = read_message(&mut &stream[..]).await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
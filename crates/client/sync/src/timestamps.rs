@@ -0,0 +1,180 @@
+//! Sanity checks of the timestamps of the synced blocks.
+//!
+//! Each block is expected to be at least as recent as its parent. Once the node follows the tip of
+//! the chain, the timestamp of the new blocks is also compared to the local clock: a block from the
+//! future, or one much older than expected, points either to an issue with the sequencer or to a
+//! misconfigured local clock, which skews the time based logic of the simulated transactions.
+//!
+//! Anomalies are logged, counted in the `deoxys_block_timestamp_anomalies_total` metric and raised
+//! as alerts, they never stop the sync. The drift between the chain and the local clock at the tip
+//! is exported as the `deoxys_block_timestamp_drift_seconds` metric.
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use prometheus_endpoint::{register, CounterVec, Gauge, Opts, PrometheusError, Registry, I64, U64};
+
+use crate::alerts::{self, Alert};
+
+/// Default maximum drift between the timestamp of a block at the tip and the local clock.
+pub const DEFAULT_DRIFT_TOLERANCE: Duration = Duration::from_secs(600);
+/// Blocks closer than this to the tip of the chain are compared to the local clock.
+const TIP_DISTANCE: u64 = 2;
+
+static METRICS: OnceLock<TimestampMetrics> = OnceLock::new();
+
+/// Prometheus metrics of the block timestamps.
+struct TimestampMetrics {
+    drift: Gauge<I64>,
+    anomalies: CounterVec<U64>,
+}
+
+/// Registers the block timestamp metrics in `registry`.
+pub fn register_metrics(registry: &Registry) -> Result<(), PrometheusError> {
+    let drift = register(
+        Gauge::new(
+            "deoxys_block_timestamp_drift_seconds",
+            "Timestamp of the latest block at the tip of the chain minus the local time",
+        )?,
+        registry,
+    )?;
+    let anomalies = register(
+        CounterVec::new(
+            Opts::new("deoxys_block_timestamp_anomalies_total", "Number of blocks with an unexpected timestamp"),
+            &["kind"],
+        )?,
+        registry,
+    )?;
+    let _ = METRICS.set(TimestampMetrics { drift, anomalies });
+    Ok(())
+}
+
+/// An unexpected block timestamp.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimestampAnomaly {
+    /// The block is older than its parent.
+    Decreasing { timestamp: u64, parent_timestamp: u64 },
+    /// The block at the tip of the chain is further from the local time than the tolerance,
+    /// `drift` being positive when the block is ahead of the local clock.
+    Drift { timestamp: u64, drift: i64 },
+}
+
+impl TimestampAnomaly {
+    /// Identifies the anomaly, independently of its details.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TimestampAnomaly::Decreasing { .. } => "decreasing",
+            TimestampAnomaly::Drift { drift, .. } if *drift > 0 => "ahead_of_clock",
+            TimestampAnomaly::Drift { .. } => "behind_clock",
+        }
+    }
+}
+
+impl fmt::Display for TimestampAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimestampAnomaly::Decreasing { timestamp, parent_timestamp } => {
+                write!(f, "timestamp {timestamp} is older than the timestamp {parent_timestamp} of its parent")
+            }
+            TimestampAnomaly::Drift { timestamp, drift } if *drift > 0 => {
+                write!(f, "timestamp {timestamp} is {drift} s ahead of the local clock, check the system time")
+            }
+            TimestampAnomaly::Drift { timestamp, drift } => {
+                write!(f, "timestamp {timestamp} is {} s behind the local clock at the tip of the chain", -drift)
+            }
+        }
+    }
+}
+
+/// Checks the timestamps of the blocks, in the order they are imported.
+pub struct TimestampMonitor {
+    tolerance: Duration,
+    parent_timestamp: Option<u64>,
+}
+
+impl TimestampMonitor {
+    pub fn new(tolerance: Duration) -> Self {
+        Self { tolerance, parent_timestamp: None }
+    }
+
+    /// Checks the timestamp of block `block_n`, `tip` being the latest block of the chain, and
+    /// reports its anomalies.
+    pub fn observe(&mut self, block_n: u64, timestamp: u64, tip: u64) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let at_tip = block_n.saturating_add(TIP_DISTANCE) >= tip;
+
+        if let Some(metrics) = METRICS.get().filter(|_| at_tip) {
+            metrics.drift.set(drift(timestamp, now));
+        }
+        for anomaly in self.check(timestamp, now, at_tip) {
+            log::warn!("⏰ Block {block_n} {anomaly}");
+            if let Some(metrics) = METRICS.get() {
+                metrics.anomalies.with_label_values(&[anomaly.kind()]).inc();
+            }
+            alerts::raise(Alert::TimestampAnomaly { block_number: block_n, anomaly });
+        }
+    }
+
+    /// The anomalies of the timestamp of the next block, at the local time `now`. The block is
+    /// only compared to the local clock when it is `at_tip` of the chain.
+    fn check(&mut self, timestamp: u64, now: u64, at_tip: bool) -> Vec<TimestampAnomaly> {
+        let mut anomalies = Vec::new();
+
+        if let Some(parent_timestamp) = self.parent_timestamp.replace(timestamp).filter(|parent| timestamp < *parent) {
+            anomalies.push(TimestampAnomaly::Decreasing { timestamp, parent_timestamp });
+        }
+        let drift = drift(timestamp, now);
+        if at_tip && drift.unsigned_abs() > self.tolerance.as_secs() {
+            anomalies.push(TimestampAnomaly::Drift { timestamp, drift });
+        }
+
+        anomalies
+    }
+}
+
+fn drift(timestamp: u64, now: u64) -> i64 {
+    (timestamp as i128 - now as i128).clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn decreasing_timestamps_are_flagged() {
+        let mut monitor = TimestampMonitor::new(DEFAULT_DRIFT_TOLERANCE);
+
+        assert!(monitor.check(100, NOW, false).is_empty());
+        assert!(monitor.check(100, NOW, false).is_empty());
+        assert_eq!(
+            monitor.check(99, NOW, false),
+            vec![TimestampAnomaly::Decreasing { timestamp: 99, parent_timestamp: 100 }]
+        );
+        assert!(monitor.check(101, NOW, false).is_empty());
+    }
+
+    #[test]
+    fn drift_is_only_checked_at_the_tip() {
+        let mut monitor = TimestampMonitor::new(Duration::from_secs(60));
+
+        // historical blocks are expected to be old
+        assert!(monitor.check(NOW - 3600, NOW, false).is_empty());
+        assert!(monitor.check(NOW - 30, NOW, true).is_empty());
+        assert_eq!(
+            monitor.check(NOW + 120, NOW, true),
+            vec![TimestampAnomaly::Drift { timestamp: NOW + 120, drift: 120 }]
+        );
+
+        let anomalies = monitor.check(NOW - 120, NOW, true);
+        assert_eq!(
+            anomalies,
+            vec![
+                TimestampAnomaly::Decreasing { timestamp: NOW - 120, parent_timestamp: NOW + 120 },
+                TimestampAnomaly::Drift { timestamp: NOW - 120, drift: -120 },
+            ]
+        );
+        assert_eq!(anomalies[1].kind(), "behind_clock");
+    }
+}
@@ -0,0 +1,71 @@
+//! Maintenance of the transaction index, which finds the transactions and their events from the
+//! transaction hashes without going through the block bodies.
+//!
+//! The transactions of each block and the position of their events are indexed as the block is
+//! imported. Databases created before the index existed, or before it recorded the events, are
+//! missing the blocks synced until then: these are indexed from the local chain by [`backfill`],
+//! which runs alongside the sync and resumes where it stopped after a restart.
+use std::sync::Arc;
+
+use mc_db::DeoxysBackend;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::pedersen::PedersenHasher;
+use mp_types::block::DBlockT;
+use sp_blockchain::HeaderBackend;
+use starknet_api::transaction::TransactionHash;
+
+use crate::l2::local_block;
+
+/// Indexes the transactions and events of the blocks synced before the transaction index existed.
+///
+/// Does nothing if there is nothing to backfill. Failures are logged and stop the backfill, which
+/// is resumed on the next startup: the transactions which are not indexed yet are still found by
+/// hashing the transactions of their block in the meantime.
+pub async fn backfill<C>(client: Arc<C>, chain_id: Felt252Wrapper)
+where
+    C: HeaderBackend<DBlockT> + 'static,
+{
+    let transactions = DeoxysBackend::transaction();
+    let missing = match transactions.backfill_range() {
+        Ok(missing) => missing,
+        Err(e) => {
+            log::error!("Failed to read the transaction index backfill progress: {e}");
+            return;
+        }
+    };
+    if missing.is_empty() {
+        return;
+    }
+
+    log::info!("🗂️ Backfilling the transaction index from block {} to {}", missing.start, missing.end - 1);
+
+    // the blocks are read from the database, away from the async runtime
+    let backfilled = tokio::task::spawn_blocking(move || {
+        let end = missing.end;
+        for block_n in missing {
+            let block =
+                local_block(client.as_ref(), block_n).ok_or_else(|| format!("block {block_n} is not stored"))?;
+            let transaction_hashes: Vec<TransactionHash> =
+                block.transactions_hashes::<PedersenHasher>(chain_id, Some(block_n)).collect();
+
+            // the blocks are indexed in order, so all the previous blocks are backfilled
+            transactions
+                .store_block(block_n, &transaction_hashes)
+                .and_then(|()| transactions.store_events(block_n, &transaction_hashes, block.events()))
+                .and_then(|()| transactions.set_backfilled(block_n + 1))
+                .map_err(|e| format!("block {block_n}: {e}"))?;
+
+            if block_n % 10_000 == 0 {
+                log::info!("🗂️ Transaction index backfilled up to block {block_n}/{}", end - 1);
+            }
+        }
+        Ok::<_, String>(())
+    })
+    .await;
+
+    match backfilled {
+        Ok(Ok(())) => log::info!("🗂️ Transaction index backfill complete"),
+        Ok(Err(e)) => log::error!("Failed to backfill the transaction index at {e}"),
+        Err(e) => log::error!("The transaction index backfill panicked: {e}"),
+    }
+}
@@ -0,0 +1,81 @@
+//! Metrics of the trie commits of the synced blocks.
+//!
+//! For each block, the time taken to commit its state diff to the tries is exported as
+//! `deoxys_trie_commit_duration_seconds`, the number of trie nodes created, modified and removed as
+//! `deoxys_trie_nodes_per_block`, and the bytes written to each column of the tries as
+//! `deoxys_trie_written_bytes_per_block`. They show how the tries grow and catch regressions in the
+//! trie encoding, the writes being taken from the database layer, see [`TrieWrites`].
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use mc_db::TrieWrites;
+use prometheus_endpoint::{
+    exponential_buckets, register, Histogram, HistogramOpts, HistogramVec, PrometheusError, Registry,
+};
+
+static METRICS: OnceLock<TrieMetrics> = OnceLock::new();
+
+/// Prometheus metrics of the trie commits.
+struct TrieMetrics {
+    commit_duration: Histogram,
+    nodes: HistogramVec,
+    written_bytes: HistogramVec,
+}
+
+/// Registers the trie commit metrics in `registry`.
+pub fn register_metrics(registry: &Registry) -> Result<(), PrometheusError> {
+    let commit_duration = register(
+        Histogram::with_opts(HistogramOpts::new(
+            "deoxys_trie_commit_duration_seconds",
+            "Time taken to commit the state diff of a block to the tries",
+        ))?,
+        registry,
+    )?;
+    let nodes = register(
+        HistogramVec::new(
+            HistogramOpts::new("deoxys_trie_nodes_per_block", "Number of trie nodes written by the commit of a block")
+                .buckets(exponential_buckets(1.0, 4.0, 10)?),
+            &["kind"],
+        )?,
+        registry,
+    )?;
+    let written_bytes = register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "deoxys_trie_written_bytes_per_block",
+                "Bytes written to each column of the tries by the commit of a block",
+            )
+            .buckets(exponential_buckets(1024.0, 4.0, 12)?),
+            &["column"],
+        )?,
+        registry,
+    )?;
+    let _ = METRICS.set(TrieMetrics { commit_duration, nodes, written_bytes });
+    Ok(())
+}
+
+/// Records the commit of block `block_n` to the tries, which took `duration`, along with the
+/// writes made by the tries since the previous commit.
+pub fn record(block_n: u64, duration: Duration) {
+    let writes = TrieWrites::take();
+    let nodes = writes.nodes();
+    log::debug!(
+        "🌳 Block {block_n} committed to the tries in {duration:?}: {} nodes created, {} modified, {} removed, {} \
+         bytes written",
+        nodes.created,
+        nodes.modified,
+        nodes.removed,
+        writes.0.values().map(|column| column.bytes).sum::<u64>()
+    );
+
+    let Some(metrics) = METRICS.get() else {
+        return;
+    };
+    metrics.commit_duration.observe(duration.as_secs_f64());
+    metrics.nodes.with_label_values(&["created"]).observe(nodes.created as f64);
+    metrics.nodes.with_label_values(&["modified"]).observe(nodes.modified as f64);
+    metrics.nodes.with_label_values(&["removed"]).observe(nodes.removed as f64);
+    for (column, column_writes) in &writes.0 {
+        metrics.written_bytes.with_label_values(&[column]).observe(column_writes.bytes as f64);
+    }
+}
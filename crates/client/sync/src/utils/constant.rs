@@ -8,4 +8,7 @@ pub mod starknet_core_address {
     pub const SEPOLIA_INTEGRATION: &str = "0x4737c0c1B4D5b1A687B42610DdabEE781152359c";
 }
 
+/// The latest Starknet version whose hash and commitment rules are implemented by this build.
+pub const MAX_SUPPORTED_STARKNET_VERSION: &str = "0.13.2.1";
+
 pub const LOG_STATE_UPDTATE_TOPIC: &str = "0xd342ddf7a308dec111745b00315c14b7efb2bdae570a6856e088ed0c65a3576c";
@@ -1,5 +1,7 @@
 pub mod constant;
+#[cfg(feature = "substrate")]
 pub mod convert;
 #[cfg(feature = "m")]
 pub mod m;
+#[cfg(feature = "substrate")]
 pub mod utility;
@@ -1,4 +1,6 @@
-use crate::commands::{ExtendedRunCmd, SetupCmd};
+#[cfg(feature = "tui")]
+use crate::commands::TopCmd;
+use crate::commands::{ExportBlocksCmd, ExportClassesCmd, ExtendedRunCmd, ImportBlocksCmd, ImportClassesCmd, SetupCmd};
 
 #[derive(Debug, clap::Parser)]
 pub struct Cli {
@@ -25,14 +27,20 @@ pub enum Subcommand {
     /// Validate blocks.
     CheckBlock(sc_cli::CheckBlockCmd),
 
-    /// Export blocks.
-    ExportBlocks(sc_cli::ExportBlocksCmd),
+    /// Export blocks, with their state diff, classes and receipts, to an archive.
+    ExportBlocks(ExportBlocksCmd),
+
+    /// Export the compiled classes declared in a range of blocks to an archive.
+    ExportClasses(ExportClassesCmd),
 
     /// Export the state of a given block into a chain spec.
     ExportState(sc_cli::ExportStateCmd),
 
-    /// Import blocks.
-    ImportBlocks(sc_cli::ImportBlocksCmd),
+    /// Import the blocks of an archive produced by `export-blocks`, then keep syncing.
+    ImportBlocks(ImportBlocksCmd),
+
+    /// Import the compiled classes of an archive produced by `export-classes`.
+    ImportClasses(ImportClassesCmd),
 
     /// Key management cli utilities
     #[command(subcommand)]
@@ -47,6 +55,10 @@ pub enum Subcommand {
     /// Setup madara node
     Setup(SetupCmd),
 
+    /// Display the sync progress of a running node.
+    #[cfg(feature = "tui")]
+    Top(TopCmd),
+
     /// Try some command against runtime state.
     #[cfg(feature = "try-runtime")]
     TryRuntime(try_runtime_cli::TryRuntimeCmd),
@@ -0,0 +1,48 @@
+//! Cold storage of the data of old blocks.
+//!
+//! When started with `--cold-storage-path`, the node moves the transaction receipts and trie logs
+//! of the blocks deeper than `--cold-storage-depth` out of its primary database as the chain grows,
+//! to a separate database in that directory, which is read through transparently. Archive nodes
+//! can then keep their primary database on fast drives while the history grows on a larger and
+//! slower one. See [`mc_db::DeoxysBackend::cold_storage`].
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use mc_db::DeoxysBackend;
+use sp_blockchain::HeaderBackend;
+use tokio::time::MissedTickBehavior;
+
+use crate::service::FullClient;
+
+/// Interval between two moves of the blocks which got deep enough.
+const MOVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Where and when the data of old blocks is moved.
+#[derive(Debug, Clone)]
+pub struct ColdStorageConfig {
+    /// The directory of the cold storage.
+    pub path: PathBuf,
+    /// Number of blocks under the tip after which the data of a block is moved, deep enough for
+    /// the block not to be reverted anymore.
+    pub depth: u64,
+}
+
+/// Moves the data of the blocks deeper than `depth` to the cold storage, as the chain grows.
+pub async fn move_old_blocks(client: Arc<FullClient>, depth: u64) {
+    let mut interval = tokio::time::interval(MOVE_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        let Some(target) = u64::from(client.info().best_number).checked_sub(depth) else {
+            continue;
+        };
+
+        let moved = tokio::task::spawn_blocking(move || DeoxysBackend::cold_storage().move_blocks(target));
+        match moved.await.expect("join error") {
+            Ok(0) => {}
+            Ok(count) => log::debug!("🧊 Moved {count} blocks to the cold storage, up to block {target}"),
+            Err(e) => log::error!("Failed to move the blocks up to {target} to the cold storage: {e}"),
+        }
+    }
+}
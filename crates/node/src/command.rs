@@ -4,7 +4,7 @@ use sc_cli::{ChainSpec, SubstrateCli};
 
 use crate::benchmarking::{inherent_benchmark_data, RemarkBuilder};
 use crate::cli::{Cli, Subcommand};
-use crate::commands::run_node;
+use crate::commands::{run_node, NetworkType};
 use crate::constants::DEV_CHAIN_ID;
 #[cfg(feature = "sharingan")]
 use crate::constants::SHARINGAN_CHAIN_ID;
@@ -41,7 +41,7 @@ impl SubstrateCli for Cli {
                 let sealing = self.run.sealing.map(Into::into).unwrap_or_default();
                 Box::new(chain_spec::development_config(sealing)?)
             }
-            "starknet" => {
+            id if NetworkType::from_chain_spec_id(id).is_some() => {
                 let sealing = self.run.sealing.map(Into::into).unwrap_or_default();
                 Box::new(chain_spec::deoxys_config(sealing, id)?)
             }
@@ -69,9 +69,9 @@ pub fn run() -> sc_cli::Result<()> {
         }
         Some(Subcommand::ExportBlocks(ref cmd)) => {
             let runner = cli.create_runner(cmd)?;
-            runner.async_run(|mut config| {
-                let (client, _, _, task_manager, _) = service::new_chain_ops(&mut config, cli.run.cache)?;
-                Ok((cmd.run(client, config.database), task_manager))
+            runner.sync_run(|mut config| {
+                let (client, _, _, _, _) = service::new_chain_ops(&mut config, cli.run.cache)?;
+                cmd.run(client)
             })
         }
         Some(Subcommand::ExportState(ref cmd)) => {
@@ -81,11 +81,21 @@ pub fn run() -> sc_cli::Result<()> {
                 Ok((cmd.run(client, config.chain_spec), task_manager))
             })
         }
-        Some(Subcommand::ImportBlocks(ref cmd)) => {
+        // blocks are imported by the sync of a running node
+        Some(Subcommand::ImportBlocks(_)) => run_node(cli),
+        Some(Subcommand::ExportClasses(ref cmd)) => {
             let runner = cli.create_runner(cmd)?;
-            runner.async_run(|mut config| {
-                let (client, _, import_queue, task_manager, _) = service::new_chain_ops(&mut config, cli.run.cache)?;
-                Ok((cmd.run(client, import_queue), task_manager))
+            runner.sync_run(|mut config| {
+                let (client, _, _, _, _) = service::new_chain_ops(&mut config, cli.run.cache)?;
+                cmd.run(client)
+            })
+        }
+        Some(Subcommand::ImportClasses(ref cmd)) => {
+            let runner = cli.create_runner(cmd)?;
+            runner.sync_run(|mut config| {
+                // opens the database
+                let _ = service::new_chain_ops(&mut config, cli.run.cache)?;
+                cmd.run()
             })
         }
         Some(Subcommand::PurgeChain(ref cmd)) => {
@@ -173,6 +183,8 @@ pub fn run() -> sc_cli::Result<()> {
             runner.sync_run(|config| cmd.run::<Block>(&config))
         }
         Some(Subcommand::Setup(ref cmd)) => cmd.run(),
+        #[cfg(feature = "tui")]
+        Some(Subcommand::Top(ref cmd)) => cmd.run(),
         None => run_node(cli),
     }
 }
@@ -0,0 +1,115 @@
+//! Export and import of blocks, so that new nodes can bootstrap from the dump of a trusted peer.
+//!
+//! Archived blocks hold the Starknet block, state diff and classes carried by the Substrate block
+//! headers, along with the transaction receipts. They are imported by the sync, see
+//! [`mc_sync::import`].
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use mc_db::DeoxysBackend;
+use mc_sync::archive::{ArchiveKind, ArchiveWriter};
+use mc_sync::import::ArchivedBlock;
+use mp_block::state_update::StateUpdateWrapper;
+use mp_contract::class::ClassUpdateWrapper;
+use mp_digest_log::{find_starknet_block, CLASS_ENGINE_ID, STATE_ENGINE_ID};
+use sc_cli::{CliConfiguration, Error, Result, SharedParams};
+use sp_blockchain::HeaderBackend;
+use sp_runtime::traits::Header as _;
+
+use crate::service::FullClient;
+
+/// Export the blocks of a range to an archive.
+#[derive(Debug, clap::Args)]
+pub struct ExportBlocksCmd {
+    /// First block of the range. The genesis block is part of the chain spec and is not exported.
+    #[arg(long, default_value_t = 1)]
+    pub from: u64,
+
+    /// Last block of the range, included. Defaults to the latest block.
+    #[arg(long)]
+    pub to: Option<u64>,
+
+    /// Path of the archive to write.
+    #[arg(long, value_name = "FILE")]
+    pub out: PathBuf,
+
+    #[allow(missing_docs)]
+    #[clap(flatten)]
+    pub shared_params: SharedParams,
+}
+
+impl ExportBlocksCmd {
+    pub fn run(&self, client: Arc<FullClient>) -> Result<()> {
+        let from = self.from.max(1);
+        let to = self.to.unwrap_or(u64::from(client.info().best_number));
+
+        let mut archive = ArchiveWriter::create(&self.out, ArchiveKind::Blocks)?;
+        for block_number in from..=to {
+            archive.write(&archived_block(&client, block_number)?)?;
+            if block_number % 10_000 == 0 {
+                log::info!("📦 Exported blocks up to {block_number}");
+            }
+        }
+        archive.finish()?;
+
+        log::info!("📦 Exported blocks {from}..={to} to {}", self.out.display());
+        Ok(())
+    }
+}
+
+/// Reads the block `block_number` from the database, as stored in archives.
+pub fn archived_block(client: &FullClient, block_number: u64) -> Result<ArchivedBlock> {
+    let db_error = |e| Error::Application(Box::new(e));
+    let missing = || Error::Input(format!("Block {block_number} is not in the database"));
+    let number = u32::try_from(block_number).map_err(|_| missing())?;
+    let hash = client.hash(number)?.ok_or_else(missing)?;
+    let header = client.header(hash)?.ok_or_else(missing)?;
+    let digest = header.digest();
+
+    let block = find_starknet_block(digest)
+        .map_err(|e| Error::Input(format!("Block {block_number} holds no Starknet block: {e}")))?;
+    let state_update = digest
+        .logs()
+        .iter()
+        .find_map(|item| item.pre_runtime_try_to::<StateUpdateWrapper>(&STATE_ENGINE_ID))
+        .ok_or_else(|| Error::Input(format!("Block {block_number} holds no state update")))?;
+    let class_update = digest
+        .logs()
+        .iter()
+        .find_map(|item| item.pre_runtime_try_to::<ClassUpdateWrapper>(&CLASS_ENGINE_ID))
+        .ok_or_else(|| Error::Input(format!("Block {block_number} holds no class update")))?;
+
+    let mut receipts = Vec::with_capacity(block.transactions().len());
+    for index in 0..block.transactions().len() as u64 {
+        let receipt = DeoxysBackend::transaction()
+            .hash_at(block_number, index)
+            .map_err(db_error)?
+            .map(|transaction_hash| DeoxysBackend::receipt().get(&transaction_hash))
+            .transpose()
+            .map_err(db_error)?
+            .flatten()
+            .ok_or_else(|| Error::Input(format!("Missing receipt of transaction {index} of block {block_number}")))?;
+        receipts.push(receipt);
+    }
+
+    Ok(ArchivedBlock { block, state_update, class_update, receipts })
+}
+
+impl CliConfiguration for ExportBlocksCmd {
+    fn shared_params(&self) -> &SharedParams {
+        &self.shared_params
+    }
+}
+
+/// Import the blocks of archives produced by `export-blocks`, or of the segments of a snapshot,
+/// then keep syncing from the gateway.
+///
+/// The node is started with the options given before the subcommand, e.g.
+/// `deoxys --deoxys --l1-endpoint <URL> import-blocks --input blocks.dxa`.
+#[derive(Debug, clap::Args)]
+pub struct ImportBlocksCmd {
+    /// Paths of the archives to read, in order.
+    #[arg(long, value_name = "FILE", num_args = 1.., required = true)]
+    pub input: Vec<PathBuf>,
+}
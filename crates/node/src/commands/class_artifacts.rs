@@ -0,0 +1,138 @@
+//! Export and import of compiled classes, so that a fleet of nodes compiles each class once.
+//!
+//! The node does not keep the Sierra program of the classes it compiled, the archives hold the
+//! compiled classes along with their ABI, as stored by the node.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use mc_db::DeoxysBackend;
+use mc_storage::overrides_handle;
+use mc_sync::archive::{ArchiveKind, ArchiveReader, ArchiveWriter};
+use mp_contract::class::{ContractClassData, ContractClassWrapper};
+use sc_cli::{CliConfiguration, Error, Result, SharedParams};
+use sp_blockchain::HeaderBackend;
+
+use crate::service::FullClient;
+
+/// Number of class declarations listed at once.
+const DECLARATIONS_PAGE_SIZE: usize = 1_000;
+/// Number of classes written to the database at once on import.
+const IMPORT_BATCH_SIZE: usize = 100;
+
+/// Export the classes declared in a range of blocks to an archive.
+#[derive(Debug, clap::Args)]
+pub struct ExportClassesCmd {
+    /// First block of the range.
+    #[arg(long, default_value_t = 0)]
+    pub from: u64,
+
+    /// Last block of the range, included. Defaults to the latest block.
+    #[arg(long)]
+    pub to: Option<u64>,
+
+    /// Path of the archive to write.
+    #[arg(long, value_name = "FILE")]
+    pub out: PathBuf,
+
+    #[allow(missing_docs)]
+    #[clap(flatten)]
+    pub shared_params: SharedParams,
+}
+
+impl ExportClassesCmd {
+    pub fn run(&self, client: Arc<FullClient>) -> Result<()> {
+        let best_hash = client.info().best_hash;
+        let to = self.to.unwrap_or(u64::from(client.info().best_number));
+        let overrides = overrides_handle(Arc::clone(&client));
+        let storage = overrides.for_block_hash(client.as_ref(), best_hash);
+
+        let mut archive = ArchiveWriter::create(&self.out, ArchiveKind::Classes)?;
+        let mut exported = 0;
+        let mut from = self.from;
+        loop {
+            let page = DeoxysBackend::class()
+                .declarations(from, to, DECLARATIONS_PAGE_SIZE)
+                .map_err(|e| Error::Application(Box::new(e)))?;
+
+            for (block_number, declaration) in page.declarations {
+                let class_hash = declaration.class_hash;
+                let (Some(contract), Some(abi)) = (
+                    storage.contract_class_by_class_hash(best_hash, class_hash),
+                    storage.contract_abi_by_class_hash(best_hash, class_hash),
+                ) else {
+                    return Err(Error::Input(format!(
+                        "Class {} declared in block {block_number} is missing from the state",
+                        class_hash.0
+                    )));
+                };
+
+                archive.write(&ContractClassData {
+                    hash: class_hash,
+                    contract_class: ContractClassWrapper { contract, abi },
+                })?;
+                exported += 1;
+            }
+
+            match page.continuation_block {
+                Some(block_number) => from = block_number,
+                None => break,
+            }
+        }
+        archive.finish()?;
+
+        log::info!("📦 Exported {exported} classes declared in blocks {}..={to} to {}", self.from, self.out.display());
+        Ok(())
+    }
+}
+
+impl CliConfiguration for ExportClassesCmd {
+    fn shared_params(&self) -> &SharedParams {
+        &self.shared_params
+    }
+}
+
+/// Import the classes of an archive produced by `export-classes`.
+///
+/// Imported classes are used by the sync instead of downloading and compiling them.
+#[derive(Debug, clap::Args)]
+pub struct ImportClassesCmd {
+    /// Path of the archive to read.
+    #[arg(long, value_name = "FILE")]
+    pub input: PathBuf,
+
+    #[allow(missing_docs)]
+    #[clap(flatten)]
+    pub shared_params: SharedParams,
+}
+
+impl ImportClassesCmd {
+    pub fn run(&self) -> Result<()> {
+        let mut archive = ArchiveReader::open(&self.input, ArchiveKind::Classes)?;
+        let store = |batch: &[ContractClassData]| {
+            DeoxysBackend::class_artifact().store(batch).map_err(|e| Error::Application(Box::new(e)))
+        };
+
+        let mut imported = 0;
+        let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+        while let Some(class) = archive.next::<ContractClassData>()? {
+            batch.push(class);
+            if batch.len() == IMPORT_BATCH_SIZE {
+                store(&batch)?;
+                imported += batch.len();
+                batch.clear();
+            }
+        }
+        store(&batch)?;
+        imported += batch.len();
+
+        log::info!("📦 Imported {imported} classes from {}", self.input.display());
+        Ok(())
+    }
+}
+
+impl CliConfiguration for ImportClassesCmd {
+    fn shared_params(&self) -> &SharedParams {
+        &self.shared_params
+    }
+}
@@ -1,5 +1,13 @@
+mod blocks_archive;
+mod class_artifacts;
 mod run;
 mod setup;
+#[cfg(feature = "tui")]
+mod top;
 
+pub use blocks_archive::*;
+pub use class_artifacts::*;
 pub use run::*;
 pub use setup::*;
+#[cfg(feature = "tui")]
+pub use top::*;
@@ -1,17 +1,41 @@
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
+use std::sync::Arc;
+use std::time::Duration;
 
 use deoxys_runtime::SealingMode;
-use mc_sync::fetch::fetchers::{fetch_apply_genesis_block, FetchConfig};
+use mc_db::{Column, DbTuning, DeoxysBackend, MAX_STORAGE_TRIE_SHARDS};
+use mc_otel::OtelConfig;
+use mc_p2p::{Multiaddr, P2pConfig};
+use mc_rpc::devnet::DevnetConfig;
+use mc_rpc::{AccountClassWhitelist, MethodLimits, RpcConfig};
+use mc_sync::admission::AdmissionConfig;
+use mc_sync::alerts::AlertConfig;
+use mc_sync::commitments::hashers::{CommitmentHashers, CHAIN_SPEC_PROPERTY};
+use mc_sync::disk_guard::DiskWatermark;
+use mc_sync::fanout::{FanOutConfig, SubscriberLimits};
+use mc_sync::fetch::fetchers::{fetch_apply_genesis_block, FetchConfig, GatewayUrls};
+use mc_sync::gas_oracle::{GasOracleConfig, StrkRateSource};
+use mc_sync::replication::{PrimaryNode, ReplicationSecret};
+use mc_sync::timestamps::DEFAULT_DRIFT_TOLERANCE;
 use mc_sync::utility::update_config;
 use mc_sync::utils::constant::starknet_core_address;
+use mp_block::versioned_constants::{self, VersionedConstantsMap};
 use reqwest::Url;
 use sc_cli::{Result, RpcMethods, RunCmd, SubstrateCli};
 use serde::{Deserialize, Serialize};
 use sp_core::H160;
+use starknet_core::types::{BlockTag, FieldElement};
 
-use crate::cli::Cli;
+use crate::cli::{Cli, Subcommand};
+use crate::cold_storage::ColdStorageConfig;
+use crate::rpc::auth::{load_or_create_secret, read_secret};
+use crate::rpc::method_filter::{MethodFilter, MethodSelector};
+use crate::rpc::replication::ReplicationConfig;
 use crate::service;
+use crate::snapshots::SnapshotConfig;
+use crate::starknet::db_config_dir;
 
 /// Available Sealing methods.
 #[derive(Debug, Copy, Clone, clap::ValueEnum, Default, Serialize, Deserialize)]
@@ -36,24 +60,112 @@ impl From<Sealing> for SealingMode {
     }
 }
 
-/// Starknet network types.
-#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+/// What to do with the subscribers of the chain head and progress events falling behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum SlowSubscriberPolicy {
+    /// Drop their oldest queued events.
+    #[default]
+    DropOldest,
+    /// Disconnect them.
+    Disconnect,
+}
+
+impl From<SlowSubscriberPolicy> for mc_sync::fanout::SlowSubscriberPolicy {
+    fn from(value: SlowSubscriberPolicy) -> Self {
+        match value {
+            SlowSubscriberPolicy::DropOldest => mc_sync::fanout::SlowSubscriberPolicy::DropOldest,
+            SlowSubscriberPolicy::Disconnect => mc_sync::fanout::SlowSubscriberPolicy::Disconnect,
+        }
+    }
+}
+
+/// Block read by the RPC methods whose block id is omitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum DefaultBlock {
+    /// The latest block accepted on L2.
+    #[default]
+    Latest,
+    /// The pending block, being built by the sequencer.
+    Pending,
+}
+
+impl From<DefaultBlock> for BlockTag {
+    fn from(value: DefaultBlock) -> Self {
+        match value {
+            DefaultBlock::Latest => BlockTag::Latest,
+            DefaultBlock::Pending => BlockTag::Pending,
+        }
+    }
+}
+
+/// Presets of the RocksDB tuning, whose settings can be overridden by the other `--db-*` options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum DbProfile {
+    /// Balanced settings for a database on an SSD.
+    #[default]
+    SsdDefault,
+    /// Large caches and write buffers, and as many background jobs as there are cores.
+    Throughput,
+    /// Small caches and write buffers, and few open files and background jobs.
+    LowMemory,
+}
+
+impl From<DbProfile> for mc_db::DbProfile {
+    fn from(value: DbProfile) -> Self {
+        match value {
+            DbProfile::SsdDefault => mc_db::DbProfile::SsdDefault,
+            DbProfile::Throughput => mc_db::DbProfile::Throughput,
+            DbProfile::LowMemory => mc_db::DbProfile::LowMemory,
+        }
+    }
+}
+
+/// Compression of the RocksDB data files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DbCompression {
+    None,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+impl From<DbCompression> for mc_db::DbCompression {
+    fn from(value: DbCompression) -> Self {
+        match value {
+            DbCompression::None => mc_db::DbCompression::None,
+            DbCompression::Snappy => mc_db::DbCompression::Snappy,
+            DbCompression::Lz4 => mc_db::DbCompression::Lz4,
+            DbCompression::Zstd => mc_db::DbCompression::Zstd,
+        }
+    }
+}
+
+/// Starknet network presets.
+///
+/// A preset sets the chain id, the gateway URLs and the address of the core contract on Ethereum.
+/// The genesis block is fetched from the gateway of the network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 pub enum NetworkType {
     /// The main network (mainnet).
+    #[value(name = "mainnet", alias = "main")]
     Main,
-    /// The test network (testnet).
+    /// The Sepolia test network (testnet).
+    #[value(name = "sepolia", alias = "test")]
     Test,
-    /// The integration network.
+    /// The Sepolia integration network.
+    #[value(name = "integration-sepolia", alias = "integration")]
     Integration,
 }
 
 /// Starknet network configuration.
 impl NetworkType {
+    const ALL: [NetworkType; 3] = [NetworkType::Main, NetworkType::Test, NetworkType::Integration];
+
     pub fn uri(&self) -> &'static str {
         match self {
             NetworkType::Main => "https://alpha-mainnet.starknet.io",
             NetworkType::Test => "https://alpha-sepolia.starknet.io",
-            NetworkType::Integration => "https://external.integration.starknet.io",
+            NetworkType::Integration => "https://integration-sepolia.starknet.io",
         }
     }
 
@@ -61,7 +173,9 @@ impl NetworkType {
         match self {
             NetworkType::Main => starknet_core::types::FieldElement::from_byte_slice_be(b"SN_MAIN").unwrap(),
             NetworkType::Test => starknet_core::types::FieldElement::from_byte_slice_be(b"SN_SEPOLIA").unwrap(),
-            NetworkType::Integration => starknet_core::types::FieldElement::from_byte_slice_be(b"SN_INTE").unwrap(),
+            NetworkType::Integration => {
+                starknet_core::types::FieldElement::from_byte_slice_be(b"SN_INTEGRATION_SEPOLIA").unwrap()
+            }
         }
     }
 
@@ -73,6 +187,21 @@ impl NetworkType {
         }
     }
 
+    /// The id of the chain spec of the network, which also names its directory under the base
+    /// path, so that the databases of different networks are kept apart.
+    pub fn chain_spec_id(&self) -> &'static str {
+        match self {
+            NetworkType::Main => "starknet",
+            NetworkType::Test => "starknet-sepolia",
+            NetworkType::Integration => "starknet-integration-sepolia",
+        }
+    }
+
+    /// The network whose chain spec has the given id.
+    pub fn from_chain_spec_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|network| network.chain_spec_id() == id)
+    }
+
     pub fn block_fetch_config(&self) -> FetchConfig {
         let uri = self.uri();
         let chain_id = self.chain_id();
@@ -89,7 +218,21 @@ impl NetworkType {
             sound: false,
             l1_core_address,
             verify: true,
+            verify_execution: false,
+            force_unsupported: false,
             api_key: None,
+            fallback_gateways: Vec::new(),
+            gateway_rate_limit: None,
+            block_queue_capacity: 10,
+            gateway_cache: None,
+            import_archives: Vec::new(),
+            replicate_from: None,
+            admission: AdmissionConfig::default(),
+            hashers: CommitmentHashers::default(),
+            timestamp_drift_tolerance: DEFAULT_DRIFT_TOLERANCE,
+            disk_watermark: None,
+            trusted_checkpoint: None,
+            p2p: None,
         }
     }
 }
@@ -98,6 +241,29 @@ fn parse_url(s: &str) -> StdResult<Url, url::ParseError> {
     s.parse()
 }
 
+fn parse_storage_trie_shards(s: &str) -> StdResult<usize, String> {
+    match s.parse() {
+        Ok(shards) if (1..=MAX_STORAGE_TRIE_SHARDS).contains(&shards) => Ok(shards),
+        _ => Err(format!("Invalid number of shards {s:?}, expected 1 to {MAX_STORAGE_TRIE_SHARDS}")),
+    }
+}
+
+fn parse_column_compression(s: &str) -> StdResult<(Column, DbCompression), String> {
+    let (column, compression) =
+        s.split_once('=').ok_or_else(|| format!("Invalid column compression {s:?}, expected COLUMN=COMPRESSION"))?;
+    let column = Column::from_name(column).ok_or_else(|| format!("Unknown database column {column:?}"))?;
+    let compression = <DbCompression as clap::ValueEnum>::from_str(compression, true)?;
+    Ok((column, compression))
+}
+
+fn parse_felt(s: &str) -> StdResult<FieldElement, String> {
+    FieldElement::from_hex_be(s).map_err(|e| format!("Invalid class hash {s:?}: {e}"))
+}
+
+fn parse_block_hash(s: &str) -> StdResult<FieldElement, String> {
+    FieldElement::from_hex_be(s).map_err(|e| format!("Invalid block hash {s:?}: {e}"))
+}
+
 #[derive(Clone, Debug, clap::Args)]
 pub struct ExtendedRunCmd {
     #[clap(flatten)]
@@ -112,7 +278,7 @@ pub struct ExtendedRunCmd {
     pub l1_endpoint: Option<Url>,
 
     /// The network type to connect to.
-    #[clap(long, short, default_value = "integration")]
+    #[clap(long, short, default_value = "integration-sepolia")]
     pub network: NetworkType,
 
     /// When enabled, more information about the blocks and their transaction is cached and stored
@@ -123,6 +289,81 @@ pub struct ExtendedRunCmd {
     #[clap(long)]
     pub cache: bool,
 
+    /// Number of RocksDB instances the contract storage tries are sharded across, by contract
+    /// address, so that large archive databases are compacted in parallel. Can only be set when
+    /// the database is created, defaults to the number it was created with.
+    #[clap(long, value_name = "COUNT", value_parser = parse_storage_trie_shards)]
+    pub storage_trie_shards: Option<usize>,
+
+    /// Directory, possibly on a larger and slower drive, the transaction receipts and trie logs
+    /// of the blocks deeper than `--cold-storage-depth` are moved to. Once blocks were moved, the
+    /// database can't be opened without it.
+    #[clap(long, value_name = "PATH")]
+    pub cold_storage_path: Option<PathBuf>,
+
+    /// Number of blocks under the tip after which the data of a block is moved to
+    /// `--cold-storage-path`.
+    #[clap(long, value_name = "BLOCKS", default_value_t = 10_000, requires = "cold_storage_path")]
+    pub cold_storage_depth: u64,
+
+    /// S3 compatible bucket, as `s3://<bucket>/<prefix>`, a snapshot of the verified blocks and
+    /// their trie deltas is published to every `--snapshot-interval` hours, along with its
+    /// manifest, for new nodes to bootstrap from with `import-blocks`. The credentials and
+    /// endpoint of the bucket are read from the `AWS_*` environment variables.
+    #[clap(long, value_name = "URL", value_parser = parse_url)]
+    pub snapshot_bucket: Option<Url>,
+
+    /// Number of hours between two snapshots published to `--snapshot-bucket`.
+    #[clap(long, value_name = "HOURS", default_value_t = 24, requires = "snapshot_bucket")]
+    pub snapshot_interval: u64,
+
+    /// Number of snapshots kept in `--snapshot-bucket`, the older ones being deleted.
+    #[clap(long, value_name = "COUNT", default_value_t = 2, requires = "snapshot_bucket")]
+    pub snapshot_keep: usize,
+
+    /// Stop the sync when the free space left on the disks holding the database drops below this
+    /// many MiB, before RocksDB runs out of space halfway through a block.
+    #[clap(long, value_name = "MiB")]
+    pub sync_min_free_disk: Option<u64>,
+
+    /// Hash of a trusted block up to which the transaction and event commitments of the synced
+    /// blocks are taken from the feeder gateway instead of being computed, and their state diffs
+    /// committed to the tries in batches. The sync stops if the blocks up to it do not chain up to
+    /// its hash, and fully verifies the blocks from it onwards.
+    #[clap(long, value_name = "BLOCK_HASH", value_parser = parse_block_hash)]
+    pub sync_trusted_checkpoint: Option<FieldElement>,
+
+    /// Preset of the RocksDB tuning of the database.
+    #[clap(long, value_enum, default_value = "ssd-default")]
+    pub db_profile: DbProfile,
+
+    /// Size of the block cache of each RocksDB instance of the database, overriding the profile.
+    #[clap(long, value_name = "MiB")]
+    pub db_block_cache_size: Option<usize>,
+
+    /// Size of the write buffer of each database column, overriding the profile.
+    #[clap(long, value_name = "MiB")]
+    pub db_write_buffer_size: Option<usize>,
+
+    /// Maximum number of files each RocksDB instance of the database keeps open, unlimited if -1,
+    /// overriding the profile.
+    #[clap(long, value_name = "COUNT", allow_negative_numbers = true)]
+    pub db_max_open_files: Option<i32>,
+
+    /// Maximum number of concurrent flushes and compactions of each RocksDB instance of the
+    /// database, overriding the profile.
+    #[clap(long, value_name = "COUNT")]
+    pub db_background_jobs: Option<i32>,
+
+    /// Compression of the database columns, overriding the profile.
+    #[clap(long, value_enum)]
+    pub db_compression: Option<DbCompression>,
+
+    /// Compression of a database column, e.g. `bonsai_classes_log=zstd`, overriding
+    /// `--db-compression`. Can be repeated.
+    #[clap(long, value_name = "COLUMN=COMPRESSION", value_parser = parse_column_compression)]
+    pub db_column_compression: Vec<(Column, DbCompression)>,
+
     /// This will invoke sound interpreted from the block hashes.
     #[clap(long)]
     pub sound: bool,
@@ -135,10 +376,317 @@ pub struct ExtendedRunCmd {
     #[clap(long)]
     pub disable_root: bool,
 
+    /// Also verify the imported blocks by executing their transactions again, in the background,
+    /// checking their revert status and fee against their receipts. Blocks are only counted as
+    /// verified once re-executed, which takes much longer than the sync itself.
+    #[clap(long)]
+    pub verify_execution: bool,
+
+    /// Keep syncing blocks of Starknet versions newer than the ones supported by this build,
+    /// instead of stopping the sync. Their hashes and commitments may be computed with the wrong
+    /// rules, so this requires root verification to be disabled.
+    #[clap(long, requires = "disable_root")]
+    pub force_unsupported: bool,
+
     /// Gateway api key to avoid rate limiting (optional)
     #[clap(long)]
     pub gateway_key: Option<String>,
 
+    /// Base URLs of secondary gateways, used when the primary one keeps being rate limited or
+    /// failing, in order of preference. The gateway and feeder gateway endpoints are derived from
+    /// each of them.
+    #[clap(long, value_parser = parse_url, value_name = "URL", value_delimiter = ',')]
+    pub gateway_fallback_url: Vec<Url>,
+
+    /// Maximum number of requests per second sent to the gateway.
+    #[clap(long, value_name = "REQUESTS")]
+    pub gateway_rate_limit: Option<u32>,
+
+    /// Directory where the blocks, state updates and classes fetched from the gateway are
+    /// cached, so that a resync replays them from disk instead of the gateway. Only blocks
+    /// accepted on L1 are cached.
+    #[clap(long, value_name = "DIR")]
+    pub gateway_cache: Option<PathBuf>,
+
+    /// Maximum number of fetched blocks waiting to be applied. Fetching pauses while the queue is
+    /// full, raising it trades memory for smoother throughput when applying blocks is bursty.
+    #[clap(long, value_name = "BLOCKS", default_value_t = 10)]
+    pub sync_queue_capacity: usize,
+
+    /// Slow down the sync of historical blocks while the 99th percentile of the RPC latency, over
+    /// the last 30 seconds, exceeds this many milliseconds. Blocks close to the tip of the chain
+    /// are never delayed.
+    #[clap(long, value_name = "MS")]
+    pub sync_throttle_rpc_p99: Option<u64>,
+
+    /// Slow down the sync of historical blocks while the 1 minute load average, divided by the
+    /// number of CPUs, exceeds this value, e.g. `0.8`.
+    #[clap(long, value_name = "LOAD")]
+    pub sync_throttle_load: Option<f64>,
+
+    /// Maximum difference, in seconds, between the timestamp of the blocks at the tip of the chain
+    /// and the local clock. Blocks further from it, or older than their parent, are reported in
+    /// the logs, metrics and alerts.
+    #[clap(long, value_name = "SECONDS", default_value_t = DEFAULT_DRIFT_TOLERANCE.as_secs())]
+    pub sync_timestamp_tolerance: u64,
+
+    /// Maximum amount of memory, in MiB, a single RPC execution (call, fee estimation, simulation
+    /// or trace) is allowed to allocate. Executions going over it are stopped at their next read of
+    /// the state, and the request is rejected.
+    #[clap(long, value_name = "MiB")]
+    pub rpc_execution_memory_limit: Option<usize>,
+
+    /// Only accept transactions, in the write and simulation RPC methods, from accounts of this
+    /// class. Can be repeated. Transactions from accounts of any class are accepted if not set.
+    #[clap(long, value_parser = parse_felt, value_name = "CLASS_HASH")]
+    pub rpc_allowed_account_class: Vec<FieldElement>,
+
+    /// Maximum number of events returned in a single `starknet_getEvents` page. Requests for
+    /// larger pages fail with `PAGE_SIZE_TOO_BIG`.
+    #[clap(long, value_name = "EVENTS", default_value_t = RpcConfig::default().max_events_chunk_size)]
+    pub rpc_max_events_chunk_size: usize,
+
+    /// Maximum number of `pathfinder_getProof` requests served at once. Other requests wait for
+    /// up to `--rpc-max-queue-time`.
+    #[clap(long, value_name = "REQUESTS", default_value_t = RpcConfig::default().proof_limits.max_concurrent)]
+    pub rpc_max_concurrent_proofs: usize,
+
+    /// Maximum number of `pathfinder_getProof` requests started per second, across all callers.
+    #[clap(long, value_name = "REQUESTS", default_value_t = RpcConfig::default().proof_limits.max_per_second)]
+    pub rpc_max_proofs_per_second: u64,
+
+    /// Maximum number of trace and simulation requests served at once. Other requests wait for up
+    /// to `--rpc-max-queue-time`.
+    #[clap(long, value_name = "REQUESTS", default_value_t = RpcConfig::default().trace_limits.max_concurrent)]
+    pub rpc_max_concurrent_traces: usize,
+
+    /// Maximum number of trace and simulation requests started per second, across all callers.
+    #[clap(long, value_name = "REQUESTS", default_value_t = RpcConfig::default().trace_limits.max_per_second)]
+    pub rpc_max_traces_per_second: u64,
+
+    /// Maximum time, in seconds, a proof, trace or simulation request waits to be served. Requests
+    /// over the limits fail with `TOO_MANY_REQUESTS`, their data advising when to retry.
+    #[clap(long, value_name = "SECONDS", default_value_t = RpcConfig::default().proof_limits.max_queue_time.as_secs())]
+    pub rpc_max_queue_time: u64,
+
+    /// Forward the transactions of each account to the gateway in nonce order: a transaction
+    /// waits for up to this time, in seconds, for those of its account with lower nonces.
+    #[clap(long, value_name = "SECONDS")]
+    pub rpc_nonce_queue_time: Option<u64>,
+
+    /// Keep the transactions which could not be forwarded because the gateway is unavailable in a
+    /// persistent pool, and retry forwarding them up to this number of times. Such transactions
+    /// fail right away if unset.
+    #[clap(long, value_name = "ATTEMPTS")]
+    pub rpc_forward_retries: Option<u32>,
+
+    /// Block read by `starknet_call`, `starknet_estimateFee`, `starknet_estimateMessageFee`,
+    /// `starknet_getNonce` and `starknet_getStorageAt` when their block id is omitted. Returned
+    /// by `deoxys_getChainInfo`.
+    #[clap(long, value_enum, value_name = "BLOCK", default_value = "latest")]
+    pub rpc_default_block: DefaultBlock,
+
+    /// Only serve the blocks whose content was verified against their header, which is done in
+    /// the background once they are imported: the `latest` and `pending` tags are the last
+    /// verified block, and the blocks after it are not found. The last imported and verified
+    /// blocks are returned by `deoxys_getVerificationStatus`.
+    #[clap(long)]
+    pub rpc_only_verified: bool,
+
+    /// Do not serve an RPC method, e.g. `starknet_traceTransaction`, the methods starting with a
+    /// prefix, e.g. `starknet_add*`, or a whole group of methods: `read`, `write`, `trace`,
+    /// `deoxys`, `pathfinder`, `admin`, `system` or `manual-seal`. Can be repeated. Requests to
+    /// disabled methods fail with `METHOD_NOT_FOUND`.
+    #[clap(long, value_name = "METHOD", value_delimiter = ',')]
+    pub rpc_disable: Vec<MethodSelector>,
+
+    /// Only serve these RPC methods, prefixes or groups, in the same format as `--rpc-disable`.
+    /// Can be repeated. All the methods are served if not set.
+    #[clap(long, value_name = "METHOD", value_delimiter = ',')]
+    pub rpc_allow: Vec<MethodSelector>,
+
+    /// Only serve the RPC methods reading the chain, so that the RPC can be exposed publicly: the
+    /// `write`, `trace`, `admin` and `manual-seal` groups are disabled.
+    #[clap(long)]
+    pub rpc_read_only: bool,
+
+    /// Address of a secondary RPC server serving each supported version of the Starknet RPC spec
+    /// under its own path (`/rpc/v0_6`, `/rpc/v0_7`). Disabled if not set. Browsers may query it
+    /// from the origins allowed by `--rpc-cors`, and its request, response and connection limits
+    /// are set by `--rpc-max-request-size`, `--rpc-max-response-size` and `--rpc-max-connections`.
+    #[clap(long, value_name = "ADDR")]
+    pub rpc_versioned_addr: Option<SocketAddr>,
+
+    /// Require a JWT, signed with the hex encoded 32 bytes secret in this file, to serve the
+    /// `write`, `admin` and `manual-seal` RPC methods, as for the Engine API of Ethereum clients.
+    /// The file is created with a random secret if it does not exist. These methods are then only
+    /// served by the versioned RPC server, to requests with an `Authorization: Bearer <JWT>`
+    /// header.
+    #[clap(long, value_name = "PATH", requires = "rpc_versioned_addr")]
+    pub rpc_jwt_secret: Option<PathBuf>,
+
+    /// Address to serve the chain head over plain HTTP on: the latest block at `/head`, and a
+    /// Server-Sent Events stream of the new blocks at `/heads`. Disabled if not set.
+    #[clap(long, value_name = "ADDR")]
+    pub head_events_addr: Option<SocketAddr>,
+
+    /// Address to serve the synced blocks, state updates and Cairo 0 classes on, in the format of
+    /// the feeder gateway (`/feeder_gateway/get_block`, `/feeder_gateway/get_state_update`,
+    /// `/feeder_gateway/get_class_by_hash`), for tools which only speak the gateway protocol.
+    /// Disabled if not set.
+    #[clap(long, value_name = "ADDR")]
+    pub feeder_gateway_addr: Option<SocketAddr>,
+
+    /// Address to stream the progress of the sync on, as JSON lines over TCP, e.g.
+    /// `127.0.0.1:9947`. Used by `deoxys top`. Disabled if not set.
+    #[clap(long, value_name = "ADDR")]
+    pub progress_events_addr: Option<SocketAddr>,
+
+    /// Number of chain head or progress events queued for each of their subscribers (`/heads`
+    /// clients, `deoxys top`, read replicas), 256 head events and 1024 progress events if not set.
+    #[clap(long, value_name = "EVENTS")]
+    pub events_queue_capacity: Option<usize>,
+
+    /// What to do with the subscribers of the chain head and progress events whose queue is
+    /// full: drop their oldest events, or disconnect them.
+    #[clap(long, value_enum, default_value = "drop-oldest")]
+    pub events_slow_subscriber: SlowSubscriberPolicy,
+
+    /// Maximum number of chain head and progress event streams open on a single connection.
+    /// Further streams are refused with a `connection_subscription_limit` error.
+    #[clap(long, value_name = "STREAMS", default_value_t = 4)]
+    pub events_max_subscriptions_per_connection: usize,
+
+    /// Maximum number of chain head and progress event streams open from a single IP address,
+    /// across all its connections. Further streams are refused with an
+    /// `identity_subscription_limit` error.
+    #[clap(long, value_name = "STREAMS", default_value_t = 32)]
+    pub events_max_subscriptions_per_ip: usize,
+
+    /// Address to stream the synced blocks to read replicas on, e.g. `0.0.0.0:9948`. Replicas
+    /// are started with `--replicate-from`, and authenticated with `--replication-secret`. The
+    /// stream is not encrypted, the address should only be reachable from a private network.
+    /// Disabled if not set.
+    #[clap(long, value_name = "ADDR", requires = "replication_secret")]
+    pub replication_addr: Option<SocketAddr>,
+
+    /// Follow the primary node at this address, started with `--replication-addr`, instead of
+    /// syncing from the gateway. The tries are updated with the changes computed by the primary,
+    /// and the resulting state root is checked against each block. The tries of the blocks whose
+    /// changes the primary no longer has are recomputed, unless `--disable-root` is set.
+    #[clap(long, value_name = "ADDR", requires = "replication_secret")]
+    pub replicate_from: Option<SocketAddr>,
+
+    /// Hex encoded 32 bytes secret shared by a primary node and its read replicas, authenticating
+    /// the replicas. The file is created with a random secret on a primary node if it does not
+    /// exist, and must be copied to its replicas.
+    #[clap(long, value_name = "PATH")]
+    pub replication_secret: Option<PathBuf>,
+
+    /// OTLP gRPC endpoint to export traces to, e.g. `http://localhost:4317`. Traces for RPC
+    /// requests, block imports and gateway fetches are only exported when this is set.
+    #[clap(long, value_name = "URL")]
+    pub otlp_endpoint: Option<String>,
+
+    /// Ratio of the traces to export, between 0 and 1.
+    #[clap(long, value_name = "RATIO", default_value_t = 1.0, requires = "otlp_endpoint")]
+    pub otlp_sampling_ratio: f64,
+
+    /// Service name the traces are exported under.
+    #[clap(long, default_value = "deoxys", requires = "otlp_endpoint")]
+    pub otlp_service_name: String,
+
+    /// Serve synced blocks to other nodes over the Starknet p2p protocol (experimental).
+    #[clap(long)]
+    pub p2p: bool,
+
+    /// Address to listen on for p2p connections.
+    #[clap(long, value_name = "MULTIADDR", default_value = "/ip4/0.0.0.0/tcp/30334", requires = "p2p")]
+    pub p2p_listen_addr: Multiaddr,
+
+    /// Peer to connect to on startup, e.g. `/ip4/1.2.3.4/tcp/30334/p2p/<peer id>`. Can be repeated.
+    #[clap(long, value_name = "MULTIADDR", requires = "p2p")]
+    pub p2p_bootnode: Vec<Multiaddr>,
+
+    /// Pull the state diffs and classes of the synced blocks from the p2p bootnodes whose address
+    /// holds their peer id, instead of the gateway (experimental). The state diffs are checked
+    /// against the block hashes, which only commit to them from Starknet 0.13.2.
+    #[clap(long, requires = "p2p_bootnode")]
+    pub p2p_sync: bool,
+
+    /// JSON file listing the blockifier constants of each Starknet version, used to re-execute
+    /// historical blocks. Blocks of unlisted versions are executed with the latest constants.
+    #[clap(long, value_name = "PATH")]
+    pub execution_constants: Option<PathBuf>,
+
+    /// JSON file configuring the alerts sent on critical conditions (state root mismatch, stalled
+    /// sync, low disk space, L1 divergence) to a webhook and/or a shell command.
+    #[clap(long, value_name = "PATH")]
+    pub alerts_config: Option<PathBuf>,
+
+    /// Estimate the fees at the tip of the chain with gas prices polled from L1, smoothed over
+    /// `--gas-oracle-window` polls, instead of the prices the blocks were executed with.
+    #[clap(long)]
+    pub gas_oracle: bool,
+
+    /// Interval, in seconds, between two polls of the gas price oracle.
+    #[clap(long, value_name = "SECONDS", default_value_t = 12, requires = "gas_oracle")]
+    pub gas_oracle_interval: u64,
+
+    /// Number of polls the gas prices are averaged over.
+    #[clap(long, value_name = "POLLS", default_value_t = 20, requires = "gas_oracle")]
+    pub gas_oracle_window: u32,
+
+    /// Constant number of STRK an ETH is worth, used to derive the STRK gas prices. The rate of
+    /// the latest synced block is used if neither this nor `--gas-oracle-strk-rate-url` is set.
+    #[clap(long, value_name = "RATE", requires = "gas_oracle", conflicts_with = "gas_oracle_strk_rate_url")]
+    pub gas_oracle_strk_rate: Option<f64>,
+
+    /// URL of a JSON document holding the number of STRK an ETH is worth.
+    #[clap(long, value_parser = parse_url, value_name = "URL", requires = "gas_oracle")]
+    pub gas_oracle_strk_rate_url: Option<Url>,
+
+    /// JSON pointer to the rate in the document at `--gas-oracle-strk-rate-url`, e.g.
+    /// `/ethereum/strk`.
+    #[clap(long, value_name = "POINTER", default_value = "", requires = "gas_oracle_strk_rate_url")]
+    pub gas_oracle_strk_rate_pointer: String,
+
+    /// URL of an L1 beacon node, from which the blobs of the state updates sent to L1 are
+    /// downloaded to check the state diffs they publish against the synced ones. Requires
+    /// `--l1-endpoint`.
+    #[clap(long, value_parser = parse_url, value_name = "URL", requires = "l1_endpoint")]
+    pub l1_beacon_url: Option<Url>,
+
+    /// Produce blocks locally from the transactions submitted over RPC instead of syncing the
+    /// chain, on top of the genesis block of the network or of the blocks already in the
+    /// database. The sealing is manual and `--l1-endpoint` is optional.
+    #[clap(long)]
+    pub devnet: bool,
+
+    /// Interval, in seconds, between two devnet blocks, produced even when empty. By default, a
+    /// block is produced as soon as a transaction is received.
+    #[clap(long, value_name = "SECONDS", requires = "devnet")]
+    pub devnet_block_time: Option<u64>,
+
+    /// Charge the fee of the transactions included in the devnet blocks.
+    #[clap(long, requires = "devnet")]
+    pub devnet_charge_fee: bool,
+
+    /// Start the devnet from a custom genesis instead of the genesis of the network: a
+    /// `genesis.json` listing the classes to declare, the account and ERC20 classes, and the
+    /// accounts to deploy with their private key and balance in each fee token. The devnet then
+    /// runs without the gateway.
+    #[clap(long, value_name = "PATH", requires = "devnet")]
+    pub devnet_genesis: Option<PathBuf>,
+
+    /// Make runs reproducible across machines: the node name and p2p identity are derived from
+    /// this seed instead of being random. Devnet blocks are timestamped at a fixed interval after
+    /// their parent, `--devnet-block-time` or one second, instead of with the clock, and the
+    /// accounts of `--devnet-genesis` get private keys derived from the seed instead of the ones
+    /// of its `genesis.json`.
+    #[clap(long, value_name = "SEED")]
+    pub seed: Option<u64>,
+
     /// A flag to run the TUI dashboard
     #[cfg(feature = "tui")]
     #[clap(long)]
@@ -158,39 +706,267 @@ pub fn run_node(mut cli: Cli) -> Result<()> {
             });
         }
     }
+    if let Some(seed) = cli.run.seed {
+        cli.run.base.name.get_or_insert_with(|| format!("deoxys-{seed}"));
+    }
     if cli.run.base.shared_params.dev {
         override_dev_environment(&mut cli.run);
     } else if cli.run.deoxys {
         deoxys_environment(&mut cli.run);
     }
 
+    if cli.run.devnet {
+        // the devnet blocks are sealed on demand, as the synced ones
+        cli.run.sealing = Some(Sealing::Manual);
+    }
+
     let runner = cli.create_runner(&cli.run.base)?;
+    let import_archives = match &cli.subcommand {
+        Some(Subcommand::ImportBlocks(cmd)) => cmd.input.clone(),
+        _ => Vec::new(),
+    };
 
     // TODO: verify that the l1_endpoint is valid
-    let l1_endpoint = if let Some(url) = cli.run.l1_endpoint {
-        url
-    } else {
-        return Err(sc_cli::Error::Input(
-            "Missing required --l1-endpoint argument please reffer to https://deoxys-docs.kasar.io".to_string(),
-        ));
+    let l1_endpoint = match cli.run.l1_endpoint {
+        Some(url) => Some(url),
+        None if cli.run.devnet => None,
+        None => {
+            return Err(sc_cli::Error::Input(
+                "Missing required --l1-endpoint argument please reffer to https://deoxys-docs.kasar.io".to_string(),
+            ));
+        }
     };
 
-    runner.run_node_until_exit(|config| async move {
+    let result = runner.run_node_until_exit(|config| async move {
         let sealing = cli.run.sealing.map(Into::into).unwrap_or_default();
         let cache = cli.run.cache;
+        let execution_memory_limit = cli.run.rpc_execution_memory_limit.map(|mib| mib.saturating_mul(1024 * 1024));
         let mut fetch_block_config = cli.run.network.block_fetch_config();
         fetch_block_config.sound = cli.run.sound;
         fetch_block_config.verify = !cli.run.disable_root;
+        fetch_block_config.verify_execution = cli.run.verify_execution;
+        fetch_block_config.force_unsupported = cli.run.force_unsupported;
         fetch_block_config.api_key = cli.run.gateway_key.clone();
+        fetch_block_config.gateway_rate_limit = cli.run.gateway_rate_limit;
+        fetch_block_config.gateway_cache = cli.run.gateway_cache.clone();
+        fetch_block_config.block_queue_capacity = cli.run.sync_queue_capacity.max(1);
+        fetch_block_config.import_archives = import_archives;
+        let replication_secret = match &cli.run.replication_secret {
+            Some(path) => Some(read_replication_secret(path, cli.run.replication_addr.is_some())?),
+            None => None,
+        };
+        fetch_block_config.replicate_from = cli
+            .run
+            .replicate_from
+            .zip(replication_secret.clone())
+            .map(|(address, secret)| PrimaryNode { address, secret });
+        fetch_block_config.admission = AdmissionConfig {
+            max_rpc_p99: cli.run.sync_throttle_rpc_p99.map(Duration::from_millis),
+            max_load: cli.run.sync_throttle_load,
+        };
+        fetch_block_config.timestamp_drift_tolerance = Duration::from_secs(cli.run.sync_timestamp_tolerance);
+        fetch_block_config.disk_watermark = cli.run.sync_min_free_disk.map(|min_free_mib| DiskWatermark {
+            paths: std::iter::once(db_config_dir(&config)).chain(cli.run.cold_storage_path.clone()).collect(),
+            min_free_mib,
+        });
+        fetch_block_config.trusted_checkpoint = cli.run.sync_trusted_checkpoint;
+        fetch_block_config.fallback_gateways = cli
+            .run
+            .gateway_fallback_url
+            .iter()
+            .map(|url| {
+                let url = url.as_str().trim_end_matches('/');
+                GatewayUrls {
+                    gateway: format!("{url}/gateway").parse().unwrap(),
+                    feeder_gateway: format!("{url}/feeder_gateway").parse().unwrap(),
+                }
+            })
+            .collect();
+        fetch_block_config.hashers = CommitmentHashers::from_properties(&config.chain_spec.properties())
+            .map_err(|e| sc_cli::Error::Input(format!("Invalid `{CHAIN_SPEC_PROPERTY}` chain spec property: {e}")))?;
 
         update_config(&fetch_block_config);
         log::debug!("Using fetch block config: {:?}", fetch_block_config);
 
-        let genesis_block = fetch_apply_genesis_block(fetch_block_config.clone()).await.unwrap();
+        if let Some(endpoint) = cli.run.otlp_endpoint {
+            let otel_config = OtelConfig {
+                endpoint,
+                service_name: cli.run.otlp_service_name,
+                sampling_ratio: cli.run.otlp_sampling_ratio,
+            };
+            mc_otel::init(&otel_config).map_err(|e| sc_cli::Error::Input(e.to_string()))?;
+        }
+
+        mc_sync::fanout::configure(FanOutConfig {
+            queue_capacity: cli.run.events_queue_capacity,
+            policy: cli.run.events_slow_subscriber.into(),
+            limits: SubscriberLimits {
+                per_connection: Some(cli.run.events_max_subscriptions_per_connection),
+                per_identity: Some(cli.run.events_max_subscriptions_per_ip),
+            },
+        });
+
+        if let Some(path) = &cli.run.execution_constants {
+            let constants =
+                VersionedConstantsMap::from_file(path).map_err(|e| sc_cli::Error::Input(format!("{e:#}")))?;
+            versioned_constants::init(constants).map_err(|e| sc_cli::Error::Input(e.to_string()))?;
+        }
+
+        let gas_oracle_config = cli.run.gas_oracle.then(|| GasOracleConfig {
+            poll_interval: Duration::from_secs(cli.run.gas_oracle_interval.max(1)),
+            ema_window: cli.run.gas_oracle_window,
+            strk_rate: match (cli.run.gas_oracle_strk_rate, cli.run.gas_oracle_strk_rate_url) {
+                (Some(rate), _) => StrkRateSource::Fixed(rate),
+                (None, Some(url)) => StrkRateSource::Url { url, pointer: cli.run.gas_oracle_strk_rate_pointer },
+                (None, None) => StrkRateSource::Chain,
+            },
+        });
+
+        let devnet_config = cli.run.devnet.then(|| DevnetConfig {
+            block_time: cli.run.devnet_block_time.map(|seconds| Duration::from_secs(seconds.max(1))),
+            charge_fee: cli.run.devnet_charge_fee,
+            genesis: cli.run.devnet_genesis,
+            seed: cli.run.seed,
+        });
+
+        let alert_config = cli.run.alerts_config.as_deref().map(read_alert_config).transpose()?;
+
+        let account_class_whitelist = (!cli.run.rpc_allowed_account_class.is_empty())
+            .then(|| Arc::new(AccountClassWhitelist::new(cli.run.rpc_allowed_account_class)));
+        let method_filter = Arc::new(MethodFilter::new(cli.run.rpc_disable, cli.run.rpc_allow, cli.run.rpc_read_only));
+        // the versioned RPC server follows the `--rpc-cors` and `--rpc-max-*` options of the main one
+        let rpc_config = RpcConfig {
+            max_events_chunk_size: cli.run.rpc_max_events_chunk_size,
+            cors: config.rpc_cors.clone(),
+            max_request_body_size: (config.rpc_max_request_size as usize).saturating_mul(1024 * 1024),
+            max_response_body_size: (config.rpc_max_response_size as usize).saturating_mul(1024 * 1024),
+            max_connections: config.rpc_max_connections as usize,
+            proof_limits: MethodLimits {
+                max_concurrent: cli.run.rpc_max_concurrent_proofs,
+                max_per_second: cli.run.rpc_max_proofs_per_second,
+                max_queue_time: Duration::from_secs(cli.run.rpc_max_queue_time),
+            },
+            trace_limits: MethodLimits {
+                max_concurrent: cli.run.rpc_max_concurrent_traces,
+                max_per_second: cli.run.rpc_max_traces_per_second,
+                max_queue_time: Duration::from_secs(cli.run.rpc_max_queue_time),
+            },
+            nonce_queue_time: cli.run.rpc_nonce_queue_time.map(Duration::from_secs),
+            forward_retries: cli.run.rpc_forward_retries,
+            default_block: cli.run.rpc_default_block.into(),
+            only_verified: cli.run.rpc_only_verified,
+        };
+
+        let p2p_config = cli.run.p2p.then(|| P2pConfig {
+            listen_address: cli.run.p2p_listen_addr,
+            bootnodes: cli.run.p2p_bootnode,
+            identity_seed: cli.run.seed,
+            sync: cli.run.p2p_sync,
+        });
+
+        if let Some(bucket) = &cli.run.snapshot_bucket {
+            crate::snapshots::open_bucket(bucket)?;
+        }
+        let snapshot_dir = config.data_path.join("snapshots");
+
+        let genesis_block = match fetch_apply_genesis_block(fetch_block_config.clone()).await {
+            Ok(genesis_block) => genesis_block,
+            // the genesis block is only used on an empty database, which needs a custom genesis
+            Err(e) if cli.run.devnet => {
+                log::warn!("🧪 Starting the devnet offline, the genesis block can't be fetched: {e}");
+                crate::devnet::offline_genesis_block()
+            }
+            Err(e) => return Err(sc_cli::Error::Input(format!("Failed to fetch the genesis block: {e}"))),
+        };
+
+        service::new_full(
+            config,
+            sealing,
+            l1_endpoint,
+            cache,
+            cli.run.storage_trie_shards,
+            cli.run.cold_storage_path.clone().map(|path| ColdStorageConfig { path, depth: cli.run.cold_storage_depth }),
+            cli.run.snapshot_bucket.clone().map(|bucket| SnapshotConfig {
+                bucket,
+                scratch_dir: snapshot_dir,
+                interval: Duration::from_secs(cli.run.snapshot_interval.max(1) * 3600),
+                keep: cli.run.snapshot_keep,
+            }),
+            db_tuning(&cli.run),
+            fetch_block_config,
+            genesis_block,
+            execution_memory_limit,
+            account_class_whitelist,
+            rpc_config,
+            method_filter,
+            cli.run.rpc_versioned_addr,
+            cli.run.rpc_jwt_secret,
+            cli.run.head_events_addr,
+            cli.run.feeder_gateway_addr,
+            cli.run.progress_events_addr,
+            cli.run
+                .replication_addr
+                .zip(replication_secret)
+                .map(|(address, secret)| ReplicationConfig { address, secret }),
+            p2p_config,
+            alert_config,
+            gas_oracle_config,
+            cli.run.l1_beacon_url,
+            devnet_config,
+        )
+        .map_err(sc_cli::Error::Service)
+    });
+
+    mc_otel::shutdown();
+
+    // the node has stopped at this point, mark the shutdown as clean
+    if let Err(e) = DeoxysBackend::close() {
+        log::error!("Failed to close the Deoxys database: {e:#}");
+    }
 
-        service::new_full(config, sealing, l1_endpoint, cache, fetch_block_config, genesis_block)
-            .map_err(sc_cli::Error::Service)
-    })
+    result
+}
+
+/// Reads the secret shared by a primary node and its read replicas, created if it does not exist
+/// when `create` is set, on primary nodes.
+fn read_replication_secret(path: &Path, create: bool) -> Result<ReplicationSecret> {
+    let secret = if create { load_or_create_secret(path, "replication") } else { read_secret(path, "replication") };
+    secret
+        .map(ReplicationSecret)
+        .map_err(|e| sc_cli::Error::Input(format!("Failed to load the replication secret at {}: {e}", path.display())))
+}
+
+fn read_alert_config(path: &Path) -> Result<AlertConfig> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| sc_cli::Error::Input(format!("Failed to read the alerts config at {}: {e}", path.display())))?;
+    serde_json::from_str(&content)
+        .map_err(|e| sc_cli::Error::Input(format!("Invalid alerts config at {}: {e}", path.display())))
+}
+
+/// The tuning of the database: the selected profile, with the settings overridden by the operator.
+fn db_tuning(cmd: &ExtendedRunCmd) -> DbTuning {
+    let mut tuning = DbTuning::profile(cmd.db_profile.into());
+    if let Some(mib) = cmd.db_block_cache_size {
+        tuning.block_cache_size = mib.saturating_mul(1024 * 1024);
+    }
+    if let Some(mib) = cmd.db_write_buffer_size {
+        tuning.write_buffer_size = mib.saturating_mul(1024 * 1024);
+    }
+    if let Some(max_open_files) = cmd.db_max_open_files {
+        tuning.max_open_files = max_open_files;
+    }
+    if let Some(background_jobs) = cmd.db_background_jobs {
+        tuning.background_jobs = background_jobs;
+    }
+    if let Some(compression) = cmd.db_compression {
+        // overrides the column compressions of the profile too
+        tuning.compression = compression.into();
+        tuning.column_compression.clear();
+    }
+    tuning
+        .column_compression
+        .extend(cmd.db_column_compression.iter().map(|&(column, compression)| (column, compression.into())));
+    tuning
 }
 
 fn override_dev_environment(cmd: &mut ExtendedRunCmd) {
@@ -210,8 +986,8 @@ fn override_dev_environment(cmd: &mut ExtendedRunCmd) {
 }
 
 fn deoxys_environment(cmd: &mut ExtendedRunCmd) {
-    // Set the blockchain network to 'starknet'
-    cmd.base.shared_params.chain = Some("starknet".to_string());
+    // Set the blockchain network to the one of the selected preset
+    cmd.base.shared_params.chain = Some(cmd.network.chain_spec_id().to_string());
     cmd.base.shared_params.base_path.get_or_insert_with(|| PathBuf::from("/tmp/deoxys"));
 
     // Assign a random pokemon name at each startup
@@ -0,0 +1,20 @@
+//! Live view of the sync progress of a running node.
+
+use std::net::SocketAddr;
+
+use sc_cli::{Error, Result};
+
+/// Display the sync progress of a node started with `--progress-events-addr`.
+#[derive(Debug, clap::Args)]
+pub struct TopCmd {
+    /// Address the node streams its sync progress on.
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:9947")]
+    pub addr: SocketAddr,
+}
+
+impl TopCmd {
+    pub fn run(&self) -> Result<()> {
+        let runtime = tokio::runtime::Runtime::new()?;
+        runtime.block_on(deoxys_tui::top(self.addr)).map_err(|e| Error::Application(e.into()))
+    }
+}
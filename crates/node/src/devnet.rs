@@ -0,0 +1,199 @@
+//! Block production of the devnet mode.
+//!
+//! Instead of syncing the chain from the gateway, the node builds its own blocks from the
+//! transactions submitted over RPC, on top of the genesis block of the selected network, or of the
+//! blocks already in the database. The blocks are sealed by the manual seal engine, as the synced
+//! ones are.
+//!
+//! With a custom genesis, the first block produced declares its classes and deploys its funded
+//! accounts, whose keys are logged.
+use std::path::Path;
+use std::sync::Arc;
+
+use mc_db::storage::StorageHandler;
+use mc_db::DeoxysBackend;
+use mc_genesis_data_provider::builder::{BuiltGenesis, GenesisBuilder};
+use mc_rpc::devnet::{DevnetBlockBuilder, DevnetConfig, DevnetPool};
+use mc_sync::errors::SyncError;
+use mc_sync::fetch::fetchers::FetchConfig;
+use mc_sync::fetch::gateway::GatewayProvider;
+use mc_sync::import::{import_block, verify_block};
+use mc_sync::l2::{self, L2StateUpdate, STARKNET_HIGHEST_BLOCK_HASH_AND_NUMBER};
+use mc_sync::SenderConfig;
+use mp_block::{DeoxysBlock, Header};
+use mp_felt::Felt252Wrapper;
+use mp_types::block::DHasherT;
+use sc_client_api::HeaderBackend;
+use tokio::time::MissedTickBehavior;
+
+use crate::service::{FullBackend, FullClient};
+
+/// Starknet version of the blocks of an offline devnet.
+const OFFLINE_PROTOCOL_VERSION: &[u8] = b"0.13.2";
+
+/// Builds and imports a block from the pending transactions of `pool`, as soon as they are
+/// received or at each block time.
+pub async fn produce_blocks(
+    client: Arc<FullClient>,
+    pool: Arc<DevnetPool>,
+    config: DevnetConfig,
+    fetch_config: FetchConfig,
+    mut sender_config: SenderConfig,
+    starting_block: u32,
+) {
+    let first_block = u64::from(starting_block) + 1;
+    // a custom genesis is only written on an empty database
+    let mut genesis = match &config.genesis {
+        Some(path) if first_block == 1 => match load_genesis(path, config.seed) {
+            Ok(genesis) => Some(genesis),
+            Err(e) => {
+                log::error!("🧪 Failed to load the devnet genesis at {}: {e}", path.display());
+                return;
+            }
+        },
+        _ => None,
+    };
+    let init = match &genesis {
+        Some(_) => DeoxysBackend::contract_storage().start_live_updates(0).map(|_| ()).map_err(SyncError::from),
+        None => {
+            let provider = GatewayProvider::new(&fetch_config, None);
+            l2::init_state(&provider, first_block, &sender_config.overrides, fetch_config.hashers).await
+        }
+    };
+    if let Err(e) = init {
+        log::error!(
+            "🧪 Failed to initialize the devnet state: {e}. Starting from the genesis of the network needs its state \
+             from the gateway, start from a custom genesis with `--devnet-genesis` to run offline"
+        );
+        return;
+    }
+
+    let builder = Arc::new(DevnetBlockBuilder::<_, FullBackend>::new(
+        Arc::clone(&client),
+        Arc::clone(&sender_config.overrides),
+        &config,
+    ));
+    let mut interval = config.block_time.map(|block_time| {
+        let mut interval = tokio::time::interval(block_time);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        interval
+    });
+    let mut last_block_hash = None;
+
+    log::info!("🧪 Producing devnet blocks from block {first_block}");
+    loop {
+        // the genesis block is produced right away
+        if genesis.is_none() {
+            match interval.as_mut() {
+                Some(interval) => {
+                    interval.tick().await;
+                }
+                None => pool.wait_for_transactions().await,
+            }
+        }
+
+        // the tries may be updated by a block which is then not imported, and reverted to its parent
+        let parent = u64::from(client.info().best_number);
+        let builder = Arc::clone(&builder);
+        let is_genesis = genesis.is_some();
+        let build = match genesis.take() {
+            Some(genesis) => tokio::task::spawn_blocking(move || builder.build_genesis_block::<DHasherT>(&genesis)),
+            None => {
+                let transactions = pool.take();
+                tokio::task::spawn_blocking(move || builder.build_block::<DHasherT>(transactions))
+            }
+        };
+        let archived = match build.await.map_err(|e| e.to_string()).and_then(|build| build.map_err(|e| e.to_string())) {
+            Ok(archived) => archived,
+            Err(e) if is_genesis => {
+                log::error!("🧪 Failed to build the devnet genesis block: {e}");
+                return;
+            }
+            Err(e) => {
+                log::error!("🧪 Failed to build the next devnet block: {e}");
+                if !discard_block(parent) {
+                    return;
+                }
+                continue;
+            }
+        };
+
+        let block_number = archived.block.header().block_number;
+        // a block whose header does not match its content could not be imported by other nodes
+        if let Err(e) = verify_block::<DHasherT>(&archived, fetch_config.chain_id.into(), fetch_config.hashers) {
+            log::error!("🧪 Devnet block {block_number} failed its verification, stopping block production: {e}");
+            return;
+        }
+        let global_root = archived.block.header().global_state_root;
+        let block_hash = archived.state_update.block_hash.unwrap_or_default();
+        let transaction_count = archived.receipts.len();
+        // the tries were updated when the block was built, they are reverted if it is not imported
+        if let Err(e) = import_block(
+            archived,
+            &mut sender_config,
+            &mut last_block_hash,
+            client.as_ref(),
+            false,
+            fetch_config.hashers,
+        )
+        .await
+        {
+            log::error!("🧪 Failed to import devnet block {block_number}, its transactions are dropped: {e}");
+            if !discard_block(parent) {
+                return;
+            }
+            continue;
+        }
+
+        l2::update_l2(L2StateUpdate { block_number, global_root, block_hash: block_hash.into() });
+        *STARKNET_HIGHEST_BLOCK_HASH_AND_NUMBER
+            .write()
+            .expect("Failed to acquire write lock on STARKNET_HIGHEST_BLOCK_HASH_AND_NUMBER") =
+            (block_hash.into(), block_number);
+        log::info!("🧪 Produced block #{block_number} ({:#x}) with {transaction_count} transactions", block_hash.0);
+    }
+}
+
+/// Reverts the tries to their state right after block `parent`, undoing the block built on top of
+/// it, which was not imported. Returns whether block production can go on.
+fn discard_block(parent: u64) -> bool {
+    match StorageHandler::rewind_to(parent) {
+        Ok(()) => true,
+        Err(e) => {
+            log::error!("🧪 Failed to revert the tries to block {parent}, stopping block production: {e}");
+            false
+        }
+    }
+}
+
+/// The genesis block the devnet starts from when the one of the network can't be fetched from the
+/// gateway: an empty block, on top of which a custom genesis writes its state.
+///
+/// It is only used when the database is empty, the genesis block being stored with the chain.
+pub fn offline_genesis_block() -> DeoxysBlock {
+    let header = Header {
+        protocol_version: Felt252Wrapper::try_from(OFFLINE_PROTOCOL_VERSION).expect("valid protocol version"),
+        ..Default::default()
+    };
+    DeoxysBlock::new(header, Vec::new(), Vec::new())
+}
+
+/// Builds the custom genesis at `path`, logging its funded accounts, whose private keys are derived
+/// from `seed` if set.
+fn load_genesis(path: &Path, seed: Option<u64>) -> Result<BuiltGenesis, String> {
+    let mut builder = GenesisBuilder::from_file(path).map_err(|e| e.to_string())?;
+    if let Some(seed) = seed {
+        builder = builder.with_account_seed(seed);
+    }
+    let genesis = builder.build().map_err(|e| e.to_string())?;
+    for account in &genesis.accounts {
+        let private_key = account.private_key.as_deref().map(hex::encode).unwrap_or_default();
+        log::info!(
+            "🧪 Predeployed account {}: address {:#x}, private key 0x{private_key}, public key {:#x}",
+            account.name,
+            account.contract_address,
+            account.public_key
+        );
+    }
+    Ok(genesis)
+}
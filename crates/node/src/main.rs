@@ -5,14 +5,22 @@
 mod service;
 mod benchmarking;
 mod chain_spec;
+mod cold_storage;
 mod cli;
 mod command;
 mod commands;
 mod configs;
 mod constants;
+mod devnet;
 mod genesis_block;
 mod rpc;
+mod snapshots;
 mod starknet;
+mod verification;
+
+/// Accounts for the memory allocated by RPC executions, see `--rpc-execution-memory-limit`.
+#[global_allocator]
+static ALLOCATOR: mc_rpc::execution_memory::TrackingAllocator = mc_rpc::execution_memory::TrackingAllocator;
 
 fn main() -> sc_cli::Result<()> {
     command::run()
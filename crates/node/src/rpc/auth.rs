@@ -0,0 +1,109 @@
+//! JWT authentication of the RPC methods writing to the chain or administering the node.
+//!
+//! As with the Engine API of Ethereum clients, the node and its clients share a 32 bytes secret,
+//! stored hex encoded in a file, which is created with a random secret if it does not exist.
+//! Requests to protected methods must carry an `Authorization: Bearer <token>` header, the token
+//! being a JWT signed with the secret (HS256), whose `iat` claim is within [`MAX_CLOCK_DRIFT`] of
+//! the clock of the node.
+//!
+//! The `write`, `admin` and `manual-seal` groups are protected, the other methods stay public. The
+//! Substrate RPC server can't authenticate requests: with authentication enabled, the protected
+//! methods are only served by the versioned RPC server, see [`super::versioned`].
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{self, ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use jsonrpsee::RpcModule;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+
+use super::method_filter::MethodGroup;
+
+/// The groups of methods only served to authenticated clients.
+pub const PROTECTED_GROUPS: [MethodGroup; 3] = [MethodGroup::Write, MethodGroup::Admin, MethodGroup::ManualSeal];
+
+/// Maximum difference between the issuance time of a token and the clock of the node.
+pub const MAX_CLOCK_DRIFT: Duration = Duration::from_secs(60);
+
+const SECRET_LEN: usize = 32;
+
+#[derive(Deserialize)]
+struct Claims {
+    iat: u64,
+}
+
+/// Checks the tokens of the requests to the protected methods.
+pub struct RpcAuth {
+    key: DecodingKey,
+    protected: HashSet<String>,
+}
+
+impl RpcAuth {
+    /// Reads the secret at `path`, or creates it, to protect the methods of `protected`.
+    pub fn new<T>(path: &Path, protected: &RpcModule<T>) -> io::Result<Self> {
+        let secret = load_or_create_secret(path, "RPC JWT")?;
+        Ok(Self {
+            key: DecodingKey::from_secret(&secret),
+            protected: protected.method_names().map(str::to_string).collect(),
+        })
+    }
+
+    /// Whether requests to `method` must be authenticated.
+    pub fn is_protected(&self, method: &str) -> bool {
+        self.protected.contains(method)
+    }
+
+    /// Checks the value of the `Authorization` header of a request.
+    pub fn authorize(&self, authorization: Option<&str>) -> Result<(), String> {
+        let token = authorization
+            .and_then(|authorization| authorization.strip_prefix("Bearer "))
+            .ok_or_else(|| "missing bearer token".to_string())?;
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.required_spec_claims.clear();
+        validation.validate_exp = false;
+        let claims = jsonwebtoken::decode::<Claims>(token.trim(), &self.key, &validation)
+            .map_err(|e| format!("invalid token: {e}"))?
+            .claims;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        if now.abs_diff(claims.iat) > MAX_CLOCK_DRIFT.as_secs() {
+            return Err(format!(
+                "token issued at {}, {} seconds away from the node clock",
+                claims.iat,
+                now.abs_diff(claims.iat)
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Reads the hex encoded secret at `path`, or writes a new random one there if there is none.
+///
+/// `name` names the secret in the errors and logs.
+pub(crate) fn load_or_create_secret(path: &Path, name: &str) -> io::Result<[u8; SECRET_LEN]> {
+    match read_secret(path, name) {
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let secret: [u8; SECRET_LEN] = rand::random();
+            let mut file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?;
+            writeln!(file, "0x{}", hex::encode(secret))?;
+            log::info!("🔑 Generated a new {name} secret at {}", path.display());
+            Ok(secret)
+        }
+        result => result,
+    }
+}
+
+/// Reads the hex encoded secret at `path`, named `name` in the errors.
+pub(crate) fn read_secret(path: &Path, name: &str) -> io::Result<[u8; SECRET_LEN]> {
+    let content = std::fs::read_to_string(path)?;
+    let content = content.trim();
+    let secret = hex::decode(content.strip_prefix("0x").unwrap_or(content))
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, format!("Invalid hex {name} secret: {e}")))?;
+    secret.try_into().map_err(|_| {
+        io::Error::new(ErrorKind::InvalidData, format!("The {name} secret must be {SECRET_LEN} bytes long"))
+    })
+}
@@ -0,0 +1,476 @@
+//! Feeder gateway compatible HTTP endpoints, for tools which only speak the gateway protocol.
+//!
+//! * `GET /feeder_gateway/get_block` returns a block with its transactions and receipts.
+//! * `GET /feeder_gateway/get_state_update` returns the state diff of a block.
+//! * `GET /feeder_gateway/get_class_by_hash?classHash=0x...` returns the definition of a Cairo 0
+//!   class.
+//!
+//! Blocks are selected with the `blockNumber` (a number or `latest`) or `blockHash` query
+//! parameters, and default to the latest block. The pending block is not served. Errors follow the
+//! gateway format, e.g. `{"code": "StarknetErrorCode.BLOCK_NOT_FOUND", "message": "..."}`.
+//!
+//! Responses are built from the local database, see [`archived_block`], on the blocking thread
+//! pool. The node does not keep the Sierra program of the classes it compiled, so only Cairo 0
+//! classes are served: requests for Sierra classes fail with `SIERRA_PROGRAM_NOT_STORED` rather
+//! than returning a class the gateway would never return. These endpoints can't back the sync of
+//! another node, which needs the Sierra classes.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::num::NonZeroU128;
+use std::sync::Arc;
+
+use blockifier::blockifier::block::GasPrices;
+use blockifier::execution::contract_class::ContractClass as ContractClassBlockifier;
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use mc_db::DeoxysBackend;
+use mc_storage::{overrides_handle, OverrideHandle};
+use mc_sync::import::ArchivedBlock;
+use mc_sync::l1::ETHEREUM_STATE_UPDATE;
+use mp_block::receipt::TransactionReceiptWrapper;
+use mp_block::state_update::StateUpdateWrapper;
+use mp_contract::class::ContractClassWrapper;
+use mp_convert::gateway::to_gateway_transaction;
+use mp_felt::Felt252Wrapper;
+use mp_types::block::{DBlockT, DHasherT};
+use serde_json::{json, Value};
+use sp_blockchain::HeaderBackend;
+use starknet_api::data_availability::L1DataAvailabilityMode;
+use starknet_core::types::{ContractClass, FieldElement};
+
+use crate::commands::archived_block;
+use crate::service::FullClient;
+
+/// A gateway error, sent with the given HTTP status.
+struct GatewayError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl GatewayError {
+    fn block_not_found(message: impl Into<String>) -> Self {
+        Self { status: StatusCode::BAD_REQUEST, code: "StarknetErrorCode.BLOCK_NOT_FOUND", message: message.into() }
+    }
+
+    fn undeclared_class(class_hash: FieldElement) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            code: "StarknetErrorCode.UNDECLARED_CLASS",
+            message: format!("Class with hash {class_hash:#x} is not declared."),
+        }
+    }
+
+    fn sierra_program_not_stored(class_hash: FieldElement) -> Self {
+        Self {
+            status: StatusCode::NOT_IMPLEMENTED,
+            code: "StarknetErrorCode.SIERRA_PROGRAM_NOT_STORED",
+            message: format!("Class {class_hash:#x} is a Sierra class, whose program is not stored by this node."),
+        }
+    }
+
+    fn malformed(message: impl Into<String>) -> Self {
+        Self { status: StatusCode::BAD_REQUEST, code: "StarkErrorCode.MALFORMED_REQUEST", message: message.into() }
+    }
+
+    fn internal(message: impl Into<String>) -> Self {
+        let message = message.into();
+        log::error!("Feeder gateway request failed: {message}");
+        Self { status: StatusCode::INTERNAL_SERVER_ERROR, code: "StarknetErrorCode.INTERNAL_ERROR", message }
+    }
+}
+
+type GatewayResult = Result<Value, GatewayError>;
+
+/// Serves the feeder gateway endpoints on `address`, from the blocks and classes of `client`.
+pub async fn serve(address: SocketAddr, client: Arc<FullClient>) {
+    let overrides = overrides_handle(Arc::clone(&client));
+    let make_service = make_service_fn(move |_| {
+        let (client, overrides) = (Arc::clone(&client), Arc::clone(&overrides));
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let (client, overrides) = (Arc::clone(&client), Arc::clone(&overrides));
+                async move {
+                    // the responses are read from the database
+                    let response = tokio::task::spawn_blocking(move || handle(request, &client, &overrides))
+                        .await
+                        .unwrap_or_else(|e| {
+                            log::error!("Feeder gateway request panicked: {e}");
+                            empty_response(StatusCode::INTERNAL_SERVER_ERROR)
+                        });
+                    Ok::<_, Infallible>(response)
+                }
+            }))
+        }
+    });
+
+    log::info!("🌐 Feeder gateway listening on {address} (/feeder_gateway)");
+    if let Err(e) = Server::bind(&address).serve(make_service).await {
+        log::error!("Feeder gateway server failed: {e}");
+    }
+}
+
+fn handle(request: Request<Body>, client: &FullClient, overrides: &OverrideHandle<DBlockT>) -> Response<Body> {
+    if request.method() != Method::GET {
+        return empty_response(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    let params: HashMap<String, String> = request
+        .uri()
+        .query()
+        .map(|query| url::form_urlencoded::parse(query.as_bytes()).into_owned().collect())
+        .unwrap_or_default();
+
+    let result = match request.uri().path() {
+        "/feeder_gateway/get_block" => get_block(client, &params),
+        "/feeder_gateway/get_state_update" => get_state_update(client, &params),
+        "/feeder_gateway/get_class_by_hash" => get_class_by_hash(client, overrides, &params),
+        _ => return empty_response(StatusCode::NOT_FOUND),
+    };
+    json_response(result)
+}
+
+fn empty_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+fn json_response(result: GatewayResult) -> Response<Body> {
+    let (status, body) = match result {
+        Ok(body) => (StatusCode::OK, body),
+        Err(e) => (e.status, json!({ "code": e.code, "message": e.message })),
+    };
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("Response with valid headers should build")
+}
+
+fn get_block(client: &FullClient, params: &HashMap<String, String>) -> GatewayResult {
+    let ArchivedBlock { block, receipts, .. } = load_block(client, params)?;
+    let header = block.header();
+    let block_hash: FieldElement = header.hash::<DHasherT>().into();
+
+    let transactions = block
+        .transactions()
+        .iter()
+        .zip(&receipts)
+        .map(|(transaction, receipt)| {
+            to_gateway_transaction(transaction, receipt.transaction_hash.0)
+                .map_err(|e| GatewayError::internal(format!("Failed to serialize a transaction: {e}")))
+        })
+        .collect::<Result<Vec<_>, GatewayError>>()?;
+    let transaction_receipts: Vec<_> =
+        receipts.iter().enumerate().map(|(index, receipt)| transaction_receipt(index, receipt)).collect();
+
+    let status = if header.block_number <= ETHEREUM_STATE_UPDATE.read().unwrap().block_number {
+        "ACCEPTED_ON_L1"
+    } else {
+        "ACCEPTED_ON_L2"
+    };
+    let (l1_gas_price, l1_data_gas_price) = gas_prices(header.l1_gas_price.as_ref());
+    let l1_da_mode = match header.l1_da_mode {
+        L1DataAvailabilityMode::Calldata => "CALLDATA",
+        L1DataAvailabilityMode::Blob => "BLOB",
+    };
+
+    Ok(json!({
+        "block_hash": felt(block_hash),
+        "parent_block_hash": felt(Felt252Wrapper::from(header.parent_block_hash).into()),
+        "block_number": header.block_number,
+        "state_root": felt(Felt252Wrapper::from(header.global_state_root).into()),
+        "transaction_commitment": felt(Felt252Wrapper::from(header.transaction_commitment).into()),
+        "event_commitment": felt(Felt252Wrapper::from(header.event_commitment).into()),
+        "status": status,
+        "l1_da_mode": l1_da_mode,
+        "l1_gas_price": l1_gas_price,
+        "l1_data_gas_price": l1_data_gas_price,
+        "transactions": transactions,
+        "timestamp": header.block_timestamp,
+        "sequencer_address": felt(Felt252Wrapper::from(header.sequencer_address).into()),
+        "transaction_receipts": transaction_receipts,
+        "starknet_version": header.protocol_version.from_utf8().ok(),
+    }))
+}
+
+fn get_state_update(client: &FullClient, params: &HashMap<String, String>) -> GatewayResult {
+    let ArchivedBlock { block, state_update, .. } = load_block(client, params)?;
+    let StateUpdateWrapper { old_root, state_diff, .. } = state_update;
+    let block_hash: FieldElement = block.header().hash::<DHasherT>().into();
+    let new_root: FieldElement = Felt252Wrapper::from(block.header().global_state_root).into();
+
+    let storage_diffs: serde_json::Map<_, _> = state_diff
+        .storage_diffs
+        .iter()
+        .map(|(address, diffs)| {
+            let diffs: Vec<_> =
+                diffs.iter().map(|diff| json!({ "key": felt(diff.key.0), "value": felt(diff.value.0) })).collect();
+            (felt(address.0), json!(diffs))
+        })
+        .collect();
+    let nonces: serde_json::Map<_, _> =
+        state_diff.nonces.iter().map(|(address, nonce)| (felt(address.0), json!(felt(nonce.0)))).collect();
+    let contracts = |contracts: &[mp_block::state_update::DeployedContractWrapper]| -> Vec<Value> {
+        contracts
+            .iter()
+            .map(|contract| json!({ "address": felt(contract.address.0), "class_hash": felt(contract.class_hash.0) }))
+            .collect()
+    };
+
+    Ok(json!({
+        "block_hash": felt(block_hash),
+        "new_root": felt(new_root),
+        "old_root": felt(old_root.0),
+        "state_diff": {
+            "storage_diffs": storage_diffs,
+            "nonces": nonces,
+            "deployed_contracts": contracts(&state_diff.deployed_contracts),
+            "old_declared_contracts": state_diff.old_declared_contracts.iter().map(|hash| felt(hash.0)).collect::<Vec<_>>(),
+            "declared_classes": state_diff
+                .declared_classes
+                .iter()
+                .map(|class| json!({
+                    "class_hash": felt(class.class_hash.0),
+                    "compiled_class_hash": felt(class.compiled_class_hash.0),
+                }))
+                .collect::<Vec<_>>(),
+            "replaced_classes": contracts(&state_diff.replaced_classes),
+        },
+    }))
+}
+
+fn get_class_by_hash(
+    client: &FullClient,
+    overrides: &OverrideHandle<DBlockT>,
+    params: &HashMap<String, String>,
+) -> GatewayResult {
+    let class_hash = params
+        .get("classHash")
+        .ok_or_else(|| GatewayError::malformed("Missing classHash parameter"))
+        .and_then(|hash| parse_felt("classHash", hash))?;
+    let starknet_class_hash = Felt252Wrapper(class_hash).into();
+
+    let declared_at = DeoxysBackend::class()
+        .declaration_block_number(&starknet_class_hash)
+        .map_err(|e| GatewayError::internal(format!("Failed to read the declaration of class {class_hash:#x}: {e}")))?;
+    // the class is only served from its declaration block onwards
+    if params.contains_key("blockNumber") || params.contains_key("blockHash") {
+        let block_number = load_block_number(client, params)?;
+        if declared_at.is_some_and(|declared_at| block_number < declared_at) {
+            return Err(GatewayError::undeclared_class(class_hash));
+        }
+    }
+
+    let best_hash = client.info().best_hash;
+    let storage = overrides.for_block_hash(client, best_hash);
+    let (Some(contract), Some(abi)) = (
+        storage.contract_class_by_class_hash(best_hash, starknet_class_hash),
+        storage.contract_abi_by_class_hash(best_hash, starknet_class_hash),
+    ) else {
+        return Err(GatewayError::undeclared_class(class_hash));
+    };
+
+    // the gateway serves the program of Cairo 0 classes as plain JSON
+    let program = match &contract {
+        ContractClassBlockifier::V0(contract) => contract.program.serialize().map_err(|e| {
+            GatewayError::internal(format!("Failed to serialize the program of class {class_hash:#x}: {e}"))
+        })?,
+        ContractClassBlockifier::V1(_) => return Err(GatewayError::sierra_program_not_stored(class_hash)),
+    };
+    let class: ContractClass = ContractClassWrapper { contract, abi }
+        .try_into()
+        .map_err(|e| GatewayError::internal(format!("Failed to convert class {class_hash:#x}: {e}")))?;
+
+    let mut class = serde_json::to_value(class)
+        .map_err(|e| GatewayError::internal(format!("Failed to serialize class {class_hash:#x}: {e}")))?;
+    class["program"] = serde_json::from_slice(&program)
+        .map_err(|e| GatewayError::internal(format!("Invalid program of class {class_hash:#x}: {e}")))?;
+    Ok(class)
+}
+
+/// Reads the block selected by the `blockNumber` or `blockHash` query parameters.
+fn load_block(client: &FullClient, params: &HashMap<String, String>) -> Result<ArchivedBlock, GatewayError> {
+    let block_number = load_block_number(client, params)?;
+    archived_block(client, block_number).map_err(|e| GatewayError::internal(e.to_string()))
+}
+
+/// Resolves the number of the block selected by the `blockNumber` or `blockHash` query
+/// parameters, the latest block if there are none.
+fn load_block_number(client: &FullClient, params: &HashMap<String, String>) -> Result<u64, GatewayError> {
+    let best_number = u64::from(client.info().best_number);
+
+    let block_number = match (params.get("blockNumber"), params.get("blockHash")) {
+        (Some(_), Some(_)) => return Err(GatewayError::malformed("Only one of blockNumber and blockHash can be set")),
+        (None, None) => best_number,
+        (Some(number), None) => match number.as_str() {
+            "latest" => best_number,
+            "pending" => return Err(GatewayError::block_not_found("The pending block is not served by this node")),
+            number => number.parse().map_err(|_| GatewayError::malformed(format!("Invalid blockNumber: {number}")))?,
+        },
+        (None, Some(hash)) => {
+            let hash = parse_felt("blockHash", hash)?;
+            let substrate_hashes = DeoxysBackend::mapping()
+                .block_hash(Felt252Wrapper(hash).into())
+                .map_err(|e| GatewayError::internal(format!("Failed to resolve block {hash:#x}: {e}")))?
+                .unwrap_or_default();
+            // only blocks of the canonical chain are served
+            substrate_hashes
+                .into_iter()
+                .find_map(|substrate_hash| {
+                    let number = client.number(substrate_hash).ok().flatten()?;
+                    (client.hash(number).ok().flatten() == Some(substrate_hash)).then_some(u64::from(number))
+                })
+                .ok_or_else(|| GatewayError::block_not_found(format!("Block with hash {hash:#x} not found")))?
+        }
+    };
+
+    // the genesis block is part of the chain spec and holds no Starknet block data
+    if block_number == 0 || block_number > best_number {
+        return Err(GatewayError::block_not_found(format!("Block number {block_number} was not found")));
+    }
+    Ok(block_number)
+}
+
+fn transaction_receipt(index: usize, receipt: &TransactionReceiptWrapper) -> Value {
+    let resources = &receipt.execution_resources;
+    let builtins: serde_json::Map<_, _> = [
+        ("range_check_builtin", resources.range_check_builtin_applications),
+        ("pedersen_builtin", resources.pedersen_builtin_applications),
+        ("poseidon_builtin", resources.poseidon_builtin_applications),
+        ("ec_op_builtin", resources.ec_op_builtin_applications),
+        ("ecdsa_builtin", resources.ecdsa_builtin_applications),
+        ("bitwise_builtin", resources.bitwise_builtin_applications),
+        ("keccak_builtin", resources.keccak_builtin_applications),
+        ("segment_arena_builtin", resources.segment_arena_builtin),
+    ]
+    .into_iter()
+    .filter_map(|(name, count)| Some((name.to_string(), json!(count?))))
+    .collect();
+
+    let mut receipt_json = json!({
+        "transaction_index": index,
+        "transaction_hash": felt(receipt.transaction_hash.0),
+        "l2_to_l1_messages": receipt
+            .messages_sent
+            .iter()
+            .map(|message| json!({
+                "from_address": felt(message.from_address.0),
+                "to_address": felt(message.to_address.0),
+                "payload": message.payload.iter().map(|felt_| felt(felt_.0)).collect::<Vec<_>>(),
+            }))
+            .collect::<Vec<_>>(),
+        "events": receipt
+            .events
+            .iter()
+            .map(|event| json!({
+                "from_address": felt(event.from_address.0),
+                "keys": event.keys.iter().map(|key| felt(key.0)).collect::<Vec<_>>(),
+                "data": event.data.iter().map(|data| felt(data.0)).collect::<Vec<_>>(),
+            }))
+            .collect::<Vec<_>>(),
+        "execution_resources": {
+            "n_steps": resources.steps,
+            "builtin_instance_counter": builtins,
+            "n_memory_holes": resources.memory_holes.unwrap_or_default(),
+            "data_availability": { "l1_gas": resources.l1_gas, "l1_data_gas": resources.l1_data_gas },
+        },
+        "actual_fee": felt(receipt.actual_fee.0),
+        "execution_status": if receipt.is_reverted() { "REVERTED" } else { "SUCCEEDED" },
+    });
+    if let Some(revert_error) = &receipt.revert_error {
+        receipt_json["revert_error"] = json!(revert_error);
+    }
+    receipt_json
+}
+
+/// The L1 gas and data gas prices of a block, in the gateway format.
+fn gas_prices(prices: Option<&GasPrices>) -> (Value, Value) {
+    // 1 stands for 0, gas prices are stored as NonZeroU128
+    let price = |price: NonZeroU128| if price.get() == 1 { hex(0) } else { hex(price.get()) };
+    match prices {
+        Some(prices) => (
+            json!({ "price_in_wei": price(prices.eth_l1_gas_price), "price_in_fri": price(prices.strk_l1_gas_price) }),
+            json!({
+                "price_in_wei": price(prices.eth_l1_data_gas_price),
+                "price_in_fri": price(prices.strk_l1_data_gas_price),
+            }),
+        ),
+        None => (
+            json!({ "price_in_wei": "0x0", "price_in_fri": "0x0" }),
+            json!({ "price_in_wei": "0x1", "price_in_fri": "0x1" }),
+        ),
+    }
+}
+
+fn parse_felt(name: &str, value: &str) -> Result<FieldElement, GatewayError> {
+    FieldElement::from_hex_be(value).map_err(|_| GatewayError::malformed(format!("Invalid {name}: {value}")))
+}
+
+fn felt(value: FieldElement) -> String {
+    format!("{value:#x}")
+}
+
+fn hex(value: u128) -> String {
+    format!("{value:#x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use mp_block::receipt::ExecutionResourcesWrapper;
+
+    use super::*;
+
+    #[test]
+    fn receipts_only_list_the_builtins_they_used() {
+        let receipt = TransactionReceiptWrapper {
+            transaction_hash: Felt252Wrapper::from(1u64),
+            actual_fee: Felt252Wrapper::from(2u64),
+            revert_error: Some("reverted".to_string()),
+            execution_resources: ExecutionResourcesWrapper {
+                steps: 10,
+                range_check_builtin_applications: Some(3),
+                ..Default::default()
+            },
+            messages_sent: vec![],
+            events: vec![],
+        };
+
+        let json = transaction_receipt(4, &receipt);
+        assert_eq!(json["transaction_index"], 4);
+        assert_eq!(json["transaction_hash"], "0x1");
+        assert_eq!(json["actual_fee"], "0x2");
+        assert_eq!(json["execution_status"], "REVERTED");
+        assert_eq!(json["revert_error"], "reverted");
+        assert_eq!(json["execution_resources"]["builtin_instance_counter"], json!({ "range_check_builtin": 3 }));
+    }
+
+    #[test]
+    fn stored_zero_gas_prices_are_served_as_zero() {
+        let non_zero = |price| NonZeroU128::new(price).unwrap();
+        let prices = GasPrices {
+            eth_l1_gas_price: non_zero(1),
+            strk_l1_gas_price: non_zero(2),
+            eth_l1_data_gas_price: non_zero(1),
+            strk_l1_data_gas_price: non_zero(3),
+        };
+
+        let (l1_gas_price, l1_data_gas_price) = gas_prices(Some(&prices));
+        assert_eq!(l1_gas_price, json!({ "price_in_wei": "0x0", "price_in_fri": "0x2" }));
+        assert_eq!(l1_data_gas_price, json!({ "price_in_wei": "0x0", "price_in_fri": "0x3" }));
+    }
+
+    #[test]
+    fn errors_are_sent_in_the_gateway_format() {
+        let response = json_response(Err(GatewayError::sierra_program_not_stored(FieldElement::ONE)));
+        assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+
+        let body = futures::executor::block_on(hyper::body::to_bytes(response.into_body())).unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "StarknetErrorCode.SIERRA_PROGRAM_NOT_STORED");
+    }
+}
@@ -0,0 +1,125 @@
+//! HTTP endpoints following the chain head, for consumers which can't keep a JSON-RPC WebSocket
+//! connection open (dashboards, shell scripts...).
+//!
+//! * `GET /head` returns the summary of the latest imported block, or `404` before the first one.
+//! * `GET /heads` streams [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html):
+//!   a `head` event with the summary of each imported block, a `reorg` event when blocks are
+//!   replaced, and an `l1_confirmed` event when a new state update is verified on L1, e.g.
+//!
+//! ```text
+//! curl -N http://localhost:9945/heads
+//! event: head
+//! data: {"block_number":612,"block_hash":"0x5a1...","timestamp":1700000000,"transaction_count":12}
+//! ```
+//!
+//! Events are published by the sync worker, see [`mc_sync::head`]. Streams going over the
+//! [subscriber limits](mc_sync::fanout::SubscriberLimits) of their connection or IP address are
+//! refused with `429 Too Many Requests`, and a JSON body telling which limit was hit, e.g.
+//! `{"code":"identity_subscription_limit","message":"too many subscriptions from ..."}`.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::stream;
+use hyper::header::{CACHE_CONTROL, CONTENT_TYPE};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use mc_sync::fanout::{Client, ConnectionId};
+use mc_sync::head::{self, HeadEvent, NewHeadStream};
+use mp_block::Header;
+use mp_types::block::DHasherT;
+use serde_json::{json, Value};
+use starknet_core::types::FieldElement;
+
+/// Interval between two keep-alive comments, so that proxies don't close idle streams.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Serves the chain head endpoints on `address`.
+pub async fn serve(address: SocketAddr) {
+    let make_service = make_service_fn(|connection: &AddrStream| {
+        let client = Client { connection: ConnectionId::next(), identity: connection.remote_addr().ip() };
+        async move { Ok::<_, Infallible>(service_fn(move |request| handle(request, client))) }
+    });
+
+    log::info!("🌐 Chain head events listening on {address} (/head, /heads)");
+    if let Err(e) = Server::bind(&address).serve(make_service).await {
+        log::error!("Chain head events server failed: {e}");
+    }
+}
+
+async fn handle(request: Request<Body>, client: Client) -> Result<Response<Body>, Infallible> {
+    if request.method() != Method::GET {
+        return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+    }
+
+    let response = match request.uri().path() {
+        "/head" => match head::latest_head().borrow().as_ref() {
+            Some(header) => Response::builder()
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(summary(header).to_string())),
+            None => return Ok(status(StatusCode::NOT_FOUND)),
+        },
+        "/heads" => match head::subscribe_as(client) {
+            Ok(subscription) => Response::builder()
+                .header(CONTENT_TYPE, "text/event-stream")
+                .header(CACHE_CONTROL, "no-cache")
+                .body(Body::wrap_stream(events(subscription))),
+            Err(e) => Response::builder()
+                .status(StatusCode::TOO_MANY_REQUESTS)
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "code": e.code(), "message": e.to_string() }).to_string())),
+        },
+        _ => return Ok(status(StatusCode::NOT_FOUND)),
+    };
+
+    Ok(response.expect("Response with valid headers should build"))
+}
+
+/// Formats the head events as Server-Sent Events, interleaved with keep-alive comments. The
+/// stream ends when the client falls behind and is disconnected.
+fn events(events: NewHeadStream) -> impl futures::Stream<Item = Result<String, Infallible>> {
+    let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+    keep_alive.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    stream::unfold((events, keep_alive), |(mut events, mut keep_alive)| async move {
+        let message = tokio::select! {
+            event = events.next() => {
+                let (name, data) = match event? {
+                    HeadEvent::NewHead(header) => ("head", summary(&header)),
+                    HeadEvent::Reorg { common_ancestor, previous_head: (hash, number) } => (
+                        "reorg",
+                        json!({
+                            "common_ancestor": common_ancestor,
+                            "previous_head": { "block_number": number, "block_hash": format!("{hash:#x}") },
+                        }),
+                    ),
+                    HeadEvent::L1Confirmed(update) => (
+                        "l1_confirmed",
+                        json!({ "block_number": update.block_number, "block_hash": update.block_hash.to_string() }),
+                    ),
+                };
+                format!("event: {name}\ndata: {data}\n\n")
+            }
+            _ = keep_alive.tick() => ": keep-alive\n\n".to_string(),
+        };
+        Some((Ok(message), (events, keep_alive)))
+    })
+}
+
+fn summary(header: &Header) -> Value {
+    let block_hash: FieldElement = header.hash::<DHasherT>().into();
+    json!({
+        "block_number": header.block_number,
+        "block_hash": format!("{block_hash:#x}"),
+        "timestamp": header.block_timestamp,
+        "transaction_count": header.transaction_count,
+    })
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
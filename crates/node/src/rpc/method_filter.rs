@@ -0,0 +1,153 @@
+//! Disabling of RPC methods, e.g. to only serve reads on public replicas.
+//!
+//! Methods are selected either by name (`starknet_traceTransaction`), by prefix
+//! (`starknet_trace*`), or by group:
+//!
+//! * `read`, `write` and `trace`: the corresponding parts of the Starknet RPC spec,
+//! * `deoxys` and `pathfinder`: the node specific extensions,
+//! * `admin`: the node administration methods, only served with unsafe RPC methods enabled,
+//! * `system` and `manual-seal`: the Substrate methods.
+//!
+//! Selected methods are either disabled, or, when an allowlist is given, the only ones served. The
+//! read-only mode disables the `write`, `trace`, `admin` and `manual-seal` groups, so that the RPC
+//! can be exposed publicly. Disabled methods are not registered, so requests to them fail with
+//! `METHOD_NOT_FOUND`.
+use std::str::FromStr;
+
+use jsonrpsee::RpcModule;
+
+/// A group of RPC methods, registered together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MethodGroup {
+    /// Starknet methods reading the chain state.
+    Read,
+    /// Starknet methods submitting transactions.
+    Write,
+    /// Starknet methods tracing and simulating transactions.
+    Trace,
+    /// Deoxys specific methods.
+    Deoxys,
+    /// Pathfinder compatible methods.
+    Pathfinder,
+    /// Deoxys administration methods.
+    Admin,
+    /// Substrate system methods.
+    System,
+    /// Manual sealing of blocks, on dev chains.
+    ManualSeal,
+}
+
+impl MethodGroup {
+    const ALL: [(&'static str, MethodGroup); 8] = [
+        ("read", MethodGroup::Read),
+        ("write", MethodGroup::Write),
+        ("trace", MethodGroup::Trace),
+        ("deoxys", MethodGroup::Deoxys),
+        ("pathfinder", MethodGroup::Pathfinder),
+        ("admin", MethodGroup::Admin),
+        ("system", MethodGroup::System),
+        ("manual-seal", MethodGroup::ManualSeal),
+    ];
+
+    /// The groups not served in read-only mode.
+    const UNSAFE: [MethodGroup; 4] =
+        [MethodGroup::Write, MethodGroup::Trace, MethodGroup::Admin, MethodGroup::ManualSeal];
+}
+
+/// A method, prefix or group of methods, as given on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MethodSelector {
+    /// All the methods of a group.
+    Group(MethodGroup),
+    /// The methods whose name starts with a prefix.
+    Prefix(String),
+    /// A single method.
+    Name(String),
+}
+
+impl FromStr for MethodSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, group)) = MethodGroup::ALL.iter().find(|(name, _)| *name == s) {
+            return Ok(MethodSelector::Group(*group));
+        }
+        if let Some(prefix) = s.strip_suffix('*') {
+            return Ok(MethodSelector::Prefix(prefix.to_string()));
+        }
+        if s.contains('_') {
+            return Ok(MethodSelector::Name(s.to_string()));
+        }
+
+        let groups: Vec<_> = MethodGroup::ALL.iter().map(|(name, _)| *name).collect();
+        Err(format!(
+            "Unknown RPC method or group {s:?}, expected a method name, a prefix ending with `*`, or one of {}",
+            groups.join(", ")
+        ))
+    }
+}
+
+impl MethodSelector {
+    fn matches(&self, group: MethodGroup, method: &str) -> bool {
+        match self {
+            MethodSelector::Group(selected) => *selected == group,
+            MethodSelector::Prefix(prefix) => method.starts_with(prefix.as_str()),
+            MethodSelector::Name(name) => method == name,
+        }
+    }
+}
+
+/// The RPC methods served by the node.
+#[derive(Debug, Clone, Default)]
+pub struct MethodFilter {
+    disabled: Vec<MethodSelector>,
+    /// The only methods served, all if empty.
+    allowed: Vec<MethodSelector>,
+}
+
+impl MethodFilter {
+    /// Disables the given methods, prefixes and groups. When `allowed` is not empty, only the
+    /// methods it selects are served. `read_only` disables the methods writing to the chain or the
+    /// node, and the expensive traces.
+    pub fn new(mut disabled: Vec<MethodSelector>, allowed: Vec<MethodSelector>, read_only: bool) -> Self {
+        if read_only {
+            disabled.extend(MethodGroup::UNSAFE.map(MethodSelector::Group));
+        }
+        Self { disabled, allowed }
+    }
+
+    /// The same filter, also disabling the methods of `groups`.
+    pub fn without_groups(&self, groups: &[MethodGroup]) -> Self {
+        let mut filter = self.clone();
+        filter.disabled.extend(groups.iter().copied().map(MethodSelector::Group));
+        filter
+    }
+
+    fn is_group_disabled(&self, group: MethodGroup) -> bool {
+        self.disabled.contains(&MethodSelector::Group(group))
+    }
+
+    fn is_method_disabled(&self, group: MethodGroup, method: &str) -> bool {
+        self.disabled.iter().any(|disabled| disabled.matches(group, method))
+            || (!self.allowed.is_empty() && !self.allowed.iter().any(|allowed| allowed.matches(group, method)))
+    }
+
+    /// Merges the methods of `group` into `module`, except for the disabled ones.
+    pub fn merge<T: Send + Sync + 'static>(
+        &self,
+        module: &mut RpcModule<()>,
+        group: MethodGroup,
+        mut methods: RpcModule<T>,
+    ) -> Result<(), jsonrpsee::core::Error> {
+        if self.is_group_disabled(group) {
+            return Ok(());
+        }
+
+        let disabled: Vec<&'static str> =
+            methods.method_names().filter(|method| self.is_method_disabled(group, method)).collect();
+        for method in disabled {
+            methods.remove_method(method);
+        }
+        module.merge(methods)
+    }
+}
@@ -5,7 +5,14 @@
 
 #![warn(missing_docs)]
 
+pub mod auth;
+pub mod feeder_gateway;
+pub mod head_events;
+pub mod method_filter;
+pub mod progress_events;
+pub mod replication;
 mod starknet;
+pub mod versioned;
 use std::sync::Arc;
 
 use futures::channel::mpsc;
@@ -24,6 +31,8 @@ use sp_block_builder::BlockBuilder;
 use sp_blockchain::{Error as BlockChainError, HeaderBackend, HeaderMetadata};
 pub use starknet::StarknetDeps;
 
+use self::method_filter::{MethodFilter, MethodGroup};
+
 /// Full client dependencies.
 pub struct FullDeps<A: ChainApi, C, G: GenesisProvider, P> {
     /// The client instance to use.
@@ -38,6 +47,8 @@ pub struct FullDeps<A: ChainApi, C, G: GenesisProvider, P> {
     pub command_sink: Option<mpsc::Sender<EngineCommand<DHashT>>>,
     /// Starknet dependencies
     pub starknet: StarknetDeps<C, G, DBlockT>,
+    /// RPC methods which are not served
+    pub method_filter: Arc<MethodFilter>,
 }
 
 /// Instantiate all full RPC extensions.
@@ -61,44 +72,136 @@ where
     P: TransactionPool<Block = DBlockT> + 'static,
     BE: Backend<DBlockT> + 'static,
 {
-    use mc_rpc::{Starknet, StarknetReadRpcApiServer, StarknetTraceRpcApiServer, StarknetWriteRpcApiServer};
+    use mc_rpc::{
+        DeoxysAdminRpcApiServer, DeoxysRpcApiServer, PathfinderRpcApiServer, Starknet, StarknetReadRpcApiServer,
+        StarknetTraceRpcApiServer, StarknetWriteRpcApiServer,
+    };
     use sc_consensus_manual_seal::rpc::{ManualSeal, ManualSealApiServer};
     use substrate_frame_rpc_system::{System, SystemApiServer};
 
     let mut module = RpcModule::new(());
-    let FullDeps { client, pool, deny_unsafe, starknet: starknet_params, command_sink, graph, .. } = deps;
+    let FullDeps { client, pool, deny_unsafe, starknet: starknet_params, command_sink, graph, method_filter } = deps;
 
-    module.merge(System::new(client.clone(), pool.clone(), deny_unsafe).into_rpc())?;
-    module.merge(StarknetReadRpcApiServer::into_rpc(Starknet::<_, _, _, _, _, DHasherT>::new(
-        client.clone(),
-        starknet_params.overrides.clone(),
-        pool.clone(),
-        graph.clone(),
-        starknet_params.sync_service.clone(),
-        starknet_params.starting_block,
-        starknet_params.genesis_provider.clone(),
-    )))?;
-    module.merge(StarknetWriteRpcApiServer::into_rpc(Starknet::<_, _, _, _, _, DHasherT>::new(
-        client.clone(),
-        starknet_params.overrides.clone(),
-        pool.clone(),
-        graph.clone(),
-        starknet_params.sync_service.clone(),
-        starknet_params.starting_block,
-        starknet_params.genesis_provider.clone(),
-    )))?;
-    module.merge(StarknetTraceRpcApiServer::into_rpc(Starknet::<_, _, _, _, _, DHasherT>::new(
-        client,
-        starknet_params.overrides,
-        pool,
-        graph,
-        starknet_params.sync_service,
-        starknet_params.starting_block,
-        starknet_params.genesis_provider,
-    )))?;
+    method_filter.merge(
+        &mut module,
+        MethodGroup::System,
+        System::new(client.clone(), pool.clone(), deny_unsafe).into_rpc(),
+    )?;
+    method_filter.merge(
+        &mut module,
+        MethodGroup::Read,
+        StarknetReadRpcApiServer::into_rpc(Starknet::<_, _, _, _, _, DHasherT>::new(
+            client.clone(),
+            starknet_params.overrides.clone(),
+            pool.clone(),
+            graph.clone(),
+            starknet_params.genesis_provider.clone(),
+            starknet_params.execution_memory_limit,
+            starknet_params.account_class_whitelist.clone(),
+            starknet_params.rpc_config.clone(),
+            starknet_params.devnet_pool.clone(),
+            starknet_params.method_limiters.clone(),
+            starknet_params.nonce_queue.clone(),
+            starknet_params.forward_pool.clone(),
+        )),
+    )?;
+    method_filter.merge(
+        &mut module,
+        MethodGroup::Write,
+        StarknetWriteRpcApiServer::into_rpc(Starknet::<_, _, _, _, _, DHasherT>::new(
+            client.clone(),
+            starknet_params.overrides.clone(),
+            pool.clone(),
+            graph.clone(),
+            starknet_params.genesis_provider.clone(),
+            starknet_params.execution_memory_limit,
+            starknet_params.account_class_whitelist.clone(),
+            starknet_params.rpc_config.clone(),
+            starknet_params.devnet_pool.clone(),
+            starknet_params.method_limiters.clone(),
+            starknet_params.nonce_queue.clone(),
+            starknet_params.forward_pool.clone(),
+        )),
+    )?;
+    method_filter.merge(
+        &mut module,
+        MethodGroup::Deoxys,
+        DeoxysRpcApiServer::into_rpc(Starknet::<_, _, _, _, _, DHasherT>::new(
+            client.clone(),
+            starknet_params.overrides.clone(),
+            pool.clone(),
+            graph.clone(),
+            starknet_params.genesis_provider.clone(),
+            starknet_params.execution_memory_limit,
+            starknet_params.account_class_whitelist.clone(),
+            starknet_params.rpc_config.clone(),
+            starknet_params.devnet_pool.clone(),
+            starknet_params.method_limiters.clone(),
+            starknet_params.nonce_queue.clone(),
+            starknet_params.forward_pool.clone(),
+        )),
+    )?;
+    method_filter.merge(
+        &mut module,
+        MethodGroup::Pathfinder,
+        PathfinderRpcApiServer::into_rpc(Starknet::<_, _, _, _, _, DHasherT>::new(
+            client.clone(),
+            starknet_params.overrides.clone(),
+            pool.clone(),
+            graph.clone(),
+            starknet_params.genesis_provider.clone(),
+            starknet_params.execution_memory_limit,
+            starknet_params.account_class_whitelist.clone(),
+            starknet_params.rpc_config.clone(),
+            starknet_params.devnet_pool.clone(),
+            starknet_params.method_limiters.clone(),
+            starknet_params.nonce_queue.clone(),
+            starknet_params.forward_pool.clone(),
+        )),
+    )?;
+    if matches!(deny_unsafe, DenyUnsafe::No) {
+        method_filter.merge(
+            &mut module,
+            MethodGroup::Admin,
+            DeoxysAdminRpcApiServer::into_rpc(Starknet::<_, _, _, _, _, DHasherT>::new(
+                client.clone(),
+                starknet_params.overrides.clone(),
+                pool.clone(),
+                graph.clone(),
+                starknet_params.genesis_provider.clone(),
+                starknet_params.execution_memory_limit,
+                starknet_params.account_class_whitelist.clone(),
+                starknet_params.rpc_config.clone(),
+                starknet_params.devnet_pool.clone(),
+                starknet_params.method_limiters.clone(),
+                starknet_params.nonce_queue.clone(),
+                starknet_params.forward_pool.clone(),
+            )),
+        )?;
+    }
+    method_filter.merge(
+        &mut module,
+        MethodGroup::Trace,
+        StarknetTraceRpcApiServer::into_rpc(Starknet::<_, _, _, _, _, DHasherT>::new(
+            client,
+            starknet_params.overrides,
+            pool,
+            graph,
+            starknet_params.genesis_provider,
+            starknet_params.execution_memory_limit,
+            starknet_params.account_class_whitelist.clone(),
+            starknet_params.rpc_config,
+            starknet_params.devnet_pool,
+            starknet_params.method_limiters,
+            starknet_params.nonce_queue,
+            starknet_params.forward_pool,
+        )),
+    )?;
 
     if let Some(command_sink) = command_sink {
-        module.merge(
+        method_filter.merge(
+            &mut module,
+            MethodGroup::ManualSeal,
             // We provide the rpc handler with the sending end of the channel to allow the rpc
             // send EngineCommands to the background block authorship task.
             ManualSeal::new(command_sink).into_rpc(),
@@ -0,0 +1,66 @@
+//! TCP endpoint streaming the progress of the sync as JSON lines, consumed by `deoxys top`.
+//!
+//! Each connection receives the [progress events](mc_sync::progress) published after it was
+//! opened, one JSON object per line, e.g.
+//!
+//! ```text
+//! nc localhost 9947
+//! {"event":"stage","stage":"fetch","block_number":612,"elapsed_ms":431}
+//! {"event":"queue","queue":"fetched","depth":8,"capacity":10}
+//! ```
+//!
+//! Connections going over the [subscriber limits](mc_sync::fanout::SubscriberLimits) of their IP
+//! address receive a single line telling which limit was hit, and are closed, e.g.
+//! `{"code":"identity_subscription_limit","message":"too many subscriptions from ..."}`.
+
+use std::net::SocketAddr;
+
+use mc_sync::fanout::{Client, ConnectionId};
+use mc_sync::progress::{self, ProgressStream};
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Serves the progress events on `address`.
+pub async fn serve(address: SocketAddr) {
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("Failed to listen for sync progress consumers on {address}: {e}");
+            return;
+        }
+    };
+
+    log::info!("📊 Sync progress events listening on {address}");
+    loop {
+        match listener.accept().await {
+            Ok((mut stream, consumer)) => {
+                let client = Client { connection: ConnectionId::next(), identity: consumer.ip() };
+                match progress::subscribe_as(client) {
+                    Ok(events) => {
+                        tokio::spawn(send_events(stream, events));
+                    }
+                    Err(e) => {
+                        tokio::spawn(async move {
+                            let line = format!("{}\n", json!({ "code": e.code(), "message": e.to_string() }));
+                            let _ = stream.write_all(line.as_bytes()).await;
+                        });
+                    }
+                }
+            }
+            Err(e) => log::debug!("Failed to accept a sync progress consumer: {e}"),
+        }
+    }
+}
+
+/// Writes the events to `stream` until the consumer disconnects, or falls behind and is
+/// disconnected.
+async fn send_events(mut stream: TcpStream, mut events: ProgressStream) {
+    while let Some(event) = events.next().await {
+        let mut line = serde_json::to_vec(&event).expect("Progress events should serialize");
+        line.push(b'\n');
+        if stream.write_all(&line).await.is_err() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! TCP endpoint streaming the synced blocks to read replicas, the nodes started with
+//! `--replicate-from`.
+//!
+//! Each replica must first prove it knows the replication secret, then receives the blocks from
+//! the one it requests onwards, read from the database along with their trie deltas, then the new
+//! blocks as they are imported. See [`mc_sync::replication`] for the protocol.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use mc_db::TrieDelta;
+use mc_sync::fanout::{Client, ConnectionId};
+use mc_sync::head::{self, HeadEvent};
+use mc_sync::replication::{read_request, write_block, write_header, write_reorg, ReplicationSecret, SECRET_LEN};
+use sp_blockchain::HeaderBackend;
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::commands::archived_block;
+use crate::service::FullClient;
+
+/// Time given to a replica to answer the challenge once connected.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where the blocks are streamed to read replicas, and the secret they must know.
+#[derive(Debug, Clone)]
+pub struct ReplicationConfig {
+    pub address: SocketAddr,
+    pub secret: ReplicationSecret,
+}
+
+/// Serves the blocks of `client` to the replicas connecting on `config.address`.
+pub async fn serve(config: ReplicationConfig, client: Arc<FullClient>) {
+    let ReplicationConfig { address, secret } = config;
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("Failed to listen for read replicas on {address}: {e}");
+            return;
+        }
+    };
+
+    let secret = Arc::new(secret);
+    log::info!("🔁 Replicating blocks to read replicas on {address}");
+    loop {
+        match listener.accept().await {
+            Ok((stream, replica)) => {
+                tokio::spawn(replicate(stream, replica, Arc::clone(&secret), Arc::clone(&client)));
+            }
+            Err(e) => log::debug!("Failed to accept a read replica: {e}"),
+        }
+    }
+}
+
+/// Streams the blocks requested by `replica` until it disconnects.
+async fn replicate(
+    mut stream: TcpStream,
+    replica: SocketAddr,
+    secret: Arc<ReplicationSecret>,
+    client: Arc<FullClient>,
+) {
+    let challenge: [u8; SECRET_LEN] = rand::random();
+    if write_header(&mut stream, &challenge).await.is_err() {
+        return;
+    }
+    let request = tokio::time::timeout(AUTH_TIMEOUT, read_request(&mut stream, &secret, &challenge));
+    let mut next_block = match request.await {
+        Ok(Ok(next_block)) => next_block.max(1),
+        Ok(Err(e)) => {
+            log::warn!("🔁 Rejected the read replica {replica}: {e}");
+            return;
+        }
+        Err(_) => {
+            log::debug!("Read replica {replica} did not answer the challenge in time");
+            return;
+        }
+    };
+    // subscribed before reading the head, so that no block imported in between is missed
+    let mut events = match head::subscribe_as(Client { connection: ConnectionId::next(), identity: replica.ip() }) {
+        Ok(events) => events,
+        Err(e) => {
+            log::warn!("🔁 Refused the read replica {replica}: {e}");
+            return;
+        }
+    };
+
+    log::info!("🔁 Read replica {replica} connected, replicating from block {next_block}");
+    loop {
+        let best = u64::from(client.info().best_number);
+        while next_block <= best {
+            let block = match archived_block(&client, next_block) {
+                Ok(block) => block,
+                Err(e) => {
+                    log::error!("Failed to read block {next_block} for read replica {replica}: {e}");
+                    return;
+                }
+            };
+            let tries = match TrieDelta::of_block(next_block) {
+                Ok(tries) => tries,
+                Err(e) => {
+                    log::error!("Failed to read the trie delta of block {next_block} for read replica {replica}: {e}");
+                    return;
+                }
+            };
+            if write_block(&mut stream, &block, tries.as_ref()).await.is_err() {
+                log::info!("🔁 Read replica {replica} disconnected");
+                return;
+            }
+            next_block += 1;
+        }
+
+        match events.next().await {
+            Some(HeadEvent::Reorg { common_ancestor, .. }) => {
+                if write_reorg(&mut stream, common_ancestor).await.is_err() {
+                    log::info!("🔁 Read replica {replica} disconnected");
+                    return;
+                }
+                next_block = next_block.min(common_ancestor + 1);
+            }
+            Some(_) => {}
+            None => {
+                log::warn!("🔁 Read replica {replica} fell behind the chain head events, disconnecting it");
+                return;
+            }
+        }
+    }
+}
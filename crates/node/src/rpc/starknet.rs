@@ -2,10 +2,10 @@ use std::sync::Arc;
 
 use mc_db::DeoxysBackend;
 use mc_genesis_data_provider::GenesisProvider;
+use mc_rpc::devnet::DevnetPool;
+use mc_rpc::{AccountClassWhitelist, ForwardPool, MethodLimiters, NonceQueue, RpcConfig};
 use mc_storage::OverrideHandle;
-use sc_network_sync::SyncingService;
 use sp_api::BlockT;
-use sp_runtime::traits::Header as HeaderT;
 
 /// Extra dependencies for Starknet compatibility.
 pub struct StarknetDeps<C, G: GenesisProvider, B: BlockT> {
@@ -15,12 +15,23 @@ pub struct StarknetDeps<C, G: GenesisProvider, B: BlockT> {
     pub madara_backend: Arc<DeoxysBackend>,
     /// Starknet data access overrides.
     pub overrides: Arc<OverrideHandle<B>>,
-    /// The Substrate client sync service.
-    pub sync_service: Arc<SyncingService<B>>,
-    /// The starting block for the syncing.
-    pub starting_block: <<B>::Header as HeaderT>::Number,
     /// The genesis state data provider
     pub genesis_provider: Arc<G>,
+    /// Maximum number of bytes a single execution is allowed to allocate.
+    pub execution_memory_limit: Option<usize>,
+    /// Classes of the accounts allowed to send transactions, all if `None`.
+    pub account_class_whitelist: Option<Arc<AccountClassWhitelist>>,
+    /// Limits of the Starknet RPC methods and servers.
+    pub rpc_config: RpcConfig,
+    /// Transactions waiting for the next local block, in devnet mode.
+    pub devnet_pool: Option<Arc<DevnetPool>>,
+    /// Limiters of the proof and trace methods, shared by the servers of all versions.
+    pub method_limiters: Arc<MethodLimiters>,
+    /// Next nonces of the accounts which forwarded transactions, if they are forwarded in nonce
+    /// order.
+    pub nonce_queue: Option<Arc<NonceQueue>>,
+    /// Transactions waiting to be forwarded once the gateway is back, if they are pooled.
+    pub forward_pool: Option<Arc<ForwardPool>>,
 }
 
 impl<C, G: GenesisProvider, B: BlockT> Clone for StarknetDeps<C, G, B> {
@@ -29,9 +40,14 @@ impl<C, G: GenesisProvider, B: BlockT> Clone for StarknetDeps<C, G, B> {
             client: self.client.clone(),
             madara_backend: self.madara_backend.clone(),
             overrides: self.overrides.clone(),
-            sync_service: self.sync_service.clone(),
-            starting_block: self.starting_block,
             genesis_provider: self.genesis_provider.clone(),
+            execution_memory_limit: self.execution_memory_limit,
+            account_class_whitelist: self.account_class_whitelist.clone(),
+            rpc_config: self.rpc_config.clone(),
+            devnet_pool: self.devnet_pool.clone(),
+            method_limiters: self.method_limiters.clone(),
+            nonce_queue: self.nonce_queue.clone(),
+            forward_pool: self.forward_pool.clone(),
         }
     }
 }
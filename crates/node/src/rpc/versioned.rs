@@ -0,0 +1,209 @@
+//! Server routing the Starknet RPC requests to a version of the spec by path.
+//!
+//! Requests to `/rpc/v0_6` and `/rpc/v0_7` get the responses shaped after the corresponding
+//! version of the spec, see [`RpcVersion`]. Only single requests over HTTP are supported: batches
+//! and subscriptions go to the main RPC server.
+//!
+//! When RPC authentication is enabled, requests to the protected methods must carry a valid token,
+//! see [`super::auth`].
+//!
+//! The server follows the limits of the main RPC server, see [`RpcConfig`]: browsers may only query
+//! it from the allowed origins, and the requests and responses above the maximum size, as well as
+//! the connections above the maximum number, are rejected.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hyper::body::HttpBody;
+use hyper::header::{
+    HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+    ACCESS_CONTROL_MAX_AGE, AUTHORIZATION, CONTENT_TYPE, ORIGIN, VARY,
+};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use jsonrpsee::types::error::{OVERSIZED_RESPONSE_CODE, OVERSIZED_RESPONSE_MSG};
+use jsonrpsee::RpcModule;
+use mc_rpc::{RpcConfig, RpcVersion};
+use serde_json::{json, Value};
+use tokio::sync::Semaphore;
+
+use super::auth::RpcAuth;
+
+/// How long browsers may cache the answer to a CORS preflight request, in seconds.
+const CORS_MAX_AGE: &str = "86400";
+
+/// Serves the methods of `module` on `address`, under a path per spec version, within the limits
+/// of `config`. When `auth` is set, the protected methods are only served to authenticated clients.
+pub async fn serve(address: SocketAddr, module: RpcModule<()>, config: RpcConfig, auth: Option<Arc<RpcAuth>>) {
+    let module = Arc::new(module);
+    let config = Arc::new(config);
+    let connections = Arc::new(Semaphore::new(config.max_connections));
+    let make_service = make_service_fn(move |_| {
+        let module = Arc::clone(&module);
+        let config = Arc::clone(&config);
+        let auth = auth.clone();
+        // the permit is held by the service, and so released when the connection is closed
+        let permit = Arc::clone(&connections).try_acquire_owned().ok();
+        if permit.is_none() {
+            log::debug!("Too many versioned RPC connections, rejecting a new one");
+        }
+        let service = service_fn(move |request| {
+            let module = Arc::clone(&module);
+            let config = Arc::clone(&config);
+            let admitted = permit.is_some();
+            let auth = auth.clone();
+            async move {
+                if !admitted {
+                    return Ok(status(StatusCode::SERVICE_UNAVAILABLE));
+                }
+                handle(module, &config, auth, request).await
+            }
+        });
+        async move { Ok::<_, Infallible>(service) }
+    });
+
+    log::info!("🌐 Versioned Starknet RPC listening on {address} (/rpc/v0_6, /rpc/v0_7)");
+    if let Err(e) = Server::bind(&address).serve(make_service).await {
+        log::error!("Versioned Starknet RPC server failed: {e}");
+    }
+}
+
+async fn handle(
+    module: Arc<RpcModule<()>>,
+    config: &RpcConfig,
+    auth: Option<Arc<RpcAuth>>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let origin = request.headers().get(ORIGIN).cloned();
+    if let Some(origin) = &origin {
+        if !origin.to_str().is_ok_and(|origin| config.allows_origin(origin)) {
+            log::debug!("Versioned RPC request from a disallowed origin: {origin:?}");
+            return Ok(status(StatusCode::FORBIDDEN));
+        }
+    }
+    let response = route(module, config, auth, request).await;
+    Ok(match origin {
+        Some(origin) => with_cors(response, origin),
+        None => response,
+    })
+}
+
+async fn route(
+    module: Arc<RpcModule<()>>,
+    config: &RpcConfig,
+    auth: Option<Arc<RpcAuth>>,
+    request: Request<Body>,
+) -> Response<Body> {
+    let Some(version) = RpcVersion::from_path(request.uri().path()) else {
+        return status(StatusCode::NOT_FOUND);
+    };
+    if request.method() == Method::OPTIONS {
+        return status(StatusCode::NO_CONTENT);
+    }
+    if request.method() != Method::POST {
+        return status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    let authorization = request.headers().get(AUTHORIZATION).and_then(|value| value.to_str().ok()).map(str::to_string);
+
+    let body = match read_body(request.into_body(), config.max_request_body_size).await {
+        Ok(Some(body)) => body,
+        Ok(None) => return status(StatusCode::PAYLOAD_TOO_LARGE),
+        Err(e) => {
+            log::debug!("Failed to read versioned RPC request: {e}");
+            return status(StatusCode::BAD_REQUEST);
+        }
+    };
+    let Ok(body) = std::str::from_utf8(&body) else {
+        return status(StatusCode::BAD_REQUEST);
+    };
+
+    let request = serde_json::from_str::<Value>(body).ok();
+    let method = request.as_ref().and_then(|request| request.get("method")?.as_str().map(str::to_string));
+
+    if let Some(auth) = auth {
+        // requests whose method can't be read are authenticated too, as they could be batches
+        if method.as_deref().map_or(true, |method| auth.is_protected(method)) {
+            if let Err(e) = auth.authorize(authorization.as_deref()) {
+                log::debug!("Unauthorized versioned RPC request: {e}");
+                return status(StatusCode::UNAUTHORIZED);
+            }
+        }
+    }
+
+    let response = match module.raw_json_request(body).await {
+        Ok((response, _)) => response.result,
+        Err(e) => {
+            log::debug!("Invalid versioned RPC request: {e}");
+            return status(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let response = match method {
+        Some(method) if version != RpcVersion::LATEST => adapt(version, &method, response),
+        _ => response,
+    };
+    let response = if response.len() > config.max_response_body_size {
+        let id = request.as_ref().and_then(|request| request.get("id")).cloned().unwrap_or(Value::Null);
+        json!({
+            "jsonrpc": "2.0",
+            "error": { "code": OVERSIZED_RESPONSE_CODE, "message": OVERSIZED_RESPONSE_MSG },
+            "id": id,
+        })
+        .to_string()
+    } else {
+        response
+    };
+
+    Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(response))
+        .expect("Response with a valid header should build")
+}
+
+/// Reads the body of a request, `None` if it is larger than `limit` bytes.
+async fn read_body(mut body: Body, limit: usize) -> Result<Option<Vec<u8>>, hyper::Error> {
+    if body.size_hint().lower() > limit as u64 {
+        return Ok(None);
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if bytes.len() + chunk.len() > limit {
+            return Ok(None);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(Some(bytes))
+}
+
+/// Adds the CORS headers allowing a browser page served from `origin` to read the response.
+fn with_cors(mut response: Response<Body>, origin: HeaderValue) -> Response<Body> {
+    let headers = response.headers_mut();
+    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    headers.insert(ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static("POST, OPTIONS"));
+    headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, HeaderValue::from_static("content-type, authorization"));
+    headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from_static(CORS_MAX_AGE));
+    headers.insert(VARY, HeaderValue::from_static("origin"));
+    response
+}
+
+/// Converts a JSON-RPC response of the latest spec version to the given version.
+fn adapt(version: RpcVersion, method: &str, response: String) -> String {
+    let Ok(mut response_json) = serde_json::from_str::<Value>(&response) else {
+        return response;
+    };
+    match response_json.get_mut("result") {
+        Some(result) => {
+            version.adapt_response(method, result);
+            response_json.to_string()
+        }
+        None => response,
+    }
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
@@ -1,6 +1,7 @@
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
 use std::cell::RefCell;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
@@ -10,11 +11,16 @@ use futures::channel::mpsc;
 use futures::future;
 use futures::future::BoxFuture;
 use futures::prelude::*;
-use mc_db::DeoxysBackend;
+use mc_db::{DbTuning, DeoxysBackend};
 use mc_genesis_data_provider::OnDiskGenesisConfig;
 use mc_mapping_sync::MappingSyncWorker;
+use mc_p2p::{ClientBlockSource, P2pConfig};
+use mc_rpc::devnet::{DevnetConfig, DevnetPool};
+use mc_rpc::{AccountClassWhitelist, ForwardPool, MethodLimiters, NonceQueue, RpcConfig};
 use mc_storage::overrides_handle;
+use mc_sync::alerts::AlertConfig;
 use mc_sync::fetch::fetchers::FetchConfig;
+use mc_sync::gas_oracle::GasOracleConfig;
 use mc_sync::starknet_sync_worker;
 use mp_block::state_update::StateUpdateWrapper;
 use mp_block::DeoxysBlock;
@@ -47,9 +53,15 @@ use sp_runtime::testing::Digest;
 use sp_runtime::traits::Block as BlockT;
 use sp_runtime::DigestItem;
 
+use crate::cold_storage::ColdStorageConfig;
 use crate::genesis_block::MadaraGenesisBlockBuilder;
+use crate::rpc::auth::{RpcAuth, PROTECTED_GROUPS};
+use crate::rpc::method_filter::{MethodFilter, MethodSelector};
+use crate::rpc::replication::ReplicationConfig;
 use crate::rpc::StarknetDeps;
+use crate::snapshots::SnapshotConfig;
 use crate::starknet::{db_config_dir, MadaraBackend};
+use crate::verification::VerificationConfig;
 // Our native executor instance.
 pub struct ExecutorDispatch;
 
@@ -74,7 +86,7 @@ impl sc_executor::NativeExecutionDispatch for ExecutorDispatch {
 }
 
 pub type FullClient = sc_service::TFullClient<DBlockT, RuntimeApi, NativeElseWasmExecutor<ExecutorDispatch>>;
-type FullBackend = sc_service::TFullBackend<DBlockT>;
+pub type FullBackend = sc_service::TFullBackend<DBlockT>;
 type FullSelectChain = sc_consensus::LongestChain<FullBackend, DBlockT>;
 
 type BasicImportQueue = sc_consensus::DefaultImportQueue<DBlockT>;
@@ -89,6 +101,9 @@ pub fn new_partial<BIQ>(
     config: &Configuration,
     build_import_queue: BIQ,
     cache_more_things: bool,
+    storage_trie_shards: Option<usize>,
+    cold_storage: Option<PathBuf>,
+    db_tuning: DbTuning,
     genesis_block: DeoxysBlock,
 ) -> Result<
     sc_service::PartialComponents<
@@ -131,6 +146,17 @@ where
 
     let executor = sc_service::new_native_or_wasm_executor(config);
 
+    // opened first, as it makes sure no other node is running on the same data directory
+    let deoxys_backend = DeoxysBackend::open(
+        &config.database,
+        &db_config_dir(config),
+        cache_more_things,
+        storage_trie_shards,
+        cold_storage,
+        db_tuning,
+    )
+    .map_err(|e| ServiceError::Other(format!("Failed to open the Deoxys database: {e:#}")))?;
+
     let backend = new_db_backend(config.db_config())?;
 
     let genesis_block_builder = MadaraGenesisBlockBuilder::<DBlockT, _, _>::new(
@@ -180,8 +206,6 @@ where
         telemetry.as_ref().map(|x| x.handle()),
     )?;
 
-    let deoxys_backend = DeoxysBackend::open(&config.database, &db_config_dir(config), cache_more_things).unwrap();
-
     let (import_queue, block_import) = build_import_queue(
         client.clone(),
         config,
@@ -272,13 +296,64 @@ where
 /// # Arguments
 ///
 /// - `cache`: whether more information should be cached when storing the block in the database.
+/// - `storage_trie_shards`: the number of RocksDB instances the contract storage tries are sharded
+///   across, when creating the database.
+/// - `cold_storage`: when set, the transaction receipts and trie logs of old blocks are moved to
+///   this cold storage.
+/// - `snapshots`: when set, snapshots of the verified blocks are published on a schedule.
+/// - `db_tuning`: the tuning of the RocksDB instances of the database.
+/// - `execution_memory_limit`: the maximum number of bytes an RPC execution is allowed to allocate.
+/// - `account_class_whitelist`: when set, the RPC only accepts transactions from accounts of the
+///   whitelisted classes.
+/// - `rpc_config`: the limits of the Starknet RPC methods and of the versioned RPC server.
+/// - `method_filter`: the RPC methods which are not served.
+/// - `versioned_rpc_addr`: when set, the Starknet RPC is also served on this address, with a path
+///   per spec version.
+/// - `rpc_jwt_secret`: when set, the path of the secret authenticating the requests to the
+///   protected RPC methods, which are then only served on `versioned_rpc_addr`.
+/// - `head_events_addr`: when set, the chain head is served over plain HTTP on this address.
+/// - `feeder_gateway_addr`: when set, the synced blocks and classes are served on this address in
+///   the format of the feeder gateway.
+/// - `progress_events_addr`: when set, the progress of the sync is streamed over TCP on this
+///   address.
+/// - `replication`: when set, the synced blocks are streamed over TCP to the read replicas which
+///   know the replication secret.
+/// - `p2p_config`: when set, synced blocks are served to other nodes over the Starknet p2p
+///   protocol, and their state diffs and classes may be pulled from the bootnodes.
+/// - `alert_config`: when set, the operator is alerted of critical conditions.
+/// - `gas_oracle_config`: when set, the fees at the tip of the chain are estimated with gas prices
+///   polled from L1.
+/// - `l1_beacon_url`: when set along with `l1_url`, the synced state diffs are checked against the
+///   blobs published on L1, downloaded from this beacon node.
+/// - `devnet_config`: when set, the node produces its own blocks from the transactions submitted
+///   over RPC instead of syncing the chain, and `l1_url` may be omitted.
+#[allow(clippy::too_many_arguments)]
 pub fn new_full(
     config: Configuration,
     sealing: SealingMode,
-    l1_url: Url,
+    l1_url: Option<Url>,
     cache_more_things: bool,
-    fetch_config: FetchConfig,
+    storage_trie_shards: Option<usize>,
+    cold_storage: Option<ColdStorageConfig>,
+    snapshots: Option<SnapshotConfig>,
+    db_tuning: DbTuning,
+    mut fetch_config: FetchConfig,
     genesis_block: DeoxysBlock,
+    execution_memory_limit: Option<usize>,
+    account_class_whitelist: Option<Arc<AccountClassWhitelist>>,
+    rpc_config: RpcConfig,
+    method_filter: Arc<MethodFilter>,
+    versioned_rpc_addr: Option<SocketAddr>,
+    rpc_jwt_secret: Option<PathBuf>,
+    head_events_addr: Option<SocketAddr>,
+    feeder_gateway_addr: Option<SocketAddr>,
+    progress_events_addr: Option<SocketAddr>,
+    replication: Option<ReplicationConfig>,
+    p2p_config: Option<P2pConfig>,
+    alert_config: Option<AlertConfig>,
+    gas_oracle_config: Option<GasOracleConfig>,
+    l1_beacon_url: Option<Url>,
+    devnet_config: Option<DevnetConfig>,
 ) -> Result<TaskManager, ServiceError> {
     let build_import_queue =
         if sealing.is_default() { build_aura_grandpa_import_queue } else { build_manual_seal_import_queue };
@@ -292,7 +367,15 @@ pub fn new_full(
         select_chain,
         transaction_pool,
         other: (block_import, grandpa_link, mut telemetry, madara_backend),
-    } = new_partial(&config, build_import_queue, cache_more_things, genesis_block)?;
+    } = new_partial(
+        &config,
+        build_import_queue,
+        cache_more_things,
+        storage_trie_shards,
+        cold_storage.as_ref().map(|cold_storage| cold_storage.path.clone()),
+        db_tuning,
+        genesis_block,
+    )?;
 
     let mut net_config = sc_network::config::FullNetworkConfiguration::new(&config.network);
 
@@ -352,6 +435,15 @@ pub fn new_full(
     let name = config.network.node_name.clone();
     let enable_grandpa = !config.disable_grandpa && sealing.is_default();
     let prometheus_registry = config.prometheus_registry().cloned();
+    if let Some(Err(e)) = prometheus_registry.as_ref().map(mc_rpc::register_metrics) {
+        log::error!("Failed to register RPC metrics: {e}");
+    }
+    if let Some(Err(e)) = prometheus_registry.as_ref().map(mc_storage::register_metrics) {
+        log::error!("Failed to register class cache metrics: {e}");
+    }
+    if let Some(Err(e)) = prometheus_registry.as_ref().map(crate::verification::register_metrics) {
+        log::error!("Failed to register block verification metrics: {e}");
+    }
     let starting_block = client.info().best_number;
 
     // Channel for the rpc handler to communicate with the authorship task.
@@ -366,15 +458,109 @@ pub fn new_full(
     let overrides = overrides_handle(client.clone());
     let config_dir: PathBuf = config.data_path.clone();
     let genesis_data = OnDiskGenesisConfig(config_dir);
+    let devnet_pool = devnet_config.as_ref().map(|_| Arc::new(DevnetPool::new()));
+    let method_limiters = Arc::new(MethodLimiters::new(&rpc_config.proof_limits, &rpc_config.trace_limits));
+    let nonce_queue = rpc_config.nonce_queue_time.map(|max_wait| Arc::new(NonceQueue::new(max_wait)));
+    let forward_pool = rpc_config.forward_retries.map(|max_attempts| {
+        let (pool, worker) = ForwardPool::new(max_attempts);
+        task_manager.spawn_handle().spawn("starknet-forward-pool", Some(MADARA_TASK_GROUP), worker.run());
+        Arc::new(pool)
+    });
     let starknet_rpc_params = StarknetDeps {
         client: client.clone(),
         madara_backend: madara_backend.clone(),
         overrides: overrides.clone(),
-        sync_service: sync_service.clone(),
-        starting_block,
         genesis_provider: genesis_data.into(),
+        execution_memory_limit,
+        account_class_whitelist,
+        rpc_config,
+        devnet_pool: devnet_pool.clone(),
+        method_limiters,
+        nonce_queue,
+        forward_pool,
     };
 
+    let rpc_auth = match rpc_jwt_secret {
+        Some(path) => {
+            let deps = crate::rpc::FullDeps {
+                client: client.clone(),
+                pool: transaction_pool.clone(),
+                graph: transaction_pool.pool().clone(),
+                deny_unsafe: crate::rpc::DenyUnsafe::No,
+                starknet: starknet_rpc_params.clone(),
+                command_sink: command_sink.clone(),
+                method_filter: Arc::new(MethodFilter::new(
+                    vec![],
+                    PROTECTED_GROUPS.map(MethodSelector::Group).to_vec(),
+                    false,
+                )),
+            };
+            let protected = crate::rpc::create_full(deps).map_err(|e| ServiceError::Other(e.to_string()))?;
+            let auth = RpcAuth::new(&path, &protected).map_err(|e| {
+                ServiceError::Other(format!("Failed to load the RPC JWT secret at {}: {e}", path.display()))
+            })?;
+            Some(Arc::new(auth))
+        }
+        None => None,
+    };
+
+    if let Some(address) = versioned_rpc_addr {
+        let deps = crate::rpc::FullDeps {
+            client: client.clone(),
+            pool: transaction_pool.clone(),
+            graph: transaction_pool.pool().clone(),
+            // the admin methods are only served to authenticated clients
+            deny_unsafe: if rpc_auth.is_some() { crate::rpc::DenyUnsafe::No } else { crate::rpc::DenyUnsafe::Yes },
+            starknet: starknet_rpc_params.clone(),
+            command_sink: None,
+            method_filter: method_filter.clone(),
+        };
+        let module = crate::rpc::create_full(deps).map_err(|e| ServiceError::Other(e.to_string()))?;
+        task_manager.spawn_handle().spawn(
+            "starknet-versioned-rpc",
+            Some(MADARA_TASK_GROUP),
+            crate::rpc::versioned::serve(address, module, starknet_rpc_params.rpc_config.clone(), rpc_auth.clone()),
+        );
+    }
+
+    // the Substrate RPC server can't authenticate requests, the protected methods are left out
+    let method_filter = match rpc_auth {
+        Some(_) => Arc::new(method_filter.without_groups(&PROTECTED_GROUPS)),
+        None => method_filter,
+    };
+
+    if let Some(address) = head_events_addr {
+        task_manager.spawn_handle().spawn(
+            "starknet-head-events",
+            Some(MADARA_TASK_GROUP),
+            crate::rpc::head_events::serve(address),
+        );
+    }
+
+    if let Some(address) = feeder_gateway_addr {
+        task_manager.spawn_handle().spawn(
+            "starknet-feeder-gateway",
+            Some(MADARA_TASK_GROUP),
+            crate::rpc::feeder_gateway::serve(address, client.clone()),
+        );
+    }
+
+    if let Some(address) = progress_events_addr {
+        task_manager.spawn_handle().spawn(
+            "sync-progress-events",
+            Some(MADARA_TASK_GROUP),
+            crate::rpc::progress_events::serve(address),
+        );
+    }
+
+    if let Some(replication) = replication {
+        task_manager.spawn_handle().spawn(
+            "block-replication",
+            Some(MADARA_TASK_GROUP),
+            crate::rpc::replication::serve(replication, client.clone()),
+        );
+    }
+
     let rpc_extensions_builder = {
         let client = client.clone();
         let pool = transaction_pool.clone();
@@ -389,11 +575,14 @@ pub fn new_full(
                 deny_unsafe,
                 starknet: starknet_rpc_params.clone(),
                 command_sink: command_sink.clone(),
+                method_filter: method_filter.clone(),
             };
             crate::rpc::create_full(deps).map_err(Into::into)
         })
     };
 
+    let database_dir = db_config_dir(&config);
+
     let _rpc_handlers = sc_service::spawn_tasks(sc_service::SpawnTasksParams {
         network: network.clone(),
         client: client.clone(),
@@ -424,6 +613,76 @@ pub fn new_full(
         .for_each(|()| future::ready(())),
     );
 
+    if let Some(p2p_config) = p2p_config {
+        let sync = p2p_config.sync;
+        let source = ClientBlockSource::<_, DHasherT>::new(
+            Arc::clone(&client),
+            Arc::clone(&overrides),
+            fetch_config.chain_id.into(),
+        );
+        let (p2p_service, p2p_handle) =
+            mc_p2p::new(p2p_config, Arc::new(source)).map_err(|e| ServiceError::Other(e.to_string()))?;
+        task_manager.spawn_handle().spawn("starknet-p2p", Some(MADARA_TASK_GROUP), p2p_service.run());
+        if sync {
+            fetch_config.p2p = Some(p2p_handle);
+        }
+    }
+
+    if let Some(alert_config) = alert_config {
+        task_manager.spawn_handle().spawn(
+            "starknet-alerts",
+            Some(MADARA_TASK_GROUP),
+            mc_sync::alerts::run(alert_config, database_dir),
+        );
+    }
+
+    if let (Some(gas_oracle_config), Some(l1_url)) = (gas_oracle_config, &l1_url) {
+        task_manager.spawn_handle().spawn(
+            "starknet-gas-oracle",
+            Some(MADARA_TASK_GROUP),
+            mc_sync::gas_oracle::run(l1_url.clone(), gas_oracle_config),
+        );
+    }
+
+    if let (Some(l1_beacon_url), Some(l1_url)) = (l1_beacon_url, &l1_url) {
+        task_manager.spawn_handle().spawn(
+            "starknet-blob-da",
+            Some(MADARA_TASK_GROUP),
+            mc_sync::blob_da::run(l1_url.clone(), l1_beacon_url),
+        );
+    }
+
+    task_manager.spawn_handle().spawn(
+        "starknet-block-verification",
+        Some(MADARA_TASK_GROUP),
+        crate::verification::verify_blocks(
+            client.clone(),
+            Arc::clone(&overrides),
+            VerificationConfig {
+                chain_id: fetch_config.chain_id.into(),
+                hashers: fetch_config.hashers,
+                state_root: fetch_config.verify,
+                execution: fetch_config.verify_execution,
+            },
+        ),
+    );
+
+    if let Some(cold_storage) = cold_storage {
+        task_manager.spawn_handle().spawn(
+            "starknet-cold-storage",
+            Some(MADARA_TASK_GROUP),
+            crate::cold_storage::move_old_blocks(client.clone(), cold_storage.depth),
+        );
+    }
+
+    if let Some(snapshots) = snapshots {
+        task_manager.spawn_handle().spawn(
+            "starknet-snapshots",
+            Some(MADARA_TASK_GROUP),
+            crate::snapshots::publish_snapshots(client.clone(), snapshots),
+        );
+    }
+
     let (block_sender, block_receiver) = tokio::sync::mpsc::channel::<DeoxysBlock>(100);
     let (state_update_sender, state_update_receiver) = tokio::sync::mpsc::channel::<StateUpdateWrapper>(100);
     let (class_sender, class_receiver) = tokio::sync::mpsc::channel::<ClassUpdateWrapper>(100);
@@ -434,13 +693,48 @@ pub fn new_full(
         command_sink: command_sink.unwrap().clone(),
         class_sender,
         overrides,
+        // the blocks are finalized as soon as they are sealed
+        revert_chain: {
+            let backend = backend.clone();
+            Arc::new(move |blocks| {
+                let blocks = u32::try_from(blocks).map_err(|e| e.to_string())?;
+                let (reverted, _) = backend.revert(blocks, true).map_err(|e| e.to_string())?;
+                Ok(reverted.into())
+            })
+        },
     };
 
-    task_manager.spawn_essential_handle().spawn(
-        "starknet-sync-worker",
-        Some("madara"),
-        starknet_sync_worker::sync(fetch_config, sender_config, l1_url, Arc::clone(&client), starting_block),
-    );
+    match (devnet_config, devnet_pool, l1_url) {
+        (Some(devnet_config), Some(devnet_pool), _) => {
+            task_manager.spawn_essential_handle().spawn(
+                "starknet-devnet",
+                Some("madara"),
+                crate::devnet::produce_blocks(
+                    Arc::clone(&client),
+                    devnet_pool,
+                    devnet_config,
+                    fetch_config,
+                    sender_config,
+                    starting_block,
+                ),
+            );
+        }
+        (_, _, Some(l1_url)) => {
+            task_manager.spawn_essential_handle().spawn(
+                "starknet-sync-worker",
+                Some("madara"),
+                starknet_sync_worker::sync(
+                    fetch_config,
+                    sender_config,
+                    l1_url,
+                    Arc::clone(&client),
+                    starting_block,
+                    prometheus_registry.clone(),
+                ),
+            );
+        }
+        _ => return Err(ServiceError::Other("An L1 endpoint is required to sync the chain".to_string())),
+    }
 
     // manual-seal authorship
     if !sealing.is_default() {
@@ -733,7 +1027,14 @@ type ChainOpsResult =
 
 pub fn new_chain_ops(config: &mut Configuration, cache_more_things: bool) -> ChainOpsResult {
     config.keystore = sc_service::config::KeystoreConfig::InMemory;
-    let sc_service::PartialComponents { client, backend, import_queue, task_manager, other, .. } =
-        new_partial::<_>(config, build_aura_grandpa_import_queue, cache_more_things, DeoxysBlock::default())?;
+    let sc_service::PartialComponents { client, backend, import_queue, task_manager, other, .. } = new_partial::<_>(
+        config,
+        build_aura_grandpa_import_queue,
+        cache_more_things,
+        None,
+        None,
+        DbTuning::default(),
+        DeoxysBlock::default(),
+    )?;
     Ok((client, backend, import_queue, task_manager, other.3))
 }
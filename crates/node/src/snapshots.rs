@@ -0,0 +1,568 @@
+//! Scheduled publication of snapshots to an S3 compatible bucket.
+//!
+//! When started with `--snapshot-bucket`, the node publishes a snapshot of the blocks up to the
+//! last verified one, see [`crate::verification`], every `--snapshot-interval` hours. The
+//! credentials of the bucket are read from the `AWS_*` environment variables, `AWS_ENDPOINT`
+//! pointing to other S3 compatible services, GCS included.
+//!
+//! A snapshot is a chain of segments, each holding the blocks published since the previous
+//! snapshot along with the trie deltas of their commits, see [`mc_db::TrieDelta`]. Each
+//! publication only exports the new blocks, and new nodes bootstrap from a snapshot by applying
+//! the deltas to their tries instead of recomputing them, with `import-blocks --input
+//! <segments>...`. The blocks whose trie logs were already pruned when they were published are
+//! exported without their delta, and their tries recomputed on import. Segments are shared by the
+//! snapshots holding them:
+//!
+//! ```text
+//! segments/0000000001-0000650000.dxs   blocks 1..=650000 and their trie deltas
+//! segments/0000650001-0000700000.dxs   blocks 650001..=700000 and their trie deltas
+//! manifests/0000700000.json            {"to":700000,"segments":[{"file":"segments/...","from":1,...},...],...}
+//! latest.json                          the manifest of the last snapshot
+//! ```
+//!
+//! Segments are written and checked locally, then uploaded with multipart uploads, which only
+//! become visible once complete, and the manifests are written last: a manifest only ever lists
+//! complete segments. Uploads interrupted by a crash are left for the lifecycle rules of the bucket
+//! to abort.
+//!
+//! The last block of each segment is checked to still be part of the chain before a new segment
+//! is added: the segments replaced by a reorg are exported again, and the manifests listing them
+//! deleted. Only the last `--snapshot-keep` manifests are kept, along with the segments they list.
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::TryStreamExt;
+use mc_db::{DeoxysBackend, TrieDelta};
+use mc_sync::archive::{ArchiveKind, ArchiveReader, ArchiveWriter, FORMAT_VERSION};
+use mc_sync::import::ArchivedBlock;
+use mp_block::state_update::StateUpdateWrapper;
+use mp_digest_log::STATE_ENGINE_ID;
+use mp_felt::Felt252Wrapper;
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path as ObjectPath;
+use object_store::prefix::PrefixStore;
+use object_store::ObjectStore;
+use sc_cli::{Error, Result};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use sp_blockchain::HeaderBackend;
+use sp_runtime::traits::Header as _;
+use tokio::io::AsyncWriteExt;
+use tokio::time::MissedTickBehavior;
+use url::Url;
+
+use crate::commands::archived_block;
+use crate::service::FullClient;
+
+const SEGMENTS_DIR: &str = "segments";
+const MANIFESTS_DIR: &str = "manifests";
+const LATEST_MANIFEST: &str = "latest.json";
+const PARTIAL_EXTENSION: &str = "partial";
+
+/// Where, how often and how many snapshots are published.
+#[derive(Debug, Clone)]
+pub struct SnapshotConfig {
+    /// The bucket the snapshots are published to, as `s3://<bucket>/<prefix>`.
+    pub bucket: Url,
+    /// The local directory the segments are written to before being uploaded.
+    pub scratch_dir: PathBuf,
+    /// Interval between two snapshots.
+    pub interval: Duration,
+    /// Number of snapshots kept in the bucket.
+    pub keep: usize,
+}
+
+/// A segment of a snapshot, holding blocks along with the trie deltas of their commits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotSegment {
+    /// Key of the segment in the bucket.
+    pub file: String,
+    /// First block of the segment.
+    pub from: u64,
+    /// Last block of the segment, included.
+    pub to: u64,
+    /// Hash of the last block of the segment.
+    pub last_block_hash: String,
+    /// Size of the segment, in bytes.
+    pub size: u64,
+    /// Hex encoded SHA3-256 digest of the segment.
+    pub sha3_256: String,
+}
+
+/// Description of a published snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    /// Version of the archive format of the segments, see [`mc_sync::archive`].
+    pub format_version: u32,
+    /// Last block of the snapshot.
+    pub to: u64,
+    /// The segments to import, in order, from the block following the genesis block to `to`.
+    pub segments: Vec<SnapshotSegment>,
+    /// Unix timestamp of the publication of the snapshot, in seconds.
+    pub created_at: u64,
+}
+
+/// The blocks snapshots are made of.
+trait SnapshotBlocks: Clone + Send + 'static {
+    /// The hash of block `block_number` of the local chain, `None` if it does not hold it.
+    fn block_hash(&self, block_number: u64) -> Result<Option<String>>;
+
+    /// Block `block_number`, along with the trie delta of its commit if the trie logs still hold
+    /// it.
+    fn block(&self, block_number: u64) -> Result<(ArchivedBlock, Option<TrieDelta>)>;
+}
+
+impl SnapshotBlocks for Arc<FullClient> {
+    fn block_hash(&self, block_number: u64) -> Result<Option<String>> {
+        let Some(hash) = u32::try_from(block_number).ok().map(|number| self.hash(number)).transpose()?.flatten() else {
+            return Ok(None);
+        };
+        let Some(header) = self.header(hash)? else {
+            return Ok(None);
+        };
+        let state_update = header
+            .digest()
+            .logs()
+            .iter()
+            .find_map(|item| item.pre_runtime_try_to::<StateUpdateWrapper>(&STATE_ENGINE_ID))
+            .ok_or_else(|| Error::Input(format!("Block {block_number} holds no state update")))?;
+        Ok(state_update.block_hash.map(|block_hash| block_hash.to_string()))
+    }
+
+    fn block(&self, block_number: u64) -> Result<(ArchivedBlock, Option<TrieDelta>)> {
+        let tries = TrieDelta::of_block(block_number).map_err(|e| Error::Application(Box::new(e)))?;
+        Ok((archived_block(self, block_number)?, tries))
+    }
+}
+
+/// Opens the bucket at `url`, of the form `s3://<bucket>/<prefix>`.
+pub fn open_bucket(url: &Url) -> Result<PrefixStore<object_store::aws::AmazonS3>> {
+    let bucket = match (url.scheme(), url.host_str()) {
+        ("s3", Some(bucket)) => bucket,
+        _ => return Err(Error::Input(format!("Expected a bucket of the form s3://<bucket>/<prefix>, got {url}"))),
+    };
+    let store = AmazonS3Builder::from_env().with_bucket_name(bucket).build().map_err(bucket_error)?;
+    Ok(PrefixStore::new(store, url.path().trim_matches('/')))
+}
+
+/// Publishes a snapshot of the verified blocks every `config.interval`, when new blocks were
+/// verified since the last one.
+pub async fn publish_snapshots(client: Arc<FullClient>, config: SnapshotConfig) {
+    let store = match open_bucket(&config.bucket) {
+        Ok(store) => store,
+        Err(e) => {
+            log::error!("Failed to open the snapshot bucket {}: {e}", config.bucket);
+            return;
+        }
+    };
+    if let Err(e) = clean_scratch_dir(&config.scratch_dir) {
+        log::error!("Failed to prepare the snapshot directory {}: {e}", config.scratch_dir.display());
+        return;
+    }
+
+    let mut interval = tokio::time::interval(config.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        let verified = match DeoxysBackend::meta().verified_block() {
+            Ok(Some(verified)) => verified,
+            Ok(None) => continue,
+            Err(e) => {
+                log::error!("Failed to read the last verified block: {e}");
+                continue;
+            }
+        };
+        // the watermark is rewound by the verification after a reorg
+        let canonical_hash = |number| u32::try_from(number).ok().and_then(|number| client.hash(number).ok().flatten());
+        if !verified.is_canonical(canonical_hash) {
+            continue;
+        }
+
+        match publish_snapshot(&store, &config, client.clone(), verified.number).await {
+            Ok(Some(manifest)) => {
+                log::info!("📸 Published the snapshot of the blocks up to {} to {}", manifest.to, config.bucket)
+            }
+            Ok(None) => {}
+            Err(e) => log::error!("Failed to publish the snapshot of the blocks up to {}: {e}", verified.number),
+        }
+    }
+}
+
+/// Publishes the snapshot of the blocks up to `to`, unless the last snapshot already ends there,
+/// then deletes the snapshots beyond `config.keep`.
+async fn publish_snapshot<B: SnapshotBlocks>(
+    store: &dyn ObjectStore,
+    config: &SnapshotConfig,
+    blocks: B,
+    to: u64,
+) -> Result<Option<SnapshotManifest>> {
+    let mut segments =
+        read_manifest(store, &ObjectPath::from(LATEST_MANIFEST)).await?.map_or(Vec::new(), |m| m.segments);
+    while let Some(last) = segments.last() {
+        if blocks.block_hash(last.to)?.as_ref() == Some(&last.last_block_hash) {
+            break;
+        }
+        log::warn!("📸 The blocks of the snapshot segment {} were reorganized away, exporting them again", last.file);
+        segments.pop();
+    }
+    // the genesis block is part of the chain spec
+    let from = segments.last().map_or(1, |last| last.to + 1);
+    if from > to {
+        return Ok(None);
+    }
+
+    let partial = config.scratch_dir.join(format!("{from:010}-{to:010}.{PARTIAL_EXTENSION}"));
+    let parent_hash = segments.last().map(|last| last.last_block_hash.clone());
+    let (segment_blocks, segment_path) = (blocks.clone(), partial.clone());
+    let segment = tokio::task::spawn_blocking(move || {
+        write_segment(&segment_blocks, &segment_path, from, to, parent_hash.as_deref())
+    })
+    .await
+    .map_err(|e| Error::Application(Box::new(e)))?;
+    let segment = match segment {
+        Ok(segment) => segment,
+        Err(e) => {
+            remove_if_exists(&partial)?;
+            return Err(e);
+        }
+    };
+
+    let location = ObjectPath::from(segment.file.as_str());
+    let uploaded = upload(store, &location, &partial).await;
+    remove_if_exists(&partial)?;
+    uploaded?;
+
+    // a reorg while the segment was exported, the segment is deleted with the next expiration
+    if blocks.block_hash(to)?.as_ref() != Some(&segment.last_block_hash) {
+        log::warn!("📸 Block {to} was reorganized away while being published, skipping its snapshot");
+        return Ok(None);
+    }
+
+    segments.push(segment);
+    let manifest = SnapshotManifest {
+        format_version: FORMAT_VERSION,
+        to,
+        segments,
+        created_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs()),
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest).map_err(|e| Error::Application(Box::new(e)))?;
+    store.put(&manifest_path(to), manifest_json.clone().into()).await.map_err(bucket_error)?;
+    store.put(&ObjectPath::from(LATEST_MANIFEST), manifest_json.into()).await.map_err(bucket_error)?;
+
+    expire_snapshots(store, &manifest, config.keep).await?;
+    Ok(Some(manifest))
+}
+
+/// Writes the segment of the blocks `from..=to` to `path` and reads it back, checking that the
+/// first block follows `parent_hash`, the hash of the last block of the previous segment.
+fn write_segment<B: SnapshotBlocks>(
+    blocks: &B,
+    path: &Path,
+    from: u64,
+    to: u64,
+    parent_hash: Option<&str>,
+) -> Result<SnapshotSegment> {
+    let mut archive = ArchiveWriter::create(path, ArchiveKind::Snapshot)?;
+    let mut last_block_hash = None;
+    for block_number in from..=to {
+        let (block, tries) = blocks.block(block_number)?;
+        if block_number == from {
+            let parent = Felt252Wrapper::from(block.block.header().parent_block_hash).to_string();
+            if parent_hash.is_some_and(|parent_hash| parent_hash != parent) {
+                return Err(Error::Input(format!(
+                    "Block {from} does not follow the last block of the previous snapshot segment"
+                )));
+            }
+        }
+        last_block_hash = block.state_update.block_hash;
+        archive.write(&(block, tries))?;
+    }
+    archive.finish()?.get_ref().sync_all()?;
+    check_segment(path, from, to)?;
+
+    Ok(SnapshotSegment {
+        file: format!("{SEGMENTS_DIR}/{from:010}-{to:010}.dxs"),
+        from,
+        to,
+        last_block_hash: last_block_hash.ok_or_else(|| Error::Input(format!("Block {to} has no hash")))?.to_string(),
+        size: fs::metadata(path)?.len(),
+        sha3_256: hex::encode(sha3_256(path)?),
+    })
+}
+
+/// Uploads the file at `path` to `location`, in parts.
+async fn upload(store: &dyn ObjectStore, location: &ObjectPath, path: &Path) -> Result<()> {
+    let (multipart_id, mut writer) = store.put_multipart(location).await.map_err(bucket_error)?;
+    let uploaded = async {
+        let mut file = tokio::fs::File::open(path).await?;
+        tokio::io::copy(&mut file, &mut writer).await?;
+        writer.shutdown().await
+    }
+    .await;
+
+    if let Err(e) = uploaded {
+        // the parts uploaded so far are not visible, but are stored until the upload is aborted
+        if let Err(e) = store.abort_multipart(location, &multipart_id).await {
+            log::warn!("Failed to abort the upload of {location}: {e}");
+        }
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Deletes the manifests beyond the last `keep` ones and the ones listing segments which were
+/// reorganized away, then the segments no manifest lists anymore.
+async fn expire_snapshots(store: &dyn ObjectStore, latest: &SnapshotManifest, keep: usize) -> Result<()> {
+    let mut manifests: Vec<ObjectPath> = store
+        .list(Some(&ObjectPath::from(MANIFESTS_DIR)))
+        .map_ok(|object| object.location)
+        .try_collect()
+        .await
+        .map_err(bucket_error)?;
+    // the manifests are named after their last block, zero padded
+    manifests.sort_unstable();
+
+    let mut kept = Vec::new();
+    for location in manifests.into_iter().rev() {
+        let manifest = read_manifest(store, &location).await?;
+        let on_chain = manifest.as_ref().is_some_and(|manifest| latest.segments.starts_with(&manifest.segments));
+        if on_chain && kept.len() < keep.max(1) {
+            kept.extend(manifest);
+        } else {
+            store.delete(&location).await.map_err(bucket_error)?;
+            log::debug!("📸 Deleted the expired snapshot manifest {location}");
+        }
+    }
+
+    let listed: HashSet<&str> =
+        kept.iter().flat_map(|manifest| &manifest.segments).map(|segment| segment.file.as_str()).collect();
+    let segments: Vec<ObjectPath> = store
+        .list(Some(&ObjectPath::from(SEGMENTS_DIR)))
+        .map_ok(|object| object.location)
+        .try_collect()
+        .await
+        .map_err(bucket_error)?;
+    for location in segments {
+        if !listed.contains(location.as_ref()) {
+            store.delete(&location).await.map_err(bucket_error)?;
+            log::debug!("📸 Deleted the expired snapshot segment {location}");
+        }
+    }
+    Ok(())
+}
+
+async fn read_manifest(store: &dyn ObjectStore, location: &ObjectPath) -> Result<Option<SnapshotManifest>> {
+    let bytes = match store.get(location).await {
+        Ok(object) => object.bytes().await.map_err(bucket_error)?,
+        Err(object_store::Error::NotFound { .. }) => return Ok(None),
+        Err(e) => return Err(bucket_error(e)),
+    };
+    serde_json::from_slice(&bytes).map(Some).map_err(|e| Error::Application(Box::new(e)))
+}
+
+fn manifest_path(to: u64) -> ObjectPath {
+    ObjectPath::from(format!("{MANIFESTS_DIR}/{to:010}.json"))
+}
+
+/// Creates the directory the segments are written to, deleting the segments left partial by an
+/// interrupted publication.
+fn clean_scratch_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == PARTIAL_EXTENSION) {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads back the segment at `path`, checking that it holds the blocks `from..=to` in order.
+fn check_segment(path: &Path, from: u64, to: u64) -> Result<()> {
+    let mut archive = ArchiveReader::open(path, ArchiveKind::Snapshot)?;
+    let mut expected = from;
+    while let Some((archived, _)) = archive.next::<(ArchivedBlock, Option<TrieDelta>)>()? {
+        let block_number = archived.block.header().block_number;
+        if block_number != expected {
+            return Err(Error::Input(format!("Expected block {expected} in the snapshot, found {block_number}")));
+        }
+        expected += 1;
+    }
+    if expected != to + 1 {
+        return Err(Error::Input(format!("The snapshot ends at block {}, expected {to}", expected - 1)));
+    }
+    Ok(())
+}
+
+fn sha3_256(path: &Path) -> io::Result<[u8; 32]> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha3_256::new();
+    let mut buffer = vec![0u8; 1 << 20];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => return Ok(hasher.finalize().into()),
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn bucket_error(e: object_store::Error) -> Error {
+    Error::Application(Box::new(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use mp_block::state_update::StateDiffWrapper;
+    use mp_block::{DeoxysBlock, Header};
+    use mp_contract::class::ClassUpdateWrapper;
+    use object_store::memory::InMemory;
+    use starknet_api::hash::StarkFelt;
+
+    use super::*;
+
+    /// A chain of `tip` blocks, whose blocks from `fork` onwards were reorganized.
+    #[derive(Clone)]
+    struct TestChain {
+        tip: u64,
+        fork: u64,
+    }
+
+    impl TestChain {
+        fn hash(&self, block_number: u64) -> StarkFelt {
+            StarkFelt::from(u128::from(block_number) + if block_number >= self.fork { 1000 } else { 0 })
+        }
+    }
+
+    impl SnapshotBlocks for TestChain {
+        fn block_hash(&self, block_number: u64) -> Result<Option<String>> {
+            Ok((block_number <= self.tip).then(|| Felt252Wrapper::from(self.hash(block_number)).to_string()))
+        }
+
+        fn block(&self, block_number: u64) -> Result<(ArchivedBlock, Option<TrieDelta>)> {
+            let header = Header { block_number, parent_block_hash: self.hash(block_number - 1), ..Default::default() };
+            let state_update = StateUpdateWrapper {
+                block_hash: Some(self.hash(block_number).into()),
+                new_root: None,
+                old_root: Felt252Wrapper::ZERO,
+                state_diff: StateDiffWrapper {
+                    storage_diffs: vec![],
+                    deployed_contracts: vec![],
+                    old_declared_contracts: vec![],
+                    declared_classes: vec![],
+                    nonces: vec![],
+                    replaced_classes: vec![],
+                },
+            };
+            let block = DeoxysBlock::new(header, vec![], vec![]);
+            Ok((
+                ArchivedBlock { block, state_update, class_update: ClassUpdateWrapper(vec![]), receipts: vec![] },
+                None,
+            ))
+        }
+    }
+
+    fn config(name: &str) -> SnapshotConfig {
+        let scratch_dir = std::env::temp_dir().join(format!("deoxys-snapshots-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&scratch_dir);
+        clean_scratch_dir(&scratch_dir).unwrap();
+        SnapshotConfig {
+            bucket: Url::parse("s3://bucket").unwrap(),
+            scratch_dir,
+            interval: Duration::from_secs(3600),
+            keep: 2,
+        }
+    }
+
+    async fn list(store: &InMemory, dir: &str) -> Vec<String> {
+        let mut locations: Vec<String> = store
+            .list(Some(&ObjectPath::from(dir)))
+            .map_ok(|object| object.location.to_string())
+            .try_collect()
+            .await
+            .unwrap();
+        locations.sort();
+        locations
+    }
+
+    fn segment_files(manifest: &SnapshotManifest) -> Vec<&str> {
+        manifest.segments.iter().map(|segment| segment.file.as_str()).collect()
+    }
+
+    #[test]
+    fn snapshots_only_export_the_new_blocks_and_follow_reorgs() {
+        let config = config("publish");
+        let store = InMemory::new();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let publish = |chain: TestChain, to: u64| runtime.block_on(publish_snapshot(&store, &config, chain, to));
+
+        let manifest = publish(TestChain { tip: 3, fork: u64::MAX }, 3).unwrap().unwrap();
+        assert_eq!(segment_files(&manifest), ["segments/0000000001-0000000003.dxs"]);
+        assert!(publish(TestChain { tip: 3, fork: u64::MAX }, 3).unwrap().is_none());
+
+        let manifest = publish(TestChain { tip: 5, fork: u64::MAX }, 5).unwrap().unwrap();
+        assert_eq!(
+            segment_files(&manifest),
+            ["segments/0000000001-0000000003.dxs", "segments/0000000004-0000000005.dxs"]
+        );
+
+        // block 5 is replaced, so its segment is exported again, and the snapshot holding it deleted
+        let manifest = publish(TestChain { tip: 6, fork: 5 }, 6).unwrap().unwrap();
+        assert_eq!(
+            segment_files(&manifest),
+            ["segments/0000000001-0000000003.dxs", "segments/0000000004-0000000006.dxs"]
+        );
+        assert_eq!(
+            runtime.block_on(list(&store, MANIFESTS_DIR)),
+            ["manifests/0000000003.json", "manifests/0000000006.json"]
+        );
+        assert_eq!(runtime.block_on(list(&store, SEGMENTS_DIR)), segment_files(&manifest));
+        let latest = runtime.block_on(read_manifest(&store, &ObjectPath::from(LATEST_MANIFEST))).unwrap();
+        assert_eq!(latest, Some(manifest));
+
+        // the snapshots beyond the last two are deleted, along with the segments only they list
+        publish(TestChain { tip: 7, fork: 5 }, 7).unwrap().unwrap();
+        assert_eq!(
+            runtime.block_on(list(&store, MANIFESTS_DIR)),
+            ["manifests/0000000006.json", "manifests/0000000007.json"]
+        );
+        assert_eq!(fs::read_dir(&config.scratch_dir).unwrap().count(), 0);
+
+        fs::remove_dir_all(&config.scratch_dir).unwrap();
+    }
+
+    #[test]
+    fn segments_are_checked_when_written_and_read_back() {
+        let config = config("check");
+        let path = config.scratch_dir.join("segment.partial");
+        let chain = TestChain { tip: 5, fork: u64::MAX };
+
+        let segment = write_segment(&chain, &path, 4, 5, chain.block_hash(3).unwrap().as_deref()).unwrap();
+        assert_eq!(segment.last_block_hash, chain.block_hash(5).unwrap().unwrap());
+        assert!(check_segment(&path, 4, 5).is_ok());
+        assert!(check_segment(&path, 4, 6).is_err());
+        assert!(check_segment(&path, 3, 5).is_err());
+
+        // the first block must follow the previous segment
+        assert!(write_segment(&chain, &path, 4, 5, chain.block_hash(2).unwrap().as_deref()).is_err());
+
+        // segments left partial by an interrupted publication are deleted
+        fs::write(config.scratch_dir.join("kept.dxs"), b"").unwrap();
+        clean_scratch_dir(&config.scratch_dir).unwrap();
+        let files: Vec<_> =
+            fs::read_dir(&config.scratch_dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(files, ["kept.dxs"]);
+
+        fs::remove_dir_all(&config.scratch_dir).unwrap();
+    }
+}
@@ -0,0 +1,233 @@
+//! Verification of the imported blocks, separately from their import.
+//!
+//! Blocks are served as soon as they are imported, while their content is only checked afterwards:
+//! [`verify_blocks`] goes through the imported blocks in order, checking their counts, commitments
+//! and hash against their header with [`verify_block`], their state root against the tries, and
+//! optionally their receipts against the re-execution of their transactions. It advances the last
+//! verified block, stored in the meta db along with its hash. It is returned by
+//! `deoxys_getVerificationStatus` along with the last imported block, exported as
+//! `deoxys_verified_block`, and the RPC only serves the blocks up to it when started with
+//! `--rpc-only-verified`.
+//!
+//! The state roots of the blocks before a trusted checkpoint are not checked, the tries only
+//! holding the state of some of them, see [`mc_sync::checkpoint`]: the state root of the
+//! checkpoint block covers their state diffs.
+//!
+//! After a reorg, the last verified block is rewound to the last block shared by the verified and
+//! the new chains, and the blocks of the new chain are verified from there.
+//!
+//! The verification stops at the first block which does not match, raising an alert: the blocks
+//! after it stay unverified until the node is restarted.
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use mc_db::{DeoxysBackend, VerifiedBlock};
+use mc_rpc::reexecution::BlockReExecutor;
+use mc_storage::OverrideHandle;
+use mc_sync::alerts::{self, Alert};
+use mc_sync::commitments::hashers::CommitmentHashers;
+use mc_sync::commitments::lib::state_root_at;
+use mc_sync::import::{verify_block, ArchivedBlock};
+use mp_felt::Felt252Wrapper;
+use mp_types::block::{DBlockT, DHashT, DHasherT};
+use prometheus_endpoint::{register, Gauge, PrometheusError, Registry, U64};
+use sp_blockchain::HeaderBackend;
+use starknet_api::hash::StarkHash;
+use tokio::time::MissedTickBehavior;
+
+use crate::commands::archived_block;
+use crate::service::{FullBackend, FullClient};
+
+/// Interval between two checks for newly imported blocks.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+static METRICS: OnceLock<Gauge<U64>> = OnceLock::new();
+
+/// Registers the block verification metrics in `registry`.
+pub fn register_metrics(registry: &Registry) -> Result<(), PrometheusError> {
+    let verified_block = register(
+        Gauge::new("deoxys_verified_block", "Last block whose content was verified against its header")?,
+        registry,
+    )?;
+    let _ = METRICS.set(verified_block);
+    Ok(())
+}
+
+/// What the verification checks besides the consistency of the blocks with their header.
+#[derive(Debug, Clone, Copy)]
+pub struct VerificationConfig {
+    pub chain_id: Felt252Wrapper,
+    pub hashers: CommitmentHashers,
+    /// Whether the state diffs are committed to the tries, whose roots are then checked.
+    pub state_root: bool,
+    /// Whether the transactions are executed again, and checked against their receipts.
+    pub execution: bool,
+}
+
+/// Why a block could not be verified.
+enum Failure {
+    /// The block does not match, the verification stops.
+    Mismatch(Alert),
+    /// The block could not be checked, it is tried again at the next poll.
+    Retry(String),
+}
+
+/// Verifies the imported blocks as they are imported, from the one following the last verified
+/// block.
+pub async fn verify_blocks(
+    client: Arc<FullClient>,
+    overrides: Arc<OverrideHandle<DBlockT>>,
+    config: VerificationConfig,
+) {
+    let meta = DeoxysBackend::meta();
+    let mut verified = match meta.verified_block() {
+        Ok(verified) => verified,
+        Err(e) => {
+            log::error!("Failed to read the last verified block: {e}");
+            return;
+        }
+    };
+    set_gauge(verified);
+    let re_executor = config.execution.then(|| Arc::new(BlockReExecutor::new(Arc::clone(&client), overrides)));
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        let imported = u64::from(client.info().best_number);
+
+        // blocks replaced by a reorg are verified again, from the last block shared with the new chain
+        if let Some(last) = verified.filter(|last| !last.is_canonical(|number| canonical_hash(&client, number))) {
+            let rewound =
+                last.common_ancestor(|number| canonical_hash(&client, number), |hash| parent_hash(&client, hash));
+            let stored = match rewound {
+                Some(rewound) => meta.set_verified_block(rewound),
+                None => meta.clear_verified_block(),
+            };
+            if let Err(e) = stored {
+                log::error!("Failed to store the last verified block: {e}");
+                continue;
+            }
+            log::info!(
+                "🔍 Verified blocks replaced by a reorg, verifying again from block {}",
+                rewound.map_or(1, |rewound| rewound.number + 1)
+            );
+            verified = rewound;
+            set_gauge(verified);
+        }
+
+        // the tries hold no state root for most of the blocks before a trusted checkpoint
+        let trusted_checkpoint = match meta.trusted_checkpoint() {
+            Ok(trusted_checkpoint) => trusted_checkpoint,
+            Err(e) => {
+                log::error!("Failed to read the trusted checkpoint: {e}");
+                continue;
+            }
+        };
+
+        // the genesis block is part of the chain spec
+        for block_number in verified.map_or(1, |verified| verified.number + 1)..=imported {
+            let Some(hash) = canonical_hash(&client, block_number) else {
+                break;
+            };
+            let block = match archived_block(&client, block_number) {
+                Ok(block) => block,
+                Err(e) => {
+                    log::error!("Failed to read block {block_number} for its verification: {e}");
+                    break;
+                }
+            };
+            let re_executor = re_executor.clone();
+            let config = VerificationConfig {
+                state_root: config.state_root
+                    && trusted_checkpoint.map_or(true, |trusted_checkpoint| block_number >= trusted_checkpoint),
+                ..config
+            };
+            let verification =
+                tokio::task::spawn_blocking(move || verify(&block, hash, config, re_executor.as_deref()));
+            match verification.await.expect("join error") {
+                Ok(()) => {}
+                Err(Failure::Mismatch(alert)) => {
+                    log::error!("🔍 Block {block_number} does not match, stopping the verification: {alert}");
+                    alerts::raise(alert);
+                    return;
+                }
+                Err(Failure::Retry(reason)) => {
+                    log::error!("Failed to verify block {block_number}: {reason}");
+                    break;
+                }
+            }
+
+            let block = VerifiedBlock { number: block_number, hash };
+            if let Err(e) = meta.set_verified_block(block) {
+                log::error!("Failed to store the last verified block: {e}");
+                break;
+            }
+            verified = Some(block);
+            set_gauge(verified);
+            if block_number % 10_000 == 0 {
+                log::info!("🔍 Verified blocks up to {block_number}");
+            }
+        }
+    }
+}
+
+/// Checks the content of `archived`, wrapped in the Substrate block `hash`, against its header,
+/// then its state root and its execution as set in `config`.
+fn verify(
+    archived: &ArchivedBlock,
+    hash: DHashT,
+    config: VerificationConfig,
+    re_executor: Option<&BlockReExecutor<FullClient, FullBackend>>,
+) -> Result<(), Failure> {
+    let header = archived.block.header();
+    let block_number = header.block_number;
+    let mismatch = |reason: String| Failure::Mismatch(Alert::VerificationFailed { block_number, reason });
+
+    verify_block::<DHasherT>(archived, config.chain_id, config.hashers).map_err(|e| mismatch(e.to_string()))?;
+
+    if config.state_root {
+        let computed: StarkHash = state_root_at(block_number, config.hashers)
+            .map_err(|e| Failure::Retry(format!("failed to read the state root from the tries: {e}")))?
+            .into();
+        let fetched = header.global_state_root;
+        if computed != fetched {
+            return Err(Failure::Mismatch(Alert::StateRootMismatch { block_number, computed, fetched }));
+        }
+    }
+
+    if let Some(re_executor) = re_executor {
+        re_executor.check_block::<DHasherT>(&archived.block, hash, &archived.receipts, config.chain_id).map_err(
+            |e| {
+                if e.is_mismatch() { mismatch(e.to_string()) } else { Failure::Retry(e.to_string()) }
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// The hash of the Substrate block at height `number` in the local chain.
+fn canonical_hash(client: &FullClient, number: u64) -> Option<DHashT> {
+    let number = u32::try_from(number).ok()?;
+    client.hash(number).unwrap_or_else(|e| {
+        log::error!("Failed to read the hash of block {number}: {e}");
+        None
+    })
+}
+
+/// The hash of the parent of the Substrate block `hash`.
+fn parent_hash(client: &FullClient, hash: DHashT) -> Option<DHashT> {
+    match client.header(hash) {
+        Ok(header) => header.map(|header| header.parent_hash),
+        Err(e) => {
+            log::error!("Failed to read the header of block {hash}: {e}");
+            None
+        }
+    }
+}
+
+fn set_gauge(verified: Option<VerifiedBlock>) {
+    if let Some(gauge) = METRICS.get() {
+        gauge.set(verified.map_or(0, |verified| verified.number));
+    }
+}
@@ -2,8 +2,10 @@ use core::num::NonZeroU128;
 
 use blockifier::blockifier::block::{BlockInfo, GasPrices};
 use blockifier::context::{BlockContext, ChainInfo, FeeTokenAddresses};
+#[cfg(not(feature = "std"))]
 use blockifier::versioned_constants::VersionedConstants;
 use mp_felt::Felt252Wrapper;
+use mp_hashers::poseidon::PoseidonHasher;
 use mp_hashers::HasherT;
 use sp_core::U256;
 use starknet_api::block::{BlockNumber, BlockTimestamp};
@@ -73,6 +75,12 @@ pub struct Header {
     pub l1_da_mode: L1DataAvailabilityMode,
     /// Extraneous data that might be useful for running transactions
     pub extra_data: Option<U256>,
+    /// A commitment to the receipts of the transactions, from Starknet 0.13.2
+    pub receipt_commitment: Option<StarkHash>,
+    /// A commitment to the state diff of this block, from Starknet 0.13.2
+    pub state_diff_commitment: Option<StarkHash>,
+    /// The number of entries of the state diff of this block, from Starknet 0.13.2
+    pub state_diff_length: Option<u64>,
 }
 
 impl Header {
@@ -93,6 +101,9 @@ impl Header {
         gas_prices: Option<GasPrices>,
         l1_da_mode: L1DataAvailabilityMode,
         extra_data: Option<U256>,
+        receipt_commitment: Option<StarkHash>,
+        state_diff_commitment: Option<StarkHash>,
+        state_diff_length: Option<u64>,
     ) -> Self {
         Self {
             parent_block_hash,
@@ -108,11 +119,22 @@ impl Header {
             l1_gas_price: gas_prices,
             l1_da_mode,
             extra_data,
+            receipt_commitment,
+            state_diff_commitment,
+            state_diff_length,
         }
     }
 
     /// Converts to a blockifier BlockContext
+    ///
+    /// The execution constants are those of the Starknet version of the block, see
+    /// [`crate::versioned_constants`].
     pub fn into_block_context(&self, fee_token_addresses: FeeTokenAddresses, chain_id: ChainId) -> BlockContext {
+        #[cfg(feature = "std")]
+        let versioned_constants = crate::versioned_constants::for_protocol_version(&self.protocol_version);
+        #[cfg(not(feature = "std"))]
+        let versioned_constants = VersionedConstants::latest_constants();
+
         BlockContext::new_unchecked(
             &BlockInfo {
                 block_number: BlockNumber(self.block_number),
@@ -129,15 +151,44 @@ impl Header {
                 use_kzg_da: false,
             },
             &ChainInfo { chain_id, fee_token_addresses },
-            // TODO
-            // I'm clueless on what those values should be
-            VersionedConstants::latest_constants(),
+            versioned_constants,
         )
     }
 
     /// Compute the hash of the header.
+    ///
+    /// Headers holding the receipt and state diff commitments, those of the blocks of Starknet
+    /// 0.13.2 and later, are always hashed with Poseidon.
     pub fn hash<H: HasherT>(&self) -> Felt252Wrapper {
-        if self.block_number >= 833 {
+        if let (Some(receipt_commitment), Some(state_diff_commitment), Some(state_diff_length)) =
+            (self.receipt_commitment, self.state_diff_commitment, self.state_diff_length)
+        {
+            // Computes the block hash for blocks generated after Starknet 0.13.2
+            let gas_price = |price: fn(&GasPrices) -> NonZeroU128| {
+                self.l1_gas_price.as_ref().map_or(Felt252Wrapper::ZERO, |prices| price(prices).get().into())
+            };
+            let data: &[Felt252Wrapper] = &[
+                Felt252Wrapper::try_from(&b"STARKNET_BLOCK_HASH0"[..]).unwrap(),
+                self.block_number.into(),
+                self.global_state_root.into(),
+                self.sequencer_address.into(),
+                self.block_timestamp.into(),
+                self.concatenated_counts(state_diff_length),
+                state_diff_commitment.into(),
+                self.transaction_commitment.into(),
+                self.event_commitment.into(),
+                receipt_commitment.into(),
+                gas_price(|prices| prices.eth_l1_gas_price),
+                gas_price(|prices| prices.strk_l1_gas_price),
+                gas_price(|prices| prices.eth_l1_data_gas_price),
+                gas_price(|prices| prices.strk_l1_data_gas_price),
+                self.protocol_version,
+                Felt252Wrapper::ZERO, // reserved: extra data
+                self.parent_block_hash.into(),
+            ];
+
+            PoseidonHasher::compute_hash_on_wrappers(data)
+        } else if self.block_number >= 833 {
             // Computes the block hash for blocks generated after Cairo 0.7.0
             let data: &[Felt252Wrapper] = &[
                 self.block_number.into(),           // block number
@@ -174,4 +225,18 @@ impl Header {
             H::compute_hash_on_wrappers(data)
         }
     }
+
+    /// Packs the transaction, event and state diff counts and the data availability mode in a
+    /// single felt, as hashed from Starknet 0.13.2: 64 bits per count, followed by a bit set when
+    /// the state diff is posted as a blob.
+    fn concatenated_counts(&self, state_diff_length: u64) -> Felt252Wrapper {
+        let mut counts = [0u8; 32];
+        counts[..8].copy_from_slice(&(self.transaction_count as u64).to_be_bytes());
+        counts[8..16].copy_from_slice(&(self.event_count as u64).to_be_bytes());
+        counts[16..24].copy_from_slice(&state_diff_length.to_be_bytes());
+        if self.l1_da_mode == L1DataAvailabilityMode::Blob {
+            counts[24] = 0b1000_0000;
+        }
+        Felt252Wrapper(FieldElement::from_bytes_be(&counts).expect("Block counts should fit in a felt"))
+    }
 }
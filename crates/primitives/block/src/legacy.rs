@@ -0,0 +1,68 @@
+//! Layout of the blocks stored before their headers held the commitments of Starknet 0.13.2.
+//!
+//! Blocks are stored SCALE encoded in the digests of the wrapper blocks, so the blocks of existing
+//! databases keep this layout. They are decoded as such and converted to the current one, without
+//! the new commitments.
+use blockifier::blockifier::block::GasPrices;
+use mp_felt::Felt252Wrapper;
+use sp_core::U256;
+use starknet_api::core::ContractAddress;
+use starknet_api::data_availability::L1DataAvailabilityMode;
+use starknet_api::hash::StarkHash;
+
+use crate::{BlockEvents, BlockTransactions, DeoxysBlock, Header};
+
+/// A header without the receipt and state diff commitments.
+#[derive(Clone, Debug, Default, parity_scale_codec::Encode, parity_scale_codec::Decode)]
+pub struct LegacyHeader {
+    pub parent_block_hash: StarkHash,
+    pub block_number: u64,
+    pub global_state_root: StarkHash,
+    pub sequencer_address: ContractAddress,
+    pub block_timestamp: u64,
+    pub transaction_count: u128,
+    pub transaction_commitment: StarkHash,
+    pub event_count: u128,
+    pub event_commitment: StarkHash,
+    pub protocol_version: Felt252Wrapper,
+    pub l1_gas_price: Option<GasPrices>,
+    pub l1_da_mode: L1DataAvailabilityMode,
+    pub extra_data: Option<U256>,
+}
+
+/// A block whose header is a [`LegacyHeader`].
+#[derive(Clone, Debug, Default, parity_scale_codec::Encode, parity_scale_codec::Decode)]
+pub struct LegacyDeoxysBlock {
+    pub header: LegacyHeader,
+    pub transactions: BlockTransactions,
+    pub events: BlockEvents,
+}
+
+impl From<LegacyHeader> for Header {
+    fn from(header: LegacyHeader) -> Self {
+        Self {
+            parent_block_hash: header.parent_block_hash,
+            block_number: header.block_number,
+            global_state_root: header.global_state_root,
+            sequencer_address: header.sequencer_address,
+            block_timestamp: header.block_timestamp,
+            transaction_count: header.transaction_count,
+            transaction_commitment: header.transaction_commitment,
+            event_count: header.event_count,
+            event_commitment: header.event_commitment,
+            protocol_version: header.protocol_version,
+            l1_gas_price: header.l1_gas_price,
+            l1_da_mode: header.l1_da_mode,
+            extra_data: header.extra_data,
+            receipt_commitment: None,
+            state_diff_commitment: None,
+            state_diff_length: None,
+        }
+    }
+}
+
+impl From<LegacyDeoxysBlock> for DeoxysBlock {
+    fn from(block: LegacyDeoxysBlock) -> Self {
+        Self::new(block.header.into(), block.transactions, block.events)
+    }
+}
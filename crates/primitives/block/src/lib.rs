@@ -6,8 +6,13 @@ pub extern crate alloc;
 use alloc::vec::Vec;
 
 mod header;
+#[cfg(feature = "parity-scale-codec")]
+pub mod legacy;
 mod ordered_events;
+pub mod receipt;
 pub mod state_update;
+#[cfg(feature = "std")]
+pub mod versioned_constants;
 pub use header::Header;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
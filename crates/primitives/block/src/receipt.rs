@@ -0,0 +1,139 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use mp_felt::Felt252Wrapper;
+
+/// A transaction receipt, as provided by the feeder gateway.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "parity-scale-codec", derive(parity_scale_codec::Encode, parity_scale_codec::Decode))]
+pub struct TransactionReceiptWrapper {
+    pub transaction_hash: Felt252Wrapper,
+    pub actual_fee: Felt252Wrapper,
+    /// The revert reason, if the transaction was reverted.
+    pub revert_error: Option<String>,
+    pub execution_resources: ExecutionResourcesWrapper,
+    pub messages_sent: Vec<MessageToL1Wrapper>,
+    pub events: Vec<EventWrapper>,
+}
+
+impl TransactionReceiptWrapper {
+    /// Whether the transaction was reverted.
+    pub fn is_reverted(&self) -> bool {
+        self.revert_error.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "parity-scale-codec", derive(parity_scale_codec::Encode, parity_scale_codec::Decode))]
+pub struct ExecutionResourcesWrapper {
+    pub steps: u64,
+    pub memory_holes: Option<u64>,
+    pub range_check_builtin_applications: Option<u64>,
+    pub pedersen_builtin_applications: Option<u64>,
+    pub poseidon_builtin_applications: Option<u64>,
+    pub ec_op_builtin_applications: Option<u64>,
+    pub ecdsa_builtin_applications: Option<u64>,
+    pub bitwise_builtin_applications: Option<u64>,
+    pub keccak_builtin_applications: Option<u64>,
+    pub segment_arena_builtin: Option<u64>,
+    pub l1_gas: u64,
+    pub l1_data_gas: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "parity-scale-codec", derive(parity_scale_codec::Encode, parity_scale_codec::Decode))]
+pub struct MessageToL1Wrapper {
+    pub from_address: Felt252Wrapper,
+    pub to_address: Felt252Wrapper,
+    pub payload: Vec<Felt252Wrapper>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "parity-scale-codec", derive(parity_scale_codec::Encode, parity_scale_codec::Decode))]
+pub struct EventWrapper {
+    pub from_address: Felt252Wrapper,
+    pub keys: Vec<Felt252Wrapper>,
+    pub data: Vec<Felt252Wrapper>,
+}
+
+/// module `starknet-provider` uses `std` and as result cannot be included in the
+/// substrate runtime. This ensures conversions using `starknet-provider` are
+/// only available in `std` environments.
+#[cfg(feature = "std")]
+pub mod convert {
+    use starknet_core::types::FieldElement;
+    use starknet_providers::sequencer::models::{
+        ConfirmedTransactionReceipt, Event, ExecutionResources, L2ToL1Message, TransactionExecutionStatus,
+    };
+
+    use super::*;
+
+    impl From<&ConfirmedTransactionReceipt> for TransactionReceiptWrapper {
+        fn from(receipt: &ConfirmedTransactionReceipt) -> Self {
+            let revert_error = match receipt.execution_status {
+                Some(TransactionExecutionStatus::Reverted) => Some(receipt.revert_error.clone().unwrap_or_default()),
+                _ => None,
+            };
+
+            TransactionReceiptWrapper {
+                transaction_hash: Felt252Wrapper::from(receipt.transaction_hash),
+                actual_fee: Felt252Wrapper::from(receipt.actual_fee.unwrap_or_default()),
+                revert_error,
+                execution_resources: receipt
+                    .execution_resources
+                    .as_ref()
+                    .map(ExecutionResourcesWrapper::from)
+                    .unwrap_or_default(),
+                messages_sent: receipt.l2_to_l1_messages.iter().map(MessageToL1Wrapper::from).collect(),
+                events: receipt.events.iter().map(EventWrapper::from).collect(),
+            }
+        }
+    }
+
+    impl From<&ExecutionResources> for ExecutionResourcesWrapper {
+        fn from(resources: &ExecutionResources) -> Self {
+            let builtins = &resources.builtin_instance_counter;
+            let (l1_gas, l1_data_gas) = resources
+                .data_availability
+                .as_ref()
+                .map_or((0, 0), |data_availability| (data_availability.l1_gas, data_availability.l1_data_gas));
+
+            ExecutionResourcesWrapper {
+                steps: resources.n_steps,
+                memory_holes: Some(resources.n_memory_holes),
+                range_check_builtin_applications: builtins.range_check_builtin,
+                pedersen_builtin_applications: builtins.pedersen_builtin,
+                poseidon_builtin_applications: builtins.poseidon_builtin,
+                ec_op_builtin_applications: builtins.ec_op_builtin,
+                ecdsa_builtin_applications: builtins.ecdsa_builtin,
+                bitwise_builtin_applications: builtins.bitwise_builtin,
+                keccak_builtin_applications: builtins.keccak_builtin,
+                segment_arena_builtin: builtins.segment_arena_builtin,
+                l1_gas,
+                l1_data_gas,
+            }
+        }
+    }
+
+    impl From<&L2ToL1Message> for MessageToL1Wrapper {
+        fn from(message: &L2ToL1Message) -> Self {
+            MessageToL1Wrapper {
+                from_address: Felt252Wrapper::from(message.from_address),
+                to_address: Felt252Wrapper::from(
+                    FieldElement::from_byte_slice_be(message.to_address.as_bytes()).unwrap(),
+                ),
+                payload: message.payload.iter().copied().map(Felt252Wrapper::from).collect(),
+            }
+        }
+    }
+
+    impl From<&Event> for EventWrapper {
+        fn from(event: &Event) -> Self {
+            EventWrapper {
+                from_address: Felt252Wrapper::from(event.from_address),
+                keys: event.keys.iter().copied().map(Felt252Wrapper::from).collect(),
+                data: event.data.iter().copied().map(Felt252Wrapper::from).collect(),
+            }
+        }
+    }
+}
@@ -3,9 +3,11 @@ use core::convert::TryFrom;
 use blockifier::context::FeeTokenAddresses;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::pedersen::PedersenHasher;
+use mp_hashers::poseidon::PoseidonHasher;
 use mp_hashers::HasherT;
 use starknet_api::block::{BlockNumber, BlockTimestamp};
 use starknet_api::core::{ChainId, ContractAddress, PatriciaKey};
+use starknet_api::data_availability::L1DataAvailabilityMode;
 use starknet_api::hash::{StarkFelt, StarkHash};
 
 use crate::Header;
@@ -77,6 +79,52 @@ fn test_real_header_hash() {
     assert_eq!(hash, expected_hash);
 }
 
+#[test]
+fn test_header_hash_v0_13_2() {
+    let mut header = Header {
+        parent_block_hash: StarkFelt::from(1u128),
+        block_number: 2,
+        global_state_root: StarkFelt::from(3u128),
+        block_timestamp: 4,
+        transaction_count: 1,
+        transaction_commitment: StarkFelt::from(5u128),
+        event_count: 2,
+        event_commitment: StarkFelt::from(6u128),
+        protocol_version: Felt252Wrapper::try_from(&b"0.13.2"[..]).unwrap(),
+        l1_da_mode: L1DataAvailabilityMode::Blob,
+        receipt_commitment: Some(StarkFelt::from(7u128)),
+        state_diff_commitment: Some(StarkFelt::from(8u128)),
+        state_diff_length: Some(3),
+        ..Default::default()
+    };
+
+    let expected_hash = <PoseidonHasher as HasherT>::compute_hash_on_wrappers(&[
+        Felt252Wrapper::try_from(&b"STARKNET_BLOCK_HASH0"[..]).unwrap(),
+        2u64.into(),          // block_number
+        3u64.into(),          // global_state_root
+        Felt252Wrapper::ZERO, // sequencer_address
+        4u64.into(),          // block_timestamp
+        // transaction, event and state diff counts, followed by the blob data availability bit
+        Felt252Wrapper::from_hex_be("0x1000000000000000200000000000000038000000000000000").unwrap(),
+        8u64.into(),          // state_diff_commitment
+        5u64.into(),          // transaction_commitment
+        6u64.into(),          // event_commitment
+        7u64.into(),          // receipt_commitment
+        Felt252Wrapper::ZERO, // gas prices
+        Felt252Wrapper::ZERO,
+        Felt252Wrapper::ZERO,
+        Felt252Wrapper::ZERO,
+        header.protocol_version,
+        Felt252Wrapper::ZERO, // placeholder
+        1u64.into(),          // parent_block_hash
+    ]);
+    assert_eq!(header.hash::<PedersenHasher>(), expected_hash);
+
+    // headers without the commitments of Starknet 0.13.2 are hashed as before
+    header.receipt_commitment = None;
+    assert_ne!(header.hash::<PedersenHasher>(), expected_hash);
+}
+
 #[test]
 fn test_to_block_context() {
     let sequencer_address = ContractAddress(PatriciaKey(StarkFelt::try_from("0xFF").unwrap()));
@@ -0,0 +1,116 @@
+//! Blockifier constants used to execute the blocks of each Starknet version.
+//!
+//! Execution costs and limits change between Starknet versions (0.13.0, 0.13.1...). By default,
+//! blocks are executed with the latest constants bundled with blockifier. The constants of older
+//! versions are loaded from a JSON file listing the file holding the constants of each version,
+//! e.g.
+//!
+//! ```json
+//! {
+//!     "0.13.0": "versioned_constants_13_0.json",
+//!     "0.13.1": "versioned_constants_13_1.json"
+//! }
+//! ```
+//!
+//! Relative paths are resolved against the directory of the listing. A block is executed with the
+//! constants of the latest listed version which is not newer than its own.
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Context};
+use blockifier::versioned_constants::VersionedConstants;
+use mp_felt::Felt252Wrapper;
+
+static VERSIONED_CONSTANTS: OnceLock<VersionedConstantsMap> = OnceLock::new();
+
+/// A Starknet version, e.g. `0.13.1.1`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StarknetVersion(Vec<u64>);
+
+impl FromStr for StarknetVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split('.')
+            .map(|part| part.parse())
+            .collect::<Result<_, _>>()
+            .map(Self)
+            .map_err(|_| anyhow!("Invalid Starknet version: {s:?}"))
+    }
+}
+
+/// The blockifier constants of each Starknet version.
+#[derive(Debug, Default)]
+pub struct VersionedConstantsMap {
+    constants: BTreeMap<StarknetVersion, VersionedConstants>,
+}
+
+impl VersionedConstantsMap {
+    /// Loads the constants listed in the given file.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let listing = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the versioned constants listing at {}", path.display()))?;
+        let listing: BTreeMap<String, String> = serde_json::from_str(&listing)
+            .with_context(|| format!("Invalid versioned constants listing at {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+
+        let mut constants = BTreeMap::new();
+        for (version, file) in listing {
+            let file = dir.join(file);
+            let content = std::fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read the constants of Starknet {version} at {}", file.display()))?;
+            let versioned_constants: VersionedConstants = serde_json::from_str(&content)
+                .with_context(|| format!("Invalid constants for Starknet {version} at {}", file.display()))?;
+            constants.insert(version.parse()?, versioned_constants);
+        }
+
+        Ok(Self { constants })
+    }
+
+    /// Returns the constants to execute blocks of the given Starknet version with.
+    pub fn get(&self, version: &StarknetVersion) -> &VersionedConstants {
+        self.constants
+            .range(..=version.clone())
+            .next_back()
+            .map(|(_, constants)| constants)
+            .unwrap_or_else(|| VersionedConstants::latest_constants())
+    }
+}
+
+/// Sets the constants used to execute blocks, once at startup.
+pub fn init(constants: VersionedConstantsMap) -> anyhow::Result<()> {
+    VERSIONED_CONSTANTS.set(constants).map_err(|_| anyhow!("Versioned constants already initialized"))
+}
+
+/// Returns the constants to execute a block with, given its protocol version.
+///
+/// Blocks with no valid protocol version are executed with the latest constants.
+pub fn for_protocol_version(protocol_version: &Felt252Wrapper) -> &'static VersionedConstants {
+    let version = protocol_version.from_utf8().ok().and_then(|version| version.parse().ok());
+
+    match (VERSIONED_CONSTANTS.get(), version) {
+        (Some(constants), Some(version)) => constants.get(&version),
+        _ => VersionedConstants::latest_constants(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_use_the_constants_of_their_version() {
+        let v0_13_0: StarknetVersion = "0.13.0".parse().unwrap();
+        assert!(v0_13_0 < "0.13.1".parse().unwrap());
+        assert!(v0_13_0 < "0.13.0.1".parse().unwrap());
+        assert!("0.12.3".parse::<StarknetVersion>().unwrap() < v0_13_0);
+        assert!("0.13.x".parse::<StarknetVersion>().is_err());
+
+        let map = VersionedConstantsMap { constants: BTreeMap::from([(v0_13_0, VersionedConstants::default())]) };
+        let listed = map.get(&"0.13.0".parse().unwrap());
+        assert!(std::ptr::eq(map.get(&"0.13.1".parse().unwrap()), listed));
+        assert!(std::ptr::eq(map.get(&"0.12.3".parse().unwrap()), VersionedConstants::latest_constants()));
+    }
+}
@@ -6,6 +6,7 @@ use anyhow::anyhow;
 use blockifier::execution::contract_class::{
     ContractClass as ContractClassBlockifier, ContractClassV0, ContractClassV0Inner, ContractClassV1,
 };
+use cairo_lang_starknet_classes::casm_contract_class::CasmContractClass;
 use cairo_vm::types::program::Program;
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
@@ -14,6 +15,7 @@ use mp_transactions::from_broadcasted_transactions::flattened_sierra_to_casm_con
 use starknet_api::core::EntryPointSelector;
 use starknet_api::deprecated_contract_class::{EntryPoint, EntryPointOffset, EntryPointType};
 use starknet_api::hash::StarkFelt;
+use starknet_core::types::contract::CompiledClass;
 use starknet_core::types::{
     CompressedLegacyContractClass, ContractClass as ContractClassCore, EntryPointsByType, FieldElement,
     FlattenedSierraClass, FromByteArrayError, LegacyContractAbiEntry, LegacyContractEntryPoint,
@@ -44,10 +46,29 @@ pub fn to_contract_class_sierra(_: &ContractClassV1, abi: String) -> anyhow::Res
 /// json representation.
 pub fn from_contract_class_sierra(contract_class: FlattenedSierraClass) -> anyhow::Result<ContractClassBlockifier> {
     let raw_casm_contract = flattened_sierra_to_casm_contract_class(&Arc::new(contract_class))?;
-    let blockifier_contract = ContractClassV1::try_from(raw_casm_contract).unwrap();
+    from_casm_contract_class(raw_casm_contract)
+}
+
+/// Converts a [CasmContractClass] to a [ContractClassBlockifier]
+pub fn from_casm_contract_class(casm_contract_class: CasmContractClass) -> anyhow::Result<ContractClassBlockifier> {
+    let blockifier_contract = ContractClassV1::try_from(casm_contract_class)?;
     anyhow::Ok(ContractClassBlockifier::V1(blockifier_contract))
 }
 
+/// Returns the compiled class hash of a [CasmContractClass], as declared on chain
+///
+/// Note: The hash is computed by starknet-rs, through the json representation shared by both
+/// compiled class types.
+pub fn casm_compiled_class_hash(casm_contract_class: &CasmContractClass) -> anyhow::Result<FieldElement> {
+    let compiled_class: CompiledClass = serde_json::from_value(serde_json::to_value(casm_contract_class)?)?;
+    Ok(compiled_class.class_hash()?)
+}
+
+/// Converts a [CompiledClass], as returned by the feeder gateway, to a [CasmContractClass]
+pub fn casm_from_compiled_class(compiled_class: &CompiledClass) -> anyhow::Result<CasmContractClass> {
+    Ok(serde_json::from_value(serde_json::to_value(compiled_class)?)?)
+}
+
 pub fn to_contract_class_cairo(
     contract_class: &ContractClassV0,
     abi: Option<Vec<LegacyContractAbiEntry>>,
@@ -0,0 +1,21 @@
+use starknet_core::types::FieldElement;
+use thiserror::Error;
+
+/// An error converting data to the node types, e.g. the data returned by the gateway.
+#[derive(Error, Debug)]
+pub enum ConversionError {
+    #[error("no {0} provided")]
+    MissingField(&'static str),
+    #[error("invalid {0}")]
+    InvalidField(&'static str),
+    #[error("{kind} transaction version {version:#x} not supported")]
+    UnsupportedTransactionVersion { kind: &'static str, version: FieldElement },
+    #[error("{kind} transaction version {version:#x} is only accepted from Starknet {since}, not {protocol}")]
+    NotYetSupported { kind: &'static str, version: FieldElement, protocol: String, since: &'static str },
+    #[error("{kind} transaction version {version:#x} is no longer accepted since Starknet {until}, not in {protocol}")]
+    NoLongerSupported { kind: &'static str, version: FieldElement, protocol: String, until: &'static str },
+    #[error("{0} transactions can't be executed")]
+    NotExecutable(&'static str),
+    #[error("invalid contract class: {0}")]
+    ContractClass(String),
+}
@@ -0,0 +1,129 @@
+//! Conversion of the transactions stored by the node, from [`starknet_api`], to the transactions
+//! executed by blockifier, to execute the transactions of a block again.
+//!
+//! What blockifier needs on top of the stored transaction is read from the state of the node by
+//! the caller: the class info of the declared class for declarations, and the fee paid on L1 for L1
+//! handlers.
+use blockifier::execution::contract_class::ClassInfo;
+use blockifier::transaction::account_transaction::AccountTransaction;
+use blockifier::transaction::transaction_execution::Transaction as ExecutableTransaction;
+use blockifier::transaction::transactions::{
+    DeclareTransaction, DeployAccountTransaction, InvokeTransaction, L1HandlerTransaction,
+};
+use starknet_api::core::{calculate_contract_address, ContractAddress};
+use starknet_api::transaction::{Fee, Transaction, TransactionHash};
+
+use crate::ConversionError;
+
+/// Converts `transaction`, of hash `transaction_hash`, to a transaction executable by blockifier.
+///
+/// Declarations need the `class_info` of their class and L1 handlers the fee paid on L1 for them.
+/// Deploy transactions, which were removed from Starknet, can't be executed.
+pub fn to_executable_transaction(
+    transaction: &Transaction,
+    transaction_hash: TransactionHash,
+    class_info: Option<ClassInfo>,
+    paid_fee_on_l1: Option<Fee>,
+) -> Result<ExecutableTransaction, ConversionError> {
+    let transaction = match transaction {
+        Transaction::Invoke(tx) => AccountTransaction::Invoke(InvokeTransaction {
+            tx: tx.clone(),
+            tx_hash: transaction_hash,
+            only_query: false,
+        }),
+        Transaction::DeployAccount(tx) => {
+            let contract_address = calculate_contract_address(
+                tx.contract_address_salt(),
+                tx.class_hash(),
+                &tx.constructor_calldata(),
+                ContractAddress::default(),
+            )
+            .map_err(|_| ConversionError::InvalidField("deployed contract address"))?;
+            AccountTransaction::DeployAccount(DeployAccountTransaction::new(
+                tx.clone(),
+                transaction_hash,
+                contract_address,
+            ))
+        }
+        Transaction::Declare(tx) => {
+            let class_info = class_info.ok_or(ConversionError::MissingField("class info"))?;
+            // fails if the class is not of the version expected by the declaration
+            let tx = DeclareTransaction::new(tx.clone(), transaction_hash, class_info)
+                .map_err(|e| ConversionError::ContractClass(e.to_string()))?;
+            AccountTransaction::Declare(tx)
+        }
+        Transaction::L1Handler(tx) => {
+            return Ok(ExecutableTransaction::L1HandlerTransaction(L1HandlerTransaction {
+                tx: tx.clone(),
+                tx_hash: transaction_hash,
+                paid_fee_on_l1: paid_fee_on_l1.ok_or(ConversionError::MissingField("fee paid on L1"))?,
+            }));
+        }
+        Transaction::Deploy(_) => return Err(ConversionError::NotExecutable("deploy")),
+    };
+    Ok(ExecutableTransaction::AccountTransaction(transaction))
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_api::core::{ClassHash, Nonce};
+    use starknet_api::hash::StarkFelt;
+    use starknet_api::transaction::{
+        ContractAddressSalt, DeclareTransaction as ApiDeclareTransaction, DeclareTransactionV0V1,
+        DeployAccountTransaction as ApiDeployAccountTransaction, DeployAccountTransactionV1, DeployTransaction,
+        TransactionVersion,
+    };
+
+    use super::*;
+    use crate::transaction::{calldata, contract_address, signature};
+
+    #[test]
+    fn deploy_accounts_are_deployed_at_their_computed_address() {
+        let tx = ApiDeployAccountTransaction::V1(DeployAccountTransactionV1 {
+            max_fee: Fee(10),
+            signature: signature(vec![]),
+            nonce: Nonce(StarkFelt::ZERO),
+            class_hash: ClassHash(StarkFelt::from(1u128)),
+            contract_address_salt: ContractAddressSalt(StarkFelt::from(2u128)),
+            constructor_calldata: calldata(vec![3u64.into()]),
+        });
+        let expected = calculate_contract_address(
+            ContractAddressSalt(StarkFelt::from(2u128)),
+            ClassHash(StarkFelt::from(1u128)),
+            &calldata(vec![3u64.into()]),
+            ContractAddress::default(),
+        )
+        .unwrap();
+
+        let executable =
+            to_executable_transaction(&Transaction::DeployAccount(tx), TransactionHash::default(), None, None).unwrap();
+        match executable {
+            ExecutableTransaction::AccountTransaction(AccountTransaction::DeployAccount(tx)) => {
+                assert_eq!(tx.contract_address, expected)
+            }
+            _ => panic!("not a deploy account transaction"),
+        }
+    }
+
+    #[test]
+    fn missing_execution_data_is_reported() {
+        let declare = Transaction::Declare(ApiDeclareTransaction::V1(DeclareTransactionV0V1 {
+            max_fee: Fee(10),
+            signature: signature(vec![]),
+            nonce: Nonce(StarkFelt::ZERO),
+            class_hash: ClassHash(StarkFelt::from(1u128)),
+            sender_address: contract_address(2u64.into()),
+        }));
+        let error = to_executable_transaction(&declare, TransactionHash::default(), None, None).unwrap_err();
+        assert_eq!(error.to_string(), "no class info provided");
+
+        let deploy = Transaction::Deploy(DeployTransaction {
+            version: TransactionVersion(StarkFelt::ZERO),
+            class_hash: ClassHash(StarkFelt::from(1u128)),
+            contract_address_salt: ContractAddressSalt(StarkFelt::ZERO),
+            constructor_calldata: calldata(vec![]),
+        });
+        let error = to_executable_transaction(&deploy, TransactionHash::default(), None, None).unwrap_err();
+        assert_eq!(error.to_string(), "deploy transactions can't be executed");
+    }
+}
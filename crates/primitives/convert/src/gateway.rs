@@ -0,0 +1,673 @@
+//! Conversions of the data returned by the feeder gateway, from [`starknet_providers`], to the
+//! types stored by the node, and of the stored transactions back to the gateway format, for the
+//! feeder gateway endpoints served by the node.
+//!
+//! Transactions go through the same helpers as the conversions of [`crate::transaction`], so that
+//! a synced transaction is served by the RPC exactly as the gateway returned it.
+use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroU128;
+
+use blockifier::blockifier::block::GasPrices;
+use mp_felt::Felt252Wrapper;
+use serde_json::{json, Value};
+use starknet_api::core::{calculate_contract_address, ClassHash, CompiledClassHash, EntryPointSelector, Nonce};
+use starknet_api::data_availability::{DataAvailabilityMode, L1DataAvailabilityMode};
+use starknet_api::hash::StarkFelt;
+use starknet_api::transaction::{
+    AccountDeploymentData, Calldata, ContractAddressSalt, DeclareTransaction, DeclareTransactionV0V1,
+    DeclareTransactionV2, DeclareTransactionV3, DeployAccountTransaction, DeployAccountTransactionV1,
+    DeployAccountTransactionV3, DeployTransaction, Event, EventContent, EventData, EventKey, InvokeTransaction,
+    InvokeTransactionV0, InvokeTransactionV1, InvokeTransactionV3, L1HandlerTransaction, PaymasterData, Resource,
+    ResourceBounds, ResourceBoundsMapping, Tip, Transaction, TransactionVersion,
+};
+use starknet_core::types::{
+    ContractStorageDiffItem, DeclaredClassItem, DeployedContractItem, FieldElement, NonceUpdate, PendingStateUpdate,
+    ReplacedClassItem, ResourcePrice, StateDiff, StorageEntry,
+};
+use starknet_providers::sequencer::models::state_update::{DeclaredContract, DeployedContract, StorageDiff};
+use starknet_providers::sequencer::models::{self as p};
+
+use crate::transaction::{calldata, contract_address, fee_from_core, felt, signature, stark_felt, stark_felts};
+use crate::versions::{check_transaction_version, transaction_version};
+use crate::ConversionError;
+
+/// Converts the transactions of a block of Starknet version `starknet_version`, see
+/// [`transaction`].
+pub fn transactions(
+    txs: Vec<p::TransactionType>,
+    starknet_version: Option<&str>,
+) -> Result<Vec<Transaction>, ConversionError> {
+    txs.into_iter().map(|tx| transaction(tx, starknet_version)).collect()
+}
+
+/// Converts a transaction of a block of Starknet version `starknet_version`.
+///
+/// Fails if the transaction version is not accepted by the Starknet version of the block, e.g. a V3
+/// transaction in a block older than 0.13.0, see [`crate::versions`].
+pub fn transaction(
+    transaction: p::TransactionType,
+    starknet_version: Option<&str>,
+) -> Result<Transaction, ConversionError> {
+    let transaction = match transaction {
+        p::TransactionType::Declare(tx) => Transaction::Declare(declare_transaction(tx)?),
+        p::TransactionType::Deploy(tx) => Transaction::Deploy(deploy_transaction(tx)),
+        p::TransactionType::DeployAccount(tx) => Transaction::DeployAccount(deploy_account_transaction(tx)?),
+        p::TransactionType::InvokeFunction(tx) => Transaction::Invoke(invoke_transaction(tx)?),
+        p::TransactionType::L1Handler(tx) => Transaction::L1Handler(l1_handler_transaction(tx)),
+    };
+    let (kind, version) = transaction_version(&transaction);
+    check_transaction_version(kind, version, starknet_version)?;
+    Ok(transaction)
+}
+
+fn declare_transaction(tx: p::DeclareTransaction) -> Result<DeclareTransaction, ConversionError> {
+    let tx = if tx.version == FieldElement::ZERO {
+        DeclareTransaction::V0(DeclareTransactionV0V1 {
+            max_fee: fee_from_core(tx.max_fee.ok_or(ConversionError::MissingField("max fee"))?)?,
+            signature: signature(tx.signature),
+            nonce: Nonce(stark_felt(tx.nonce)),
+            class_hash: ClassHash(stark_felt(tx.class_hash)),
+            sender_address: contract_address(tx.sender_address),
+        })
+    } else if tx.version == FieldElement::ONE {
+        DeclareTransaction::V1(DeclareTransactionV0V1 {
+            max_fee: fee_from_core(tx.max_fee.ok_or(ConversionError::MissingField("max fee"))?)?,
+            signature: signature(tx.signature),
+            nonce: Nonce(stark_felt(tx.nonce)),
+            class_hash: ClassHash(stark_felt(tx.class_hash)),
+            sender_address: contract_address(tx.sender_address),
+        })
+    } else if tx.version == FieldElement::TWO {
+        DeclareTransaction::V2(DeclareTransactionV2 {
+            max_fee: fee_from_core(tx.max_fee.ok_or(ConversionError::MissingField("max fee"))?)?,
+            signature: signature(tx.signature),
+            nonce: Nonce(stark_felt(tx.nonce)),
+            class_hash: ClassHash(stark_felt(tx.class_hash)),
+            compiled_class_hash: CompiledClassHash(stark_felt(
+                tx.compiled_class_hash.ok_or(ConversionError::MissingField("compiled class hash"))?,
+            )),
+            sender_address: contract_address(tx.sender_address),
+        })
+    } else if tx.version == FieldElement::THREE {
+        DeclareTransaction::V3(DeclareTransactionV3 {
+            resource_bounds: resource_bounds(
+                tx.resource_bounds.ok_or(ConversionError::MissingField("resource bounds"))?,
+            ),
+            tip: Tip(tx.tip.ok_or(ConversionError::MissingField("tip"))?),
+            signature: signature(tx.signature),
+            nonce: Nonce(stark_felt(tx.nonce)),
+            class_hash: ClassHash(stark_felt(tx.class_hash)),
+            compiled_class_hash: CompiledClassHash(stark_felt(
+                tx.compiled_class_hash.ok_or(ConversionError::MissingField("compiled class hash"))?,
+            )),
+            sender_address: contract_address(tx.sender_address),
+            nonce_data_availability_mode: data_availability_mode(
+                tx.nonce_data_availability_mode.ok_or(ConversionError::MissingField("nonce_data_availability_mode"))?,
+            ),
+            fee_data_availability_mode: data_availability_mode(
+                tx.fee_data_availability_mode.ok_or(ConversionError::MissingField("fee_data_availability_mode"))?,
+            ),
+            paymaster_data: PaymasterData(stark_felts(
+                tx.paymaster_data.ok_or(ConversionError::MissingField("paymaster_data"))?,
+            )),
+            account_deployment_data: AccountDeploymentData(stark_felts(
+                tx.account_deployment_data.ok_or(ConversionError::MissingField("account_deployment_data"))?,
+            )),
+        })
+    } else {
+        return Err(ConversionError::UnsupportedTransactionVersion { kind: "declare", version: tx.version });
+    };
+    Ok(tx)
+}
+
+fn deploy_transaction(tx: p::DeployTransaction) -> DeployTransaction {
+    DeployTransaction {
+        version: TransactionVersion(stark_felt(tx.version)),
+        class_hash: ClassHash(stark_felt(tx.class_hash)),
+        contract_address_salt: ContractAddressSalt(stark_felt(tx.contract_address_salt)),
+        constructor_calldata: calldata(tx.constructor_calldata),
+    }
+}
+
+fn deploy_account_transaction(tx: p::DeployAccountTransaction) -> Result<DeployAccountTransaction, ConversionError> {
+    // the gateway does not return the version of deploy account transactions
+    let tx = match tx.resource_bounds {
+        None => DeployAccountTransaction::V1(DeployAccountTransactionV1 {
+            max_fee: fee_from_core(tx.max_fee.ok_or(ConversionError::MissingField("max fee"))?)?,
+            signature: signature(tx.signature),
+            nonce: Nonce(stark_felt(tx.nonce)),
+            class_hash: ClassHash(stark_felt(tx.class_hash)),
+            contract_address_salt: ContractAddressSalt(stark_felt(tx.contract_address_salt)),
+            constructor_calldata: calldata(tx.constructor_calldata),
+        }),
+        Some(bounds) => DeployAccountTransaction::V3(DeployAccountTransactionV3 {
+            resource_bounds: resource_bounds(bounds),
+            tip: Tip(tx.tip.ok_or(ConversionError::MissingField("tip"))?),
+            signature: signature(tx.signature),
+            nonce: Nonce(stark_felt(tx.nonce)),
+            class_hash: ClassHash(stark_felt(tx.class_hash)),
+            contract_address_salt: ContractAddressSalt(stark_felt(tx.contract_address_salt)),
+            constructor_calldata: calldata(tx.constructor_calldata),
+            nonce_data_availability_mode: data_availability_mode(
+                tx.nonce_data_availability_mode.ok_or(ConversionError::MissingField("nonce_data_availability_mode"))?,
+            ),
+            fee_data_availability_mode: data_availability_mode(
+                tx.fee_data_availability_mode.ok_or(ConversionError::MissingField("fee_data_availability_mode"))?,
+            ),
+            paymaster_data: PaymasterData(stark_felts(
+                tx.paymaster_data.ok_or(ConversionError::MissingField("paymaster_data"))?,
+            )),
+        }),
+    };
+    Ok(tx)
+}
+
+fn invoke_transaction(tx: p::InvokeFunctionTransaction) -> Result<InvokeTransaction, ConversionError> {
+    let tx = if tx.version == FieldElement::ZERO {
+        InvokeTransaction::V0(InvokeTransactionV0 {
+            max_fee: fee_from_core(tx.max_fee.ok_or(ConversionError::MissingField("max fee"))?)?,
+            signature: signature(tx.signature),
+            contract_address: contract_address(tx.sender_address),
+            entry_point_selector: EntryPointSelector(stark_felt(
+                tx.entry_point_selector.ok_or(ConversionError::MissingField("entry_point_selector"))?,
+            )),
+            calldata: calldata(tx.calldata),
+        })
+    } else if tx.version == FieldElement::ONE {
+        InvokeTransaction::V1(InvokeTransactionV1 {
+            max_fee: fee_from_core(tx.max_fee.ok_or(ConversionError::MissingField("max fee"))?)?,
+            signature: signature(tx.signature),
+            nonce: Nonce(stark_felt(tx.nonce.ok_or(ConversionError::MissingField("nonce"))?)),
+            sender_address: contract_address(tx.sender_address),
+            calldata: calldata(tx.calldata),
+        })
+    } else if tx.version == FieldElement::THREE {
+        InvokeTransaction::V3(InvokeTransactionV3 {
+            resource_bounds: resource_bounds(
+                tx.resource_bounds.ok_or(ConversionError::MissingField("resource bounds"))?,
+            ),
+            tip: Tip(tx.tip.ok_or(ConversionError::MissingField("tip"))?),
+            signature: signature(tx.signature),
+            nonce: Nonce(stark_felt(tx.nonce.ok_or(ConversionError::MissingField("nonce"))?)),
+            sender_address: contract_address(tx.sender_address),
+            calldata: calldata(tx.calldata),
+            nonce_data_availability_mode: data_availability_mode(
+                tx.nonce_data_availability_mode.ok_or(ConversionError::MissingField("nonce_data_availability_mode"))?,
+            ),
+            fee_data_availability_mode: data_availability_mode(
+                tx.fee_data_availability_mode.ok_or(ConversionError::MissingField("fee_data_availability_mode"))?,
+            ),
+            paymaster_data: PaymasterData(stark_felts(
+                tx.paymaster_data.ok_or(ConversionError::MissingField("paymaster_data"))?,
+            )),
+            account_deployment_data: AccountDeploymentData(stark_felts(
+                tx.account_deployment_data.ok_or(ConversionError::MissingField("account_deployment_data"))?,
+            )),
+        })
+    } else {
+        return Err(ConversionError::UnsupportedTransactionVersion { kind: "invoke", version: tx.version });
+    };
+    Ok(tx)
+}
+
+fn l1_handler_transaction(tx: p::L1HandlerTransaction) -> L1HandlerTransaction {
+    L1HandlerTransaction {
+        version: TransactionVersion(stark_felt(tx.version)),
+        // the first L1 handler transactions have no nonce
+        nonce: Nonce(stark_felt(tx.nonce.unwrap_or_default())),
+        contract_address: contract_address(tx.contract_address),
+        entry_point_selector: EntryPointSelector(stark_felt(tx.entry_point_selector)),
+        calldata: calldata(tx.calldata),
+    }
+}
+
+fn resource_bounds(bounds: p::ResourceBoundsMapping) -> ResourceBoundsMapping {
+    ResourceBoundsMapping(BTreeMap::from([
+        (
+            Resource::L1Gas,
+            ResourceBounds {
+                max_amount: bounds.l1_gas.max_amount,
+                max_price_per_unit: bounds.l1_gas.max_price_per_unit,
+            },
+        ),
+        (
+            Resource::L2Gas,
+            ResourceBounds {
+                max_amount: bounds.l2_gas.max_amount,
+                max_price_per_unit: bounds.l2_gas.max_price_per_unit,
+            },
+        ),
+    ]))
+}
+
+fn data_availability_mode(mode: p::DataAvailabilityMode) -> DataAvailabilityMode {
+    match mode {
+        p::DataAvailabilityMode::L1 => DataAvailabilityMode::L1,
+        p::DataAvailabilityMode::L2 => DataAvailabilityMode::L2,
+    }
+}
+
+/// Converts a transaction to the format of the feeder gateway, the inverse of [`transaction`].
+///
+/// Unlike the RPC, the gateway names invoke transactions `INVOKE_FUNCTION`, always sets their
+/// version, serializes the data availability modes as numbers and returns the address of the
+/// contracts deployed by `DEPLOY` and `DEPLOY_ACCOUNT` transactions.
+pub fn to_gateway_transaction(tx: &Transaction, transaction_hash: FieldElement) -> Result<Value, ConversionError> {
+    let mut json = match tx {
+        Transaction::Declare(DeclareTransaction::V0(tx)) => gateway_declare_v0_v1(tx, "0x0"),
+        Transaction::Declare(DeclareTransaction::V1(tx)) => gateway_declare_v0_v1(tx, "0x1"),
+        Transaction::Declare(DeclareTransaction::V2(tx)) => json!({
+            "type": "DECLARE",
+            "version": "0x2",
+            "max_fee": hex(tx.max_fee.0),
+            "signature": felts_hex(&tx.signature.0),
+            "nonce": felt_hex(tx.nonce.0),
+            "class_hash": felt_hex(tx.class_hash.0),
+            "compiled_class_hash": felt_hex(tx.compiled_class_hash.0),
+            "sender_address": felt_hex(*tx.sender_address.0.key()),
+        }),
+        Transaction::Declare(DeclareTransaction::V3(tx)) => json!({
+            "type": "DECLARE",
+            "version": "0x3",
+            "resource_bounds": gateway_resource_bounds(&tx.resource_bounds),
+            "tip": hex(tx.tip.0.into()),
+            "signature": felts_hex(&tx.signature.0),
+            "nonce": felt_hex(tx.nonce.0),
+            "class_hash": felt_hex(tx.class_hash.0),
+            "compiled_class_hash": felt_hex(tx.compiled_class_hash.0),
+            "sender_address": felt_hex(*tx.sender_address.0.key()),
+            "nonce_data_availability_mode": gateway_da_mode(tx.nonce_data_availability_mode),
+            "fee_data_availability_mode": gateway_da_mode(tx.fee_data_availability_mode),
+            "paymaster_data": felts_hex(&tx.paymaster_data.0),
+            "account_deployment_data": felts_hex(&tx.account_deployment_data.0),
+        }),
+        Transaction::Deploy(tx) => json!({
+            "type": "DEPLOY",
+            "version": felt_hex(tx.version.0),
+            "contract_address": deployed_address(tx.contract_address_salt, tx.class_hash, &tx.constructor_calldata)?,
+            "contract_address_salt": felt_hex(tx.contract_address_salt.0),
+            "class_hash": felt_hex(tx.class_hash.0),
+            "constructor_calldata": felts_hex(&tx.constructor_calldata.0),
+        }),
+        Transaction::DeployAccount(DeployAccountTransaction::V1(tx)) => json!({
+            "type": "DEPLOY_ACCOUNT",
+            "version": "0x1",
+            "contract_address": deployed_address(tx.contract_address_salt, tx.class_hash, &tx.constructor_calldata)?,
+            "max_fee": hex(tx.max_fee.0),
+            "signature": felts_hex(&tx.signature.0),
+            "nonce": felt_hex(tx.nonce.0),
+            "class_hash": felt_hex(tx.class_hash.0),
+            "contract_address_salt": felt_hex(tx.contract_address_salt.0),
+            "constructor_calldata": felts_hex(&tx.constructor_calldata.0),
+        }),
+        Transaction::DeployAccount(DeployAccountTransaction::V3(tx)) => json!({
+            "type": "DEPLOY_ACCOUNT",
+            "version": "0x3",
+            "contract_address": deployed_address(tx.contract_address_salt, tx.class_hash, &tx.constructor_calldata)?,
+            "resource_bounds": gateway_resource_bounds(&tx.resource_bounds),
+            "tip": hex(tx.tip.0.into()),
+            "signature": felts_hex(&tx.signature.0),
+            "nonce": felt_hex(tx.nonce.0),
+            "class_hash": felt_hex(tx.class_hash.0),
+            "contract_address_salt": felt_hex(tx.contract_address_salt.0),
+            "constructor_calldata": felts_hex(&tx.constructor_calldata.0),
+            "nonce_data_availability_mode": gateway_da_mode(tx.nonce_data_availability_mode),
+            "fee_data_availability_mode": gateway_da_mode(tx.fee_data_availability_mode),
+            "paymaster_data": felts_hex(&tx.paymaster_data.0),
+        }),
+        Transaction::Invoke(InvokeTransaction::V0(tx)) => json!({
+            "type": "INVOKE_FUNCTION",
+            "version": "0x0",
+            "max_fee": hex(tx.max_fee.0),
+            "signature": felts_hex(&tx.signature.0),
+            "contract_address": felt_hex(*tx.contract_address.0.key()),
+            "entry_point_selector": felt_hex(tx.entry_point_selector.0),
+            "calldata": felts_hex(&tx.calldata.0),
+        }),
+        Transaction::Invoke(InvokeTransaction::V1(tx)) => json!({
+            "type": "INVOKE_FUNCTION",
+            "version": "0x1",
+            "max_fee": hex(tx.max_fee.0),
+            "signature": felts_hex(&tx.signature.0),
+            "nonce": felt_hex(tx.nonce.0),
+            "sender_address": felt_hex(*tx.sender_address.0.key()),
+            "calldata": felts_hex(&tx.calldata.0),
+        }),
+        Transaction::Invoke(InvokeTransaction::V3(tx)) => json!({
+            "type": "INVOKE_FUNCTION",
+            "version": "0x3",
+            "resource_bounds": gateway_resource_bounds(&tx.resource_bounds),
+            "tip": hex(tx.tip.0.into()),
+            "signature": felts_hex(&tx.signature.0),
+            "nonce": felt_hex(tx.nonce.0),
+            "sender_address": felt_hex(*tx.sender_address.0.key()),
+            "calldata": felts_hex(&tx.calldata.0),
+            "nonce_data_availability_mode": gateway_da_mode(tx.nonce_data_availability_mode),
+            "fee_data_availability_mode": gateway_da_mode(tx.fee_data_availability_mode),
+            "paymaster_data": felts_hex(&tx.paymaster_data.0),
+            "account_deployment_data": felts_hex(&tx.account_deployment_data.0),
+        }),
+        Transaction::L1Handler(tx) => json!({
+            "type": "L1_HANDLER",
+            "version": felt_hex(tx.version.0),
+            "nonce": felt_hex(tx.nonce.0),
+            "contract_address": felt_hex(*tx.contract_address.0.key()),
+            "entry_point_selector": felt_hex(tx.entry_point_selector.0),
+            "calldata": felts_hex(&tx.calldata.0),
+        }),
+    };
+    json["transaction_hash"] = json!(format!("{transaction_hash:#x}"));
+    Ok(json)
+}
+
+fn gateway_declare_v0_v1(tx: &DeclareTransactionV0V1, version: &str) -> Value {
+    json!({
+        "type": "DECLARE",
+        "version": version,
+        "max_fee": hex(tx.max_fee.0),
+        "signature": felts_hex(&tx.signature.0),
+        "nonce": felt_hex(tx.nonce.0),
+        "class_hash": felt_hex(tx.class_hash.0),
+        "sender_address": felt_hex(*tx.sender_address.0.key()),
+    })
+}
+
+fn gateway_resource_bounds(bounds: &ResourceBoundsMapping) -> Value {
+    let bounds = |resource| {
+        let (max_amount, max_price_per_unit) =
+            bounds.0.get(&resource).map_or((0, 0), |bounds| (bounds.max_amount, bounds.max_price_per_unit));
+        json!({ "max_amount": hex(max_amount.into()), "max_price_per_unit": hex(max_price_per_unit) })
+    };
+    json!({ "L1_GAS": bounds(Resource::L1Gas), "L2_GAS": bounds(Resource::L2Gas) })
+}
+
+fn gateway_da_mode(mode: DataAvailabilityMode) -> u8 {
+    match mode {
+        DataAvailabilityMode::L1 => 0,
+        DataAvailabilityMode::L2 => 1,
+    }
+}
+
+/// The address of a contract deployed by a `DEPLOY` or `DEPLOY_ACCOUNT` transaction.
+fn deployed_address(
+    salt: ContractAddressSalt,
+    class_hash: ClassHash,
+    constructor_calldata: &Calldata,
+) -> Result<String, ConversionError> {
+    let address = calculate_contract_address(salt, class_hash, constructor_calldata, Default::default())
+        .map_err(|_| ConversionError::InvalidField("deployed contract address"))?;
+    Ok(felt_hex(*address.0.key()))
+}
+
+fn felt_hex(value: StarkFelt) -> String {
+    format!("{:#x}", felt(value))
+}
+
+fn felts_hex(values: &[StarkFelt]) -> Vec<String> {
+    values.iter().copied().map(felt_hex).collect()
+}
+
+fn hex(value: u128) -> String {
+    format!("{value:#x}")
+}
+
+/// The events of the transactions of a block, in order.
+pub fn events(receipts: &[p::ConfirmedTransactionReceipt]) -> Vec<Event> {
+    receipts.iter().flat_map(|r| &r.events).map(event).collect()
+}
+
+pub fn event(event: &p::Event) -> Event {
+    Event {
+        from_address: contract_address(event.from_address),
+        content: EventContent {
+            keys: event.keys.iter().copied().map(stark_felt).map(EventKey).collect(),
+            data: EventData(stark_felts(event.data.clone())),
+        },
+    }
+}
+
+/// Converts a starknet version string to a felt value.
+/// Fails if the string contains more than 31 bytes.
+pub fn starknet_version(version: &Option<String>) -> Result<Felt252Wrapper, ConversionError> {
+    match version {
+        Some(version) => {
+            Felt252Wrapper::try_from(version.as_bytes()).map_err(|_| ConversionError::InvalidField("starknet version"))
+        }
+        None => Ok(Felt252Wrapper::ZERO),
+    }
+}
+
+/// Converts the l1 gas price and l1 data gas price to a GasPrices struct, if the l1 gas price is
+/// not 0. If the l1 gas price is 0, returns None.
+/// The other prices are converted to NonZeroU128, with 0 being converted to 1.
+pub fn resource_price(
+    l1_gas_price: ResourcePrice,
+    l1_data_gas_price: ResourcePrice,
+) -> Result<Option<GasPrices>, ConversionError> {
+    /// Converts a FieldElement to a NonZeroU128, with 0 being converted to 1.
+    fn field_element_to_non_zero_u128(field_element: FieldElement) -> Result<NonZeroU128, ConversionError> {
+        let value: u128 = field_element.try_into().map_err(|_| ConversionError::InvalidField("l1 gas price"))?;
+        Ok(NonZeroU128::new(value).unwrap_or(NonZeroU128::MIN))
+    }
+
+    if l1_gas_price.price_in_wei == FieldElement::ZERO {
+        Ok(None)
+    } else {
+        Ok(Some(GasPrices {
+            eth_l1_gas_price: field_element_to_non_zero_u128(l1_gas_price.price_in_wei)?,
+            strk_l1_gas_price: field_element_to_non_zero_u128(l1_gas_price.price_in_fri)?,
+            eth_l1_data_gas_price: field_element_to_non_zero_u128(l1_data_gas_price.price_in_wei)?,
+            strk_l1_data_gas_price: field_element_to_non_zero_u128(l1_data_gas_price.price_in_fri)?,
+        }))
+    }
+}
+
+pub fn l1_da_mode(mode: starknet_core::types::L1DataAvailabilityMode) -> L1DataAvailabilityMode {
+    match mode {
+        starknet_core::types::L1DataAvailabilityMode::Calldata => L1DataAvailabilityMode::Calldata,
+        starknet_core::types::L1DataAvailabilityMode::Blob => L1DataAvailabilityMode::Blob,
+    }
+}
+
+pub fn state_update(state_update: p::StateUpdate) -> PendingStateUpdate {
+    PendingStateUpdate { old_root: state_update.old_root, state_diff: state_diff(state_update.state_diff) }
+}
+
+fn state_diff(state_diff: p::state_update::StateDiff) -> StateDiff {
+    StateDiff {
+        storage_diffs: storage_diffs(state_diff.storage_diffs),
+        deprecated_declared_classes: state_diff.old_declared_contracts,
+        declared_classes: declared_classes(state_diff.declared_classes),
+        deployed_contracts: deployed_contracts(state_diff.deployed_contracts),
+        replaced_classes: replaced_classes(state_diff.replaced_classes),
+        nonces: nonces(state_diff.nonces),
+    }
+}
+
+fn storage_diffs(storage_diffs: HashMap<FieldElement, Vec<StorageDiff>>) -> Vec<ContractStorageDiffItem> {
+    storage_diffs
+        .into_iter()
+        .map(|(address, entries)| ContractStorageDiffItem { address, storage_entries: storage_entries(entries) })
+        .collect()
+}
+
+fn storage_entries(storage_entries: Vec<StorageDiff>) -> Vec<StorageEntry> {
+    storage_entries.into_iter().map(|StorageDiff { key, value }| StorageEntry { key, value }).collect()
+}
+
+fn declared_classes(declared_classes: Vec<DeclaredContract>) -> Vec<DeclaredClassItem> {
+    declared_classes
+        .into_iter()
+        .map(|DeclaredContract { class_hash, compiled_class_hash }| DeclaredClassItem {
+            class_hash,
+            compiled_class_hash,
+        })
+        .collect()
+}
+
+fn deployed_contracts(deployed_contracts: Vec<DeployedContract>) -> Vec<DeployedContractItem> {
+    deployed_contracts
+        .into_iter()
+        .map(|DeployedContract { address, class_hash }| DeployedContractItem { address, class_hash })
+        .collect()
+}
+
+fn replaced_classes(replaced_classes: Vec<DeployedContract>) -> Vec<ReplacedClassItem> {
+    replaced_classes
+        .into_iter()
+        .map(|DeployedContract { address, class_hash }| ReplacedClassItem { contract_address: address, class_hash })
+        .collect()
+}
+
+fn nonces(nonces: HashMap<FieldElement, FieldElement>) -> Vec<NonceUpdate> {
+    nonces.into_iter().map(|(contract_address, nonce)| NonceUpdate { contract_address, nonce }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gas_prices_are_non_zero() {
+        let price = |wei: u64, fri: u64| ResourcePrice { price_in_wei: wei.into(), price_in_fri: fri.into() };
+
+        assert!(resource_price(price(0, 5), price(1, 1)).unwrap().is_none());
+
+        let prices = resource_price(price(10, 20), price(0, 3)).unwrap().unwrap();
+        assert_eq!(prices.eth_l1_gas_price.get(), 10);
+        assert_eq!(prices.strk_l1_gas_price.get(), 20);
+        assert_eq!(prices.eth_l1_data_gas_price, NonZeroU128::MIN);
+        assert_eq!(prices.strk_l1_data_gas_price.get(), 3);
+    }
+
+    #[test]
+    fn transaction_versions_are_gated_by_protocol_version() {
+        let v3 = Transaction::Invoke(InvokeTransaction::V3(InvokeTransactionV3 {
+            resource_bounds: ResourceBoundsMapping(BTreeMap::new()),
+            tip: Tip(0),
+            signature: signature(vec![]),
+            nonce: Nonce(stark_felt(FieldElement::ONE)),
+            sender_address: contract_address(FieldElement::TWO),
+            calldata: calldata(vec![]),
+            nonce_data_availability_mode: DataAvailabilityMode::L1,
+            fee_data_availability_mode: DataAvailabilityMode::L1,
+            paymaster_data: PaymasterData(vec![]),
+            account_deployment_data: AccountDeploymentData(vec![]),
+        }));
+
+        let check = |protocol| {
+            let (kind, version) = transaction_version(&v3);
+            check_transaction_version(kind, version, protocol)
+        };
+        assert!(check(None).is_ok());
+        assert!(check(Some("0.13")).is_ok());
+        assert!(check(Some("0.13.1.1")).is_ok());
+        assert!(check(Some("next")).is_ok());
+        let error = check(Some("0.12.3")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invoke transaction version 0x3 is only accepted from Starknet 0.13.0, not 0.12.3"
+        );
+    }
+
+    #[test]
+    fn transactions_roundtrip_through_the_gateway_format() {
+        let felts = |values: &[u64]| values.iter().copied().map(FieldElement::from).collect::<Vec<_>>();
+        let resource_bounds = ResourceBoundsMapping(BTreeMap::from([
+            (Resource::L1Gas, ResourceBounds { max_amount: 10, max_price_per_unit: 20 }),
+            (Resource::L2Gas, ResourceBounds { max_amount: 0, max_price_per_unit: 0 }),
+        ]));
+        let transactions = vec![
+            Transaction::Declare(DeclareTransaction::V1(DeclareTransactionV0V1 {
+                max_fee: starknet_api::transaction::Fee(1234),
+                signature: signature(felts(&[1, 2])),
+                nonce: Nonce(stark_felt(3u64.into())),
+                class_hash: ClassHash(stark_felt(4u64.into())),
+                sender_address: contract_address(5u64.into()),
+            })),
+            Transaction::DeployAccount(DeployAccountTransaction::V3(DeployAccountTransactionV3 {
+                resource_bounds: resource_bounds.clone(),
+                tip: Tip(7),
+                signature: signature(felts(&[8])),
+                nonce: Nonce(stark_felt(9u64.into())),
+                class_hash: ClassHash(stark_felt(10u64.into())),
+                contract_address_salt: ContractAddressSalt(stark_felt(11u64.into())),
+                constructor_calldata: calldata(felts(&[12, 13])),
+                nonce_data_availability_mode: DataAvailabilityMode::L1,
+                fee_data_availability_mode: DataAvailabilityMode::L2,
+                paymaster_data: PaymasterData(vec![]),
+            })),
+            Transaction::Deploy(DeployTransaction {
+                version: TransactionVersion(stark_felt(FieldElement::ZERO)),
+                class_hash: ClassHash(stark_felt(14u64.into())),
+                contract_address_salt: ContractAddressSalt(stark_felt(15u64.into())),
+                constructor_calldata: calldata(felts(&[16])),
+            }),
+            Transaction::Invoke(InvokeTransaction::V0(InvokeTransactionV0 {
+                max_fee: starknet_api::transaction::Fee(17),
+                signature: signature(vec![]),
+                contract_address: contract_address(18u64.into()),
+                entry_point_selector: EntryPointSelector(stark_felt(19u64.into())),
+                calldata: calldata(felts(&[20])),
+            })),
+            Transaction::Invoke(InvokeTransaction::V3(InvokeTransactionV3 {
+                resource_bounds,
+                tip: Tip(0),
+                signature: signature(felts(&[21])),
+                nonce: Nonce(stark_felt(22u64.into())),
+                sender_address: contract_address(23u64.into()),
+                calldata: calldata(felts(&[24, 25])),
+                nonce_data_availability_mode: DataAvailabilityMode::L2,
+                fee_data_availability_mode: DataAvailabilityMode::L1,
+                paymaster_data: PaymasterData(stark_felts(felts(&[26]))),
+                account_deployment_data: AccountDeploymentData(vec![]),
+            })),
+            Transaction::L1Handler(L1HandlerTransaction {
+                version: TransactionVersion(stark_felt(FieldElement::ZERO)),
+                nonce: Nonce(stark_felt(27u64.into())),
+                contract_address: contract_address(28u64.into()),
+                entry_point_selector: EntryPointSelector(stark_felt(29u64.into())),
+                calldata: calldata(felts(&[30])),
+            }),
+        ];
+
+        for (index, tx) in transactions.into_iter().enumerate() {
+            let hash = FieldElement::from(100 + index as u64);
+            let json = to_gateway_transaction(&tx, hash).unwrap();
+            assert_eq!(json["transaction_hash"], format!("{hash:#x}"));
+            let gateway_tx: p::TransactionType = serde_json::from_value(json).unwrap();
+            assert_eq!(transaction(gateway_tx, None).unwrap(), tx);
+        }
+    }
+
+    #[test]
+    fn invoke_transactions_are_served_in_the_gateway_shape() {
+        let tx = Transaction::Invoke(InvokeTransaction::V3(InvokeTransactionV3 {
+            resource_bounds: ResourceBoundsMapping(BTreeMap::new()),
+            tip: Tip(0),
+            signature: signature(vec![]),
+            nonce: Nonce(stark_felt(FieldElement::ONE)),
+            sender_address: contract_address(FieldElement::TWO),
+            calldata: calldata(vec![]),
+            nonce_data_availability_mode: DataAvailabilityMode::L1,
+            fee_data_availability_mode: DataAvailabilityMode::L2,
+            paymaster_data: PaymasterData(vec![]),
+            account_deployment_data: AccountDeploymentData(vec![]),
+        }));
+
+        let json = to_gateway_transaction(&tx, FieldElement::THREE).unwrap();
+        assert_eq!(json["type"], "INVOKE_FUNCTION");
+        assert_eq!(json["version"], "0x3");
+        assert_eq!(json["nonce_data_availability_mode"], 0);
+        assert_eq!(json["fee_data_availability_mode"], 1);
+        assert_eq!(json["resource_bounds"]["L1_GAS"]["max_amount"], "0x0");
+        assert!(json.get("max_fee").is_none());
+    }
+
+    #[test]
+    fn starknet_versions_fit_in_a_felt() {
+        assert_eq!(starknet_version(&None).unwrap(), Felt252Wrapper::ZERO);
+        assert!(starknet_version(&Some("0.13.1".to_string())).is_ok());
+        assert!(starknet_version(&Some("0".repeat(32))).is_err());
+    }
+}
@@ -1,2 +1,17 @@
+//! Conversions between the types of the feeder gateway, of the RPC spec ([`starknet_core`]), and
+//! the types stored by the node ([`starknet_api`] and blockifier).
 #[cfg(feature = "std")]
 pub mod contract;
+#[cfg(feature = "std")]
+mod errors;
+#[cfg(feature = "std")]
+pub mod executable;
+#[cfg(feature = "std")]
+pub mod gateway;
+#[cfg(feature = "std")]
+pub mod transaction;
+#[cfg(feature = "std")]
+pub mod versions;
+
+#[cfg(feature = "std")]
+pub use errors::ConversionError;
@@ -0,0 +1,436 @@
+//! Conversions between the transactions stored by the node, from [`starknet_api`], and the
+//! transactions of the RPC spec, from [`starknet_core`].
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use mp_felt::Felt252Wrapper;
+use starknet_api::core::{ClassHash, CompiledClassHash, ContractAddress, EntryPointSelector, Nonce, PatriciaKey};
+use starknet_api::data_availability::DataAvailabilityMode;
+use starknet_api::hash::StarkFelt;
+use starknet_api::transaction::{
+    AccountDeploymentData, Calldata, ContractAddressSalt, DeclareTransaction, DeclareTransactionV0V1,
+    DeclareTransactionV2, DeclareTransactionV3, DeployAccountTransaction, DeployAccountTransactionV1,
+    DeployAccountTransactionV3, DeployTransaction, Fee, InvokeTransaction, InvokeTransactionV0, InvokeTransactionV1,
+    InvokeTransactionV3, L1HandlerTransaction, PaymasterData, Resource, ResourceBounds, ResourceBoundsMapping, Tip,
+    Transaction, TransactionSignature, TransactionVersion,
+};
+use starknet_core::types::{self as rpc, FieldElement};
+
+use crate::ConversionError;
+
+/// Converts a transaction to its RPC representation.
+pub fn to_starknet_core_tx(tx: Transaction, transaction_hash: FieldElement) -> rpc::Transaction {
+    match tx {
+        Transaction::Declare(tx) => rpc::Transaction::Declare(match tx {
+            DeclareTransaction::V0(tx) => rpc::DeclareTransaction::V0(rpc::DeclareTransactionV0 {
+                transaction_hash,
+                max_fee: fee(tx.max_fee),
+                signature: felts(&tx.signature.0),
+                class_hash: felt(tx.class_hash.0),
+                sender_address: address(tx.sender_address),
+            }),
+            DeclareTransaction::V1(tx) => rpc::DeclareTransaction::V1(rpc::DeclareTransactionV1 {
+                transaction_hash,
+                max_fee: fee(tx.max_fee),
+                signature: felts(&tx.signature.0),
+                nonce: felt(tx.nonce.0),
+                class_hash: felt(tx.class_hash.0),
+                sender_address: address(tx.sender_address),
+            }),
+            DeclareTransaction::V2(tx) => rpc::DeclareTransaction::V2(rpc::DeclareTransactionV2 {
+                transaction_hash,
+                max_fee: fee(tx.max_fee),
+                signature: felts(&tx.signature.0),
+                nonce: felt(tx.nonce.0),
+                class_hash: felt(tx.class_hash.0),
+                compiled_class_hash: felt(tx.compiled_class_hash.0),
+                sender_address: address(tx.sender_address),
+            }),
+            DeclareTransaction::V3(tx) => rpc::DeclareTransaction::V3(rpc::DeclareTransactionV3 {
+                transaction_hash,
+                resource_bounds: resource_bounds_to_core(&tx.resource_bounds),
+                tip: tx.tip.0,
+                signature: felts(&tx.signature.0),
+                nonce: felt(tx.nonce.0),
+                class_hash: felt(tx.class_hash.0),
+                compiled_class_hash: felt(tx.compiled_class_hash.0),
+                sender_address: address(tx.sender_address),
+                nonce_data_availability_mode: da_mode_to_core(tx.nonce_data_availability_mode),
+                fee_data_availability_mode: da_mode_to_core(tx.fee_data_availability_mode),
+                paymaster_data: felts(&tx.paymaster_data.0),
+                account_deployment_data: felts(&tx.account_deployment_data.0),
+            }),
+        }),
+        Transaction::DeployAccount(tx) => rpc::Transaction::DeployAccount(match tx {
+            DeployAccountTransaction::V1(tx) => rpc::DeployAccountTransaction::V1(rpc::DeployAccountTransactionV1 {
+                transaction_hash,
+                max_fee: fee(tx.max_fee),
+                signature: felts(&tx.signature.0),
+                nonce: felt(tx.nonce.0),
+                contract_address_salt: felt(tx.contract_address_salt.0),
+                constructor_calldata: felts(&tx.constructor_calldata.0),
+                class_hash: felt(tx.class_hash.0),
+            }),
+            DeployAccountTransaction::V3(tx) => rpc::DeployAccountTransaction::V3(rpc::DeployAccountTransactionV3 {
+                transaction_hash,
+                resource_bounds: resource_bounds_to_core(&tx.resource_bounds),
+                tip: tx.tip.0,
+                signature: felts(&tx.signature.0),
+                nonce: felt(tx.nonce.0),
+                class_hash: felt(tx.class_hash.0),
+                contract_address_salt: felt(tx.contract_address_salt.0),
+                constructor_calldata: felts(&tx.constructor_calldata.0),
+                nonce_data_availability_mode: da_mode_to_core(tx.nonce_data_availability_mode),
+                fee_data_availability_mode: da_mode_to_core(tx.fee_data_availability_mode),
+                paymaster_data: felts(&tx.paymaster_data.0),
+            }),
+        }),
+        Transaction::Deploy(tx) => rpc::Transaction::Deploy(rpc::DeployTransaction {
+            transaction_hash,
+            version: felt(tx.version.0),
+            contract_address_salt: felt(tx.contract_address_salt.0),
+            constructor_calldata: felts(&tx.constructor_calldata.0),
+            class_hash: felt(tx.class_hash.0),
+        }),
+        Transaction::Invoke(tx) => rpc::Transaction::Invoke(match tx {
+            InvokeTransaction::V0(tx) => rpc::InvokeTransaction::V0(rpc::InvokeTransactionV0 {
+                transaction_hash,
+                max_fee: fee(tx.max_fee),
+                signature: felts(&tx.signature.0),
+                contract_address: address(tx.contract_address),
+                entry_point_selector: felt(tx.entry_point_selector.0),
+                calldata: felts(&tx.calldata.0),
+            }),
+            InvokeTransaction::V1(tx) => rpc::InvokeTransaction::V1(rpc::InvokeTransactionV1 {
+                transaction_hash,
+                max_fee: fee(tx.max_fee),
+                signature: felts(&tx.signature.0),
+                nonce: felt(tx.nonce.0),
+                sender_address: address(tx.sender_address),
+                calldata: felts(&tx.calldata.0),
+            }),
+            InvokeTransaction::V3(tx) => rpc::InvokeTransaction::V3(rpc::InvokeTransactionV3 {
+                transaction_hash,
+                resource_bounds: resource_bounds_to_core(&tx.resource_bounds),
+                tip: tx.tip.0,
+                signature: felts(&tx.signature.0),
+                nonce: felt(tx.nonce.0),
+                sender_address: address(tx.sender_address),
+                calldata: felts(&tx.calldata.0),
+                nonce_data_availability_mode: da_mode_to_core(tx.nonce_data_availability_mode),
+                fee_data_availability_mode: da_mode_to_core(tx.fee_data_availability_mode),
+                paymaster_data: felts(&tx.paymaster_data.0),
+                account_deployment_data: felts(&tx.account_deployment_data.0),
+            }),
+        }),
+        Transaction::L1Handler(tx) => rpc::Transaction::L1Handler(rpc::L1HandlerTransaction {
+            transaction_hash,
+            version: felt(tx.version.0),
+            // L1 handler nonces are the nonces of the L1 to L2 messages, which fit in a u64
+            nonce: u64::try_from(Felt252Wrapper::from(tx.nonce.0)).unwrap_or(u64::MAX),
+            contract_address: address(tx.contract_address),
+            entry_point_selector: felt(tx.entry_point_selector.0),
+            calldata: felts(&tx.calldata.0),
+        }),
+    }
+}
+
+/// Converts a transaction of the RPC spec to the representation stored by the node, along with its
+/// hash.
+pub fn from_starknet_core_tx(tx: rpc::Transaction) -> Result<(Transaction, FieldElement), ConversionError> {
+    Ok(match tx {
+        rpc::Transaction::Declare(tx) => match tx {
+            rpc::DeclareTransaction::V0(tx) => (
+                Transaction::Declare(DeclareTransaction::V0(DeclareTransactionV0V1 {
+                    max_fee: fee_from_core(tx.max_fee)?,
+                    signature: signature(tx.signature),
+                    nonce: Nonce::default(),
+                    class_hash: ClassHash(stark_felt(tx.class_hash)),
+                    sender_address: contract_address(tx.sender_address),
+                })),
+                tx.transaction_hash,
+            ),
+            rpc::DeclareTransaction::V1(tx) => (
+                Transaction::Declare(DeclareTransaction::V1(DeclareTransactionV0V1 {
+                    max_fee: fee_from_core(tx.max_fee)?,
+                    signature: signature(tx.signature),
+                    nonce: Nonce(stark_felt(tx.nonce)),
+                    class_hash: ClassHash(stark_felt(tx.class_hash)),
+                    sender_address: contract_address(tx.sender_address),
+                })),
+                tx.transaction_hash,
+            ),
+            rpc::DeclareTransaction::V2(tx) => (
+                Transaction::Declare(DeclareTransaction::V2(DeclareTransactionV2 {
+                    max_fee: fee_from_core(tx.max_fee)?,
+                    signature: signature(tx.signature),
+                    nonce: Nonce(stark_felt(tx.nonce)),
+                    class_hash: ClassHash(stark_felt(tx.class_hash)),
+                    compiled_class_hash: CompiledClassHash(stark_felt(tx.compiled_class_hash)),
+                    sender_address: contract_address(tx.sender_address),
+                })),
+                tx.transaction_hash,
+            ),
+            rpc::DeclareTransaction::V3(tx) => (
+                Transaction::Declare(DeclareTransaction::V3(DeclareTransactionV3 {
+                    resource_bounds: resource_bounds_from_core(&tx.resource_bounds),
+                    tip: Tip(tx.tip),
+                    signature: signature(tx.signature),
+                    nonce: Nonce(stark_felt(tx.nonce)),
+                    class_hash: ClassHash(stark_felt(tx.class_hash)),
+                    compiled_class_hash: CompiledClassHash(stark_felt(tx.compiled_class_hash)),
+                    sender_address: contract_address(tx.sender_address),
+                    nonce_data_availability_mode: da_mode_from_core(tx.nonce_data_availability_mode),
+                    fee_data_availability_mode: da_mode_from_core(tx.fee_data_availability_mode),
+                    paymaster_data: PaymasterData(stark_felts(tx.paymaster_data)),
+                    account_deployment_data: AccountDeploymentData(stark_felts(tx.account_deployment_data)),
+                })),
+                tx.transaction_hash,
+            ),
+        },
+        rpc::Transaction::DeployAccount(tx) => match tx {
+            rpc::DeployAccountTransaction::V1(tx) => (
+                Transaction::DeployAccount(DeployAccountTransaction::V1(DeployAccountTransactionV1 {
+                    max_fee: fee_from_core(tx.max_fee)?,
+                    signature: signature(tx.signature),
+                    nonce: Nonce(stark_felt(tx.nonce)),
+                    class_hash: ClassHash(stark_felt(tx.class_hash)),
+                    contract_address_salt: ContractAddressSalt(stark_felt(tx.contract_address_salt)),
+                    constructor_calldata: calldata(tx.constructor_calldata),
+                })),
+                tx.transaction_hash,
+            ),
+            rpc::DeployAccountTransaction::V3(tx) => (
+                Transaction::DeployAccount(DeployAccountTransaction::V3(DeployAccountTransactionV3 {
+                    resource_bounds: resource_bounds_from_core(&tx.resource_bounds),
+                    tip: Tip(tx.tip),
+                    signature: signature(tx.signature),
+                    nonce: Nonce(stark_felt(tx.nonce)),
+                    class_hash: ClassHash(stark_felt(tx.class_hash)),
+                    contract_address_salt: ContractAddressSalt(stark_felt(tx.contract_address_salt)),
+                    constructor_calldata: calldata(tx.constructor_calldata),
+                    nonce_data_availability_mode: da_mode_from_core(tx.nonce_data_availability_mode),
+                    fee_data_availability_mode: da_mode_from_core(tx.fee_data_availability_mode),
+                    paymaster_data: PaymasterData(stark_felts(tx.paymaster_data)),
+                })),
+                tx.transaction_hash,
+            ),
+        },
+        rpc::Transaction::Deploy(tx) => (
+            Transaction::Deploy(DeployTransaction {
+                version: TransactionVersion(stark_felt(tx.version)),
+                class_hash: ClassHash(stark_felt(tx.class_hash)),
+                contract_address_salt: ContractAddressSalt(stark_felt(tx.contract_address_salt)),
+                constructor_calldata: calldata(tx.constructor_calldata),
+            }),
+            tx.transaction_hash,
+        ),
+        rpc::Transaction::Invoke(tx) => match tx {
+            rpc::InvokeTransaction::V0(tx) => (
+                Transaction::Invoke(InvokeTransaction::V0(InvokeTransactionV0 {
+                    max_fee: fee_from_core(tx.max_fee)?,
+                    signature: signature(tx.signature),
+                    contract_address: contract_address(tx.contract_address),
+                    entry_point_selector: EntryPointSelector(stark_felt(tx.entry_point_selector)),
+                    calldata: calldata(tx.calldata),
+                })),
+                tx.transaction_hash,
+            ),
+            rpc::InvokeTransaction::V1(tx) => (
+                Transaction::Invoke(InvokeTransaction::V1(InvokeTransactionV1 {
+                    max_fee: fee_from_core(tx.max_fee)?,
+                    signature: signature(tx.signature),
+                    nonce: Nonce(stark_felt(tx.nonce)),
+                    sender_address: contract_address(tx.sender_address),
+                    calldata: calldata(tx.calldata),
+                })),
+                tx.transaction_hash,
+            ),
+            rpc::InvokeTransaction::V3(tx) => (
+                Transaction::Invoke(InvokeTransaction::V3(InvokeTransactionV3 {
+                    resource_bounds: resource_bounds_from_core(&tx.resource_bounds),
+                    tip: Tip(tx.tip),
+                    signature: signature(tx.signature),
+                    nonce: Nonce(stark_felt(tx.nonce)),
+                    sender_address: contract_address(tx.sender_address),
+                    calldata: calldata(tx.calldata),
+                    nonce_data_availability_mode: da_mode_from_core(tx.nonce_data_availability_mode),
+                    fee_data_availability_mode: da_mode_from_core(tx.fee_data_availability_mode),
+                    paymaster_data: PaymasterData(stark_felts(tx.paymaster_data)),
+                    account_deployment_data: AccountDeploymentData(stark_felts(tx.account_deployment_data)),
+                })),
+                tx.transaction_hash,
+            ),
+        },
+        rpc::Transaction::L1Handler(tx) => (
+            Transaction::L1Handler(L1HandlerTransaction {
+                version: TransactionVersion(stark_felt(tx.version)),
+                nonce: Nonce(Felt252Wrapper::from(tx.nonce).into()),
+                contract_address: contract_address(tx.contract_address),
+                entry_point_selector: EntryPointSelector(stark_felt(tx.entry_point_selector)),
+                calldata: calldata(tx.calldata),
+            }),
+            tx.transaction_hash,
+        ),
+    })
+}
+
+/// Converts the resource bounds of a transaction to their RPC representation. Missing bounds are
+/// zero.
+pub fn resource_bounds_to_core(resource_bounds: &ResourceBoundsMapping) -> rpc::ResourceBoundsMapping {
+    let bounds = |resource| {
+        resource_bounds.0.get(&resource).map_or(
+            rpc::ResourceBounds { max_amount: 0, max_price_per_unit: 0 },
+            |bounds: &ResourceBounds| rpc::ResourceBounds {
+                max_amount: bounds.max_amount,
+                max_price_per_unit: bounds.max_price_per_unit,
+            },
+        )
+    };
+    rpc::ResourceBoundsMapping { l1_gas: bounds(Resource::L1Gas), l2_gas: bounds(Resource::L2Gas) }
+}
+
+/// Converts resource bounds of the RPC spec to the representation stored by the node.
+pub fn resource_bounds_from_core(resource_bounds: &rpc::ResourceBoundsMapping) -> ResourceBoundsMapping {
+    let bounds = |bounds: &rpc::ResourceBounds| ResourceBounds {
+        max_amount: bounds.max_amount,
+        max_price_per_unit: bounds.max_price_per_unit,
+    };
+    ResourceBoundsMapping(BTreeMap::from([
+        (Resource::L1Gas, bounds(&resource_bounds.l1_gas)),
+        (Resource::L2Gas, bounds(&resource_bounds.l2_gas)),
+    ]))
+}
+
+pub fn da_mode_to_core(mode: DataAvailabilityMode) -> rpc::DataAvailabilityMode {
+    match mode {
+        DataAvailabilityMode::L1 => rpc::DataAvailabilityMode::L1,
+        DataAvailabilityMode::L2 => rpc::DataAvailabilityMode::L2,
+    }
+}
+
+pub fn da_mode_from_core(mode: rpc::DataAvailabilityMode) -> DataAvailabilityMode {
+    match mode {
+        rpc::DataAvailabilityMode::L1 => DataAvailabilityMode::L1,
+        rpc::DataAvailabilityMode::L2 => DataAvailabilityMode::L2,
+    }
+}
+
+pub(crate) fn felt(felt: StarkFelt) -> FieldElement {
+    Felt252Wrapper::from(felt).into()
+}
+
+pub fn stark_felt(felt: FieldElement) -> StarkFelt {
+    Felt252Wrapper::from(felt).into()
+}
+
+fn felts(felts: &[StarkFelt]) -> Vec<FieldElement> {
+    felts.iter().copied().map(felt).collect()
+}
+
+pub(crate) fn stark_felts(felts: Vec<FieldElement>) -> Vec<StarkFelt> {
+    felts.into_iter().map(stark_felt).collect()
+}
+
+fn address(address: ContractAddress) -> FieldElement {
+    Felt252Wrapper::from(address).into()
+}
+
+pub fn contract_address(address: FieldElement) -> ContractAddress {
+    ContractAddress(PatriciaKey(stark_felt(address)))
+}
+
+pub(crate) fn signature(signature: Vec<FieldElement>) -> TransactionSignature {
+    TransactionSignature(stark_felts(signature))
+}
+
+pub(crate) fn calldata(calldata: Vec<FieldElement>) -> Calldata {
+    Calldata(Arc::new(stark_felts(calldata)))
+}
+
+fn fee(fee: Fee) -> FieldElement {
+    Felt252Wrapper::from(fee.0).into()
+}
+
+pub(crate) fn fee_from_core(fee: FieldElement) -> Result<Fee, ConversionError> {
+    Ok(Fee(fee.try_into().map_err(|_| ConversionError::InvalidField("max fee"))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn felt_vec(values: &[u64]) -> Vec<FieldElement> {
+        values.iter().copied().map(FieldElement::from).collect()
+    }
+
+    fn resource_bounds() -> ResourceBoundsMapping {
+        ResourceBoundsMapping(BTreeMap::from([
+            (Resource::L1Gas, ResourceBounds { max_amount: 10, max_price_per_unit: 20 }),
+            (Resource::L2Gas, ResourceBounds { max_amount: 0, max_price_per_unit: 0 }),
+        ]))
+    }
+
+    #[test]
+    fn transactions_roundtrip_through_the_rpc_representation() {
+        let transactions = vec![
+            Transaction::Declare(DeclareTransaction::V2(DeclareTransactionV2 {
+                max_fee: Fee(1234),
+                signature: signature(felt_vec(&[1, 2])),
+                nonce: Nonce(stark_felt(3u64.into())),
+                class_hash: ClassHash(stark_felt(4u64.into())),
+                compiled_class_hash: CompiledClassHash(stark_felt(5u64.into())),
+                sender_address: contract_address(6u64.into()),
+            })),
+            Transaction::DeployAccount(DeployAccountTransaction::V3(DeployAccountTransactionV3 {
+                resource_bounds: resource_bounds(),
+                tip: Tip(7),
+                signature: signature(felt_vec(&[8])),
+                nonce: Nonce(stark_felt(9u64.into())),
+                class_hash: ClassHash(stark_felt(10u64.into())),
+                contract_address_salt: ContractAddressSalt(stark_felt(11u64.into())),
+                constructor_calldata: calldata(felt_vec(&[12, 13])),
+                nonce_data_availability_mode: DataAvailabilityMode::L1,
+                fee_data_availability_mode: DataAvailabilityMode::L2,
+                paymaster_data: PaymasterData(vec![]),
+            })),
+            Transaction::Deploy(DeployTransaction {
+                version: TransactionVersion(stark_felt(1u64.into())),
+                class_hash: ClassHash(stark_felt(14u64.into())),
+                contract_address_salt: ContractAddressSalt(stark_felt(15u64.into())),
+                constructor_calldata: calldata(felt_vec(&[16])),
+            }),
+            Transaction::Invoke(InvokeTransaction::V3(InvokeTransactionV3 {
+                resource_bounds: resource_bounds(),
+                tip: Tip(0),
+                signature: signature(felt_vec(&[17])),
+                nonce: Nonce(stark_felt(18u64.into())),
+                sender_address: contract_address(19u64.into()),
+                calldata: calldata(felt_vec(&[20, 21, 22])),
+                nonce_data_availability_mode: DataAvailabilityMode::L2,
+                fee_data_availability_mode: DataAvailabilityMode::L1,
+                paymaster_data: PaymasterData(stark_felts(felt_vec(&[23]))),
+                account_deployment_data: AccountDeploymentData(stark_felts(felt_vec(&[24]))),
+            })),
+            Transaction::L1Handler(L1HandlerTransaction {
+                version: TransactionVersion(stark_felt(FieldElement::ZERO)),
+                nonce: Nonce(stark_felt(25u64.into())),
+                contract_address: contract_address(26u64.into()),
+                entry_point_selector: EntryPointSelector(stark_felt(27u64.into())),
+                calldata: calldata(felt_vec(&[28])),
+            }),
+        ];
+
+        for (index, transaction) in transactions.into_iter().enumerate() {
+            let hash = FieldElement::from(100 + index as u64);
+            let rpc_transaction = to_starknet_core_tx(transaction.clone(), hash);
+            assert_eq!(from_starknet_core_tx(rpc_transaction).unwrap(), (transaction, hash));
+        }
+    }
+
+    #[test]
+    fn fees_must_fit_in_a_u128() {
+        assert_eq!(fee_from_core(FieldElement::from(u128::MAX)).unwrap(), Fee(u128::MAX));
+        assert!(fee_from_core(FieldElement::from(u128::MAX) + FieldElement::ONE).is_err());
+    }
+}
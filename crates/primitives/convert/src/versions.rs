@@ -0,0 +1,165 @@
+//! Transaction versions accepted by each Starknet version.
+//!
+//! [`TRANSACTION_VERSIONS`] lists the versions of each kind of transaction, with the Starknet
+//! versions which introduced and removed them. The sync checks the transactions of a block against
+//! the Starknet version of the block, and the RPC checks the transactions it is sent against the
+//! version of the block they are executed on, both with [`check_transaction_version`].
+use starknet_api::transaction::{DeclareTransaction, DeployAccountTransaction, InvokeTransaction, Transaction};
+use starknet_core::types::{
+    BroadcastedDeclareTransaction, BroadcastedDeployAccountTransaction, BroadcastedInvokeTransaction,
+    BroadcastedTransaction, FieldElement,
+};
+
+use crate::ConversionError;
+
+/// A transaction version and the Starknet versions accepting it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionVersionSupport {
+    pub kind: &'static str,
+    pub version: u64,
+    /// First Starknet version accepting the transaction version, `None` if it always was.
+    pub since: Option<&'static str>,
+    /// First Starknet version no longer accepting it, `None` if it is still accepted.
+    pub until: Option<&'static str>,
+}
+
+const fn support(
+    kind: &'static str,
+    version: u64,
+    since: Option<&'static str>,
+    until: Option<&'static str>,
+) -> TransactionVersionSupport {
+    TransactionVersionSupport { kind, version, since, until }
+}
+
+/// The transaction versions known to the node.
+pub const TRANSACTION_VERSIONS: &[TransactionVersionSupport] = &[
+    support("declare", 0, None, None),
+    support("declare", 1, None, None),
+    support("declare", 2, Some("0.11.0"), None),
+    support("declare", 3, Some("0.13.0"), None),
+    support("deploy", 0, None, Some("0.11.0")),
+    support("deploy account", 1, Some("0.10.1"), None),
+    support("deploy account", 3, Some("0.13.0"), None),
+    support("invoke", 0, None, None),
+    support("invoke", 1, Some("0.10.0"), None),
+    support("invoke", 3, Some("0.13.0"), None),
+    support("l1 handler", 0, None, None),
+];
+
+/// Fails if version `version` of `kind` transactions is not accepted in blocks of Starknet version
+/// `protocol`.
+///
+/// Blocks which do not report their version predate all the gated transaction versions, and
+/// versions which can't be parsed are newer than the ones known here.
+pub fn check_transaction_version(
+    kind: &'static str,
+    version: u64,
+    protocol: Option<&str>,
+) -> Result<(), ConversionError> {
+    let support = TRANSACTION_VERSIONS
+        .iter()
+        .find(|support| support.kind == kind && support.version == version)
+        .ok_or(ConversionError::UnsupportedTransactionVersion { kind, version: version.into() })?;
+    let Some(protocol) = protocol else {
+        return Ok(());
+    };
+
+    let parsed = parse(protocol);
+    let older_than = |bound: &str| parsed.as_ref().is_some_and(|parsed| parsed < &parse(bound).expect("Invalid bound"));
+    if let Some(since) = support.since.filter(|since| older_than(since)) {
+        return Err(ConversionError::NotYetSupported {
+            kind,
+            version: version.into(),
+            protocol: protocol.into(),
+            since,
+        });
+    }
+    if let Some(until) = support.until.filter(|until| !older_than(until)) {
+        return Err(ConversionError::NoLongerSupported {
+            kind,
+            version: version.into(),
+            protocol: protocol.into(),
+            until,
+        });
+    }
+    Ok(())
+}
+
+/// Kind and version of a stored transaction, as listed in [`TRANSACTION_VERSIONS`].
+pub fn transaction_version(transaction: &Transaction) -> (&'static str, u64) {
+    match transaction {
+        Transaction::Declare(DeclareTransaction::V0(_)) => ("declare", 0),
+        Transaction::Declare(DeclareTransaction::V1(_)) => ("declare", 1),
+        Transaction::Declare(DeclareTransaction::V2(_)) => ("declare", 2),
+        Transaction::Declare(DeclareTransaction::V3(_)) => ("declare", 3),
+        Transaction::Deploy(_) => ("deploy", 0),
+        Transaction::DeployAccount(DeployAccountTransaction::V1(_)) => ("deploy account", 1),
+        Transaction::DeployAccount(DeployAccountTransaction::V3(_)) => ("deploy account", 3),
+        Transaction::Invoke(InvokeTransaction::V0(_)) => ("invoke", 0),
+        Transaction::Invoke(InvokeTransaction::V1(_)) => ("invoke", 1),
+        Transaction::Invoke(InvokeTransaction::V3(_)) => ("invoke", 3),
+        Transaction::L1Handler(_) => ("l1 handler", 0),
+    }
+}
+
+/// Kind and version of a transaction sent to the node, as listed in [`TRANSACTION_VERSIONS`].
+pub fn broadcasted_transaction_version(transaction: &BroadcastedTransaction) -> (&'static str, u64) {
+    match transaction {
+        BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V1(_)) => ("declare", 1),
+        BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V2(_)) => ("declare", 2),
+        BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V3(_)) => ("declare", 3),
+        BroadcastedTransaction::DeployAccount(BroadcastedDeployAccountTransaction::V1(_)) => ("deploy account", 1),
+        BroadcastedTransaction::DeployAccount(BroadcastedDeployAccountTransaction::V3(_)) => ("deploy account", 3),
+        BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V1(_)) => ("invoke", 1),
+        BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V3(_)) => ("invoke", 3),
+    }
+}
+
+/// Parses a Starknet version, whose missing trailing components are zero: "0.13" is "0.13.0".
+fn parse(version: &str) -> Option<[u64; 4]> {
+    let mut parsed = [0; 4];
+    let mut components = version.split('.');
+    for (component, value) in parsed.iter_mut().zip(components.by_ref()) {
+        *component = value.parse().ok()?;
+    }
+    components.next().is_none().then_some(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transaction_versions_are_checked_against_the_table() {
+        assert!(check_transaction_version("invoke", 3, None).is_ok());
+        assert!(check_transaction_version("invoke", 3, Some("0.13")).is_ok());
+        assert!(check_transaction_version("invoke", 3, Some("0.13.1.1")).is_ok());
+        assert!(check_transaction_version("invoke", 3, Some("next")).is_ok());
+        let error = check_transaction_version("invoke", 3, Some("0.12.3")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invoke transaction version 0x3 is only accepted from Starknet 0.13.0, not 0.12.3"
+        );
+
+        assert!(check_transaction_version("deploy", 0, Some("0.10.3")).is_ok());
+        let error = check_transaction_version("deploy", 0, Some("0.11.0")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "deploy transaction version 0x0 is no longer accepted since Starknet 0.11.0, not in 0.11.0"
+        );
+        assert!(check_transaction_version("deploy", 0, Some("next")).is_err());
+
+        let error = check_transaction_version("declare", 4, None).unwrap_err();
+        assert_eq!(error.to_string(), "declare transaction version 0x4 not supported");
+    }
+
+    #[test]
+    fn starknet_versions_are_parsed_with_trailing_zeros() {
+        assert_eq!(parse("0.13"), Some([0, 13, 0, 0]));
+        assert_eq!(parse("0.13.1.1"), Some([0, 13, 1, 1]));
+        assert_eq!(parse("0.13.1.1.1"), None);
+        assert_eq!(parse("next"), None);
+        assert!(parse("0.12.3") < parse("0.13"));
+    }
+}
@@ -20,6 +20,7 @@ mod error;
 mod tests;
 
 pub use error::FindLogError;
+use mp_block::legacy::LegacyDeoxysBlock;
 use mp_block::DeoxysBlock;
 use parity_scale_codec::{Decode, Encode};
 use sp_runtime::generic::{Digest, OpaqueDigestItemId};
@@ -35,15 +36,22 @@ pub const CLASS_ENGINE_ID: ConsensusEngineId = [b'c', b'l', b'a', b'z'];
 ///
 /// Right now we only expect Deoxys to log the Starknet block,
 /// but other usecases may appears later on.
+///
+/// The variant is bumped whenever the encoding of the block changes, so that the blocks logged by
+/// previous versions of the node can still be read.
 #[derive(Debug, Clone, Encode, Decode)]
 pub enum Log {
+    /// A block logged before the headers held the commitments of Starknet 0.13.2.
     #[codec(index = 0)]
+    LegacyBlock(LegacyDeoxysBlock),
+    #[codec(index = 1)]
     Block(DeoxysBlock),
 }
 
 /// Return the wrapped [DeoxysBlock] contained in a given [Digest]
 pub fn find_starknet_block(digest: &Digest) -> Result<DeoxysBlock, FindLogError> {
     find_log(digest).map(|log| match log {
+        Log::LegacyBlock(b) => b.into(),
         Log::Block(b) => b,
     })
 }
@@ -1,4 +1,5 @@
 use assert_matches::assert_matches;
+use mp_block::legacy::LegacyHeader;
 use sp_runtime::{Digest, DigestItem};
 
 use super::*;
@@ -46,3 +47,16 @@ fn other_consensus_engine_id() {
     assert_matches!(find_log(&digest), Err(FindLogError::NotLog));
     assert_matches!(find_starknet_block(&digest), Err(FindLogError::NotLog));
 }
+
+#[test]
+fn legacy_blocks_are_found() {
+    let mut digest = Digest::default();
+    let header = LegacyHeader { block_number: 3, event_count: 2, ..Default::default() };
+    let block = LegacyDeoxysBlock { header, ..Default::default() };
+
+    digest.push(DigestItem::Consensus(MADARA_ENGINE_ID, Log::LegacyBlock(block).encode()));
+
+    let block = find_starknet_block(&digest).unwrap();
+    assert_eq!((block.header().block_number, block.header().event_count), (3, 2));
+    assert_eq!(block.header().receipt_commitment, None);
+}
@@ -1,8 +1,60 @@
-use serde::Serializer;
-use serde_with::SerializeAs;
+use serde::{Deserializer, Serializer};
+use serde_with::{DeserializeAs, SerializeAs};
+use starknet_ff::FieldElement;
 
 use crate::Felt252Wrapper;
 
+/// Length of the longest hex encoded felt, with its `0x` prefix.
+pub const MAX_HEX_LEN: usize = 2 + 64;
+
+/// Encodes `value` in `buffer` as a `0x` prefixed hex string without leading zeros, as formatted
+/// by `{:#x}`, and returns it.
+pub fn encode_hex<'a>(value: &FieldElement, buffer: &'a mut [u8; MAX_HEX_LEN]) -> &'a str {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+    buffer[..2].copy_from_slice(b"0x");
+    let mut len = 2;
+    for byte in value.to_bytes_be() {
+        for nibble in [byte >> 4, byte & 0xf] {
+            // leading zeros
+            if len == 2 && nibble == 0 {
+                continue;
+            }
+            buffer[len] = DIGITS[nibble as usize];
+            len += 1;
+        }
+    }
+    if len == 2 {
+        buffer[2] = b'0';
+        len = 3;
+    }
+    core::str::from_utf8(&buffer[..len]).expect("Hex digits should be ASCII")
+}
+
+/// Serializes felts like [`starknet_core::serde::unsigned_field_element::UfeHex`], encoding them
+/// on the stack instead of formatting them into a `String` first.
+///
+/// Large responses hold thousands of felts, whose formatting dominated their serialization.
+pub struct FeltHex;
+
+impl SerializeAs<FieldElement> for FeltHex {
+    fn serialize_as<S>(value: &FieldElement, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(encode_hex(value, &mut [0; MAX_HEX_LEN]))
+    }
+}
+
+impl<'de> DeserializeAs<'de, FieldElement> for FeltHex {
+    fn deserialize_as<D>(deserializer: D) -> Result<FieldElement, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        starknet_core::serde::unsigned_field_element::UfeHex::deserialize_as(deserializer)
+    }
+}
+
 pub struct UfeHex;
 
 impl SerializeAs<Felt252Wrapper> for UfeHex {
@@ -10,6 +62,21 @@ impl SerializeAs<Felt252Wrapper> for UfeHex {
     where
         S: Serializer,
     {
-        starknet_core::serde::unsigned_field_element::UfeHex::serialize_as::<S>(&value.0, serializer)
+        FeltHex::serialize_as::<S>(&value.0, serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+
+    #[test]
+    fn felts_are_encoded_as_formatted() {
+        let mut buffer = [0; MAX_HEX_LEN];
+        for value in [FieldElement::ZERO, FieldElement::ONE, FieldElement::from(0xabc_u64), FieldElement::MAX] {
+            assert_eq!(encode_hex(&value, &mut buffer), format!("{value:#x}"));
+        }
     }
 }
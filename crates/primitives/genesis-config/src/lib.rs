@@ -9,7 +9,7 @@ use lazy_static::lazy_static;
 use mp_felt::Felt252Wrapper;
 use serde::de::Error;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use serde_with::serde_as;
+use serde_with::{serde_as, DisplayFromStr};
 use starknet_core::serde::unsigned_field_element::UfeHex;
 use starknet_crypto::FieldElement;
 
@@ -130,6 +130,53 @@ impl GenesisLoader {
     }
 }
 
+/// A class declared at the genesis of a custom chain.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct GenesisClass {
+    pub class_hash: ClassHash,
+    /// Hash of the compiled class of Sierra classes, `None` for legacy classes.
+    #[serde(default)]
+    pub compiled_class_hash: Option<ClassHash>,
+    /// The class, or the path of its JSON artifact relative to the `genesis.json`.
+    pub class: ContractClass,
+}
+
+/// An account deployed at the genesis of a custom chain.
+#[serde_as]
+#[derive(Deserialize, Serialize, Clone)]
+pub struct GenesisAccount {
+    #[serde(default)]
+    pub name: String,
+    pub private_key: HexFelt,
+    /// Balance of the account in each fee token, in their smallest unit, as a decimal string.
+    #[serde_as(as = "DisplayFromStr")]
+    pub balance: u128,
+}
+
+/// The genesis of a custom chain, as read from a `genesis.json`: the classes it declares, the
+/// funded accounts it deploys, and the class of the fee tokens deployed at the ETH and STRK
+/// addresses of the networks.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct CustomGenesis {
+    pub classes: Vec<GenesisClass>,
+    /// Class of the accounts, taking their public key as only constructor argument.
+    pub account_class_hash: ClassHash,
+    /// Class of the fee tokens.
+    pub erc20_class_hash: ClassHash,
+    #[serde(default)]
+    pub accounts: Vec<GenesisAccount>,
+}
+
+impl CustomGenesis {
+    pub fn eth_fee_token_address() -> ContractAddress {
+        *ETH_TOKEN_ADDR
+    }
+
+    pub fn strk_fee_token_address() -> ContractAddress {
+        *STRK_TOKEN_ADDR
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone)]
 #[serde(untagged)]
 pub enum ContractClass {
@@ -138,7 +185,7 @@ pub enum ContractClass {
 }
 
 /// A struct containing predeployed accounts info.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct PredeployedAccount {
     pub contract_address: ContractAddress,
     pub class_hash: ClassHash,
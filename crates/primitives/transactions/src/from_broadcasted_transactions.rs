@@ -94,21 +94,7 @@ fn declare_to_account_transaction(
             std::io::Read::read_to_end(&mut gz, &mut decompressed_bytes)
                 .map_err(|_| BroadcastedTransactionConversionError::ProgramDecompressionFailed)?;
 
-            let class_hash = {
-                let legacy_contract_class = LegacyContractClass {
-                    program: serde_json::from_slice(decompressed_bytes.as_slice())
-                        .map_err(|_| BroadcastedTransactionConversionError::ProgramDeserializationFailed)?,
-                    abi: match contract_class.abi.as_ref() {
-                        Some(abi) => Some(abi.iter().cloned().map(|entry| entry.into()).collect::<Vec<_>>()),
-                        None => vec![].into(),
-                    },
-                    entry_points_by_type: to_raw_legacy_entry_points(contract_class.entry_points_by_type.clone()),
-                };
-
-                legacy_contract_class
-                    .class_hash()
-                    .map_err(|_| BroadcastedTransactionConversionError::ClassHashComputationFailed)?
-            };
+            let class_hash = legacy_class_hash_of(&contract_class, &decompressed_bytes)?;
 
             let blockifier_contract_class = instantiate_blockifier_contract_class(&contract_class, decompressed_bytes)?;
 
@@ -465,6 +451,36 @@ fn cast_vec_of_field_elements(data: Vec<FieldElement>) -> Vec<Felt252Wrapper> {
     unsafe { alloc::vec::Vec::from_raw_parts(data.as_mut_ptr() as *mut Felt252Wrapper, data.len(), data.capacity()) }
 }
 
+/// Computes the hash of a legacy class, as declared on chain.
+pub fn legacy_class_hash(
+    contract_class: &CompressedLegacyContractClass,
+) -> Result<FieldElement, BroadcastedTransactionConversionError> {
+    let mut gz = GzDecoder::new(&contract_class.program[..]);
+    let mut decompressed_bytes = Vec::new();
+    std::io::Read::read_to_end(&mut gz, &mut decompressed_bytes)
+        .map_err(|_| BroadcastedTransactionConversionError::ProgramDecompressionFailed)?;
+
+    legacy_class_hash_of(contract_class, &decompressed_bytes)
+}
+
+/// Computes the hash of a legacy class whose program is already decompressed.
+fn legacy_class_hash_of(
+    contract_class: &CompressedLegacyContractClass,
+    program_decompressed_bytes: &[u8],
+) -> Result<FieldElement, BroadcastedTransactionConversionError> {
+    let legacy_contract_class = LegacyContractClass {
+        program: serde_json::from_slice(program_decompressed_bytes)
+            .map_err(|_| BroadcastedTransactionConversionError::ProgramDeserializationFailed)?,
+        abi: match contract_class.abi.as_ref() {
+            Some(abi) => Some(abi.iter().cloned().map(|entry| entry.into()).collect::<Vec<_>>()),
+            None => vec![].into(),
+        },
+        entry_points_by_type: to_raw_legacy_entry_points(contract_class.entry_points_by_type.clone()),
+    };
+
+    legacy_contract_class.class_hash().map_err(|_| BroadcastedTransactionConversionError::ClassHashComputationFailed)
+}
+
 fn instantiate_blockifier_contract_class(
     contract_class: &Arc<CompressedLegacyContractClass>,
     program_decompressed_bytes: Vec<u8>,
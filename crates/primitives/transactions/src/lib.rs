@@ -9,7 +9,6 @@ pub mod compute_hash;
 pub mod from_broadcasted_transactions;
 pub mod getters;
 #[cfg(feature = "client")]
-pub mod to_starknet_core_transaction;
 #[cfg(feature = "client")]
 pub mod utils;
 
@@ -2,7 +2,9 @@ mod app;
 mod deoxys_ui;
 mod logging;
 mod radar;
+mod top;
 mod ui;
 
 pub use deoxys_ui::*;
 pub use logging::modify_substrate_sources;
+pub use top::top;
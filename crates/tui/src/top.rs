@@ -0,0 +1,314 @@
+//! `deoxys top`: live view of the sync progress of a running node.
+//!
+//! Reads the JSON lines streamed by a node started with `--progress-events-addr`, and displays the
+//! progress towards the tip of the chain, the throughput and latency of each stage of the sync,
+//! and the depth of its queues.
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use crossterm::event::Event::Key;
+use crossterm::event::KeyCode::Char;
+use crossterm::event::{self};
+use ratatui::layout::{Alignment, Constraint, Direction, Layout, Margin, Rect};
+use ratatui::prelude::{CrosstermBackend, Frame, Terminal};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::widgets::{Block, Borders, Gauge, Paragraph, Row, Table};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+
+use crate::ui::render;
+use crate::ui::widgets::utils::render_zone;
+
+/// Number of latencies averaged per stage.
+const LATENCY_WINDOW: usize = 100;
+/// Period over which the block rates are computed.
+const RATE_WINDOW: Duration = Duration::from_secs(30);
+/// Capacity of the queue of fetched blocks of the nodes which don't report it.
+const DEFAULT_FETCHED_QUEUE_CAPACITY: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Stage {
+    Fetch,
+    Store,
+    Convert,
+    Verify,
+    Seal,
+}
+
+impl Stage {
+    const ALL: [Stage; 5] = [Stage::Fetch, Stage::Store, Stage::Convert, Stage::Verify, Stage::Seal];
+
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Fetch => "Fetch",
+            Stage::Store => "Store",
+            Stage::Convert => "Convert",
+            Stage::Verify => "Verify",
+            Stage::Seal => "Seal",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Queue {
+    Fetched,
+    Reordering,
+}
+
+/// Events sent by the node, other events are ignored.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum ProgressEvent {
+    Stage {
+        stage: Stage,
+        block_number: u64,
+        elapsed_ms: u64,
+    },
+    Queue {
+        queue: Queue,
+        depth: usize,
+        #[serde(default)]
+        capacity: Option<usize>,
+    },
+    Target {
+        block_number: u64,
+    },
+}
+
+#[derive(Default)]
+struct StageStats {
+    last_block: Option<u64>,
+    latencies_ms: VecDeque<u64>,
+    completions: VecDeque<Instant>,
+}
+
+impl StageStats {
+    fn record(&mut self, block_number: u64, elapsed_ms: u64, now: Instant) {
+        self.last_block = Some(self.last_block.map_or(block_number, |last| last.max(block_number)));
+        if self.latencies_ms.len() == LATENCY_WINDOW {
+            self.latencies_ms.pop_front();
+        }
+        self.latencies_ms.push_back(elapsed_ms);
+        self.completions.push_back(now);
+        self.expire(now);
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while self.completions.front().is_some_and(|time| now.duration_since(*time) > RATE_WINDOW) {
+            self.completions.pop_front();
+        }
+    }
+
+    fn average_latency_ms(&self) -> Option<f64> {
+        (!self.latencies_ms.is_empty())
+            .then(|| self.latencies_ms.iter().sum::<u64>() as f64 / self.latencies_ms.len() as f64)
+    }
+
+    /// Blocks per second over the last [`RATE_WINDOW`].
+    fn rate(&self) -> f64 {
+        self.completions.len() as f64 / RATE_WINDOW.as_secs_f64()
+    }
+}
+
+#[derive(Default)]
+struct SyncView {
+    stages: [StageStats; 5],
+    fetched_queue: usize,
+    fetched_queue_capacity: Option<usize>,
+    reordering_queue: usize,
+    target: Option<u64>,
+    disconnected: Option<String>,
+    should_quit: bool,
+}
+
+impl SyncView {
+    fn apply(&mut self, event: ProgressEvent, now: Instant) {
+        match event {
+            ProgressEvent::Stage { stage, block_number, elapsed_ms } => {
+                self.stage_mut(stage).record(block_number, elapsed_ms, now)
+            }
+            ProgressEvent::Queue { queue: Queue::Fetched, depth, capacity } => {
+                self.fetched_queue = depth;
+                self.fetched_queue_capacity = capacity.or(self.fetched_queue_capacity);
+            }
+            ProgressEvent::Queue { queue: Queue::Reordering, depth, .. } => self.reordering_queue = depth,
+            ProgressEvent::Target { block_number } => self.target = Some(block_number),
+        }
+    }
+
+    fn stage(&self, stage: Stage) -> &StageStats {
+        &self.stages[stage as usize]
+    }
+
+    fn stage_mut(&mut self, stage: Stage) -> &mut StageStats {
+        &mut self.stages[stage as usize]
+    }
+}
+
+/// Displays the sync progress streamed by the node at `address`, until `q` is pressed.
+pub async fn top(address: SocketAddr) -> Result<()> {
+    let stream = TcpStream::connect(address)
+        .await
+        .with_context(|| format!("Failed to connect to {address}, is the node started with --progress-events-addr?"))?;
+    let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+    tokio::spawn(read_events(stream, events_tx));
+
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+    let mut view = SyncView::default();
+
+    render::startup()?;
+    while !view.should_quit {
+        let now = Instant::now();
+        loop {
+            match events_rx.try_recv() {
+                Ok(Ok(event)) => view.apply(event, now),
+                Ok(Err(reason)) => view.disconnected = Some(reason),
+                Err(_) => break,
+            }
+        }
+        view.stages.iter_mut().for_each(|stage| stage.expire(now));
+
+        terminal.draw(|frame| ui(&view, address, frame))?;
+
+        if event::poll(Duration::from_millis(200))? {
+            if let Key(key) = event::read()? {
+                if key.kind == event::KeyEventKind::Press && key.code == Char('q') {
+                    view.should_quit = true;
+                }
+            }
+        }
+    }
+    render::shutdown()?;
+    Ok(())
+}
+
+/// Forwards the events read from the node, then the reason the stream ended.
+async fn read_events(stream: TcpStream, events: mpsc::UnboundedSender<Result<ProgressEvent, String>>) {
+    let mut lines = BufReader::new(stream).lines();
+    let reason = loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => {
+                // unknown events come from newer nodes, they are skipped
+                if let Ok(event) = serde_json::from_str(&line) {
+                    if events.send(Ok(event)).is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(None) => break "the node closed the connection".to_string(),
+            Err(e) => break format!("connection lost: {e}"),
+        }
+    };
+    let _ = events.send(Err(reason));
+}
+
+fn ui(view: &SyncView, address: SocketAddr, frame: &mut Frame) {
+    let outline = Block::new()
+        .borders(Borders::ALL)
+        .title(format!(" deoxys top - {address} (Press q to quit) "))
+        .title_style(Color::Magenta)
+        .title_alignment(Alignment::Center);
+    frame.render_widget(outline, frame.size());
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Length(5), Constraint::Min(9), Constraint::Length(6)])
+        .split(frame.size().inner(&Margin::new(2, 1)));
+
+    render_zone(frame, rows[0], "Progress");
+    render_progress(frame, view, rows[0].inner(&Margin::new(1, 1)));
+
+    render_zone(frame, rows[1], "Stages");
+    render_stages(frame, view, rows[1].inner(&Margin::new(1, 1)));
+
+    render_zone(frame, rows[2], "Queues");
+    render_queues(frame, view, rows[2].inner(&Margin::new(1, 1)));
+}
+
+fn render_progress(frame: &mut Frame, view: &SyncView, area: Rect) {
+    let lines = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Length(1), Constraint::Length(1), Constraint::Min(0)])
+        .split(area);
+
+    let synced = view.stage(Stage::Seal).last_block;
+    let ratio = match (synced, view.target) {
+        (Some(synced), Some(target)) if target > 0 => (synced as f64 / target as f64).min(1.),
+        _ => 0.,
+    };
+    let label = match (synced, view.target) {
+        (Some(synced), Some(target)) => format!("{synced} / {target}"),
+        (Some(synced), None) => format!("{synced} / ?"),
+        (None, _) => "waiting for blocks".to_string(),
+    };
+    frame.render_widget(Gauge::default().gauge_style(Color::Green).ratio(ratio).label(label), lines[0]);
+
+    let status = match &view.disconnected {
+        Some(reason) => format!("Disconnected: {reason}").red(),
+        None => {
+            let rate = view.stage(Stage::Seal).rate();
+            let eta = match (synced, view.target) {
+                (Some(synced), Some(target)) if rate > 0. && target > synced => {
+                    format_duration(Duration::from_secs_f64((target - synced) as f64 / rate))
+                }
+                (Some(synced), Some(target)) if target <= synced => "synced".to_string(),
+                _ => "-".to_string(),
+            };
+            format!("{rate:.2} blocks/s, ETA {eta}").light_green()
+        }
+    };
+    frame.render_widget(Paragraph::new(status), lines[1]);
+}
+
+fn render_stages(frame: &mut Frame, view: &SyncView, area: Rect) {
+    let header = Row::new(vec!["Stage", "Last block", "Avg latency", "Blocks/s"]).style(Style::new().bold());
+    let rows = Stage::ALL.into_iter().map(|stage| {
+        let stats = view.stage(stage);
+        Row::new(vec![
+            stage.name().to_string(),
+            stats.last_block.map_or("-".to_string(), |block| block.to_string()),
+            stats.average_latency_ms().map_or("-".to_string(), |latency| format!("{latency:.0} ms")),
+            format!("{:.2}", stats.rate()),
+        ])
+    });
+    let widths = [Constraint::Length(10), Constraint::Length(12), Constraint::Length(12), Constraint::Length(10)];
+    frame.render_widget(Table::new(rows, widths).header(header).column_spacing(2), area);
+}
+
+fn render_queues(frame: &mut Frame, view: &SyncView, area: Rect) {
+    let lines = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Length(1), Constraint::Length(1), Constraint::Length(1), Constraint::Min(0)])
+        .split(area);
+
+    let capacity = view.fetched_queue_capacity.unwrap_or(DEFAULT_FETCHED_QUEUE_CAPACITY).max(1);
+    let fetched = view.fetched_queue.min(capacity);
+    frame.render_widget(Paragraph::new("Fetched, waiting to be applied"), lines[0]);
+    frame.render_widget(
+        Gauge::default()
+            .gauge_style(Color::Cyan)
+            .ratio(fetched as f64 / capacity as f64)
+            .label(format!("{} / {capacity}", view.fetched_queue)),
+        lines[1],
+    );
+    frame.render_widget(
+        Paragraph::new(format!("Fetched ahead, waiting for the next block: {}", view.reordering_queue)),
+        lines[2],
+    );
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        3600..=86_399 => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {}h", secs / 86_400, secs % 86_400 / 3600),
+    }
+}
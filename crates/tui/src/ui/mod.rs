@@ -1,2 +1,2 @@
 pub mod render;
-mod widgets;
+pub(crate) mod widgets;